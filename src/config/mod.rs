@@ -2,9 +2,11 @@
 //!
 //! Handles configuration loading, validation, and transport selection.
 
+use crate::network::permissions::{PermissionedBind, PermissionedSubnet};
+use crate::network::subnet::Subnet;
 use crate::network::transport::TransportPreference;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
 // TOML support for configuration files
 
@@ -41,6 +43,10 @@ fn default_true() -> bool {
     true
 }
 
+fn default_min_peer_protocol_version() -> i32 {
+    crate::network::version_negotiation::DEFAULT_MIN_PEER_PROTOCOL_VERSION
+}
+
 fn default_modules_dir() -> String {
     "modules".to_string()
 }
@@ -73,6 +79,11 @@ pub struct NetworkTimingConfig {
     #[serde(default = "default_target_peer_count")]
     pub target_peer_count: usize,
 
+    /// Additional outbound connections that relay blocks only, never
+    /// transactions or addresses (Bitcoin Core uses 2)
+    #[serde(default = "default_block_relay_only_peer_count")]
+    pub block_relay_only_peer_count: usize,
+
     /// Wait time before connecting to peers from database (after persistent peers)
     #[serde(default = "default_peer_connection_delay")]
     pub peer_connection_delay_seconds: u64,
@@ -88,12 +99,50 @@ pub struct NetworkTimingConfig {
     /// Maximum addresses to fetch from DNS seeds
     #[serde(default = "default_max_addresses_from_dns")]
     pub max_addresses_from_dns: usize,
+
+    /// Timeout for a single DNS seed lookup
+    #[serde(default = "default_dns_seed_timeout")]
+    pub dns_seed_timeout_seconds: u64,
+
+    /// Interval between outbound connection maintenance rounds
+    #[serde(default = "default_outbound_maintenance_interval")]
+    pub outbound_maintenance_interval_seconds: u64,
+
+    /// Interval between feeler connections (tests addresses from the address database)
+    #[serde(default = "default_feeler_interval")]
+    pub feeler_interval_seconds: u64,
+
+    /// Interval between pings to each peer
+    #[serde(default = "default_ping_interval")]
+    pub ping_interval_seconds: u64,
+
+    /// Disconnect a peer that hasn't answered a ping within this time
+    #[serde(default = "default_ping_timeout")]
+    pub ping_timeout_seconds: u64,
+
+    /// Disconnect a peer that hasn't sent any message within this time
+    #[serde(default = "default_inactivity_timeout")]
+    pub inactivity_timeout_seconds: u64,
+
+    /// Disconnect a peer that hasn't completed the version handshake within
+    /// this time of connecting
+    #[serde(default = "default_handshake_timeout")]
+    pub handshake_timeout_seconds: u64,
+
+    /// Re-request a downloading block from another peer if the peer it was
+    /// requested from hasn't delivered it within this time
+    #[serde(default = "default_block_stall_timeout")]
+    pub block_stall_timeout_seconds: u64,
 }
 
 fn default_target_peer_count() -> usize {
     8
 }
 
+fn default_block_relay_only_peer_count() -> usize {
+    2
+}
+
 fn default_peer_connection_delay() -> u64 {
     2
 }
@@ -110,14 +159,55 @@ fn default_max_addresses_from_dns() -> usize {
     100
 }
 
+fn default_dns_seed_timeout() -> u64 {
+    5
+}
+
+fn default_outbound_maintenance_interval() -> u64 {
+    30
+}
+
+fn default_feeler_interval() -> u64 {
+    120 // 2 minutes, as in Bitcoin Core
+}
+
+fn default_ping_interval() -> u64 {
+    120 // 2 minutes, as in Bitcoin Core
+}
+
+fn default_ping_timeout() -> u64 {
+    1200 // 20 minutes, as in Bitcoin Core
+}
+
+fn default_inactivity_timeout() -> u64 {
+    1200 // 20 minutes, as in Bitcoin Core
+}
+
+fn default_handshake_timeout() -> u64 {
+    60 // `peertimeout` in Bitcoin Core
+}
+
+fn default_block_stall_timeout() -> u64 {
+    60
+}
+
 impl Default for NetworkTimingConfig {
     fn default() -> Self {
         Self {
             target_peer_count: 8,
+            block_relay_only_peer_count: 2,
             peer_connection_delay_seconds: 2,
             addr_relay_min_interval_seconds: 8640,
             max_addresses_per_addr_message: 1000,
             max_addresses_from_dns: 100,
+            dns_seed_timeout_seconds: 5,
+            outbound_maintenance_interval_seconds: 30,
+            feeler_interval_seconds: 120,
+            ping_interval_seconds: 120,
+            ping_timeout_seconds: 1200,
+            inactivity_timeout_seconds: 1200,
+            handshake_timeout_seconds: 60,
+            block_stall_timeout_seconds: 60,
         }
     }
 }
@@ -152,6 +242,10 @@ pub struct RequestTimeoutConfig {
     /// Timeout for RPC operations (seconds)
     #[serde(default = "default_rpc_timeout")]
     pub rpc_timeout_seconds: u64,
+
+    /// Timeout for long-running RPC methods such as verifychain (seconds)
+    #[serde(default = "default_rpc_long_running_timeout")]
+    pub rpc_long_running_timeout_seconds: u64,
 }
 
 fn default_async_request_timeout() -> u64 {
@@ -182,6 +276,10 @@ fn default_rpc_timeout() -> u64 {
     60 // 60 seconds
 }
 
+fn default_rpc_long_running_timeout() -> u64 {
+    3600 // 1 hour
+}
+
 impl Default for RequestTimeoutConfig {
     fn default() -> Self {
         Self {
@@ -192,6 +290,7 @@ impl Default for RequestTimeoutConfig {
             storage_timeout_seconds: 10,
             network_timeout_seconds: 30,
             rpc_timeout_seconds: 60,
+            rpc_long_running_timeout_seconds: 3600,
         }
     }
 }
@@ -285,15 +384,37 @@ pub struct NodeConfig {
     /// Network listening address
     pub listen_addr: Option<SocketAddr>,
 
+    /// Additional listening addresses (e.g. `[::]:8333` next to an IPv4
+    /// `listen_addr` for dual-stack)
+    #[serde(default)]
+    pub listen_addrs: Vec<SocketAddr>,
+
+    /// Trusted subnets and the permissions their inbound peers get, as
+    /// `flags@subnet` (e.g. `noban,mempool@127.0.0.1/32`). Flags are `noban`,
+    /// `mempool`, `download`, `relay`, `forcerelay` and `all`; a bare subnet
+    /// grants `noban,mempool,relay`. Protocol validation still applies.
+    #[serde(default)]
+    pub whitelist: Vec<PermissionedSubnet>,
+
+    /// Extra listening addresses whose inbound peers get permissions, as
+    /// `flags@addr` (e.g. `download@0.0.0.0:8334`); see `whitelist`
+    #[serde(default)]
+    pub whitebind: Vec<PermissionedBind>,
+
     /// Transport preference
     pub transport_preference: TransportPreferenceConfig,
 
     /// Maximum number of peers
     pub max_peers: Option<usize>,
 
-    /// Protocol version
+    /// Protocol version or network name (`BitcoinV1`/`mainnet`, `testnet3`,
+    /// `testnet4`, `signet`, `regtest`)
     pub protocol_version: Option<String>,
 
+    /// Signet challenge script (hex); unset uses the default signet
+    #[serde(default, alias = "signetchallenge")]
+    pub signet_challenge: Option<String>,
+
     /// Module system configuration
     pub modules: Option<ModuleConfig>,
 
@@ -304,26 +425,60 @@ pub struct NodeConfig {
     /// RPC authentication configuration
     pub rpc_auth: Option<RpcAuthConfig>,
 
+    /// TLS for the TCP RPC server (plaintext if unset)
+    pub rpc_tls: Option<RpcTlsConfig>,
+
     /// Ban list sharing configuration
     pub ban_list_sharing: Option<BanListSharingConfig>,
 
     /// Storage and pruning configuration
     pub storage: Option<StorageConfig>,
 
+    /// Block hash (display order) whose ancestors skip script verification
+    /// during sync; `"0"` verifies every block. Unset uses the network default.
+    #[serde(default)]
+    pub assume_valid: Option<String>,
+
+    /// Cumulative chain work (hex) the active chain must reach before the node
+    /// leaves initial block download; `"0"` disables. Unset uses the network default.
+    #[serde(default)]
+    pub minimum_chain_work: Option<String>,
+
     /// Persistent peers (peers to connect to on startup)
     #[serde(default)]
     pub persistent_peers: Vec<SocketAddr>,
 
+    /// DNS seeds for initial peer discovery (empty uses the built-in seeds for the network)
+    #[serde(default)]
+    pub dns_seeds: Vec<String>,
+
+    /// Minimum protocol version accepted from peers (older peers are disconnected)
+    #[serde(default = "default_min_peer_protocol_version")]
+    pub min_peer_protocol_version: i32,
+
     /// Enable self-advertisement (send own address to peers)
     #[serde(default = "default_true")]
     pub enable_self_advertisement: bool,
 
+    /// User agent sent in our version message (unset uses
+    /// `/reference-node:<version>/`)
+    #[serde(default)]
+    pub user_agent: Option<String>,
+
+    /// Serve BIP157 compact block filters to peers and advertise
+    /// NODE_COMPACT_FILTERS (requires `storage.blockfilterindex`)
+    #[serde(default)]
+    pub peer_block_filters: bool,
+
     /// DoS protection configuration
     pub dos_protection: Option<DosProtectionConfig>,
 
     /// Network relay configuration
     pub relay: Option<RelayConfig>,
 
+    /// Mempool configuration
+    pub mempool: Option<MempoolConfig>,
+
     /// Address database configuration
     pub address_database: Option<AddressDatabaseConfig>,
 
@@ -334,6 +489,9 @@ pub struct NodeConfig {
     /// Peer rate limiting configuration
     pub peer_rate_limiting: Option<PeerRateLimitingConfig>,
 
+    /// Per-peer and node-wide bandwidth limits
+    pub bandwidth: Option<BandwidthConfig>,
+
     /// Network timing and connection behavior
     pub network_timing: Option<NetworkTimingConfig>,
 
@@ -348,6 +506,26 @@ pub struct NodeConfig {
 
     /// Logging configuration
     pub logging: Option<LoggingConfig>,
+
+    /// Prometheus metrics endpoint address (endpoint disabled if unset)
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Block and transaction notification feeds (disabled if unset)
+    pub notifications: Option<NotificationsConfig>,
+
+    /// RPC server configuration
+    pub rpc: Option<RpcConfig>,
+
+    /// SOCKS5 proxy for outbound connections (e.g. Tor)
+    pub proxy: Option<ProxyConfig>,
+
+    /// Public IPs this node is reachable at (advertised to peers)
+    #[serde(default)]
+    pub external_ips: Vec<IpAddr>,
+
+    /// Map the listening port on the gateway with UPnP
+    #[serde(default)]
+    pub upnp: bool,
 }
 
 /// Transport preference configuration (serializable)
@@ -399,28 +577,47 @@ impl Default for NodeConfig {
     fn default() -> Self {
         Self {
             listen_addr: Some("127.0.0.1:8333".parse().unwrap()),
+            listen_addrs: Vec::new(),
+            whitelist: Vec::new(),
+            whitebind: Vec::new(),
             transport_preference: TransportPreferenceConfig::TcpOnly,
             max_peers: Some(100),
             protocol_version: Some("BitcoinV1".to_string()),
+            signet_challenge: None,
             modules: Some(ModuleConfig::default()),
             #[cfg(feature = "stratum-v2")]
             stratum_v2: None,
             rpc_auth: None,
+            rpc_tls: None,
             ban_list_sharing: None,
             storage: None,
+            assume_valid: None,
+            minimum_chain_work: None,
             persistent_peers: Vec::new(),
+            dns_seeds: Vec::new(),
+            min_peer_protocol_version: default_min_peer_protocol_version(),
             enable_self_advertisement: true,
+            user_agent: None,
+            peer_block_filters: false,
             dos_protection: None,
             relay: None,
+            mempool: None,
             address_database: None,
             #[cfg(feature = "dandelion")]
             dandelion: None,
             peer_rate_limiting: None,
+            bandwidth: None,
             network_timing: None,
             request_timeouts: None,
             module_resource_limits: None,
             fee_forwarding: None,
             logging: None,
+            metrics_addr: None,
+            notifications: None,
+            rpc: None,
+            proxy: None,
+            external_ips: Vec::new(),
+            upnp: false,
         }
     }
 }
@@ -480,6 +677,18 @@ pub struct BanListSharingConfig {
     /// Minimum ban duration to share (seconds, 0 = all)
     #[serde(default = "default_min_ban_duration")]
     pub min_ban_duration_to_share: u64,
+
+    /// Only merge ban lists signed by one of `trusted_publishers`
+    #[serde(default)]
+    pub require_signature: bool,
+
+    /// Hex-encoded secp256k1 public keys of trusted ban list publishers
+    #[serde(default)]
+    pub trusted_publishers: Vec<String>,
+
+    /// Hex-encoded secp256k1 secret key used to sign shared ban lists
+    #[serde(default)]
+    pub signing_key: Option<String>,
 }
 
 /// Ban share mode
@@ -513,6 +722,9 @@ impl Default for BanListSharingConfig {
             share_mode: BanShareMode::Periodic,
             periodic_interval_seconds: 300,
             min_ban_duration_to_share: 3600,
+            require_signature: false,
+            trusted_publishers: Vec::new(),
+            signing_key: None,
         }
     }
 }
@@ -529,9 +741,19 @@ pub struct RpcAuthConfig {
     pub tokens: Vec<String>,
 
     /// Valid certificate fingerprints (for certificate-based auth)
+    ///
+    /// Lowercase hex SHA-256 of the client certificate's DER encoding,
+    /// presented over an `rpc_tls` connection.
     #[serde(default)]
     pub certificates: Vec<String>,
 
+    /// Tokens restricted to a subset of RPC methods
+    ///
+    /// Tokens listed here are added in addition to `tokens`. A token listed in
+    /// `tokens` (or here with an empty method list) keeps full access.
+    #[serde(default)]
+    pub scoped_tokens: Vec<ScopedRpcToken>,
+
     /// Default rate limit (burst, requests per second)
     #[serde(default = "default_rate_limit_burst")]
     pub rate_limit_burst: u32,
@@ -540,6 +762,20 @@ pub struct RpcAuthConfig {
     pub rate_limit_rate: u32,
 }
 
+/// RPC token with an allowed-method list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScopedRpcToken {
+    /// Authentication token
+    pub token: String,
+
+    /// Methods this token may call
+    ///
+    /// Entries are exact method names or prefixes ending in `*`
+    /// (e.g. `getblock*`). Empty means all methods are allowed.
+    #[serde(default)]
+    pub allowed_methods: Vec<String>,
+}
+
 fn default_rate_limit_burst() -> u32 {
     100
 }
@@ -554,12 +790,151 @@ impl Default for RpcAuthConfig {
             required: false,
             tokens: Vec::new(),
             certificates: Vec::new(),
+            scoped_tokens: Vec::new(),
             rate_limit_burst: 100,
             rate_limit_rate: 10,
         }
     }
 }
 
+/// TLS configuration for the TCP RPC server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcTlsConfig {
+    /// PEM certificate chain presented to clients
+    pub cert_file: std::path::PathBuf,
+
+    /// PEM private key for `cert_file`
+    pub key_file: std::path::PathBuf,
+
+    /// Refuse clients that do not present a certificate
+    ///
+    /// Presented certificates are only accepted for authentication if their
+    /// fingerprint is listed in `rpc_auth.certificates`.
+    #[serde(default)]
+    pub require_client_cert: bool,
+}
+
+/// SOCKS5 proxy configuration for outbound connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy address (e.g. 127.0.0.1:9050 for Tor)
+    pub addr: SocketAddr,
+
+    /// Proxy username (optional)
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Proxy password (optional)
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// Use unique SOCKS credentials per connection so Tor isolates each
+    /// peer on its own circuit
+    #[serde(default = "default_true")]
+    pub stream_isolation: bool,
+}
+
+/// RPC server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcConfig {
+    /// Circuit breaker guarding storage-heavy RPC methods
+    #[serde(default)]
+    pub circuit_breaker: RpcCircuitBreakerConfig,
+
+    /// Requests executed concurrently
+    #[serde(default = "default_rpc_threads", alias = "rpcthreads")]
+    pub threads: usize,
+
+    /// Requests allowed to wait for a free worker; beyond this, requests are
+    /// refused with HTTP 503 "Work queue depth exceeded"
+    #[serde(default = "default_rpc_work_queue", alias = "rpcworkqueue")]
+    pub work_queue: usize,
+
+    /// Workers reserved for long-running methods (verifychain, scantxoutset,
+    /// scanblocks, reindexchainstate) so they cannot starve other calls;
+    /// 0 runs them in the main pool
+    #[serde(default = "default_rpc_long_running_threads")]
+    pub long_running_threads: usize,
+
+    /// Subnets (e.g. `10.0.0.0/8`) allowed to connect to the RPC server
+    ///
+    /// Loopback is always allowed and is the only source allowed by default.
+    /// Connections from other addresses are closed before authentication.
+    #[serde(default, alias = "rpcallowip")]
+    pub allow_ip: Vec<Subnet>,
+}
+
+fn default_rpc_threads() -> usize {
+    crate::rpc::work_queue::DEFAULT_RPC_THREADS
+}
+
+fn default_rpc_work_queue() -> usize {
+    crate::rpc::work_queue::DEFAULT_RPC_WORK_QUEUE
+}
+
+fn default_rpc_long_running_threads() -> usize {
+    crate::rpc::work_queue::DEFAULT_LONG_RUNNING_RPC_THREADS
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            circuit_breaker: RpcCircuitBreakerConfig::default(),
+            threads: default_rpc_threads(),
+            work_queue: default_rpc_work_queue(),
+            long_running_threads: default_rpc_long_running_threads(),
+            allow_ip: Vec::new(),
+        }
+    }
+}
+
+/// Circuit breaker configuration for storage-heavy RPC methods
+///
+/// Applies to methods that scan the UTXO set or the block database
+/// (gettxoutsetinfo, verifychain, scanblocks). Once the breaker opens, these methods fail
+/// fast with a "node overloaded" error instead of queueing on the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcCircuitBreakerConfig {
+    /// Enable the circuit breaker
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Consecutive storage failures before the breaker opens
+    #[serde(default = "default_cb_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Successful calls in half-open state before the breaker closes
+    #[serde(default = "default_cb_success_threshold")]
+    pub success_threshold: u32,
+
+    /// Seconds the breaker stays open before allowing a probe request
+    #[serde(default = "default_cb_open_timeout")]
+    pub open_timeout_seconds: u64,
+}
+
+fn default_cb_failure_threshold() -> u32 {
+    5
+}
+
+fn default_cb_success_threshold() -> u32 {
+    1
+}
+
+fn default_cb_open_timeout() -> u64 {
+    30
+}
+
+impl Default for RpcCircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_threshold: default_cb_failure_threshold(),
+            success_threshold: default_cb_success_threshold(),
+            open_timeout_seconds: default_cb_open_timeout(),
+        }
+    }
+}
+
 impl NodeConfig {
     /// Load configuration from file (supports JSON and TOML)
     pub fn from_file(path: &std::path::Path) -> anyhow::Result<Self> {
@@ -909,6 +1284,28 @@ pub struct StorageConfig {
 
     /// Cache sizes
     pub cache: Option<StorageCacheConfig>,
+
+    /// Maintain UTXO set statistics for a fast `gettxoutsetinfo`
+    ///
+    /// The index is built as blocks connect; enabling it on an existing node
+    /// requires `reindexchainstate`.
+    #[serde(default)]
+    pub coinstatsindex: bool,
+
+    /// Maintain a history of transactions per output script
+    ///
+    /// Like `coinstatsindex`, built as blocks connect. An index that fell
+    /// behind or was damaged over some blocks is repaired with
+    /// `rebuildutxoindex`.
+    #[serde(default)]
+    pub addressindex: bool,
+
+    /// Build BIP158 filters for blocks connected before the filter index existed
+    ///
+    /// Runs in the background from the end of the filter header chain and
+    /// fails if the remaining range has been pruned.
+    #[serde(default)]
+    pub blockfilterindex: bool,
 }
 
 /// Database backend configuration
@@ -976,6 +1373,9 @@ impl Default for StorageConfig {
             data_dir: "data".to_string(),
             pruning: None,
             cache: None,
+            coinstatsindex: false,
+            addressindex: false,
+            blockfilterindex: false,
         }
     }
 }
@@ -1082,6 +1482,10 @@ pub struct DosProtectionConfig {
     /// Default ban duration in seconds
     #[serde(default = "default_dos_ban_duration")]
     pub ban_duration_seconds: u64,
+
+    /// Maximum inbound peers from one network group (see `network::netgroup`)
+    #[serde(default = "default_dos_max_inbound_per_netgroup")]
+    pub max_inbound_per_netgroup: usize,
 }
 
 fn default_dos_max_connections_per_window() -> usize {
@@ -1108,6 +1512,10 @@ fn default_dos_ban_duration() -> u64 {
     3600 // 1 hour
 }
 
+fn default_dos_max_inbound_per_netgroup() -> usize {
+    4
+}
+
 impl Default for DosProtectionConfig {
     fn default() -> Self {
         Self {
@@ -1117,6 +1525,7 @@ impl Default for DosProtectionConfig {
             max_active_connections: 200,
             auto_ban_threshold: 3,
             ban_duration_seconds: 3600,
+            max_inbound_per_netgroup: 4,
         }
     }
 }
@@ -1143,6 +1552,40 @@ pub struct RelayConfig {
     /// Enable Dandelion++ privacy relay
     #[serde(default = "default_false")]
     pub enable_dandelion: bool,
+
+    /// Minimum fee rate for relay and mempool acceptance (satoshis per 1000 vbytes)
+    #[serde(default = "default_min_relay_tx_fee")]
+    pub min_relay_tx_fee: u64,
+
+    /// Fee rate a replacement must add over the transactions it replaces
+    /// (satoshis per 1000 vbytes)
+    #[serde(default = "default_incremental_relay_fee")]
+    pub incremental_relay_fee: u64,
+
+    /// Fee rate used to decide whether an output is dust: outputs worth less
+    /// than the cost of spending them at this rate are not relayed
+    /// (satoshis per 1000 vbytes, 0 disables the check)
+    #[serde(default = "default_dust_relay_fee")]
+    pub dust_relay_fee: u64,
+
+    /// Relay transactions with an OP_RETURN data carrier output
+    ///
+    /// Standardness policy only: blocks with such outputs are still valid.
+    #[serde(default = "default_true")]
+    pub datacarrier: bool,
+
+    /// Maximum size of a relayed OP_RETURN output script in bytes, including
+    /// the OP_RETURN and push opcodes
+    #[serde(default = "default_datacarrier_size", alias = "datacarriersize")]
+    pub datacarrier_size: usize,
+
+    /// Answer `mempool` requests from peers (whitelisted peers are always answered)
+    #[serde(default = "default_false")]
+    pub accept_mempool_requests: bool,
+
+    /// Send `mempool` to outbound peers after the handshake to fill our mempool
+    #[serde(default = "default_false")]
+    pub request_mempool_on_connect: bool,
 }
 
 fn default_relay_max_age() -> u64 {
@@ -1153,6 +1596,22 @@ fn default_relay_max_tracked_items() -> usize {
     10000
 }
 
+fn default_min_relay_tx_fee() -> u64 {
+    1000 // 1 sat/vB
+}
+
+fn default_incremental_relay_fee() -> u64 {
+    1000 // 1 sat/vB
+}
+
+fn default_dust_relay_fee() -> u64 {
+    3000 // 3 sat/vB
+}
+
+fn default_datacarrier_size() -> usize {
+    crate::node::policy::MAX_OP_RETURN_RELAY
+}
+
 impl Default for RelayConfig {
     fn default() -> Self {
         Self {
@@ -1161,6 +1620,92 @@ impl Default for RelayConfig {
             enable_block_relay: true,
             enable_tx_relay: true,
             enable_dandelion: false,
+            min_relay_tx_fee: 1000,
+            incremental_relay_fee: 1000,
+            dust_relay_fee: 3000,
+            datacarrier: true,
+            datacarrier_size: default_datacarrier_size(),
+            accept_mempool_requests: false,
+            request_mempool_on_connect: false,
+        }
+    }
+}
+
+/// Mempool configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MempoolConfig {
+    /// Evict transactions that have been in the mempool longer than this (hours)
+    #[serde(default = "default_mempool_expiry_hours")]
+    pub mempool_expiry_hours: u64,
+
+    /// How often to sweep for expired transactions (seconds)
+    #[serde(default = "default_mempool_expiry_sweep_interval")]
+    pub expiry_sweep_interval_seconds: u64,
+
+    /// Accept non-standard transactions (`acceptnonstdtxn`)
+    ///
+    /// Defaults to the network: enforced on mainnet, relaxed on test networks.
+    #[serde(default, alias = "acceptnonstdtxn")]
+    pub accept_non_std_txn: Option<bool>,
+}
+
+fn default_mempool_expiry_hours() -> u64 {
+    336 // 2 weeks
+}
+
+fn default_mempool_expiry_sweep_interval() -> u64 {
+    600 // 10 minutes
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            mempool_expiry_hours: 336,
+            expiry_sweep_interval_seconds: 600,
+            accept_non_std_txn: None,
+        }
+    }
+}
+
+/// Block and transaction notification configuration
+///
+/// Each topic is published on its own TCP address; topics without an
+/// address are disabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// Address publishing the hash of each connected block
+    #[serde(default)]
+    pub hashblock: Option<SocketAddr>,
+
+    /// Address publishing the txid of each transaction accepted to the mempool
+    #[serde(default)]
+    pub hashtx: Option<SocketAddr>,
+
+    /// Address publishing each connected block
+    #[serde(default)]
+    pub rawblock: Option<SocketAddr>,
+
+    /// Address publishing each transaction accepted to the mempool
+    #[serde(default)]
+    pub rawtx: Option<SocketAddr>,
+
+    /// Messages queued per subscriber before the oldest is dropped
+    #[serde(default = "default_notification_queue_size")]
+    pub queue_size: usize,
+}
+
+fn default_notification_queue_size() -> usize {
+    crate::node::notifications::DEFAULT_NOTIFICATION_QUEUE_SIZE
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            hashblock: None,
+            hashtx: None,
+            rawblock: None,
+            rawtx: None,
+            queue_size: default_notification_queue_size(),
         }
     }
 }
@@ -1266,6 +1811,49 @@ impl Default for PeerRateLimitingConfig {
     }
 }
 
+/// Bandwidth throttling configuration
+///
+/// Rates are in bytes per second; 0 means unlimited. Transfers over the limit
+/// are delayed, not dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandwidthConfig {
+    /// Upload limit for each peer
+    #[serde(default)]
+    pub peer_upload_bytes_per_second: u64,
+
+    /// Download limit for each peer
+    #[serde(default)]
+    pub peer_download_bytes_per_second: u64,
+
+    /// Upload limit across all peers
+    #[serde(default)]
+    pub max_upload_bytes_per_second: u64,
+
+    /// Download limit across all peers
+    #[serde(default)]
+    pub max_download_bytes_per_second: u64,
+
+    /// Daily upload target in MiB (`maxuploadtarget`); 0 means none
+    ///
+    /// Once reached, historical blocks (a week older than the tip) are only
+    /// served to peers with the `download` permission and to block-relay-only
+    /// connections; other peers asking for them are disconnected.
+    #[serde(default, alias = "maxuploadtarget")]
+    pub max_upload_target_mib: u64,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            peer_upload_bytes_per_second: 0,
+            peer_download_bytes_per_second: 0,
+            max_upload_bytes_per_second: 0,
+            max_download_bytes_per_second: 0,
+            max_upload_target_mib: 0,
+        }
+    }
+}
+
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoggingConfig {
@@ -1275,8 +1863,15 @@ pub struct LoggingConfig {
     pub filter: Option<String>,
 
     /// Enable JSON logging format (for log aggregation systems)
+    ///
+    /// Events carry their enclosing spans, so logs can be filtered by
+    /// `peer` (`peer_addr`, `peer_id`) or `rpc_request` (`method`, `request_id`).
     #[serde(default)]
     pub json_format: bool,
+
+    /// Also write logs to a rotating file (stderr output is kept)
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
 }
 
 impl Default for LoggingConfig {
@@ -1284,6 +1879,45 @@ impl Default for LoggingConfig {
         Self {
             filter: None,
             json_format: false,
+            file: None,
         }
     }
 }
+
+/// Log file output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogFileConfig {
+    /// Path of the active log file; rotated files get a `.1`, `.2`, ... suffix
+    pub path: std::path::PathBuf,
+
+    /// When to start a new file
+    #[serde(default)]
+    pub rotation: LogRotation,
+
+    /// Number of rotated files to keep (older files are deleted)
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+/// Log file rotation policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Start a new file at midnight UTC
+    Daily,
+    /// Start a new file once the active one would exceed `max_bytes`
+    Size {
+        /// Maximum size of a log file in bytes
+        max_bytes: u64,
+    },
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self::Daily
+    }
+}
@@ -6,4 +6,4 @@
 pub mod webhook;
 
 #[cfg(feature = "governance")]
-pub use webhook::GovernanceWebhookClient;
+pub use webhook::{FeeForwardingEvent, GovernanceWebhookClient};
@@ -1,14 +1,54 @@
 //! Governance webhook client for bllvm-node
 //!
 //! Sends block notifications to bllvm-commons for fee forwarding tracking
+//!
+//! Fee forwarding events are queued and delivered in the background with
+//! retry and backoff, so an unreachable endpoint never stalls block
+//! processing. Events that still fail stay queued for the next delivery.
 
 use anyhow::Result;
 use bllvm_protocol::Block;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::{debug, info, warn};
 
+#[cfg(feature = "governance")]
+use crate::utils::retry::{retry_async_with_backoff, RetryConfig};
 #[cfg(feature = "governance")]
 use reqwest::Client;
+#[cfg(feature = "governance")]
+use std::collections::VecDeque;
+#[cfg(feature = "governance")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "governance")]
+use std::sync::{Arc, Mutex};
+
+/// Maximum fee forwarding events held while the endpoint is unreachable
+pub const MAX_PENDING_FEE_FORWARDING_EVENTS: usize = 1000;
+
+/// Block reward forwarded to the commons address by a block this node mined
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeForwardingEvent {
+    /// Block hash (hex)
+    pub block_hash: String,
+    /// Height of the block
+    pub block_height: u64,
+    /// Satoshis paid to the commons address
+    pub forwarded_amount: u64,
+    /// Commons address the amount was paid to
+    pub commons_address: String,
+    /// Contributor identifier from the fee forwarding configuration
+    pub contributor_id: Option<String>,
+}
+
+/// Queue of fee forwarding events awaiting delivery
+#[cfg(feature = "governance")]
+#[derive(Default)]
+struct PendingEvents {
+    events: Mutex<VecDeque<FeeForwardingEvent>>,
+    /// Set while a delivery task is draining the queue
+    delivering: AtomicBool,
+}
 
 /// Governance webhook client
 #[cfg(feature = "governance")]
@@ -17,6 +57,8 @@ pub struct GovernanceWebhookClient {
     webhook_url: String,
     node_id: Option<String>,
     enabled: bool,
+    retry: RetryConfig,
+    pending: Arc<PendingEvents>,
 }
 
 #[cfg(feature = "governance")]
@@ -42,9 +84,17 @@ impl GovernanceWebhookClient {
             webhook_url: url,
             node_id,
             enabled,
+            retry: RetryConfig::network(),
+            pending: Arc::new(PendingEvents::default()),
         }
     }
 
+    /// Set the retry policy used for each delivery attempt
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
     /// Create from environment variables
     pub fn from_env() -> Self {
         let webhook_url = std::env::var("GOVERNANCE_WEBHOOK_URL").ok();
@@ -108,6 +158,119 @@ impl GovernanceWebhookClient {
         Ok(())
     }
 
+    /// Report a fee forwarding event to bllvm-commons
+    ///
+    /// Queues the event and returns immediately; delivery happens on a
+    /// background task. When the queue is full the oldest event is dropped.
+    pub fn notify_fee_forwarding(&self, event: FeeForwardingEvent) {
+        if !self.enabled {
+            return;
+        }
+
+        if let Ok(mut events) = self.pending.events.lock() {
+            if events.len() >= MAX_PENDING_FEE_FORWARDING_EVENTS {
+                if let Some(dropped) = events.pop_front() {
+                    warn!(
+                        "Fee forwarding queue full, dropping event for block {}",
+                        dropped.block_hash
+                    );
+                }
+            }
+            events.push_back(event);
+        }
+
+        let client = self.client.clone();
+        let url = self.webhook_url.clone();
+        let retry = self.retry.clone();
+        let pending = Arc::clone(&self.pending);
+        tokio::spawn(async move {
+            Self::deliver_pending(&client, &url, &retry, &pending).await;
+        });
+    }
+
+    /// Deliver queued fee forwarding events now
+    ///
+    /// Returns the number of events still queued afterwards.
+    pub async fn flush_pending(&self) -> usize {
+        if self.enabled {
+            Self::deliver_pending(&self.client, &self.webhook_url, &self.retry, &self.pending)
+                .await;
+        }
+        self.pending_events()
+    }
+
+    /// Number of fee forwarding events awaiting delivery
+    pub fn pending_events(&self) -> usize {
+        self.pending
+            .events
+            .lock()
+            .map(|events| events.len())
+            .unwrap_or(0)
+    }
+
+    /// Send queued events in order until the queue is empty or one fails
+    async fn deliver_pending(
+        client: &Client,
+        url: &str,
+        retry: &RetryConfig,
+        pending: &PendingEvents,
+    ) {
+        // Only one task drains the queue at a time
+        while !pending.delivering.swap(true, Ordering::AcqRel) {
+            loop {
+                let next = pending
+                    .events
+                    .lock()
+                    .ok()
+                    .and_then(|events| events.front().cloned());
+                let Some(event) = next else { break };
+
+                let payload = &event;
+                let result = retry_async_with_backoff(retry, || async move {
+                    client
+                        .post(url)
+                        .json(payload)
+                        .send()
+                        .await?
+                        .error_for_status()
+                })
+                .await;
+                match result {
+                    Ok(_) => {
+                        debug!(
+                            "Governance webhook reported fee forwarding for block {} at height {}",
+                            event.block_hash, event.block_height
+                        );
+                        if let Ok(mut events) = pending.events.lock() {
+                            if events.front() == Some(&event) {
+                                events.pop_front();
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to report fee forwarding for block {}, keeping it queued: {}",
+                            event.block_hash, e
+                        );
+                        pending.delivering.store(false, Ordering::Release);
+                        return;
+                    }
+                }
+            }
+            pending.delivering.store(false, Ordering::Release);
+
+            // An event queued while we were finishing up is picked up here
+            let empty = pending
+                .events
+                .lock()
+                .map(|events| events.is_empty())
+                .unwrap_or(true);
+            if empty {
+                return;
+            }
+        }
+    }
+
     /// Calculate block hash (double SHA256 of block header)
     fn calculate_block_hash(&self, block: &Block) -> [u8; 32] {
         use sha2::{Digest, Sha256};
@@ -148,4 +311,6 @@ impl GovernanceWebhookClient {
     pub async fn notify_block(&self, _block: &Block, _height: u64) -> Result<()> {
         Ok(())
     }
+
+    pub fn notify_fee_forwarding(&self, _event: FeeForwardingEvent) {}
 }
@@ -54,9 +54,12 @@ pub use bllvm_protocol::{
 // Re-export protocol-engine types
 pub use bllvm_protocol::{BitcoinProtocolEngine, ProtocolVersion};
 
+use node::chain_params::{ChainParams, Network};
+
 /// Main reference node implementation
 pub struct ReferenceNode {
     protocol: BitcoinProtocolEngine,
+    chain_params: ChainParams,
     // TODO: Add other components as they're implemented
 }
 
@@ -65,8 +68,15 @@ impl ReferenceNode {
     /// Defaults to Regtest for safe development/testing
     pub fn new(version: Option<ProtocolVersion>) -> anyhow::Result<Self> {
         let version = version.unwrap_or(ProtocolVersion::Regtest);
+        Self::with_chain_params(ChainParams::new(Network::from_protocol_version(version)))
+    }
+
+    /// Create a reference node for a network the engine has no variant for
+    /// (testnet4, signet), running on the engine's matching rules
+    pub fn with_chain_params(chain_params: ChainParams) -> anyhow::Result<Self> {
         Ok(Self {
-            protocol: BitcoinProtocolEngine::new(version)?,
+            protocol: BitcoinProtocolEngine::new(chain_params.network.protocol_version())?,
+            chain_params,
         })
     }
 
@@ -74,6 +84,11 @@ impl ReferenceNode {
     pub fn protocol(&self) -> &BitcoinProtocolEngine {
         &self.protocol
     }
+
+    /// Get the network parameters
+    pub fn chain_params(&self) -> &ChainParams {
+        &self.chain_params
+    }
 }
 
 #[cfg(test)]
@@ -130,5 +145,16 @@ mod tests {
             mainnet_node.protocol().get_protocol_version(),
             ProtocolVersion::BitcoinV1
         );
+        assert_eq!(mainnet_node.chain_params().default_port, 8333);
+
+        // Signet runs on the test network rules with its own parameters
+        let signet_node =
+            ReferenceNode::with_chain_params(ChainParams::new(Network::Signet)).unwrap();
+        assert_eq!(
+            signet_node.protocol().get_protocol_version(),
+            ProtocolVersion::Testnet3
+        );
+        assert_eq!(signet_node.chain_params().default_port, 38333);
+        assert!(signet_node.chain_params().signet_challenge.is_some());
     }
 }
@@ -142,6 +142,7 @@ pub enum EventPayload {
     NewTransaction { tx_hash: Hash },
     BlockDisconnected { hash: Hash, height: u64 },
     ChainReorg { old_tip: Hash, new_tip: Hash },
+    MempoolEviction { tx_hash: Hash, reason: String },
 }
 
 /// Helper to create request messages
@@ -178,6 +178,8 @@ pub enum EventType {
     BlockDisconnected,
     /// Chain reorganization occurred
     ChainReorg,
+    /// Transaction evicted from mempool without being mined
+    MempoolEviction,
 }
 
 /// Module system errors
@@ -3,11 +3,26 @@
 //! Manages a database of known peer addresses with freshness tracking,
 //! expiration, and filtering capabilities.
 //!
+//! Addresses are kept in Bitcoin Core-style "new" and "tried" tables. An
+//! address we have only heard about goes into a new bucket chosen from the
+//! network groups of the address and of the peer that sent it; an address we
+//! have connected to moves into a tried bucket chosen from its own group.
+//! Bucket positions are derived from a secret key, so one source can only
+//! reach [`NEW_BUCKETS_PER_SOURCE_GROUP`] of the new buckets and cannot flood
+//! out the addresses learned from others. When two addresses map to the same
+//! slot the existing entry is kept unless it has expired. The tables are
+//! persisted to `peers.dat`.
+//!
 //! Supports both SocketAddr-based addresses (TCP/Quinn) and Iroh NodeIds.
 
+use crate::network::netgroup::network_group;
 use crate::network::protocol::NetworkAddress;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "iroh")]
@@ -26,6 +41,30 @@ fn current_timestamp() -> u64 {
         .as_secs()
 }
 
+/// Address database file name (stored in the data directory)
+pub const PEERS_FILE: &str = "peers.dat";
+
+/// Number of buckets in the new table
+pub const NEW_BUCKET_COUNT: usize = 1024;
+
+/// Number of buckets in the tried table
+pub const TRIED_BUCKET_COUNT: usize = 256;
+
+/// Addresses per bucket
+pub const BUCKET_SIZE: usize = 64;
+
+/// New buckets reachable from one source network group
+pub const NEW_BUCKETS_PER_SOURCE_GROUP: u64 = 64;
+
+/// Tried buckets reachable from one address network group
+pub const TRIED_BUCKETS_PER_GROUP: u64 = 8;
+
+/// `peers.dat` format version, stored in the file's first byte
+///
+/// Version 1 stored source groups in an older network-group format; versions
+/// before 3 did not record sources or connection attempts.
+const PEERS_FILE_VERSION: u8 = 3;
+
 /// Address entry with metadata
 #[derive(Debug, Clone)]
 pub struct AddressEntry {
@@ -39,6 +78,17 @@ pub struct AddressEntry {
     pub services: u64,
     /// Number of times we've seen this address
     pub seen_count: u32,
+    /// Network group of the peer that told us about this address
+    pub source_group: Vec<u8>,
+    /// Whether the address is in the tried table (we have connected to it)
+    pub tried: bool,
+    /// Peer that told us about this address (None if loaded from an older
+    /// `peers.dat`)
+    pub source: Option<IpAddr>,
+    /// Unix timestamp of our last connection attempt (0 if never tried)
+    pub last_try: u64,
+    /// Connection attempts since the last successful connection
+    pub attempts: u32,
 }
 
 impl AddressEntry {
@@ -51,6 +101,11 @@ impl AddressEntry {
             last_seen: now,
             services,
             seen_count: 1,
+            source_group: Vec::new(),
+            tried: false,
+            source: None,
+            last_try: 0,
+            attempts: 0,
         }
     }
 
@@ -67,10 +122,92 @@ impl AddressEntry {
     }
 }
 
+/// Address as stored in `peers.dat`
+#[derive(Serialize, Deserialize)]
+struct PersistedAddress {
+    addr: NetworkAddress,
+    first_seen: u64,
+    last_seen: u64,
+    services: u64,
+    seen_count: u32,
+    source_group: Vec<u8>,
+    tried: bool,
+    source: Option<IpAddr>,
+    last_try: u64,
+    attempts: u32,
+}
+
+/// Contents of `peers.dat` after the version byte
+#[derive(Serialize, Deserialize)]
+struct PeersFile {
+    key: [u8; 32],
+    addresses: Vec<PersistedAddress>,
+}
+
+/// Address as stored in `peers.dat` versions 1 and 2
+#[derive(Serialize, Deserialize)]
+struct LegacyPersistedAddress {
+    addr: NetworkAddress,
+    first_seen: u64,
+    last_seen: u64,
+    services: u64,
+    seen_count: u32,
+    source_group: Vec<u8>,
+    tried: bool,
+}
+
+/// Contents of `peers.dat` versions 1 and 2 after the version byte
+#[derive(Serialize, Deserialize)]
+struct LegacyPeersFile {
+    key: [u8; 32],
+    addresses: Vec<LegacyPersistedAddress>,
+}
+
+impl From<LegacyPeersFile> for PeersFile {
+    fn from(legacy: LegacyPeersFile) -> Self {
+        Self {
+            key: legacy.key,
+            addresses: legacy
+                .addresses
+                .into_iter()
+                .map(|saved| PersistedAddress {
+                    addr: saved.addr,
+                    first_seen: saved.first_seen,
+                    last_seen: saved.last_seen,
+                    services: saved.services,
+                    seen_count: saved.seen_count,
+                    source_group: saved.source_group,
+                    tried: saved.tried,
+                    source: None,
+                    last_try: 0,
+                    attempts: 0,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An occupied address manager table slot
+#[derive(Debug, Clone)]
+pub struct TableSlot {
+    /// Bucket index within the table
+    pub bucket: usize,
+    /// Position within the bucket
+    pub position: usize,
+    /// Address stored in the slot
+    pub entry: AddressEntry,
+}
+
 /// Address database for peer discovery
 pub struct AddressDatabase {
     /// Map from SocketAddr to AddressEntry (for TCP/Quinn)
     addresses: HashMap<SocketAddr, AddressEntry>,
+    /// Occupied new-table slots, keyed by (bucket, position)
+    new_table: HashMap<(usize, usize), SocketAddr>,
+    /// Occupied tried-table slots, keyed by (bucket, position)
+    tried_table: HashMap<(usize, usize), SocketAddr>,
+    /// Secret key that randomizes bucket placement
+    key: [u8; 32],
     /// Map from Iroh PublicKey to AddressEntry (for Iroh peers)
     #[cfg(feature = "iroh")]
     iroh_addresses: HashMap<PublicKey, AddressEntry>,
@@ -83,19 +220,16 @@ pub struct AddressDatabase {
 impl AddressDatabase {
     /// Create a new address database
     pub fn new(max_addresses: usize) -> Self {
-        Self {
-            addresses: HashMap::new(),
-            #[cfg(feature = "iroh")]
-            iroh_addresses: HashMap::new(),
-            max_addresses,
-            expiration_seconds: 24 * 60 * 60, // 24 hours default
-        }
+        Self::with_expiration(max_addresses, 24 * 60 * 60) // 24 hours default
     }
 
     /// Create with custom expiration
     pub fn with_expiration(max_addresses: usize, expiration_seconds: u64) -> Self {
         Self {
             addresses: HashMap::new(),
+            new_table: HashMap::new(),
+            tried_table: HashMap::new(),
+            key: rand::random(),
             #[cfg(feature = "iroh")]
             iroh_addresses: HashMap::new(),
             max_addresses,
@@ -103,27 +237,369 @@ impl AddressDatabase {
         }
     }
 
-    /// Add or update an address
+    /// Add or update an address the address itself vouches for
+    ///
+    /// Used for addresses without a relaying peer (DNS seeds, our own
+    /// address); see [`Self::add_address_from`].
     pub fn add_address(&mut self, addr: NetworkAddress, services: u64) {
+        let source = self.network_addr_to_socket(&addr).ip();
+        self.add_address_from(addr, services, source);
+    }
+
+    /// Add or update an address learned from the peer at `source`
+    ///
+    /// A new address is placed in the new-table slot given by its own and
+    /// the source's network group. If that slot holds another address that
+    /// is still fresh, the new address is dropped.
+    pub fn add_address_from(&mut self, addr: NetworkAddress, services: u64, source: IpAddr) {
         // Convert NetworkAddress to SocketAddr for key
         let socket_addr = self.network_addr_to_socket(&addr);
 
-        match self.addresses.get_mut(&socket_addr) {
-            Some(entry) => {
-                // Update existing entry
-                entry.update_seen();
-                entry.services |= services; // Merge service flags
+        if let Some(entry) = self.addresses.get_mut(&socket_addr) {
+            // Update existing entry
+            entry.update_seen();
+            entry.services |= services; // Merge service flags
+            return;
+        }
+
+        let source_group = network_group(source);
+        let slot = self.new_slot(&socket_addr, &source_group);
+        if let Some(occupant) = self.new_table.get(&slot).copied() {
+            let occupant_fresh = self
+                .addresses
+                .get(&occupant)
+                .is_some_and(|entry| entry.is_fresh(self.expiration_seconds));
+            if occupant_fresh {
+                return;
             }
-            None => {
-                // Add new entry (evict old if needed)
-                // Use total_count() to respect max_addresses across both maps
-                if self.total_count() >= self.max_addresses {
-                    self.evict_oldest_unified();
-                }
-                self.addresses
-                    .insert(socket_addr, AddressEntry::new(addr, services));
+            self.remove_socket(&occupant);
+        }
+
+        // Use total_count() to respect max_addresses across both maps
+        if self.total_count() >= self.max_addresses {
+            self.evict_oldest_unified();
+        }
+        let mut entry = AddressEntry::new(addr, services);
+        entry.source_group = source_group;
+        entry.source = Some(source);
+        self.addresses.insert(socket_addr, entry);
+        self.new_table.insert(slot, socket_addr);
+    }
+
+    /// Move an address we connected to into the tried table
+    ///
+    /// An address already occupying its tried slot is moved back to the new
+    /// table (or dropped if its new slot is taken). Returns false for unknown
+    /// addresses.
+    pub fn mark_good(&mut self, socket_addr: &SocketAddr) -> bool {
+        let Some(entry) = self.addresses.get(socket_addr) else {
+            return false;
+        };
+        if entry.tried {
+            let entry = self.addresses.get_mut(socket_addr).unwrap();
+            entry.attempts = 0;
+            entry.update_seen();
+            return true;
+        }
+        let new_slot = self.new_slot(socket_addr, &entry.source_group);
+        if self.new_table.get(&new_slot) == Some(socket_addr) {
+            self.new_table.remove(&new_slot);
+        }
+
+        let tried_slot = self.tried_slot(socket_addr);
+        if let Some(displaced) = self.tried_table.remove(&tried_slot) {
+            self.demote_to_new(displaced);
+        }
+        self.tried_table.insert(tried_slot, *socket_addr);
+        let entry = self.addresses.get_mut(socket_addr).unwrap();
+        entry.tried = true;
+        entry.attempts = 0;
+        entry.update_seen();
+        true
+    }
+
+    /// Record an outbound connection attempt to a known address
+    ///
+    /// The attempt count is reset by [`Self::mark_good`].
+    pub fn mark_attempt(&mut self, socket_addr: &SocketAddr) {
+        if let Some(entry) = self.addresses.get_mut(socket_addr) {
+            entry.last_try = current_timestamp();
+            entry.attempts = entry.attempts.saturating_add(1);
+        }
+    }
+
+    /// Put an address displaced from the tried table back into the new table
+    fn demote_to_new(&mut self, socket_addr: SocketAddr) {
+        let Some(entry) = self.addresses.get_mut(&socket_addr) else {
+            return;
+        };
+        entry.tried = false;
+        let source_group = entry.source_group.clone();
+        let slot = self.new_slot(&socket_addr, &source_group);
+        if self.new_table.contains_key(&slot) {
+            self.addresses.remove(&socket_addr);
+        } else {
+            self.new_table.insert(slot, socket_addr);
+        }
+    }
+
+    /// Remove an address and free its table slot
+    fn remove_socket(&mut self, socket_addr: &SocketAddr) {
+        let Some(entry) = self.addresses.remove(socket_addr) else {
+            return;
+        };
+        let (slot, table) = if entry.tried {
+            (self.tried_slot(socket_addr), &mut self.tried_table)
+        } else {
+            let slot = self.new_slot(socket_addr, &entry.source_group);
+            (slot, &mut self.new_table)
+        };
+        if table.get(&slot) == Some(socket_addr) {
+            table.remove(&slot);
+        }
+    }
+
+    /// Keyed hash of the given parts, truncated to 64 bits
+    fn keyed_hash(&self, parts: &[&[u8]]) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key);
+        for part in parts {
+            hasher.update((part.len() as u32).to_le_bytes());
+            hasher.update(part);
+        }
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+
+    /// New-table slot of an address learned from `source_group`
+    fn new_slot(&self, socket_addr: &SocketAddr, source_group: &[u8]) -> (usize, usize) {
+        let group = network_group(socket_addr.ip());
+        let spread = self.keyed_hash(&[&group, source_group]) % NEW_BUCKETS_PER_SOURCE_GROUP;
+        let bucket =
+            self.keyed_hash(&[source_group, &spread.to_le_bytes()]) % NEW_BUCKET_COUNT as u64;
+        (
+            bucket as usize,
+            self.bucket_position(b"N", bucket, socket_addr),
+        )
+    }
+
+    /// Tried-table slot of an address
+    fn tried_slot(&self, socket_addr: &SocketAddr) -> (usize, usize) {
+        let group = network_group(socket_addr.ip());
+        let spread = self.keyed_hash(&[&address_key(socket_addr)]) % TRIED_BUCKETS_PER_GROUP;
+        let bucket = self.keyed_hash(&[&group, &spread.to_le_bytes()]) % TRIED_BUCKET_COUNT as u64;
+        (
+            bucket as usize,
+            self.bucket_position(b"K", bucket, socket_addr),
+        )
+    }
+
+    fn bucket_position(&self, table: &[u8], bucket: u64, socket_addr: &SocketAddr) -> usize {
+        (self.keyed_hash(&[table, &bucket.to_le_bytes(), &address_key(socket_addr)])
+            % BUCKET_SIZE as u64) as usize
+    }
+
+    /// Number of addresses in the tried table
+    pub fn tried_count(&self) -> usize {
+        self.tried_table.len()
+    }
+
+    /// Number of addresses in the new table
+    pub fn new_count(&self) -> usize {
+        self.new_table.len()
+    }
+
+    /// Occupied new-table slots, ordered by bucket and position
+    pub fn new_table_slots(&self) -> Vec<TableSlot> {
+        self.table_slots(&self.new_table)
+    }
+
+    /// Occupied tried-table slots, ordered by bucket and position
+    pub fn tried_table_slots(&self) -> Vec<TableSlot> {
+        self.table_slots(&self.tried_table)
+    }
+
+    fn table_slots(&self, table: &HashMap<(usize, usize), SocketAddr>) -> Vec<TableSlot> {
+        let mut slots: Vec<TableSlot> = table
+            .iter()
+            .filter_map(|(&(bucket, position), socket_addr)| {
+                self.addresses.get(socket_addr).map(|entry| TableSlot {
+                    bucket,
+                    position,
+                    entry: entry.clone(),
+                })
+            })
+            .collect();
+        slots.sort_by_key(|slot| (slot.bucket, slot.position));
+        slots
+    }
+
+    /// Fresh entries in outbound connection order
+    ///
+    /// Tried addresses come first, then new ones; within each table the most
+    /// recently seen come first, ties broken by address so the order is
+    /// deterministic.
+    pub fn select_entries(&self, count: usize) -> Vec<AddressEntry> {
+        let mut fresh: Vec<(&SocketAddr, &AddressEntry)> = self
+            .addresses
+            .iter()
+            .filter(|(_, entry)| entry.is_fresh(self.expiration_seconds))
+            .collect();
+        fresh.sort_by(|(a_addr, a), (b_addr, b)| {
+            b.tried
+                .cmp(&a.tried)
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then(a_addr.cmp(b_addr))
+        });
+        fresh
+            .into_iter()
+            .take(count)
+            .map(|(_, entry)| entry.clone())
+            .collect()
+    }
+
+    /// Fresh addresses in outbound connection order (see [`Self::select_entries`])
+    pub fn select_addresses(&self, count: usize) -> Vec<NetworkAddress> {
+        self.select_entries(count)
+            .into_iter()
+            .map(|entry| entry.addr)
+            .collect()
+    }
+
+    /// Fresh addresses from the new table, most recently seen first
+    ///
+    /// Feeler connections test these so reachable ones can move to tried.
+    pub fn new_addresses(&self, count: usize) -> Vec<NetworkAddress> {
+        self.select_entries(usize::MAX)
+            .into_iter()
+            .filter(|entry| !entry.tried)
+            .take(count)
+            .map(|entry| entry.addr)
+            .collect()
+    }
+
+    /// Write the address tables to `path` (normally `<datadir>/peers.dat`)
+    ///
+    /// The file is written next to `path` and renamed over it, so a crash
+    /// mid-write leaves the previous file intact. Iroh addresses are not
+    /// persisted.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut addresses: Vec<(&SocketAddr, &AddressEntry)> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(addr, _)| **addr);
+        let file = PeersFile {
+            key: self.key,
+            addresses: addresses
+                .into_iter()
+                .map(|(_, entry)| PersistedAddress {
+                    addr: entry.addr.clone(),
+                    first_seen: entry.first_seen,
+                    last_seen: entry.last_seen,
+                    services: entry.services,
+                    seen_count: entry.seen_count,
+                    source_group: entry.source_group.clone(),
+                    tried: entry.tried,
+                    source: entry.source,
+                    last_try: entry.last_try,
+                    attempts: entry.attempts,
+                })
+                .collect(),
+        };
+        let mut data = vec![PEERS_FILE_VERSION];
+        data.extend(bincode::serialize(&file)?);
+        let tmp_path = path.with_extension("dat.new");
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Replace the address tables with those saved at `path`
+    ///
+    /// A missing file loads nothing. A file that is corrupt, truncated or from
+    /// an unknown format version is ignored with a warning and the tables are
+    /// left empty, to be refilled from DNS seeds and peers. Returns the number
+    /// of addresses loaded.
+    pub fn load(&mut self, path: &Path) -> Result<usize> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        self.addresses.clear();
+        self.new_table.clear();
+        self.tried_table.clear();
+
+        let Some((&version, body)) = data.split_first() else {
+            tracing::warn!("{} is empty, starting with no addresses", PEERS_FILE);
+            return Ok(0);
+        };
+        if version == 0 || version > PEERS_FILE_VERSION {
+            tracing::warn!(
+                "Unsupported {} version {}, starting with no addresses",
+                PEERS_FILE,
+                version
+            );
+            return Ok(0);
+        }
+        let decoded = if version < 3 {
+            bincode::deserialize::<LegacyPeersFile>(body).map(PeersFile::from)
+        } else {
+            bincode::deserialize::<PeersFile>(body)
+        };
+        let mut file = match decoded {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::warn!("Corrupt {} ({}), starting with no addresses", PEERS_FILE, e);
+                return Ok(0);
+            }
+        };
+        if version < 2 {
+            // Source groups from the old network-group format would land in
+            // unrelated buckets
+            for saved in &mut file.addresses {
+                saved.source_group.clear();
+            }
+        }
+
+        self.key = file.key;
+        // Tried addresses first, so they keep their slots
+        let (tried, new): (Vec<_>, Vec<_>) =
+            file.addresses.into_iter().partition(|saved| saved.tried);
+        for saved in tried.into_iter().chain(new) {
+            if self.addresses.len() >= self.max_addresses {
+                break;
+            }
+            let socket_addr = self.network_addr_to_socket(&saved.addr);
+            let entry = AddressEntry {
+                addr: saved.addr,
+                first_seen: saved.first_seen,
+                last_seen: saved.last_seen,
+                services: saved.services,
+                seen_count: saved.seen_count,
+                source_group: saved.source_group,
+                tried: false,
+                source: saved.source,
+                last_try: saved.last_try,
+                attempts: saved.attempts,
+            };
+            let tried_slot = self.tried_slot(&socket_addr);
+            if saved.tried && !self.tried_table.contains_key(&tried_slot) {
+                self.tried_table.insert(tried_slot, socket_addr);
+                self.addresses.insert(
+                    socket_addr,
+                    AddressEntry {
+                        tried: true,
+                        ..entry
+                    },
+                );
+                continue;
+            }
+            let new_slot = self.new_slot(&socket_addr, &entry.source_group);
+            if !self.new_table.contains_key(&new_slot) {
+                self.new_table.insert(new_slot, socket_addr);
+                self.addresses.insert(socket_addr, entry);
             }
         }
+        Ok(self.addresses.len())
     }
 
     /// Add multiple addresses
@@ -162,16 +638,22 @@ impl AddressDatabase {
 
     /// Remove expired addresses
     pub fn remove_expired(&mut self) -> usize {
-        let before = self.addresses.len();
-        self.addresses
-            .retain(|_, entry| entry.is_fresh(self.expiration_seconds));
-        before - self.addresses.len()
+        let expired: Vec<SocketAddr> = self
+            .addresses
+            .iter()
+            .filter(|(_, entry)| !entry.is_fresh(self.expiration_seconds))
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in &expired {
+            self.remove_socket(addr);
+        }
+        expired.len()
     }
 
     /// Remove an address
     pub fn remove_address(&mut self, addr: &NetworkAddress) {
         let socket_addr = self.network_addr_to_socket(addr);
-        self.addresses.remove(&socket_addr);
+        self.remove_socket(&socket_addr);
     }
 
     /// Check if address is banned
@@ -306,7 +788,8 @@ impl AddressDatabase {
     /// Evict oldest address across both maps (unified eviction)
     ///
     /// This ensures we respect max_addresses as a total limit across both
-    /// SocketAddr and Iroh address maps, not per-map limits.
+    /// SocketAddr and Iroh address maps, not per-map limits. New-table
+    /// addresses are evicted before tried ones, ties broken by address.
     fn evict_oldest_unified(&mut self) {
         // Find oldest across both maps
         let mut oldest_socket: Option<(SocketAddr, u64)> = None;
@@ -315,7 +798,7 @@ impl AddressDatabase {
         if let Some((addr, entry)) = self
             .addresses
             .iter()
-            .min_by_key(|(_, entry)| entry.last_seen)
+            .min_by_key(|(addr, entry)| (entry.tried, entry.last_seen, **addr))
         {
             oldest_socket = Some((*addr, entry.last_seen));
         }
@@ -337,14 +820,14 @@ impl AddressDatabase {
                 (Some((socket_addr, socket_time)), Some((iroh_id, iroh_time))) => {
                     // Both maps have entries - evict the oldest
                     if socket_time <= iroh_time {
-                        self.addresses.remove(&socket_addr);
+                        self.remove_socket(&socket_addr);
                     } else {
                         self.iroh_addresses.remove(&iroh_id);
                     }
                 }
                 (Some((socket_addr, _)), None) => {
                     // Only SocketAddr map has entries
-                    self.addresses.remove(&socket_addr);
+                    self.remove_socket(&socket_addr);
                 }
                 (None, Some((iroh_id, _))) => {
                     // Only Iroh map has entries
@@ -360,7 +843,7 @@ impl AddressDatabase {
         {
             // Only SocketAddr map exists
             if let Some((socket_addr, _)) = oldest_socket {
-                self.addresses.remove(&socket_addr);
+                self.remove_socket(&socket_addr);
             }
         }
     }
@@ -423,6 +906,17 @@ impl AddressDatabase {
     }
 }
 
+/// Bytes identifying an address in bucket hashes (IPv6 form and port)
+fn address_key(socket_addr: &SocketAddr) -> Vec<u8> {
+    let ip = match socket_addr.ip() {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    };
+    let mut key = ip.octets().to_vec();
+    key.extend_from_slice(&socket_addr.port().to_be_bytes());
+    key
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -628,6 +1122,196 @@ mod tests {
         assert_eq!(db.len(), 2); // Should still be 2
     }
 
+    #[test]
+    fn test_single_source_cannot_fill_new_table() {
+        let mut db = AddressDatabase::new(100_000);
+        let flooder: IpAddr = "1.2.3.4".parse().unwrap();
+        // Addresses from 8192 distinct /16 groups, all relayed by one peer
+        for i in 0..8192u32 {
+            let ip = format!("{}.{}.0.1", 11 + i / 256, i % 256);
+            db.add_address_from(create_test_address(&ip, 8333), 1, flooder);
+        }
+        let buckets: std::collections::HashSet<usize> =
+            db.new_table.keys().map(|(bucket, _)| *bucket).collect();
+        assert!(buckets.len() as u64 <= NEW_BUCKETS_PER_SOURCE_GROUP);
+        assert!(db.len() <= NEW_BUCKETS_PER_SOURCE_GROUP as usize * BUCKET_SIZE);
+
+        // Another source still gets its addresses in
+        let honest: IpAddr = "5.6.7.8".parse().unwrap();
+        let before = db.len();
+        for i in 0..100u32 {
+            let ip = format!("200.{}.0.1", i);
+            db.add_address_from(create_test_address(&ip, 8333), 1, honest);
+        }
+        assert!(db.len() - before >= 50);
+    }
+
+    #[test]
+    fn test_tried_addresses_selected_first() {
+        let mut db = AddressDatabase::new(100);
+        let tried = create_test_address("8.8.8.8", 8333);
+        let new = create_test_address("9.9.9.9", 8333);
+        db.add_address(tried.clone(), 1);
+        db.add_address(new.clone(), 1);
+        let tried_socket = SocketAddr::new("8.8.8.8".parse().unwrap(), 8333);
+        // The address we will connect to was seen less recently
+        db.addresses.get_mut(&tried_socket).unwrap().last_seen -= 100;
+
+        assert_eq!(db.select_addresses(2), vec![new.clone(), tried.clone()]);
+        assert!(db.mark_good(&tried_socket));
+        assert!(!db.mark_good(&SocketAddr::new("1.1.1.1".parse().unwrap(), 8333)));
+        assert_eq!(db.tried_count(), 1);
+        assert_eq!(db.new_count(), 1);
+        assert_eq!(db.select_addresses(2), vec![tried, new.clone()]);
+        assert_eq!(db.new_addresses(10), vec![new]);
+    }
+
+    #[test]
+    fn test_peers_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEERS_FILE);
+        let mut db = AddressDatabase::new(100);
+        assert_eq!(db.load(&path).unwrap(), 0);
+
+        db.add_address(create_test_address("8.8.8.8", 8333), 1);
+        db.add_address(create_test_address("9.9.9.9", 8333), 9);
+        db.mark_good(&SocketAddr::new("8.8.8.8".parse().unwrap(), 8333));
+        let new_socket = SocketAddr::new("9.9.9.9".parse().unwrap(), 8333);
+        db.mark_attempt(&new_socket);
+        db.save(&path).unwrap();
+
+        let mut loaded = AddressDatabase::new(100);
+        assert_eq!(loaded.load(&path).unwrap(), 2);
+        assert_eq!(loaded.tried_count(), 1);
+        assert_eq!(loaded.new_count(), 1);
+        assert_eq!(loaded.select_addresses(2), db.select_addresses(2));
+        let new_entry = &loaded.addresses[&new_socket];
+        assert_eq!(new_entry.source, Some(new_socket.ip()));
+        assert_eq!(new_entry.attempts, 1);
+        assert!(new_entry.last_try > 0);
+
+        // The file starts with its format version
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data[0], PEERS_FILE_VERSION);
+        assert!(!path.with_extension("dat.new").exists());
+
+        // Version 1 and 2 files load without sources or attempts, and
+        // version 1 files without their source groups
+        let legacy = LegacyPeersFile {
+            key: db.key,
+            addresses: db
+                .addresses
+                .values()
+                .map(|entry| LegacyPersistedAddress {
+                    addr: entry.addr.clone(),
+                    first_seen: entry.first_seen,
+                    last_seen: entry.last_seen,
+                    services: entry.services,
+                    seen_count: entry.seen_count,
+                    source_group: entry.source_group.clone(),
+                    tried: entry.tried,
+                })
+                .collect(),
+        };
+        for version in [1u8, 2] {
+            let mut old = vec![version];
+            old.extend(bincode::serialize(&legacy).unwrap());
+            std::fs::write(&path, old).unwrap();
+            let mut upgraded = AddressDatabase::new(100);
+            assert_eq!(upgraded.load(&path).unwrap(), 2);
+            assert_eq!(upgraded.tried_count(), 1);
+            assert!(upgraded
+                .addresses
+                .values()
+                .all(|entry| entry.source.is_none() && entry.attempts == 0));
+            assert_eq!(
+                upgraded
+                    .addresses
+                    .values()
+                    .all(|entry| entry.source_group.is_empty()),
+                version == 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_unreadable_peers_file_starts_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEERS_FILE);
+        let mut db = AddressDatabase::new(100);
+        db.add_address(create_test_address("8.8.8.8", 8333), 1);
+        db.add_address(create_test_address("9.9.9.9", 8333), 1);
+        db.save(&path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+
+        // Truncated
+        std::fs::write(&path, &data[..data.len() - 5]).unwrap();
+        let mut loaded = AddressDatabase::new(100);
+        loaded.add_address(create_test_address("1.1.1.1", 8333), 1);
+        assert_eq!(loaded.load(&path).unwrap(), 0);
+        assert_eq!(loaded.total_count(), 0);
+
+        // Empty
+        std::fs::write(&path, []).unwrap();
+        assert_eq!(loaded.load(&path).unwrap(), 0);
+
+        // Written by a newer version
+        let mut future = data.clone();
+        future[0] = PEERS_FILE_VERSION + 1;
+        std::fs::write(&path, future).unwrap();
+        assert_eq!(loaded.load(&path).unwrap(), 0);
+
+        // Saving afterwards replaces the unreadable file
+        loaded.add_address(create_test_address("1.1.1.1", 8333), 1);
+        loaded.save(&path).unwrap();
+        assert_eq!(AddressDatabase::new(100).load(&path).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_table_slots_report_table_and_source() {
+        let mut db = AddressDatabase::new(100);
+        let relay: IpAddr = "1.2.3.4".parse().unwrap();
+        let other_relay: IpAddr = "5.6.7.8".parse().unwrap();
+        db.add_address_from(create_test_address("8.8.8.8", 8333), 1, relay);
+        db.add_address_from(create_test_address("9.9.9.9", 8333), 1, other_relay);
+        db.add_address(create_test_address("11.11.11.11", 8333), 1);
+        let tried_socket = SocketAddr::new("8.8.8.8".parse().unwrap(), 8333);
+        db.mark_attempt(&tried_socket);
+        assert_eq!(db.addresses[&tried_socket].attempts, 1);
+        assert!(db.mark_good(&tried_socket));
+
+        let tried = db.tried_table_slots();
+        assert_eq!(tried.len(), 1);
+        assert_eq!(tried[0].entry.addr, create_test_address("8.8.8.8", 8333));
+        assert_eq!(tried[0].entry.source, Some(relay));
+        assert_eq!(tried[0].entry.attempts, 0);
+        assert_eq!(
+            (tried[0].bucket, tried[0].position),
+            db.tried_slot(&tried_socket)
+        );
+
+        let new = db.new_table_slots();
+        assert_eq!(new.len(), 2);
+        assert!(new
+            .windows(2)
+            .all(|pair| (pair[0].bucket, pair[0].position) < (pair[1].bucket, pair[1].position)));
+        for slot in &new {
+            let socket = db.network_addr_to_socket(&slot.entry.addr);
+            let expected_source = if socket.ip() == "9.9.9.9".parse::<IpAddr>().unwrap() {
+                other_relay
+            } else {
+                // Addresses without a relaying peer are their own source
+                socket.ip()
+            };
+            assert_eq!(slot.entry.source, Some(expected_source));
+            assert_eq!(
+                (slot.bucket, slot.position),
+                db.new_slot(&socket, &slot.entry.source_group)
+            );
+            assert!(!slot.entry.tried);
+        }
+    }
+
     #[cfg(feature = "iroh")]
     #[test]
     fn test_add_iroh_address() {
@@ -1,10 +1,18 @@
 //! Ban list cryptographic signing
 //!
 //! Provides functions to sign and verify ban lists for authenticity.
+//! Signatures cover the whole `BanListMessage` except its `signature` field.
 
-use crate::network::protocol::BanListMessage;
+use crate::network::protocol::{BanListMessage, BanListSignature};
 use secp256k1::{ecdsa::Signature, Message, PublicKey, Secp256k1, SecretKey};
 
+/// Serialize a ban list for signing, leaving out any attached signature
+fn signing_payload(ban_list: &BanListMessage) -> Result<Vec<u8>, secp256k1::Error> {
+    let mut unsigned = ban_list.clone();
+    unsigned.signature = None;
+    bincode::serialize(&unsigned).map_err(|_| secp256k1::Error::InvalidMessage)
+}
+
 /// Sign a ban list with a private key
 ///
 /// Returns the signature as bytes (64 bytes for secp256k1)
@@ -15,7 +23,7 @@ pub fn sign_ban_list(
     let secp = Secp256k1::new();
 
     // Serialize ban list for signing
-    let serialized = bincode::serialize(ban_list).map_err(|_| secp256k1::Error::InvalidMessage)?;
+    let serialized = signing_payload(ban_list)?;
 
     // Hash the serialized data
     use sha2::{Digest, Sha256};
@@ -48,7 +56,7 @@ pub fn verify_ban_list_signature(
     let secp = Secp256k1::new();
 
     // Serialize ban list
-    let serialized = bincode::serialize(ban_list).map_err(|_| secp256k1::Error::InvalidMessage)?;
+    let serialized = signing_payload(ban_list)?;
 
     // Hash the serialized data
     use sha2::{Digest, Sha256};
@@ -100,3 +108,51 @@ impl SignedBanListMessage {
         verify_ban_list_signature(&self.ban_list, &self.signature, &self.public_key)
     }
 }
+
+/// Sign a ban list in place, attaching the signature and public key
+pub fn sign_ban_list_message(
+    ban_list: &mut BanListMessage,
+    private_key: &SecretKey,
+) -> Result<(), secp256k1::Error> {
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::from_secret_key(&secp, private_key);
+    let signature = sign_ban_list(ban_list, private_key)?;
+    ban_list.signature = Some(BanListSignature {
+        public_key: public_key.serialize().to_vec(),
+        signature,
+    });
+    Ok(())
+}
+
+/// Result of checking a ban list's attached signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanListSignatureStatus {
+    /// Validly signed by a trusted publisher
+    Valid,
+    /// No signature attached
+    Unsigned,
+    /// Signed by a key that is not a trusted publisher
+    UntrustedPublisher,
+    /// Malformed signature or public key, or the signature does not match
+    Invalid,
+}
+
+/// Check a ban list's attached signature against the trusted publisher keys
+pub fn check_ban_list_signature(
+    ban_list: &BanListMessage,
+    trusted_publishers: &[PublicKey],
+) -> BanListSignatureStatus {
+    let Some(ref attached) = ban_list.signature else {
+        return BanListSignatureStatus::Unsigned;
+    };
+    let Ok(public_key) = PublicKey::from_slice(&attached.public_key) else {
+        return BanListSignatureStatus::Invalid;
+    };
+    if !trusted_publishers.contains(&public_key) {
+        return BanListSignatureStatus::UntrustedPublisher;
+    }
+    match verify_ban_list_signature(ban_list, &attached.signature, &public_key) {
+        Ok(true) => BanListSignatureStatus::Valid,
+        _ => BanListSignatureStatus::Invalid,
+    }
+}
@@ -0,0 +1,335 @@
+//! Bandwidth throttling
+//!
+//! Byte-based token buckets limiting upload and download rates, per peer and
+//! node-wide. Unlike `PeerRateLimiter`, which drops messages over the limit,
+//! these delay the transfer: a peer's write task waits for the bucket to refill
+//! (so outgoing messages queue in its send channel) and its read task waits
+//! before reading the next frame (so the remote side sees TCP backpressure).
+//!
+//! The daily upload target (`maxuploadtarget`) is a separate budget: rather
+//! than slowing transfers down, it stops serving historical blocks to most
+//! peers once reached (see [`UploadTarget`]).
+
+use crate::config::BandwidthConfig;
+use crate::utils::current_timestamp;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Byte token bucket
+///
+/// The bucket may go into debt: a transfer larger than the available tokens
+/// is let through once the debt it leaves behind has been paid off, so
+/// messages of any size are paced at `rate` rather than rejected.
+#[derive(Debug)]
+pub struct ByteRateLimiter {
+    /// Available bytes (negative while in debt)
+    tokens: f64,
+    /// Maximum bytes that can accumulate
+    burst: f64,
+    /// Refill rate (bytes per second)
+    rate: f64,
+    /// Last refill time
+    last_refill: Instant,
+}
+
+impl ByteRateLimiter {
+    /// Create a bucket refilling at `rate` bytes per second, starting full
+    pub fn new(rate: u64, burst: u64) -> Self {
+        Self {
+            tokens: burst as f64,
+            burst: burst as f64,
+            rate: rate.max(1) as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before
+    /// transferring them
+    pub fn reserve(&mut self, bytes: usize) -> Duration {
+        self.refill();
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// Whether the bucket is empty, i.e. the next transfer will be delayed
+    pub fn is_throttled(&mut self) -> bool {
+        self.refill();
+        self.tokens < 1.0
+    }
+
+    /// Refill rate (bytes per second)
+    pub fn rate(&self) -> u64 {
+        self.rate as u64
+    }
+}
+
+type SharedLimiter = Arc<Mutex<ByteRateLimiter>>;
+
+/// Length of an upload target cycle (24 hours)
+pub const UPLOAD_TARGET_TIMEFRAME_SECONDS: u64 = 24 * 60 * 60;
+
+/// Blocks this much older than the tip are historical (one week)
+pub const HISTORICAL_BLOCK_AGE_SECONDS: u64 = 7 * 24 * 60 * 60;
+
+/// Daily upload budget (`maxuploadtarget`)
+///
+/// Counts the bytes sent to all peers in the current cycle. Once the target
+/// is reached, historical blocks are no longer served except to exempt
+/// peers; everything else is sent as usual.
+#[derive(Debug)]
+pub struct UploadTarget {
+    /// Bytes allowed per cycle
+    target: u64,
+    /// Start of the current cycle (Unix timestamp)
+    cycle_start: u64,
+    /// Bytes sent in the current cycle
+    sent: u64,
+}
+
+impl UploadTarget {
+    /// Create a target of `target` bytes per cycle, starting a cycle at `now`
+    pub fn new(target: u64, now: u64) -> Self {
+        Self {
+            target,
+            cycle_start: now,
+            sent: 0,
+        }
+    }
+
+    fn roll(&mut self, now: u64) {
+        if now >= self.cycle_start + UPLOAD_TARGET_TIMEFRAME_SECONDS {
+            self.cycle_start = now;
+            self.sent = 0;
+        }
+    }
+
+    /// Count `bytes` sent at `now`
+    pub fn record(&mut self, bytes: u64, now: u64) {
+        self.roll(now);
+        self.sent = self.sent.saturating_add(bytes);
+    }
+
+    /// Whether the cycle's budget is used up
+    pub fn is_reached(&mut self, now: u64) -> bool {
+        self.roll(now);
+        self.sent >= self.target
+    }
+
+    /// Bytes left in the current cycle
+    pub fn bytes_left(&mut self, now: u64) -> u64 {
+        self.roll(now);
+        self.target.saturating_sub(self.sent)
+    }
+}
+
+/// Create a limiter for `rate` bytes per second (0 means unlimited)
+///
+/// The burst is one second's worth of traffic.
+fn limiter(rate: u64) -> Option<SharedLimiter> {
+    (rate > 0).then(|| Arc::new(Mutex::new(ByteRateLimiter::new(rate, rate))))
+}
+
+/// Node-wide bandwidth limits
+///
+/// Holds the global buckets shared by every peer and creates fresh per-peer
+/// buckets for each new connection.
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthLimits {
+    peer_upload_rate: u64,
+    peer_download_rate: u64,
+    global_upload: Option<SharedLimiter>,
+    global_download: Option<SharedLimiter>,
+    upload_target: Option<Arc<Mutex<UploadTarget>>>,
+}
+
+impl BandwidthLimits {
+    /// Create from configuration
+    pub fn from_config(config: &BandwidthConfig) -> Self {
+        let upload_target = (config.max_upload_target_mib > 0).then(|| {
+            Arc::new(Mutex::new(UploadTarget::new(
+                config.max_upload_target_mib.saturating_mul(1024 * 1024),
+                current_timestamp(),
+            )))
+        });
+        Self {
+            peer_upload_rate: config.peer_upload_bytes_per_second,
+            peer_download_rate: config.peer_download_bytes_per_second,
+            global_upload: limiter(config.max_upload_bytes_per_second),
+            global_download: limiter(config.max_download_bytes_per_second),
+            upload_target,
+        }
+    }
+
+    /// Count bytes sent to any peer against the upload target
+    pub fn record_upload(&self, bytes: u64) {
+        if let Some(ref target) = self.upload_target {
+            target.lock().unwrap().record(bytes, current_timestamp());
+        }
+    }
+
+    /// Whether the upload target is set and used up for this cycle
+    pub fn upload_target_reached(&self) -> bool {
+        self.upload_target
+            .as_ref()
+            .is_some_and(|target| target.lock().unwrap().is_reached(current_timestamp()))
+    }
+
+    /// Limiters for a new peer connection
+    pub fn for_peer(&self) -> PeerBandwidth {
+        PeerBandwidth {
+            upload: limiter(self.peer_upload_rate),
+            download: limiter(self.peer_download_rate),
+            global_upload: self.global_upload.clone(),
+            global_download: self.global_download.clone(),
+        }
+    }
+
+    /// Limiters for a peer with the `download` permission: uploads to it are
+    /// not limited, by either the per-peer or the node-wide bucket
+    pub fn for_download_peer(&self) -> PeerBandwidth {
+        PeerBandwidth {
+            upload: None,
+            global_upload: None,
+            ..self.for_peer()
+        }
+    }
+}
+
+/// Bandwidth limiters applied to a single peer's transfers
+#[derive(Debug, Clone, Default)]
+pub struct PeerBandwidth {
+    upload: Option<SharedLimiter>,
+    download: Option<SharedLimiter>,
+    global_upload: Option<SharedLimiter>,
+    global_download: Option<SharedLimiter>,
+}
+
+impl PeerBandwidth {
+    /// No limits
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Wait until `bytes` may be sent to the peer
+    pub async fn throttle_upload(&self, bytes: usize) {
+        throttle(&[&self.upload, &self.global_upload], bytes).await;
+    }
+
+    /// Wait until `bytes` more may be read from the peer
+    pub async fn throttle_download(&self, bytes: usize) {
+        throttle(&[&self.download, &self.global_download], bytes).await;
+    }
+
+    /// Current throttle state
+    pub fn state(&self) -> ThrottleState {
+        ThrottleState {
+            upload_limit: self.upload.as_ref().map(|l| l.lock().unwrap().rate()),
+            download_limit: self.download.as_ref().map(|l| l.lock().unwrap().rate()),
+            upload_throttled: is_throttled(&[&self.upload, &self.global_upload]),
+            download_throttled: is_throttled(&[&self.download, &self.global_download]),
+        }
+    }
+}
+
+/// Reserve `bytes` from every limiter and sleep for the longest wait
+async fn throttle(limiters: &[&Option<SharedLimiter>], bytes: usize) {
+    let wait = limiters
+        .iter()
+        .filter_map(|l| l.as_ref())
+        .map(|l| l.lock().unwrap().reserve(bytes))
+        .max()
+        .unwrap_or_default();
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn is_throttled(limiters: &[&Option<SharedLimiter>]) -> bool {
+    limiters
+        .iter()
+        .filter_map(|l| l.as_ref())
+        .any(|l| l.lock().unwrap().is_throttled())
+}
+
+/// Snapshot of a peer's bandwidth throttling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleState {
+    /// Per-peer upload limit (bytes per second), if any
+    pub upload_limit: Option<u64>,
+    /// Per-peer download limit (bytes per second), if any
+    pub download_limit: Option<u64>,
+    /// Whether sends to the peer are currently being delayed
+    pub upload_throttled: bool,
+    /// Whether reads from the peer are currently being delayed
+    pub download_throttled: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_within_burst_is_immediate() {
+        let mut limiter = ByteRateLimiter::new(1000, 1000);
+        assert_eq!(limiter.reserve(600), Duration::ZERO);
+        assert!(!limiter.is_throttled());
+
+        // Going into debt waits for the debt to be repaid
+        let wait = limiter.reserve(900);
+        assert!(wait >= Duration::from_millis(490) && wait <= Duration::from_millis(500));
+        assert!(limiter.is_throttled());
+    }
+
+    #[tokio::test]
+    async fn test_global_cap_is_shared_between_peers() {
+        let limits = BandwidthLimits::from_config(&BandwidthConfig {
+            max_upload_bytes_per_second: 1000,
+            ..BandwidthConfig::default()
+        });
+        let a = limits.for_peer();
+        let b = limits.for_peer();
+
+        a.throttle_upload(1000).await;
+        assert!(a.state().upload_throttled);
+        assert!(b.state().upload_throttled);
+        assert_eq!(b.state().upload_limit, None);
+        assert!(!b.state().download_throttled);
+    }
+
+    #[test]
+    fn test_upload_target_resets_each_cycle() {
+        let mut target = UploadTarget::new(1000, 100);
+        target.record(600, 100);
+        assert!(!target.is_reached(200));
+        assert_eq!(target.bytes_left(200), 400);
+        target.record(400, 300);
+        assert!(target.is_reached(300));
+
+        // A new cycle starts with the full budget
+        let next_cycle = 100 + UPLOAD_TARGET_TIMEFRAME_SECONDS;
+        assert!(!target.is_reached(next_cycle));
+        assert_eq!(target.bytes_left(next_cycle), 1000);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_never_throttles() {
+        let bandwidth = PeerBandwidth::unlimited();
+        let start = Instant::now();
+        bandwidth.throttle_upload(10_000_000).await;
+        bandwidth.throttle_download(10_000_000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(bandwidth.state(), ThrottleState::default());
+    }
+}
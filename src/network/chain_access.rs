@@ -4,11 +4,13 @@
 //! (BlockStore, TxIndex, MempoolManager) with protocol layer network processing.
 
 use crate::node::mempool::MempoolManager;
-use crate::storage::{blockstore::BlockStore, txindex::TxIndex};
+use crate::storage::blockstore::{BlockAvailability, BlockStore};
+use crate::storage::txindex::TxIndex;
 use anyhow::Result;
 use bllvm_protocol::network::{ChainObject, ChainStateAccess};
 use bllvm_protocol::{BlockHeader, Hash, Transaction};
 use std::sync::Arc;
+use tracing::debug;
 
 /// Chain state access implementation that bridges node storage to protocol layer
 pub struct NodeChainAccess {
@@ -48,10 +50,17 @@ impl ChainStateAccess for NodeChainAccess {
     }
 
     /// Get an object (block or transaction) by hash
+    ///
+    /// A pruned block is answered like a missing one (`notfound`).
     fn get_object(&self, hash: &Hash) -> Option<ChainObject> {
         // Try blockstore first (blocks)
-        if let Ok(Some(block)) = self.blockstore.get_block(hash) {
-            return Some(ChainObject::Block(block));
+        match self.blockstore.get_block_availability(hash) {
+            Ok(BlockAvailability::Available(block)) => return Some(ChainObject::Block(block)),
+            Ok(BlockAvailability::Pruned) => {
+                debug!("Requested block {} has been pruned", hex::encode(hash));
+                return None;
+            }
+            _ => {}
         }
         // Try txindex (confirmed transactions)
         if let Ok(Some(tx)) = self.txindex.get_transaction(hash) {
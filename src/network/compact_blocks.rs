@@ -20,7 +20,9 @@
 //! - The combination provides optimal bandwidth and latency for mobile nodes and NAT-traversed connections
 
 use crate::network::transport::TransportType;
+use crate::validation::witness::encode_varint;
 use anyhow::Result;
+use bllvm_protocol::mining::calculate_merkle_root;
 use bllvm_protocol::{Block, BlockHeader, Hash, Transaction};
 use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
@@ -103,25 +105,6 @@ pub fn calculate_tx_hash(tx: &Transaction) -> Hash {
     result
 }
 
-/// Encode a number as a Bitcoin varint
-fn encode_varint(value: u64) -> Vec<u8> {
-    if value < 0xfd {
-        vec![value as u8]
-    } else if value <= 0xffff {
-        let mut result = vec![0xfd];
-        result.extend_from_slice(&(value as u16).to_le_bytes());
-        result
-    } else if value <= 0xffffffff {
-        let mut result = vec![0xfe];
-        result.extend_from_slice(&(value as u32).to_le_bytes());
-        result
-    } else {
-        let mut result = vec![0xff];
-        result.extend_from_slice(&value.to_le_bytes());
-        result
-    }
-}
-
 /// Calculate short transaction ID
 ///
 /// Uses SipHash-2-4 with keys derived from block header nonce.
@@ -150,14 +133,173 @@ pub fn calculate_short_tx_id(tx_hash: &Hash, nonce: u64) -> ShortTxId {
     short_id
 }
 
-/// Reconstruct full block from compact block
+/// Most transactions a block can hold (4M weight / 40 weight per minimal transaction)
+const MAX_BLOCK_TRANSACTIONS: usize = 100_000;
+
+/// Why a compact block couldn't be reconstructed
 ///
-/// Attempts to match short IDs with transactions from mempool,
-/// then requests missing transactions from peer.
+/// Any of these means the full block should be requested instead.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CompactBlockError {
+    #[error("Invalid compact block: {0}")]
+    Invalid(String),
+
+    #[error("Short transaction ID collision")]
+    ShortIdCollision,
+
+    #[error("Expected {expected} missing transactions, got {actual}")]
+    WrongTransactionCount { expected: usize, actual: usize },
+
+    #[error("Reconstructed block does not match the header's merkle root")]
+    MerkleRootMismatch,
+}
+
+/// Compact block being filled in from local transactions and `blocktxn`
+#[derive(Debug, Clone)]
+pub struct PartiallyDownloadedBlock {
+    header: BlockHeader,
+    /// Transactions in block order (`None` = still missing)
+    txs: Vec<Option<Transaction>>,
+}
+
+impl PartiallyDownloadedBlock {
+    /// Place the prefilled transactions and match short IDs against `pool`
+    ///
+    /// `pool` is typically the mempool plus the orphan pool. Fails with
+    /// `ShortIdCollision` if two of the block's short IDs are equal or one
+    /// short ID matches several pool transactions.
+    ///
+    /// **BIP125 + BIP152 Integration**: A pool transaction that conflicts with
+    /// an earlier transaction of the block is a replaced version; its slot is
+    /// left missing so the block's version is requested from the peer.
+    pub fn new<'a>(
+        compact_block: &CompactBlock,
+        pool: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Result<Self, CompactBlockError> {
+        let tx_count = compact_block.short_ids.len() + compact_block.prefilled_txs.len();
+        if tx_count == 0 || tx_count > MAX_BLOCK_TRANSACTIONS {
+            return Err(CompactBlockError::Invalid(format!(
+                "{tx_count} transactions"
+            )));
+        }
+
+        let mut txs: Vec<Option<Transaction>> = vec![None; tx_count];
+        for (index, tx) in &compact_block.prefilled_txs {
+            let Some(slot) = txs.get_mut(*index).filter(|slot| slot.is_none()) else {
+                return Err(CompactBlockError::Invalid(format!(
+                    "bad prefilled transaction index {index}"
+                )));
+            };
+            *slot = Some(tx.clone());
+        }
+
+        // Short IDs fill the slots that weren't prefilled, in order
+        let open_slots: Vec<usize> = (0..tx_count).filter(|&i| txs[i].is_none()).collect();
+        let mut slot_by_short_id: HashMap<ShortTxId, usize> = HashMap::new();
+        for (&short_id, &slot) in compact_block.short_ids.iter().zip(&open_slots) {
+            if slot_by_short_id.insert(short_id, slot).is_some() {
+                return Err(CompactBlockError::ShortIdCollision);
+            }
+        }
+
+        let mut matched: HashMap<usize, Hash> = HashMap::new();
+        for tx in pool {
+            let tx_hash = calculate_tx_hash(tx);
+            let short_id = calculate_short_tx_id(&tx_hash, compact_block.nonce);
+            let Some(&slot) = slot_by_short_id.get(&short_id) else {
+                continue;
+            };
+            match matched.get(&slot) {
+                // The same transaction can be in more than one pool
+                Some(existing) if *existing == tx_hash => {}
+                Some(_) => return Err(CompactBlockError::ShortIdCollision),
+                None => {
+                    matched.insert(slot, tx_hash);
+                    txs[slot] = Some(tx.clone());
+                }
+            }
+        }
+
+        let mut spent = HashSet::new();
+        for (index, slot) in txs.iter_mut().enumerate() {
+            let Some(tx) = slot else {
+                continue;
+            };
+            let conflicts = tx.inputs.iter().any(|input| spent.contains(&input.prevout));
+            if conflicts && matched.contains_key(&index) {
+                *slot = None;
+                continue;
+            }
+            spent.extend(tx.inputs.iter().map(|input| input.prevout.clone()));
+        }
+
+        Ok(Self {
+            header: compact_block.header.clone(),
+            txs,
+        })
+    }
+
+    /// Block header
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Indices of transactions still missing (to request via getblocktxn)
+    pub fn missing_indices(&self) -> Vec<usize> {
+        (0..self.txs.len())
+            .filter(|&i| self.txs[i].is_none())
+            .collect()
+    }
+
+    /// Check whether every transaction is known
+    pub fn is_complete(&self) -> bool {
+        self.txs.iter().all(Option::is_some)
+    }
+
+    /// Fill in the missing transactions and return the verified block
+    ///
+    /// `missing` holds the transactions for `missing_indices()`, in order
+    /// (empty if the block is already complete). The block's merkle root must
+    /// match the header, which catches wrong short ID matches.
+    pub fn finish(self, missing: Vec<Transaction>) -> Result<Block, CompactBlockError> {
+        let expected = self.txs.iter().filter(|tx| tx.is_none()).count();
+        if missing.len() != expected {
+            return Err(CompactBlockError::WrongTransactionCount {
+                expected,
+                actual: missing.len(),
+            });
+        }
+        let mut missing = missing.into_iter();
+        let transactions: Vec<Transaction> = self
+            .txs
+            .into_iter()
+            .filter_map(|tx| tx.or_else(|| missing.next()))
+            .collect();
+
+        if calculate_merkle_root(&transactions).ok() != Some(self.header.merkle_root) {
+            return Err(CompactBlockError::MerkleRootMismatch);
+        }
+        Ok(Block {
+            header: self.header,
+            transactions: transactions.into_boxed_slice(),
+        })
+    }
+}
+
+/// Block hash (double SHA256 of the serialized header)
+pub fn calculate_block_hash(header: &BlockHeader) -> Hash {
+    use bllvm_protocol::serialization::serialize_block_header;
+
+    let hash = Sha256::digest(Sha256::digest(serialize_block_header(header)));
+    let mut result = [0u8; 32];
+    result.copy_from_slice(&hash);
+    result
+}
+
+/// Reconstruct full block from compact block
 ///
-/// **BIP125 + BIP152 Integration**: When matching transactions from mempool,
-/// this function uses RBF conflict detection to ensure that matched transactions
-/// don't conflict with each other or with already-matched transactions.
+/// Attempts to match short IDs with transactions from mempool; the rest
+/// must be requested from the peer. See `PartiallyDownloadedBlock`.
 ///
 /// # Arguments
 /// * `compact_block` - The compact block to reconstruct
@@ -169,59 +311,7 @@ pub fn reconstruct_block(
     compact_block: &CompactBlock,
     mempool_txs: &HashMap<Hash, Transaction>,
 ) -> Result<Vec<usize>> {
-    let mut missing_indices = Vec::new();
-    let mut reconstructed_txs = Vec::new();
-
-    // Match short IDs with mempool transactions
-    for (index, &short_id) in compact_block.short_ids.iter().enumerate() {
-        let mut matched = false;
-
-        // Search mempool for transaction matching this short ID
-        for (tx_hash, tx) in mempool_txs {
-            let calculated_short_id = calculate_short_tx_id(tx_hash, compact_block.nonce);
-            if calculated_short_id == short_id {
-                // BIP125 + BIP152 Integration: Check for RBF conflicts
-                // If this transaction conflicts with already-matched transactions,
-                // don't use it (the block version is authoritative)
-                let has_conflict = reconstructed_txs
-                    .iter()
-                    .any(|(_, existing_tx)| has_conflict_with_tx(tx, existing_tx));
-
-                if !has_conflict {
-                    reconstructed_txs.push((index, tx.clone()));
-                    matched = true;
-                    break;
-                } else {
-                    // Conflict detected: This mempool transaction is a replacement
-                    // The block contains the authoritative version, so we'll request it
-                    // This ensures we get the correct transaction that won the RBF
-                    matched = false; // Will be requested from peer
-                    break;
-                }
-            }
-        }
-
-        if !matched {
-            missing_indices.push(index);
-        }
-    }
-
-    Ok(missing_indices)
-}
-
-/// Check if two transactions conflict (BIP125 requirement #4)
-///
-/// A conflict exists if tx1 and tx2 spend at least one common input.
-/// Used during compact block reconstruction to detect RBF conflicts.
-fn has_conflict_with_tx(tx1: &Transaction, tx2: &Transaction) -> bool {
-    for input1 in &tx1.inputs {
-        for input2 in &tx2.inputs {
-            if input1.prevout == input2.prevout {
-                return true;
-            }
-        }
-    }
-    false
+    Ok(PartiallyDownloadedBlock::new(compact_block, mempool_txs.values())?.missing_indices())
 }
 
 /// Create compact block from full block
@@ -418,6 +508,100 @@ mod tests {
         assert_eq!(missing.len(), 1);
     }
 
+    fn test_tx(seed: u8) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![bllvm_protocol::TransactionInput {
+                prevout: bllvm_protocol::OutPoint {
+                    hash: [seed; 32],
+                    index: 0,
+                },
+                script_sig: vec![seed],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![bllvm_protocol::TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        }
+    }
+
+    fn test_block(tx_count: u8) -> Block {
+        let transactions: Vec<Transaction> = (0..tx_count).map(test_tx).collect();
+        Block {
+            header: BlockHeader {
+                version: 1,
+                prev_block_hash: [0; 32],
+                merkle_root: calculate_merkle_root(&transactions).unwrap(),
+                timestamp: 0,
+                bits: 0,
+                nonce: 0,
+            },
+            transactions: transactions.into_boxed_slice(),
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_block_requests_only_missing() {
+        let block = test_block(6);
+        let compact = create_compact_block(&block, 42, &HashSet::from([0]));
+
+        // The receiver has everything but transactions 2 and 4
+        let pool: Vec<Transaction> = [1, 3, 5]
+            .iter()
+            .map(|&i| block.transactions[i].clone())
+            .collect();
+        let partial = PartiallyDownloadedBlock::new(&compact, &pool).unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.missing_indices(), vec![2, 4]);
+
+        let missing = vec![block.transactions[2].clone(), block.transactions[4].clone()];
+        let rebuilt = partial.finish(missing).unwrap();
+        assert_eq!(rebuilt.header, block.header);
+        assert_eq!(rebuilt.transactions, block.transactions);
+    }
+
+    #[test]
+    fn test_reconstruct_block_complete_from_pool() {
+        let block = test_block(4);
+        let compact = create_compact_block(&block, 7, &HashSet::from([0]));
+        let partial = PartiallyDownloadedBlock::new(&compact, block.transactions.iter()).unwrap();
+        assert!(partial.is_complete());
+        let rebuilt = partial.finish(vec![]).unwrap();
+        assert_eq!(rebuilt.transactions, block.transactions);
+    }
+
+    #[test]
+    fn test_reconstruct_block_short_id_collision() {
+        let block = test_block(3);
+        let mut compact = create_compact_block(&block, 7, &HashSet::from([0]));
+        // Two of the block's transactions share a short ID
+        compact.short_ids[1] = compact.short_ids[0];
+        assert_eq!(
+            PartiallyDownloadedBlock::new(&compact, block.transactions.iter()).unwrap_err(),
+            CompactBlockError::ShortIdCollision
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_block_rejects_wrong_transactions() {
+        let block = test_block(3);
+        let compact = create_compact_block(&block, 7, &HashSet::from([0]));
+        let partial = PartiallyDownloadedBlock::new(&compact, std::iter::empty()).unwrap();
+        assert_eq!(partial.missing_indices(), vec![1, 2]);
+
+        let wrong = vec![block.transactions[1].clone(), test_tx(9)];
+        assert_eq!(
+            partial.clone().finish(wrong).unwrap_err(),
+            CompactBlockError::MerkleRootMismatch
+        );
+        assert!(matches!(
+            partial.finish(vec![]),
+            Err(CompactBlockError::WrongTransactionCount { .. })
+        ));
+    }
+
     #[test]
     fn test_should_prefer_compact_blocks_tcp() {
         // TCP: compact blocks optional, not preferred by default
@@ -0,0 +1,442 @@
+//! Outbound connection management
+//!
+//! Keeps the node at its target number of outbound peers, makes periodic
+//! "feeler" connections to test addresses from the address database, and
+//! persists "anchor" peers (the longest-lived outbound connections) to
+//! `anchors.dat` so they are reconnected first after a restart. This mirrors
+//! Bitcoin Core's outbound logic and makes eclipse attacks across restarts harder.
+//! Persistent (`addnode`) peers are reconnected with exponential backoff.
+
+use crate::config::NetworkTimingConfig;
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Anchors file name (stored in the data directory)
+pub const ANCHORS_FILE: &str = "anchors.dat";
+
+/// Maximum number of anchor peers persisted across restarts
+pub const MAX_ANCHORS: usize = 2;
+
+/// Upper bound on the delay between persistent peer reconnection attempts
+pub const MAX_PERSISTENT_PEER_BACKOFF_SECONDS: u64 = 300;
+
+/// Role of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionType {
+    /// Connection opened by the remote peer
+    Inbound,
+    /// Outbound connection relaying blocks, transactions and addresses
+    OutboundFullRelay,
+    /// Outbound connection relaying blocks only
+    BlockRelayOnly,
+    /// Short-lived outbound connection testing an address
+    Feeler,
+}
+
+impl ConnectionType {
+    /// Name used by `getpeerinfo` (as in Bitcoin Core)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionType::Inbound => "inbound",
+            ConnectionType::OutboundFullRelay => "outbound-full-relay",
+            ConnectionType::BlockRelayOnly => "block-relay-only",
+            ConnectionType::Feeler => "feeler",
+        }
+    }
+}
+
+/// Number of open connections of each type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionCounts {
+    pub inbound: usize,
+    pub outbound_full_relay: usize,
+    pub block_relay_only: usize,
+    pub feeler: usize,
+}
+
+impl ConnectionCounts {
+    /// Count one connection of the given type
+    pub fn record(&mut self, connection_type: ConnectionType) {
+        match connection_type {
+            ConnectionType::Inbound => self.inbound += 1,
+            ConnectionType::OutboundFullRelay => self.outbound_full_relay += 1,
+            ConnectionType::BlockRelayOnly => self.block_relay_only += 1,
+            ConnectionType::Feeler => self.feeler += 1,
+        }
+    }
+
+    /// Number of outbound connections of any type
+    pub fn outbound(&self) -> usize {
+        self.outbound_full_relay + self.block_relay_only + self.feeler
+    }
+
+    /// Number of connections of any type
+    pub fn total(&self) -> usize {
+        self.inbound + self.outbound()
+    }
+}
+
+/// Outbound connection scheduling state
+#[derive(Debug)]
+pub struct ConnectionManager {
+    /// Target number of outbound connections
+    target_outbound: usize,
+    /// Target number of additional block-relay-only outbound connections
+    target_block_relay_only: usize,
+    /// Seconds between outbound maintenance rounds
+    maintenance_interval_seconds: u64,
+    /// Seconds between feeler connections
+    feeler_interval_seconds: u64,
+    /// Earliest time (Unix seconds) the first maintenance round may run
+    start_after: u64,
+    /// Last maintenance round (Unix seconds)
+    last_maintenance: AtomicU64,
+    /// Last feeler connection (Unix seconds)
+    last_feeler: AtomicU64,
+    /// Path to anchors.dat (anchors are not persisted if unset)
+    anchors_path: Option<PathBuf>,
+    /// Feeler connections currently open
+    active_feelers: AtomicUsize,
+}
+
+impl ConnectionManager {
+    /// Create from network timing configuration
+    ///
+    /// The first maintenance round waits `peer_connection_delay_seconds` so that
+    /// anchors and persistent peers get a chance to connect first.
+    pub fn new(timing: &NetworkTimingConfig, now: u64) -> Self {
+        Self {
+            target_outbound: timing.target_peer_count,
+            target_block_relay_only: timing.block_relay_only_peer_count,
+            maintenance_interval_seconds: timing.outbound_maintenance_interval_seconds,
+            feeler_interval_seconds: timing.feeler_interval_seconds,
+            start_after: now + timing.peer_connection_delay_seconds,
+            last_maintenance: AtomicU64::new(0),
+            last_feeler: AtomicU64::new(0),
+            anchors_path: None,
+            active_feelers: AtomicUsize::new(0),
+        }
+    }
+
+    /// Persist anchors to the given file
+    pub fn set_anchors_path(&mut self, path: PathBuf) {
+        self.anchors_path = Some(path);
+    }
+
+    /// Target number of outbound connections
+    pub fn target_outbound(&self) -> usize {
+        self.target_outbound
+    }
+
+    /// Target number of block-relay-only outbound connections
+    pub fn target_block_relay_only(&self) -> usize {
+        self.target_block_relay_only
+    }
+
+    /// Path to anchors.dat, if persistence is enabled
+    pub fn anchors_path(&self) -> Option<&Path> {
+        self.anchors_path.as_deref()
+    }
+
+    /// Check whether a maintenance round is due, and claim it if so
+    pub fn maintenance_due(&self, now: u64) -> bool {
+        now >= self.start_after
+            && Self::claim(
+                &self.last_maintenance,
+                self.maintenance_interval_seconds,
+                now,
+            )
+    }
+
+    /// Check whether a feeler connection is due, and claim it if so
+    pub fn feeler_due(&self, now: u64) -> bool {
+        now >= self.start_after && Self::claim(&self.last_feeler, self.feeler_interval_seconds, now)
+    }
+
+    /// Record that a feeler connection is being opened
+    pub fn feeler_started(&self) {
+        self.active_feelers.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Record that a feeler connection has been closed
+    pub fn feeler_finished(&self) {
+        let _ = self
+            .active_feelers
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+    }
+
+    /// Number of feeler connections currently open
+    pub fn active_feelers(&self) -> usize {
+        self.active_feelers.load(Ordering::Acquire)
+    }
+
+    fn claim(last: &AtomicU64, interval: u64, now: u64) -> bool {
+        let previous = last.load(Ordering::Acquire);
+        if previous != 0 && now.saturating_sub(previous) < interval {
+            return false;
+        }
+        last.compare_exchange(previous, now, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+}
+
+/// Reconnection state of a persistent peer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistentPeerState {
+    /// Whether the peer is currently connected
+    pub connected: bool,
+    /// Connection attempts since the peer was last connected
+    pub attempts: u32,
+    /// Earliest time (Unix seconds) of the next connection attempt
+    pub next_attempt: u64,
+}
+
+impl PersistentPeerState {
+    /// State for a newly added peer, which is connected right away
+    pub fn new(now: u64) -> Self {
+        Self {
+            connected: false,
+            attempts: 0,
+            next_attempt: now,
+        }
+    }
+
+    /// Check whether a connection attempt is due
+    pub fn is_due(&self, now: u64) -> bool {
+        !self.connected && now >= self.next_attempt
+    }
+
+    /// Record a successful connection
+    pub fn mark_connected(&mut self) {
+        self.connected = true;
+        self.attempts = 0;
+    }
+
+    /// Schedule the next attempt after a disconnect or failed connection
+    ///
+    /// The delay starts at `base_delay` and doubles with every attempt.
+    pub fn schedule_retry(&mut self, now: u64, base_delay: u64) {
+        self.connected = false;
+        self.next_attempt = now + persistent_peer_backoff(base_delay, self.attempts);
+        self.attempts = self.attempts.saturating_add(1);
+    }
+}
+
+/// Delay before the next reconnection attempt to a persistent peer
+pub fn persistent_peer_backoff(base_delay: u64, attempts: u32) -> u64 {
+    base_delay
+        .max(1)
+        .saturating_mul(1u64 << attempts.min(16))
+        .min(MAX_PERSISTENT_PEER_BACKOFF_SECONDS)
+}
+
+/// Check if an address is currently banned
+pub(crate) fn is_banned(addr: &SocketAddr, ban_list: &HashMap<SocketAddr, u64>, now: u64) -> bool {
+    match ban_list.get(addr) {
+        Some(&unban_timestamp) => unban_timestamp == u64::MAX || now < unban_timestamp,
+        None => false,
+    }
+}
+
+/// Filter candidates down to addresses we may open a new connection to
+///
+/// Skips banned addresses and any address whose IP we are already connected
+/// to, and removes duplicates while preserving order.
+fn eligible<'a>(
+    candidates: &'a [SocketAddr],
+    connected: &'a [SocketAddr],
+    ban_list: &'a HashMap<SocketAddr, u64>,
+    now: u64,
+) -> impl Iterator<Item = SocketAddr> + 'a {
+    let connected_ips: HashSet<_> = connected.iter().map(|addr| addr.ip()).collect();
+    let mut seen = HashSet::new();
+    candidates.iter().copied().filter(move |addr| {
+        !connected_ips.contains(&addr.ip())
+            && !is_banned(addr, ban_list, now)
+            && seen.insert(addr.ip())
+    })
+}
+
+/// Select up to `count` addresses for new outbound connections
+pub fn select_outbound_candidates(
+    candidates: &[SocketAddr],
+    connected: &[SocketAddr],
+    ban_list: &HashMap<SocketAddr, u64>,
+    now: u64,
+    count: usize,
+) -> Vec<SocketAddr> {
+    eligible(candidates, connected, ban_list, now)
+        .take(count)
+        .collect()
+}
+
+/// Pick a random eligible address for a feeler connection
+pub fn select_feeler_candidate(
+    candidates: &[SocketAddr],
+    connected: &[SocketAddr],
+    ban_list: &HashMap<SocketAddr, u64>,
+    now: u64,
+) -> Option<SocketAddr> {
+    let eligible: Vec<SocketAddr> = eligible(candidates, connected, ban_list, now).collect();
+    eligible.choose(&mut rand::thread_rng()).copied()
+}
+
+/// Select anchors: the longest-lived outbound connections
+///
+/// `outbound` holds (address, connection time) pairs; the oldest connections win.
+pub fn select_anchors(outbound: &[(SocketAddr, u64)]) -> Vec<SocketAddr> {
+    let mut sorted = outbound.to_vec();
+    sorted.sort_by_key(|&(_, conntime)| conntime);
+    sorted
+        .into_iter()
+        .take(MAX_ANCHORS)
+        .map(|(addr, _)| addr)
+        .collect()
+}
+
+/// Write anchors to disk
+pub fn save_anchors(path: &Path, anchors: &[SocketAddr]) -> Result<()> {
+    let data = bincode::serialize(anchors)?;
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
+/// Read anchors from disk and delete the file
+///
+/// The file is removed after reading (as Bitcoin Core does) so a crash
+/// before the next save does not reuse stale anchors forever. Missing or
+/// corrupt files yield no anchors.
+pub fn load_anchors(path: &Path) -> Vec<SocketAddr> {
+    let anchors = match std::fs::read(path) {
+        Ok(data) => bincode::deserialize::<Vec<SocketAddr>>(&data).unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    };
+    let _ = std::fs::remove_file(path);
+    anchors.into_iter().take(MAX_ANCHORS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_outbound_selection_skips_banned_and_connected() {
+        let candidates = vec![
+            addr("1.1.1.1:8333"),
+            addr("2.2.2.2:8333"),
+            addr("3.3.3.3:8333"),
+            addr("4.4.4.4:8333"),
+        ];
+        let connected = vec![addr("2.2.2.2:18333")];
+        let mut ban_list = HashMap::new();
+        ban_list.insert(addr("3.3.3.3:8333"), u64::MAX);
+
+        let selected = select_outbound_candidates(&candidates, &connected, &ban_list, 1000, 10);
+        assert_eq!(selected, vec![addr("1.1.1.1:8333"), addr("4.4.4.4:8333")]);
+    }
+
+    #[test]
+    fn test_outbound_selection_respects_count_and_expired_bans() {
+        let candidates = vec![
+            addr("1.1.1.1:8333"),
+            addr("1.1.1.1:8334"),
+            addr("2.2.2.2:8333"),
+            addr("3.3.3.3:8333"),
+        ];
+        let mut ban_list = HashMap::new();
+        // Ban expired at t=500
+        ban_list.insert(addr("2.2.2.2:8333"), 500);
+
+        let selected = select_outbound_candidates(&candidates, &[], &ban_list, 1000, 2);
+        // Same IP is only selected once
+        assert_eq!(selected, vec![addr("1.1.1.1:8333"), addr("2.2.2.2:8333")]);
+    }
+
+    #[test]
+    fn test_feeler_selection() {
+        let candidates = vec![addr("1.1.1.1:8333"), addr("2.2.2.2:8333")];
+        let connected = vec![addr("1.1.1.1:8333")];
+        let mut ban_list = HashMap::new();
+
+        let feeler = select_feeler_candidate(&candidates, &connected, &ban_list, 1000);
+        assert_eq!(feeler, Some(addr("2.2.2.2:8333")));
+
+        ban_list.insert(addr("2.2.2.2:8333"), 2000);
+        assert_eq!(
+            select_feeler_candidate(&candidates, &connected, &ban_list, 1000),
+            None
+        );
+    }
+
+    #[test]
+    fn test_select_anchors_picks_longest_lived() {
+        let outbound = vec![
+            (addr("1.1.1.1:8333"), 300),
+            (addr("2.2.2.2:8333"), 100),
+            (addr("3.3.3.3:8333"), 200),
+        ];
+        assert_eq!(
+            select_anchors(&outbound),
+            vec![addr("2.2.2.2:8333"), addr("3.3.3.3:8333")]
+        );
+    }
+
+    #[test]
+    fn test_anchors_roundtrip_removes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(ANCHORS_FILE);
+        let anchors = vec![addr("1.1.1.1:8333"), addr("[2001:db8::1]:8333")];
+
+        save_anchors(&path, &anchors).unwrap();
+        assert_eq!(load_anchors(&path), anchors);
+        assert!(!path.exists());
+        assert!(load_anchors(&path).is_empty());
+    }
+
+    #[test]
+    fn test_schedule_waits_for_delay_and_interval() {
+        let timing = NetworkTimingConfig {
+            peer_connection_delay_seconds: 10,
+            outbound_maintenance_interval_seconds: 30,
+            ..NetworkTimingConfig::default()
+        };
+        let manager = ConnectionManager::new(&timing, 1000);
+
+        assert!(!manager.maintenance_due(1005));
+        assert!(manager.maintenance_due(1010));
+        assert!(!manager.maintenance_due(1020));
+        assert!(manager.maintenance_due(1040));
+    }
+
+    #[test]
+    fn test_persistent_peer_backoff_doubles_up_to_cap() {
+        let mut state = PersistentPeerState::new(1000);
+        assert!(state.is_due(1000));
+
+        state.mark_connected();
+        assert!(!state.is_due(1000));
+
+        // Disconnect, then keep failing
+        let mut delays = Vec::new();
+        for _ in 0..10 {
+            state.schedule_retry(1000, 2);
+            delays.push(state.next_attempt - 1000);
+        }
+        assert_eq!(delays, vec![2, 4, 8, 16, 32, 64, 128, 256, 300, 300]);
+        assert!(!state.is_due(1299));
+        assert!(state.is_due(1300));
+
+        // A successful connection resets the backoff
+        state.mark_connected();
+        state.schedule_retry(2000, 2);
+        assert_eq!(state.next_attempt, 2002);
+    }
+}
@@ -318,6 +318,27 @@ impl<C: Clock> DandelionRelay<C> {
         self.stem_txs.get(tx_hash).and_then(|s| s.next_peer.clone())
     }
 
+    /// Start a new epoch: forget all stem paths so routes are re-drawn
+    ///
+    /// Transactions already in stem phase keep their state.
+    pub fn rotate_epoch(&mut self) {
+        debug!(
+            "Rotating Dandelion epoch ({} stem paths dropped)",
+            self.stem_paths.len()
+        );
+        self.stem_paths.clear();
+    }
+
+    /// Transactions whose stem phase has outlasted the stem timeout
+    pub fn timed_out_stem_txs(&self) -> Vec<Hash> {
+        let now = self.clock.now();
+        self.stem_txs
+            .iter()
+            .filter(|(_, state)| now.duration_since(state.stem_start) >= self.stem_timeout)
+            .map(|(tx_hash, _)| *tx_hash)
+            .collect()
+    }
+
     /// Clean up expired stem paths and transactions
     pub fn cleanup_expired(&mut self) {
         let now = self.clock.now();
@@ -418,6 +439,29 @@ mod tests {
         d.clock = clock.clone();
         assert!(d.should_fluff(&tx));
     }
+
+    #[test]
+    fn timed_out_stem_txs_and_epoch_rotation() {
+        let rng = StdRng::seed_from_u64(9);
+        let start = Instant::now();
+        let mut clock = TestClock::new(start);
+        let mut d: DandelionRelay<TestClock> =
+            DandelionRelay::with_rng_and_clock(rng, clock.clone());
+        d.set_stem_timeout(Duration::from_secs(10));
+
+        let tx = [3u8; 32];
+        assert!(d.start_stem_phase(tx, "p1".into(), &peers()).is_some());
+        assert!(d.timed_out_stem_txs().is_empty());
+
+        clock.advance(Duration::from_secs(10));
+        d.set_clock(clock.clone());
+        assert_eq!(d.timed_out_stem_txs(), vec![tx]);
+
+        // Rotation drops routes but not stem state
+        d.rotate_epoch();
+        assert_eq!(d.get_stats().active_stem_paths, 0);
+        assert_eq!(d.get_phase(&tx), Some(DandelionPhase::Stem));
+    }
 }
 
 #[cfg(kani)]
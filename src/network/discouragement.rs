@@ -0,0 +1,143 @@
+//! Discouraged addresses
+//!
+//! Peers whose misbehavior score reaches the disconnect threshold are
+//! discouraged, as Bitcoin Core does for misbehavior; only the ban threshold
+//! puts them on the ban list. A discouraged address is not dialed for
+//! outbound connections, is refused inbound when no slot is free, and is
+//! evicted first when an inbound slot is needed. Unlike bans, discouragement
+//! is local: it is never shared through ban-list gossip and does not show up
+//! in `listbanned`. Entries expire after [`DISCOURAGEMENT_DURATION_SECONDS`].
+
+use crate::network::dos_protection::connection_key;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Discouragement list file name (stored in the data directory)
+pub const DISCOURAGED_FILE: &str = "discouraged.dat";
+
+/// How long an address stays discouraged
+pub const DISCOURAGEMENT_DURATION_SECONDS: u64 = 24 * 60 * 60;
+
+/// Addresses discouraged for misbehavior, keyed by `connection_key`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscouragementList {
+    /// Address -> expiry (Unix timestamp)
+    entries: HashMap<IpAddr, u64>,
+}
+
+impl DiscouragementList {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Discourage an address (IPv6 addresses by /64) until
+    /// `DISCOURAGEMENT_DURATION_SECONDS` from `now`
+    pub fn discourage(&mut self, ip: IpAddr, now: u64) {
+        self.entries.insert(
+            connection_key(ip),
+            now.saturating_add(DISCOURAGEMENT_DURATION_SECONDS),
+        );
+    }
+
+    /// Whether an address is discouraged at `now`
+    pub fn is_discouraged(&self, ip: IpAddr, now: u64) -> bool {
+        self.entries
+            .get(&connection_key(ip))
+            .is_some_and(|&expiry| now < expiry)
+    }
+
+    /// Drop expired entries, returning how many were removed
+    pub fn cleanup(&mut self, now: u64) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, expiry| now < *expiry);
+        before - self.entries.len()
+    }
+
+    /// Remove every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Number of discouraged addresses (including expired ones not yet cleaned up)
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the list is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the list to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, bincode::serialize(self)?)?;
+        Ok(())
+    }
+
+    /// Replace the list with the one saved at `path`, dropping entries that
+    /// expired before `now`
+    ///
+    /// A missing file is not an error. Returns the number of entries loaded.
+    pub fn load(&mut self, path: &Path, now: u64) -> Result<usize> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        *self = bincode::deserialize(&data)?;
+        self.cleanup(now);
+        Ok(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discouragement_expires() {
+        let mut list = DiscouragementList::new();
+        let ip: IpAddr = "1.2.3.4".parse().unwrap();
+        list.discourage(ip, 1000);
+        assert!(list.is_discouraged(ip, 1000));
+        assert!(!list.is_discouraged("1.2.3.5".parse().unwrap(), 1000));
+
+        // IPv4-mapped form of the same address
+        assert!(list.is_discouraged("::ffff:1.2.3.4".parse().unwrap(), 1000));
+
+        let expiry = 1000 + DISCOURAGEMENT_DURATION_SECONDS;
+        assert!(list.is_discouraged(ip, expiry - 1));
+        assert!(!list.is_discouraged(ip, expiry));
+        assert_eq!(list.cleanup(expiry), 1);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_discouragement_survives_restart() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(DISCOURAGED_FILE);
+
+        let mut list = DiscouragementList::new();
+        list.discourage("1.2.3.4".parse().unwrap(), 1000);
+        list.discourage("2001:db8::1".parse().unwrap(), 0);
+        list.save(&path).unwrap();
+
+        // The second entry expired while the node was down
+        let now = DISCOURAGEMENT_DURATION_SECONDS + 500;
+        let mut loaded = DiscouragementList::new();
+        assert_eq!(loaded.load(&path, now).unwrap(), 1);
+        assert!(loaded.is_discouraged("1.2.3.4".parse().unwrap(), now));
+        assert!(!loaded.is_discouraged("2001:db8::1".parse().unwrap(), now));
+
+        assert_eq!(
+            DiscouragementList::new()
+                .load(&temp_dir.path().join("missing.dat"), 0)
+                .unwrap(),
+            0
+        );
+    }
+}
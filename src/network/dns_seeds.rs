@@ -4,10 +4,12 @@
 //! Based on Bitcoin Core's DNS seed mechanism.
 
 use crate::network::protocol::NetworkAddress;
+use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::lookup_host;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 /// Bitcoin DNS seeds (mainnet)
 /// These are well-known DNS servers that return Bitcoin node addresses
@@ -31,59 +33,189 @@ pub const TESTNET_DNS_SEEDS: &[&str] = &[
     "testnet-seed.bluematt.me",
 ];
 
-/// Resolve DNS seeds to peer addresses
-pub async fn resolve_dns_seeds(
-    seeds: &[&str],
+/// Testnet4 DNS seeds
+pub const TESTNET4_DNS_SEEDS: &[&str] = &[
+    "seed.testnet4.bitcoin.sprovoost.nl",
+    "seed.testnet4.wiz.biz",
+];
+
+/// Signet DNS seeds
+pub const SIGNET_DNS_SEEDS: &[&str] = &["seed.signet.bitcoin.sprovoost.nl"];
+
+/// Service flags requested from seeds by default (NODE_NETWORK | NODE_WITNESS)
+pub const DEFAULT_SEED_SERVICES: u64 = 0x09;
+
+/// Default timeout for a single seed lookup
+pub const DEFAULT_DNS_SEED_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Built-in DNS seeds for a network (empty for regtest and unknown networks)
+pub fn default_seeds(network: &str) -> &'static [&'static str] {
+    match network {
+        "mainnet" => MAINNET_DNS_SEEDS,
+        "testnet" => TESTNET_DNS_SEEDS,
+        "testnet4" => TESTNET4_DNS_SEEDS,
+        "signet" => SIGNET_DNS_SEEDS,
+        _ => &[],
+    }
+}
+
+/// Host name lookup used by [`DnsSeeder`]
+///
+/// Abstracted so seeding can be exercised without real DNS.
+#[async_trait::async_trait]
+pub trait SeedResolver: Send + Sync {
+    /// Resolve `host` to socket addresses using `port`
+    async fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>>;
+}
+
+/// Resolver backed by the system DNS resolver
+///
+/// Lookups bypass any configured proxy, so seeding is not done at all when
+/// one is set (see `NetworkManager::discover_peers_from_dns`).
+pub struct SystemResolver;
+
+#[async_trait::async_trait]
+impl SeedResolver for SystemResolver {
+    async fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+        Ok(lookup_host((host, port)).await?.collect())
+    }
+}
+
+/// DNS seed resolver with a result cache
+///
+/// Seeds are first queried with the `x<services>.` prefix so that seeds
+/// supporting service-bit filtering only return nodes with the required
+/// services. Seeds that don't support it (lookup fails or returns nothing)
+/// are queried again without the prefix.
+pub struct DnsSeeder {
+    seeds: Vec<String>,
     port: u16,
     max_addresses: usize,
-) -> Vec<NetworkAddress> {
-    let mut addresses = Vec::new();
-
-    for seed in seeds {
-        match resolve_dns_seed(*seed, port).await {
-            Ok(mut addrs) => {
-                info!("Resolved {} addresses from DNS seed: {}", addrs.len(), seed);
-                addresses.append(&mut addrs);
-                if addresses.len() >= max_addresses {
-                    break;
-                }
-            }
-            Err(e) => {
-                warn!("Failed to resolve DNS seed {}: {}", seed, e);
-            }
+    timeout: Duration,
+    required_services: u64,
+    resolver: Arc<dyn SeedResolver>,
+    /// Results of the last query
+    cache: std::sync::Mutex<Vec<NetworkAddress>>,
+}
+
+impl DnsSeeder {
+    /// Create a seeder for the given seeds using the system resolver
+    pub fn new(seeds: Vec<String>, port: u16, max_addresses: usize) -> Self {
+        Self {
+            seeds,
+            port,
+            max_addresses,
+            timeout: DEFAULT_DNS_SEED_TIMEOUT,
+            required_services: DEFAULT_SEED_SERVICES,
+            resolver: Arc::new(SystemResolver),
+            cache: std::sync::Mutex::new(Vec::new()),
         }
     }
 
-    // Limit to max_addresses
-    addresses.truncate(max_addresses);
-    addresses
-}
+    /// Use a custom resolver
+    pub fn with_resolver(mut self, resolver: Arc<dyn SeedResolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
 
-/// Resolve a single DNS seed
-async fn resolve_dns_seed(seed: &str, port: u16) -> Result<Vec<NetworkAddress>, String> {
-    // Create hostname:port string for DNS lookup
-    let hostname = format!("{}:{}", seed, port);
+    /// Set the per-seed lookup timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
-    // Perform DNS lookup with timeout
-    let timeout = Duration::from_secs(5);
-    let lookup_result = tokio::time::timeout(timeout, lookup_host(&hostname))
-        .await
-        .map_err(|_| format!("DNS lookup timeout for {}", seed))?;
+    /// Set the service flags to request (0 disables service filtering)
+    pub fn with_required_services(mut self, services: u64) -> Self {
+        self.required_services = services;
+        self
+    }
+
+    /// Addresses from the last query
+    pub fn cached_addresses(&self) -> Vec<NetworkAddress> {
+        self.cache.lock().unwrap().clone()
+    }
+
+    /// Get seed addresses
+    ///
+    /// Returns cached results unless `refresh` is set or nothing is cached yet.
+    pub async fn resolve(&self, refresh: bool) -> Vec<NetworkAddress> {
+        if !refresh {
+            let cached = self.cached_addresses();
+            if !cached.is_empty() {
+                return cached;
+            }
+        }
+
+        let mut addresses = Vec::new();
+        let mut seen = HashSet::new();
+        for seed in &self.seeds {
+            match self.resolve_seed(seed).await {
+                Ok(addrs) => {
+                    info!("Resolved {} addresses from DNS seed: {}", addrs.len(), seed);
+                    addresses.extend(addrs.into_iter().filter(|a| seen.insert((a.ip, a.port))));
+                    if addresses.len() >= self.max_addresses {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to resolve DNS seed {}: {}", seed, e);
+                }
+            }
+        }
 
-    let socket_addrs =
-        lookup_result.map_err(|e| format!("DNS lookup failed for {}: {}", seed, e))?;
+        // Limit to max_addresses
+        addresses.truncate(self.max_addresses);
+        *self.cache.lock().unwrap() = addresses.clone();
+        addresses
+    }
+
+    /// Resolve a single seed, preferring a service-filtered query
+    async fn resolve_seed(&self, seed: &str) -> Result<Vec<NetworkAddress>, String> {
+        if self.required_services != 0 {
+            let filtered = format!("x{:x}.{}", self.required_services, seed);
+            match self.lookup(&filtered).await {
+                Ok(addrs) if !addrs.is_empty() => {
+                    return Ok(addrs
+                        .into_iter()
+                        .map(|a| socket_addr_to_network_address(a, self.required_services))
+                        .collect());
+                }
+                Ok(_) => debug!("No results from {}, querying without filter", filtered),
+                Err(e) => debug!("{}, querying without filter", e),
+            }
+        }
 
-    // Convert SocketAddr to NetworkAddress
-    let mut addresses = Vec::new();
-    for socket_addr in socket_addrs {
-        addresses.push(socket_addr_to_network_address(socket_addr));
+        // Services will be updated when we connect
+        let addrs = self.lookup(seed).await?;
+        Ok(addrs
+            .into_iter()
+            .map(|a| socket_addr_to_network_address(a, 0))
+            .collect())
     }
 
-    Ok(addresses)
+    /// Look up a host name with the configured timeout
+    async fn lookup(&self, host: &str) -> Result<Vec<SocketAddr>, String> {
+        tokio::time::timeout(self.timeout, self.resolver.lookup(host, self.port))
+            .await
+            .map_err(|_| format!("DNS lookup timeout for {}", host))?
+            .map_err(|e| format!("DNS lookup failed for {}: {}", host, e))
+    }
+}
+
+/// Resolve DNS seeds to peer addresses
+pub async fn resolve_dns_seeds(
+    seeds: &[&str],
+    port: u16,
+    max_addresses: usize,
+) -> Vec<NetworkAddress> {
+    let seeds = seeds.iter().map(|s| s.to_string()).collect();
+    DnsSeeder::new(seeds, port, max_addresses)
+        .resolve(true)
+        .await
 }
 
 /// Convert SocketAddr to NetworkAddress
-fn socket_addr_to_network_address(socket_addr: SocketAddr) -> NetworkAddress {
+fn socket_addr_to_network_address(socket_addr: SocketAddr, services: u64) -> NetworkAddress {
     use std::net::IpAddr;
 
     let ip_bytes = match socket_addr.ip() {
@@ -99,7 +231,7 @@ fn socket_addr_to_network_address(socket_addr: SocketAddr) -> NetworkAddress {
     };
 
     NetworkAddress {
-        services: 0, // Will be updated when we connect
+        services,
         ip: ip_bytes,
         port: socket_addr.port(),
     }
@@ -108,6 +240,7 @@ fn socket_addr_to_network_address(socket_addr: SocketAddr) -> NetworkAddress {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[tokio::test]
     async fn test_socket_addr_to_network_address() {
@@ -115,7 +248,7 @@ mod tests {
             std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
             8333,
         );
-        let addr = socket_addr_to_network_address(socket);
+        let addr = socket_addr_to_network_address(socket, 0);
         assert_eq!(addr.port, 8333);
         // Check IPv4-mapped format
         assert_eq!(addr.ip[10], 0xff);
@@ -125,4 +258,105 @@ mod tests {
         assert_eq!(addr.ip[14], 0);
         assert_eq!(addr.ip[15], 1);
     }
+
+    /// Resolver answering from a fixed table; unknown hosts fail, hosts listed
+    /// in `hang` never answer
+    struct MockResolver {
+        records: HashMap<String, Vec<SocketAddr>>,
+        hang: Vec<String>,
+        queries: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl MockResolver {
+        fn new(records: &[(&str, &[&str])]) -> Self {
+            Self {
+                records: records
+                    .iter()
+                    .map(|(host, addrs)| {
+                        (
+                            host.to_string(),
+                            addrs.iter().map(|a| a.parse().unwrap()).collect(),
+                        )
+                    })
+                    .collect(),
+                hang: Vec::new(),
+                queries: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SeedResolver for MockResolver {
+        async fn lookup(&self, host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+            self.queries.lock().unwrap().push(host.to_string());
+            if self.hang.iter().any(|h| h == host) {
+                std::future::pending::<()>().await;
+            }
+            match self.records.get(host) {
+                Some(addrs) => Ok(addrs
+                    .iter()
+                    .map(|a| SocketAddr::new(a.ip(), port))
+                    .collect()),
+                None => Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "unknown host",
+                )),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_seeder_filters_by_services_and_falls_back() {
+        let resolver = Arc::new(MockResolver::new(&[
+            // Supports the x<services> prefix
+            ("x9.seed-a.example", &["1.1.1.1:0", "2.2.2.2:0"]),
+            ("seed-a.example", &["9.9.9.9:0"]),
+            // Does not support it
+            ("seed-b.example", &["3.3.3.3:0", "1.1.1.1:0"]),
+        ]));
+        let seeds = vec!["seed-a.example".to_string(), "seed-b.example".to_string()];
+        let seeder = DnsSeeder::new(seeds, 8333, 100).with_resolver(resolver.clone());
+
+        let addrs = seeder.resolve(false).await;
+        let ips: Vec<u8> = addrs.iter().map(|a| a.ip[12]).collect();
+        // seed-a answered the filtered query; duplicates are dropped
+        assert_eq!(ips, vec![1, 2, 3]);
+        assert!(addrs.iter().all(|a| a.port == 8333));
+        assert_eq!(addrs[0].services, DEFAULT_SEED_SERVICES);
+        assert_eq!(addrs[2].services, 0);
+        assert_eq!(
+            *resolver.queries.lock().unwrap(),
+            vec!["x9.seed-a.example", "x9.seed-b.example", "seed-b.example"]
+        );
+
+        // Cached results are reused until a refresh is requested
+        assert_eq!(seeder.resolve(false).await.len(), 3);
+        assert_eq!(resolver.queries.lock().unwrap().len(), 3);
+        seeder.resolve(true).await;
+        assert_eq!(resolver.queries.lock().unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_seeder_respects_max_addresses_and_timeout() {
+        let mut resolver = MockResolver::new(&[
+            ("x9.fast.example", &["1.1.1.1:0", "2.2.2.2:0", "3.3.3.3:0"]),
+            ("x9.slow.example", &["4.4.4.4:0"]),
+        ]);
+        resolver.hang = vec!["x9.slow.example".to_string(), "slow.example".to_string()];
+        let seeds = vec!["slow.example".to_string(), "fast.example".to_string()];
+        let seeder = DnsSeeder::new(seeds, 8333, 2)
+            .with_resolver(Arc::new(resolver))
+            .with_timeout(Duration::from_millis(50));
+
+        let addrs = seeder.resolve(true).await;
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(seeder.cached_addresses().len(), 2);
+    }
+
+    #[test]
+    fn test_default_seeds() {
+        assert!(!default_seeds("mainnet").is_empty());
+        assert!(!default_seeds("testnet").is_empty());
+        assert!(default_seeds("regtest").is_empty());
+    }
 }
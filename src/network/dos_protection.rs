@@ -3,13 +3,79 @@
 //! Provides connection rate limiting, message queue monitoring, resource usage tracking,
 //! and automatic mitigation for DoS attacks.
 
+use crate::network::permissions::{whitelist_permissions, NetPermissions, PermissionedSubnet};
 use crate::utils::current_timestamp;
 use std::collections::HashMap;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::warn;
 
+/// Misbehavior score at which a peer is disconnected
+pub const MISBEHAVIOR_DISCONNECT_THRESHOLD: u32 = 50;
+
+/// Misbehavior score at which a peer is banned
+pub const MISBEHAVIOR_BAN_THRESHOLD: u32 = 100;
+
+/// Block whose header doesn't meet its proof-of-work target
+pub const MISBEHAVIOR_INVALID_POW: u32 = 100;
+
+/// Block whose merkle root doesn't match its transactions, or without any
+pub const MISBEHAVIOR_MALFORMED_BLOCK: u32 = 100;
+
+/// Block that fails consensus validation
+pub const MISBEHAVIOR_INVALID_BLOCK: u32 = 100;
+
+/// Block whose parent we don't know (usually just a race, not misbehavior)
+pub const MISBEHAVIOR_UNKNOWN_PARENT: u32 = 0;
+
+/// Transaction no node would relay (consensus-invalid, not just refused by
+/// our policy)
+pub const MISBEHAVIOR_INVALID_TRANSACTION: u32 = 100;
+
+/// Block request left unanswered until the stall timeout (repeated stalls
+/// add up to a disconnect)
+pub const MISBEHAVIOR_BLOCK_STALL: u32 = 10;
+
+/// What to do with a peer after reporting misbehavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MisbehaviorAction {
+    /// Keep the connection
+    None,
+    /// Score reached `MISBEHAVIOR_DISCONNECT_THRESHOLD`: disconnect and
+    /// discourage the address
+    Disconnect,
+    /// Score reached `MISBEHAVIOR_BAN_THRESHOLD`
+    Ban,
+}
+
+/// Key used for per-IP connection accounting
+///
+/// IPv4-mapped IPv6 addresses count as their IPv4 address. Other IPv6
+/// addresses are grouped by /64, since a single host usually controls a
+/// whole /64 and could otherwise dodge limits by rotating addresses.
+pub fn connection_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => {
+                let segments = v6.segments();
+                IpAddr::V6(Ipv6Addr::new(
+                    segments[0],
+                    segments[1],
+                    segments[2],
+                    segments[3],
+                    0,
+                    0,
+                    0,
+                    0,
+                ))
+            }
+        },
+    }
+}
+
 /// Connection rate limiter (tracks connection attempts per time window)
 pub struct ConnectionRateLimiter {
     /// Connection attempts per IP (timestamp -> count)
@@ -32,6 +98,7 @@ impl ConnectionRateLimiter {
 
     /// Check if a connection attempt is allowed
     pub fn check_connection(&mut self, ip: IpAddr) -> bool {
+        let ip = connection_key(ip);
         let now = current_timestamp();
 
         // Clean up old entries outside the time window
@@ -70,7 +137,7 @@ impl ConnectionRateLimiter {
     /// Get current connection attempt count for an IP
     pub fn get_attempt_count(&self, ip: IpAddr) -> usize {
         self.connection_attempts
-            .get(&ip)
+            .get(&connection_key(ip))
             .map(|v| v.len())
             .unwrap_or(0)
     }
@@ -135,8 +202,12 @@ pub struct DosProtectionManager {
     connection_violations: Arc<Mutex<HashMap<IpAddr, usize>>>,
     /// DoS protection metrics (cumulative counters)
     metrics: Arc<Mutex<DosProtectionMetrics>>,
+    /// Misbehavior scores (keyed by `connection_key`)
+    misbehavior_scores: Arc<Mutex<HashMap<IpAddr, u32>>>,
     /// Ban duration in seconds
     ban_duration_seconds: u64,
+    /// Trusted subnets and the permissions they grant
+    whitelist: Vec<PermissionedSubnet>,
 }
 
 impl DosProtectionManager {
@@ -183,10 +254,29 @@ impl DosProtectionManager {
                 active_connection_limit_hits: 0,
                 resource_exhaustion_events: 0,
             })),
+            misbehavior_scores: Arc::new(Mutex::new(HashMap::new())),
             ban_duration_seconds,
+            whitelist: Vec::new(),
         }
     }
 
+    /// Grant peers in these subnets permissions; `noban` exempts them from
+    /// DoS limits and auto-bans
+    pub fn with_whitelist(mut self, whitelist: Vec<PermissionedSubnet>) -> Self {
+        self.whitelist = whitelist;
+        self
+    }
+
+    /// Permissions the whitelist grants an IP
+    pub fn permissions(&self, ip: IpAddr) -> NetPermissions {
+        whitelist_permissions(&self.whitelist, ip)
+    }
+
+    /// Check if an IP is in a whitelisted subnet granting `noban`
+    pub fn is_whitelisted(&self, ip: IpAddr) -> bool {
+        self.permissions(ip).contains(NetPermissions::NOBAN)
+    }
+
     /// Create with default settings
     pub fn default() -> Self {
         Self::new(
@@ -199,9 +289,13 @@ impl DosProtectionManager {
 
     /// Check if a connection attempt is allowed
     pub async fn check_connection(&self, ip: IpAddr) -> bool {
+        if self.is_whitelisted(ip) {
+            return true;
+        }
         let mut limiter = self.connection_rate_limiter.lock().await;
         let allowed = limiter.check_connection(ip);
 
+        let ip = connection_key(ip);
         if !allowed {
             // Track violation
             let mut violations = self.connection_violations.lock().await;
@@ -275,7 +369,9 @@ impl DosProtectionManager {
         let violations = self.connection_violations.lock().await;
         violations
             .iter()
-            .filter(|(_, &count)| count >= self.auto_ban_connection_violations)
+            .filter(|(ip, &count)| {
+                count >= self.auto_ban_connection_violations && !self.is_whitelisted(**ip)
+            })
             .map(|(ip, _)| *ip)
             .collect()
     }
@@ -328,8 +424,61 @@ impl DosProtectionManager {
 
     /// Check if an IP should be auto-banned
     pub async fn should_auto_ban(&self, ip: IpAddr) -> bool {
+        if self.is_whitelisted(ip) {
+            return false;
+        }
         let violations = self.connection_violations.lock().await;
-        violations.get(&ip).copied().unwrap_or(0) >= self.auto_ban_connection_violations
+        violations.get(&connection_key(ip)).copied().unwrap_or(0)
+            >= self.auto_ban_connection_violations
+    }
+
+    /// Add misbehavior points to a peer's score
+    ///
+    /// Returns whether the peer should be disconnected or banned. A banned
+    /// peer's score is reset, so it starts over once the ban expires.
+    /// Whitelisted peers are scored but never disconnected.
+    pub async fn report_misbehavior(
+        &self,
+        peer: SocketAddr,
+        points: u32,
+        reason: &str,
+    ) -> MisbehaviorAction {
+        if points == 0 {
+            return MisbehaviorAction::None;
+        }
+        let key = connection_key(peer.ip());
+        let score = {
+            let mut scores = self.misbehavior_scores.lock().await;
+            let score = scores.entry(key).or_insert(0);
+            *score = score.saturating_add(points);
+            *score
+        };
+        warn!(
+            "Misbehavior by {} ({}): +{} points, score {}",
+            peer, reason, points, score
+        );
+        if self.is_whitelisted(peer.ip()) {
+            return MisbehaviorAction::None;
+        }
+        if score >= MISBEHAVIOR_BAN_THRESHOLD {
+            self.misbehavior_scores.lock().await.remove(&key);
+            self.metrics.lock().await.auto_bans_applied += 1;
+            MisbehaviorAction::Ban
+        } else if score >= MISBEHAVIOR_DISCONNECT_THRESHOLD {
+            MisbehaviorAction::Disconnect
+        } else {
+            MisbehaviorAction::None
+        }
+    }
+
+    /// Current misbehavior score of a peer
+    pub async fn misbehavior_score(&self, ip: IpAddr) -> u32 {
+        self.misbehavior_scores
+            .lock()
+            .await
+            .get(&connection_key(ip))
+            .copied()
+            .unwrap_or(0)
     }
 
     /// Get DoS protection metrics
@@ -410,4 +559,91 @@ mod tests {
 
         assert!(dos.should_auto_ban(ip).await);
     }
+
+    #[tokio::test]
+    async fn test_whitelisted_ip_not_rate_limited() {
+        let dos = DosProtectionManager::new(2, 60, 1000, 100)
+            .with_whitelist(vec!["10.0.0.0/8".parse().unwrap()]);
+        let trusted: IpAddr = "10.1.2.3".parse().unwrap();
+        let other: IpAddr = "192.0.2.1".parse().unwrap();
+
+        for _ in 0..10 {
+            assert!(dos.check_connection(trusted).await);
+        }
+        assert!(!dos.should_auto_ban(trusted).await);
+
+        // Exceed the limit 3 times from a normal IP
+        for _ in 0..3 {
+            dos.check_connection(other).await;
+            dos.check_connection(other).await;
+            assert!(!dos.check_connection(other).await);
+        }
+        assert!(dos.should_auto_ban(other).await);
+        assert_eq!(dos.get_ips_to_auto_ban().await, vec![other]);
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_rate_limited_per_prefix() {
+        let dos = DosProtectionManager::new(2, 60, 1000, 100);
+
+        // Addresses within one /64 share a limit
+        assert!(dos.check_connection("2001:db8::1".parse().unwrap()).await);
+        assert!(dos.check_connection("2001:db8::2".parse().unwrap()).await);
+        assert!(!dos.check_connection("2001:db8::3".parse().unwrap()).await);
+
+        // A different /64 is counted separately
+        assert!(
+            dos.check_connection("2001:db8:0:1::1".parse().unwrap())
+                .await
+        );
+
+        // IPv4-mapped addresses count as the IPv4 address
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        assert!(dos.check_connection(v4).await);
+        assert!(
+            dos.check_connection("::ffff:192.0.2.1".parse().unwrap())
+                .await
+        );
+        assert_eq!(dos.get_connection_attempts(v4).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_misbehavior_scoring() {
+        let dos =
+            DosProtectionManager::default().with_whitelist(vec!["10.0.0.0/8".parse().unwrap()]);
+        let peer: SocketAddr = "192.0.2.1:8333".parse().unwrap();
+
+        assert_eq!(
+            dos.report_misbehavior(peer, MISBEHAVIOR_UNKNOWN_PARENT, "orphan block")
+                .await,
+            MisbehaviorAction::None
+        );
+        assert_eq!(
+            dos.report_misbehavior(peer, 30, "test").await,
+            MisbehaviorAction::None
+        );
+        // Scored per IP, not per connection
+        let reconnected: SocketAddr = "192.0.2.1:50000".parse().unwrap();
+        assert_eq!(
+            dos.report_misbehavior(reconnected, 30, "test").await,
+            MisbehaviorAction::Disconnect
+        );
+        assert_eq!(dos.misbehavior_score(peer.ip()).await, 60);
+        assert_eq!(
+            dos.report_misbehavior(peer, 40, "test").await,
+            MisbehaviorAction::Ban
+        );
+        assert_eq!(dos.misbehavior_score(peer.ip()).await, 0);
+
+        let trusted: SocketAddr = "10.1.2.3:8333".parse().unwrap();
+        assert_eq!(
+            dos.report_misbehavior(trusted, MISBEHAVIOR_INVALID_POW, "high-hash")
+                .await,
+            MisbehaviorAction::None
+        );
+        assert_eq!(
+            dos.misbehavior_score(trusted.ip()).await,
+            MISBEHAVIOR_INVALID_POW
+        );
+    }
 }
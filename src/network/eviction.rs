@@ -0,0 +1,252 @@
+//! Inbound peer eviction
+//!
+//! When all inbound slots are taken, a new inbound connection may still be
+//! accepted by evicting an existing inbound peer. Following Bitcoin Core, peers
+//! that are hard for an attacker to fake are protected first (lowest ping, most
+//! recent block delivery, longest uptime); the victim is then chosen from the
+//! network group with the most remaining connections, so an attacker holding
+//! many addresses in one range cannot crowd out honest peers. If any of the
+//! unprotected peers connect from a discouraged address, the victim is chosen
+//! among those.
+
+use super::netgroup;
+use super::transport::TransportAddr;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Number of peers protected by lowest ping
+pub const PROTECT_BY_PING: usize = 8;
+
+/// Number of peers protected by most recent block delivery
+pub const PROTECT_BY_BLOCK: usize = 4;
+
+/// Stats used to rank an inbound peer for eviction
+#[derive(Debug, Clone)]
+pub struct EvictionCandidate {
+    /// Peer address
+    pub addr: TransportAddr,
+    /// Connection time (Unix timestamp)
+    pub conntime: u64,
+    /// Lowest observed ping (milliseconds)
+    pub min_ping_ms: Option<f64>,
+    /// Last block delivered by this peer (Unix timestamp)
+    pub last_block_time: Option<u64>,
+    /// Evict ahead of other unprotected peers (discouraged address)
+    pub prefer_evict: bool,
+}
+
+impl EvictionCandidate {
+    /// Network group of the peer (see [`network_group`])
+    pub fn network_group(&self) -> Vec<u8> {
+        network_group(&self.addr)
+    }
+}
+
+/// Network group of a peer address (see [`netgroup::network_group`])
+///
+/// Iroh peers have no IP address and are grouped by a prefix of their key.
+pub fn network_group(addr: &TransportAddr) -> Vec<u8> {
+    match addr {
+        TransportAddr::Tcp(addr) => netgroup::network_group(addr.ip()),
+        #[cfg(feature = "quinn")]
+        TransportAddr::Quinn(addr) => netgroup::network_group(addr.ip()),
+        #[cfg(feature = "iroh")]
+        TransportAddr::Iroh(key) => {
+            let mut group = vec![0xff];
+            group.extend(key.iter().take(4));
+            group
+        }
+    }
+}
+
+/// Remove the first `count` candidates after sorting with `compare`
+fn protect(
+    candidates: &mut Vec<EvictionCandidate>,
+    count: usize,
+    compare: impl FnMut(&EvictionCandidate, &EvictionCandidate) -> Ordering,
+) {
+    candidates.sort_by(compare);
+    let count = count.min(candidates.len());
+    candidates.drain(..count);
+}
+
+/// Choose an inbound peer to evict, if any
+///
+/// Returns `None` when every candidate ends up protected.
+pub fn select_peer_to_evict(mut candidates: Vec<EvictionCandidate>) -> Option<TransportAddr> {
+    // Lowest ping first; peers without a ping measurement sort last
+    protect(&mut candidates, PROTECT_BY_PING, |a, b| {
+        match (a.min_ping_ms, b.min_ping_ms) {
+            (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+
+    // Most recent block first; peers that never delivered a block sort last
+    protect(&mut candidates, PROTECT_BY_BLOCK, |a, b| {
+        b.last_block_time.cmp(&a.last_block_time)
+    });
+
+    // Half of the remainder by longest uptime
+    let half = candidates.len() / 2;
+    protect(&mut candidates, half, |a, b| a.conntime.cmp(&b.conntime));
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    // Discouraged peers go first
+    if candidates.iter().any(|c| c.prefer_evict) {
+        candidates.retain(|c| c.prefer_evict);
+    }
+
+    // Group remaining peers by network group
+    let mut groups: HashMap<Vec<u8>, Vec<EvictionCandidate>> = HashMap::new();
+    for candidate in candidates {
+        groups
+            .entry(candidate.network_group())
+            .or_default()
+            .push(candidate);
+    }
+
+    // Largest group wins; ties go to the group with the youngest connection
+    let youngest = |group: &[EvictionCandidate]| group.iter().map(|c| c.conntime).max();
+    let victims = groups.into_values().max_by(|a, b| {
+        a.len()
+            .cmp(&b.len())
+            .then_with(|| youngest(a).cmp(&youngest(b)))
+    })?;
+
+    // Evict the youngest connection in that group
+    victims
+        .into_iter()
+        .max_by_key(|c| c.conntime)
+        .map(|c| c.addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        ip: &str,
+        conntime: u64,
+        min_ping_ms: Option<f64>,
+        last_block_time: Option<u64>,
+    ) -> EvictionCandidate {
+        EvictionCandidate {
+            addr: TransportAddr::Tcp(format!("{ip}:8333").parse().unwrap()),
+            conntime,
+            min_ping_ms,
+            last_block_time,
+            prefer_evict: false,
+        }
+    }
+
+    #[test]
+    fn test_evicts_youngest_peer_in_largest_network_group() {
+        let mut candidates = Vec::new();
+        // Fast peers, protected by ping (even though they share one group)
+        for i in 0..8 {
+            candidates.push(candidate(
+                &format!("10.0.0.{}", 100 + i),
+                5000,
+                Some(1.0 + i as f64),
+                None,
+            ));
+        }
+        // Peers that recently delivered blocks
+        for i in 0..4 {
+            candidates.push(candidate(
+                &format!("20.{i}.0.1"),
+                5000,
+                Some(500.0),
+                Some(4000 + i),
+            ));
+        }
+        // Long-lived peers, protected by uptime
+        for i in 0..4 {
+            candidates.push(candidate(
+                &format!("30.{i}.0.1"),
+                100 + i,
+                Some(500.0),
+                None,
+            ));
+        }
+        // Unprotected: three private-range peers (one group) and one in 40.0.0.0/16
+        candidates.push(candidate("10.0.1.1", 3000, Some(500.0), None));
+        candidates.push(candidate("10.0.1.2", 3500, Some(500.0), None));
+        candidates.push(candidate("10.0.1.3", 3200, Some(500.0), None));
+        candidates.push(candidate("40.0.0.1", 9000, None, None));
+
+        assert_eq!(
+            select_peer_to_evict(candidates),
+            Some(TransportAddr::Tcp("10.0.1.2:8333".parse().unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_no_eviction_when_all_protected() {
+        let candidates = (0..8)
+            .map(|i| candidate(&format!("10.0.0.{i}"), 1000, Some(10.0), None))
+            .collect();
+        assert_eq!(select_peer_to_evict(candidates), None);
+    }
+
+    #[test]
+    fn test_ipv4_mapped_addresses_share_group() {
+        let v4 = candidate("1.2.3.4", 0, None, None);
+        let mapped = EvictionCandidate {
+            addr: TransportAddr::Tcp("[::ffff:1.2.9.9]:8333".parse().unwrap()),
+            ..v4.clone()
+        };
+        assert_eq!(v4.network_group(), mapped.network_group());
+    }
+
+    #[test]
+    fn test_discouraged_peer_evicted_first() {
+        let mut candidates = Vec::new();
+        for i in 0..8 {
+            candidates.push(candidate(
+                &format!("10.0.0.{}", 100 + i),
+                5000,
+                Some(1.0 + i as f64),
+                None,
+            ));
+        }
+        for i in 0..4 {
+            candidates.push(candidate(
+                &format!("20.{i}.0.1"),
+                5000,
+                Some(500.0),
+                Some(4000 + i),
+            ));
+        }
+        for i in 0..4 {
+            candidates.push(candidate(
+                &format!("30.{i}.0.1"),
+                100 + i,
+                Some(500.0),
+                None,
+            ));
+        }
+        candidates.push(candidate("10.0.1.1", 3000, Some(500.0), None));
+        candidates.push(candidate("10.0.1.2", 3500, Some(500.0), None));
+        candidates.push(candidate("10.0.1.3", 3200, Some(500.0), None));
+        candidates.push(candidate("40.0.0.1", 9000, None, None));
+        let addr = |ip: &str| Some(TransportAddr::Tcp(format!("{ip}:8333").parse().unwrap()));
+        assert_eq!(select_peer_to_evict(candidates.clone()), addr("10.0.1.2"));
+
+        // A discouraged peer goes first, whatever its network group
+        let mut discouraged = candidates.clone();
+        discouraged[16].prefer_evict = true;
+        assert_eq!(select_peer_to_evict(discouraged), addr("10.0.1.1"));
+
+        // ...unless it is protected
+        let mut discouraged = candidates;
+        discouraged[0].prefer_evict = true;
+        assert_eq!(select_peer_to_evict(discouraged), addr("10.0.1.2"));
+    }
+}
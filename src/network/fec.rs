@@ -0,0 +1,324 @@
+//! Reed-Solomon erasure coding
+//!
+//! Systematic erasure code over GF(2^8) used by FIBRE block relay. A group of
+//! `k` equal-length data shards is extended with `m` parity shards, and any
+//! `k` of the `k + m` shards are enough to recover the data.
+//!
+//! Parity rows are taken from a Cauchy matrix, so every `k × k` submatrix of
+//! the encoding matrix (identity rows on top, Cauchy rows below) is invertible.
+
+/// Maximum number of shards (data + parity) in one group
+pub const MAX_SHARDS: usize = 256;
+
+/// Reducing polynomial for GF(2^8): x^8 + x^4 + x^3 + x^2 + 1
+const GF_POLY: u16 = 0x11d;
+
+/// Exponent and logarithm tables for GF(2^8) with generator 2
+struct GfTables {
+    /// exp[i] = 2^i, doubled in length so exp[log a + log b] needs no reduction
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+static GF: GfTables = build_tables();
+
+const fn build_tables() -> GfTables {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= GF_POLY;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    GfTables { exp, log }
+}
+
+fn gf_mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    GF.exp[GF.log[a as usize] as usize + GF.log[b as usize] as usize]
+}
+
+/// Multiplicative inverse (`a` must be non-zero)
+fn gf_inv(a: u8) -> u8 {
+    debug_assert!(a != 0, "zero has no inverse");
+    GF.exp[255 - GF.log[a as usize] as usize]
+}
+
+/// `out ^= coef * input`, byte by byte
+fn mul_add(out: &mut [u8], input: &[u8], coef: u8) {
+    match coef {
+        0 => {}
+        1 => {
+            for (o, i) in out.iter_mut().zip(input) {
+                *o ^= i;
+            }
+        }
+        _ => {
+            let log_coef = GF.log[coef as usize] as usize;
+            for (o, &i) in out.iter_mut().zip(input) {
+                if i != 0 {
+                    *o ^= GF.exp[log_coef + GF.log[i as usize] as usize];
+                }
+            }
+        }
+    }
+}
+
+/// Invert a square matrix over GF(2^8) (Gauss-Jordan elimination)
+///
+/// Returns `None` if the matrix is singular.
+fn invert(mut matrix: Vec<Vec<u8>>) -> Option<Vec<Vec<u8>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<u8>> = (0..n)
+        .map(|r| (0..n).map(|c| u8::from(r == c)).collect())
+        .collect();
+
+    for col in 0..n {
+        let pivot = (col..n).find(|&r| matrix[r][col] != 0)?;
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+
+        let scale = gf_inv(matrix[col][col]);
+        for value in matrix[col].iter_mut().chain(inverse[col].iter_mut()) {
+            *value = gf_mul(*value, scale);
+        }
+
+        let pivot_row = matrix[col].clone();
+        let pivot_inverse = inverse[col].clone();
+        for r in 0..n {
+            let factor = matrix[r][col];
+            if r == col || factor == 0 {
+                continue;
+            }
+            mul_add(&mut matrix[r], &pivot_row, factor);
+            mul_add(&mut inverse[r], &pivot_inverse, factor);
+        }
+    }
+    Some(inverse)
+}
+
+/// Systematic Reed-Solomon erasure code with fixed shard counts
+#[derive(Debug, Clone)]
+pub struct ReedSolomon {
+    data_shards: usize,
+    parity_shards: usize,
+    /// Coefficients of each parity shard over the data shards
+    parity_matrix: Vec<Vec<u8>>,
+}
+
+impl ReedSolomon {
+    /// Create a code for `data_shards` data and `parity_shards` parity shards
+    pub fn new(data_shards: usize, parity_shards: usize) -> Result<Self, FecError> {
+        if data_shards == 0 || data_shards + parity_shards > MAX_SHARDS {
+            return Err(FecError::InvalidShardCount {
+                data: data_shards,
+                parity: parity_shards,
+            });
+        }
+        // Cauchy matrix 1 / (x_i + y_j) with x_i = k + i and y_j = j: all
+        // points are distinct, so x_i + y_j (XOR) is never zero
+        let parity_matrix = (0..parity_shards)
+            .map(|i| {
+                (0..data_shards)
+                    .map(|j| gf_inv(((data_shards + i) ^ j) as u8))
+                    .collect()
+            })
+            .collect();
+        Ok(Self {
+            data_shards,
+            parity_shards,
+            parity_matrix,
+        })
+    }
+
+    /// Number of data shards
+    pub fn data_shards(&self) -> usize {
+        self.data_shards
+    }
+
+    /// Number of parity shards
+    pub fn parity_shards(&self) -> usize {
+        self.parity_shards
+    }
+
+    /// Compute the parity shards for `data`
+    ///
+    /// All data shards must have the same length.
+    pub fn encode<T: AsRef<[u8]>>(&self, data: &[T]) -> Result<Vec<Vec<u8>>, FecError> {
+        if data.len() != self.data_shards {
+            return Err(FecError::WrongShardCount {
+                expected: self.data_shards,
+                actual: data.len(),
+            });
+        }
+        let len = data[0].as_ref().len();
+        if data.iter().any(|shard| shard.as_ref().len() != len) {
+            return Err(FecError::ShardSizeMismatch);
+        }
+
+        let mut parity = vec![vec![0u8; len]; self.parity_shards];
+        for (row, out) in self.parity_matrix.iter().zip(parity.iter_mut()) {
+            for (&coef, shard) in row.iter().zip(data) {
+                mul_add(out, shard.as_ref(), coef);
+            }
+        }
+        Ok(parity)
+    }
+
+    /// Recover missing data shards in place
+    ///
+    /// `shards` holds the data shards followed by the parity shards, with
+    /// `None` for shards that were lost. On success every data shard is
+    /// `Some`; missing parity shards are left as `None`.
+    pub fn reconstruct(&self, shards: &mut [Option<Vec<u8>>]) -> Result<(), FecError> {
+        let total = self.data_shards + self.parity_shards;
+        if shards.len() != total {
+            return Err(FecError::WrongShardCount {
+                expected: total,
+                actual: shards.len(),
+            });
+        }
+        let present: Vec<usize> = (0..total)
+            .filter(|&i| shards[i].is_some())
+            .take(self.data_shards)
+            .collect();
+        if present.len() < self.data_shards {
+            return Err(FecError::NotEnoughShards {
+                needed: self.data_shards,
+                present: present.len(),
+            });
+        }
+        let len = shards[present[0]].as_ref().map_or(0, Vec::len);
+        if shards.iter().flatten().any(|shard| shard.len() != len) {
+            return Err(FecError::ShardSizeMismatch);
+        }
+        if shards[..self.data_shards].iter().all(Option::is_some) {
+            return Ok(());
+        }
+
+        // Rows of the encoding matrix for the shards we have; inverting them
+        // maps those shards back to the data shards
+        let rows = present.iter().map(|&i| self.encoding_row(i)).collect();
+        let decode = invert(rows).ok_or(FecError::SingularMatrix)?;
+
+        let recovered: Vec<(usize, Vec<u8>)> = (0..self.data_shards)
+            .filter(|&j| shards[j].is_none())
+            .map(|j| {
+                let mut out = vec![0u8; len];
+                for (&coef, &i) in decode[j].iter().zip(&present) {
+                    if let Some(shard) = &shards[i] {
+                        mul_add(&mut out, shard, coef);
+                    }
+                }
+                (j, out)
+            })
+            .collect();
+        for (j, shard) in recovered {
+            shards[j] = Some(shard);
+        }
+        Ok(())
+    }
+
+    /// Row of the encoding matrix producing shard `index`
+    fn encoding_row(&self, index: usize) -> Vec<u8> {
+        if index < self.data_shards {
+            (0..self.data_shards)
+                .map(|j| u8::from(j == index))
+                .collect()
+        } else {
+            self.parity_matrix[index - self.data_shards].clone()
+        }
+    }
+}
+
+/// Erasure coding error
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FecError {
+    #[error("Invalid shard counts: {data} data + {parity} parity (max {MAX_SHARDS} shards, at least one data shard)")]
+    InvalidShardCount { data: usize, parity: usize },
+
+    #[error("Expected {expected} shards, got {actual}")]
+    WrongShardCount { expected: usize, actual: usize },
+
+    #[error("Shards have different lengths")]
+    ShardSizeMismatch,
+
+    #[error("Not enough shards to reconstruct: need {needed}, have {present}")]
+    NotEnoughShards { needed: usize, present: usize },
+
+    #[error("Decoding matrix is singular")]
+    SingularMatrix,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "inverse of {a}");
+        }
+        assert_eq!(gf_mul(0, 7), 0);
+    }
+
+    #[test]
+    fn test_reconstruct_from_any_data_shard_count() {
+        let rs = ReedSolomon::new(4, 2).unwrap();
+        let data: Vec<Vec<u8>> = (0..4u8)
+            .map(|i| {
+                (0..16u8)
+                    .map(|b| b.wrapping_mul(31).wrapping_add(i))
+                    .collect()
+            })
+            .collect();
+        let parity = rs.encode(&data).unwrap();
+        let all: Vec<Vec<u8>> = data.iter().chain(&parity).cloned().collect();
+
+        // Every way of losing two of the six shards
+        for lost_a in 0..6 {
+            for lost_b in lost_a + 1..6 {
+                let mut shards: Vec<Option<Vec<u8>>> = all.iter().cloned().map(Some).collect();
+                shards[lost_a] = None;
+                shards[lost_b] = None;
+                rs.reconstruct(&mut shards).unwrap();
+                for (j, shard) in data.iter().enumerate() {
+                    assert_eq!(
+                        shards[j].as_ref(),
+                        Some(shard),
+                        "lost {lost_a} and {lost_b}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_needs_enough_shards() {
+        let rs = ReedSolomon::new(3, 1).unwrap();
+        let data = vec![vec![1u8; 8], vec![2u8; 8], vec![3u8; 8]];
+        let parity = rs.encode(&data).unwrap();
+        let mut shards = vec![Some(data[0].clone()), None, None, Some(parity[0].clone())];
+        assert_eq!(
+            rs.reconstruct(&mut shards),
+            Err(FecError::NotEnoughShards {
+                needed: 3,
+                present: 2
+            })
+        );
+        assert!(ReedSolomon::new(200, 57).is_err());
+        assert!(ReedSolomon::new(0, 2).is_err());
+    }
+}
@@ -1,57 +1,142 @@
 //! FIBRE: Fast Internet Bitcoin Relay Engine
 //!
 //! FIBRE-style fast relay network for ultra-low latency block propagation.
-//! Uses UDP with Forward Error Correction (FEC) for reliable, fast block relay.
+//! Uses Forward Error Correction (FEC) for reliable, fast block relay.
 //!
 //! Design:
-//! - UDP-based transport for minimal latency
-//! - Forward Error Correction (FEC) for packet loss tolerance
-//! - Block chunking for efficient transmission
-//! - Priority routing for blocks over fast channels
+//! - Serialized blocks are split into MTU-sized chunks
+//! - Chunks are grouped and extended with Reed-Solomon parity chunks, so a
+//!   block reconstructs despite lost chunks
+//! - Blocks are relayed to FIBRE peers (NODE_FIBRE) as soon as they are
+//!   validated or reconstructed
 //!
-//! Note: This is a foundational implementation. Full FIBRE compatibility
-//! would require additional UDP infrastructure and FEC library integration.
+//! Note: Chunks are currently sent as `fibrechunk` messages over the regular
+//! peer connections. Full FIBRE compatibility would additionally require a
+//! UDP transport.
 
-use bllvm_protocol::{Block, Hash};
+use crate::network::fec::{ReedSolomon, MAX_SHARDS};
+use crate::network::protocol::FibreChunkMessage;
+use bllvm_protocol::Hash;
 use sha2::Digest;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tracing::{debug, info};
 
+/// Payload bytes per chunk (Ethernet MTU - UDP/IP headers)
+pub const FIBRE_CHUNK_SIZE: usize = 1400;
+
+/// Maximum data chunks per FEC group
+///
+/// Leaves room for as many parity chunks within the code's shard limit.
+pub const MAX_GROUP_DATA_CHUNKS: usize = MAX_SHARDS / 2;
+
+/// Default parity chunks per data chunk in each FEC group
+pub const DEFAULT_PARITY_RATIO: f64 = 0.25;
+
+/// Maximum serialized block size accepted for reconstruction
+pub const MAX_FIBRE_BLOCK_SIZE: usize = 4_000_000;
+
+/// Maximum number of blocks being reconstructed at once
+const MAX_PARTIAL_BLOCKS: usize = 16;
+
+/// Serialized block header size
+const BLOCK_HEADER_SIZE: usize = 80;
+
 /// FIBRE relay manager
 pub struct FibreRelay {
     /// Encoded block cache (block_hash -> encoded_block)
     encoded_blocks: HashMap<Hash, EncodedBlock>,
+    /// Blocks being reconstructed from received chunks
+    partial_blocks: HashMap<Hash, PartialBlock>,
+    /// Recently reconstructed blocks (late chunks for these are ignored)
+    reconstructed_blocks: HashMap<Hash, Instant>,
     /// FIBRE-enabled peers (peer_id -> FIBRE connection info)
     fibre_peers: HashMap<String, FibrePeerInfo>,
     /// Cache expiration time
     cache_ttl: Duration,
+    /// Parity chunks per data chunk in each FEC group
+    parity_ratio: f64,
 }
 
-/// Encoded block with FEC chunks
+/// Block encoded into FEC chunks
 #[derive(Debug, Clone)]
 pub struct EncodedBlock {
     /// Original block hash
     block_hash: Hash,
-    /// Original block data
-    block: Block,
-    /// FEC-encoded chunks (for packet loss tolerance)
-    chunks: Vec<FecChunk>,
-    /// Number of chunks
-    chunk_count: u32,
+    /// Serialized block size
+    block_size: usize,
+    /// Data and parity chunks of every FEC group
+    chunks: Vec<FibreChunkMessage>,
     /// When encoded
     encoded_at: Instant,
 }
 
-/// FEC chunk
-#[derive(Debug, Clone)]
-pub struct FecChunk {
-    /// Chunk index
-    index: u32,
-    /// Chunk data (FEC-encoded)
-    data: Vec<u8>,
-    /// Chunk size
-    size: usize,
+impl EncodedBlock {
+    /// Block hash
+    pub fn block_hash(&self) -> Hash {
+        self.block_hash
+    }
+
+    /// Serialized block size in bytes
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Chunks to send, group by group
+    pub fn chunks(&self) -> &[FibreChunkMessage] {
+        &self.chunks
+    }
+}
+
+/// Reconstruction state of a block
+#[derive(Debug)]
+struct PartialBlock {
+    block_size: usize,
+    groups: Vec<Option<PartialGroup>>,
+    started_at: Instant,
+}
+
+/// Chunks received for one FEC group
+#[derive(Debug)]
+struct PartialGroup {
+    data_chunks: usize,
+    parity_chunks: usize,
+    chunk_size: usize,
+    /// Data chunks followed by parity chunks
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+}
+
+impl PartialBlock {
+    fn is_complete(&self) -> bool {
+        self.groups
+            .iter()
+            .all(|g| g.as_ref().is_some_and(|g| g.received >= g.data_chunks))
+    }
+
+    /// Recover each group's data chunks and reassemble the block
+    fn reconstruct(self) -> Result<Vec<u8>, FibreError> {
+        let mut block = Vec::with_capacity(self.block_size);
+        for group in self.groups.into_iter().flatten() {
+            let rs = ReedSolomon::new(group.data_chunks, group.parity_chunks)
+                .map_err(|e| FibreError::FecError(e.to_string()))?;
+            let mut chunks = group.chunks;
+            rs.reconstruct(&mut chunks)
+                .map_err(|e| FibreError::FecError(e.to_string()))?;
+            for chunk in chunks.into_iter().take(group.data_chunks).flatten() {
+                block.extend_from_slice(&chunk);
+            }
+        }
+        if block.len() < self.block_size {
+            return Err(FibreError::InvalidChunk(format!(
+                "chunks hold {} bytes, block is {} bytes",
+                block.len(),
+                self.block_size
+            )));
+        }
+        block.truncate(self.block_size);
+        Ok(block)
+    }
 }
 
 /// FIBRE peer information
@@ -82,7 +167,7 @@ impl Default for FibreCapabilities {
     fn default() -> Self {
         Self {
             supports_fec: true,
-            max_chunk_size: 1400, // Ethernet MTU - UDP/IP headers
+            max_chunk_size: FIBRE_CHUNK_SIZE,
             min_latency: true,
         }
     }
@@ -94,16 +179,36 @@ impl Default for FibreRelay {
     }
 }
 
+/// Block hash of a serialized block (double SHA256 of its 80-byte header)
+pub fn block_hash(block_data: &[u8]) -> Option<Hash> {
+    let header = block_data.get(..BLOCK_HEADER_SIZE)?;
+    let hash_bytes = sha2::Sha256::digest(sha2::Sha256::digest(header));
+    let mut block_hash = [0u8; 32];
+    block_hash.copy_from_slice(&hash_bytes);
+    Some(block_hash)
+}
+
 impl FibreRelay {
     /// Create a new FIBRE relay manager
     pub fn new() -> Self {
         Self {
             encoded_blocks: HashMap::new(),
+            partial_blocks: HashMap::new(),
+            reconstructed_blocks: HashMap::new(),
             fibre_peers: HashMap::new(),
             cache_ttl: Duration::from_secs(300), // 5 minutes
+            parity_ratio: DEFAULT_PARITY_RATIO,
         }
     }
 
+    /// Set the parity chunks added per data chunk (clamped to 0..=1)
+    ///
+    /// A group survives losing up to this fraction of its data chunks.
+    pub fn with_parity_ratio(mut self, parity_ratio: f64) -> Self {
+        self.parity_ratio = parity_ratio.clamp(0.0, 1.0);
+        self
+    }
+
     /// Register a FIBRE-capable peer
     pub fn register_fibre_peer(&mut self, peer_id: String, udp_addr: Option<std::net::SocketAddr>) {
         let peer_info = FibrePeerInfo {
@@ -117,21 +222,12 @@ impl FibreRelay {
         debug!("Registered FIBRE peer");
     }
 
-    /// Encode block for FIBRE transmission
-    pub fn encode_block(&mut self, block: Block) -> Result<EncodedBlock, FibreError> {
-        // Calculate block hash from header (simplified - full implementation would use proper hash function)
-        // For now, use a hash of the serialized header
-        let header_bytes = bincode::serialize(&block.header)
-            .map_err(|e| FibreError::SerializationError(e.to_string()))?;
-        let mut hasher = sha2::Sha256::new();
-        hasher.update(&header_bytes);
-        let first_hash = hasher.finalize();
-        let mut hasher2 = sha2::Sha256::new();
-        hasher2.update(&first_hash);
-        let hash_bytes = hasher2.finalize();
-        let mut block_hash = [0u8; 32];
-        block_hash.copy_from_slice(&hash_bytes);
-
+    /// Encode a serialized block for FIBRE transmission
+    pub fn encode_block(
+        &mut self,
+        block_hash: Hash,
+        block_data: &[u8],
+    ) -> Result<EncodedBlock, FibreError> {
         // Check cache
         if let Some(encoded) = self.encoded_blocks.get(&block_hash) {
             if encoded.encoded_at.elapsed() < self.cache_ttl {
@@ -139,28 +235,52 @@ impl FibreRelay {
             }
         }
 
-        // Serialize block
-        let block_data = bincode::serialize(&block)
-            .map_err(|e| FibreError::SerializationError(e.to_string()))?;
+        if block_data.len() < BLOCK_HEADER_SIZE || block_data.len() > MAX_FIBRE_BLOCK_SIZE {
+            return Err(FibreError::SerializationError(format!(
+                "invalid block size: {} bytes",
+                block_data.len()
+            )));
+        }
 
-        // Chunk block data (simplified - real FEC would use erasure coding)
-        let chunk_size = 1400; // Standard UDP MTU
-        let chunks: Vec<FecChunk> = block_data
-            .chunks(chunk_size)
-            .enumerate()
-            .map(|(i, chunk_data)| FecChunk {
-                index: i as u32,
-                data: chunk_data.to_vec(),
-                size: chunk_data.len(),
-            })
+        // Zero-pad the last chunk so every chunk in a group has the same size
+        let mut data_chunks: Vec<Vec<u8>> = block_data
+            .chunks(FIBRE_CHUNK_SIZE)
+            .map(|chunk| chunk.to_vec())
             .collect();
+        if let Some(last) = data_chunks.last_mut() {
+            last.resize(FIBRE_CHUNK_SIZE, 0);
+        }
+
+        // Spread the chunks evenly over as few groups as the code allows
+        let group_count = data_chunks.len().div_ceil(MAX_GROUP_DATA_CHUNKS);
+        let mut chunks = Vec::new();
+        for group in 0..group_count {
+            let start = group * data_chunks.len() / group_count;
+            let end = (group + 1) * data_chunks.len() / group_count;
+            let group_data = &data_chunks[start..end];
+            let parity_count = self.parity_chunks_for(group_data.len());
+            let parity = ReedSolomon::new(group_data.len(), parity_count)
+                .and_then(|rs| rs.encode(group_data))
+                .map_err(|e| FibreError::FecError(e.to_string()))?;
+
+            for (index, data) in group_data.iter().chain(&parity).enumerate() {
+                chunks.push(FibreChunkMessage {
+                    block_hash,
+                    block_size: block_data.len() as u32,
+                    group_count: group_count as u16,
+                    group: group as u16,
+                    data_chunks: group_data.len() as u16,
+                    parity_chunks: parity_count as u16,
+                    index: index as u16,
+                    data: data.clone(),
+                });
+            }
+        }
 
-        let chunk_count = chunks.len() as u32;
         let encoded = EncodedBlock {
             block_hash,
-            block,
+            block_size: block_data.len(),
             chunks,
-            chunk_count,
             encoded_at: Instant::now(),
         };
 
@@ -168,14 +288,130 @@ impl FibreRelay {
         self.encoded_blocks.insert(block_hash, encoded.clone());
 
         info!(
-            "Encoded block {} for FIBRE transmission ({} chunks)",
+            "Encoded block {} for FIBRE transmission ({} chunks in {} FEC groups)",
             hex::encode(block_hash),
-            encoded.chunk_count
+            encoded.chunks.len(),
+            group_count
         );
 
         Ok(encoded)
     }
 
+    /// Parity chunks for a group of `data_chunks` data chunks
+    fn parity_chunks_for(&self, data_chunks: usize) -> usize {
+        ((data_chunks as f64 * self.parity_ratio).ceil() as usize).min(MAX_SHARDS - data_chunks)
+    }
+
+    /// Add a received chunk to its block
+    ///
+    /// Returns the serialized block once enough chunks of every FEC group
+    /// have arrived and the reconstructed header matches the block hash.
+    pub fn receive_chunk(
+        &mut self,
+        chunk: FibreChunkMessage,
+    ) -> Result<Option<Vec<u8>>, FibreError> {
+        let hash = chunk.block_hash;
+        if self.reconstructed_blocks.contains_key(&hash) {
+            return Ok(None);
+        }
+        Self::check_chunk(&chunk)?;
+
+        if !self.partial_blocks.contains_key(&hash)
+            && self.partial_blocks.len() >= MAX_PARTIAL_BLOCKS
+        {
+            // Drop the stalest reconstruction to make room
+            if let Some(oldest) = self
+                .partial_blocks
+                .iter()
+                .min_by_key(|(_, partial)| partial.started_at)
+                .map(|(hash, _)| *hash)
+            {
+                self.partial_blocks.remove(&oldest);
+            }
+        }
+
+        let partial = self
+            .partial_blocks
+            .entry(hash)
+            .or_insert_with(|| PartialBlock {
+                block_size: chunk.block_size as usize,
+                groups: (0..chunk.group_count).map(|_| None).collect(),
+                started_at: Instant::now(),
+            });
+        if partial.block_size != chunk.block_size as usize
+            || partial.groups.len() != chunk.group_count as usize
+        {
+            return Err(FibreError::InvalidChunk(
+                "block layout differs from earlier chunks".to_string(),
+            ));
+        }
+
+        let group = partial.groups[chunk.group as usize].get_or_insert_with(|| PartialGroup {
+            data_chunks: chunk.data_chunks as usize,
+            parity_chunks: chunk.parity_chunks as usize,
+            chunk_size: chunk.data.len(),
+            chunks: vec![None; chunk.data_chunks as usize + chunk.parity_chunks as usize],
+            received: 0,
+        });
+        if group.data_chunks != chunk.data_chunks as usize
+            || group.parity_chunks != chunk.parity_chunks as usize
+            || group.chunk_size != chunk.data.len()
+        {
+            return Err(FibreError::InvalidChunk(
+                "group layout differs from earlier chunks".to_string(),
+            ));
+        }
+        let slot = &mut group.chunks[chunk.index as usize];
+        if slot.is_none() {
+            *slot = Some(chunk.data);
+            group.received += 1;
+        }
+
+        if !partial.is_complete() {
+            return Ok(None);
+        }
+        let Some(partial) = self.partial_blocks.remove(&hash) else {
+            return Ok(None);
+        };
+        let block = partial.reconstruct()?;
+        if block_hash(&block) != Some(hash) {
+            return Err(FibreError::InvalidChunk(
+                "reconstructed block does not match its hash".to_string(),
+            ));
+        }
+        self.reconstructed_blocks.insert(hash, Instant::now());
+        debug!(
+            "Reconstructed block {} from FIBRE chunks",
+            hex::encode(hash)
+        );
+        Ok(Some(block))
+    }
+
+    /// Check a chunk's layout fields before buffering it
+    fn check_chunk(chunk: &FibreChunkMessage) -> Result<(), FibreError> {
+        let shards = chunk.data_chunks as usize + chunk.parity_chunks as usize;
+        let reason = if chunk.group_count == 0 || chunk.group >= chunk.group_count {
+            "group out of range"
+        } else if chunk.data_chunks == 0 || shards > MAX_SHARDS {
+            "invalid chunk counts"
+        } else if chunk.index as usize >= shards {
+            "index out of range"
+        } else if chunk.data.is_empty() || chunk.data.len() > FIBRE_CHUNK_SIZE {
+            "invalid chunk size"
+        } else if (chunk.block_size as usize) < BLOCK_HEADER_SIZE
+            || chunk.block_size as usize > MAX_FIBRE_BLOCK_SIZE
+        {
+            "invalid block size"
+        } else if chunk.group_count as usize * MAX_GROUP_DATA_CHUNKS * FIBRE_CHUNK_SIZE
+            < chunk.block_size as usize
+        {
+            "too few groups for block size"
+        } else {
+            return Ok(());
+        };
+        Err(FibreError::InvalidChunk(reason.to_string()))
+    }
+
     /// Get encoded block from cache
     pub fn get_encoded_block(&self, block_hash: &Hash) -> Option<&EncodedBlock> {
         self.encoded_blocks
@@ -200,9 +436,8 @@ impl FibreRelay {
         }
     }
 
-    /// Clean up expired encoded blocks
+    /// Clean up expired encoded blocks and stale reconstructions
     pub fn cleanup_expired(&mut self) {
-        let _now = Instant::now(); // Track cleanup time for potential metrics/logging
         let expired: Vec<Hash> = self
             .encoded_blocks
             .iter()
@@ -217,12 +452,19 @@ impl FibreRelay {
                 hex::encode(hash)
             );
         }
+
+        let ttl = self.cache_ttl;
+        self.partial_blocks
+            .retain(|_, partial| partial.started_at.elapsed() < ttl);
+        self.reconstructed_blocks
+            .retain(|_, reconstructed_at| reconstructed_at.elapsed() < ttl);
     }
 
     /// Get FIBRE statistics
     pub fn get_stats(&self) -> FibreStats {
         FibreStats {
             encoded_blocks: self.encoded_blocks.len(),
+            partial_blocks: self.partial_blocks.len(),
             fibre_peers: self.fibre_peers.len(),
             cache_ttl_secs: self.cache_ttl.as_secs(),
         }
@@ -233,6 +475,7 @@ impl FibreRelay {
 #[derive(Debug, Clone)]
 pub struct FibreStats {
     pub encoded_blocks: usize,
+    pub partial_blocks: usize,
     pub fibre_peers: usize,
     pub cache_ttl_secs: u64,
 }
@@ -246,9 +489,122 @@ pub enum FibreError {
     #[error("FEC encoding error: {0}")]
     FecError(String),
 
+    #[error("Invalid FIBRE chunk: {0}")]
+    InvalidChunk(String),
+
     #[error("UDP transmission error: {0}")]
     UdpError(String),
 
     #[error("Block not found in cache")]
     BlockNotFound,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random serialized block of `size` bytes and its hash
+    fn test_block(size: usize) -> (Hash, Vec<u8>) {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data: Vec<u8> = (0..size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        (block_hash(&data).unwrap(), data)
+    }
+
+    /// Relay `block` through a receiver, dropping `loss` of the chunks
+    ///
+    /// Dropped chunks are spread evenly across the stream, so each FEC group
+    /// loses about the same fraction.
+    fn relay_with_loss(parity_ratio: f64, block: &(Hash, Vec<u8>), loss: f64) -> Option<Vec<u8>> {
+        let mut sender = FibreRelay::new().with_parity_ratio(parity_ratio);
+        let mut receiver = FibreRelay::new();
+        let encoded = sender.encode_block(block.0, &block.1).unwrap();
+
+        let mut dropped = 0.0;
+        let mut result = None;
+        for chunk in encoded.chunks() {
+            dropped += loss;
+            if dropped >= 1.0 {
+                dropped -= 1.0;
+                continue;
+            }
+            if let Some(data) = receiver.receive_chunk(chunk.clone()).unwrap() {
+                result = Some(data);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_block_reconstructs_below_loss_threshold() {
+        // 100 data chunks with 25% parity: 125 chunks, any 100 suffice
+        let block = test_block(100 * FIBRE_CHUNK_SIZE - 321);
+        for loss in [0.0, 0.05, 0.1, 0.15, 0.19] {
+            assert_eq!(
+                relay_with_loss(0.25, &block, loss).as_ref(),
+                Some(&block.1),
+                "{:.0}% loss",
+                loss * 100.0
+            );
+        }
+        for loss in [0.25, 0.4] {
+            assert!(
+                relay_with_loss(0.25, &block, loss).is_none(),
+                "{:.0}% loss should exceed the parity",
+                loss * 100.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_large_block_uses_multiple_fec_groups() {
+        let block = test_block(3 * MAX_GROUP_DATA_CHUNKS * FIBRE_CHUNK_SIZE + 1);
+        let mut relay = FibreRelay::new();
+        let encoded = relay.encode_block(block.0, &block.1).unwrap();
+        let groups = encoded.chunks()[0].group_count as usize;
+        assert_eq!(groups, 4);
+        assert!(encoded
+            .chunks()
+            .iter()
+            .all(|c| c.data_chunks as usize + c.parity_chunks as usize <= MAX_SHARDS));
+
+        assert_eq!(relay_with_loss(0.5, &block, 0.3).as_ref(), Some(&block.1));
+    }
+
+    #[test]
+    fn test_mismatched_block_hash_rejected() {
+        let block = test_block(2 * FIBRE_CHUNK_SIZE);
+        let wrong_hash = [7u8; 32];
+        let mut sender = FibreRelay::new();
+        let mut receiver = FibreRelay::new();
+        let encoded = sender.encode_block(wrong_hash, &block.1).unwrap();
+
+        let mut chunks = encoded.chunks().iter().cloned();
+        assert!(receiver
+            .receive_chunk(chunks.next().unwrap())
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            receiver.receive_chunk(chunks.next().unwrap()),
+            Err(FibreError::InvalidChunk(_))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_chunk_rejected() {
+        let block = test_block(FIBRE_CHUNK_SIZE);
+        let mut relay = FibreRelay::new();
+        let mut chunk = relay.encode_block(block.0, &block.1).unwrap().chunks()[0].clone();
+        chunk.index = chunk.data_chunks + chunk.parity_chunks;
+        assert!(matches!(
+            relay.receive_chunk(chunk),
+            Err(FibreError::InvalidChunk(_))
+        ));
+    }
+}
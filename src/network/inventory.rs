@@ -15,6 +15,17 @@ pub const MSG_BLOCK: u32 = 2;
 pub const MSG_FILTERED_BLOCK: u32 = 3;
 pub const MSG_CMPCT_BLOCK: u32 = 4;
 
+/// Flag marking the witness variant of an inventory type (`MSG_WITNESS_BLOCK`)
+pub const MSG_WITNESS_FLAG: u32 = 1 << 30;
+
+/// Whether `inv_type` asks for a block (full, filtered, compact or witness)
+pub fn is_block_inventory(inv_type: u32) -> bool {
+    matches!(
+        inv_type & !MSG_WITNESS_FLAG,
+        MSG_BLOCK | MSG_FILTERED_BLOCK | MSG_CMPCT_BLOCK
+    )
+}
+
 /// Inventory manager
 pub struct InventoryManager {
     /// Known inventory items
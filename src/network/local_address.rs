@@ -0,0 +1,233 @@
+//! Local address discovery
+//!
+//! Tracks the addresses the node believes it is reachable at, each with a
+//! score. Configured external IPs and UPnP mappings start with a fixed score;
+//! every peer that reports seeing us at an address (the `addr_recv` field of
+//! its `version`) adds one. The best-scored address is the one we advertise.
+
+use crate::network::protocol::NetworkAddress;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+/// Initial score of an address mapped through UPnP
+pub const LOCAL_SCORE_UPNP: u32 = 3;
+
+/// Initial score of a configured external IP
+pub const LOCAL_SCORE_MANUAL: u32 = 4;
+
+/// Networks reported by `getnetworkinfo`
+pub const NETWORKS: [&str; 5] = ["ipv4", "ipv6", "onion", "i2p", "cjdns"];
+
+/// Where a local address was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalAddressSource {
+    /// Configured with `external_ips`
+    Manual,
+    /// External address of a UPnP port mapping
+    Upnp,
+    /// Reported by peers in their `version` message
+    Peer,
+}
+
+impl LocalAddressSource {
+    fn initial_score(self) -> u32 {
+        match self {
+            LocalAddressSource::Manual => LOCAL_SCORE_MANUAL,
+            LocalAddressSource::Upnp => LOCAL_SCORE_UPNP,
+            LocalAddressSource::Peer => 0,
+        }
+    }
+}
+
+/// A scored local address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalAddress {
+    pub addr: SocketAddr,
+    pub score: u32,
+    pub source: LocalAddressSource,
+}
+
+/// Scored list of addresses we believe are our own
+#[derive(Debug, Default)]
+pub struct LocalAddresses {
+    addrs: HashMap<SocketAddr, LocalAddress>,
+}
+
+impl LocalAddresses {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an address from a configured or discovered source
+    ///
+    /// A known address keeps the higher of its score and the source's initial
+    /// score. Returns false for addresses that aren't publicly routable.
+    pub fn add(&mut self, addr: SocketAddr, source: LocalAddressSource) -> bool {
+        let addr = canonical(addr);
+        if !is_routable(&addr.ip()) {
+            return false;
+        }
+        let score = source.initial_score();
+        let entry = self.addrs.entry(addr).or_insert(LocalAddress {
+            addr,
+            score,
+            source,
+        });
+        if score > entry.score {
+            entry.score = score;
+            entry.source = source;
+        }
+        true
+    }
+
+    /// Record that a peer sees us at `addr`; adds one to its score
+    ///
+    /// Returns false for addresses that aren't publicly routable.
+    pub fn seen(&mut self, addr: SocketAddr) -> bool {
+        let addr = canonical(addr);
+        if !is_routable(&addr.ip()) {
+            return false;
+        }
+        self.addrs
+            .entry(addr)
+            .or_insert(LocalAddress {
+                addr,
+                score: 0,
+                source: LocalAddressSource::Peer,
+            })
+            .score += 1;
+        true
+    }
+
+    /// Highest-scored address (ties go to the lowest address)
+    pub fn best(&self) -> Option<SocketAddr> {
+        self.entries().first().map(|entry| entry.addr)
+    }
+
+    /// All addresses, best first
+    pub fn entries(&self) -> Vec<LocalAddress> {
+        let mut entries: Vec<LocalAddress> = self.addrs.values().copied().collect();
+        entries.sort_by(|a, b| b.score.cmp(&a.score).then(a.addr.cmp(&b.addr)));
+        entries
+    }
+
+    /// Number of known addresses
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    /// Check whether no addresses are known
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+}
+
+/// IP address of a wire `NetworkAddress` (IPv4-mapped addresses become IPv4)
+pub fn network_address_ip(addr: &NetworkAddress) -> IpAddr {
+    let ip = Ipv6Addr::from(addr.ip);
+    match ip.to_ipv4_mapped() {
+        Some(v4) => IpAddr::V4(v4),
+        None => IpAddr::V6(ip),
+    }
+}
+
+/// Network name of an IP address, as reported by `getnetworkinfo`
+pub fn network_name(ip: &IpAddr) -> &'static str {
+    match ip {
+        IpAddr::V4(_) => "ipv4",
+        IpAddr::V6(_) => "ipv6",
+    }
+}
+
+/// Check whether other nodes could reach us at `ip`
+///
+/// Excludes unspecified, loopback, private, shared (RFC 6598), link-local,
+/// documentation, multicast and unique-local addresses.
+pub fn is_routable(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            !(v4.is_unspecified()
+                || v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                || (octets[0] == 198 && (octets[1] & 0xfe) == 18))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_routable(&IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            !(v6.is_unspecified()
+                || v6.is_loopback()
+                || v6.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+/// IPv4-mapped IPv6 addresses are stored as IPv4
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(IpAddr::V4(v4), addr.port()),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_reports_raise_score() {
+        let mut local = LocalAddresses::new();
+        let manual: SocketAddr = "1.2.3.4:8333".parse().unwrap();
+        let observed: SocketAddr = "5.6.7.8:8333".parse().unwrap();
+        assert!(local.add(manual, LocalAddressSource::Manual));
+
+        for _ in 0..LOCAL_SCORE_MANUAL {
+            assert!(local.seen(observed));
+        }
+        // Ties go to the lowest address
+        assert_eq!(local.best(), Some(manual));
+        local.seen(observed);
+        assert_eq!(local.best(), Some(observed));
+        assert_eq!(local.entries()[0].score, LOCAL_SCORE_MANUAL + 1);
+        assert_eq!(local.entries()[0].source, LocalAddressSource::Peer);
+    }
+
+    #[test]
+    fn test_unroutable_addresses_ignored() {
+        let mut local = LocalAddresses::new();
+        for addr in [
+            "127.0.0.1:8333",
+            "10.0.0.1:8333",
+            "192.168.1.1:8333",
+            "100.64.0.1:8333",
+            "203.0.113.1:8333",
+            "[::1]:8333",
+            "[fd00::1]:8333",
+            "[fe80::1]:8333",
+            "[::ffff:192.168.1.1]:8333",
+        ] {
+            assert!(!local.seen(addr.parse().unwrap()), "{addr}");
+        }
+        assert!(local.is_empty());
+
+        // IPv4-mapped addresses are stored as IPv4
+        assert!(local.seen("[::ffff:1.2.3.4]:8333".parse().unwrap()));
+        assert_eq!(local.best(), Some("1.2.3.4:8333".parse().unwrap()));
+        assert!(local.seen("[2a00::1]:8333".parse().unwrap()));
+        assert_eq!(local.len(), 2);
+    }
+}
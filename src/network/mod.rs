@@ -6,18 +6,29 @@
 pub mod address_db;
 pub mod ban_list_merging;
 pub mod ban_list_signing;
+pub mod bandwidth;
 pub mod chain_access;
+pub mod connection_manager;
+pub mod discouragement;
 pub mod dns_seeds;
 pub mod dos_protection;
+pub mod eviction;
 pub mod inventory;
+pub mod local_address;
 pub mod message_bridge;
+pub mod netgroup;
 pub mod peer;
+pub mod permissions;
 pub mod protocol;
 pub mod protocol_adapter;
 pub mod protocol_extensions;
 pub mod relay;
+pub mod socks5;
+pub mod subnet;
 pub mod tcp_transport;
 pub mod transport;
+pub mod upnp;
+pub mod version_negotiation;
 
 #[cfg(feature = "quinn")]
 pub mod quinn_transport;
@@ -43,24 +54,30 @@ pub mod bip70_handler;
 // Privacy and Performance Enhancements
 #[cfg(feature = "dandelion")]
 pub mod dandelion; // Dandelion++ privacy-preserving transaction relay
+pub mod fec; // Reed-Solomon erasure coding for FIBRE
 pub mod fibre; // FIBRE-style Fast Relay Network
 pub mod package_relay; // BIP 331 Package Relay
 pub mod package_relay_handler; // BIP 331 handlers
 pub mod txhash; // Non-consensus hashing helpers for relay
 
-use crate::network::protocol::{AddrMessage, NetworkAddress, ProtocolMessage, ProtocolParser};
-use crate::node::mempool::MempoolManager;
+use crate::network::protocol::{
+    AddrMessage, NetworkAddress, ProtocolError, ProtocolMessage, ProtocolParser, VersionMessage,
+};
+use crate::node::mempool::{MempoolAcceptResult, MempoolManager};
+use crate::node::orphan_pool::OrphanPool;
 use crate::storage::Storage;
-use crate::utils::current_timestamp;
+use crate::utils::{current_timestamp, current_timestamp_duration, ShutdownController};
 use anyhow::Result;
 use bllvm_protocol::mempool::Mempool;
-use bllvm_protocol::{BitcoinProtocolEngine, ConsensusProof, UtxoSet};
+use bllvm_protocol::{BitcoinProtocolEngine, ConsensusProof, Hash, UtxoSet};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex, RwLock};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, warn, Instrument};
 
+use crate::network::permissions::NetPermissions;
 use crate::network::tcp_transport::TcpTransport;
 use crate::network::transport::{Transport, TransportAddr, TransportListener, TransportPreference};
 use std::collections::HashSet;
@@ -91,24 +108,116 @@ impl NetworkIO {
 pub struct PeerManager {
     peers: HashMap<TransportAddr, peer::Peer>,
     max_peers: usize,
+    /// Slots kept free for outbound connections (capped at half of `max_peers`)
+    outbound_slots: usize,
+    /// Inbound peers allowed from one network group (see [`eviction::network_group`])
+    max_inbound_per_netgroup: usize,
 }
 
+/// Default number of slots reserved for outbound peers
+pub const DEFAULT_OUTBOUND_SLOTS: usize = 8;
+
+/// Default number of inbound peers allowed from one network group
+pub const DEFAULT_MAX_INBOUND_PER_NETGROUP: usize = 4;
+
 impl PeerManager {
     pub fn new(max_peers: usize) -> Self {
         Self {
             peers: HashMap::new(),
             max_peers,
+            outbound_slots: DEFAULT_OUTBOUND_SLOTS,
+            max_inbound_per_netgroup: DEFAULT_MAX_INBOUND_PER_NETGROUP,
         }
     }
 
+    /// Reserve `slots` connections for outbound peers
+    pub fn with_outbound_slots(mut self, slots: usize) -> Self {
+        self.outbound_slots = slots;
+        self
+    }
+
+    /// Allow at most `limit` inbound peers from one network group
+    pub fn with_max_inbound_per_netgroup(mut self, limit: usize) -> Self {
+        self.max_inbound_per_netgroup = limit;
+        self
+    }
+
+    /// Add a peer; whitelisted peers may exceed the peer limit
+    ///
+    /// Inbound peers are also limited to [`Self::max_inbound`], so they can
+    /// never take the slots reserved for outbound connections, and to
+    /// `max_inbound_per_netgroup` per network group, so one address range
+    /// cannot fill the inbound slots.
     pub fn add_peer(&mut self, addr: TransportAddr, peer: peer::Peer) -> Result<()> {
-        if self.peers.len() >= self.max_peers {
-            return Err(anyhow::anyhow!("Maximum peer limit reached"));
+        if !peer.is_whitelisted() {
+            if self.peers.len() >= self.max_peers {
+                return Err(anyhow::anyhow!("Maximum peer limit reached"));
+            }
+            if peer.is_inbound() && self.inbound_count() >= self.max_inbound() {
+                return Err(anyhow::anyhow!("Maximum inbound peer limit reached"));
+            }
+            if peer.is_inbound() && self.netgroup_full(&addr) {
+                return Err(anyhow::anyhow!(
+                    "Maximum inbound peers from this network group reached"
+                ));
+            }
         }
         self.peers.insert(addr, peer);
         Ok(())
     }
 
+    /// Maximum number of connected peers
+    pub fn max_peers(&self) -> usize {
+        self.max_peers
+    }
+
+    /// Change the peer limit; existing peers above a lowered limit are kept
+    pub fn set_max_peers(&mut self, max_peers: usize) {
+        self.max_peers = max_peers;
+    }
+
+    /// Maximum number of inbound peers (`max_peers` minus reserved outbound slots)
+    pub fn max_inbound(&self) -> usize {
+        self.max_peers - self.outbound_slots.min(self.max_peers / 2)
+    }
+
+    /// Number of connected inbound peers
+    pub fn inbound_count(&self) -> usize {
+        self.peers.values().filter(|peer| peer.is_inbound()).count()
+    }
+
+    /// Number of connected outbound peers
+    pub fn outbound_count(&self) -> usize {
+        self.peers.len() - self.inbound_count()
+    }
+
+    /// Number of connected peers of each connection type
+    ///
+    /// Feelers are not registered as peers, so their count is always zero here.
+    pub fn connection_counts(&self) -> connection_manager::ConnectionCounts {
+        let mut counts = connection_manager::ConnectionCounts::default();
+        for peer in self.peers.values() {
+            counts.record(peer.connection_type());
+        }
+        counts
+    }
+
+    /// Number of inbound peers in the same network group as `addr`
+    pub fn inbound_count_in_netgroup(&self, addr: &TransportAddr) -> usize {
+        let group = eviction::network_group(addr);
+        self.peers
+            .iter()
+            .filter(|(peer_addr, peer)| {
+                peer.is_inbound() && eviction::network_group(peer_addr) == group
+            })
+            .count()
+    }
+
+    /// Whether the network group of `addr` has used up its inbound peers
+    pub fn netgroup_full(&self, addr: &TransportAddr) -> bool {
+        self.inbound_count_in_netgroup(addr) >= self.max_inbound_per_netgroup
+    }
+
     pub fn remove_peer(&mut self, addr: &TransportAddr) -> Option<peer::Peer> {
         self.peers.remove(addr)
     }
@@ -129,6 +238,17 @@ impl PeerManager {
         self.peers.keys().cloned().collect()
     }
 
+    /// Socket addresses of peers that take part in address relay
+    ///
+    /// Block-relay-only connections are left out.
+    pub fn addr_relay_socket_addresses(&self) -> Vec<SocketAddr> {
+        self.peers
+            .values()
+            .filter(|peer| !peer.is_block_relay_only())
+            .map(|peer| peer.address())
+            .collect()
+    }
+
     /// Get peer addresses as SocketAddr (for backward compatibility)
     /// Only returns SocketAddr for TCP/Quinn peers, skips Iroh peers
     pub fn peer_socket_addresses(&self) -> Vec<SocketAddr> {
@@ -150,6 +270,33 @@ impl PeerManager {
         self.peers.len() < self.max_peers
     }
 
+    /// Whether a new inbound peer fits without evicting anyone
+    pub fn can_accept_inbound(&self) -> bool {
+        self.can_accept_peer() && self.inbound_count() < self.max_inbound()
+    }
+
+    /// Evict an inbound peer to make room for a new inbound connection
+    ///
+    /// Returns the evicted peer's address, or `None` if every inbound peer is
+    /// protected (see [`eviction::select_peer_to_evict`]).
+    pub fn evict_inbound_peer(&mut self) -> Option<TransportAddr> {
+        let candidates = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.is_inbound() && !peer.is_whitelisted())
+            .map(|(addr, peer)| eviction::EvictionCandidate {
+                addr: addr.clone(),
+                conntime: peer.conntime(),
+                min_ping_ms: peer.min_ping_ms(),
+                last_block_time: peer.last_block_time(),
+                prefer_evict: peer.is_discouraged(),
+            })
+            .collect();
+        let victim = eviction::select_peer_to_evict(candidates)?;
+        self.peers.remove(&victim);
+        Some(victim)
+    }
+
     /// Select best peers based on quality score
     ///
     /// Returns peers sorted by quality score (highest first)
@@ -302,6 +449,33 @@ impl ConnectionManager {
     }
 }
 
+/// Open an outbound connection, giving up after `timeout`
+///
+/// A timeout is counted in `timeouts` and logged as a connect timeout, as
+/// opposed to a handshake timeout (see [`NetworkManager::check_peer_liveness`]).
+async fn connect_with_timeout<T: Transport>(
+    transport: &T,
+    addr: TransportAddr,
+    timeout: std::time::Duration,
+    timeouts: &AtomicU64,
+) -> Result<T::Connection> {
+    match crate::utils::with_custom_timeout(transport.connect(addr.clone()), timeout).await {
+        Ok(result) => result,
+        Err(_) => {
+            timeouts.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Connect timeout: {:?} did not connect within {}s",
+                addr,
+                timeout.as_secs()
+            );
+            Err(anyhow::anyhow!(
+                "connect timeout after {}s",
+                timeout.as_secs()
+            ))
+        }
+    }
+}
+
 /// Network manager that coordinates all network operations
 ///
 /// Supports multiple transports (TCP, Quinn, Iroh) based on configuration.
@@ -332,8 +506,8 @@ pub struct NetworkManager {
     /// Peer state storage (per-connection state)
     /// Read-heavy: many reads to check peer state, fewer writes when updating state
     peer_states: Arc<RwLock<HashMap<SocketAddr, bllvm_protocol::network::PeerState>>>,
-    /// Persistent peer list (peers to connect to on startup)
-    persistent_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    /// Persistent peers (kept connected) and their reconnection state
+    persistent_peers: Arc<Mutex<HashMap<SocketAddr, connection_manager::PersistentPeerState>>>,
     /// Eclipse attack prevention: track peer diversity
     /// Maps IP address prefixes (first 3 octets) to connection count
     /// Prevents too many connections from same IP range
@@ -344,7 +518,24 @@ pub struct NetworkManager {
     /// Read-heavy: many reads to check if peer is banned, fewer writes when banning/unbanning
     ban_list: Arc<RwLock<HashMap<SocketAddr, u64>>>, // addr -> unban timestamp
     /// Per-IP connection count (to prevent Sybil attacks)
+    /// Keyed by [`dos_protection::connection_key`], so IPv6 counts per /64
     connections_per_ip: Arc<Mutex<HashMap<std::net::IpAddr, usize>>>,
+    /// Additional TCP listening addresses besides the one passed to `start`
+    listen_addrs: Vec<SocketAddr>,
+    /// Listening addresses granting permissions to peers connecting there
+    whitebind: Vec<permissions::PermissionedBind>,
+    /// Addresses the TCP listeners are bound to
+    local_addrs: Vec<SocketAddr>,
+    /// Port passed to `with_config` (advertised if no listener is bound)
+    listen_port: u16,
+    /// Scored addresses we believe we are reachable at
+    local_addresses: Arc<Mutex<local_address::LocalAddresses>>,
+    /// Configured public IPs (`external_ips`)
+    external_ips: Vec<std::net::IpAddr>,
+    /// Map the listening port with UPnP when started
+    upnp_enabled: bool,
+    /// SOCKS5 proxy configuration (reported by `getnetworkinfo`)
+    proxy_config: Option<crate::config::ProxyConfig>,
     /// Per-peer message rate limiting (token bucket)
     peer_message_rates: Arc<Mutex<HashMap<SocketAddr, PeerRateLimiter>>>,
     /// Network statistics
@@ -357,24 +548,170 @@ pub struct NetworkManager {
     /// Pending async requests with metadata
     /// Key: request_id, Value: (sender, peer_addr, timestamp)
     pending_requests: Arc<Mutex<HashMap<u64, PendingRequest>>>,
+    /// Outstanding block requests (`getblockfrompeer` and block download)
+    requested_blocks: Arc<Mutex<HashMap<Hash, BlockRequest>>>,
     /// DoS protection manager
     dos_protection: Arc<dos_protection::DosProtectionManager>,
     /// Pending ban shares (for periodic sharing)
     pending_ban_shares: Arc<Mutex<Vec<(SocketAddr, u64, String)>>>, // (addr, unban_timestamp, reason)
     /// Ban list sharing configuration
     ban_list_sharing_config: Option<crate::config::BanListSharingConfig>,
+    /// Trusted ban list publisher keys (from `ban_list_sharing.trusted_publishers`)
+    ban_list_publishers: Vec<secp256k1::PublicKey>,
+    /// Key signing the ban lists we share (from `ban_list_sharing.signing_key`)
+    ban_list_signing_key: Option<secp256k1::SecretKey>,
+    /// Last time we broadcast our ban list (Unix timestamp)
+    last_ban_list_share: Arc<Mutex<u64>>,
     /// Address database for peer discovery
     /// Read-heavy: many reads to query addresses, fewer writes when adding addresses
     address_database: Arc<RwLock<address_db::AddressDatabase>>,
+    /// Where the address database is persisted (normally `<datadir>/peers.dat`)
+    peers_path: Option<std::path::PathBuf>,
+    /// Addresses discouraged for misbehavior (local only, never gossiped)
+    discouraged: Arc<RwLock<discouragement::DiscouragementList>>,
+    /// Where the discouragement list is persisted (normally `<datadir>/discouraged.dat`)
+    discouraged_path: Option<std::path::PathBuf>,
+    /// Shutdown broadcast observed by the listener and background tasks
+    shutdown: ShutdownController,
     /// Last time we sent addr message (Unix timestamp)
     last_addr_sent: Arc<Mutex<u64>>,
+    /// Rotates the peers chosen for each addr gossip round
+    addr_relay_cursor: Arc<Mutex<usize>>,
     /// Enable self-advertisement (send own address to peers)
     enable_self_advertisement: bool,
+    /// User agent sent in our version message
+    user_agent: String,
+    /// Serve BIP157 block filters to peers (`peer_block_filters` with the
+    /// filter index enabled)
+    serve_block_filters: bool,
+    /// Answer `mempool` requests from peers without the `mempool` permission
+    accept_mempool_requests: bool,
+    /// Ask outbound peers for their mempool after the handshake
+    request_mempool_on_connect: bool,
+    /// Last time we advertised our best local address (Unix timestamp)
+    last_self_advertisement: Arc<Mutex<u64>>,
+    /// Minimum protocol version accepted from peers
+    min_peer_protocol_version: i32,
     /// Request timeout configuration
     request_timeout_config: Arc<crate::config::RequestTimeoutConfig>,
+    /// Network timing configuration (ping interval and peer liveness timeouts)
+    network_timing_config: Arc<crate::config::NetworkTimingConfig>,
+    /// Outbound connections that timed out before the transport connected
+    connect_timeouts: Arc<AtomicU64>,
+    /// Peers disconnected for not completing the version handshake in time
+    handshake_timeouts: AtomicU64,
     /// Peer reconnection queue (exponential backoff)
     /// Maps SocketAddr to (attempts, last_attempt_timestamp, quality_score)
     peer_reconnection_queue: Arc<Mutex<HashMap<SocketAddr, (u32, u64, f64)>>>,
+    /// Outbound connection scheduling (target count, feelers, anchors)
+    connection_manager: connection_manager::ConnectionManager,
+    /// Addresses of peers we connected to (outbound)
+    outbound_peers: Arc<Mutex<HashSet<SocketAddr>>>,
+    /// DNS seeder (set up by `discover_peers_from_dns`, reused to reseed)
+    dns_seeder: Arc<Mutex<Option<Arc<dns_seeds::DnsSeeder>>>>,
+    /// Per-peer and node-wide bandwidth limits
+    bandwidth_limits: bandwidth::BandwidthLimits,
+    /// FIBRE fast block relay (FEC encoding and block reconstruction)
+    fibre: Arc<Mutex<fibre::FibreRelay>>,
+    /// Transactions with unknown inputs (also used for compact block reconstruction)
+    orphan_pool: Arc<Mutex<OrphanPool>>,
+    /// Compact blocks waiting for `blocktxn` (block hash -> (peer, partial block))
+    partial_compact_blocks:
+        Arc<Mutex<HashMap<Hash, (SocketAddr, compact_blocks::PartiallyDownloadedBlock)>>>,
+    /// Peers that sent blocks handed to the node (blamed if the block is invalid)
+    block_sources: Arc<Mutex<HashMap<Hash, SocketAddr>>>,
+    /// Dandelion++ stem routing (None unless `relay.enable_dandelion` is set)
+    #[cfg(feature = "dandelion")]
+    dandelion: Option<Arc<Mutex<DandelionRouting>>>,
+}
+
+/// Maximum compact blocks waiting for `blocktxn` at once
+const MAX_PARTIAL_COMPACT_BLOCKS: usize = 16;
+
+/// Maximum blocks awaiting validation whose source peer is remembered
+const MAX_BLOCK_SOURCES: usize = 64;
+
+/// Most transactions announced in answer to a `mempool` request (the `inv` size limit)
+const MAX_MEMPOOL_INV_ENTRIES: usize = 50_000;
+
+/// Minimum time between answered `mempool` requests from one peer
+const MEMPOOL_REQUEST_INTERVAL_SECONDS: u64 = 60;
+
+/// Interval between advertisements of our best local address
+const SELF_ADVERTISEMENT_INTERVAL_SECONDS: u64 = 24 * 60 * 60;
+
+/// Services advertised with our own address
+const SELF_ADVERTISEMENT_SERVICES: u64 = protocol::NODE_NETWORK | protocol::NODE_WITNESS;
+
+/// User agent sent in our version message unless configured
+pub const DEFAULT_USER_AGENT: &str = concat!("/reference-node:", env!("CARGO_PKG_VERSION"), "/");
+
+/// Peers sent known addresses in each gossip round (Bitcoin Core relays each address to 2)
+const ADDR_RELAY_FANOUT: usize = 2;
+
+/// Address-message form of a socket address (IPv4 as IPv4-mapped IPv6)
+fn to_network_address(addr: SocketAddr, services: u64) -> NetworkAddress {
+    let ip = match addr.ip() {
+        std::net::IpAddr::V4(ipv4) => ipv4.to_ipv6_mapped().octets(),
+        std::net::IpAddr::V6(ipv6) => ipv6.octets(),
+    };
+    NetworkAddress {
+        services,
+        ip,
+        port: addr.port(),
+    }
+}
+
+/// Map `port` on the UPnP gateway to this host, returning the external IP
+async fn map_port_with_upnp(port: u16) -> Result<std::net::IpAddr> {
+    let gateway = upnp::Gateway::discover(upnp::UPNP_DISCOVERY_TIMEOUT).await?;
+    let local_ip = gateway.local_ip().await?;
+    gateway
+        .add_port_mapping(
+            port,
+            SocketAddr::new(local_ip, port),
+            upnp::UPNP_LEASE_SECONDS,
+        )
+        .await?;
+    gateway.external_ip().await
+}
+
+/// Length of a Dandelion++ epoch; stem routes are re-drawn each epoch
+#[cfg(feature = "dandelion")]
+const DANDELION_EPOCH_SECONDS: u64 = 600;
+
+/// Dandelion++ routing state
+#[cfg(feature = "dandelion")]
+struct DandelionRouting {
+    /// Stem routing table and stem-phase transactions
+    relay: dandelion::DandelionRelay,
+    /// Fee rates of stem-phase transactions (for feefilter when they fluff)
+    stem_fee_rates: HashMap<Hash, u64>,
+    /// Start of the current epoch (Unix timestamp)
+    epoch_start: u64,
+}
+
+#[cfg(feature = "dandelion")]
+impl DandelionRouting {
+    fn from_config(config: &crate::config::DandelionConfig) -> Self {
+        Self {
+            relay: dandelion::DandelionRelay::with_params(
+                std::time::Duration::from_secs(config.stem_timeout_seconds),
+                config.fluff_probability,
+                config.max_stem_hops,
+            ),
+            stem_fee_rates: HashMap::new(),
+            epoch_start: current_timestamp(),
+        }
+    }
+
+    /// Rotate stem routes if the current epoch has ended
+    fn rotate_epoch_if_due(&mut self, now: u64) {
+        if now.saturating_sub(self.epoch_start) >= DANDELION_EPOCH_SECONDS {
+            self.relay.rotate_epoch();
+            self.epoch_start = now;
+        }
+    }
 }
 
 /// Pending request metadata
@@ -391,6 +728,21 @@ struct PendingRequest {
     retry_count: u8,
 }
 
+/// Maximum times a stalled block download is moved to another peer
+const MAX_BLOCK_REQUEST_RETRIES: u8 = 3;
+
+/// An outstanding `getdata` for a block
+#[derive(Debug, Clone, Copy)]
+struct BlockRequest {
+    /// Peer the block was requested from
+    peer_addr: SocketAddr,
+    /// Pending request answered with the serialized block
+    request_id: u64,
+    /// Block download rather than `getblockfrompeer`: moved to another peer
+    /// if this one stalls, and passed on to block processing when it arrives
+    download: bool,
+}
+
 /// Network message types
 #[derive(Debug, Clone)]
 pub enum NetworkMessage {
@@ -428,7 +780,7 @@ impl NetworkManager {
 
     /// Create a new network manager with configuration
     pub fn with_config(
-        _listen_addr: SocketAddr,
+        listen_addr: SocketAddr,
         max_peers: usize,
         preference: TransportPreference,
         config: Option<&crate::config::NodeConfig>,
@@ -441,14 +793,17 @@ impl NetworkManager {
             .and_then(|c| c.dos_protection.as_ref())
             .unwrap_or(&dos_config_default);
 
-        let dos_protection = Arc::new(dos_protection::DosProtectionManager::with_ban_settings(
-            dos_config.max_connections_per_window,
-            dos_config.window_seconds,
-            dos_config.max_message_queue_size,
-            dos_config.max_active_connections,
-            dos_config.auto_ban_threshold,
-            dos_config.ban_duration_seconds,
-        ));
+        let dos_protection = Arc::new(
+            dos_protection::DosProtectionManager::with_ban_settings(
+                dos_config.max_connections_per_window,
+                dos_config.window_seconds,
+                dos_config.max_message_queue_size,
+                dos_config.max_active_connections,
+                dos_config.auto_ban_threshold,
+                dos_config.ban_duration_seconds,
+            )
+            .with_whitelist(config.map(|c| c.whitelist.clone()).unwrap_or_default()),
+        );
 
         // Use config for address database
         let addr_db_config_default = crate::config::AddressDatabaseConfig::default();
@@ -468,10 +823,82 @@ impl NetworkManager {
             .unwrap_or(&timeout_config_default);
         let request_timeout_config = Arc::new(timeout_config.clone());
 
+        // Outbound connection scheduling
+        let timing_config_default = crate::config::NetworkTimingConfig::default();
+        let timing_config = config
+            .and_then(|c| c.network_timing.as_ref())
+            .unwrap_or(&timing_config_default);
+        let connection_manager =
+            connection_manager::ConnectionManager::new(timing_config, current_timestamp());
+        let network_timing_config = Arc::new(timing_config.clone());
+
+        let ban_list_publishers = config
+            .and_then(|c| c.ban_list_sharing.as_ref())
+            .map(|sharing| {
+                sharing
+                    .trusted_publishers
+                    .iter()
+                    .filter_map(|key| {
+                        let parsed = hex::decode(key)
+                            .ok()
+                            .and_then(|bytes| secp256k1::PublicKey::from_slice(&bytes).ok());
+                        if parsed.is_none() {
+                            warn!("Ignoring invalid ban list publisher key: {}", key);
+                        }
+                        parsed
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let ban_list_signing_key = config
+            .and_then(|c| c.ban_list_sharing.as_ref())
+            .and_then(|sharing| sharing.signing_key.as_ref())
+            .and_then(|key| {
+                let parsed = hex::decode(key)
+                    .ok()
+                    .and_then(|bytes| secp256k1::SecretKey::from_slice(&bytes).ok());
+                if parsed.is_none() {
+                    warn!("Invalid ban list signing key, shared ban lists will be unsigned");
+                }
+                parsed
+            });
+
+        // Bandwidth throttling (unlimited unless configured)
+        let bandwidth_limits = config
+            .and_then(|c| c.bandwidth.as_ref())
+            .map(bandwidth::BandwidthLimits::from_config)
+            .unwrap_or_default();
+
+        // Dandelion++ stem routing (off unless enabled in the relay config)
+        #[cfg(feature = "dandelion")]
+        let dandelion = config
+            .filter(|c| c.relay.as_ref().is_some_and(|r| r.enable_dandelion))
+            .map(|c| {
+                let dandelion_config = c.dandelion.clone().unwrap_or_default();
+                info!("Dandelion++ transaction relay enabled");
+                Arc::new(Mutex::new(DandelionRouting::from_config(&dandelion_config)))
+            });
+
+        // Route outbound TCP through a SOCKS5 proxy (e.g. Tor) if configured
+        let tcp_transport = match config.and_then(|c| c.proxy.as_ref()) {
+            Some(proxy_config) => {
+                info!(
+                    "Routing outbound TCP connections through SOCKS5 proxy {}",
+                    proxy_config.addr
+                );
+                TcpTransport::with_proxy(socks5::Socks5Proxy::from_config(proxy_config))
+            }
+            None => TcpTransport::new(),
+        };
+
         Self {
-            peer_manager: Arc::new(Mutex::new(PeerManager::new(max_peers))),
+            peer_manager: Arc::new(Mutex::new(
+                PeerManager::new(max_peers)
+                    .with_outbound_slots(timing_config.target_peer_count)
+                    .with_max_inbound_per_netgroup(dos_config.max_inbound_per_netgroup),
+            )),
             peer_diversity: Arc::new(Mutex::new(HashMap::new())),
-            tcp_transport: TcpTransport::new(),
+            tcp_transport,
             #[cfg(feature = "quinn")]
             quinn_transport: None,
             #[cfg(feature = "iroh")]
@@ -487,25 +914,107 @@ impl NetworkManager {
             storage: None,
             mempool_manager: None,
             peer_states: Arc::new(RwLock::new(HashMap::new())),
-            persistent_peers: Arc::new(Mutex::new(HashSet::new())),
+            persistent_peers: Arc::new(Mutex::new(HashMap::new())),
             network_active: Arc::new(Mutex::new(true)),
             ban_list: Arc::new(RwLock::new(HashMap::new())),
             connections_per_ip: Arc::new(Mutex::new(HashMap::new())),
+            listen_addrs: config.map(|c| c.listen_addrs.clone()).unwrap_or_default(),
+            whitebind: config.map(|c| c.whitebind.clone()).unwrap_or_default(),
+            local_addrs: Vec::new(),
+            listen_port: listen_addr.port(),
+            local_addresses: Arc::new(Mutex::new(local_address::LocalAddresses::new())),
+            external_ips: config.map(|c| c.external_ips.clone()).unwrap_or_default(),
+            upnp_enabled: config.is_some_and(|c| c.upnp),
+            proxy_config: config.and_then(|c| c.proxy.clone()),
             peer_message_rates: Arc::new(Mutex::new(HashMap::new())),
             bytes_sent: Arc::new(Mutex::new(0)),
             bytes_received: Arc::new(Mutex::new(0)),
             socket_to_transport: Arc::new(Mutex::new(HashMap::new())),
             request_id_counter: Arc::new(Mutex::new(0)),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
+            requested_blocks: Arc::new(Mutex::new(HashMap::new())),
             dos_protection,
             pending_ban_shares: Arc::new(Mutex::new(Vec::new())),
             ban_list_sharing_config: config.and_then(|c| c.ban_list_sharing.clone()),
+            ban_list_publishers,
+            ban_list_signing_key,
+            last_ban_list_share: Arc::new(Mutex::new(current_timestamp())),
             address_database,
+            peers_path: None,
+            discouraged: Arc::new(RwLock::new(discouragement::DiscouragementList::new())),
+            discouraged_path: None,
+            shutdown: ShutdownController::new(),
             last_addr_sent: Arc::new(Mutex::new(0)),
+            addr_relay_cursor: Arc::new(Mutex::new(0)),
             enable_self_advertisement: config.map(|c| c.enable_self_advertisement).unwrap_or(true),
+            user_agent: config
+                .and_then(|c| c.user_agent.clone())
+                .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string()),
+            serve_block_filters: config.is_some_and(|c| {
+                c.peer_block_filters && c.storage.as_ref().is_some_and(|s| s.blockfilterindex)
+            }),
+            last_self_advertisement: Arc::new(Mutex::new(0)),
+            accept_mempool_requests: config
+                .and_then(|c| c.relay.as_ref())
+                .is_some_and(|r| r.accept_mempool_requests),
+            request_mempool_on_connect: config
+                .and_then(|c| c.relay.as_ref())
+                .is_some_and(|r| r.request_mempool_on_connect),
+            min_peer_protocol_version: config
+                .map(|c| c.min_peer_protocol_version)
+                .unwrap_or(version_negotiation::DEFAULT_MIN_PEER_PROTOCOL_VERSION),
             request_timeout_config,
+            network_timing_config,
+            connect_timeouts: Arc::new(AtomicU64::new(0)),
+            handshake_timeouts: AtomicU64::new(0),
             peer_reconnection_queue: Arc::new(Mutex::new(HashMap::new())),
+            connection_manager,
+            outbound_peers: Arc::new(Mutex::new(HashSet::new())),
+            dns_seeder: Arc::new(Mutex::new(None)),
+            bandwidth_limits,
+            fibre: Arc::new(Mutex::new(fibre::FibreRelay::new())),
+            orphan_pool: Arc::new(Mutex::new(OrphanPool::new())),
+            partial_compact_blocks: Arc::new(Mutex::new(HashMap::new())),
+            block_sources: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "dandelion")]
+            dandelion,
+        }
+    }
+
+    /// Persist anchor peers to the given file (normally `<datadir>/anchors.dat`)
+    pub fn with_anchors_path(mut self, path: std::path::PathBuf) -> Self {
+        self.connection_manager.set_anchors_path(path);
+        self
+    }
+
+    /// Load and persist the address database at the given file (normally
+    /// `<datadir>/peers.dat`)
+    pub fn with_peers_path(mut self, path: std::path::PathBuf) -> Self {
+        if let Ok(mut db) = self.address_database.try_write() {
+            match db.load(&path) {
+                Ok(count) if count > 0 => info!("Loaded {} addresses from {:?}", count, path),
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load {:?}: {}", path, e),
+            }
+        }
+        self.peers_path = Some(path);
+        self
+    }
+
+    /// Load and persist the discouragement list at the given file (normally
+    /// `<datadir>/discouraged.dat`)
+    pub fn with_discouraged_path(mut self, path: std::path::PathBuf) -> Self {
+        if let Ok(mut list) = self.discouraged.try_write() {
+            match list.load(&path, current_timestamp()) {
+                Ok(count) if count > 0 => {
+                    info!("Loaded {} discouraged addresses from {:?}", count, path)
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load {:?}: {}", path, e),
+            }
         }
+        self.discouraged_path = Some(path);
+        self
     }
 
     /// Set dependencies for protocol message processing
@@ -521,6 +1030,11 @@ impl NetworkManager {
         self
     }
 
+    /// Mempool used for transaction relay, if dependencies are set
+    pub fn mempool_manager(&self) -> Option<&Arc<MempoolManager>> {
+        self.mempool_manager.as_ref()
+    }
+
     /// Create a new network manager with transport preference
     pub fn with_transport_preference(
         listen_addr: SocketAddr,
@@ -535,62 +1049,209 @@ impl NetworkManager {
         self.transport_preference
     }
 
+    /// Listen on additional TCP addresses, e.g. an IPv6 socket next to IPv4
+    pub fn with_listen_addrs(mut self, addrs: Vec<SocketAddr>) -> Self {
+        self.listen_addrs = addrs;
+        self
+    }
+
+    /// Listen on `whitebind` addresses, granting their permissions to peers
+    /// connecting there
+    pub fn with_whitebind(mut self, whitebind: Vec<permissions::PermissionedBind>) -> Self {
+        self.whitebind = whitebind;
+        self
+    }
+
+    /// Addresses the TCP listeners are bound to (empty until started)
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Port we advertise to peers (the first bound listener's)
+    fn advertised_port(&self) -> u16 {
+        self.local_addrs
+            .first()
+            .map(|addr| addr.port())
+            .unwrap_or(self.listen_port)
+    }
+
+    /// Addresses we believe we are reachable at, best first
+    pub async fn local_addresses(&self) -> Vec<local_address::LocalAddress> {
+        self.local_addresses.lock().await.entries()
+    }
+
+    /// SOCKS5 proxy used for outbound connections, if configured
+    pub fn proxy_config(&self) -> Option<&crate::config::ProxyConfig> {
+        self.proxy_config.as_ref()
+    }
+
+    /// Check whether we can make connections to a network
+    ///
+    /// IPv4 and IPv6 are always reachable; onion addresses only through a
+    /// proxy. I2P and CJDNS are not supported.
+    pub fn is_network_reachable(&self, network: &str) -> bool {
+        match network {
+            "ipv4" | "ipv6" => true,
+            "onion" => self.proxy_config.is_some(),
+            _ => false,
+        }
+    }
+
     /// Discover peers from DNS seeds and add to address database
+    ///
+    /// Seeds are only queried when the address database is empty. Uses
+    /// `dns_seeds` from the config, falling back to the built-in seeds for
+    /// the network.
+    ///
+    /// Skipped entirely when outbound connections go through a proxy: seed
+    /// lookups use the system resolver, which would reveal the node outside
+    /// the proxy. Peers then come from the address database, `connect` and
+    /// `addnode`.
     pub async fn discover_peers_from_dns(
         &self,
         network: &str,
         port: u16,
         config: &crate::config::NodeConfig,
     ) -> Result<()> {
-        use crate::network::dns_seeds;
-
-        let seeds = match network {
-            "mainnet" => dns_seeds::MAINNET_DNS_SEEDS,
-            "testnet" => dns_seeds::TESTNET_DNS_SEEDS,
-            _ => {
-                warn!("Unknown network: {}, skipping DNS seed discovery", network);
-                return Ok(());
-            }
+        if self.tcp_transport.proxy().is_some() {
+            info!("Proxy configured, skipping DNS seed discovery");
+            return Ok(());
+        }
+        let seeds: Vec<String> = if config.dns_seeds.is_empty() {
+            dns_seeds::default_seeds(network)
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        } else {
+            config.dns_seeds.clone()
         };
+        if seeds.is_empty() {
+            warn!(
+                "No DNS seeds for network: {}, skipping DNS seed discovery",
+                network
+            );
+            return Ok(());
+        }
 
-        info!("Discovering peers from DNS seeds for {}", network);
-        // Get max addresses from config
+        // Get max addresses and lookup timeout from config
         let timing_config_default = crate::config::NetworkTimingConfig::default();
         let timing_config = config
             .network_timing
             .as_ref()
             .unwrap_or(&timing_config_default);
-        let max_addresses = timing_config.max_addresses_from_dns;
-        let addresses = dns_seeds::resolve_dns_seeds(seeds, port, max_addresses).await;
-        let address_count = addresses.len();
+        let seeder = Arc::new(
+            dns_seeds::DnsSeeder::new(seeds, port, timing_config.max_addresses_from_dns)
+                .with_timeout(std::time::Duration::from_secs(
+                    timing_config.dns_seed_timeout_seconds,
+                )),
+        );
+        *self.dns_seeder.lock().await = Some(Arc::clone(&seeder));
 
-        // Add discovered addresses to database
-        {
-            let mut db = self.address_database.write().await;
-            for addr in addresses {
-                db.add_address(addr, 0); // Services will be updated on connection
-            }
+        if !self.address_database.read().await.is_empty() {
+            debug!("Address database already populated, skipping DNS seed discovery");
+            return Ok(());
         }
 
+        info!("Discovering peers from DNS seeds for {}", network);
+        let address_count = self.add_seed_addresses(&seeder, false).await;
         info!("Discovered {} addresses from DNS seeds", address_count);
         Ok(())
     }
 
+    /// Resolve seed addresses and add them to the address database
+    async fn add_seed_addresses(&self, seeder: &dns_seeds::DnsSeeder, refresh: bool) -> usize {
+        let addresses = seeder.resolve(refresh).await;
+        let address_count = addresses.len();
+        let mut db = self.address_database.write().await;
+        for addr in addresses {
+            let services = addr.services;
+            db.add_address(addr, services);
+        }
+        address_count
+    }
+
     /// Connect to persistent peers from config
+    ///
+    /// Peers that fail to connect are retried by [`Self::maintain_persistent_peers`].
     pub async fn connect_persistent_peers(&self, persistent_peers: &[SocketAddr]) -> Result<()> {
-        for peer_addr in persistent_peers {
-            // Add to persistent peer set
-            self.add_persistent_peer(*peer_addr);
+        {
+            let now = current_timestamp();
+            let mut peers = self.persistent_peers.lock().await;
+            for peer_addr in persistent_peers {
+                peers
+                    .entry(*peer_addr)
+                    .or_insert_with(|| connection_manager::PersistentPeerState::new(now));
+            }
+        }
+        self.maintain_persistent_peers().await
+    }
+
+    /// Keep persistent peers connected
+    ///
+    /// Connects every persistent peer whose next attempt is due. Failed
+    /// attempts and disconnects are retried with exponential backoff starting
+    /// at `peer_connection_delay_seconds`. Banned peers are not retried.
+    /// Intended to be called periodically.
+    pub async fn maintain_persistent_peers(&self) -> Result<()> {
+        let now = current_timestamp();
+        if !*self.network_active.lock().await {
+            return Ok(());
+        }
+        let base_delay = self.network_timing_config.peer_connection_delay_seconds;
+        let connected = self.peer_manager.lock().await.peer_socket_addresses();
+        let ban_list = self.ban_list.read().await.clone();
+
+        let due: Vec<SocketAddr> = {
+            let mut peers = self.persistent_peers.lock().await;
+            for (addr, state) in peers.iter_mut() {
+                if connected.contains(addr) {
+                    state.mark_connected();
+                } else if state.connected {
+                    // Dropped without a disconnect notification
+                    state.schedule_retry(now, base_delay);
+                }
+            }
+            peers
+                .iter()
+                .filter(|(addr, state)| {
+                    state.is_due(now) && !connection_manager::is_banned(addr, &ban_list, now)
+                })
+                .map(|(addr, _)| *addr)
+                .collect()
+        };
 
-            // Try to connect
-            info!("Connecting to persistent peer: {}", peer_addr);
-            if let Err(e) = self.connect_to_peer(*peer_addr).await {
-                warn!("Failed to connect to persistent peer {}: {}", peer_addr, e);
+        for addr in due {
+            info!("Connecting to persistent peer: {}", addr);
+            let result = self.connect_to_peer(addr).await;
+            let mut peers = self.persistent_peers.lock().await;
+            // Skip peers removed while connecting
+            let Some(state) = peers.get_mut(&addr) else {
+                continue;
+            };
+            match result {
+                Ok(()) => state.mark_connected(),
+                Err(e) => {
+                    state.schedule_retry(current_timestamp(), base_delay);
+                    warn!(
+                        "Failed to connect to persistent peer {}: {} (retrying in {}s)",
+                        addr,
+                        e,
+                        state.next_attempt.saturating_sub(now)
+                    );
+                }
             }
         }
         Ok(())
     }
 
+    /// Reconnection state of a persistent peer
+    pub async fn persistent_peer_state(
+        &self,
+        addr: SocketAddr,
+    ) -> Option<connection_manager::PersistentPeerState> {
+        self.persistent_peers.lock().await.get(&addr).cloned()
+    }
+
     /// Discover Iroh peers and add to address database
     ///
     /// Iroh peers are discovered through:
@@ -673,11 +1334,12 @@ impl NetworkManager {
                     // Create peer from Iroh connection
                     // Iroh uses placeholder SocketAddr for peer identification
                     let placeholder_socket = SocketAddr::from(([0, 0, 0, 0], 0));
-                    let peer = Peer::from_transport_connection(
+                    let peer = Peer::from_transport_connection_with_bandwidth(
                         conn,
                         placeholder_socket,
                         transport_addr.clone(),
                         self.peer_tx.clone(),
+                        self.bandwidth_limits.for_peer(),
                     );
 
                     // Add peer to manager
@@ -738,7 +1400,7 @@ impl NetworkManager {
 
         let addresses: Vec<_> = {
             let db = self.address_database.read().await;
-            let fresh = db.get_fresh_addresses(needed * 3); // Get 3x needed for retries
+            let fresh = db.select_addresses(needed * 3); // Get 3x needed for retries
             db.filter_addresses(fresh, &ban_list, &connected_peers)
         };
 
@@ -783,51 +1445,339 @@ impl NetworkManager {
         Ok(connected)
     }
 
-    /// Initialize peer connections after startup
-    ///
-    /// This is automatically called by `start()` to:
-    /// 1. Discover peers from DNS seeds (for TCP/Quinn transports)
-    /// 2. Connect to persistent peers from config
-    /// 3. Discover Iroh peers (if Iroh is enabled) - uses Iroh's DERP servers and gossip
-    /// 4. Connect to peers from address database to reach target count
+    /// Connect to anchor peers saved by the previous run
     ///
-    /// Note: The address database now supports both SocketAddr-based addresses (TCP/Quinn)
-    /// and Iroh NodeIds. Iroh peers are discovered through:
-    /// - DERP servers (handled by Iroh's MagicEndpoint)
-    /// - Gossip discovery (when available)
-    /// - Incoming connections (automatically stored)
-    /// - Persistent peers from config
-    pub async fn initialize_peer_connections(
-        &self,
-        config: &crate::config::NodeConfig,
-        network: &str,
-        port: u16,
-        target_peer_count: usize,
-    ) -> Result<()> {
-        // 1. Discover peers from DNS seeds (only for TCP/Quinn, not Iroh)
-        let should_discover_dns = self.transport_preference.allows_tcp() || {
-            #[cfg(feature = "quinn")]
-            {
-                self.transport_preference.allows_quinn()
-            }
-            #[cfg(not(feature = "quinn"))]
-            {
-                false
-            }
+    /// Returns the number of anchors successfully connected.
+    pub async fn connect_anchors(&self) -> usize {
+        let Some(path) = self.connection_manager.anchors_path() else {
+            return 0;
         };
-        if should_discover_dns {
-            if let Err(e) = self.discover_peers_from_dns(network, port, config).await {
-                warn!("DNS seed discovery failed: {}", e);
+        let mut connected = 0;
+        for anchor in connection_manager::load_anchors(path) {
+            match self.connect_to_peer(anchor).await {
+                Ok(()) => connected += 1,
+                Err(e) => debug!("Failed to reconnect to anchor {}: {}", anchor, e),
             }
         }
+        connected
+    }
 
-        // 2. Connect to persistent peers
-        if !config.persistent_peers.is_empty() {
-            if let Err(e) = self
-                .connect_persistent_peers(&config.persistent_peers)
-                .await
-            {
-                warn!("Failed to connect to some persistent peers: {}", e);
+    /// Outbound peers that are still connected, with their connection times
+    ///
+    /// Also prunes disconnected peers from the outbound set.
+    async fn live_outbound_peers(&self) -> Vec<(SocketAddr, u64)> {
+        let pm = self.peer_manager.lock().await;
+        let mut outbound = self.outbound_peers.lock().await;
+        let mut live = Vec::new();
+        outbound.retain(|addr| {
+            let peer = pm
+                .find_transport_addr_by_socket(*addr)
+                .and_then(|transport_addr| pm.get_peer(&transport_addr));
+            match peer {
+                Some(peer) => {
+                    live.push((*addr, peer.conntime()));
+                    true
+                }
+                None => false,
+            }
+        });
+        live
+    }
+
+    /// Fresh, non-local addresses from the address database, tried ones first
+    async fn address_candidates(&self, count: usize) -> Vec<(SocketAddr, NetworkAddress)> {
+        let db = self.address_database.read().await;
+        db.select_addresses(count)
+            .into_iter()
+            .filter(|addr| !db.is_local(addr))
+            .map(|addr| (db.network_addr_to_socket(&addr), addr))
+            .collect()
+    }
+
+    /// Run one round of outbound connection management
+    ///
+    /// Tops up full-relay and block-relay-only outbound connections to their
+    /// target counts, makes a feeler
+    /// connection when one is due and refreshes `anchors.dat`. Intended to be
+    /// called periodically; rounds that are not yet due are skipped.
+    pub async fn maintain_outbound_connections(&self) -> Result<()> {
+        let now = current_timestamp();
+        if !*self.network_active.lock().await {
+            return Ok(());
+        }
+
+        if self.connection_manager.maintenance_due(now) {
+            let outbound = self.live_outbound_peers().await;
+            let block_relay_only = {
+                let pm = self.peer_manager.lock().await;
+                outbound
+                    .iter()
+                    .filter(|(addr, _)| {
+                        pm.find_transport_addr_by_socket(*addr)
+                            .and_then(|transport_addr| pm.get_peer(&transport_addr))
+                            .is_some_and(|peer| peer.is_block_relay_only())
+                    })
+                    .count()
+            };
+            let needed_full_relay = self
+                .connection_manager
+                .target_outbound()
+                .saturating_sub(outbound.len() - block_relay_only);
+            let needed_block_relay_only = self
+                .connection_manager
+                .target_block_relay_only()
+                .saturating_sub(block_relay_only);
+            let needed = needed_full_relay + needed_block_relay_only;
+            if needed > 0 {
+                let mut candidates: Vec<SocketAddr> = self
+                    .address_candidates(needed * 3)
+                    .await
+                    .into_iter()
+                    .map(|(socket, _)| socket)
+                    .collect();
+                if candidates.is_empty() {
+                    // Out of addresses while below target: query the DNS seeds again
+                    let seeder = self.dns_seeder.lock().await.clone();
+                    if let Some(seeder) = seeder {
+                        let count = self.add_seed_addresses(&seeder, true).await;
+                        info!("Re-queried DNS seeds, got {} addresses", count);
+                        candidates = self
+                            .address_candidates(needed * 3)
+                            .await
+                            .into_iter()
+                            .map(|(socket, _)| socket)
+                            .collect();
+                    }
+                }
+                {
+                    let discouraged = self.discouraged.read().await;
+                    candidates.retain(|addr| !discouraged.is_discouraged(addr.ip(), now));
+                }
+                let connected = self.peer_manager.lock().await.peer_socket_addresses();
+                let ban_list = self.ban_list.read().await.clone();
+                let selected = connection_manager::select_outbound_candidates(
+                    &candidates,
+                    &connected,
+                    &ban_list,
+                    now,
+                    needed,
+                );
+                // Full-relay slots are filled first
+                for (i, addr) in selected.into_iter().enumerate() {
+                    let block_relay_only = i >= needed_full_relay;
+                    if let Err(e) = self.connect_outbound(addr, block_relay_only).await {
+                        debug!("Outbound connection to {} failed: {}", addr, e);
+                    }
+                }
+            }
+
+            if let Err(e) = self.save_anchors().await {
+                warn!("Failed to save anchors: {}", e);
+            }
+            if let Err(e) = self.save_peers().await {
+                warn!("Failed to save peers: {}", e);
+            }
+            if let Err(e) = self.save_discouraged().await {
+                warn!("Failed to save discouraged addresses: {}", e);
+            }
+        }
+
+        if self.connection_manager.feeler_due(now) {
+            self.make_feeler_connection(now).await;
+        }
+
+        Ok(())
+    }
+
+    /// Test one new-table address with a short-lived connection
+    ///
+    /// Reachable addresses move to the tried table; unreachable ones are removed.
+    async fn make_feeler_connection(&self, now: u64) {
+        let candidates: Vec<(SocketAddr, NetworkAddress)> = {
+            let db = self.address_database.read().await;
+            db.new_addresses(100)
+                .into_iter()
+                .filter(|addr| !db.is_local(addr))
+                .map(|addr| (db.network_addr_to_socket(&addr), addr))
+                .collect()
+        };
+        let sockets: Vec<SocketAddr> = {
+            let discouraged = self.discouraged.read().await;
+            candidates
+                .iter()
+                .map(|(socket, _)| *socket)
+                .filter(|socket| !discouraged.is_discouraged(socket.ip(), now))
+                .collect()
+        };
+        let connected = self.peer_manager.lock().await.peer_socket_addresses();
+        let ban_list = self.ban_list.read().await.clone();
+        let Some(target) =
+            connection_manager::select_feeler_candidate(&sockets, &connected, &ban_list, now)
+        else {
+            return;
+        };
+        let Some(net_addr) = candidates
+            .into_iter()
+            .find(|(socket, _)| *socket == target)
+            .map(|(_, addr)| addr)
+        else {
+            return;
+        };
+
+        self.connection_manager.feeler_started();
+        self.address_database.write().await.mark_attempt(&target);
+        let result = crate::utils::with_custom_timeout(
+            self.tcp_transport.connect(TransportAddr::Tcp(target)),
+            self.connect_timeout(),
+        )
+        .await;
+
+        let mut db = self.address_database.write().await;
+        match result {
+            Ok(Ok(mut conn)) => {
+                debug!("Feeler connection to {} succeeded", target);
+                use crate::network::transport::TransportConnection;
+                let _ = conn.close().await;
+                self.connection_manager.feeler_finished();
+                db.mark_good(&target);
+            }
+            Ok(Err(e)) => {
+                self.connection_manager.feeler_finished();
+                debug!("Feeler connection to {} failed: {}", target, e);
+                db.remove_address(&net_addr);
+            }
+            Err(_) => {
+                self.connection_manager.feeler_finished();
+                debug!("Feeler connection to {} timed out", target);
+                db.remove_address(&net_addr);
+            }
+        }
+    }
+
+    /// Persist the longest-lived outbound peers to `anchors.dat`
+    pub async fn save_anchors(&self) -> Result<()> {
+        let Some(path) = self.connection_manager.anchors_path() else {
+            return Ok(());
+        };
+        let outbound = self.live_outbound_peers().await;
+        let anchors = connection_manager::select_anchors(&outbound);
+        connection_manager::save_anchors(path, &anchors)
+    }
+
+    /// Persist the address database to `peers.dat`
+    pub async fn save_peers(&self) -> Result<()> {
+        let Some(path) = &self.peers_path else {
+            return Ok(());
+        };
+        self.address_database.read().await.save(path)
+    }
+
+    /// Persist the discouragement list to `discouraged.dat`
+    pub async fn save_discouraged(&self) -> Result<()> {
+        let Some(path) = &self.discouraged_path else {
+            return Ok(());
+        };
+        self.discouraged.read().await.save(path)
+    }
+
+    /// Discourage an address: it is not dialed, is refused inbound when no
+    /// slot is free and is evicted first (see [`discouragement`])
+    pub async fn discourage(&self, ip: std::net::IpAddr) {
+        self.discouraged
+            .write()
+            .await
+            .discourage(ip, current_timestamp());
+    }
+
+    /// Whether an address is discouraged
+    pub async fn is_discouraged(&self, ip: std::net::IpAddr) -> bool {
+        self.discouraged
+            .read()
+            .await
+            .is_discouraged(ip, current_timestamp())
+    }
+
+    /// Disconnect every peer, returning how many were connected
+    ///
+    /// Used on shutdown after the listeners have stopped; dropping a peer
+    /// closes its send queue so its writer finishes and the socket closes.
+    pub async fn disconnect_all_peers(&self) -> usize {
+        let mut pm = self.peer_manager.lock().await;
+        let addrs = pm.peer_addresses();
+        for addr in &addrs {
+            pm.remove_peer(addr);
+        }
+        addrs.len()
+    }
+
+    /// Known addresses for `getnodeaddresses`, tried ones first
+    pub async fn node_addresses(&self, count: usize) -> Vec<address_db::AddressEntry> {
+        let db = self.address_database.read().await;
+        db.select_entries(count)
+            .into_iter()
+            .filter(|entry| !db.is_local(&entry.addr))
+            .collect()
+    }
+
+    /// Occupied new and tried table slots for `getrawaddrman`
+    pub async fn address_table_slots(
+        &self,
+    ) -> (Vec<address_db::TableSlot>, Vec<address_db::TableSlot>) {
+        let db = self.address_database.read().await;
+        (db.new_table_slots(), db.tried_table_slots())
+    }
+
+    /// Initialize peer connections after startup
+    ///
+    /// This is automatically called by `start()` to:
+    /// 1. Discover peers from DNS seeds (for TCP/Quinn transports)
+    /// 2. Connect to persistent peers from config
+    /// 3. Discover Iroh peers (if Iroh is enabled) - uses Iroh's DERP servers and gossip
+    /// 4. Connect to peers from address database to reach target count
+    ///
+    /// Note: The address database now supports both SocketAddr-based addresses (TCP/Quinn)
+    /// and Iroh NodeIds. Iroh peers are discovered through:
+    /// - DERP servers (handled by Iroh's MagicEndpoint)
+    /// - Gossip discovery (when available)
+    /// - Incoming connections (automatically stored)
+    /// - Persistent peers from config
+    pub async fn initialize_peer_connections(
+        &self,
+        config: &crate::config::NodeConfig,
+        network: &str,
+        port: u16,
+        target_peer_count: usize,
+    ) -> Result<()> {
+        // 1. Discover peers from DNS seeds (only for TCP/Quinn, not Iroh)
+        let should_discover_dns = self.transport_preference.allows_tcp() || {
+            #[cfg(feature = "quinn")]
+            {
+                self.transport_preference.allows_quinn()
+            }
+            #[cfg(not(feature = "quinn"))]
+            {
+                false
+            }
+        };
+        if should_discover_dns {
+            if let Err(e) = self.discover_peers_from_dns(network, port, config).await {
+                warn!("DNS seed discovery failed: {}", e);
+            }
+        }
+
+        // Reconnect to anchors from the previous run before anything else
+        let anchors_connected = self.connect_anchors().await;
+        if anchors_connected > 0 {
+            info!("Reconnected to {} anchor peer(s)", anchors_connected);
+        }
+
+        // 2. Connect to persistent peers
+        if !config.persistent_peers.is_empty() {
+            if let Err(e) = self
+                .connect_persistent_peers(&config.persistent_peers)
+                .await
+            {
+                warn!("Failed to connect to some persistent peers: {}", e);
             }
         }
 
@@ -857,6 +1807,15 @@ impl NetworkManager {
     }
 
     /// Start the network manager
+    /// Share a shutdown broadcast with the owner of this manager
+    ///
+    /// Listener and background tasks started by [`NetworkManager::start`]
+    /// stop when it is triggered.
+    pub fn with_shutdown(mut self, shutdown: ShutdownController) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     pub async fn start(&mut self, listen_addr: SocketAddr) -> Result<()> {
         info!(
             "Starting network manager with transport preference: {:?}",
@@ -901,18 +1860,67 @@ impl NetworkManager {
 
         // Start listening on TCP if allowed
         if self.transport_preference.allows_tcp() {
-            let mut tcp_listener = self.tcp_transport.listen(listen_addr).await?;
-            info!("TCP listener started on {}", listen_addr);
+            // Bind every listening address, then run one accept loop per
+            // socket feeding a shared connection handler. Connections carry
+            // the permissions of the `whitebind` they arrived on.
+            let (accept_tx, mut accept_rx) = mpsc::unbounded_channel();
+            let mut addrs = vec![(listen_addr, NetPermissions::empty())];
+            let whitebinds = self
+                .whitebind
+                .iter()
+                .map(|bind| (bind.addr, bind.permissions));
+            for (addr, permissions) in self
+                .listen_addrs
+                .iter()
+                .map(|addr| (*addr, NetPermissions::empty()))
+                .chain(whitebinds)
+            {
+                match addrs.iter_mut().find(|(bound, _)| *bound == addr) {
+                    Some((_, bound_permissions)) => *bound_permissions |= permissions,
+                    None => addrs.push((addr, permissions)),
+                }
+            }
+            self.local_addrs.clear();
+            for (addr, bind_permissions) in addrs {
+                let mut tcp_listener = self.tcp_transport.listen(addr).await?;
+                let local_addr = tcp_listener.local_addr()?;
+                if bind_permissions.is_empty() {
+                    info!("TCP listener started on {}", local_addr);
+                } else {
+                    info!(
+                        "TCP listener started on {} (permissions: {})",
+                        local_addr,
+                        bind_permissions.names().join(",")
+                    );
+                }
+                self.local_addrs.push(local_addr);
+
+                let accept_tx = accept_tx.clone();
+                self.shutdown.spawn(async move {
+                    loop {
+                        let accepted = tcp_listener.accept().await;
+                        if accept_tx.send((accepted, bind_permissions)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(accept_tx);
+            self.start_local_address_discovery().await;
 
-            // Start TCP accept loop
+            // Start TCP connection handler
             use crate::utils::arc_clone;
             let peer_tx = self.peer_tx.clone();
+            let bandwidth_limits = self.bandwidth_limits.clone();
             let dos_protection = arc_clone(&self.dos_protection);
             let peer_manager_clone = arc_clone(&self.peer_manager);
             let ban_list = arc_clone(&self.ban_list);
-            tokio::spawn(async move {
-                loop {
-                    match tcp_listener.accept().await {
+            let pending_ban_shares = arc_clone(&self.pending_ban_shares);
+            let connections_per_ip = arc_clone(&self.connections_per_ip);
+            let discouraged_list = arc_clone(&self.discouraged);
+            self.shutdown.spawn(async move {
+                while let Some((accepted, bind_permissions)) = accept_rx.recv().await {
+                    match accepted {
                         Ok((conn, transport_addr)) => {
                             // Extract SocketAddr from TransportAddr::Tcp
                             let socket_addr = match transport_addr {
@@ -931,8 +1939,16 @@ impl NetworkManager {
                             info!("New TCP connection from {:?}", socket_addr);
 
                             // Check DoS protection: connection rate limiting
+                            // (`noban` peers always pass)
                             let ip = socket_addr.ip();
-                            if !dos_protection.check_connection(ip).await {
+                            let permissions = dos_protection.permissions(ip) | bind_permissions;
+                            let whitelisted = permissions.contains(NetPermissions::NOBAN);
+                            let discouraged = !whitelisted
+                                && discouraged_list
+                                    .read()
+                                    .await
+                                    .is_discouraged(ip, current_timestamp());
+                            if !whitelisted && !dos_protection.check_connection(ip).await {
                                 warn!("Connection rate limit exceeded for IP {}, rejecting connection", ip);
 
                                 // Check if we should auto-ban
@@ -947,6 +1963,11 @@ impl NetworkManager {
                                         + ban_duration;
                                     let mut ban_list_guard = ban_list.write().await;
                                     ban_list_guard.insert(socket_addr, unban_timestamp);
+                                    pending_ban_shares.lock().await.push((
+                                        socket_addr,
+                                        unban_timestamp,
+                                        "connection rate violations".to_string(),
+                                    ));
                                 }
 
                                 // Close connection immediately
@@ -959,15 +1980,23 @@ impl NetworkManager {
                                 let pm = peer_manager_clone.lock().await;
                                 pm.peer_count()
                             };
-                            if !dos_protection
-                                .check_active_connections(current_connections)
-                                .await
+                            if !whitelisted
+                                && !dos_protection
+                                    .check_active_connections(current_connections)
+                                    .await
                             {
                                 warn!("Active connection limit exceeded, rejecting connection from {}", socket_addr);
                                 drop(conn);
                                 continue;
                             }
 
+                            // Released again when the peer disconnects
+                            *connections_per_ip
+                                .lock()
+                                .await
+                                .entry(dos_protection::connection_key(ip))
+                                .or_insert(0) += 1;
+
                             // Send connection notification
                             let _ =
                                 peer_tx.send(NetworkMessage::PeerConnected(transport_addr.clone()));
@@ -977,17 +2006,61 @@ impl NetworkManager {
                             use crate::utils::arc_clone;
                             let peer_manager_for_peer = arc_clone(&peer_manager_clone);
                             let transport_addr_for_peer = transport_addr.clone();
-                            tokio::spawn(async move {
+                            let peer_bandwidth =
+                                if permissions.contains(NetPermissions::DOWNLOAD) {
+                                    bandwidth_limits.for_download_peer()
+                                } else {
+                                    bandwidth_limits.for_peer()
+                                };
+                            let handle_connection = async move {
                                 // Create peer from transport connection
-                                let peer = peer::Peer::from_transport_connection(
+                                let mut peer = peer::Peer::from_transport_connection_with_bandwidth(
                                     conn,
                                     socket_addr,
                                     transport_addr_for_peer.clone(),
                                     peer_tx_clone.clone(),
+                                    peer_bandwidth,
                                 );
+                                peer.set_inbound(true);
+                                peer.set_permissions(permissions);
+                                peer.set_discouraged(discouraged);
+                                tracing::Span::current().record("peer_id", peer.id());
 
                                 // Add peer to manager (async-safe)
                                 let mut pm = peer_manager_for_peer.lock().await;
+                                if !whitelisted && pm.netgroup_full(&transport_addr_for_peer) {
+                                    // Refuse before evicting anyone on its behalf
+                                    debug!(
+                                        "Refusing inbound peer {}: network group is full",
+                                        socket_addr
+                                    );
+                                    let _ = peer_tx_clone.send(NetworkMessage::PeerDisconnected(
+                                        transport_addr_for_peer.clone(),
+                                    ));
+                                    return;
+                                }
+                                if discouraged && !pm.can_accept_inbound() {
+                                    // Never evict anyone for a discouraged address
+                                    debug!(
+                                        "Refusing inbound peer {}: discouraged and no free slot",
+                                        socket_addr
+                                    );
+                                    let _ = peer_tx_clone.send(NetworkMessage::PeerDisconnected(
+                                        transport_addr_for_peer.clone(),
+                                    ));
+                                    return;
+                                }
+                                if !pm.can_accept_inbound() && !whitelisted {
+                                    // Inbound slots full: make room by evicting a peer
+                                    if let Some(victim) = pm.evict_inbound_peer() {
+                                        info!(
+                                            "Evicted inbound peer {} to accept {}",
+                                            victim, socket_addr
+                                        );
+                                        let _ = peer_tx_clone
+                                            .send(NetworkMessage::PeerDisconnected(victim));
+                                    }
+                                }
                                 if let Err(e) = pm.add_peer(transport_addr_for_peer.clone(), peer) {
                                     warn!("Failed to add peer {}: {}", socket_addr, e);
                                     let _ = peer_tx_clone.send(NetworkMessage::PeerDisconnected(
@@ -1003,7 +2076,14 @@ impl NetworkManager {
 
                                 // Connection will be cleaned up automatically when read/write tasks exit
                                 // Peer removal happens in process_messages when PeerDisconnected is received
-                            });
+                            };
+                            // Log per-connection bookkeeping with the peer address
+                            let span = tracing::info_span!(
+                                "peer",
+                                peer_addr = %socket_addr,
+                                peer_id = tracing::field::Empty
+                            );
+                            tokio::spawn(handle_connection.instrument(span));
                         }
                         Err(e) => {
                             error!("Failed to accept TCP connection: {}", e);
@@ -1020,12 +2100,14 @@ impl NetworkManager {
                 Ok(mut quinn_listener) => {
                     info!("Quinn listener started on {}", listen_addr);
                     let peer_tx = self.peer_tx.clone();
+                    let bandwidth_limits = self.bandwidth_limits.clone();
                     use crate::utils::arc_clone;
                     let peer_manager = arc_clone(&self.peer_manager);
                     let dos_protection = arc_clone(&self.dos_protection);
                     let ban_list = arc_clone(&self.ban_list);
+                    let pending_ban_shares = arc_clone(&self.pending_ban_shares);
 
-                    tokio::spawn(async move {
+                    self.shutdown.spawn(async move {
                         loop {
                             match quinn_listener.accept().await {
                                 Ok((conn, addr)) => {
@@ -1053,6 +2135,11 @@ impl NetworkManager {
                                                 current_timestamp() + ban_duration;
                                             let mut ban_list_guard = ban_list.write().await;
                                             ban_list_guard.insert(socket_addr, unban_timestamp);
+                                            pending_ban_shares.lock().await.push((
+                                                socket_addr,
+                                                unban_timestamp,
+                                                "connection rate violations".to_string(),
+                                            ));
                                         }
                                         drop(conn);
                                         continue;
@@ -1063,9 +2150,12 @@ impl NetworkManager {
                                         let pm = peer_manager.lock().await;
                                         pm.peer_count()
                                     };
-                                    if !dos_protection
-                                        .check_active_connections(current_connections)
-                                        .await
+                                    let permissions = dos_protection.permissions(ip);
+                                    let whitelisted = permissions.contains(NetPermissions::NOBAN);
+                                    if !whitelisted
+                                        && !dos_protection
+                                            .check_active_connections(current_connections)
+                                            .await
                                     {
                                         warn!("Active connection limit exceeded, rejecting Quinn connection from {}", socket_addr);
                                         drop(conn);
@@ -1082,17 +2172,27 @@ impl NetworkManager {
                                     use crate::utils::arc_clone;
                                     let peer_tx_clone = peer_tx.clone();
                                     let peer_manager_clone = arc_clone(&peer_manager);
+                                    let peer_bandwidth =
+                                        if permissions.contains(NetPermissions::DOWNLOAD) {
+                                            bandwidth_limits.for_download_peer()
+                                        } else {
+                                            bandwidth_limits.for_peer()
+                                        };
                                     tokio::spawn(async move {
                                         use crate::network::transport::TransportAddr;
 
                                         let quinn_addr = TransportAddr::Quinn(socket_addr);
                                         let quinn_addr_clone = quinn_addr.clone();
-                                        let peer = peer::Peer::from_transport_connection(
-                                            conn,
-                                            socket_addr,
-                                            quinn_addr,
-                                            peer_tx_clone.clone(),
-                                        );
+                                        let mut peer =
+                                            peer::Peer::from_transport_connection_with_bandwidth(
+                                                conn,
+                                                socket_addr,
+                                                quinn_addr,
+                                                peer_tx_clone.clone(),
+                                                peer_bandwidth,
+                                            );
+                                        peer.set_inbound(true);
+                                        peer.set_permissions(permissions);
 
                                         // Add peer to manager (async-safe)
                                         let mut pm = peer_manager_clone.lock().await;
@@ -1138,12 +2238,13 @@ impl NetworkManager {
                 Ok(mut iroh_listener) => {
                     info!("Iroh listener started on {}", listen_addr);
                     let peer_tx = self.peer_tx.clone();
+                    let bandwidth_limits = self.bandwidth_limits.clone();
                     use crate::utils::arc_clone;
                     let peer_manager = arc_clone(&self.peer_manager);
                     let dos_protection = arc_clone(&self.dos_protection);
                     let address_database = arc_clone(&self.address_database);
                     let socket_to_transport = arc_clone(&self.socket_to_transport);
-                    tokio::spawn(async move {
+                    self.shutdown.spawn(async move {
                         loop {
                             match iroh_listener.accept().await {
                                 Ok((conn, addr)) => {
@@ -1188,6 +2289,7 @@ impl NetworkManager {
                                     let socket_to_transport_clone =
                                         Arc::clone(&socket_to_transport);
                                     let address_database_clone = Arc::clone(&address_database);
+                                    let peer_bandwidth = bandwidth_limits.for_peer();
                                     tokio::spawn(async move {
                                         // For Iroh, we need a SocketAddr for Peer::from_transport_connection
                                         // Generate a unique placeholder based on key hash for lookups
@@ -1212,12 +2314,15 @@ impl NetworkManager {
                                                 std::net::SocketAddr::from(([0, 0, 0, 0], 0))
                                             };
 
-                                        let peer = peer::Peer::from_transport_connection(
-                                            conn,
-                                            placeholder_socket,
-                                            iroh_addr_clone.clone(),
-                                            peer_tx_clone.clone(),
-                                        );
+                                        let mut peer =
+                                            peer::Peer::from_transport_connection_with_bandwidth(
+                                                conn,
+                                                placeholder_socket,
+                                                iroh_addr_clone.clone(),
+                                                peer_tx_clone.clone(),
+                                                peer_bandwidth,
+                                            );
+                                        peer.set_inbound(true);
 
                                         // Add peer to manager (async-safe)
                                         let mut pm = peer_manager_clone.lock().await;
@@ -1428,7 +2533,7 @@ impl NetworkManager {
         let pending_requests = arc_clone(&self.pending_requests);
         let timeout_config = arc_clone(&self.request_timeout_config);
 
-        tokio::spawn(async move {
+        self.shutdown.spawn(async move {
             let cleanup_interval = timeout_config.request_cleanup_interval_seconds;
             let max_age = timeout_config.pending_request_max_age_seconds;
             let mut interval =
@@ -1461,46 +2566,53 @@ impl NetworkManager {
         use crate::utils::arc_clone;
         let dos_protection = arc_clone(&self.dos_protection);
         let ban_list = arc_clone(&self.ban_list);
+        let pending_ban_shares = arc_clone(&self.pending_ban_shares);
 
-        tokio::spawn(async move {
+        let dos_cleanup = arc_clone(&dos_protection);
+        self.shutdown.spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // Every 5 minutes
             loop {
                 interval.tick().await;
 
                 // Cleanup old connection rate limiter entries
-                dos_protection.cleanup().await;
-
-                // Auto-ban IPs that should be banned
-                // Periodic check for IPs that have exceeded violation thresholds
-                use crate::utils::arc_clone;
-                let dos_clone = arc_clone(&dos_protection);
-                let ban_list_clone = arc_clone(&ban_list);
-                let ban_duration = dos_protection.ban_duration_seconds();
-                tokio::spawn(async move {
-                    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60)); // Check every minute
-                    loop {
-                        interval.tick().await;
+                dos_cleanup.cleanup().await;
+            }
+        });
 
-                        // Get IPs that should be auto-banned
-                        let ips_to_ban = dos_clone.get_ips_to_auto_ban().await;
+        // Auto-ban IPs that should be banned
+        // Periodic check for IPs that have exceeded violation thresholds
+        let ban_duration = dos_protection.ban_duration_seconds();
+        self.shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60)); // Check every minute
+            loop {
+                interval.tick().await;
 
-                        // Ban IPs that exceed threshold
-                        if !ips_to_ban.is_empty() {
-                            let now = current_timestamp();
-                            let unban_timestamp = now + ban_duration;
+                // Get IPs that should be auto-banned
+                let ips_to_ban = dos_protection.get_ips_to_auto_ban().await;
 
-                            let mut ban_list_guard = ban_list_clone.write().await;
-                            for ip in ips_to_ban {
-                                // Convert IpAddr to SocketAddr (use port 0 as placeholder)
-                                let socket_addr = std::net::SocketAddr::new(ip, 0);
-                                if !ban_list_guard.contains_key(&socket_addr) {
-                                    ban_list_guard.insert(socket_addr, unban_timestamp);
-                                    warn!("Auto-banned IP {} for connection rate violations (unban at {})", ip, unban_timestamp);
-                                }
-                            }
+                // Ban IPs that exceed threshold
+                if !ips_to_ban.is_empty() {
+                    let now = current_timestamp();
+                    let unban_timestamp = now + ban_duration;
+
+                    let mut ban_list_guard = ban_list.write().await;
+                    for ip in ips_to_ban {
+                        // Convert IpAddr to SocketAddr (use port 0 as placeholder)
+                        let socket_addr = std::net::SocketAddr::new(ip, 0);
+                        if !ban_list_guard.contains_key(&socket_addr) {
+                            ban_list_guard.insert(socket_addr, unban_timestamp);
+                            pending_ban_shares.lock().await.push((
+                                socket_addr,
+                                unban_timestamp,
+                                "connection rate violations".to_string(),
+                            ));
+                            warn!(
+                                "Auto-banned IP {} for connection rate violations (unban at {})",
+                                ip, unban_timestamp
+                            );
                         }
                     }
-                });
+                }
             }
         });
     }
@@ -1509,7 +2621,8 @@ impl NetworkManager {
     fn start_ban_cleanup_task(&self) {
         use crate::utils::arc_clone;
         let ban_list = arc_clone(&self.ban_list);
-        tokio::spawn(async move {
+        let discouraged = arc_clone(&self.discouraged);
+        self.shutdown.spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // Every 5 minutes
             loop {
                 interval.tick().await;
@@ -1534,6 +2647,15 @@ impl NetworkManager {
                 if expired_count > 0 {
                     info!("Cleaned up {} expired ban(s)", expired_count);
                 }
+                drop(ban_list_guard);
+
+                let expired_discouraged = discouraged.write().await.cleanup(now);
+                if expired_discouraged > 0 {
+                    debug!(
+                        "Cleaned up {} expired discouraged address(es)",
+                        expired_discouraged
+                    );
+                }
             }
         });
     }
@@ -1544,11 +2666,14 @@ impl NetworkManager {
         let reconnection_queue = arc_clone(&self.peer_reconnection_queue);
         let peer_manager = arc_clone(&self.peer_manager);
         let peer_tx = self.peer_tx.clone();
+        let bandwidth_limits = self.bandwidth_limits.clone();
         let tcp_transport = self.tcp_transport.clone();
         let ban_list = arc_clone(&self.ban_list);
+        let connect_timeout = self.connect_timeout();
+        let connect_timeouts = arc_clone(&self.connect_timeouts);
         // Get max_peers (we'll need to access it later, so we'll query it in the loop)
 
-        tokio::spawn(async move {
+        self.shutdown.spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(10)); // Check every 10 seconds
             loop {
                 interval.tick().await;
@@ -1656,6 +2781,9 @@ impl NetworkManager {
                     let peer_manager_clone = arc_clone(&peer_manager);
                     let tcp_transport_clone = tcp_transport.clone();
                     let reconnection_queue_clone = arc_clone(&reconnection_queue);
+                    let connect_timeouts_clone = arc_clone(&connect_timeouts);
+
+                    let peer_bandwidth = bandwidth_limits.for_peer();
 
                     // Attempt connection in background
                     tokio::spawn(async move {
@@ -1663,19 +2791,24 @@ impl NetworkManager {
                         use crate::network::transport::TransportAddr;
 
                         // Try TCP connection (most common)
-                        match tcp_transport_clone
-                            .connect(TransportAddr::Tcp(addr_clone))
-                            .await
+                        match connect_with_timeout(
+                            &tcp_transport_clone,
+                            TransportAddr::Tcp(addr_clone),
+                            connect_timeout,
+                            &connect_timeouts_clone,
+                        )
+                        .await
                         {
                             Ok(conn) => {
                                 info!("Successfully reconnected to peer {}", addr_clone);
 
                                 // Create peer from transport connection
-                                let peer = Peer::from_transport_connection(
+                                let peer = Peer::from_transport_connection_with_bandwidth(
                                     conn,
                                     addr_clone,
                                     TransportAddr::Tcp(addr_clone),
                                     peer_tx_clone.clone(),
+                                    peer_bandwidth,
                                 );
 
                                 // Add peer to manager
@@ -1719,6 +2852,27 @@ impl NetworkManager {
         })
     }
 
+    /// Number of connected peers (async version for RPC)
+    pub async fn connection_count(&self) -> usize {
+        self.peer_manager.lock().await.peer_count()
+    }
+
+    /// Number of open connections of each type, including feelers
+    pub async fn connection_counts(&self) -> connection_manager::ConnectionCounts {
+        let mut counts = self.peer_manager.lock().await.connection_counts();
+        counts.feeler = self.connection_manager.active_feelers();
+        counts
+    }
+
+    /// Change the maximum number of peers at runtime
+    ///
+    /// Inbound capacity follows the new limit; connected peers above a lowered
+    /// limit stay until they disconnect.
+    pub async fn set_max_peers(&self, max_peers: usize) {
+        self.peer_manager.lock().await.set_max_peers(max_peers);
+        info!("Maximum peers set to {}", max_peers);
+    }
+
     /// Get all peer addresses (as SocketAddr for backward compatibility)
     pub fn peer_addresses(&self) -> Vec<SocketAddr> {
         // Use block_in_place to avoid blocking async runtime
@@ -1762,2026 +2916,6076 @@ impl NetworkManager {
         Ok(())
     }
 
-    /// Broadcast to reliable peers first, then others
-    /// Uses peer quality to prioritize reliable peers for critical messages
-    pub async fn broadcast_with_quality_priority(&self, message: Vec<u8>) -> Result<()> {
-        // Get reliable peers first, then drop lock before async operations
-        let reliable_peers = {
-            let pm = self.peer_manager.lock().await;
-            pm.select_reliable_peers()
-        };
+    /// Announce a locally accepted transaction to peers
+    ///
+    /// Sends an `inv` to every peer that doesn't already know the transaction
+    /// and whose feefilter (BIP133) the fee rate meets. With Dandelion++
+    /// enabled, the transaction is stemmed instead (see [`Self::relay_transaction`]).
+    pub async fn announce_transaction(&self, txid: Hash, fee_rate_per_kvb: u64) -> Result<usize> {
+        self.relay_transaction(txid, fee_rate_per_kvb, None).await
+    }
 
-        // Send to reliable peers first
-        for addr in &reliable_peers {
-            if let Err(e) = self
-                .send_to_peer_by_transport(addr.clone(), message.clone())
-                .await
-            {
-                warn!("Failed to send to reliable peer {:?}: {}", addr, e);
+    /// Relay a transaction to peers
+    ///
+    /// With Dandelion++ enabled, a new transaction enters the stem phase and
+    /// is announced to a single stem peer only; it is announced to all peers
+    /// once it fluffs. `source` is the peer the transaction came from (`None`
+    /// for local submissions, which always start in stem phase).
+    /// Returns the number of peers the transaction was announced to.
+    pub async fn relay_transaction(
+        &self,
+        txid: Hash,
+        fee_rate_per_kvb: u64,
+        source: Option<SocketAddr>,
+    ) -> Result<usize> {
+        #[cfg(feature = "dandelion")]
+        {
+            if let Some(stem_peer) = self.route_stem(txid, fee_rate_per_kvb, source).await {
+                debug!("Stemming tx {} to {}", hex::encode(txid), stem_peer);
+                return self
+                    .announce_inventory(
+                        inventory::MSG_TX,
+                        txid,
+                        Some(fee_rate_per_kvb),
+                        Some(&stem_peer),
+                    )
+                    .await;
             }
         }
+        #[cfg(not(feature = "dandelion"))]
+        let _ = source;
+        self.announce_inventory(inventory::MSG_TX, txid, Some(fee_rate_per_kvb), None)
+            .await
+    }
 
-        // Then send to remaining peers
-        let pm = self.peer_manager.lock().await;
-        let all_peers = pm.peer_addresses();
-        let remaining_peers: Vec<_> = all_peers
-            .iter()
-            .filter(|addr| !reliable_peers.contains(addr))
-            .collect();
-        drop(pm);
+    /// Pick the Dandelion++ stem peer for a transaction
+    ///
+    /// Returns `None` if the transaction should fluff (or Dandelion++ is off).
+    #[cfg(feature = "dandelion")]
+    async fn route_stem(
+        &self,
+        txid: Hash,
+        fee_rate_per_kvb: u64,
+        source: Option<SocketAddr>,
+    ) -> Option<TransportAddr> {
+        let routing = self.dandelion.as_ref()?;
+        let (peers, origin) = {
+            let pm = self.peer_manager.lock().await;
+            let origin = source.map(|addr| {
+                pm.find_transport_addr_by_socket(addr)
+                    .unwrap_or(TransportAddr::Tcp(addr))
+                    .to_string()
+            });
+            (pm.peer_addresses(), origin)
+        };
+        let peer_ids: Vec<String> = peers.iter().map(|addr| addr.to_string()).collect();
+
+        let mut routing = routing.lock().await;
+        routing.rotate_epoch_if_due(current_timestamp());
+        let relay = &mut routing.relay;
+        let next = if relay.get_stem_peer(&txid).is_some() {
+            if relay.should_fluff(&txid) {
+                None
+            } else {
+                relay.advance_stem(txid, &peer_ids)
+            }
+        } else {
+            let local = origin.is_none();
+            let next = relay.start_stem_phase(
+                txid,
+                origin.unwrap_or_else(|| "local".to_string()),
+                &peer_ids,
+            );
+            // Relays fluff with fluff_probability; the originator always stems
+            if next.is_some() && !local && relay.should_fluff(&txid) {
+                None
+            } else {
+                next
+            }
+        };
 
-        for addr in remaining_peers {
-            if let Err(e) = self
-                .send_to_peer_by_transport(addr.clone(), message.clone())
-                .await
-            {
-                warn!("Failed to send to peer {:?}: {}", addr, e);
+        match next {
+            Some(peer_id) => {
+                routing.stem_fee_rates.insert(txid, fee_rate_per_kvb);
+                peers.into_iter().find(|addr| addr.to_string() == peer_id)
+            }
+            None => {
+                routing.relay.transition_to_fluff(txid);
+                routing.stem_fee_rates.remove(&txid);
+                None
             }
         }
-
-        Ok(())
     }
 
-    /// Send to best peer (highest quality score)
-    /// Returns the peer address used, or error if no peers available
-    pub async fn send_to_best_peer(&self, message: Vec<u8>) -> Result<TransportAddr> {
-        let pm = self.peer_manager.lock().await;
-        let best_peers = pm.select_best_peers(1);
-        drop(pm);
+    /// Fluff stem-phase transactions past their stem timeout
+    ///
+    /// Also rotates the Dandelion++ epoch when it is due. Returns the
+    /// transactions that fluffed.
+    #[cfg(feature = "dandelion")]
+    pub async fn process_dandelion_timeouts(&self) -> Result<Vec<Hash>> {
+        let Some(ref routing) = self.dandelion else {
+            return Ok(Vec::new());
+        };
+        let fluffed: Vec<(Hash, u64)> = {
+            let mut routing = routing.lock().await;
+            routing.rotate_epoch_if_due(current_timestamp());
+            let timed_out = routing.relay.timed_out_stem_txs();
+            let fluffed = timed_out
+                .into_iter()
+                .map(|txid| {
+                    routing.relay.transition_to_fluff(txid);
+                    (txid, routing.stem_fee_rates.remove(&txid).unwrap_or(0))
+                })
+                .collect();
+            routing.relay.cleanup_expired();
+            fluffed
+        };
 
-        if let Some(addr) = best_peers.first() {
-            self.send_to_peer_by_transport(addr.clone(), message)
+        for (txid, fee_rate_per_kvb) in &fluffed {
+            info!("Dandelion stem timeout, fluffing tx {}", hex::encode(txid));
+            self.announce_inventory(inventory::MSG_TX, *txid, Some(*fee_rate_per_kvb), None)
                 .await?;
-            Ok(addr.clone())
-        } else {
-            Err(anyhow::anyhow!("No peers available"))
         }
+        Ok(fluffed.into_iter().map(|(txid, _)| txid).collect())
+    }
+    #[cfg(not(feature = "dandelion"))]
+    pub async fn process_dandelion_timeouts(&self) -> Result<Vec<Hash>> {
+        Ok(Vec::new())
     }
 
-    /// Send to reliable peer only (for critical operations)
-    /// Returns error if no reliable peers available
-    pub async fn send_to_reliable_peer(&self, message: Vec<u8>) -> Result<TransportAddr> {
-        let pm = self.peer_manager.lock().await;
-        let reliable_peers = pm.select_reliable_peers();
-        drop(pm);
-
-        if reliable_peers.is_empty() {
-            return Err(anyhow::anyhow!("No reliable peers available"));
+    /// Relay a transaction received from a peer (Dandelion++ stem or fluff)
+    ///
+    /// Only transactions passing mempool acceptance (see
+    /// [`NetworkManager::accept_transaction`]) are relayed, at the fee rate
+    /// acceptance computed.
+    #[cfg(feature = "dandelion")]
+    async fn relay_received_transaction(
+        &self,
+        peer_addr: SocketAddr,
+        tx: &bllvm_protocol::Transaction,
+    ) {
+        let accepted = match self.accept_transaction(peer_addr, tx).await {
+            Ok(MempoolAcceptResult::Accepted(accepted)) => accepted,
+            Ok(result) => {
+                debug!(
+                    "Not relaying tx from {}: {}",
+                    peer_addr,
+                    result.reject_reason().unwrap_or_default()
+                );
+                return;
+            }
+            Err(e) => {
+                debug!("Not relaying tx from {}: {}", peer_addr, e);
+                return;
+            }
+        };
+        let (txid, fee_rate_per_kvb) = (accepted.txid, accepted.fee_rate_per_kvb());
+        if let Err(e) = self
+            .relay_transaction(txid, fee_rate_per_kvb, Some(peer_addr))
+            .await
+        {
+            warn!("Failed to relay tx {}: {}", hex::encode(txid), e);
         }
+    }
 
-        // Select best reliable peer
-        let pm = self.peer_manager.lock().await;
-        let best_reliable = reliable_peers.iter().max_by(|a, b| {
-            let score_a = pm.get_peer(a).map(|p| p.quality_score()).unwrap_or(0.0);
-            let score_b = pm.get_peer(b).map(|p| p.quality_score()).unwrap_or(0.0);
-            score_a
-                .partial_cmp(&score_b)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-        drop(pm);
+    /// Announce a locally accepted block to peers
+    ///
+    /// Sends an `inv` to every peer that doesn't already know the block.
+    pub async fn announce_block(&self, block_hash: Hash) -> Result<usize> {
+        self.announce_inventory(inventory::MSG_BLOCK, block_hash, None, None)
+            .await
+    }
 
-        if let Some(addr) = best_reliable {
-            self.send_to_peer_by_transport(addr.clone(), message)
-                .await?;
-            Ok(addr.clone())
-        } else {
-            Err(anyhow::anyhow!("No reliable peers available"))
-        }
+    /// Relay a locally validated block to FIBRE peers
+    ///
+    /// `block_data` is the serialized block. Every peer advertising
+    /// NODE_FIBRE gets all FEC chunks of the block. Returns the number of
+    /// peers the block was sent to.
+    pub async fn relay_block_fibre(&self, block_hash: Hash, block_data: &[u8]) -> Result<usize> {
+        self.send_fibre_chunks(block_hash, block_data, None).await
     }
 
-    /// Send a message to a specific peer (by SocketAddr - for TCP/Quinn)
-    /// For Iroh peers, uses the socket_to_transport mapping
-    pub async fn send_to_peer(&self, addr: SocketAddr, message: Vec<u8>) -> Result<()> {
-        // Try to find transport address (TCP, Quinn, or Iroh mapping)
-        let transport_addr = {
-            let pm = self.peer_manager.lock().await;
-            pm.find_transport_addr_by_socket(addr).or_else(|| {
-                // Check socket_to_transport mapping (synchronous access needed)
-                // Note: This is a fallback, so we use try_lock to avoid blocking
-                // If we can't get the lock immediately, return None
-                self.socket_to_transport
-                    .try_lock()
-                    .ok()
-                    .and_then(|map| map.get(&addr).cloned())
-            })
+    /// Send the FEC chunks of a block to connected FIBRE peers except `exclude`
+    async fn send_fibre_chunks(
+        &self,
+        block_hash: Hash,
+        block_data: &[u8],
+        exclude: Option<SocketAddr>,
+    ) -> Result<usize> {
+        use crate::network::protocol::NODE_FIBRE;
+
+        let connected = self.peer_manager.lock().await.peer_socket_addresses();
+        let recipients: Vec<SocketAddr> = {
+            let peer_states = self.peer_states.read().await;
+            connected
+                .into_iter()
+                .filter(|addr| Some(*addr) != exclude)
+                .filter(|addr| {
+                    peer_states
+                        .get(addr)
+                        .is_some_and(|state| state.services & NODE_FIBRE != 0)
+                })
+                .collect()
         };
+        if recipients.is_empty() {
+            return Ok(0);
+        }
 
-        if let Some(transport_addr) = transport_addr {
-            self.send_to_peer_by_transport(transport_addr, message)
-                .await
-        } else {
-            // Fallback: try TCP (for backward compatibility)
-            self.send_to_peer_by_transport(TransportAddr::Tcp(addr), message)
-                .await
+        let encoded = self
+            .fibre
+            .lock()
+            .await
+            .encode_block(block_hash, block_data)?;
+        let messages = encoded
+            .chunks()
+            .iter()
+            .map(|chunk| {
+                ProtocolParser::serialize_message(&ProtocolMessage::FibreChunk(chunk.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for addr in &recipients {
+            for message in &messages {
+                if let Err(e) = self.send_to_peer(*addr, message.clone()).await {
+                    warn!("Failed to send FIBRE chunk to {}: {}", addr, e);
+                    break;
+                }
+            }
         }
+        debug!(
+            "Relayed block {} to {} FIBRE peers ({} chunks each)",
+            hex::encode(block_hash),
+            recipients.len(),
+            messages.len()
+        );
+        Ok(recipients.len())
     }
 
-    /// Send a message to a specific peer (by TransportAddr - supports all transports)
-    /// Returns Ok even if peer doesn't exist (graceful no-op)
-    pub async fn send_to_peer_by_transport(
+    /// Handle a FIBRE chunk: reconstruct the block once enough chunks arrived
+    ///
+    /// A reconstructed block is passed on to the other FIBRE peers right away
+    /// and then handed to the node like any received block.
+    async fn handle_fibre_chunk(
         &self,
-        addr: TransportAddr,
-        message: Vec<u8>,
+        peer_addr: SocketAddr,
+        msg: crate::network::protocol::FibreChunkMessage,
     ) -> Result<()> {
-        let message_len = message.len();
-
-        // Check if peer exists and get sender channel before dropping lock
-        let sender = {
-            let pm = self.peer_manager.lock().await;
-            if let Some(peer) = pm.get_peer(&addr) {
-                peer.send_tx.clone()
-            } else {
-                // Peer doesn't exist - return Ok (graceful no-op)
-                return Ok(());
+        let block_hash = msg.block_hash;
+        let result = self.fibre.lock().await.receive_chunk(msg);
+        match result {
+            Ok(Some(block_data)) => {
+                info!(
+                    "Reconstructed block {} from FIBRE chunks",
+                    hex::encode(block_hash)
+                );
+                if let Err(e) = self
+                    .send_fibre_chunks(block_hash, &block_data, Some(peer_addr))
+                    .await
+                {
+                    warn!(
+                        "Failed to relay block {} via FIBRE: {}",
+                        hex::encode(block_hash),
+                        e
+                    );
+                }
+                let _ = self.peer_tx.send(NetworkMessage::BlockReceived(block_data));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                debug!("Ignoring FIBRE chunk from {}: {}", peer_addr, e);
             }
-        };
-
-        // Track bytes sent only if peer exists (async-safe)
-        self.track_bytes_sent(message_len as u64).await;
-
-        // Send message without holding the lock (unbounded channel, so send won't block)
-        sender
-            .send(message)
-            .map_err(|e| anyhow::anyhow!("Failed to send message to peer: {}", e))?;
-
-        // Record send after message is sent
-        let mut pm = self.peer_manager.lock().await;
-        if let Some(peer) = pm.get_peer_mut(&addr) {
-            peer.record_send(message_len);
         }
         Ok(())
     }
 
-    /// Connect to a peer at the given address
+    /// Orphan transaction pool
+    pub fn orphan_pool(&self) -> &Arc<Mutex<OrphanPool>> {
+        &self.orphan_pool
+    }
+
+    /// Add a transaction to the orphan pool if any of its inputs is unknown
     ///
-    /// Attempts to connect using available transports with graceful degradation:
-    /// 1. Tries preferred transport (based on transport_preference)
-    /// 2. Falls back to TCP if preferred transport fails
-    /// 3. Returns error only if all transports fail
-    pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<()> {
-        // Check DoS protection: connection rate limiting (for outgoing connections too)
-        let ip = addr.ip();
-        if !self.dos_protection.check_connection(ip).await {
-            warn!(
-                "Connection rate limit exceeded for IP {}, rejecting outgoing connection",
-                ip
+    /// Returns true if the transaction is an orphan; it is then not passed
+    /// on to the protocol layer.
+    async fn store_if_orphan(
+        &self,
+        peer_addr: SocketAddr,
+        tx: &bllvm_protocol::Transaction,
+    ) -> bool {
+        let (Some(storage), Some(mempool)) = (self.storage.as_ref(), self.mempool_manager.as_ref())
+        else {
+            return false;
+        };
+        let has_unknown_input = tx.inputs.iter().any(|input| {
+            !storage.utxos().has_utxo(&input.prevout).unwrap_or(true)
+                && mempool.get_mempool_output(&input.prevout).is_none()
+        });
+        if !has_unknown_input {
+            return false;
+        }
+        if self
+            .orphan_pool
+            .lock()
+            .await
+            .add(tx.clone(), peer_addr, current_timestamp())
+        {
+            debug!(
+                "Stored orphan transaction {} from {}",
+                hex::encode(bllvm_protocol::block::calculate_tx_id(tx)),
+                peer_addr
             );
+        }
+        true
+    }
 
-            // Check if we should auto-ban
-            if self.dos_protection.should_auto_ban(ip).await {
-                warn!(
-                    "Auto-banning IP {} for repeated connection rate violations",
-                    ip
+    /// Handle a compact block (BIP152)
+    ///
+    /// Reconstructs the block from mempool and orphan pool transactions and
+    /// requests only the missing ones with `getblocktxn`. Short ID collisions
+    /// fall back to requesting the full block.
+    async fn handle_compact_block(
+        &self,
+        peer_addr: SocketAddr,
+        compact_block: compact_blocks::CompactBlock,
+    ) -> Result<()> {
+        use crate::network::protocol::GetBlockTxnMessage;
+
+        let block_hash = compact_blocks::calculate_block_hash(&compact_block.header);
+        let mut pool = self
+            .mempool_manager
+            .as_ref()
+            .map(|mempool| mempool.get_transactions())
+            .unwrap_or_default();
+        pool.extend(self.orphan_pool.lock().await.transactions().cloned());
+
+        let partial = match compact_blocks::PartiallyDownloadedBlock::new(&compact_block, &pool) {
+            Ok(partial) => partial,
+            Err(e) => {
+                debug!(
+                    "Can't reconstruct compact block {} from {}: {}",
+                    hex::encode(block_hash),
+                    peer_addr,
+                    e
                 );
-                // Ban the IP using configured ban duration
-                let ban_duration = self.dos_protection.ban_duration_seconds();
-                let unban_timestamp = current_timestamp() + ban_duration;
-                let mut ban_list = self.ban_list.write().await;
-                ban_list.insert(addr, unban_timestamp);
-                return Err(anyhow::anyhow!(
-                    "IP {} is banned due to connection rate violations",
-                    ip
-                ));
+                return self.request_full_block(peer_addr, block_hash).await;
             }
-
-            return Err(anyhow::anyhow!(
-                "Connection rate limit exceeded for IP {}",
-                ip
-            ));
+        };
+        if partial.is_complete() {
+            return self
+                .finish_compact_block(peer_addr, block_hash, partial, Vec::new())
+                .await;
         }
 
-        // Eclipse attack prevention: check IP diversity
-        if !self.check_eclipse_prevention(ip) {
-            let prefix = self.get_ip_prefix(ip);
-            warn!("Eclipse attack prevention: too many connections from IP range {:?}, rejecting connection from {}", 
-                  prefix, ip);
-            return Err(anyhow::anyhow!(
-                "Eclipse attack prevention: too many connections from IP range"
-            ));
+        let Ok(indices) = partial
+            .missing_indices()
+            .into_iter()
+            .map(u16::try_from)
+            .collect::<std::result::Result<Vec<u16>, _>>()
+        else {
+            return self.request_full_block(peer_addr, block_hash).await;
+        };
+        debug!(
+            "Requesting {} missing transactions of compact block {} from {}",
+            indices.len(),
+            hex::encode(block_hash),
+            peer_addr
+        );
+        {
+            let mut partials = self.partial_compact_blocks.lock().await;
+            if partials.len() >= MAX_PARTIAL_COMPACT_BLOCKS && !partials.contains_key(&block_hash) {
+                drop(partials);
+                return self.request_full_block(peer_addr, block_hash).await;
+            }
+            partials.insert(block_hash, (peer_addr, partial));
         }
+        let message =
+            ProtocolParser::serialize_message(&ProtocolMessage::GetBlockTxn(GetBlockTxnMessage {
+                block_hash,
+                indices,
+            }))?;
+        self.send_to_peer(peer_addr, message).await
+    }
 
-        let mut last_error = None;
-
-        // Try transports in preference order with graceful degradation
-        let transports_to_try = self.get_transports_for_connection();
+    /// Handle `blocktxn`: complete a compact block we requested transactions for
+    async fn handle_block_txn(
+        &self,
+        peer_addr: SocketAddr,
+        msg: crate::network::protocol::BlockTxnMessage,
+    ) -> Result<()> {
+        let partial = {
+            let mut partials = self.partial_compact_blocks.lock().await;
+            match partials.get(&msg.block_hash) {
+                Some((from, _)) if *from == peer_addr => {
+                    partials.remove(&msg.block_hash).map(|(_, partial)| partial)
+                }
+                _ => None,
+            }
+        };
+        let Some(partial) = partial else {
+            debug!(
+                "Ignoring unrequested blocktxn for {} from {}",
+                hex::encode(msg.block_hash),
+                peer_addr
+            );
+            return Ok(());
+        };
+        self.finish_compact_block(peer_addr, msg.block_hash, partial, msg.transactions)
+            .await
+    }
 
-        for transport_type in transports_to_try {
-            match self.try_connect_with_transport(&transport_type, addr).await {
-                Ok((peer, transport_addr)) => {
-                    // Successfully connected
-                    {
-                        let mut pm = self.peer_manager.lock().await;
-                        pm.add_peer(transport_addr.clone(), peer)?;
+    /// Complete a compact block and hand it to the node
+    ///
+    /// If the block doesn't verify, the full block is requested instead.
+    async fn finish_compact_block(
+        &self,
+        peer_addr: SocketAddr,
+        block_hash: Hash,
+        partial: compact_blocks::PartiallyDownloadedBlock,
+        missing: Vec<bllvm_protocol::Transaction>,
+    ) -> Result<()> {
+        match partial.finish(missing) {
+            Ok(block) => {
+                debug!("Reconstructed compact block {}", hex::encode(block_hash));
+                self.orphan_pool
+                    .lock()
+                    .await
+                    .remove_for_block(&block.transactions);
+                {
+                    let mut sources = self.block_sources.lock().await;
+                    if sources.len() >= MAX_BLOCK_SOURCES {
+                        sources.clear();
                     }
-
-                    // Note: Peer handler is managed by Peer::from_transport_connection
-                    // No need to spawn additional handler task
-
-                    info!(
-                        "Successfully connected to {} via {:?} (transport: {:?})",
-                        addr, transport_type, transport_addr
-                    );
-                    return Ok(());
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to connect to {} via {:?}: {}",
-                        addr, transport_type, e
-                    );
-                    last_error = Some(e);
-                    // Continue to next transport
+                    sources.insert(block_hash, peer_addr);
                 }
+                let data = crate::node::block_processor::serialize_block(&block, &[]);
+                let _ = self.peer_tx.send(NetworkMessage::BlockReceived(data));
+                Ok(())
+            }
+            Err(e) => {
+                debug!(
+                    "Compact block {} from {} failed: {}",
+                    hex::encode(block_hash),
+                    peer_addr,
+                    e
+                );
+                self.request_full_block(peer_addr, block_hash).await
             }
         }
+    }
 
-        // All transports failed
-        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All transport attempts failed")))
+    /// Peer that sent a block received through `try_recv_block`, if known
+    pub async fn take_block_source(&self, block_hash: &Hash) -> Option<SocketAddr> {
+        self.block_sources.lock().await.remove(block_hash)
     }
 
-    /// Helper: Get list of transports to try for a connection
-    fn get_transports_for_connection(&self) -> Vec<crate::network::transport::TransportType> {
-        let mut transports = Vec::new();
+    /// Check a block's proof of work and merkle root before processing it
+    ///
+    /// Failures are reported as misbehavior. A block whose parent we don't
+    /// know isn't the peer's fault and is passed on. Returns false if the
+    /// block should be dropped.
+    async fn check_received_block(
+        &self,
+        peer_addr: SocketAddr,
+        block: &bllvm_protocol::Block,
+    ) -> bool {
+        let block_hash = compact_blocks::calculate_block_hash(&block.header);
+        let failure = if !bllvm_protocol::pow::check_proof_of_work(&block.header).unwrap_or(false) {
+            Some((dos_protection::MISBEHAVIOR_INVALID_POW, "high-hash"))
+        } else if block.transactions.is_empty() {
+            Some((
+                dos_protection::MISBEHAVIOR_MALFORMED_BLOCK,
+                "bad-blk-length",
+            ))
+        } else if bllvm_protocol::mining::calculate_merkle_root(&block.transactions).ok()
+            != Some(block.header.merkle_root)
+        {
+            Some((
+                dos_protection::MISBEHAVIOR_MALFORMED_BLOCK,
+                "bad-txnmrklroot",
+            ))
+        } else {
+            None
+        };
+        if let Some((points, reason)) = failure {
+            info!(
+                "Rejected block {} from {}: {}",
+                hex::encode(block_hash),
+                peer_addr,
+                reason
+            );
+            self.report_misbehavior(peer_addr, points, reason).await;
+            return false;
+        }
 
-        // Add transports in preference order
-        if self.transport_preference.allows_tcp() {
-            transports.push(crate::network::transport::TransportType::Tcp);
+        let parent_known = self.storage.as_ref().is_none_or(|storage| {
+            storage
+                .blocks()
+                .get_header(&block.header.prev_block_hash)
+                .ok()
+                .flatten()
+                .is_some()
+        });
+        if !parent_known {
+            debug!(
+                "Block {} from {} has unknown parent {}",
+                hex::encode(block_hash),
+                peer_addr,
+                hex::encode(block.header.prev_block_hash)
+            );
+            self.report_misbehavior(
+                peer_addr,
+                dos_protection::MISBEHAVIOR_UNKNOWN_PARENT,
+                "unknown parent",
+            )
+            .await;
         }
+        true
+    }
 
-        #[cfg(feature = "quinn")]
-        if self.transport_preference.allows_quinn() {
-            if let Some(ref _quinn) = self.quinn_transport {
-                transports.push(crate::network::transport::TransportType::Quinn);
+    /// Add misbehavior points to a peer, disconnecting or banning it at the
+    /// thresholds in `dos_protection`
+    ///
+    /// `noban` peers are scored but never disconnected.
+    pub async fn report_misbehavior(&self, peer_addr: SocketAddr, points: u32, reason: &str) {
+        use dos_protection::MisbehaviorAction;
+
+        let action = self
+            .dos_protection
+            .report_misbehavior(peer_addr, points, reason)
+            .await;
+        if !matches!(action, MisbehaviorAction::None)
+            && self
+                .peer_has_permission(peer_addr, NetPermissions::NOBAN)
+                .await
+        {
+            debug!("Not disconnecting noban peer {} ({})", peer_addr, reason);
+            return;
+        }
+        match action {
+            MisbehaviorAction::None => {}
+            MisbehaviorAction::Disconnect => {
+                warn!(
+                    "Disconnecting and discouraging {} for misbehavior ({})",
+                    peer_addr, reason
+                );
+                self.discourage(peer_addr.ip()).await;
+                self.disconnect_peer_by_socket(peer_addr).await;
+            }
+            MisbehaviorAction::Ban => {
+                warn!("Banning {} for misbehavior ({})", peer_addr, reason);
+                let unban_timestamp =
+                    current_timestamp() + self.dos_protection.ban_duration_seconds();
+                self.ban_list
+                    .write()
+                    .await
+                    .insert(peer_addr, unban_timestamp);
+                self.pending_ban_shares.lock().await.push((
+                    peer_addr,
+                    unban_timestamp,
+                    reason.to_string(),
+                ));
+                self.disconnect_peer_by_socket(peer_addr).await;
             }
         }
+    }
 
-        #[cfg(feature = "iroh")]
-        if self.transport_preference.allows_iroh() {
-            if let Some(ref _iroh) = self.iroh_transport {
-                transports.push(crate::network::transport::TransportType::Iroh);
+    /// Apply the upload target (`maxuploadtarget`) to a `getdata` request
+    ///
+    /// Once the target is reached, a peer asking for a historical block (one
+    /// more than `HISTORICAL_BLOCK_AGE_SECONDS` older than our tip) is
+    /// disconnected and `None` returned, unless it has the `download`
+    /// permission or is a block-relay-only connection. Recent blocks,
+    /// transactions and compact-block reconstruction (`getblocktxn`) are
+    /// never refused.
+    async fn check_upload_target(
+        &self,
+        peer_addr: SocketAddr,
+        msg: crate::network::protocol::GetDataMessage,
+    ) -> Option<crate::network::protocol::GetDataMessage> {
+        if !self.bandwidth_limits.upload_target_reached() {
+            return Some(msg);
+        }
+        let exempt = {
+            let pm = self.peer_manager.lock().await;
+            pm.find_transport_addr_by_socket(peer_addr)
+                .and_then(|addr| pm.get_peer(&addr))
+                .is_some_and(|peer| {
+                    peer.has_permission(NetPermissions::DOWNLOAD) || peer.is_block_relay_only()
+                })
+        };
+        if exempt {
+            return Some(msg);
+        }
+        let historical = msg
+            .inventory
+            .iter()
+            .filter(|item| inventory::is_block_inventory(item.inv_type))
+            .find(|item| self.is_historical_block(&item.hash));
+        if let Some(item) = historical {
+            info!(
+                "Upload target reached, disconnecting {} for requesting historical block {}",
+                peer_addr,
+                hex::encode(item.hash)
+            );
+            self.disconnect_peer_by_socket(peer_addr).await;
+            return None;
+        }
+        Some(msg)
+    }
+
+    /// Whether a stored block is more than `HISTORICAL_BLOCK_AGE_SECONDS`
+    /// older than the tip
+    fn is_historical_block(&self, hash: &Hash) -> bool {
+        let Some(storage) = self.storage.as_ref() else {
+            return false;
+        };
+        let header = storage.blocks().get_header(hash).ok().flatten();
+        let tip = storage.chain().get_tip_header().ok().flatten();
+        match (header, tip) {
+            (Some(header), Some(tip)) => {
+                tip.timestamp.saturating_sub(header.timestamp)
+                    > bandwidth::HISTORICAL_BLOCK_AGE_SECONDS
             }
+            _ => false,
         }
+    }
 
-        // Always try TCP as fallback if not already in list
-        if !transports
-            .iter()
-            .any(|t| matches!(t, crate::network::transport::TransportType::Tcp))
+    /// Check whether the connected peer at `peer_addr` has every flag in
+    /// `permission`
+    async fn peer_has_permission(&self, peer_addr: SocketAddr, permission: NetPermissions) -> bool {
+        let pm = self.peer_manager.lock().await;
+        pm.find_transport_addr_by_socket(peer_addr)
+            .and_then(|addr| pm.get_peer(&addr))
+            .is_some_and(|peer| peer.has_permission(permission))
+    }
+
+    /// Announce a transaction from a `forcerelay` peer that is already in
+    /// our mempool (and would otherwise not be relayed again)
+    async fn force_relay_known_transaction(
+        &self,
+        peer_addr: SocketAddr,
+        tx: &bllvm_protocol::Transaction,
+    ) {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        let Some(mempool) = self.mempool_manager.as_ref() else {
+            return;
+        };
+        let txid = calculate_tx_id(tx);
+        if mempool.get_transaction(&txid).is_none()
+            || !self
+                .peer_has_permission(peer_addr, NetPermissions::FORCERELAY)
+                .await
         {
-            transports.push(crate::network::transport::TransportType::Tcp);
+            return;
+        }
+        debug!("Force-relaying tx {} from {}", hex::encode(txid), peer_addr);
+        if let Err(e) = self
+            .announce_inventory(inventory::MSG_TX, txid, None, None)
+            .await
+        {
+            warn!("Failed to relay tx {}: {}", hex::encode(txid), e);
         }
+    }
 
-        transports
+    /// Request a full block with `getdata`
+    async fn request_full_block(&self, peer_addr: SocketAddr, block_hash: Hash) -> Result<()> {
+        use crate::network::protocol::{GetDataMessage, InventoryItem};
+
+        let message =
+            ProtocolParser::serialize_message(&ProtocolMessage::GetData(GetDataMessage {
+                inventory: vec![InventoryItem {
+                    inv_type: inventory::MSG_BLOCK,
+                    hash: block_hash,
+                }],
+            }))?;
+        self.send_to_peer(peer_addr, message).await
     }
 
-    /// Helper: Try connecting with a specific transport
-    async fn try_connect_with_transport(
+    /// Fetch a block whose header is known from one peer and store it
+    ///
+    /// Sends `getdata` to `peer_addr` and waits up to `timeout` for the block.
+    /// The block and its witnesses are stored but not connected, so this can
+    /// restore a block body a pruned node has discarded.
+    pub async fn fetch_block_from_peer(
         &self,
-        transport_type: &crate::network::transport::TransportType,
-        addr: SocketAddr,
-    ) -> Result<(peer::Peer, TransportAddr)> {
-        use crate::network::transport::TransportAddr;
-        // Peer is used via fully qualified path peer::Peer, no need to import
+        peer_addr: SocketAddr,
+        block_hash: Hash,
+        timeout: std::time::Duration,
+    ) -> Result<()> {
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        if storage.blocks().get_header(&block_hash)?.is_none() {
+            return Err(anyhow::anyhow!("Block header missing"));
+        }
+        if storage.blocks().has_block_body(&block_hash)? {
+            return Err(anyhow::anyhow!("Block already downloaded"));
+        }
+        if self
+            .peer_manager
+            .lock()
+            .await
+            .find_transport_addr_by_socket(peer_addr)
+            .is_none()
+        {
+            return Err(anyhow::anyhow!("Peer does not exist"));
+        }
 
-        match transport_type {
-            crate::network::transport::TransportType::Tcp => {
-                // Use TcpTransport to create connection properly
-                let tcp_addr = TransportAddr::Tcp(addr);
-                let tcp_conn = self.tcp_transport.connect(tcp_addr).await?;
-                let transport_addr = TransportAddr::Tcp(addr);
-                Ok((
-                    peer::Peer::from_transport_connection(
-                        tcp_conn,
-                        addr,
-                        transport_addr.clone(),
-                        self.peer_tx.clone(),
-                    ),
-                    transport_addr,
-                ))
-            }
-            #[cfg(feature = "quinn")]
-            crate::network::transport::TransportType::Quinn => {
-                if let Some(ref quinn) = self.quinn_transport {
-                    let quinn_addr = TransportAddr::Quinn(addr);
-                    let quinn_addr_clone = quinn_addr.clone();
-                    let conn = quinn.connect(quinn_addr_clone.clone()).await?;
-                    Ok((
-                        peer::Peer::from_transport_connection(
-                            conn,
-                            addr,
-                            quinn_addr_clone.clone(),
-                            self.peer_tx.clone(),
-                        ),
-                        quinn_addr_clone,
-                    ))
-                } else {
-                    Err(anyhow::anyhow!("Quinn transport not available"))
-                }
+        let (request_id, response) = self.request_block(peer_addr, block_hash, false).await?;
+        let data = match tokio::time::timeout(timeout, response).await {
+            Ok(Ok(data)) => data,
+            Ok(Err(_)) => {
+                self.forget_block_request(block_hash, request_id).await;
+                return Err(anyhow::anyhow!("Block request was dropped"));
             }
-            #[cfg(feature = "iroh")]
-            crate::network::transport::TransportType::Iroh => {
-                // Iroh requires public key, not SocketAddr
-                // For now, return error - would need peer discovery or address resolution
-                Err(anyhow::anyhow!(
-                    "Iroh transport requires public key, not SocketAddr"
-                ))
+            Err(_) => {
+                self.forget_block_request(block_hash, request_id).await;
+                return Err(anyhow::anyhow!(
+                    "Timed out waiting for block {} from {}",
+                    hex::encode(block_hash),
+                    peer_addr
+                ));
             }
+        };
+
+        let (block, witnesses) = crate::node::block_processor::parse_block_from_wire(&data)?;
+        storage.blocks().store_block(&block)?;
+        if !witnesses.is_empty() {
+            storage.blocks().store_witness(&block_hash, &witnesses)?;
         }
+        info!(
+            "Stored block {} fetched from {}",
+            hex::encode(block_hash),
+            peer_addr
+        );
+        Ok(())
     }
 
-    /// Send ping message to all connected peers
-    pub async fn ping_all_peers(&self) -> Result<()> {
-        use crate::network::protocol::{PingMessage, ProtocolMessage, ProtocolParser};
-        use std::time::{SystemTime, UNIX_EPOCH};
+    /// Request a block from `peer_addr` and track it until it arrives
+    ///
+    /// Returns the request id and a receiver for the serialized block.
+    async fn request_block(
+        &self,
+        peer_addr: SocketAddr,
+        block_hash: Hash,
+        download: bool,
+    ) -> Result<(u64, tokio::sync::oneshot::Receiver<Vec<u8>>)> {
+        let (request_id, response) = self.register_request(peer_addr);
+        self.requested_blocks.lock().await.insert(
+            block_hash,
+            BlockRequest {
+                peer_addr,
+                request_id,
+                download,
+            },
+        );
+        if let Err(e) = self.request_full_block(peer_addr, block_hash).await {
+            self.forget_block_request(block_hash, request_id).await;
+            return Err(e);
+        }
+        Ok((request_id, response))
+    }
 
-        // Generate nonce for ping
-        let nonce = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
+    /// Download a block from the peer with the fewest blocks in flight
+    ///
+    /// The block goes through normal block processing when it arrives; the
+    /// returned receiver is also handed the serialized block. If the peer
+    /// stalls, [`Self::check_block_download_stalls`] moves the request to
+    /// another peer. The receiver fails if the request is given up on.
+    pub async fn request_block_download(
+        &self,
+        block_hash: Hash,
+    ) -> Result<tokio::sync::oneshot::Receiver<Vec<u8>>> {
+        let peer_addr = self
+            .block_download_peer(None)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No peer to download block from"))?;
+        let (_, response) = self.request_block(peer_addr, block_hash, true).await?;
+        Ok(response)
+    }
 
-        let ping_msg = ProtocolMessage::Ping(PingMessage { nonce });
-        let wire_msg = ProtocolParser::serialize_message(&ping_msg)?;
+    /// Connected peer with the fewest blocks in flight, other than `exclude`
+    async fn block_download_peer(&self, exclude: Option<SocketAddr>) -> Option<SocketAddr> {
+        let peers = self.peer_manager.lock().await.peer_socket_addresses();
+        let requested = self.requested_blocks.lock().await;
+        peers
+            .into_iter()
+            .filter(|addr| Some(*addr) != exclude)
+            .min_by_key(|addr| {
+                requested
+                    .values()
+                    .filter(|req| req.peer_addr == *addr)
+                    .count()
+            })
+    }
 
-        let peer_addrs = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_addresses()
+    /// Move stalled block downloads to other peers
+    ///
+    /// A download not delivered within `block_stall_timeout_seconds` is
+    /// cancelled at the stalling peer, which is penalized, and requested from
+    /// the peer with the fewest blocks in flight. A download is given up on
+    /// once no other peer is available or it has been moved
+    /// `MAX_BLOCK_REQUEST_RETRIES` times. Returns the re-requested blocks.
+    pub async fn check_block_download_stalls(&self) -> Result<Vec<Hash>> {
+        let stall_timeout = self.network_timing_config.block_stall_timeout_seconds;
+        let now = current_timestamp();
+        let stalled: Vec<(Hash, BlockRequest)> = {
+            let requested = self.requested_blocks.lock().await;
+            let pending = self.pending_requests.lock().await;
+            requested
+                .iter()
+                .filter(|(_, req)| req.download)
+                .filter(|(_, req)| {
+                    pending
+                        .get(&req.request_id)
+                        .is_none_or(|p| now.saturating_sub(p.timestamp) >= stall_timeout)
+                })
+                .map(|(hash, req)| (*hash, *req))
+                .collect()
         };
-        for addr in peer_addrs {
-            // Convert TransportAddr to SocketAddr for send_to_peer, or use send_to_peer_by_transport
-            let addr_clone = addr.clone();
-            match addr_clone {
-                crate::network::transport::TransportAddr::Tcp(sock) => {
-                    if let Err(e) = self.send_to_peer(sock, wire_msg.clone()).await {
-                        warn!("Failed to ping peer {}: {}", sock, e);
-                    }
+
+        let mut rerequested = Vec::new();
+        for (block_hash, stalled_req) in stalled {
+            warn!(
+                "Block download stall: {} did not deliver block {} within {}s",
+                stalled_req.peer_addr,
+                hex::encode(block_hash),
+                stall_timeout
+            );
+            self.report_misbehavior(
+                stalled_req.peer_addr,
+                dos_protection::MISBEHAVIOR_BLOCK_STALL,
+                "block download stall",
+            )
+            .await;
+
+            let alternate = self.block_download_peer(Some(stalled_req.peer_addr)).await;
+            let moved_to = {
+                let mut requested = self.requested_blocks.lock().await;
+                if requested.get(&block_hash).map(|req| req.request_id)
+                    != Some(stalled_req.request_id)
+                {
+                    // Delivered or replaced meanwhile
+                    continue;
                 }
-                #[cfg(feature = "quinn")]
-                crate::network::transport::TransportAddr::Quinn(sock) => {
-                    if let Err(e) = self
-                        .send_to_peer_by_transport(addr_clone.clone(), wire_msg.clone())
-                        .await
-                    {
-                        warn!("Failed to ping peer {}: {}", sock, e);
+                let mut pending = self.pending_requests.lock().await;
+                match (alternate, pending.get_mut(&stalled_req.request_id)) {
+                    (Some(peer_addr), Some(req)) if req.retry_count < MAX_BLOCK_REQUEST_RETRIES => {
+                        req.peer_addr = peer_addr;
+                        req.timestamp = now;
+                        req.retry_count += 1;
+                        requested.insert(
+                            block_hash,
+                            BlockRequest {
+                                peer_addr,
+                                ..stalled_req
+                            },
+                        );
+                        Some(peer_addr)
                     }
-                }
-                #[cfg(feature = "iroh")]
-                crate::network::transport::TransportAddr::Iroh(_) => {
-                    if let Err(e) = self
-                        .send_to_peer_by_transport(addr_clone.clone(), wire_msg.clone())
-                        .await
-                    {
-                        warn!("Failed to ping peer {}: {}", addr_clone, e);
+                    _ => {
+                        requested.remove(&block_hash);
+                        pending.remove(&stalled_req.request_id);
+                        None
                     }
                 }
+            };
+
+            let Some(peer_addr) = moved_to else {
+                warn!("Giving up on downloading block {}", hex::encode(block_hash));
+                continue;
+            };
+            debug!(
+                "Re-requesting block {} from {}",
+                hex::encode(block_hash),
+                peer_addr
+            );
+            match self.request_full_block(peer_addr, block_hash).await {
+                Ok(()) => rerequested.push(block_hash),
+                Err(e) => {
+                    warn!("Failed to re-request block from {}: {}", peer_addr, e);
+                    self.forget_block_request(block_hash, stalled_req.request_id)
+                        .await;
+                }
             }
         }
+        Ok(rerequested)
+    }
 
-        Ok(())
+    /// Drop a block request that will not be answered
+    async fn forget_block_request(&self, block_hash: Hash, request_id: u64) {
+        let mut requested = self.requested_blocks.lock().await;
+        if requested.get(&block_hash).map(|req| req.request_id) == Some(request_id) {
+            requested.remove(&block_hash);
+        }
+        drop(requested);
+        self.pending_requests.lock().await.remove(&request_id);
     }
 
-    /// Try to receive a block message (non-blocking)
-    /// Returns Some(block_data) if a block was received, None otherwise
-    pub fn try_recv_block(&mut self) -> Option<Vec<u8>> {
-        use tokio::sync::mpsc::error::TryRecvError;
+    /// Hand a received block to the request waiting for it
+    ///
+    /// Returns true if the block answered a `fetch_block_from_peer` request,
+    /// which stores the block itself. Block downloads return false so the
+    /// block is processed as usual.
+    async fn complete_block_request(
+        &self,
+        peer_addr: SocketAddr,
+        msg: &crate::network::protocol::BlockMessage,
+    ) -> bool {
+        let block_hash = compact_blocks::calculate_block_hash(&msg.block.header);
+        let request = {
+            let mut requested = self.requested_blocks.lock().await;
+            match requested.get(&block_hash) {
+                Some(&req) if req.peer_addr == peer_addr => {
+                    requested.remove(&block_hash);
+                    req
+                }
+                _ => return false,
+            }
+        };
+        let data = crate::node::block_processor::serialize_block(&msg.block, &msg.witnesses);
+        self.complete_request(request.request_id, data) && !request.download
+    }
 
-        // Check for BlockReceived messages without blocking
-        loop {
-            match self.peer_rx.try_recv() {
-                Ok(NetworkMessage::BlockReceived(data)) => {
-                    return Some(data);
+    /// Send a single-item `inv` to peers that don't know it yet
+    ///
+    /// Transactions are queued for each peer's next trickle instead (see
+    /// [`Self::flush_tx_inventory`]). `only` restricts the announcement to one
+    /// peer. Returns the number of peers the item was announced to.
+    async fn announce_inventory(
+        &self,
+        inv_type: u32,
+        hash: Hash,
+        fee_rate_per_kvb: Option<u64>,
+        only: Option<&TransportAddr>,
+    ) -> Result<usize> {
+        use crate::network::protocol::{InvMessage, InventoryItem};
+
+        let message = ProtocolParser::serialize_message(&ProtocolMessage::Inv(InvMessage {
+            inventory: vec![InventoryItem { inv_type, hash }],
+        }))?;
+
+        // Pick recipients and mark the item known in one pass, so concurrent
+        // announcements of the same item don't go out twice
+        let mut queued = 0;
+        let recipients: Vec<TransportAddr> = {
+            let mut pm = self.peer_manager.lock().await;
+            let mut recipients = Vec::new();
+            for addr in pm.peer_addresses() {
+                if only.is_some_and(|only| *only != addr) {
+                    continue;
                 }
-                Ok(_) => {
-                    // Other message types, continue checking
+                let Some(peer) = pm.get_peer_mut(&addr) else {
+                    continue;
+                };
+                if peer.has_known_inventory(&hash) {
                     continue;
                 }
-                Err(TryRecvError::Empty) => {
-                    return None;
+                if inv_type == inventory::MSG_TX && peer.is_block_relay_only() {
+                    continue;
                 }
-                Err(TryRecvError::Disconnected) => {
-                    warn!("Network message channel disconnected");
-                    return None;
+                if matches!(fee_rate_per_kvb, Some(rate) if rate < peer.fee_filter())
+                    && !peer.has_permission(NetPermissions::RELAY)
+                {
+                    continue;
+                }
+                if inv_type == inventory::MSG_TX {
+                    peer.queue_tx_inv(hash);
+                    queued += 1;
+                    continue;
                 }
+                peer.add_known_inventory(hash);
+                recipients.push(addr);
+            }
+            recipients
+        };
+        if queued > 0 {
+            debug!(
+                "Queued {} for announcement to {} peers",
+                hex::encode(hash),
+                queued
+            );
+            return Ok(queued);
+        }
+
+        for addr in &recipients {
+            if let Err(e) = self
+                .send_to_peer_by_transport(addr.clone(), message.clone())
+                .await
+            {
+                warn!("Failed to announce inventory to {:?}: {}", addr, e);
             }
         }
+        debug!(
+            "Announced {} to {} peers",
+            hex::encode(hash),
+            recipients.len()
+        );
+        Ok(recipients.len())
     }
 
-    /// Process incoming network messages
-    pub async fn process_messages(&mut self) -> Result<()> {
-        // Track message queue size manually (unbounded channel doesn't have len())
-        let mut message_count = 0u64;
-        let mut last_metrics_update = std::time::SystemTime::now();
+    /// Send each peer its queued transaction announcements once its trickle
+    /// timer expires, coalesced into one `inv`
+    ///
+    /// Called from the node's main loop. Returns the number of peers an `inv`
+    /// was sent to.
+    pub async fn flush_tx_inventory(&self) -> Result<usize> {
+        self.flush_tx_inventory_at(current_timestamp_duration().as_micros() as u64)
+            .await
+    }
 
-        while let Some(message) = self.peer_rx.recv().await {
-            message_count += 1;
+    async fn flush_tx_inventory_at(&self, now_micros: u64) -> Result<usize> {
+        use crate::network::protocol::{InvMessage, InventoryItem};
+
+        let batches: Vec<(TransportAddr, Vec<Hash>)> = {
+            let mut pm = self.peer_manager.lock().await;
+            let mut batches = Vec::new();
+            for addr in pm.peer_addresses() {
+                if let Some(peer) = pm.get_peer_mut(&addr) {
+                    let txids = peer.take_due_tx_invs(now_micros);
+                    if !txids.is_empty() {
+                        batches.push((addr, txids));
+                    }
+                }
+            }
+            batches
+        };
 
-            // Update metrics periodically (every 100 messages or 10 seconds)
-            let now = std::time::SystemTime::now();
-            let should_update = message_count % 100 == 0
-                || now
-                    .duration_since(last_metrics_update)
-                    .unwrap_or_else(|_| std::time::Duration::from_secs(0))
-                    .as_secs()
-                    >= 10;
+        for (addr, txids) in &batches {
+            let message = ProtocolParser::serialize_message(&ProtocolMessage::Inv(InvMessage {
+                inventory: txids
+                    .iter()
+                    .map(|&hash| InventoryItem {
+                        inv_type: inventory::MSG_TX,
+                        hash,
+                    })
+                    .collect(),
+            }))?;
+            if let Err(e) = self.send_to_peer_by_transport(addr.clone(), message).await {
+                warn!("Failed to announce transactions to {:?}: {}", addr, e);
+            }
+        }
+        Ok(batches.len())
+    }
 
-            if should_update {
-                let pm = self.peer_manager.lock().await;
-                let active_connections = pm.peer_count();
-                let bytes_received = *self.bytes_received.lock().await;
-                let bytes_sent = *self.bytes_sent.lock().await;
-
-                // Approximate queue size (messages processed since last check)
-                let queue_size = message_count as usize;
+    /// Broadcast to reliable peers first, then others
+    /// Uses peer quality to prioritize reliable peers for critical messages
+    pub async fn broadcast_with_quality_priority(&self, message: Vec<u8>) -> Result<()> {
+        // Get reliable peers first, then drop lock before async operations
+        let reliable_peers = {
+            let pm = self.peer_manager.lock().await;
+            pm.select_reliable_peers()
+        };
 
-                // Check message queue size limit
-                if !self
-                    .dos_protection
-                    .check_message_queue_size(queue_size)
-                    .await
-                {
-                    warn!(
-                        "Message queue size limit exceeded (processed {} messages), potential DoS",
-                        queue_size
-                    );
-                    // Optionally detect DoS attack
-                    if self.dos_protection.detect_dos_attack().await {
-                        warn!("DoS attack detected - message queue and connections at high levels");
-                        // Could trigger automatic mitigation here (e.g., increase rate limits, ban aggressive IPs)
-                    }
-                    // Reset counter to prevent false positives
-                    // Note: Counter will be reset again after metrics update (line 2043),
-                    // but this early reset prevents false positives in DoS detection.
-                    // The assignment is intentional - value is used in next loop iteration.
-                    #[allow(unused_assignments)] // Used in next loop iteration
-                    {
-                        message_count = 0;
-                    }
-                }
+        // Send to reliable peers first
+        for addr in &reliable_peers {
+            if let Err(e) = self
+                .send_to_peer_by_transport(addr.clone(), message.clone())
+                .await
+            {
+                warn!("Failed to send to reliable peer {:?}: {}", addr, e);
+            }
+        }
 
-                self.dos_protection
-                    .update_metrics(active_connections, queue_size, bytes_received, bytes_sent)
-                    .await;
+        // Then send to remaining peers
+        let pm = self.peer_manager.lock().await;
+        let all_peers = pm.peer_addresses();
+        let remaining_peers: Vec<_> = all_peers
+            .iter()
+            .filter(|addr| !reliable_peers.contains(addr))
+            .collect();
+        drop(pm);
 
-                last_metrics_update = now;
-                message_count = 0; // Reset after update for next period
+        for addr in remaining_peers {
+            if let Err(e) = self
+                .send_to_peer_by_transport(addr.clone(), message.clone())
+                .await
+            {
+                warn!("Failed to send to peer {:?}: {}", addr, e);
             }
+        }
 
-            match message {
-                NetworkMessage::PeerConnected(addr) => {
-                    info!("Peer connected: {:?}", addr);
-                }
-                NetworkMessage::PeerDisconnected(addr) => {
-                    info!("Peer disconnected: {:?}", addr);
-                    let mut pm = self.peer_manager.lock().await;
+        Ok(())
+    }
 
-                    // Get peer quality score before removing
-                    let quality_score =
-                        pm.get_peer(&addr).map(|p| p.quality_score()).unwrap_or(0.5);
+    /// Send to best peer (highest quality score)
+    /// Returns the peer address used, or error if no peers available
+    pub async fn send_to_best_peer(&self, message: Vec<u8>) -> Result<TransportAddr> {
+        let pm = self.peer_manager.lock().await;
+        let best_peers = pm.select_best_peers(1);
+        drop(pm);
 
-                    // Remove peer directly using TransportAddr
-                    pm.remove_peer(&addr);
+        if let Some(addr) = best_peers.first() {
+            self.send_to_peer_by_transport(addr.clone(), message)
+                .await?;
+            Ok(addr.clone())
+        } else {
+            Err(anyhow::anyhow!("No peers available"))
+        }
+    }
 
-                    // Extract SocketAddr for reconnection tracking (only TCP/Quinn)
-                    if let Some(socket_addr) = match &addr {
-                        TransportAddr::Tcp(sock) => Some(*sock),
-                        #[cfg(feature = "quinn")]
-                        TransportAddr::Quinn(sock) => Some(*sock),
-                        #[cfg(feature = "iroh")]
-                        TransportAddr::Iroh(_) => None, // Iroh peers use different reconnection mechanism
-                    } {
-                        // Add to reconnection queue with exponential backoff
-                        let now = current_timestamp();
-                        // Add to reconnection queue with exponential backoff
-                        let mut reconnection_queue = self.peer_reconnection_queue.lock().await;
-                        reconnection_queue.insert(socket_addr, (0, now, quality_score));
-                        info!(
-                            "Added peer {} to reconnection queue (quality: {:.2})",
-                            socket_addr, quality_score
-                        );
-                    }
+    /// Send to reliable peer only (for critical operations)
+    /// Returns error if no reliable peers available
+    pub async fn send_to_reliable_peer(&self, message: Vec<u8>) -> Result<TransportAddr> {
+        let pm = self.peer_manager.lock().await;
+        let reliable_peers = pm.select_reliable_peers();
+        drop(pm);
 
-                    // Clean up per-IP connection count (only for TCP/Quinn, not Iroh)
-                    if let Some(ip) = match &addr {
-                        TransportAddr::Tcp(sock) => Some(sock.ip()),
-                        #[cfg(feature = "quinn")]
-                        TransportAddr::Quinn(sock) => Some(sock.ip()),
-                        #[cfg(feature = "iroh")]
-                        TransportAddr::Iroh(_) => None,
-                    } {
-                        let mut ip_connections = self.connections_per_ip.lock().await;
-                        if let Some(count) = ip_connections.get_mut(&ip) {
-                            *count = count.saturating_sub(1);
-                            if *count == 0 {
-                                ip_connections.remove(&ip);
-                            }
-                        }
-                    }
+        if reliable_peers.is_empty() {
+            return Err(anyhow::anyhow!("No reliable peers available"));
+        }
 
-                    // Clean up rate limiter (use SocketAddr for TCP/Quinn, or a key for Iroh)
-                    {
-                        let mut rates = self.peer_message_rates.lock().await;
-                        // For rate limiter, we need a key - use SocketAddr for TCP/Quinn
-                        // For Iroh, we could use a hash of the key, but for now just skip
-                        if let Some(sock_addr) = match &addr {
-                            TransportAddr::Tcp(sock) => Some(*sock),
-                            #[cfg(feature = "quinn")]
-                            TransportAddr::Quinn(sock) => Some(*sock),
-                            #[cfg(feature = "iroh")]
-                            TransportAddr::Iroh(_) => None,
-                        } {
-                            rates.remove(&sock_addr);
-                        }
-                    }
+        // Select best reliable peer
+        let pm = self.peer_manager.lock().await;
+        let best_reliable = reliable_peers.iter().max_by(|a, b| {
+            let score_a = pm.get_peer(a).map(|p| p.quality_score()).unwrap_or(0.0);
+            let score_b = pm.get_peer(b).map(|p| p.quality_score()).unwrap_or(0.0);
+            score_a
+                .partial_cmp(&score_b)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        drop(pm);
 
-                    // Clean up eclipse attack prevention tracking
-                    if let Some(ip) = match &addr {
-                        TransportAddr::Tcp(sock) => Some(sock.ip()),
-                        #[cfg(feature = "quinn")]
-                        TransportAddr::Quinn(sock) => Some(sock.ip()),
-                        #[cfg(feature = "iroh")]
-                        TransportAddr::Iroh(_) => None,
-                    } {
-                        self.remove_peer_diversity(ip);
-                    }
-                }
-                NetworkMessage::BlockReceived(data) => {
-                    info!("Block received: {} bytes", data.len());
-                    // Note: BlockReceived doesn't include peer address, so we can't track peer quality here
-                    // Peer quality tracking happens when blocks are successfully processed
-                    // Block processing handled via try_recv_block() in Node::run()
-                }
-                NetworkMessage::TransactionReceived(data) => {
-                    info!("Transaction received: {} bytes", data.len());
-                    // Note: TransactionReceived doesn't include peer address, so we can't track peer quality here
-                    // Peer quality tracking happens when transactions are successfully processed
-                    // Process transaction with consensus layer
-                }
-                NetworkMessage::InventoryReceived(data) => {
-                    info!("Inventory received: {} bytes", data.len());
-                    // Process inventory
-                }
-                #[cfg(feature = "utxo-commitments")]
-                NetworkMessage::GetUTXOSetReceived(data, peer_addr) => {
-                    info!(
-                        "GetUTXOSet received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
-                    );
-                    // Handle GetUTXOSet request
-                    self.handle_get_utxo_set_request(data, peer_addr).await?;
-                }
-                #[cfg(feature = "utxo-commitments")]
-                NetworkMessage::UTXOSetReceived(data, peer_addr) => {
-                    info!("UTXOSet received from {}: {} bytes", peer_addr, data.len());
-                    // Handle UTXOSet response (would notify waiting requests)
-                    // In full implementation, would match to pending request futures
-                }
-                #[cfg(feature = "utxo-commitments")]
-                NetworkMessage::GetFilteredBlockReceived(data, peer_addr) => {
-                    info!(
-                        "GetFilteredBlock received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
-                    );
-                    // Handle GetFilteredBlock request
-                    self.handle_get_filtered_block_request(data, peer_addr)
-                        .await?;
-                }
-                #[cfg(feature = "utxo-commitments")]
-                NetworkMessage::FilteredBlockReceived(data, peer_addr) => {
-                    info!(
-                        "FilteredBlock received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
-                    );
-                    // Handle FilteredBlock response (would notify waiting requests)
-                    // In full implementation, would match to pending request futures
-                }
-                #[cfg(feature = "stratum-v2")]
-                NetworkMessage::StratumV2MessageReceived(data, peer_addr) => {
-                    info!(
-                        "Stratum V2 message received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
-                    );
-                    // Handle Stratum V2 message
-                    // In full implementation, would:
-                    // 1. Route to StratumV2Server if server mode enabled
-                    // 2. Route to StratumV2Client if client mode enabled
-                    // 3. Send response back to peer
-                    // 4. Notify waiting futures via async message routing system
+        if let Some(addr) = best_reliable {
+            self.send_to_peer_by_transport(addr.clone(), message)
+                .await?;
+            Ok(addr.clone())
+        } else {
+            Err(anyhow::anyhow!("No reliable peers available"))
+        }
+    }
+
+    /// Send a message to a specific peer (by SocketAddr - for TCP/Quinn)
+    /// For Iroh peers, uses the socket_to_transport mapping
+    pub async fn send_to_peer(&self, addr: SocketAddr, message: Vec<u8>) -> Result<()> {
+        // Try to find transport address (TCP, Quinn, or Iroh mapping)
+        let transport_addr = {
+            let pm = self.peer_manager.lock().await;
+            pm.find_transport_addr_by_socket(addr).or_else(|| {
+                // Check socket_to_transport mapping (synchronous access needed)
+                // Note: This is a fallback, so we use try_lock to avoid blocking
+                // If we can't get the lock immediately, return None
+                self.socket_to_transport
+                    .try_lock()
+                    .ok()
+                    .and_then(|map| map.get(&addr).cloned())
+            })
+        };
+
+        if let Some(transport_addr) = transport_addr {
+            self.send_to_peer_by_transport(transport_addr, message)
+                .await
+        } else {
+            // Fallback: try TCP (for backward compatibility)
+            self.send_to_peer_by_transport(TransportAddr::Tcp(addr), message)
+                .await
+        }
+    }
+
+    /// Send a message to a specific peer (by TransportAddr - supports all transports)
+    /// Returns Ok even if peer doesn't exist (graceful no-op)
+    pub async fn send_to_peer_by_transport(
+        &self,
+        addr: TransportAddr,
+        message: Vec<u8>,
+    ) -> Result<()> {
+        let message_len = message.len();
+
+        // Check if peer exists and get sender channel before dropping lock
+        let sender = {
+            let pm = self.peer_manager.lock().await;
+            if let Some(peer) = pm.get_peer(&addr) {
+                peer.send_tx.clone()
+            } else {
+                // Peer doesn't exist - return Ok (graceful no-op)
+                return Ok(());
+            }
+        };
+
+        // Track bytes sent only if peer exists (async-safe)
+        self.track_bytes_sent(message_len as u64).await;
+
+        // Send message without holding the lock (unbounded channel, so send won't block)
+        sender
+            .send(message)
+            .map_err(|e| anyhow::anyhow!("Failed to send message to peer: {}", e))?;
+
+        // Record send after message is sent
+        let mut pm = self.peer_manager.lock().await;
+        if let Some(peer) = pm.get_peer_mut(&addr) {
+            peer.record_send(message_len);
+        }
+        Ok(())
+    }
+
+    /// Connect to a peer at the given address
+    ///
+    /// Attempts to connect using available transports with graceful degradation:
+    /// 1. Tries preferred transport (based on transport_preference)
+    /// 2. Falls back to TCP if preferred transport fails
+    /// 3. Returns error only if all transports fail
+    pub async fn connect_to_peer(&self, addr: SocketAddr) -> Result<()> {
+        self.connect_outbound(addr, false).await
+    }
+
+    /// Connect to a peer for block relay only
+    ///
+    /// No transactions or addresses are exchanged on the connection, which
+    /// makes it harder for an observer to map the network topology.
+    pub async fn connect_block_relay_only(&self, addr: SocketAddr) -> Result<()> {
+        self.connect_outbound(addr, true).await
+    }
+
+    /// Connect to a peer by host name, such as an onion service
+    ///
+    /// IP literals go through [`Self::connect_to_peer`]. Other hosts are
+    /// connected over TCP only, resolved by the proxy when one is configured,
+    /// and the peer is keyed by the requested target (see
+    /// [`host_peer_addr`](tcp_transport::host_peer_addr)). Returns that key.
+    pub async fn connect_to_host(&self, host: &str, port: u16) -> Result<SocketAddr> {
+        use crate::network::transport::TransportConnection;
+
+        if let Ok(ip) = host.parse::<std::net::IpAddr>() {
+            let addr = SocketAddr::new(ip, port);
+            self.connect_to_peer(addr).await?;
+            return Ok(addr);
+        }
+
+        let timeout = self.connect_timeout();
+        let conn = match crate::utils::with_custom_timeout(
+            self.tcp_transport.connect_host(host, port),
+            timeout,
+        )
+        .await
+        {
+            Ok(result) => result?,
+            Err(_) => {
+                self.connect_timeouts.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "Connect timeout: {}:{} did not connect within {}s",
+                    host,
+                    port,
+                    timeout.as_secs()
+                );
+                return Err(anyhow::anyhow!(
+                    "connect timeout after {}s",
+                    timeout.as_secs()
+                ));
+            }
+        };
+        let transport_addr = conn.peer_addr();
+        #[allow(irrefutable_let_patterns)]
+        let TransportAddr::Tcp(addr) = transport_addr
+        else {
+            return Err(anyhow::anyhow!("TCP connection without a TCP address"));
+        };
+        let peer = peer::Peer::from_transport_connection_with_bandwidth(
+            conn,
+            addr,
+            transport_addr.clone(),
+            self.peer_tx.clone(),
+            self.bandwidth_limits.for_peer(),
+        );
+        self.peer_manager
+            .lock()
+            .await
+            .add_peer(transport_addr, peer)?;
+        self.outbound_peers.lock().await.insert(addr);
+        info!("Successfully connected to {}:{} as {}", host, port, addr);
+        Ok(addr)
+    }
+
+    async fn connect_outbound(&self, addr: SocketAddr, block_relay_only: bool) -> Result<()> {
+        // Check DoS protection: connection rate limiting (for outgoing connections too)
+        let ip = addr.ip();
+        if !self.dos_protection.check_connection(ip).await {
+            warn!(
+                "Connection rate limit exceeded for IP {}, rejecting outgoing connection",
+                ip
+            );
+
+            // Check if we should auto-ban
+            if self.dos_protection.should_auto_ban(ip).await {
+                warn!(
+                    "Auto-banning IP {} for repeated connection rate violations",
+                    ip
+                );
+                // Ban the IP using configured ban duration
+                let ban_duration = self.dos_protection.ban_duration_seconds();
+                let unban_timestamp = current_timestamp() + ban_duration;
+                let mut ban_list = self.ban_list.write().await;
+                ban_list.insert(addr, unban_timestamp);
+                self.pending_ban_shares.lock().await.push((
+                    addr,
+                    unban_timestamp,
+                    "connection rate violations".to_string(),
+                ));
+                return Err(anyhow::anyhow!(
+                    "IP {} is banned due to connection rate violations",
+                    ip
+                ));
+            }
+
+            return Err(anyhow::anyhow!(
+                "Connection rate limit exceeded for IP {}",
+                ip
+            ));
+        }
+
+        // Eclipse attack prevention: check IP diversity
+        if !self.check_eclipse_prevention(ip) {
+            let prefix = self.get_ip_prefix(ip);
+            warn!("Eclipse attack prevention: too many connections from IP range {:?}, rejecting connection from {}", 
+                  prefix, ip);
+            return Err(anyhow::anyhow!(
+                "Eclipse attack prevention: too many connections from IP range"
+            ));
+        }
+
+        let mut last_error = None;
+        self.address_database.write().await.mark_attempt(&addr);
+
+        // Try transports in preference order with graceful degradation
+        let transports_to_try = self.get_transports_for_connection();
+
+        for transport_type in transports_to_try {
+            match self.try_connect_with_transport(&transport_type, addr).await {
+                Ok((mut peer, transport_addr)) => {
+                    // Successfully connected
+                    peer.set_block_relay_only(block_relay_only);
+                    {
+                        let mut pm = self.peer_manager.lock().await;
+                        pm.add_peer(transport_addr.clone(), peer)?;
+                    }
+                    self.outbound_peers.lock().await.insert(addr);
+                    self.address_database.write().await.mark_good(&addr);
+
+                    // Note: Peer handler is managed by Peer::from_transport_connection
+                    // No need to spawn additional handler task
 
-                    // Server is active if we're processing messages (this function is called)
-                    // and route message to server.handle_message()
-                    // For now, just log the message
-                }
-                // BIP157 Block Filter messages
-                NetworkMessage::GetCfiltersReceived(data, peer_addr) => {
-                    info!(
-                        "GetCfilters received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
-                    );
-                    self.handle_getcfilters_request(data, peer_addr).await?;
-                }
-                // BIP331 Package Relay
-                NetworkMessage::PkgTxnReceived(data, peer_addr) => {
-                    info!("PkgTxn received from {}: {} bytes", peer_addr, data.len());
-                    self.handle_pkgtxn_request(data, peer_addr).await?;
-                }
-                NetworkMessage::SendPkgTxnReceived(data, _peer_addr) => {
-                    info!("SendPkgTxn received: {} bytes", data.len());
-                    // Optional: We can decide whether to request package
-                }
-                NetworkMessage::GetCfheadersReceived(data, peer_addr) => {
                     info!(
-                        "GetCfheaders received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
+                        "Successfully connected to {} via {:?} (transport: {:?})",
+                        addr, transport_type, transport_addr
                     );
-                    self.handle_getcfheaders_request(data, peer_addr).await?;
+                    return Ok(());
                 }
-                NetworkMessage::GetCfcheckptReceived(data, peer_addr) => {
-                    info!(
-                        "GetCfcheckpt received from {}: {} bytes",
-                        peer_addr,
-                        data.len()
+                Err(e) => {
+                    warn!(
+                        "Failed to connect to {} via {:?}: {}",
+                        addr, transport_type, e
                     );
-                    self.handle_getcfcheckpt_request(data, peer_addr).await?;
+                    last_error = Some(e);
+                    // Continue to next transport
                 }
-                // Raw messages from peer connections
-                NetworkMessage::RawMessageReceived(data, peer_addr) => {
-                    // Update peer receive stats - drop lock before async operations
-                    {
-                        let mut pm = self.peer_manager.lock().await;
-                        // Try to find peer by SocketAddr (TCP or Quinn, or Iroh mapping)
-                        let transport_addr = pm.find_transport_addr_by_socket(peer_addr);
-                        if let Some(transport_addr) = transport_addr {
-                            if let Some(peer) = pm.get_peer_mut(&transport_addr) {
-                                peer.record_receive(data.len());
-                            }
-                        }
-                    }
+            }
+        }
 
-                    // Check rate limiting before processing - drop lock before async
-                    // Note: transport_addr_opt was previously computed but not used - removed for now
-                    let should_process = {
-                        let mut rates = self.peer_message_rates.lock().await;
-                        let rate_limiter = rates.entry(peer_addr).or_insert_with(|| {
-                            // Default: 100 burst, 10 messages/second
-                            PeerRateLimiter::new(100, 10)
-                        });
-                        rate_limiter.check_and_consume()
-                    };
-
-                    if !should_process {
-                        warn!(
-                            "Rate limit exceeded for peer {}, dropping message",
-                            peer_addr
-                        );
-                        // Optionally ban peer after repeated rate limit violations
-                        // For now, just drop the message
-                        continue;
-                    }
+        // All transports failed
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("All transport attempts failed")))
+    }
 
-                    // Check if this is a response to a pending request (UTXOSet or FilteredBlock)
-                    // Extract request_id from message and route to correct pending request
-                    if let Ok(parsed) = ProtocolParser::parse_message(&data) {
-                        let request_id_opt = match &parsed {
-                            ProtocolMessage::UTXOSet(msg) => Some(msg.request_id),
-                            ProtocolMessage::FilteredBlock(msg) => Some(msg.request_id),
-                            _ => None,
-                        };
+    /// Helper: Get list of transports to try for a connection
+    fn get_transports_for_connection(&self) -> Vec<crate::network::transport::TransportType> {
+        let mut transports = Vec::new();
 
-                        if let Some(request_id) = request_id_opt {
-                            // Route to pending request by request_id
-                            let mut pending = self.pending_requests.lock().await;
-                            if let Some(pending_req) = pending.remove(&request_id) {
-                                drop(pending); // Release lock before sending
-                                let _ = pending_req.sender.send(data.clone());
-                                continue; // Skip normal processing for async responses
-                            } else {
-                                warn!("Received response for unknown request_id: {}", request_id);
-                            }
-                        }
-                    }
+        // Add transports in preference order
+        if self.transport_preference.allows_tcp() {
+            transports.push(crate::network::transport::TransportType::Tcp);
+        }
+
+        #[cfg(feature = "quinn")]
+        if self.transport_preference.allows_quinn() {
+            if let Some(ref _quinn) = self.quinn_transport {
+                transports.push(crate::network::transport::TransportType::Quinn);
+            }
+        }
+
+        #[cfg(feature = "iroh")]
+        if self.transport_preference.allows_iroh() {
+            if let Some(ref _iroh) = self.iroh_transport {
+                transports.push(crate::network::transport::TransportType::Iroh);
+            }
+        }
+
+        // Always try TCP as fallback if not already in list
+        if !transports
+            .iter()
+            .any(|t| matches!(t, crate::network::transport::TransportType::Tcp))
+        {
+            transports.push(crate::network::transport::TransportType::Tcp);
+        }
+
+        transports
+    }
+
+    /// Helper: Try connecting with a specific transport
+    async fn try_connect_with_transport(
+        &self,
+        transport_type: &crate::network::transport::TransportType,
+        addr: SocketAddr,
+    ) -> Result<(peer::Peer, TransportAddr)> {
+        use crate::network::transport::TransportAddr;
+        // Peer is used via fully qualified path peer::Peer, no need to import
+
+        match transport_type {
+            crate::network::transport::TransportType::Tcp => {
+                // Use TcpTransport to create connection properly
+                let tcp_addr = TransportAddr::Tcp(addr);
+                let tcp_conn = connect_with_timeout(
+                    &self.tcp_transport,
+                    tcp_addr,
+                    self.connect_timeout(),
+                    &self.connect_timeouts,
+                )
+                .await?;
+                let transport_addr = TransportAddr::Tcp(addr);
+                Ok((
+                    peer::Peer::from_transport_connection_with_bandwidth(
+                        tcp_conn,
+                        addr,
+                        transport_addr.clone(),
+                        self.peer_tx.clone(),
+                        self.bandwidth_limits.for_peer(),
+                    ),
+                    transport_addr,
+                ))
+            }
+            #[cfg(feature = "quinn")]
+            crate::network::transport::TransportType::Quinn => {
+                if let Some(ref quinn) = self.quinn_transport {
+                    let quinn_addr = TransportAddr::Quinn(addr);
+                    let quinn_addr_clone = quinn_addr.clone();
+                    let conn = connect_with_timeout(
+                        quinn,
+                        quinn_addr_clone.clone(),
+                        self.connect_timeout(),
+                        &self.connect_timeouts,
+                    )
+                    .await?;
+                    Ok((
+                        peer::Peer::from_transport_connection_with_bandwidth(
+                            conn,
+                            addr,
+                            quinn_addr_clone.clone(),
+                            self.peer_tx.clone(),
+                            self.bandwidth_limits.for_peer(),
+                        ),
+                        quinn_addr_clone,
+                    ))
+                } else {
+                    Err(anyhow::anyhow!("Quinn transport not available"))
+                }
+            }
+            #[cfg(feature = "iroh")]
+            crate::network::transport::TransportType::Iroh => {
+                // Iroh requires public key, not SocketAddr
+                // For now, return error - would need peer discovery or address resolution
+                Err(anyhow::anyhow!(
+                    "Iroh transport requires public key, not SocketAddr"
+                ))
+            }
+        }
+    }
+
+    /// How long an outbound connection may take to connect
+    /// (`request_timeouts.network_timeout_seconds`)
+    fn connect_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.request_timeout_config.network_timeout_seconds)
+    }
+
+    /// Whether the connection to `peer_addr` is block-relay-only
+    async fn is_block_relay_only(&self, peer_addr: SocketAddr) -> bool {
+        let pm = self.peer_manager.lock().await;
+        pm.find_transport_addr_by_socket(peer_addr)
+            .and_then(|addr| pm.get_peer(&addr))
+            .is_some_and(|peer| peer.is_block_relay_only())
+    }
+
+    /// `relay` flag for the version message sent to `peer_addr`
+    ///
+    /// False on block-relay-only connections, asking the peer not to
+    /// announce transactions.
+    pub async fn version_relay(&self, peer_addr: SocketAddr) -> bool {
+        !self.is_block_relay_only(peer_addr).await
+    }
+
+    /// Handle a peer's `version` message
+    ///
+    /// Records the advertised version and services. Peers below
+    /// `min_peer_protocol_version` are disconnected and `false` is returned.
+    async fn handle_version(&self, peer_addr: SocketAddr, msg: &VersionMessage) -> bool {
+        if let Err(e) =
+            version_negotiation::check_min_version(msg.version, self.min_peer_protocol_version)
+        {
+            warn!("Disconnecting {}: {}", peer_addr, e);
+            self.disconnect_peer_by_socket(peer_addr).await;
+            return false;
+        }
+
+        {
+            let mut pm = self.peer_manager.lock().await;
+            if let Some(addr) = pm.find_transport_addr_by_socket(peer_addr) {
+                if let Some(peer) = pm.get_peer_mut(&addr) {
+                    peer.version_negotiation_mut()
+                        .record_version(msg.version, msg.services);
+                    peer.set_relay_txs(msg.relay);
+                }
+            }
+        }
+
+        let mut peer_states = self.peer_states.write().await;
+        let peer_state = peer_states
+            .entry(peer_addr)
+            .or_insert_with(bllvm_protocol::network::PeerState::new);
+        peer_state.version = msg.version as u32;
+        peer_state.services = msg.services;
+        drop(peer_states);
+
+        // The peer tells us which address it sees us at
+        let seen_addr = SocketAddr::new(
+            local_address::network_address_ip(&msg.addr_recv),
+            self.advertised_port(),
+        );
+        if self.local_addresses.lock().await.seen(seen_addr) {
+            debug!("{} sees us at {}", peer_addr, seen_addr);
+        }
+        true
+    }
+
+    /// Register configured external IPs and start UPnP port mapping
+    async fn start_local_address_discovery(&self) {
+        let port = self.advertised_port();
+        {
+            let mut local_addresses = self.local_addresses.lock().await;
+            for ip in &self.external_ips {
+                let addr = SocketAddr::new(*ip, port);
+                if !local_addresses.add(addr, local_address::LocalAddressSource::Manual) {
+                    warn!("Ignoring external IP {}: not publicly routable", ip);
+                }
+            }
+        }
+        if !self.upnp_enabled {
+            return;
+        }
+
+        // Keep the mapping alive; the gateway may also change its external IP
+        let local_addresses = Arc::clone(&self.local_addresses);
+        self.shutdown.spawn(async move {
+            loop {
+                match map_port_with_upnp(port).await {
+                    Ok(external_ip) => {
+                        let addr = SocketAddr::new(external_ip, port);
+                        if local_addresses
+                            .lock()
+                            .await
+                            .add(addr, local_address::LocalAddressSource::Upnp)
+                        {
+                            info!("UPnP mapped port {}, external address {}", port, addr);
+                        }
+                    }
+                    Err(e) => warn!("UPnP port mapping failed: {}", e),
+                }
+                tokio::time::sleep(upnp::UPNP_REFRESH_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Remove a peer and notify the message loop that it disconnected
+    async fn disconnect_peer_by_socket(&self, peer_addr: SocketAddr) {
+        let removed = {
+            let mut pm = self.peer_manager.lock().await;
+            let addr = pm.find_transport_addr_by_socket(peer_addr);
+            if let Some(ref addr) = addr {
+                pm.remove_peer(addr);
+            }
+            addr
+        };
+        if let Some(addr) = removed {
+            let _ = self.peer_tx.send(NetworkMessage::PeerDisconnected(addr));
+        }
+    }
+
+    /// Handle a peer's `verack` message
+    ///
+    /// Outbound peers are asked for their mempool if
+    /// `relay.request_mempool_on_connect` is set.
+    async fn handle_verack(&self, peer_addr: SocketAddr) -> Result<()> {
+        let outbound = {
+            let mut pm = self.peer_manager.lock().await;
+            let Some(addr) = pm.find_transport_addr_by_socket(peer_addr) else {
+                return Ok(());
+            };
+            let Some(peer) = pm.get_peer_mut(&addr) else {
+                return Ok(());
+            };
+            peer.version_negotiation_mut().record_verack();
+            !peer.is_inbound()
+        };
+        if outbound && self.request_mempool_on_connect {
+            debug!("Requesting mempool from {}", peer_addr);
+            let message = ProtocolParser::serialize_message(&ProtocolMessage::MemPool)?;
+            self.send_to_peer(peer_addr, message).await?;
+        }
+        Ok(())
+    }
+
+    /// Answer a `mempool` request with an `inv` of our mempool transactions
+    ///
+    /// Peers with the `mempool` permission are always answered. Others only
+    /// if `relay.accept_mempool_requests` is set, and at most once per
+    /// `MEMPOOL_REQUEST_INTERVAL_SECONDS`. Peers that asked for no transaction
+    /// relay are never answered. Transactions below the peer's feefilter are
+    /// left out unless it has the `relay` permission; at most
+    /// `MAX_MEMPOOL_INV_ENTRIES` are announced, highest fee rate first, by the
+    /// mempool's cached fee rates (see
+    /// [`MempoolManager::cached_fee_rates_per_kvb`]).
+    async fn handle_mempool_request(&self, peer_addr: SocketAddr) -> Result<()> {
+        use crate::network::protocol::{InvMessage, InventoryItem};
+
+        let Some(mempool) = self.mempool_manager.as_ref() else {
+            return Ok(());
+        };
+
+        let fee_filter = {
+            let mut pm = self.peer_manager.lock().await;
+            let Some(addr) = pm.find_transport_addr_by_socket(peer_addr) else {
+                return Ok(());
+            };
+            let Some(peer) = pm.get_peer_mut(&addr) else {
+                return Ok(());
+            };
+            let permitted = peer.has_permission(NetPermissions::MEMPOOL);
+            if !self.accept_mempool_requests && !permitted {
+                debug!("Ignoring mempool request from {}: not allowed", peer_addr);
+                return Ok(());
+            }
+            if !peer.relay_txs() || peer.is_block_relay_only() {
+                debug!("Ignoring mempool request from {}: tx relay off", peer_addr);
+                return Ok(());
+            }
+            if !permitted
+                && !peer
+                    .allow_mempool_request(current_timestamp(), MEMPOOL_REQUEST_INTERVAL_SECONDS)
+            {
+                debug!("Ignoring mempool request from {}: too frequent", peer_addr);
+                return Ok(());
+            }
+            if peer.has_permission(NetPermissions::RELAY) {
+                0
+            } else {
+                peer.fee_filter()
+            }
+        };
+
+        let mut fee_rates: Vec<(Hash, u64)> = mempool
+            .cached_fee_rates_per_kvb()
+            .into_iter()
+            .filter(|(_, fee_rate)| *fee_rate >= fee_filter)
+            .collect();
+        fee_rates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        fee_rates.truncate(MAX_MEMPOOL_INV_ENTRIES);
+
+        {
+            let mut pm = self.peer_manager.lock().await;
+            if let Some(peer) = pm
+                .find_transport_addr_by_socket(peer_addr)
+                .and_then(|addr| pm.get_peer_mut(&addr))
+            {
+                for (txid, _) in &fee_rates {
+                    peer.add_known_inventory(*txid);
+                }
+            }
+        }
+        debug!(
+            "Answering mempool request from {} with {} transactions",
+            peer_addr,
+            fee_rates.len()
+        );
+        if fee_rates.is_empty() {
+            return Ok(());
+        }
+        let message = ProtocolParser::serialize_message(&ProtocolMessage::Inv(InvMessage {
+            inventory: fee_rates
+                .into_iter()
+                .map(|(hash, _)| InventoryItem {
+                    inv_type: inventory::MSG_TX,
+                    hash,
+                })
+                .collect(),
+        }))?;
+        self.send_to_peer(peer_addr, message).await
+    }
+
+    /// Check whether the negotiated version with a peer supports a feature
+    pub async fn peer_supports(
+        &self,
+        peer_addr: SocketAddr,
+        feature: version_negotiation::Feature,
+    ) -> bool {
+        let pm = self.peer_manager.lock().await;
+        pm.find_transport_addr_by_socket(peer_addr)
+            .and_then(|addr| pm.get_peer(&addr))
+            .is_some_and(|peer| peer.version_negotiation().supports(feature))
+    }
+
+    /// Send a feature announcement (sendheaders, feefilter, sendcmpct, sendaddrv2)
+    ///
+    /// The message is only sent if the negotiated version supports the feature
+    /// and the handshake is at the right stage (after `verack`, except for
+    /// `sendaddrv2`). Returns whether the message was sent.
+    pub async fn send_feature_message(
+        &self,
+        peer_addr: SocketAddr,
+        feature: version_negotiation::Feature,
+        message: Vec<u8>,
+    ) -> Result<bool> {
+        let allowed = {
+            let pm = self.peer_manager.lock().await;
+            pm.find_transport_addr_by_socket(peer_addr)
+                .and_then(|addr| pm.get_peer(&addr))
+                .is_some_and(|peer| peer.version_negotiation().can_send(feature))
+        };
+        if !allowed {
+            debug!(
+                "Not sending {} to {}: not allowed by negotiated version or handshake state",
+                feature.command(),
+                peer_addr
+            );
+            return Ok(false);
+        }
+        self.send_to_peer(peer_addr, message).await?;
+        Ok(true)
+    }
+
+    /// Update per-peer ping and block stats from an incoming message
+    async fn record_peer_stats(&self, peer_addr: SocketAddr, msg: &ProtocolMessage) {
+        if !matches!(
+            msg,
+            ProtocolMessage::Pong(_)
+                | ProtocolMessage::Block(_)
+                | ProtocolMessage::Inv(_)
+                | ProtocolMessage::Tx(_)
+                | ProtocolMessage::FeeFilter(_)
+        ) {
+            return;
+        }
+        let mut pm = self.peer_manager.lock().await;
+        let Some(transport_addr) = pm.find_transport_addr_by_socket(peer_addr) else {
+            return;
+        };
+        let Some(peer) = pm.get_peer_mut(&transport_addr) else {
+            return;
+        };
+        match msg {
+            ProtocolMessage::Pong(pong) => {
+                if let Some(rtt_ms) =
+                    peer.record_pong(pong.nonce, current_timestamp_duration().as_micros() as u64)
+                {
+                    debug!("Pong from {}: {:.3} ms", peer_addr, rtt_ms);
+                }
+            }
+            ProtocolMessage::Block(_) => peer.record_block_received(),
+            // Don't announce back what the peer told us about
+            ProtocolMessage::Inv(inv) => {
+                for item in &inv.inventory {
+                    peer.add_known_inventory(item.hash);
+                }
+            }
+            ProtocolMessage::Tx(tx) => {
+                use bllvm_protocol::block::calculate_tx_id;
+                peer.add_known_inventory(calculate_tx_id(&tx.transaction));
+            }
+            ProtocolMessage::FeeFilter(filter) => {
+                debug!("Feefilter from {}: {} sat/kvB", peer_addr, filter.feerate);
+                peer.set_fee_filter(filter.feerate);
+            }
+            _ => {}
+        }
+    }
+
+    /// Ping peers that are due and disconnect unresponsive ones
+    ///
+    /// A peer is disconnected if it hasn't completed the version handshake
+    /// within `handshake_timeout_seconds` of connecting, hasn't answered a
+    /// ping within `ping_timeout_seconds`, or hasn't sent anything within
+    /// `inactivity_timeout_seconds`. Returns the disconnected peers.
+    pub async fn check_peer_liveness(&self) -> Result<Vec<TransportAddr>> {
+        use crate::network::protocol::PingMessage;
+
+        let timing = &self.network_timing_config;
+        let ping_interval = std::time::Duration::from_secs(timing.ping_interval_seconds);
+        let ping_timeout = std::time::Duration::from_secs(timing.ping_timeout_seconds);
+        let now = current_timestamp();
+        let now_micros = current_timestamp_duration().as_micros() as u64;
+
+        let mut stale = Vec::new();
+        let mut pings = Vec::new();
+        {
+            let mut pm = self.peer_manager.lock().await;
+            for addr in pm.peer_addresses() {
+                let Some(peer) = pm.get_peer_mut(&addr) else {
+                    continue;
+                };
+                if !peer.version_negotiation().handshake_complete()
+                    && now.saturating_sub(peer.conntime()) >= timing.handshake_timeout_seconds
+                {
+                    warn!(
+                        "Handshake timeout: peer {} sent no version/verack, disconnecting",
+                        addr
+                    );
+                    self.handshake_timeouts.fetch_add(1, Ordering::Relaxed);
+                    stale.push(addr);
+                } else if peer.ping_timed_out(now_micros, ping_timeout) {
+                    warn!("Peer {} did not answer ping, disconnecting", addr);
+                    stale.push(addr);
+                } else if peer.is_inactive(now, timing.inactivity_timeout_seconds) {
+                    warn!("Peer {} inactive, disconnecting", addr);
+                    stale.push(addr);
+                } else if peer.ping_due(now_micros, ping_interval) {
+                    let nonce: u64 = rand::random();
+                    peer.record_ping_sent(nonce, now_micros);
+                    pings.push((addr, nonce));
+                }
+            }
+            for addr in &stale {
+                pm.remove_peer(addr);
+            }
+        }
+
+        for addr in &stale {
+            let _ = self
+                .peer_tx
+                .send(NetworkMessage::PeerDisconnected(addr.clone()));
+        }
+
+        for (addr, nonce) in pings {
+            let wire_msg =
+                ProtocolParser::serialize_message(&ProtocolMessage::Ping(PingMessage { nonce }))?;
+            if let Err(e) = self.send_to_peer_by_transport(addr.clone(), wire_msg).await {
+                warn!("Failed to ping peer {}: {}", addr, e);
+            }
+        }
+
+        Ok(stale)
+    }
+
+    /// Send ping message to all connected peers
+    ///
+    /// Each peer gets its own nonce, recorded so the matching pong yields the
+    /// round-trip time. Peers with an unanswered ping are skipped so their
+    /// ping timeout keeps running.
+    pub async fn ping_all_peers(&self) -> Result<()> {
+        use crate::network::protocol::{PingMessage, ProtocolMessage, ProtocolParser};
+
+        let now_micros = current_timestamp_duration().as_micros() as u64;
+        let pings: Vec<(TransportAddr, u64)> = {
+            let mut pm = self.peer_manager.lock().await;
+            let mut pings = Vec::new();
+            for addr in pm.peer_addresses() {
+                if let Some(peer) = pm.get_peer_mut(&addr) {
+                    if peer.ping_due(now_micros, std::time::Duration::ZERO) {
+                        let nonce: u64 = rand::random();
+                        peer.record_ping_sent(nonce, now_micros);
+                        pings.push((addr, nonce));
+                    }
+                }
+            }
+            pings
+        };
+
+        for (addr, nonce) in pings {
+            let ping_msg = ProtocolMessage::Ping(PingMessage { nonce });
+            let wire_msg = ProtocolParser::serialize_message(&ping_msg)?;
+            if let Err(e) = self.send_to_peer_by_transport(addr.clone(), wire_msg).await {
+                warn!("Failed to ping peer {}: {}", addr, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Try to receive a block message (non-blocking)
+    /// Returns Some(block_data) if a block was received, None otherwise
+    pub fn try_recv_block(&mut self) -> Option<Vec<u8>> {
+        use tokio::sync::mpsc::error::TryRecvError;
+
+        // Check for BlockReceived messages without blocking
+        loop {
+            match self.peer_rx.try_recv() {
+                Ok(NetworkMessage::BlockReceived(data)) => {
+                    return Some(data);
+                }
+                Ok(_) => {
+                    // Other message types, continue checking
+                    continue;
+                }
+                Err(TryRecvError::Empty) => {
+                    return None;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    warn!("Network message channel disconnected");
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Process incoming network messages
+    pub async fn process_messages(&mut self) -> Result<()> {
+        // Track message queue size manually (unbounded channel doesn't have len())
+        let mut message_count = 0u64;
+        let mut last_metrics_update = std::time::SystemTime::now();
+
+        while let Some(message) = self.peer_rx.recv().await {
+            message_count += 1;
+
+            // Update metrics periodically (every 100 messages or 10 seconds)
+            let now = std::time::SystemTime::now();
+            let should_update = message_count % 100 == 0
+                || now
+                    .duration_since(last_metrics_update)
+                    .unwrap_or_else(|_| std::time::Duration::from_secs(0))
+                    .as_secs()
+                    >= 10;
+
+            if should_update {
+                let pm = self.peer_manager.lock().await;
+                let active_connections = pm.peer_count();
+                let bytes_received = *self.bytes_received.lock().await;
+                let bytes_sent = *self.bytes_sent.lock().await;
+
+                // Approximate queue size (messages processed since last check)
+                let queue_size = message_count as usize;
+
+                // Check message queue size limit
+                if !self
+                    .dos_protection
+                    .check_message_queue_size(queue_size)
+                    .await
+                {
+                    warn!(
+                        "Message queue size limit exceeded (processed {} messages), potential DoS",
+                        queue_size
+                    );
+                    // Optionally detect DoS attack
+                    if self.dos_protection.detect_dos_attack().await {
+                        warn!("DoS attack detected - message queue and connections at high levels");
+                        // Could trigger automatic mitigation here (e.g., increase rate limits, ban aggressive IPs)
+                    }
+                    // Reset counter to prevent false positives
+                    // Note: Counter will be reset again after metrics update (line 2043),
+                    // but this early reset prevents false positives in DoS detection.
+                    // The assignment is intentional - value is used in next loop iteration.
+                    #[allow(unused_assignments)] // Used in next loop iteration
+                    {
+                        message_count = 0;
+                    }
+                }
+
+                self.dos_protection
+                    .update_metrics(active_connections, queue_size, bytes_received, bytes_sent)
+                    .await;
+
+                last_metrics_update = now;
+                message_count = 0; // Reset after update for next period
+            }
+
+            match message {
+                NetworkMessage::PeerConnected(addr) => {
+                    info!("Peer connected: {:?}", addr);
+                }
+                NetworkMessage::PeerDisconnected(addr) => {
+                    info!("Peer disconnected: {:?}", addr);
+                    let mut pm = self.peer_manager.lock().await;
+
+                    // Get peer quality score before removing
+                    let quality_score =
+                        pm.get_peer(&addr).map(|p| p.quality_score()).unwrap_or(0.5);
+
+                    // Remove peer directly using TransportAddr
+                    pm.remove_peer(&addr);
+
+                    // Extract SocketAddr for reconnection tracking (only TCP/Quinn)
+                    if let Some(socket_addr) = match &addr {
+                        TransportAddr::Tcp(sock) => Some(*sock),
+                        #[cfg(feature = "quinn")]
+                        TransportAddr::Quinn(sock) => Some(*sock),
+                        #[cfg(feature = "iroh")]
+                        TransportAddr::Iroh(_) => None, // Iroh peers use different reconnection mechanism
+                    } {
+                        let now = current_timestamp();
+                        let mut persistent_peers = self.persistent_peers.lock().await;
+                        if let Some(state) = persistent_peers.get_mut(&socket_addr) {
+                            // Persistent peers are reconnected by maintain_persistent_peers
+                            state.schedule_retry(
+                                now,
+                                self.network_timing_config.peer_connection_delay_seconds,
+                            );
+                            info!(
+                                "Persistent peer {} disconnected, reconnecting in {}s",
+                                socket_addr,
+                                state.next_attempt.saturating_sub(now)
+                            );
+                        } else {
+                            // Add to reconnection queue with exponential backoff
+                            let mut reconnection_queue = self.peer_reconnection_queue.lock().await;
+                            reconnection_queue.insert(socket_addr, (0, now, quality_score));
+                            info!(
+                                "Added peer {} to reconnection queue (quality: {:.2})",
+                                socket_addr, quality_score
+                            );
+                        }
+                    }
+
+                    // Clean up per-IP connection count (only for TCP/Quinn, not Iroh)
+                    if let Some(ip) = match &addr {
+                        TransportAddr::Tcp(sock) => Some(sock.ip()),
+                        #[cfg(feature = "quinn")]
+                        TransportAddr::Quinn(sock) => Some(sock.ip()),
+                        #[cfg(feature = "iroh")]
+                        TransportAddr::Iroh(_) => None,
+                    } {
+                        let ip = dos_protection::connection_key(ip);
+                        let mut ip_connections = self.connections_per_ip.lock().await;
+                        if let Some(count) = ip_connections.get_mut(&ip) {
+                            *count = count.saturating_sub(1);
+                            if *count == 0 {
+                                ip_connections.remove(&ip);
+                            }
+                        }
+                    }
+
+                    // Clean up rate limiter (use SocketAddr for TCP/Quinn, or a key for Iroh)
+                    {
+                        let mut rates = self.peer_message_rates.lock().await;
+                        // For rate limiter, we need a key - use SocketAddr for TCP/Quinn
+                        // For Iroh, we could use a hash of the key, but for now just skip
+                        if let Some(sock_addr) = match &addr {
+                            TransportAddr::Tcp(sock) => Some(*sock),
+                            #[cfg(feature = "quinn")]
+                            TransportAddr::Quinn(sock) => Some(*sock),
+                            #[cfg(feature = "iroh")]
+                            TransportAddr::Iroh(_) => None,
+                        } {
+                            rates.remove(&sock_addr);
+                        }
+                    }
+
+                    // Drop orphans and pending compact blocks from the peer
+                    if let Some(sock_addr) = match &addr {
+                        TransportAddr::Tcp(sock) => Some(*sock),
+                        #[cfg(feature = "quinn")]
+                        TransportAddr::Quinn(sock) => Some(*sock),
+                        #[cfg(feature = "iroh")]
+                        TransportAddr::Iroh(_) => None,
+                    } {
+                        self.orphan_pool.lock().await.remove_for_peer(sock_addr);
+                        self.partial_compact_blocks
+                            .lock()
+                            .await
+                            .retain(|_, (from, _)| *from != sock_addr);
+                    }
+
+                    // Clean up eclipse attack prevention tracking
+                    if let Some(ip) = match &addr {
+                        TransportAddr::Tcp(sock) => Some(sock.ip()),
+                        #[cfg(feature = "quinn")]
+                        TransportAddr::Quinn(sock) => Some(sock.ip()),
+                        #[cfg(feature = "iroh")]
+                        TransportAddr::Iroh(_) => None,
+                    } {
+                        self.remove_peer_diversity(ip);
+                    }
+                }
+                NetworkMessage::BlockReceived(data) => {
+                    info!("Block received: {} bytes", data.len());
+                    // Note: BlockReceived doesn't include peer address, so we can't track peer quality here
+                    // Peer quality tracking happens when blocks are successfully processed
+                    // Block processing handled via try_recv_block() in Node::run()
+                }
+                NetworkMessage::TransactionReceived(data) => {
+                    info!("Transaction received: {} bytes", data.len());
+                    // Note: TransactionReceived doesn't include peer address, so we can't track peer quality here
+                    // Peer quality tracking happens when transactions are successfully processed
+                    // Process transaction with consensus layer
+                }
+                NetworkMessage::InventoryReceived(data) => {
+                    info!("Inventory received: {} bytes", data.len());
+                    // Process inventory
+                }
+                #[cfg(feature = "utxo-commitments")]
+                NetworkMessage::GetUTXOSetReceived(data, peer_addr) => {
+                    info!(
+                        "GetUTXOSet received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    // Handle GetUTXOSet request
+                    self.handle_get_utxo_set_request(data, peer_addr).await?;
+                }
+                #[cfg(feature = "utxo-commitments")]
+                NetworkMessage::UTXOSetReceived(data, peer_addr) => {
+                    info!("UTXOSet received from {}: {} bytes", peer_addr, data.len());
+                    // Handle UTXOSet response (would notify waiting requests)
+                    // In full implementation, would match to pending request futures
+                }
+                #[cfg(feature = "utxo-commitments")]
+                NetworkMessage::GetFilteredBlockReceived(data, peer_addr) => {
+                    info!(
+                        "GetFilteredBlock received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    // Handle GetFilteredBlock request
+                    self.handle_get_filtered_block_request(data, peer_addr)
+                        .await?;
+                }
+                #[cfg(feature = "utxo-commitments")]
+                NetworkMessage::FilteredBlockReceived(data, peer_addr) => {
+                    info!(
+                        "FilteredBlock received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    // Handle FilteredBlock response (would notify waiting requests)
+                    // In full implementation, would match to pending request futures
+                }
+                #[cfg(feature = "stratum-v2")]
+                NetworkMessage::StratumV2MessageReceived(data, peer_addr) => {
+                    info!(
+                        "Stratum V2 message received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    // Handle Stratum V2 message
+                    // In full implementation, would:
+                    // 1. Route to StratumV2Server if server mode enabled
+                    // 2. Route to StratumV2Client if client mode enabled
+                    // 3. Send response back to peer
+                    // 4. Notify waiting futures via async message routing system
+
+                    // Server is active if we're processing messages (this function is called)
+                    // and route message to server.handle_message()
+                    // For now, just log the message
+                }
+                // BIP157 Block Filter messages
+                NetworkMessage::GetCfiltersReceived(data, peer_addr) => {
+                    info!(
+                        "GetCfilters received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    self.handle_getcfilters_request(data, peer_addr).await?;
+                }
+                // BIP331 Package Relay
+                NetworkMessage::PkgTxnReceived(data, peer_addr) => {
+                    info!("PkgTxn received from {}: {} bytes", peer_addr, data.len());
+                    self.handle_pkgtxn_request(data, peer_addr).await?;
+                }
+                NetworkMessage::SendPkgTxnReceived(data, _peer_addr) => {
+                    info!("SendPkgTxn received: {} bytes", data.len());
+                    // Optional: We can decide whether to request package
+                }
+                NetworkMessage::GetCfheadersReceived(data, peer_addr) => {
+                    info!(
+                        "GetCfheaders received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    self.handle_getcfheaders_request(data, peer_addr).await?;
+                }
+                NetworkMessage::GetCfcheckptReceived(data, peer_addr) => {
+                    info!(
+                        "GetCfcheckpt received from {}: {} bytes",
+                        peer_addr,
+                        data.len()
+                    );
+                    self.handle_getcfcheckpt_request(data, peer_addr).await?;
+                }
+                // Raw messages from peer connections
+                NetworkMessage::RawMessageReceived(data, peer_addr) => {
+                    // Update peer receive stats - drop lock before async operations
+                    {
+                        let mut pm = self.peer_manager.lock().await;
+                        // Try to find peer by SocketAddr (TCP or Quinn, or Iroh mapping)
+                        let transport_addr = pm.find_transport_addr_by_socket(peer_addr);
+                        if let Some(transport_addr) = transport_addr {
+                            if let Some(peer) = pm.get_peer_mut(&transport_addr) {
+                                peer.record_receive(data.len());
+                            }
+                        }
+                    }
+
+                    // Check rate limiting before processing
+                    if !self.check_message_rate(peer_addr).await {
+                        warn!(
+                            "Rate limit exceeded for peer {}, dropping message",
+                            peer_addr
+                        );
+                        // Optionally ban peer after repeated rate limit violations
+                        // For now, just drop the message
+                        continue;
+                    }
+
+                    // Check if this is a response to a pending request (UTXOSet or FilteredBlock)
+                    // Extract request_id from message and route to correct pending request
+                    if let Ok(parsed) = ProtocolParser::parse_message(&data) {
+                        let request_id_opt = match &parsed {
+                            ProtocolMessage::UTXOSet(msg) => Some(msg.request_id),
+                            ProtocolMessage::FilteredBlock(msg) => Some(msg.request_id),
+                            _ => None,
+                        };
+
+                        if let Some(request_id) = request_id_opt {
+                            // Route to pending request by request_id
+                            let mut pending = self.pending_requests.lock().await;
+                            if let Some(pending_req) = pending.remove(&request_id) {
+                                drop(pending); // Release lock before sending
+                                let _ = pending_req.sender.send(data.clone());
+                                continue; // Skip normal processing for async responses
+                            } else {
+                                warn!("Received response for unknown request_id: {}", request_id);
+                            }
+                        }
+                    }
+
+                    // Process through protocol layer
+                    if let Err(e) = self.handle_incoming_wire_tcp(peer_addr, data).await {
+                        if e.downcast_ref::<ProtocolError>().is_some() {
+                            // Malformed message: don't keep talking to this peer
+                            warn!("Malformed message from {}, disconnecting: {}", peer_addr, e);
+                            self.disconnect_peer_by_socket(peer_addr).await;
+                        } else {
+                            warn!("Failed to process message from {}: {}", peer_addr, e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Consume a message from the peer's rate limit budget
+    ///
+    /// Returns false if the message should be dropped. `noban` peers are
+    /// never limited; their messages are still fully validated.
+    async fn check_message_rate(&self, peer_addr: SocketAddr) -> bool {
+        if self.dos_protection.is_whitelisted(peer_addr.ip()) {
+            return true;
+        }
+        // `noban` granted by a whitebind is only known to the peer
+        if !self.whitebind.is_empty()
+            && self
+                .peer_has_permission(peer_addr, NetPermissions::NOBAN)
+                .await
+        {
+            return true;
+        }
+        let mut rates = self.peer_message_rates.lock().await;
+        let rate_limiter = rates.entry(peer_addr).or_insert_with(|| {
+            // Default: 100 burst, 10 messages/second
+            PeerRateLimiter::new(100, 10)
+        });
+        rate_limiter.check_and_consume()
+    }
+
+    /// Parse incoming TCP wire message and process with protocol layer
+    ///
+    /// This function:
+    /// 1. Converts ProtocolMessage to bllvm_protocol::network::NetworkMessage
+    /// 2. Gets or creates PeerState for this peer
+    /// 3. Creates NodeChainAccess from storage modules
+    /// 4. Calls bllvm_protocol::network::process_network_message()
+    /// 5. Converts NetworkResponse back to wire format and enqueues for sending
+    pub async fn handle_incoming_wire_tcp(
+        &self,
+        peer_addr: SocketAddr,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        // Track bytes received (async-safe)
+        self.track_bytes_received(data.len() as u64).await;
+
+        // Check if peer is banned
+        if self.is_banned(peer_addr) {
+            warn!("Rejecting message from banned peer: {}", peer_addr);
+            return Ok(()); // Silently drop messages from banned peers
+        }
+        let parsed = ProtocolParser::parse_message(&data)?;
+
+        // Block-relay-only connections carry no transactions
+        let parsed = match parsed {
+            ProtocolMessage::Inv(mut inv) if self.is_block_relay_only(peer_addr).await => {
+                inv.inventory
+                    .retain(|item| item.inv_type != inventory::MSG_TX);
+                if inv.inventory.is_empty() {
+                    return Ok(());
+                }
+                ProtocolMessage::Inv(inv)
+            }
+            ProtocolMessage::Tx(_) if self.is_block_relay_only(peer_addr).await => {
+                debug!("Ignoring tx from block-relay-only peer {}", peer_addr);
+                return Ok(());
+            }
+            parsed => parsed,
+        };
+
+        // Past the upload target, historical blocks go to exempt peers only
+        let parsed = match parsed {
+            ProtocolMessage::GetData(msg) => match self.check_upload_target(peer_addr, msg).await {
+                Some(msg) => ProtocolMessage::GetData(msg),
+                None => return Ok(()),
+            },
+            parsed => parsed,
+        };
+
+        // Track ping and block delivery (used to protect peers from eviction)
+        self.record_peer_stats(peer_addr, &parsed).await;
+
+        // Transactions with unknown inputs wait in the orphan pool
+        if let ProtocolMessage::Tx(ref msg) = parsed {
+            self.force_relay_known_transaction(peer_addr, &msg.transaction)
+                .await;
+            if self.store_if_orphan(peer_addr, &msg.transaction).await {
+                return Ok(());
+            }
+        }
+
+        // Handle special cases that don't go through protocol layer
+        match parsed {
+            // Version negotiation
+            ProtocolMessage::Version(ref msg) => {
+                if !self.handle_version(peer_addr, msg).await {
+                    return Ok(());
+                }
+            }
+            ProtocolMessage::Verack => {
+                self.handle_verack(peer_addr).await?;
+            }
+            ProtocolMessage::SendCmpct(_)
+                if !self
+                    .peer_supports(peer_addr, version_negotiation::Feature::CompactBlocks)
+                    .await =>
+            {
+                debug!(
+                    "Ignoring sendcmpct from {}: not supported by negotiated version",
+                    peer_addr
+                );
+                return Ok(());
+            }
+            ProtocolMessage::Block(ref msg) => {
+                if !self.check_received_block(peer_addr, &msg.block).await {
+                    return Ok(());
+                }
+                // Blocks fetched with getblockfrompeer are stored by the fetch;
+                // downloaded blocks continue to block processing
+                if self.complete_block_request(peer_addr, msg).await {
+                    return Ok(());
+                }
+            }
+            // Compact blocks (BIP152)
+            ProtocolMessage::CmpctBlock(msg) => {
+                return self
+                    .handle_compact_block(peer_addr, msg.compact_block)
+                    .await;
+            }
+            ProtocolMessage::BlockTxn(msg) => {
+                return self.handle_block_txn(peer_addr, msg).await;
+            }
+            // BIP331
+            ProtocolMessage::SendPkgTxn(_) => {
+                let _ = self
+                    .peer_tx
+                    .send(NetworkMessage::SendPkgTxnReceived(data, peer_addr));
+                return Ok(());
+            }
+            ProtocolMessage::PkgTxn(_) => {
+                let _ = self
+                    .peer_tx
+                    .send(NetworkMessage::PkgTxnReceived(data, peer_addr));
+                return Ok(());
+            }
+            // BIP157
+            ProtocolMessage::GetCfilters(_) => {
+                let _ = self
+                    .peer_tx
+                    .send(NetworkMessage::GetCfiltersReceived(data, peer_addr));
+                return Ok(());
+            }
+            ProtocolMessage::GetCfheaders(_) => {
+                let _ = self
+                    .peer_tx
+                    .send(NetworkMessage::GetCfheadersReceived(data, peer_addr));
+                return Ok(());
+            }
+            ProtocolMessage::GetCfcheckpt(_) => {
+                let _ = self
+                    .peer_tx
+                    .send(NetworkMessage::GetCfcheckptReceived(data, peer_addr));
+                return Ok(());
+            }
+            // Ban List Sharing
+            ProtocolMessage::GetBanList(msg) => {
+                return self.handle_get_ban_list(peer_addr, msg).await;
+            }
+            ProtocolMessage::BanList(msg) => {
+                return self.handle_ban_list(peer_addr, msg).await;
+            }
+            // FIBRE fast block relay
+            ProtocolMessage::FibreChunk(msg) => {
+                return self.handle_fibre_chunk(peer_addr, msg).await;
+            }
+            // Address relay
+            ProtocolMessage::GetAddr => {
+                return self.handle_get_addr(peer_addr).await;
+            }
+            ProtocolMessage::Addr(msg) => {
+                return self.handle_addr(peer_addr, msg).await;
+            }
+            // BIP133 (recorded on the peer by record_peer_stats)
+            ProtocolMessage::FeeFilter(_) => {
+                return Ok(());
+            }
+            // BIP35
+            ProtocolMessage::MemPool => {
+                return self.handle_mempool_request(peer_addr).await;
+            }
+            // Dandelion++ relay (protocol layer still processes the transaction)
+            #[cfg(feature = "dandelion")]
+            ProtocolMessage::Tx(ref msg) if self.dandelion.is_some() => {
+                self.relay_received_transaction(peer_addr, &msg.transaction)
+                    .await;
+            }
+            _ => {
+                // Continue to protocol layer processing
+            }
+        }
+
+        // Process with protocol layer if dependencies are available
+        if let (Some(ref protocol_engine), Some(ref storage), Some(ref mempool_manager)) = (
+            self.protocol_engine.as_ref(),
+            self.storage.as_ref(),
+            self.mempool_manager.as_ref(),
+        ) {
+            // Convert ProtocolMessage to bllvm_protocol::network::NetworkMessage
+            use crate::network::protocol_adapter::ProtocolAdapter;
+            let protocol_msg = ProtocolAdapter::protocol_to_consensus_message(&parsed)?;
+
+            // Create NodeChainAccess
+            use crate::network::chain_access::NodeChainAccess;
+            use crate::utils::arc_clone;
+            let chain_access = NodeChainAccess::new(
+                storage.blocks(),
+                storage.transactions(),
+                arc_clone(mempool_manager),
+            );
+
+            // Get UTXO set and height
+            let utxo_set = storage
+                .utxos()
+                .get_all_utxos()
+                .map_err(|e| anyhow::anyhow!("Failed to get UTXO set: {}", e))?;
+            let height = storage
+                .chain()
+                .get_height()
+                .map_err(|e| anyhow::anyhow!("Failed to get height: {}", e))?
+                .unwrap_or(0);
+
+            // Process message with protocol layer - lock peer_states only during synchronous processing
+            let response = {
+                let mut peer_states = self.peer_states.write().await;
+                let peer_state = peer_states
+                    .entry(peer_addr)
+                    .or_insert_with(|| bllvm_protocol::network::PeerState::new());
+
+                // Process message (synchronous, no await points)
+                use bllvm_protocol::network::{process_network_message, ChainStateAccess};
+                process_network_message(
+                    protocol_engine,
+                    &protocol_msg,
+                    peer_state, // &mut PeerState
+                    Some(&chain_access as &dyn ChainStateAccess),
+                    Some(&utxo_set),
+                    Some(height),
+                )
+            }; // Lock dropped here before async operations
+
+            // Now handle response without holding the lock
+            match response {
+                Ok(response) => {
+                    // Convert response to wire format and send via transport layer
+                    use crate::network::message_bridge::MessageBridge;
+                    use crate::network::transport::TransportType;
+                    if let Ok(wire_messages) =
+                        MessageBridge::extract_send_messages(&response, TransportType::Tcp)
+                    {
+                        for wire_msg in wire_messages {
+                            if let Err(e) = self.send_to_peer(peer_addr, wire_msg).await {
+                                warn!("Failed to send protocol response to {}: {}", peer_addr, e);
+                            } else {
+                                debug!("Sent protocol response message to {}", peer_addr);
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    warn!("Protocol message processing failed: {}", e);
+                }
+            }
+        } else {
+            // Dependencies not set - fall back to old behavior
+            debug!("Protocol layer dependencies not set, skipping protocol processing");
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "utxo-commitments")]
+    /// Handle GetUTXOSet request from a peer
+    async fn handle_get_utxo_set_request(
+        &self,
+        data: Vec<u8>,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+        use crate::network::protocol_extensions::handle_get_utxo_set;
+
+        // Parse the request
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let get_utxo_set_msg = match protocol_msg {
+            ProtocolMessage::GetUTXOSet(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected GetUTXOSet message")),
+        };
+
+        // Handle the request with storage integration
+        let storage = self.storage.as_ref().map(Arc::clone);
+        let response = handle_get_utxo_set(get_utxo_set_msg, storage).await?;
+
+        // Serialize and send response
+        let response_wire = ProtocolParser::serialize_message(&ProtocolMessage::UTXOSet(response))?;
+        self.send_to_peer(peer_addr, response_wire).await?;
+
+        Ok(())
+    }
+
+    /// Whether to answer a BIP157 request from `peer_addr`
+    ///
+    /// Requests are ignored unless block filters are served (and advertised).
+    fn accept_filter_request(&self, peer_addr: SocketAddr) -> bool {
+        if !self.serve_block_filters {
+            debug!(
+                "Ignoring block filter request from {}: not serving filters",
+                peer_addr
+            );
+        }
+        self.serve_block_filters
+    }
+
+    /// Handle GetCfilters request from a peer
+    async fn handle_getcfilters_request(&self, data: Vec<u8>, peer_addr: SocketAddr) -> Result<()> {
+        use crate::network::bip157_handler::handle_getcfilters;
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+
+        if !self.accept_filter_request(peer_addr) {
+            return Ok(());
+        }
+
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let request = match protocol_msg {
+            ProtocolMessage::GetCfilters(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected GetCfilters message")),
+        };
+
+        // Handle request and generate responses
+        let storage_ref = self.storage.as_ref();
+        let responses = handle_getcfilters(&request, &self.filter_service, storage_ref)?;
+
+        // Send responses to peer
+        for response in responses {
+            let response_wire = ProtocolParser::serialize_message(&response)?;
+            self.send_to_peer(peer_addr, response_wire).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle GetCfheaders request from a peer
+    async fn handle_getcfheaders_request(
+        &self,
+        data: Vec<u8>,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        use crate::network::bip157_handler::handle_getcfheaders;
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+
+        if !self.accept_filter_request(peer_addr) {
+            return Ok(());
+        }
+
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let request = match protocol_msg {
+            ProtocolMessage::GetCfheaders(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected GetCfheaders message")),
+        };
+
+        let response = handle_getcfheaders(&request, &self.filter_service)?;
+        let response_wire = ProtocolParser::serialize_message(&response)?;
+        self.send_to_peer(peer_addr, response_wire).await?;
+
+        Ok(())
+    }
+
+    /// Handle GetCfcheckpt request from a peer
+    async fn handle_getcfcheckpt_request(
+        &self,
+        data: Vec<u8>,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        use crate::network::bip157_handler::handle_getcfcheckpt;
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+
+        if !self.accept_filter_request(peer_addr) {
+            return Ok(());
+        }
+
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let request = match protocol_msg {
+            ProtocolMessage::GetCfcheckpt(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected GetCfcheckpt message")),
+        };
+
+        let response = handle_getcfcheckpt(&request, &self.filter_service)?;
+        let response_wire = ProtocolParser::serialize_message(&response)?;
+        self.send_to_peer(peer_addr, response_wire).await?;
+
+        Ok(())
+    }
+
+    /// Handle PkgTxn message from a peer
+    async fn handle_pkgtxn_request(&self, data: Vec<u8>, peer_addr: SocketAddr) -> Result<()> {
+        use crate::network::package_relay::PackageRelay;
+        use crate::network::package_relay_handler::handle_pkgtxn;
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+        use bllvm_protocol::Transaction;
+
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let request = match protocol_msg {
+            ProtocolMessage::PkgTxn(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected PkgTxn message")),
+        };
+
+        let mut relay = PackageRelay::new();
+        if let Some(reject) = handle_pkgtxn(&mut relay, &request)? {
+            let response_wire =
+                ProtocolParser::serialize_message(&ProtocolMessage::PkgTxnReject(reject))?;
+            self.send_to_peer(peer_addr, response_wire).await?;
+        }
+
+        // Best-effort: attempt to submit transactions to mempool (hook for node integration)
+        // Deserialize and submit; ignore errors for now (this node may not own mempool yet)
+        let mut txs: Vec<Transaction> = Vec::with_capacity(request.transactions.len());
+        for raw in &request.transactions {
+            if let Ok(tx) = bincode::deserialize::<Transaction>(raw) {
+                txs.push(tx);
+            }
+        }
+        let _ = self.submit_transactions_to_mempool(peer_addr, &txs).await;
+        Ok(())
+    }
+
+    /// Run mempool acceptance checks on a transaction received from a peer
+    ///
+    /// Nothing is inserted. A peer sending a transaction no node would relay
+    /// (see [`MempoolAcceptResult::is_misbehavior`]) is reported; duplicates
+    /// and policy refusals are not held against it.
+    pub async fn accept_transaction(
+        &self,
+        peer_addr: SocketAddr,
+        tx: &bllvm_protocol::Transaction,
+    ) -> Result<MempoolAcceptResult> {
+        let (Some(mempool), Some(storage)) = (self.mempool_manager.as_ref(), self.storage.as_ref())
+        else {
+            return Err(anyhow::anyhow!("Mempool or storage not available"));
+        };
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::{OutPoint, UtxoSet};
+
+        // Only the coins the transaction spends, plus its own outputs (to
+        // recognize an already confirmed transaction), are needed
+        let txid = calculate_tx_id(tx);
+        let own_outputs = (0..tx.outputs.len()).map(|index| OutPoint {
+            hash: txid,
+            index: index as u64,
+        });
+        let mut view = UtxoSet::new();
+        for outpoint in tx
+            .inputs
+            .iter()
+            .map(|input| input.prevout.clone())
+            .chain(own_outputs)
+        {
+            if let Some(utxo) = storage.utxos().get_utxo(&outpoint)? {
+                view.insert(outpoint, utxo);
+            }
+        }
+        let is_coinbase = |outpoint: &OutPoint| storage.utxos().is_coinbase_or_unknown(outpoint);
+        let spend_height = storage.chain().get_height()?.unwrap_or(0) + 1;
+
+        let result = mempool.check_transaction(tx, &view, &is_coinbase, spend_height);
+        if result.is_misbehavior() {
+            self.report_misbehavior(
+                peer_addr,
+                dos_protection::MISBEHAVIOR_INVALID_TRANSACTION,
+                result.reject_reason().unwrap_or("invalid transaction"),
+            )
+            .await;
+        }
+        Ok(result)
+    }
+
+    /// Submit validated transactions to the mempool
+    async fn submit_transactions_to_mempool(
+        &self,
+        peer_addr: SocketAddr,
+        txs: &[bllvm_protocol::Transaction],
+    ) -> Result<()> {
+        if self.mempool_manager.is_some() {
+            // Note: add_transaction requires &mut, so we need to handle this carefully
+            // This is a limitation of the current design - MempoolManager should use interior mutability
+            for tx in txs {
+                // Classify first, so peers sending invalid transactions are penalized
+                match self.accept_transaction(peer_addr, tx).await {
+                    Ok(result) if !result.is_accepted() => continue,
+                    Ok(_) => {}
+                    Err(e) => debug!("Could not classify package transaction: {}", e),
+                }
+                let utxo_lock = self.utxo_set.lock().await;
+                let mempool_lock = self.mempool.lock().await;
+                let _ = self
+                    .consensus
+                    .accept_to_memory_pool(tx, &utxo_lock, &mempool_lock, 0);
+            }
+        } else {
+            // Fallback to legacy mempool
+            let utxo_lock = self.utxo_set.lock().await;
+            let mempool_lock = self.mempool.lock().await;
+            for tx in txs {
+                let _ = self
+                    .consensus
+                    .accept_to_memory_pool(tx, &utxo_lock, &mempool_lock, 0);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "utxo-commitments")]
+    /// Handle GetFilteredBlock request from a peer
+    async fn handle_get_filtered_block_request(
+        &self,
+        data: Vec<u8>,
+        peer_addr: SocketAddr,
+    ) -> Result<()> {
+        use crate::network::protocol::ProtocolMessage;
+        use crate::network::protocol::ProtocolParser;
+        use crate::network::protocol_extensions::handle_get_filtered_block;
+
+        // Parse the request
+        let protocol_msg = ProtocolParser::parse_message(&data)?;
+        let get_filtered_block_msg = match protocol_msg {
+            ProtocolMessage::GetFilteredBlock(msg) => msg,
+            _ => return Err(anyhow::anyhow!("Expected GetFilteredBlock message")),
+        };
+
+        // Handle the request with storage and filter service
+        let storage = self.storage.as_ref().map(Arc::clone);
+        let response =
+            handle_get_filtered_block(get_filtered_block_msg, storage, Some(&self.filter_service))
+                .await?;
+
+        // Serialize and send response
+        let response_wire =
+            ProtocolParser::serialize_message(&ProtocolMessage::FilteredBlock(response))?;
+        self.send_to_peer(peer_addr, response_wire).await?;
+
+        Ok(())
+    }
+
+    /// Get peer manager reference (locked)
+    ///
+    /// Note: This is now async and uses tokio::sync::Mutex to avoid deadlocks
+    pub async fn peer_manager(&self) -> tokio::sync::MutexGuard<'_, PeerManager> {
+        self.peer_manager.lock().await
+    }
+
+    /// Check eclipse prevention for an IP address
+    /// Returns true if connection is allowed, false if it would violate eclipse prevention
+    pub fn check_eclipse_prevention(&self, ip: std::net::IpAddr) -> bool {
+        let prefix = self.get_ip_prefix(ip);
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let diversity = self.peer_diversity.lock().await;
+                let count = diversity.get(&prefix).copied().unwrap_or(0);
+                count < 3 // Allow max 3 connections from same IP prefix
+            })
+        })
+    }
+
+    /// Get IP prefix (first 3 octets) for eclipse prevention
+    pub fn get_ip_prefix(&self, ip: std::net::IpAddr) -> [u8; 3] {
+        match ip {
+            std::net::IpAddr::V4(ipv4) => {
+                let octets = ipv4.octets();
+                [octets[0], octets[1], octets[2]]
+            }
+            std::net::IpAddr::V6(ipv6) => {
+                let octets = ipv6.octets();
+                [octets[0], octets[1], octets[2]]
+            }
+        }
+    }
+
+    /// Remove peer diversity tracking for an IP address
+    pub fn remove_peer_diversity(&self, ip: std::net::IpAddr) {
+        let prefix = self.get_ip_prefix(ip);
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut diversity = self.peer_diversity.lock().await;
+                if let Some(count) = diversity.get_mut(&prefix) {
+                    if *count > 0 {
+                        *count -= 1;
+                    }
+                    if *count == 0 {
+                        diversity.remove(&prefix);
+                    }
+                }
+            })
+        })
+    }
+
+    /// Get filter service reference
+    pub fn filter_service(&self) -> &crate::network::filter_service::BlockFilterService {
+        &self.filter_service
+    }
+
+    /// Add a persistent peer (connected by the next [`Self::maintain_persistent_peers`])
+    pub fn add_persistent_peer(&self, addr: SocketAddr) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut peers = self.persistent_peers.lock().await;
+                peers.entry(addr).or_insert_with(|| {
+                    connection_manager::PersistentPeerState::new(current_timestamp())
+                });
+            })
+        })
+    }
+
+    /// Remove a persistent peer
+    pub fn remove_persistent_peer(&self, addr: SocketAddr) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut peers = self.persistent_peers.lock().await;
+                peers.remove(&addr);
+            })
+        })
+    }
+
+    /// Get list of persistent peers (async version for RPC)
+    pub async fn get_persistent_peers(&self) -> HashSet<SocketAddr> {
+        self.persistent_peers.lock().await.keys().cloned().collect()
+    }
+
+    /// Get list of persistent peers (sync version)
+    pub fn get_persistent_peers_sync(&self) -> Vec<SocketAddr> {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let peers = self.persistent_peers.lock().await;
+                peers.keys().cloned().collect()
+            })
+        })
+    }
+
+    /// Ban a peer (with optional unban timestamp, 0 = permanent)
+    pub fn ban_peer(&self, addr: SocketAddr, unban_timestamp: u64) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut ban_list = self.ban_list.write().await;
+                if unban_timestamp == 0 {
+                    // Permanent ban (use max timestamp)
+                    ban_list.insert(addr, u64::MAX);
+                } else {
+                    ban_list.insert(addr, unban_timestamp);
+                }
+            })
+        })
+    }
+
+    /// Unban a peer
+    pub fn unban_peer(&self, addr: SocketAddr) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut ban_list = self.ban_list.write().await;
+                ban_list.remove(&addr);
+            })
+        })
+    }
+
+    /// Clear all bans and discouraged addresses
+    pub fn clear_bans(&self) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let mut ban_list = self.ban_list.write().await;
+                ban_list.clear();
+                // Like Bitcoin Core, clearbanned also forgets discouragement
+                self.discouraged.write().await.clear();
+            })
+        })
+    }
+
+    /// Handle GetAddr request - return known addresses
+    async fn handle_get_addr(&self, peer_addr: SocketAddr) -> Result<()> {
+        use crate::network::protocol::{AddrMessage, ProtocolMessage, ProtocolParser};
+
+        // Get fresh addresses from database (up to 2500, Bitcoin Core limit)
+        let ban_list = self.ban_list.read().await.clone();
+        let connected_peers: Vec<SocketAddr> = {
+            let pm = self.peer_manager.lock().await;
+            pm.peer_socket_addresses()
+        };
+
+        let addresses = {
+            let db = self.address_database.write().await;
+            let fresh = db.get_fresh_addresses(2500);
+            db.filter_addresses(fresh, &ban_list, &connected_peers)
+        };
+
+        // Create Addr message
+        let addr_msg = AddrMessage { addresses };
+        let response = ProtocolMessage::Addr(addr_msg);
+        let wire_msg = ProtocolParser::serialize_message(&response)?;
+
+        // Send response
+        self.send_to_peer(peer_addr, wire_msg).await?;
+        Ok(())
+    }
+
+    /// Handle Addr message - store addresses and optionally relay
+    async fn handle_addr(&self, peer_addr: SocketAddr, msg: AddrMessage) -> Result<()> {
+        if self.is_block_relay_only(peer_addr).await {
+            debug!("Ignoring addr from block-relay-only peer {}", peer_addr);
+            return Ok(());
+        }
+
+        // AddrMessage is already in scope as parameter, NetworkAddress is available from top-level import
+
+        // Get peer services from peer state
+        let peer_services = {
+            let peer_states = self.peer_states.read().await;
+            peer_states
+                .get(&peer_addr)
+                .map(|state| state.services)
+                .unwrap_or(0)
+        };
 
-                    // Process through protocol layer
-                    if let Err(e) = self.handle_incoming_wire_tcp(peer_addr, data).await {
-                        warn!("Failed to process message from {}: {}", peer_addr, e);
-                    }
+        // Store addresses in database
+        {
+            let mut db = self.address_database.write().await;
+            for addr in &msg.addresses {
+                db.add_address_from(addr.clone(), peer_services, peer_addr.ip());
+            }
+        }
+
+        // Don't echo the addresses back to the peer that sent them
+        {
+            let mut pm = self.peer_manager.lock().await;
+            if let Some(peer) = pm
+                .find_transport_addr_by_socket(peer_addr)
+                .and_then(|addr| pm.get_peer_mut(&addr))
+            {
+                for addr in &msg.addresses {
+                    peer.add_known_address(addr);
                 }
             }
         }
+
+        // Relay addresses to other peers (with rate limiting)
+        self.relay_addresses(peer_addr, &msg.addresses).await?;
+
         Ok(())
     }
 
-    /// Parse incoming TCP wire message and process with protocol layer
-    ///
-    /// This function:
-    /// 1. Converts ProtocolMessage to bllvm_protocol::network::NetworkMessage
-    /// 2. Gets or creates PeerState for this peer
-    /// 3. Creates NodeChainAccess from storage modules
-    /// 4. Calls bllvm_protocol::network::process_network_message()
-    /// 5. Converts NetworkResponse back to wire format and enqueues for sending
-    pub async fn handle_incoming_wire_tcp(
+    /// Relay addresses to other peers (excluding sender)
+    async fn relay_addresses(
         &self,
-        peer_addr: SocketAddr,
-        data: Vec<u8>,
+        sender_addr: SocketAddr,
+        addresses: &[NetworkAddress],
     ) -> Result<()> {
-        // Track bytes received (async-safe)
-        self.track_bytes_received(data.len() as u64).await;
+        // Rate limiting: don't send addr messages too frequently (Bitcoin Core: ~every 2.4 hours)
+        let now = current_timestamp();
+        {
+            let last_sent = *self.last_addr_sent.lock().await;
+            if now.saturating_sub(last_sent)
+                < self.network_timing_config.addr_relay_min_interval_seconds
+            {
+                // Too soon, skip relay
+                return Ok(());
+            }
+        }
 
-        // Check if peer is banned
-        if self.is_banned(peer_addr) {
-            warn!("Rejecting message from banned peer: {}", peer_addr);
-            return Ok(()); // Silently drop messages from banned peers
+        // Filter addresses (exclude local, banned, already connected)
+        let ban_list = self.ban_list.read().await.clone();
+        let connected_peers: Vec<SocketAddr> = {
+            let pm = self.peer_manager.lock().await;
+            pm.peer_socket_addresses()
+        };
+
+        let filtered = {
+            let db = self.address_database.read().await;
+            db.filter_addresses(addresses.to_vec(), &ban_list, &connected_peers)
+        };
+
+        if filtered.is_empty() {
+            return Ok(());
         }
-        let parsed = ProtocolParser::parse_message(&data)?;
 
-        // Handle special cases that don't go through protocol layer
-        match parsed {
-            // BIP331
-            ProtocolMessage::SendPkgTxn(_) => {
-                let _ = self
-                    .peer_tx
-                    .send(NetworkMessage::SendPkgTxnReceived(data, peer_addr));
-                return Ok(());
-            }
-            ProtocolMessage::PkgTxn(_) => {
-                let _ = self
-                    .peer_tx
-                    .send(NetworkMessage::PkgTxnReceived(data, peer_addr));
-                return Ok(());
-            }
-            // BIP157
-            ProtocolMessage::GetCfilters(_) => {
-                let _ = self
-                    .peer_tx
-                    .send(NetworkMessage::GetCfiltersReceived(data, peer_addr));
-                return Ok(());
-            }
-            ProtocolMessage::GetCfheaders(_) => {
-                let _ = self
-                    .peer_tx
-                    .send(NetworkMessage::GetCfheadersReceived(data, peer_addr));
-                return Ok(());
+        // Send to all address-relay peers except sender
+        let peer_addrs: Vec<SocketAddr> = {
+            let pm = self.peer_manager.lock().await;
+            pm.addr_relay_socket_addresses()
+                .into_iter()
+                .filter(|addr| *addr != sender_addr)
+                .collect()
+        };
+
+        for peer_addr in peer_addrs {
+            if let Err(e) = self.send_addresses_to_peer(peer_addr, &filtered).await {
+                warn!("Failed to relay addresses to {}: {}", peer_addr, e);
             }
-            ProtocolMessage::GetCfcheckpt(_) => {
-                let _ = self
-                    .peer_tx
-                    .send(NetworkMessage::GetCfcheckptReceived(data, peer_addr));
-                return Ok(());
+        }
+
+        // Update last sent time
+        *self.last_addr_sent.lock().await = now;
+
+        Ok(())
+    }
+
+    /// Gossip known addresses to a rotating subset of peers
+    ///
+    /// Sends up to `max_addresses_per_addr_message` fresh addresses from the
+    /// address database, led by our best local address when
+    /// self-advertisement is enabled, to [`ADDR_RELAY_FANOUT`] address-relay
+    /// peers. Runs at most once per `addr_relay_min_interval_seconds`.
+    /// Intended to be called periodically; returns the number of peers that
+    /// were sent addresses.
+    pub async fn maintain_addr_relay(&self) -> Result<usize> {
+        let mut peer_addrs = self.peer_manager.lock().await.addr_relay_socket_addresses();
+        if peer_addrs.is_empty() {
+            return Ok(0);
+        }
+        let now = current_timestamp();
+        {
+            let mut last_sent = self.last_addr_sent.lock().await;
+            if now.saturating_sub(*last_sent)
+                < self.network_timing_config.addr_relay_min_interval_seconds
+            {
+                return Ok(0);
             }
-            // Ban List Sharing
-            ProtocolMessage::GetBanList(msg) => {
-                return self.handle_get_ban_list(peer_addr, msg).await;
+            *last_sent = now;
+        }
+
+        let mut addresses = Vec::new();
+        if self.enable_self_advertisement {
+            if let Some(best) = self.local_addresses.lock().await.best() {
+                let services = self.services_for_pruning(SELF_ADVERTISEMENT_SERVICES);
+                addresses.push(to_network_address(best, services));
             }
-            ProtocolMessage::BanList(msg) => {
-                return self.handle_ban_list(peer_addr, msg).await;
+        }
+        let ban_list = self.ban_list.read().await.clone();
+        let connected_peers = self.peer_manager.lock().await.peer_socket_addresses();
+        {
+            let db = self.address_database.read().await;
+            // Each recipient is sent the first addresses it doesn't know yet
+            let fresh = db.get_all_fresh_addresses();
+            addresses.extend(db.filter_addresses(fresh, &ban_list, &connected_peers));
+        }
+        if addresses.is_empty() {
+            return Ok(0);
+        }
+
+        // Rotate through peers so every peer is gossiped to over time
+        peer_addrs.sort();
+        let recipients: Vec<SocketAddr> = {
+            let mut cursor = self.addr_relay_cursor.lock().await;
+            let start = *cursor % peer_addrs.len();
+            *cursor = start + ADDR_RELAY_FANOUT;
+            peer_addrs
+                .iter()
+                .cycle()
+                .skip(start)
+                .take(ADDR_RELAY_FANOUT.min(peer_addrs.len()))
+                .copied()
+                .collect()
+        };
+
+        let mut sent = 0;
+        for peer_addr in recipients {
+            match self.send_addresses_to_peer(peer_addr, &addresses).await {
+                Ok(true) => sent += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to gossip addresses to {}: {}", peer_addr, e),
             }
-            // Address relay
-            ProtocolMessage::GetAddr => {
-                return self.handle_get_addr(peer_addr).await;
+        }
+        Ok(sent)
+    }
+
+    /// Send a peer the addresses it doesn't already know, in one `addr`
+    ///
+    /// At most `max_addresses_per_addr_message` addresses are sent and
+    /// remembered as known to the peer. Returns whether anything was sent.
+    async fn send_addresses_to_peer(
+        &self,
+        peer_addr: SocketAddr,
+        addresses: &[NetworkAddress],
+    ) -> Result<bool> {
+        let max_addresses = self.network_timing_config.max_addresses_per_addr_message;
+        let unknown: Vec<NetworkAddress> = {
+            let mut pm = self.peer_manager.lock().await;
+            let Some(peer) = pm
+                .find_transport_addr_by_socket(peer_addr)
+                .and_then(|addr| pm.get_peer_mut(&addr))
+            else {
+                return Ok(false);
+            };
+            let unknown: Vec<NetworkAddress> = addresses
+                .iter()
+                .filter(|addr| !peer.has_known_address(addr))
+                .take(max_addresses)
+                .cloned()
+                .collect();
+            for addr in &unknown {
+                peer.add_known_address(addr);
             }
-            ProtocolMessage::Addr(msg) => {
-                return self.handle_addr(peer_addr, msg).await;
+            unknown
+        };
+        if unknown.is_empty() {
+            return Ok(false);
+        }
+
+        let wire_msg = ProtocolParser::serialize_message(&ProtocolMessage::Addr(AddrMessage {
+            addresses: unknown,
+        }))?;
+        self.send_to_peer(peer_addr, wire_msg).await?;
+        Ok(true)
+    }
+
+    /// Advertise our best local address to peers once a day
+    ///
+    /// Does nothing if self-advertisement is disabled, no local address is
+    /// known or no peers are connected. Intended to be called periodically;
+    /// returns whether an address was advertised.
+    pub async fn maintain_self_advertisement(&self) -> Result<bool> {
+        if !self.enable_self_advertisement || self.peer_manager.lock().await.peer_count() == 0 {
+            return Ok(false);
+        }
+        let Some(best) = self.local_addresses.lock().await.best() else {
+            return Ok(false);
+        };
+        let now = current_timestamp();
+        {
+            let mut last_advertised = self.last_self_advertisement.lock().await;
+            if now.saturating_sub(*last_advertised) < SELF_ADVERTISEMENT_INTERVAL_SECONDS {
+                return Ok(false);
             }
-            _ => {
-                // Continue to protocol layer processing
+            *last_advertised = now;
+        }
+        debug!("Advertising local address {}", best);
+        let services = self.services_for_pruning(SELF_ADVERTISEMENT_SERVICES);
+        self.advertise_self(best, services).await?;
+        Ok(true)
+    }
+
+    /// Send our own address to peers (self-advertisement)
+    pub async fn advertise_self(&self, listen_addr: SocketAddr, services: u64) -> Result<()> {
+        if !self.enable_self_advertisement {
+            return Ok(()); // Self-advertisement disabled
+        }
+
+        use crate::network::protocol::{AddrMessage, ProtocolMessage, ProtocolParser};
+
+        let our_addr = to_network_address(listen_addr, services);
+
+        // Create Addr message with just our address
+        let addr_msg = AddrMessage {
+            addresses: vec![our_addr.clone()],
+        };
+        let relay_msg = ProtocolMessage::Addr(addr_msg);
+        let wire_msg = ProtocolParser::serialize_message(&relay_msg)?;
+
+        // Send to all connected peers that relay addresses
+        let peer_addrs: Vec<SocketAddr> = {
+            let pm = self.peer_manager.lock().await;
+            pm.addr_relay_socket_addresses()
+        };
+
+        for peer_addr in peer_addrs {
+            if let Err(e) = self.send_to_peer(peer_addr, wire_msg.clone()).await {
+                warn!("Failed to advertise self to {}: {}", peer_addr, e);
             }
         }
 
-        // Process with protocol layer if dependencies are available
-        if let (Some(ref protocol_engine), Some(ref storage), Some(ref mempool_manager)) = (
-            self.protocol_engine.as_ref(),
-            self.storage.as_ref(),
-            self.mempool_manager.as_ref(),
-        ) {
-            // Convert ProtocolMessage to bllvm_protocol::network::NetworkMessage
-            use crate::network::protocol_adapter::ProtocolAdapter;
-            let protocol_msg = ProtocolAdapter::protocol_to_consensus_message(&parsed)?;
+        // Also store our own address in database
+        {
+            let mut db = self.address_database.write().await;
+            db.add_address(our_addr, services);
+        }
+
+        Ok(())
+    }
+
+    /// Get list of banned peers
+    pub fn get_banned_peers(&self) -> Vec<(SocketAddr, u64)> {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let ban_list = self.ban_list.read().await;
+                ban_list
+                    .iter()
+                    .map(|(addr, timestamp)| (*addr, *timestamp))
+                    .collect()
+            })
+        })
+    }
+
+    /// Get peer addresses (async version for RPC)
+    pub async fn get_peer_addresses(&self) -> Vec<TransportAddr> {
+        let pm = self.peer_manager.lock().await;
+        pm.peer_addresses()
+    }
+
+    /// Set network active state
+    pub async fn set_network_active(&self, active: bool) -> Result<()> {
+        let mut state = self.network_active.lock().await;
+        *state = active;
+        info!("Network active state set to: {}", active);
+        Ok(())
+    }
+
+    /// Get network active state (async version for RPC)
+    pub async fn network_active(&self) -> bool {
+        *self.network_active.lock().await
+    }
+
+    /// Get network active state
+    pub fn is_network_active(&self) -> bool {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async { *self.network_active.lock().await })
+        })
+    }
+
+    /// Check if a peer is banned
+    pub fn is_banned(&self, addr: SocketAddr) -> bool {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let ban_list = self.ban_list.read().await;
+                if let Some(&unban_timestamp) = ban_list.get(&addr) {
+                    if unban_timestamp == u64::MAX {
+                        return true; // Permanent ban
+                    }
+                    // Check if ban has expired
+                    let now = current_timestamp();
+                    if now < unban_timestamp {
+                        return true; // Still banned
+                    } else {
+                        // Ban expired, remove it
+                        drop(ban_list);
+                        self.unban_peer(addr);
+                        return false;
+                    }
+                }
+                false
+            })
+        })
+    }
+
+    /// Track bytes sent (async-safe)
+    pub async fn track_bytes_sent(&self, bytes: u64) {
+        let mut sent = self.bytes_sent.lock().await;
+        *sent += bytes;
+        self.bandwidth_limits.record_upload(bytes);
+    }
 
-            // Create NodeChainAccess
-            use crate::network::chain_access::NodeChainAccess;
-            use crate::utils::arc_clone;
-            let chain_access = NodeChainAccess::new(
-                storage.blocks(),
-                storage.transactions(),
-                arc_clone(mempool_manager),
-            );
+    /// Track bytes received (async-safe)
+    pub async fn track_bytes_received(&self, bytes: u64) {
+        let mut received = self.bytes_received.lock().await;
+        *received += bytes;
+    }
 
-            // Get UTXO set and height
-            let utxo_set = storage
-                .utxos()
-                .get_all_utxos()
-                .map_err(|e| anyhow::anyhow!("Failed to get UTXO set: {}", e))?;
-            let height = storage
-                .chain()
-                .get_height()
-                .map_err(|e| anyhow::anyhow!("Failed to get height: {}", e))?
-                .unwrap_or(0);
+    /// Build a `BanList` message from our current bans
+    ///
+    /// Expired bans and bans shorter than `min_ban_duration` seconds are left
+    /// out. The message is signed if a ban list signing key is configured.
+    async fn build_ban_list_message(
+        &self,
+        full: bool,
+        min_ban_duration: u64,
+    ) -> crate::network::protocol::BanListMessage {
+        use crate::network::ban_list_merging::calculate_ban_list_hash;
+        use crate::network::protocol::{BanEntry, BanListMessage, NetworkAddress};
 
-            // Process message with protocol layer - lock peer_states only during synchronous processing
-            let response = {
-                let mut peer_states = self.peer_states.write().await;
-                let peer_state = peer_states
-                    .entry(peer_addr)
-                    .or_insert_with(|| bllvm_protocol::network::PeerState::new());
+        // Get current ban list
+        let ban_list = self.ban_list.read().await;
+        let now = current_timestamp();
 
-                // Process message (synchronous, no await points)
-                use bllvm_protocol::network::{process_network_message, ChainStateAccess};
-                process_network_message(
-                    protocol_engine,
-                    &protocol_msg,
-                    peer_state, // &mut PeerState
-                    Some(&chain_access as &dyn ChainStateAccess),
-                    Some(&utxo_set),
-                    Some(height),
-                )
-            }; // Lock dropped here before async operations
+        // Convert to BanEntry format, filtering by min_ban_duration
+        let mut ban_entries: Vec<BanEntry> = Vec::new();
+        for (addr, &unban_timestamp) in ban_list.iter() {
+            // Skip expired bans
+            if unban_timestamp != u64::MAX && now >= unban_timestamp {
+                continue;
+            }
 
-            // Now handle response without holding the lock
-            match response {
-                Ok(response) => {
-                    // Convert response to wire format and send via transport layer
-                    use crate::network::message_bridge::MessageBridge;
-                    use crate::network::transport::TransportType;
-                    if let Ok(wire_messages) =
-                        MessageBridge::extract_send_messages(&response, TransportType::Tcp)
-                    {
-                        for wire_msg in wire_messages {
-                            if let Err(e) = self.send_to_peer(peer_addr, wire_msg).await {
-                                warn!("Failed to send protocol response to {}: {}", peer_addr, e);
-                            } else {
-                                debug!("Sent protocol response message to {}", peer_addr);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    warn!("Protocol message processing failed: {}", e);
+            // Filter by min_ban_duration
+            if min_ban_duration > 0 {
+                let ban_duration = if unban_timestamp == u64::MAX {
+                    u64::MAX
+                } else {
+                    unban_timestamp.saturating_sub(now)
+                };
+                if ban_duration < min_ban_duration {
+                    continue;
                 }
             }
-        } else {
-            // Dependencies not set - fall back to old behavior
-            debug!("Protocol layer dependencies not set, skipping protocol processing");
+
+            // Convert SocketAddr to NetworkAddress
+            let ip_bytes = match addr.ip() {
+                std::net::IpAddr::V4(ipv4) => {
+                    let mut bytes = [0u8; 16];
+                    bytes[12..16].copy_from_slice(&ipv4.octets());
+                    bytes
+                }
+                std::net::IpAddr::V6(ipv6) => ipv6.octets(),
+            };
+
+            ban_entries.push(BanEntry {
+                addr: NetworkAddress {
+                    services: 0,
+                    ip: ip_bytes,
+                    port: addr.port(),
+                },
+                unban_timestamp,
+                reason: Some("DoS protection".to_string()),
+            });
         }
 
-        Ok(())
+        // Calculate hash
+        let ban_list_hash = calculate_ban_list_hash(&ban_entries);
+
+        let mut message = BanListMessage {
+            is_full: full,
+            ban_list_hash,
+            ban_entries: if full { ban_entries } else { Vec::new() },
+            timestamp: now,
+            signature: None,
+        };
+        if let Some(ref key) = self.ban_list_signing_key {
+            if let Err(e) =
+                crate::network::ban_list_signing::sign_ban_list_message(&mut message, key)
+            {
+                warn!("Failed to sign ban list: {}", e);
+            }
+        }
+        message
     }
 
-    #[cfg(feature = "utxo-commitments")]
-    /// Handle GetUTXOSet request from a peer
-    async fn handle_get_utxo_set_request(
+    /// Handle GetBanList message - respond with ban list or hash
+    async fn handle_get_ban_list(
         &self,
-        data: Vec<u8>,
         peer_addr: SocketAddr,
+        msg: crate::network::protocol::GetBanListMessage,
     ) -> Result<()> {
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
-        use crate::network::protocol_extensions::handle_get_utxo_set;
-
-        // Parse the request
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let get_utxo_set_msg = match protocol_msg {
-            ProtocolMessage::GetUTXOSet(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected GetUTXOSet message")),
-        };
+        debug!(
+            "GetBanList request from {}: full={}, min_duration={}",
+            peer_addr, msg.request_full, msg.min_ban_duration
+        );
 
-        // Handle the request with storage integration
-        let storage = self.storage.as_ref().map(Arc::clone);
-        let response = handle_get_utxo_set(get_utxo_set_msg, storage).await?;
+        let response = self
+            .build_ban_list_message(msg.request_full, msg.min_ban_duration)
+            .await;
+        let ban_entries_count = response.ban_entries.len();
 
         // Serialize and send response
-        let response_wire = ProtocolParser::serialize_message(&ProtocolMessage::UTXOSet(response))?;
-        self.send_to_peer(peer_addr, response_wire).await?;
+        let response_msg = ProtocolMessage::BanList(response);
+        let serialized = ProtocolParser::serialize_message(&response_msg)?;
+        self.send_to_peer(peer_addr, serialized).await?;
+
+        debug!(
+            "Sent BanList response to {}: {} entries",
+            peer_addr, ban_entries_count
+        );
 
         Ok(())
     }
 
-    /// Handle GetCfilters request from a peer
-    async fn handle_getcfilters_request(&self, data: Vec<u8>, peer_addr: SocketAddr) -> Result<()> {
-        use crate::network::bip157_handler::handle_getcfilters;
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
-
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let request = match protocol_msg {
-            ProtocolMessage::GetCfilters(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected GetCfilters message")),
+    /// Share our ban list with all peers if due under `ban_list_sharing`
+    ///
+    /// In Periodic mode the list is broadcast every `periodic_interval_seconds`;
+    /// in Immediate mode it is broadcast once an auto-ban is pending. Bans
+    /// shorter than `min_ban_duration_to_share` are not shared. Intended to
+    /// be called periodically; returns whether a list was broadcast.
+    pub async fn maintain_ban_list_sharing(&self) -> Result<bool> {
+        use crate::config::BanShareMode;
+
+        let Some(config) = self.ban_list_sharing_config.as_ref().filter(|c| c.enabled) else {
+            return Ok(false);
         };
-
-        // Handle request and generate responses
-        let storage_ref = self.storage.as_ref();
-        let responses = handle_getcfilters(&request, &self.filter_service, storage_ref)?;
-
-        // Send responses to peer
-        for response in responses {
-            let response_wire = ProtocolParser::serialize_message(&response)?;
-            self.send_to_peer(peer_addr, response_wire).await?;
+        let now = current_timestamp();
+        let due = match config.share_mode {
+            BanShareMode::Periodic => {
+                let mut last_share = self.last_ban_list_share.lock().await;
+                if now.saturating_sub(*last_share) >= config.periodic_interval_seconds {
+                    *last_share = now;
+                    true
+                } else {
+                    false
+                }
+            }
+            BanShareMode::Immediate => !self.pending_ban_shares.lock().await.is_empty(),
+            BanShareMode::Disabled => false,
+        };
+        if !due {
+            return Ok(false);
         }
+        self.pending_ban_shares.lock().await.clear();
 
-        Ok(())
+        let message = self
+            .build_ban_list_message(true, config.min_ban_duration_to_share)
+            .await;
+        if message.ban_entries.is_empty() {
+            return Ok(false);
+        }
+        info!("Sharing {} ban(s) with peers", message.ban_entries.len());
+        let serialized = ProtocolParser::serialize_message(&ProtocolMessage::BanList(message))?;
+        self.broadcast(serialized).await?;
+        Ok(true)
     }
 
-    /// Handle GetCfheaders request from a peer
-    async fn handle_getcfheaders_request(
+    /// Handle BanList message - merge received ban list
+    async fn handle_ban_list(
         &self,
-        data: Vec<u8>,
         peer_addr: SocketAddr,
+        msg: crate::network::protocol::BanListMessage,
     ) -> Result<()> {
-        use crate::network::bip157_handler::handle_getcfheaders;
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
+        use crate::network::ban_list_merging::{validate_ban_entry, verify_ban_list_hash};
+        use crate::network::ban_list_signing::{check_ban_list_signature, BanListSignatureStatus};
+        use std::net::IpAddr;
 
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let request = match protocol_msg {
-            ProtocolMessage::GetCfheaders(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected GetCfheaders message")),
-        };
+        debug!(
+            "BanList received from {}: full={}, {} entries",
+            peer_addr,
+            msg.is_full,
+            msg.ban_entries.len()
+        );
 
-        let response = handle_getcfheaders(&request, &self.filter_service)?;
-        let response_wire = ProtocolParser::serialize_message(&response)?;
-        self.send_to_peer(peer_addr, response_wire).await?;
+        // Verify hash if full list provided
+        if msg.is_full {
+            if !verify_ban_list_hash(&msg.ban_entries, &msg.ban_list_hash) {
+                warn!("Ban list hash verification failed from {}", peer_addr);
+                return Ok(()); // Silently ignore invalid ban lists
+            }
+        }
+
+        // If hash-only, we can't merge (would need to request full list)
+        if !msg.is_full {
+            debug!(
+                "Received hash-only ban list from {}, skipping merge",
+                peer_addr
+            );
+            return Ok(());
+        }
+
+        // Only merge lists from trusted publishers if signing is required
+        let require_signature = self
+            .ban_list_sharing_config
+            .as_ref()
+            .is_some_and(|c| c.require_signature);
+        if require_signature {
+            match check_ban_list_signature(&msg, &self.ban_list_publishers) {
+                BanListSignatureStatus::Valid => {}
+                BanListSignatureStatus::Invalid => {
+                    // A forged signature is deliberate: stop talking to the sender
+                    warn!(
+                        "Ban list from {} has an invalid signature, disconnecting",
+                        peer_addr
+                    );
+                    self.disconnect_peer_by_socket(peer_addr).await;
+                    return Ok(());
+                }
+                status => {
+                    warn!(
+                        "Rejecting ban list from {}: not signed by a trusted publisher ({:?})",
+                        peer_addr, status
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        // Validate and merge ban entries
+        let mut ban_list = self.ban_list.write().await;
+        let mut merged_count = 0;
 
-        Ok(())
-    }
+        for entry in &msg.ban_entries {
+            if !validate_ban_entry(entry) {
+                continue; // Skip invalid entries
+            }
 
-    /// Handle GetCfcheckpt request from a peer
-    async fn handle_getcfcheckpt_request(
-        &self,
-        data: Vec<u8>,
-        peer_addr: SocketAddr,
-    ) -> Result<()> {
-        use crate::network::bip157_handler::handle_getcfcheckpt;
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
+            // Convert NetworkAddress to SocketAddr
+            let ip = if entry.addr.ip[0..12] == [0u8; 12] {
+                // IPv4-mapped IPv6 address
+                let ipv4_bytes = &entry.addr.ip[12..16];
+                IpAddr::V4(std::net::Ipv4Addr::new(
+                    ipv4_bytes[0],
+                    ipv4_bytes[1],
+                    ipv4_bytes[2],
+                    ipv4_bytes[3],
+                ))
+            } else {
+                // IPv6 address
+                let mut ipv6_bytes = [0u8; 16];
+                ipv6_bytes.copy_from_slice(&entry.addr.ip);
+                IpAddr::V6(std::net::Ipv6Addr::from(ipv6_bytes))
+            };
 
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let request = match protocol_msg {
-            ProtocolMessage::GetCfcheckpt(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected GetCfcheckpt message")),
-        };
+            let socket_addr = SocketAddr::new(ip, entry.addr.port);
 
-        let response = handle_getcfcheckpt(&request, &self.filter_service)?;
-        let response_wire = ProtocolParser::serialize_message(&response)?;
-        self.send_to_peer(peer_addr, response_wire).await?;
+            // Merge: use longer ban duration if address already exists
+            match ban_list.get(&socket_addr) {
+                Some(&existing_timestamp) => {
+                    if entry.unban_timestamp == u64::MAX {
+                        // Permanent ban always wins
+                        ban_list.insert(socket_addr, u64::MAX);
+                        merged_count += 1;
+                    } else if existing_timestamp != u64::MAX {
+                        // Both temporary - use longer one
+                        if entry.unban_timestamp > existing_timestamp {
+                            ban_list.insert(socket_addr, entry.unban_timestamp);
+                            merged_count += 1;
+                        }
+                    }
+                }
+                None => {
+                    // New ban entry
+                    ban_list.insert(socket_addr, entry.unban_timestamp);
+                    merged_count += 1;
+                }
+            }
+        }
 
+        debug!("Merged {} ban entries from {}", merged_count, peer_addr);
         Ok(())
     }
 
-    /// Handle PkgTxn message from a peer
-    async fn handle_pkgtxn_request(&self, data: Vec<u8>, peer_addr: SocketAddr) -> Result<()> {
-        use crate::network::package_relay::PackageRelay;
-        use crate::network::package_relay_handler::handle_pkgtxn;
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
-        use bllvm_protocol::Transaction;
+    /// Get DoS protection manager reference
+    pub fn dos_protection(&self) -> &Arc<dos_protection::DosProtectionManager> {
+        &self.dos_protection
+    }
 
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let request = match protocol_msg {
-            ProtocolMessage::PkgTxn(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected PkgTxn message")),
+    /// Get network statistics
+    pub async fn get_network_stats(&self) -> crate::node::metrics::NetworkMetrics {
+        let sent = *self.bytes_sent.lock().await;
+        let received = *self.bytes_received.lock().await;
+        let active_connections = {
+            let pm = self.peer_manager.lock().await;
+            pm.peer_count()
+        };
+        let connections = self.connection_counts().await;
+        let banned_peers_count = {
+            let ban_list = self.ban_list.read().await;
+            ban_list.len()
         };
+        let dos_metrics = self.dos_protection.get_dos_metrics().await;
 
-        let mut relay = PackageRelay::new();
-        if let Some(reject) = handle_pkgtxn(&mut relay, &request)? {
-            let response_wire =
-                ProtocolParser::serialize_message(&ProtocolMessage::PkgTxnReject(reject))?;
-            self.send_to_peer(peer_addr, response_wire).await?;
+        crate::node::metrics::NetworkMetrics {
+            peer_count: active_connections,
+            bytes_sent: sent,
+            bytes_received: received,
+            messages_sent: 0,     // Would need to track this
+            messages_received: 0, // Would need to track this
+            active_connections,
+            inbound_connections: connections.inbound,
+            outbound_full_relay_connections: connections.outbound_full_relay,
+            block_relay_only_connections: connections.block_relay_only,
+            feeler_connections: connections.feeler,
+            banned_peers: banned_peers_count,
+            connection_attempts: 0, // Would need to track this
+            connection_failures: 0, // Would need to track this
+            connect_timeouts: self.connect_timeouts.load(Ordering::Relaxed),
+            handshake_timeouts: self.handshake_timeouts.load(Ordering::Relaxed),
+            dos_protection: crate::node::metrics::DosMetrics {
+                connection_rate_violations: dos_metrics.connection_rate_violations,
+                auto_bans: dos_metrics.auto_bans_applied,
+                message_queue_overflows: dos_metrics.message_queue_overflows,
+                active_connection_limit_hits: dos_metrics.active_connection_limit_hits,
+                resource_exhaustion_events: dos_metrics.resource_exhaustion_events,
+            },
         }
+    }
 
-        // Best-effort: attempt to submit transactions to mempool (hook for node integration)
-        // Deserialize and submit; ignore errors for now (this node may not own mempool yet)
-        let mut txs: Vec<Transaction> = Vec::with_capacity(request.transactions.len());
-        for raw in &request.transactions {
-            if let Ok(tx) = bincode::deserialize::<Transaction>(raw) {
-                txs.push(tx);
-            }
-        }
-        let _ = self.submit_transactions_to_mempool(&txs).await;
-        Ok(())
+    /// Get network statistics (legacy method for backward compatibility)
+    pub fn get_network_stats_legacy(&self) -> (u64, u64) {
+        // Use block_in_place to avoid blocking async runtime
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let sent = *self.bytes_sent.lock().await;
+                let received = *self.bytes_received.lock().await;
+                (sent, received)
+            })
+        })
     }
 
-    /// Submit validated transactions to the mempool
-    async fn submit_transactions_to_mempool(
-        &self,
-        txs: &[bllvm_protocol::Transaction],
-    ) -> Result<()> {
-        if let Some(ref _mempool_manager) = self.mempool_manager {
-            // Use MempoolManager's add_transaction method
-            // Note: add_transaction requires &mut, so we need to handle this carefully
-            // For now, we'll use a channel or async approach
-            // This is a limitation of the current design - MempoolManager should use interior mutability
-            for tx in txs {
-                // In a real implementation, we'd send this to a mempool processing channel
-                // For now, we validate and accept using consensus layer
-                let utxo_lock = self.utxo_set.lock().await;
-                let mempool_lock = self.mempool.lock().await;
-                let _ = self
-                    .consensus
-                    .accept_to_memory_pool(tx, &utxo_lock, &mempool_lock, 0);
-            }
+    /// Replace NODE_NETWORK with NODE_NETWORK_LIMITED once blocks have been pruned
+    ///
+    /// A pruned node cannot serve the full chain, so peers must not pick it
+    /// for historical block download.
+    fn services_for_pruning(&self, services: u64) -> u64 {
+        use crate::network::protocol::{NODE_NETWORK, NODE_NETWORK_LIMITED};
+        let pruned = self
+            .storage
+            .as_ref()
+            .is_some_and(|storage| storage.prune_height().unwrap_or(0) > 0);
+        if pruned && services & NODE_NETWORK != 0 {
+            (services & !NODE_NETWORK) | NODE_NETWORK_LIMITED
         } else {
-            // Fallback to legacy mempool
-            let utxo_lock = self.utxo_set.lock().await;
-            let mempool_lock = self.mempool.lock().await;
-            for tx in txs {
-                let _ = self
-                    .consensus
-                    .accept_to_memory_pool(tx, &utxo_lock, &mempool_lock, 0);
-            }
+            services
         }
-        Ok(())
     }
 
-    #[cfg(feature = "utxo-commitments")]
-    /// Handle GetFilteredBlock request from a peer
-    async fn handle_get_filtered_block_request(
+    /// User agent sent in our version message
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// Whether BIP157 block filters are served to peers
+    pub fn serves_block_filters(&self) -> bool {
+        self.serve_block_filters
+    }
+
+    /// Create version message with service flags
+    ///
+    /// Creates version message with service flags for all supported features
+    /// and the configured user agent
+    ///
+    /// Sets service flags based on:
+    /// - BIP157: NODE_COMPACT_FILTERS (if block filters are served to peers)
+    /// - UTXO Commitments: NODE_UTXO_COMMITMENTS (if feature enabled)
+    /// - Ban List Sharing: NODE_BAN_LIST_SHARING (if config enabled)
+    /// - Dandelion: NODE_DANDELION (if feature enabled)
+    /// - Package Relay: NODE_PACKAGE_RELAY (always enabled)
+    /// - FIBRE: NODE_FIBRE (always enabled)
+    /// - Pruning: NODE_NETWORK becomes NODE_NETWORK_LIMITED once blocks are pruned
+    pub fn create_version_message(
         &self,
-        data: Vec<u8>,
-        peer_addr: SocketAddr,
-    ) -> Result<()> {
-        use crate::network::protocol::ProtocolMessage;
-        use crate::network::protocol::ProtocolParser;
-        use crate::network::protocol_extensions::handle_get_filtered_block;
+        version: i32,
+        services: u64,
+        timestamp: i64,
+        addr_recv: crate::network::protocol::NetworkAddress,
+        addr_from: crate::network::protocol::NetworkAddress,
+        nonce: u64,
+        start_height: i32,
+        relay: bool,
+    ) -> crate::network::protocol::VersionMessage {
+        use bllvm_protocol::bip157::NODE_COMPACT_FILTERS;
 
-        // Parse the request
-        let protocol_msg = ProtocolParser::parse_message(&data)?;
-        let get_filtered_block_msg = match protocol_msg {
-            ProtocolMessage::GetFilteredBlock(msg) => msg,
-            _ => return Err(anyhow::anyhow!("Expected GetFilteredBlock message")),
-        };
+        // Add service flags for supported features
+        let mut services_with_filters = self.services_for_pruning(services);
 
-        // Handle the request with storage and filter service
-        let storage = self.storage.as_ref().map(Arc::clone);
-        let response =
-            handle_get_filtered_block(get_filtered_block_msg, storage, Some(&self.filter_service))
-                .await?;
+        // BIP157 Compact Block Filters (only if we answer filter requests)
+        if self.serve_block_filters {
+            services_with_filters |= NODE_COMPACT_FILTERS;
+        } else {
+            services_with_filters &= !NODE_COMPACT_FILTERS;
+        }
 
-        // Serialize and send response
-        let response_wire =
-            ProtocolParser::serialize_message(&ProtocolMessage::FilteredBlock(response))?;
-        self.send_to_peer(peer_addr, response_wire).await?;
+        // UTXO Commitments (if feature enabled)
+        #[cfg(feature = "utxo-commitments")]
+        {
+            services_with_filters |= crate::network::protocol::NODE_UTXO_COMMITMENTS;
+        }
 
-        Ok(())
-    }
+        // Ban List Sharing (if config enabled)
+        if self.ban_list_sharing_config.is_some() {
+            services_with_filters |= crate::network::protocol::NODE_BAN_LIST_SHARING;
+        }
 
-    /// Get peer manager reference (locked)
-    ///
-    /// Note: This is now async and uses tokio::sync::Mutex to avoid deadlocks
-    pub async fn peer_manager(&self) -> tokio::sync::MutexGuard<'_, PeerManager> {
-        self.peer_manager.lock().await
-    }
+        // Dandelion (if feature enabled)
+        #[cfg(feature = "dandelion")]
+        {
+            services_with_filters |= crate::network::protocol::NODE_DANDELION;
+        }
 
-    /// Check eclipse prevention for an IP address
-    /// Returns true if connection is allowed, false if it would violate eclipse prevention
-    pub fn check_eclipse_prevention(&self, ip: std::net::IpAddr) -> bool {
-        let prefix = self.get_ip_prefix(ip);
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let diversity = self.peer_diversity.lock().await;
-                let count = diversity.get(&prefix).copied().unwrap_or(0);
-                count < 3 // Allow max 3 connections from same IP prefix
-            })
-        })
-    }
+        // Package Relay (BIP331) - always enabled
+        services_with_filters |= crate::network::protocol::NODE_PACKAGE_RELAY;
 
-    /// Get IP prefix (first 3 octets) for eclipse prevention
-    pub fn get_ip_prefix(&self, ip: std::net::IpAddr) -> [u8; 3] {
-        match ip {
-            std::net::IpAddr::V4(ipv4) => {
-                let octets = ipv4.octets();
-                [octets[0], octets[1], octets[2]]
-            }
-            std::net::IpAddr::V6(ipv6) => {
-                let octets = ipv6.octets();
-                [octets[0], octets[1], octets[2]]
-            }
+        // FIBRE - always enabled
+        services_with_filters |= crate::network::protocol::NODE_FIBRE;
+
+        crate::network::protocol::VersionMessage {
+            version,
+            services: services_with_filters,
+            timestamp,
+            addr_recv,
+            addr_from,
+            nonce,
+            user_agent: self.user_agent.clone(),
+            start_height,
+            relay,
         }
     }
+}
 
-    /// Remove peer diversity tracking for an IP address
-    pub fn remove_peer_diversity(&self, ip: std::net::IpAddr) {
-        let prefix = self.get_ip_prefix(ip);
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut diversity = self.peer_diversity.lock().await;
-                if let Some(count) = diversity.get_mut(&prefix) {
-                    if *count > 0 {
-                        *count -= 1;
-                    }
-                    if *count == 0 {
-                        diversity.remove(&prefix);
-                    }
-                }
-            })
-        })
-    }
-
-    /// Get filter service reference
-    pub fn filter_service(&self) -> &crate::network::filter_service::BlockFilterService {
-        &self.filter_service
-    }
+#[cfg(test)]
+mod tests {
+    mod concurrency_stress_tests;
+    use super::*;
 
-    /// Add a persistent peer (will be connected to on startup)
-    pub fn add_persistent_peer(&self, addr: SocketAddr) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut peers = self.persistent_peers.lock().await;
-                peers.insert(addr);
-            })
-        })
+    #[tokio::test]
+    async fn test_peer_manager_creation() {
+        let manager = PeerManager::new(10);
+        assert_eq!(manager.peer_count(), 0);
+        assert!(manager.can_accept_peer());
     }
 
-    /// Remove a persistent peer
-    pub fn remove_persistent_peer(&self, addr: SocketAddr) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut peers = self.persistent_peers.lock().await;
-                peers.remove(&addr);
-            })
-        })
-    }
+    #[tokio::test]
+    async fn test_peer_manager_add_peer() {
+        let mut manager = PeerManager::new(2);
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        // Create a mock peer without requiring network connection
+        let (_tx, _rx): (mpsc::UnboundedSender<NetworkMessage>, _) = mpsc::unbounded_channel();
 
-    /// Get list of persistent peers (async version for RPC)
-    pub async fn get_persistent_peers(&self) -> HashSet<SocketAddr> {
-        self.persistent_peers.lock().await.clone()
+        // Skip this test since we can't easily create a mock TcpStream
+        // In a real implementation, we'd use dependency injection
+        // For now, just test the manager logic without the peer
+        assert_eq!(manager.peer_count(), 0);
+        assert!(manager.can_accept_peer());
     }
 
-    /// Get list of persistent peers (sync version)
-    pub fn get_persistent_peers_sync(&self) -> Vec<SocketAddr> {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let peers = self.persistent_peers.lock().await;
-                peers.iter().cloned().collect()
-            })
-        })
-    }
+    #[tokio::test]
+    async fn test_peer_manager_max_peers() {
+        let mut manager = PeerManager::new(1);
+        let _addr1: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let _addr2: std::net::SocketAddr = "127.0.0.1:8081".parse().unwrap();
 
-    /// Ban a peer (with optional unban timestamp, 0 = permanent)
-    pub fn ban_peer(&self, addr: SocketAddr, unban_timestamp: u64) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut ban_list = self.ban_list.write().await;
-                if unban_timestamp == 0 {
-                    // Permanent ban (use max timestamp)
-                    ban_list.insert(addr, u64::MAX);
-                } else {
-                    ban_list.insert(addr, unban_timestamp);
-                }
-            })
-        })
-    }
+        // Test manager capacity without creating real peers
+        assert_eq!(manager.peer_count(), 0);
+        assert!(manager.can_accept_peer());
 
-    /// Unban a peer
-    pub fn unban_peer(&self, addr: SocketAddr) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut ban_list = self.ban_list.write().await;
-                ban_list.remove(&addr);
-            })
-        })
+        // Test that we can't exceed max peers
+        // (In a real test, we'd create mock peers, but for now we test the logic)
+        assert_eq!(manager.peer_count(), 0);
     }
 
-    /// Clear all bans
-    pub fn clear_bans(&self) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let mut ban_list = self.ban_list.write().await;
-                ban_list.clear();
-            })
-        })
-    }
+    #[tokio::test]
+    async fn test_peer_manager_remove_peer() {
+        let mut manager = PeerManager::new(10);
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
 
-    /// Handle GetAddr request - return known addresses
-    async fn handle_get_addr(&self, peer_addr: SocketAddr) -> Result<()> {
-        use crate::network::protocol::{AddrMessage, ProtocolMessage, ProtocolParser};
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        // Get fresh addresses from database (up to 2500, Bitcoin Core limit)
-        let ban_list = self.ban_list.read().await.clone();
-        let connected_peers: Vec<SocketAddr> = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_socket_addresses()
-        };
+        // Test removing non-existent peer
+        let transport_addr = TransportAddr::Tcp(addr);
+        let removed_peer = manager.remove_peer(&transport_addr);
+        assert!(removed_peer.is_none());
+        assert_eq!(manager.peer_count(), 0);
+    }
 
-        let addresses = {
-            let db = self.address_database.write().await;
-            let fresh = db.get_fresh_addresses(2500);
-            db.filter_addresses(fresh, &ban_list, &connected_peers)
-        };
+    #[tokio::test]
+    async fn test_peer_manager_get_peer() {
+        let mut manager = PeerManager::new(10);
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
 
-        // Create Addr message
-        let addr_msg = AddrMessage { addresses };
-        let response = ProtocolMessage::Addr(addr_msg);
-        let wire_msg = ProtocolParser::serialize_message(&response)?;
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        // Send response
-        self.send_to_peer(peer_addr, wire_msg).await?;
-        Ok(())
+        // Test getting non-existent peer
+        let transport_addr = TransportAddr::Tcp(addr);
+        let retrieved_peer = manager.get_peer(&transport_addr);
+        assert!(retrieved_peer.is_none());
     }
 
-    /// Handle Addr message - store addresses and optionally relay
-    async fn handle_addr(&self, peer_addr: SocketAddr, msg: AddrMessage) -> Result<()> {
-        // AddrMessage is already in scope as parameter, NetworkAddress is available from top-level import
+    #[tokio::test]
+    async fn test_peer_manager_peer_addresses() {
+        let mut manager = PeerManager::new(10);
+        let _addr1: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let _addr2: std::net::SocketAddr = "127.0.0.1:8081".parse().unwrap();
 
-        // Get peer services from peer state
-        let peer_services = {
-            let peer_states = self.peer_states.read().await;
-            peer_states
-                .get(&peer_addr)
-                .map(|state| state.services)
-                .unwrap_or(0)
-        };
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        // Store addresses in database
-        {
-            let mut db = self.address_database.write().await;
-            for addr in &msg.addresses {
-                db.add_address(addr.clone(), peer_services);
-            }
-        }
+        // Test getting addresses when no peers exist
+        let addresses = manager.peer_addresses();
+        assert_eq!(addresses.len(), 0);
+    }
 
-        // Relay addresses to other peers (with rate limiting)
-        self.relay_addresses(peer_addr, &msg.addresses).await?;
+    #[tokio::test]
+    async fn test_connection_manager_creation() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = ConnectionManager::new(addr);
 
-        Ok(())
+        assert_eq!(manager.listen_addr, addr);
     }
 
-    /// Relay addresses to other peers (excluding sender)
-    async fn relay_addresses(
-        &self,
-        sender_addr: SocketAddr,
-        addresses: &[NetworkAddress],
-    ) -> Result<()> {
-        use crate::network::protocol::{AddrMessage, ProtocolMessage, ProtocolParser};
-        // Rate limiting: don't send addr messages too frequently (Bitcoin Core: ~every 2.4 hours)
-        let now = current_timestamp();
-        let min_interval = 2 * 60 * 60 + 24 * 60; // 2.4 hours in seconds
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_creation() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = NetworkManager::new(addr);
 
-        {
-            let last_sent = *self.last_addr_sent.lock().await;
-            if now.saturating_sub(last_sent) < min_interval {
-                // Too soon, skip relay
-                return Ok(());
-            }
-        }
+        assert_eq!(manager.peer_count(), 0);
+        assert_eq!(manager.peer_addresses().len(), 0);
+    }
 
-        // Filter addresses (exclude local, banned, already connected)
-        let ban_list = self.ban_list.read().await.clone();
-        let connected_peers: Vec<SocketAddr> = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_socket_addresses()
-        };
+    #[tokio::test]
+    async fn test_network_manager_with_config() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            addr,
+            5,
+            crate::network::transport::TransportPreference::TCP_ONLY,
+            None,
+        );
 
-        let filtered = {
-            let db = self.address_database.read().await;
-            db.filter_addresses(addresses.to_vec(), &ban_list, &connected_peers)
-        };
+        // peer_count() might not exist, check peer_manager instead
+        let peer_manager = manager.peer_manager().await;
+        assert_eq!(peer_manager.peer_count(), 0);
+    }
 
-        if filtered.is_empty() {
-            return Ok(());
-        }
+    #[tokio::test]
+    async fn test_dns_seeding_skipped_behind_proxy() {
+        let config = crate::config::NodeConfig {
+            proxy: Some(crate::config::ProxyConfig {
+                addr: "127.0.0.1:9050".parse().unwrap(),
+                username: None,
+                password: None,
+                stream_isolation: true,
+            }),
+            dns_seeds: vec!["seed.invalid".to_string()],
+            ..Default::default()
+        };
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            5,
+            crate::network::transport::TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
 
-        // Limit to 1000 addresses per message (Bitcoin Core limit)
-        let addresses_to_relay: Vec<NetworkAddress> = filtered.into_iter().take(1000).collect();
+        manager
+            .discover_peers_from_dns("mainnet", 8333, &config)
+            .await
+            .unwrap();
+        assert!(manager.dns_seeder.lock().await.is_none());
+        assert!(manager.address_database.read().await.is_empty());
+    }
 
-        // Create Addr message
-        let addr_msg = AddrMessage {
-            addresses: addresses_to_relay,
-        };
-        let relay_msg = ProtocolMessage::Addr(addr_msg);
-        let wire_msg = ProtocolParser::serialize_message(&relay_msg)?;
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_peer_count() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut manager = NetworkManager::new(addr);
 
-        // Send to all peers except sender
-        let peer_addrs: Vec<SocketAddr> = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_socket_addresses()
-                .into_iter()
-                .filter(|addr| *addr != sender_addr)
-                .collect()
-        };
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
+    }
 
-        for peer_addr in peer_addrs {
-            if let Err(e) = self.send_to_peer(peer_addr, wire_msg.clone()).await {
-                warn!("Failed to relay addresses to {}: {}", peer_addr, e);
-            }
-        }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_peer_addresses() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut manager = NetworkManager::new(addr);
 
-        // Update last sent time
-        *self.last_addr_sent.lock().await = now;
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        Ok(())
+        // Test getting addresses when no peers exist
+        let addresses = manager.peer_addresses();
+        assert_eq!(addresses.len(), 0);
     }
 
-    /// Send our own address to peers (self-advertisement)
-    pub async fn advertise_self(&self, listen_addr: SocketAddr, services: u64) -> Result<()> {
-        if !self.enable_self_advertisement {
-            return Ok(()); // Self-advertisement disabled
-        }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_broadcast() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut manager = NetworkManager::new(addr);
 
-        use crate::network::protocol::{
-            AddrMessage, NetworkAddress, ProtocolMessage, ProtocolParser,
-        };
-        use std::net::IpAddr;
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        // Convert SocketAddr to NetworkAddress
-        let ip_bytes = match listen_addr.ip() {
-            IpAddr::V4(ipv4) => {
-                // IPv4-mapped IPv6 format
-                let mut bytes = [0u8; 16];
-                bytes[10] = 0xff;
-                bytes[11] = 0xff;
-                bytes[12..16].copy_from_slice(&ipv4.octets());
-                bytes
-            }
-            IpAddr::V6(ipv6) => ipv6.octets(),
-        };
+        // Test broadcast with no peers (should succeed)
+        let message = b"test message".to_vec();
+        let result = manager.broadcast(message).await;
+        assert!(result.is_ok());
+    }
 
-        let our_addr = NetworkAddress {
-            services,
-            ip: ip_bytes,
-            port: listen_addr.port(),
-        };
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_send_to_peer() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let mut manager = NetworkManager::new(addr);
 
-        // Create Addr message with just our address
-        let addr_msg = AddrMessage {
-            addresses: vec![our_addr.clone()],
-        };
-        let relay_msg = ProtocolMessage::Addr(addr_msg);
-        let wire_msg = ProtocolParser::serialize_message(&relay_msg)?;
+        // Test manager logic without creating real peers
+        assert_eq!(manager.peer_count(), 0);
 
-        // Send to all connected peers
-        let peer_addrs: Vec<SocketAddr> = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_socket_addresses()
-        };
+        // Test send to non-existent peer (should succeed but not actually send)
+        let peer_addr = "127.0.0.1:8081".parse().unwrap();
+        let message = b"test message".to_vec();
+        let result = manager.send_to_peer(peer_addr, message).await;
+        assert!(result.is_ok()); // Should succeed even for non-existent peer
+    }
 
-        for peer_addr in peer_addrs {
-            if let Err(e) = self.send_to_peer(peer_addr, wire_msg.clone()).await {
-                warn!("Failed to advertise self to {}: {}", peer_addr, e);
+    #[tokio::test]
+    async fn test_network_manager_send_to_nonexistent_peer() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = NetworkManager::new(addr);
+
+        // Test send to non-existent peer
+        let peer_addr = "127.0.0.1:8081".parse().unwrap();
+        let message = b"test message".to_vec();
+        let result = manager.send_to_peer(peer_addr, message).await;
+        assert!(result.is_ok()); // Should not error, just do nothing
+    }
+
+    #[tokio::test]
+    async fn test_network_message_peer_connected() {
+        let socket_addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let transport_addr = TransportAddr::Tcp(socket_addr);
+        let message = NetworkMessage::PeerConnected(transport_addr.clone());
+        match message {
+            NetworkMessage::PeerConnected(addr) => {
+                assert_eq!(addr, transport_addr);
             }
+            _ => panic!("Expected PeerConnected message"),
         }
+    }
 
-        // Also store our own address in database
-        {
-            let mut db = self.address_database.write().await;
-            db.add_address(our_addr, services);
+    #[tokio::test]
+    async fn test_network_message_peer_disconnected() {
+        let socket_addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let transport_addr = TransportAddr::Tcp(socket_addr);
+        let message = NetworkMessage::PeerDisconnected(transport_addr.clone());
+        match message {
+            NetworkMessage::PeerDisconnected(addr) => {
+                assert_eq!(addr, transport_addr);
+            }
+            _ => panic!("Expected PeerDisconnected message"),
         }
-
-        Ok(())
     }
 
-    /// Get list of banned peers
-    pub fn get_banned_peers(&self) -> Vec<(SocketAddr, u64)> {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let ban_list = self.ban_list.read().await;
-                ban_list
-                    .iter()
-                    .map(|(addr, timestamp)| (*addr, *timestamp))
-                    .collect()
-            })
-        })
+    #[tokio::test]
+    async fn test_network_message_block_received() {
+        let data = b"block data".to_vec();
+        let message = NetworkMessage::BlockReceived(data.clone());
+        match message {
+            NetworkMessage::BlockReceived(msg_data) => {
+                assert_eq!(msg_data, data);
+            }
+            _ => panic!("Expected BlockReceived message"),
+        }
     }
 
-    /// Get peer addresses (async version for RPC)
-    pub async fn get_peer_addresses(&self) -> Vec<TransportAddr> {
-        let pm = self.peer_manager.lock().await;
-        pm.peer_addresses()
+    #[tokio::test]
+    async fn test_network_message_transaction_received() {
+        let data = b"tx data".to_vec();
+        let message = NetworkMessage::TransactionReceived(data.clone());
+        match message {
+            NetworkMessage::TransactionReceived(msg_data) => {
+                assert_eq!(msg_data, data);
+            }
+            _ => panic!("Expected TransactionReceived message"),
+        }
     }
 
-    /// Set network active state
-    pub async fn set_network_active(&self, active: bool) -> Result<()> {
-        let mut state = self.network_active.lock().await;
-        *state = active;
-        info!("Network active state set to: {}", active);
-        Ok(())
+    #[tokio::test]
+    async fn test_network_message_inventory_received() {
+        let data = b"inv data".to_vec();
+        let message = NetworkMessage::InventoryReceived(data.clone());
+        match message {
+            NetworkMessage::InventoryReceived(msg_data) => {
+                assert_eq!(msg_data, data);
+            }
+            _ => panic!("Expected InventoryReceived message"),
+        }
     }
 
-    /// Get network active state
-    pub fn is_network_active(&self) -> bool {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async { *self.network_active.lock().await })
-        })
+    // NOTE: test_handle_incoming_wire_tcp_enqueues_pkgtxn was removed because it hangs
+    // Note: All Mutex usage is now tokio::sync::Mutex with async-safe .await calls.
+    // The handle_incoming_wire_tcp function uses async-safe track_bytes_received
+    // and is_banned) which blocks the async runtime when there's contention.
+    // Full message routing is tested in integration tests.
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_network_manager_peer_manager_access() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = NetworkManager::new(addr);
+
+        // Test immutable access - drop the guard immediately to avoid holding lock
+        {
+            let peer_manager = manager.peer_manager().await;
+            assert_eq!(peer_manager.peer_count(), 0);
+        } // Guard dropped here
+
+        // Test peer count access (this also locks the mutex, but guard is dropped immediately)
+        assert_eq!(manager.peer_count(), 0);
     }
 
-    /// Check if a peer is banned
-    pub fn is_banned(&self, addr: SocketAddr) -> bool {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let ban_list = self.ban_list.read().await;
-                if let Some(&unban_timestamp) = ban_list.get(&addr) {
-                    if unban_timestamp == u64::MAX {
-                        return true; // Permanent ban
-                    }
-                    // Check if ban has expired
-                    let now = current_timestamp();
-                    if now < unban_timestamp {
-                        return true; // Still banned
-                    } else {
-                        // Ban expired, remove it
-                        drop(ban_list);
-                        self.unban_peer(addr);
-                        return false;
-                    }
-                }
-                false
-            })
-        })
+    #[tokio::test]
+    async fn test_network_manager_transport_preference() {
+        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let manager = NetworkManager::new(addr);
+
+        assert_eq!(
+            manager.transport_preference(),
+            TransportPreference::TCP_ONLY
+        );
     }
 
-    /// Track bytes sent (async-safe)
-    pub async fn track_bytes_sent(&self, bytes: u64) {
-        let mut sent = self.bytes_sent.lock().await;
-        *sent += bytes;
+    /// Connect a real TCP peer to the manager; the returned remote end never sends anything
+    async fn add_connected_peer(
+        manager: &NetworkManager,
+    ) -> (TransportAddr, tokio::net::TcpStream) {
+        let (transport_addr, peer, remote) = connected_test_peer(manager).await;
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .add_peer(transport_addr.clone(), peer)
+            .unwrap();
+        (transport_addr, remote)
     }
 
-    /// Track bytes received (async-safe)
-    pub async fn track_bytes_received(&self, bytes: u64) {
-        let mut received = self.bytes_received.lock().await;
-        *received += bytes;
+    /// Open a real TCP connection and wrap it in a peer without registering it
+    async fn connected_test_peer(
+        manager: &NetworkManager,
+    ) -> (TransportAddr, peer::Peer, tokio::net::TcpStream) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote_addr = listener.local_addr().unwrap();
+        let accept = tokio::spawn(async move { listener.accept().await.unwrap() });
+        let conn = TcpTransport::new()
+            .connect(TransportAddr::Tcp(remote_addr))
+            .await
+            .unwrap();
+        let (remote, _) = accept.await.unwrap();
+
+        let transport_addr = TransportAddr::Tcp(remote_addr);
+        let peer = peer::Peer::from_transport_connection_with_bandwidth(
+            conn,
+            remote_addr,
+            transport_addr.clone(),
+            manager.peer_tx.clone(),
+            manager.bandwidth_limits.for_peer(),
+        );
+        (transport_addr, peer, remote)
     }
 
-    /// Handle GetBanList message - respond with ban list or hash
-    async fn handle_get_ban_list(
-        &self,
-        peer_addr: SocketAddr,
-        msg: crate::network::protocol::GetBanListMessage,
-    ) -> Result<()> {
-        use crate::network::ban_list_merging::calculate_ban_list_hash;
-        use crate::network::protocol::{BanEntry, BanListMessage, NetworkAddress};
-        debug!(
-            "GetBanList request from {}: full={}, min_duration={}",
-            peer_addr, msg.request_full, msg.min_ban_duration
+    #[tokio::test]
+    async fn test_block_relay_only_peer_gets_block_invs_but_no_tx_invs() {
+        let manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (relay_addr, relay_remote) = add_connected_peer(&manager).await;
+        let (full_addr, _full_remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(relay_sock) = relay_addr.clone() else {
+            unreachable!()
+        };
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer_mut(&relay_addr)
+            .unwrap()
+            .set_block_relay_only(true);
+        assert!(!manager.version_relay(relay_sock).await);
+        assert_eq!(
+            manager
+                .peer_manager
+                .lock()
+                .await
+                .addr_relay_socket_addresses()
+                .len(),
+            1
         );
 
-        // Get current ban list
-        let ban_list = self.ban_list.read().await;
-        let now = current_timestamp();
+        // Only the full-relay peer hears about the transaction
+        let txid = [7u8; 32];
+        assert_eq!(manager.announce_transaction(txid, 1_000).await.unwrap(), 1);
+        {
+            let pm = manager.peer_manager.lock().await;
+            assert!(!pm.get_peer(&relay_addr).unwrap().has_known_inventory(&txid));
+            assert!(pm.get_peer(&full_addr).unwrap().has_known_inventory(&txid));
+        }
 
-        // Convert to BanEntry format, filtering by min_ban_duration
-        let mut ban_entries: Vec<BanEntry> = Vec::new();
-        for (addr, &unban_timestamp) in ban_list.iter() {
-            // Skip expired bans
-            if unban_timestamp != u64::MAX && now >= unban_timestamp {
-                continue;
+        // Both hear about the block; the tx inv never reached the relay-only peer
+        let block_hash = [8u8; 32];
+        assert_eq!(manager.announce_block(block_hash).await.unwrap(), 2);
+        let (mut relay_rd, _relay_wr) = relay_remote.into_split();
+        match read_peer_message(&mut relay_rd).await {
+            ProtocolMessage::Inv(inv) => {
+                assert_eq!(inv.inventory.len(), 1);
+                assert_eq!(inv.inventory[0].inv_type, inventory::MSG_BLOCK);
+                assert_eq!(inv.inventory[0].hash, block_hash);
             }
+            other => panic!("expected block inv, got {:?}", other),
+        }
+    }
 
-            // Filter by min_ban_duration
-            if msg.min_ban_duration > 0 {
-                let ban_duration = if unban_timestamp == u64::MAX {
-                    u64::MAX
-                } else {
-                    unban_timestamp.saturating_sub(now)
-                };
-                if ban_duration < msg.min_ban_duration {
-                    continue;
-                }
-            }
+    #[tokio::test]
+    async fn test_outbound_peers_connect_when_inbound_slots_are_full() {
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            4,
+            TransportPreference::TCP_ONLY,
+            None,
+        );
+        let add_inbound = || async {
+            let (addr, mut peer, remote) = connected_test_peer(&manager).await;
+            peer.set_inbound(true);
+            let result = manager.peer_manager.lock().await.add_peer(addr, peer);
+            (result, remote)
+        };
 
-            // Convert SocketAddr to NetworkAddress
-            let ip_bytes = match addr.ip() {
-                std::net::IpAddr::V4(ipv4) => {
-                    let mut bytes = [0u8; 16];
-                    bytes[12..16].copy_from_slice(&ipv4.octets());
-                    bytes
-                }
-                std::net::IpAddr::V6(ipv6) => ipv6.octets(),
-            };
+        // Half of the 4 slots are reserved for outbound peers
+        let (first, _remote1) = add_inbound().await;
+        let (second, _remote2) = add_inbound().await;
+        assert!(first.is_ok() && second.is_ok());
+        assert!(add_inbound().await.0.is_err());
+        assert!(!manager.peer_manager.lock().await.can_accept_inbound());
 
-            ban_entries.push(BanEntry {
-                addr: NetworkAddress {
-                    services: 0,
-                    ip: ip_bytes,
-                    port: addr.port(),
-                },
-                unban_timestamp,
-                reason: Some("DoS protection".to_string()),
-            });
+        let _outbound1 = add_connected_peer(&manager).await;
+        let _outbound2 = add_connected_peer(&manager).await;
+        {
+            let pm = manager.peer_manager.lock().await;
+            assert_eq!(pm.inbound_count(), 2);
+            assert_eq!(pm.outbound_count(), 2);
+            assert!(!pm.can_accept_peer());
         }
 
-        // Calculate hash
-        let ban_list_hash = calculate_ban_list_hash(&ban_entries);
-        let ban_entries_count = ban_entries.len();
+        // Raising the limit at runtime opens another inbound slot
+        manager.set_max_peers(6).await;
+        let (third, _remote3) = add_inbound().await;
+        assert!(third.is_ok());
+        assert!(add_inbound().await.0.is_err());
+    }
 
-        // Create response
-        let response = BanListMessage {
-            is_full: msg.request_full,
-            ban_list_hash,
-            ban_entries: if msg.request_full {
-                ban_entries
-            } else {
-                Vec::new()
-            },
-            timestamp: now,
+    #[tokio::test]
+    async fn test_inbound_peers_limited_per_network_group() {
+        let config = crate::config::NodeConfig {
+            dos_protection: Some(crate::config::DosProtectionConfig {
+                max_inbound_per_netgroup: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            20,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let manager = &manager;
+        let add_inbound = |whitelisted: bool| async move {
+            let (addr, mut peer, remote) = connected_test_peer(manager).await;
+            peer.set_inbound(true);
+            peer.set_whitelisted(whitelisted);
+            let result = manager.peer_manager.lock().await.add_peer(addr, peer);
+            (result, remote)
         };
 
-        // Serialize and send response
-        let response_msg = ProtocolMessage::BanList(response);
-        let serialized = ProtocolParser::serialize_message(&response_msg)?;
-        self.send_to_peer(peer_addr, serialized).await?;
-
-        debug!(
-            "Sent BanList response to {}: {} entries",
-            peer_addr,
-            if msg.request_full {
-                ban_entries_count
-            } else {
-                0
+        // Every test peer connects from loopback, which is one group
+        let (first, _remote1) = add_inbound(false).await;
+        let (second, _remote2) = add_inbound(false).await;
+        assert!(first.is_ok() && second.is_ok());
+        let (third, _remote3) = add_inbound(false).await;
+        assert!(third.unwrap_err().to_string().contains("network group"));
+
+        // Whitelisted and outbound peers are not subject to the limit
+        let (whitelisted, _remote4) = add_inbound(true).await;
+        assert!(whitelisted.is_ok());
+        let _outbound = add_connected_peer(manager).await;
+
+        let pm = manager.peer_manager.lock().await;
+        let local = TransportAddr::Tcp("127.0.200.1:8333".parse().unwrap());
+        let other = TransportAddr::Tcp("40.1.0.1:8333".parse().unwrap());
+        assert_eq!(pm.inbound_count_in_netgroup(&local), 3);
+        assert!(pm.netgroup_full(&local));
+        assert_eq!(pm.inbound_count_in_netgroup(&other), 0);
+        assert!(!pm.netgroup_full(&other));
+        assert_eq!(
+            pm.connection_counts(),
+            connection_manager::ConnectionCounts {
+                inbound: 3,
+                outbound_full_relay: 1,
+                block_relay_only: 0,
+                feeler: 0,
             }
         );
-
-        Ok(())
     }
 
-    /// Handle BanList message - merge received ban list
-    async fn handle_ban_list(
-        &self,
-        peer_addr: SocketAddr,
-        msg: crate::network::protocol::BanListMessage,
-    ) -> Result<()> {
-        use crate::network::ban_list_merging::{validate_ban_entry, verify_ban_list_hash};
-        use std::net::IpAddr;
+    #[tokio::test]
+    async fn test_connection_counts_by_type() {
+        let manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (relay_addr, _relay_remote) = add_connected_peer(&manager).await;
+        let _full = add_connected_peer(&manager).await;
+        let (inbound_addr, _inbound_remote) = add_connected_peer(&manager).await;
+        {
+            let mut pm = manager.peer_manager.lock().await;
+            pm.get_peer_mut(&relay_addr)
+                .unwrap()
+                .set_block_relay_only(true);
+            pm.get_peer_mut(&inbound_addr).unwrap().set_inbound(true);
+        }
+        manager.connection_manager.feeler_started();
+
+        let counts = manager.connection_counts().await;
+        assert_eq!(counts.inbound, 1);
+        assert_eq!(counts.outbound_full_relay, 1);
+        assert_eq!(counts.block_relay_only, 1);
+        assert_eq!(counts.feeler, 1);
+        assert_eq!(counts.outbound(), 3);
+
+        manager.connection_manager.feeler_finished();
+        manager.connection_manager.feeler_finished();
+        assert_eq!(manager.connection_counts().await.feeler, 0);
+
+        let stats = manager.get_network_stats().await;
+        assert_eq!(stats.inbound_connections, 1);
+        assert_eq!(stats.block_relay_only_connections, 1);
+    }
 
-        debug!(
-            "BanList received from {}: full={}, {} entries",
-            peer_addr,
-            msg.is_full,
-            msg.ban_entries.len()
+    #[tokio::test]
+    async fn test_peer_that_never_pongs_is_disconnected() {
+        let config = crate::config::NodeConfig {
+            network_timing: Some(crate::config::NetworkTimingConfig {
+                ping_timeout_seconds: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
         );
 
-        // Verify hash if full list provided
-        if msg.is_full {
-            if !verify_ban_list_hash(&msg.ban_entries, &msg.ban_list_hash) {
-                warn!("Ban list hash verification failed from {}", peer_addr);
-                return Ok(()); // Silently ignore invalid ban lists
-            }
-        }
+        // Remote end accepts the connection but never answers anything
+        let (transport_addr, _remote) = add_connected_peer(&manager).await;
 
-        // If hash-only, we can't merge (would need to request full list)
-        if !msg.is_full {
-            debug!(
-                "Received hash-only ban list from {}, skipping merge",
-                peer_addr
-            );
-            return Ok(());
+        // First check sends a ping
+        assert!(manager.check_peer_liveness().await.unwrap().is_empty());
+        {
+            let pm = manager.peer_manager.lock().await;
+            let peer = pm.get_peer(&transport_addr).unwrap();
+            assert!(peer
+                .ping_wait_ms(current_timestamp_duration().as_micros() as u64)
+                .is_some());
         }
 
-        // Validate and merge ban entries
-        let mut ban_list = self.ban_list.write().await;
-        let mut merged_count = 0;
+        // Still within the timeout: the peer stays
+        assert!(manager.check_peer_liveness().await.unwrap().is_empty());
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 1);
 
-        for entry in &msg.ban_entries {
-            if !validate_ban_entry(entry) {
-                continue; // Skip invalid entries
+        // No pong after the timeout: the peer is disconnected
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let disconnected = manager.check_peer_liveness().await.unwrap();
+        assert_eq!(disconnected, vec![transport_addr.clone()]);
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 0);
+
+        let mut saw_disconnect = false;
+        while let Ok(msg) = manager.peer_rx.try_recv() {
+            if let NetworkMessage::PeerDisconnected(addr) = msg {
+                assert_eq!(addr, transport_addr);
+                saw_disconnect = true;
             }
+        }
+        assert!(saw_disconnect);
+    }
 
-            // Convert NetworkAddress to SocketAddr
-            let ip = if entry.addr.ip[0..12] == [0u8; 12] {
-                // IPv4-mapped IPv6 address
-                let ipv4_bytes = &entry.addr.ip[12..16];
-                IpAddr::V4(std::net::Ipv4Addr::new(
-                    ipv4_bytes[0],
-                    ipv4_bytes[1],
-                    ipv4_bytes[2],
-                    ipv4_bytes[3],
-                ))
-            } else {
-                // IPv6 address
-                let mut ipv6_bytes = [0u8; 16];
-                ipv6_bytes.copy_from_slice(&entry.addr.ip);
-                IpAddr::V6(std::net::Ipv6Addr::from(ipv6_bytes))
-            };
+    #[tokio::test]
+    async fn test_peer_that_never_sends_version_is_disconnected() {
+        let config = crate::config::NodeConfig {
+            network_timing: Some(crate::config::NetworkTimingConfig {
+                handshake_timeout_seconds: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
 
-            let socket_addr = SocketAddr::new(ip, entry.addr.port);
+        // Remote end completes the TCP connection but never sends version
+        let (transport_addr, _remote) = add_connected_peer(&manager).await;
+        assert!(!manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer(&transport_addr)
+            .unwrap()
+            .version_negotiation()
+            .handshake_complete());
 
-            // Merge: use longer ban duration if address already exists
-            match ban_list.get(&socket_addr) {
-                Some(&existing_timestamp) => {
-                    if entry.unban_timestamp == u64::MAX {
-                        // Permanent ban always wins
-                        ban_list.insert(socket_addr, u64::MAX);
-                        merged_count += 1;
-                    } else if existing_timestamp != u64::MAX {
-                        // Both temporary - use longer one
-                        if entry.unban_timestamp > existing_timestamp {
-                            ban_list.insert(socket_addr, entry.unban_timestamp);
-                            merged_count += 1;
-                        }
-                    }
-                }
-                None => {
-                    // New ban entry
-                    ban_list.insert(socket_addr, entry.unban_timestamp);
-                    merged_count += 1;
-                }
+        assert!(manager.check_peer_liveness().await.unwrap().is_empty());
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        let disconnected = manager.check_peer_liveness().await.unwrap();
+        assert_eq!(disconnected, vec![transport_addr]);
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 0);
+
+        let stats = manager.get_network_stats().await;
+        assert_eq!(stats.handshake_timeouts, 1);
+        assert_eq!(stats.connect_timeouts, 0);
+    }
+
+    #[tokio::test]
+    async fn test_large_send_is_paced_by_upload_limit() {
+        use tokio::io::AsyncReadExt;
+
+        let config = crate::config::NodeConfig {
+            bandwidth: Some(crate::config::BandwidthConfig {
+                peer_upload_bytes_per_second: 10_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let (transport_addr, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+
+        // The peer's read task holds the connection while waiting for a
+        // message, so keep the remote talking to let writes through
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+
+        // 30KB at 10KB/s with a 10KB burst: the first message goes out at once,
+        // the other two are queued and paced
+        let start = std::time::Instant::now();
+        {
+            let pm = manager.peer_manager.lock().await;
+            let peer = pm.get_peer(&transport_addr).unwrap();
+            for _ in 0..3 {
+                peer.send_message(vec![0u8; 10_000]).await.unwrap();
             }
         }
 
-        debug!("Merged {} ban entries from {}", merged_count, peer_addr);
-        Ok(())
-    }
+        let mut frame = vec![0u8; 4 + 10_000];
+        remote_rd.read_exact(&mut frame).await.unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_millis(500));
+        {
+            let pm = manager.peer_manager.lock().await;
+            let throttle = pm.get_peer(&transport_addr).unwrap().throttle_state();
+            assert_eq!(throttle.upload_limit, Some(10_000));
+            assert!(throttle.upload_throttled);
+        }
 
-    /// Get DoS protection manager reference
-    pub fn dos_protection(&self) -> &Arc<dos_protection::DosProtectionManager> {
-        &self.dos_protection
+        for _ in 0..2 {
+            remote_rd.read_exact(&mut frame).await.unwrap();
+        }
+        assert!(start.elapsed() >= std::time::Duration::from_millis(1900));
     }
 
-    /// Get network statistics
-    pub async fn get_network_stats(&self) -> crate::node::metrics::NetworkMetrics {
-        let sent = *self.bytes_sent.lock().await;
-        let received = *self.bytes_received.lock().await;
-        let active_connections = {
-            let pm = self.peer_manager.lock().await;
-            pm.peer_count()
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sendrawtransaction_announces_inv_to_peers() {
+        use crate::network::protocol::FeeFilterMessage;
+        use crate::rpc::rawtx::RawTxRpc;
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::serialization::transaction::serialize_transaction;
+        use bllvm_protocol::{OutPoint, Transaction, TransactionInput, TransactionOutput, UTXO};
+        use tokio::io::AsyncReadExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let prevout = OutPoint {
+            hash: [7u8; 32],
+            index: 0,
         };
-        let banned_peers_count = {
-            let ban_list = self.ban_list.read().await;
-            ban_list.len()
+        let coin = UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
         };
-        let _resource_metrics = self.dos_protection.get_metrics().await;
+        storage.utxos().add_utxo(&prevout, &coin).unwrap();
+        let tx = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 90_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let txid = calculate_tx_id(&tx);
 
-        crate::node::metrics::NetworkMetrics {
-            peer_count: active_connections,
-            bytes_sent: sent,
-            bytes_received: received,
-            messages_sent: 0,     // Would need to track this
-            messages_received: 0, // Would need to track this
-            active_connections,
-            banned_peers: banned_peers_count,
-            connection_attempts: 0, // Would need to track this
-            connection_failures: 0, // Would need to track this
-            dos_protection: crate::node::metrics::DosMetrics {
-                connection_rate_violations: 0,   // Would need to track this
-                auto_bans: 0,                    // Would need to track this
-                message_queue_overflows: 0,      // Would need to track this
-                active_connection_limit_hits: 0, // Would need to track this
-                resource_exhaustion_events: 0,   // Would need to track this
-            },
+        let manager = Arc::new(NetworkManager::new("127.0.0.1:0".parse().unwrap()));
+        let (_, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+
+        // A second peer only wants transactions paying far more than this one
+        let (picky_addr, _picky_remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(picky_socket) = picky_addr else {
+            unreachable!()
+        };
+        let feefilter =
+            ProtocolParser::serialize_message(&ProtocolMessage::FeeFilter(FeeFilterMessage {
+                feerate: 100_000_000,
+            }))
+            .unwrap();
+        manager
+            .handle_incoming_wire_tcp(picky_socket, feefilter)
+            .await
+            .unwrap();
+
+        let rpc = RawTxRpc::with_dependencies(storage, Arc::new(MempoolManager::new()), None, None)
+            .with_network(Arc::clone(&manager));
+        let hex_tx = hex::encode(serialize_transaction(&tx));
+        rpc.sendrawtransaction(&serde_json::json!([hex_tx]))
+            .await
+            .unwrap();
+        assert_eq!(manager.flush_tx_inventory().await.unwrap(), 1);
+
+        let mut len = [0u8; 4];
+        remote_rd.read_exact(&mut len).await.unwrap();
+        let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+        remote_rd.read_exact(&mut frame).await.unwrap();
+        match ProtocolParser::parse_message(&frame).unwrap() {
+            ProtocolMessage::Inv(inv) => {
+                assert_eq!(inv.inventory.len(), 1);
+                assert_eq!(inv.inventory[0].inv_type, inventory::MSG_TX);
+                assert_eq!(inv.inventory[0].hash, txid);
+            }
+            other => panic!("expected inv, got {:?}", other),
         }
-    }
 
-    /// Get network statistics (legacy method for backward compatibility)
-    pub fn get_network_stats_legacy(&self) -> (u64, u64) {
-        // Use block_in_place to avoid blocking async runtime
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let sent = *self.bytes_sent.lock().await;
-                let received = *self.bytes_received.lock().await;
-                (sent, received)
-            })
-        })
+        // Already announced to the first peer; still filtered for the second
+        assert_eq!(manager.announce_transaction(txid, 1_000).await.unwrap(), 0);
+        let pm = manager.peer_manager.lock().await;
+        assert!(!pm.get_peer(&picky_addr).unwrap().has_known_inventory(&txid));
     }
 
-    /// Create version message with service flags
-    ///
-    /// Creates version message with service flags for all supported features
-    ///
-    /// Sets service flags based on:
-    /// - BIP157: NODE_COMPACT_FILTERS (always enabled if filter service exists)
-    /// - UTXO Commitments: NODE_UTXO_COMMITMENTS (if feature enabled)
-    /// - Ban List Sharing: NODE_BAN_LIST_SHARING (if config enabled)
-    /// - Dandelion: NODE_DANDELION (if feature enabled)
-    /// - Package Relay: NODE_PACKAGE_RELAY (always enabled)
-    /// - FIBRE: NODE_FIBRE (always enabled)
-    pub fn create_version_message(
-        &self,
-        version: i32,
-        services: u64,
-        timestamp: i64,
-        addr_recv: crate::network::protocol::NetworkAddress,
-        addr_from: crate::network::protocol::NetworkAddress,
-        nonce: u64,
-        user_agent: String,
-        start_height: i32,
-        relay: bool,
-    ) -> crate::network::protocol::VersionMessage {
-        use bllvm_protocol::bip157::NODE_COMPACT_FILTERS;
+    #[cfg(feature = "dandelion")]
+    #[tokio::test]
+    async fn test_dandelion_stems_to_one_peer_then_fluffs_after_timeout() {
+        let config = crate::config::NodeConfig {
+            relay: Some(crate::config::RelayConfig {
+                enable_dandelion: true,
+                ..Default::default()
+            }),
+            dandelion: Some(crate::config::DandelionConfig {
+                stem_timeout_seconds: 1,
+                fluff_probability: 0.0,
+                max_stem_hops: 2,
+            }),
+            ..Default::default()
+        };
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let mut remotes = Vec::new();
+        for _ in 0..3 {
+            remotes.push(add_connected_peer(&manager).await);
+        }
+        let peers_knowing = |pm: &PeerManager, txid: &Hash| {
+            remotes
+                .iter()
+                .filter(|(addr, _)| pm.get_peer(addr).unwrap().has_known_inventory(txid))
+                .count()
+        };
 
-        // Add service flags for supported features
-        let mut services_with_filters = services;
+        // Stem phase: announced to exactly one peer
+        let txid = [9u8; 32];
+        assert_eq!(manager.announce_transaction(txid, 1_000).await.unwrap(), 1);
+        assert_eq!(peers_knowing(&*manager.peer_manager.lock().await, &txid), 1);
+        assert!(manager
+            .process_dandelion_timeouts()
+            .await
+            .unwrap()
+            .is_empty());
 
-        // BIP157 Compact Block Filters (always enabled if filter service exists)
-        services_with_filters |= NODE_COMPACT_FILTERS;
+        // Stem timeout: fluffed to everyone else
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        assert_eq!(
+            manager.process_dandelion_timeouts().await.unwrap(),
+            vec![txid]
+        );
+        assert_eq!(peers_knowing(&*manager.peer_manager.lock().await, &txid), 3);
+    }
 
-        // UTXO Commitments (if feature enabled)
-        #[cfg(feature = "utxo-commitments")]
+    #[tokio::test]
+    async fn test_tx_announcements_are_coalesced_until_trickle() {
+        let manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (addr, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, _remote_wr) = remote.into_split();
+        let now = current_timestamp_duration().as_micros() as u64;
+
+        // Quick successive transactions are queued, not sent
+        let first: Vec<Hash> = (1..=3u8).map(|i| [i; 32]).collect();
+        for txid in &first {
+            assert_eq!(manager.announce_transaction(*txid, 1_000).await.unwrap(), 1);
+        }
+        // Already queued: not queued again
+        assert_eq!(
+            manager.announce_transaction(first[0], 1_000).await.unwrap(),
+            0
+        );
         {
-            services_with_filters |= crate::network::protocol::NODE_UTXO_COMMITMENTS;
+            let pm = manager.peer_manager.lock().await;
+            let peer = pm.get_peer(&addr).unwrap();
+            assert_eq!(peer.queued_tx_inv_count(), 3);
+            assert!(first.iter().all(|txid| peer.has_known_inventory(txid)));
         }
 
-        // Ban List Sharing (if config enabled)
-        if self.ban_list_sharing_config.is_some() {
-            services_with_filters |= crate::network::protocol::NODE_BAN_LIST_SHARING;
+        // The first trickle sends them together
+        assert_eq!(manager.flush_tx_inventory_at(now).await.unwrap(), 1);
+        match read_peer_message(&mut remote_rd).await {
+            ProtocolMessage::Inv(inv) => {
+                let hashes: Vec<Hash> = inv.inventory.iter().map(|item| item.hash).collect();
+                assert_eq!(hashes, first);
+            }
+            other => panic!("expected inv, got {:?}", other),
         }
 
-        // Dandelion (if feature enabled)
-        #[cfg(feature = "dandelion")]
+        // Later transactions wait for the next trickle
+        let second: Vec<Hash> = (4..=5u8).map(|i| [i; 32]).collect();
+        for txid in &second {
+            manager.announce_transaction(*txid, 1_000).await.unwrap();
+        }
+        let next = manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer(&addr)
+            .unwrap()
+            .next_inv_send_micros();
+        assert!(next >= now);
+        if next > now {
+            assert_eq!(manager.flush_tx_inventory_at(next - 1).await.unwrap(), 0);
+        }
+        assert_eq!(manager.flush_tx_inventory_at(next).await.unwrap(), 1);
+        match read_peer_message(&mut remote_rd).await {
+            ProtocolMessage::Inv(inv) => {
+                assert!(inv
+                    .inventory
+                    .iter()
+                    .all(|item| item.inv_type == inventory::MSG_TX));
+                let hashes: Vec<Hash> = inv.inventory.iter().map(|item| item.hash).collect();
+                assert_eq!(hashes, second);
+            }
+            other => panic!("expected inv, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_node_addresses_survive_restart() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(address_db::PEERS_FILE);
+        let manager =
+            NetworkManager::new("127.0.0.1:0".parse().unwrap()).with_peers_path(path.clone());
+        let known: SocketAddr = "8.8.8.8:8333".parse().unwrap();
+        manager
+            .address_database
+            .write()
+            .await
+            .add_address(to_network_address(known, 1), 1);
+        manager.save_peers().await.unwrap();
+
+        let restarted =
+            NetworkManager::new("127.0.0.1:0".parse().unwrap()).with_peers_path(path.clone());
+        let addresses = restarted.node_addresses(10).await;
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].addr, to_network_address(known, 1));
+
+        // A corrupt file does not stop the node from starting
+        std::fs::write(&path, b"not a peers file").unwrap();
+        let restarted = NetworkManager::new("127.0.0.1:0".parse().unwrap()).with_peers_path(path);
+        assert!(restarted.node_addresses(10).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_getrawaddrman_reports_tables_and_sources() {
+        use crate::rpc::network::NetworkRpc;
+        let manager = Arc::new(NetworkManager::new("127.0.0.1:0".parse().unwrap()));
+        let relay: std::net::IpAddr = "1.2.3.4".parse().unwrap();
+        let tried: SocketAddr = "8.8.8.8:8333".parse().unwrap();
+        let new: SocketAddr = "9.9.9.9:8333".parse().unwrap();
         {
-            services_with_filters |= crate::network::protocol::NODE_DANDELION;
+            let mut db = manager.address_database.write().await;
+            db.add_address_from(to_network_address(tried, 1), 1, relay);
+            db.add_address_from(to_network_address(new, 9), 9, relay);
+            db.add_address(
+                to_network_address("11.11.11.11:8333".parse().unwrap(), 1),
+                1,
+            );
+            db.mark_attempt(&new);
+            db.mark_good(&tried);
         }
+        let rpc = NetworkRpc::with_dependencies(Arc::clone(&manager));
+
+        let result = rpc.getrawaddrman(&serde_json::json!([])).await.unwrap();
+        let tried_table = result["tried"].as_object().unwrap();
+        assert_eq!(tried_table.len(), 1);
+        let (key, entry) = tried_table.iter().next().unwrap();
+        let slot = &manager.address_table_slots().await.1[0];
+        assert_eq!(key, &format!("{}/{}", slot.bucket, slot.position));
+        assert_eq!(entry["address"], "8.8.8.8");
+        assert_eq!(entry["source"], "1.2.3.4");
+        assert_eq!(entry["source_network"], "ipv4");
+        assert_eq!(entry["attempts"], 0);
+
+        let new_table = result["new"].as_object().unwrap();
+        assert_eq!(new_table.len(), 2);
+        let relayed = new_table
+            .values()
+            .find(|entry| entry["address"] == "9.9.9.9")
+            .unwrap();
+        assert_eq!(relayed["port"], 8333);
+        assert_eq!(relayed["source"], "1.2.3.4");
+        assert_eq!(relayed["attempts"], 1);
+        assert!(relayed["last_try"].as_u64().unwrap() > 0);
+        let seeded = new_table
+            .values()
+            .find(|entry| entry["address"] == "11.11.11.11")
+            .unwrap();
+        assert_eq!(seeded["source"], "11.11.11.11");
+        assert_eq!(seeded["last_try"], 0);
+
+        // Output is bounded per table
+        let result = rpc.getrawaddrman(&serde_json::json!([1])).await.unwrap();
+        assert_eq!(result["new"].as_object().unwrap().len(), 1);
+        assert_eq!(result["tried"].as_object().unwrap().len(), 1);
+    }
 
-        // Package Relay (BIP331) - always enabled
-        services_with_filters |= crate::network::protocol::NODE_PACKAGE_RELAY;
+    #[tokio::test]
+    async fn test_version_message_services_follow_node_state() {
+        use crate::network::protocol::{NODE_NETWORK, NODE_NETWORK_LIMITED};
+        use crate::storage::chainstate::{ChainInfo, ChainParams};
+        use bllvm_protocol::bip157::NODE_COMPACT_FILTERS;
+        use version_negotiation::PROTOCOL_VERSION;
+
+        let version = |manager: &NetworkManager| {
+            let addr = to_network_address("127.0.0.1:8333".parse().unwrap(), 0);
+            manager.create_version_message(
+                PROTOCOL_VERSION,
+                NODE_NETWORK | NODE_COMPACT_FILTERS,
+                0,
+                addr.clone(),
+                addr,
+                1,
+                0,
+                true,
+            )
+        };
+        let with_config = |config: &crate::config::NodeConfig| {
+            NetworkManager::with_config(
+                "127.0.0.1:0".parse().unwrap(),
+                10,
+                TransportPreference::TCP_ONLY,
+                Some(config),
+            )
+        };
 
-        // FIBRE - always enabled
-        services_with_filters |= crate::network::protocol::NODE_FIBRE;
+        // Filters are not advertised unless served
+        let msg = version(&NetworkManager::new("127.0.0.1:0".parse().unwrap()));
+        assert_ne!(msg.services & NODE_NETWORK, 0);
+        assert_eq!(msg.services & NODE_COMPACT_FILTERS, 0);
+        assert_eq!(msg.user_agent, DEFAULT_USER_AGENT);
 
-        crate::network::protocol::VersionMessage {
+        let mut config = crate::config::NodeConfig {
+            user_agent: Some("/custom:1.0/".to_string()),
+            peer_block_filters: true,
+            ..Default::default()
+        };
+        // peer_block_filters without the filter index serves nothing
+        let manager = with_config(&config);
+        assert!(!manager.serves_block_filters());
+        assert_eq!(version(&manager).services & NODE_COMPACT_FILTERS, 0);
+
+        config.storage = Some(crate::config::StorageConfig {
+            blockfilterindex: true,
+            ..Default::default()
+        });
+        let manager = with_config(&config);
+        assert!(manager.serves_block_filters());
+        let msg = version(&manager);
+        assert_ne!(msg.services & NODE_COMPACT_FILTERS, 0);
+        assert_eq!(msg.user_agent, "/custom:1.0/");
+
+        // A pruned node is NODE_NETWORK_LIMITED only
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let tip = compact_test_block(1);
+        storage
+            .chain()
+            .store_chain_info(&ChainInfo {
+                tip_hash: compact_blocks::calculate_block_hash(&tip.header),
+                tip_header: tip.header,
+                height: 5,
+                total_work: 0,
+                chain_params: ChainParams::default(),
+            })
+            .unwrap();
+        assert!(storage.prune_height().unwrap() > 0);
+        let mut manager = with_config(&config);
+        manager.storage = Some(storage);
+        let msg = version(&manager);
+        assert_eq!(msg.services & NODE_NETWORK, 0);
+        assert_ne!(msg.services & NODE_NETWORK_LIMITED, 0);
+        assert_ne!(msg.services & NODE_COMPACT_FILTERS, 0);
+    }
+
+    fn test_version_message(version: i32) -> VersionMessage {
+        let addr = NetworkAddress {
+            services: 0,
+            ip: [0u8; 16],
+            port: 0,
+        };
+        VersionMessage {
             version,
-            services: services_with_filters,
-            timestamp,
-            addr_recv,
-            addr_from,
-            nonce,
-            user_agent,
-            start_height,
-            relay,
+            services: 1,
+            timestamp: 0,
+            addr_recv: addr.clone(),
+            addr_from: addr,
+            nonce: 1,
+            user_agent: "/test/".to_string(),
+            start_height: 0,
+            relay: true,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    mod concurrency_stress_tests;
-    use super::*;
+    #[tokio::test]
+    async fn test_current_version_peer_accepted() {
+        use version_negotiation::{Feature, PROTOCOL_VERSION};
+
+        let manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (transport_addr, _remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_addr) = transport_addr.clone() else {
+            unreachable!()
+        };
+
+        assert!(
+            manager
+                .handle_version(peer_addr, &test_version_message(PROTOCOL_VERSION))
+                .await
+        );
+        // Feature messages wait for verack
+        assert!(!manager
+            .send_feature_message(peer_addr, Feature::SendHeaders, Vec::new())
+            .await
+            .unwrap());
+
+        manager.handle_verack(peer_addr).await.unwrap();
+        assert!(
+            manager
+                .peer_supports(peer_addr, Feature::CompactBlocks)
+                .await
+        );
+        assert!(manager
+            .send_feature_message(peer_addr, Feature::SendHeaders, Vec::new())
+            .await
+            .unwrap());
+
+        let pm = manager.peer_manager.lock().await;
+        let negotiation = pm.get_peer(&transport_addr).unwrap().version_negotiation();
+        assert_eq!(negotiation.peer_version(), Some(PROTOCOL_VERSION));
+        assert!(negotiation.handshake_complete());
+        drop(pm);
+        assert_eq!(
+            manager.peer_states.read().await[&peer_addr].version,
+            PROTOCOL_VERSION as u32
+        );
+    }
 
     #[tokio::test]
-    async fn test_peer_manager_creation() {
-        let manager = PeerManager::new(10);
-        assert_eq!(manager.peer_count(), 0);
-        assert!(manager.can_accept_peer());
+    async fn test_ancient_version_peer_disconnected() {
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (transport_addr, _remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_addr) = transport_addr.clone() else {
+            unreachable!()
+        };
+
+        assert!(
+            !manager
+                .handle_version(peer_addr, &test_version_message(209))
+                .await
+        );
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 0);
+        assert!(matches!(
+            manager.peer_rx.try_recv(),
+            Ok(NetworkMessage::PeerDisconnected(addr)) if addr == transport_addr
+        ));
     }
 
     #[tokio::test]
-    async fn test_peer_manager_add_peer() {
-        let mut manager = PeerManager::new(2);
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        // Create a mock peer without requiring network connection
-        let (_tx, _rx): (mpsc::UnboundedSender<NetworkMessage>, _) = mpsc::unbounded_channel();
+    async fn test_over_length_frame_disconnects_peer() {
+        use tokio::io::AsyncWriteExt;
 
-        // Skip this test since we can't easily create a mock TcpStream
-        // In a real implementation, we'd use dependency injection
-        // For now, just test the manager logic without the peer
-        assert_eq!(manager.peer_count(), 0);
-        assert!(manager.can_accept_peer());
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (transport_addr, mut remote) = add_connected_peer(&manager).await;
+
+        // Length prefix far beyond the protocol limit
+        remote.write_u32(u32::MAX).await.unwrap();
+
+        let msg = tokio::time::timeout(std::time::Duration::from_secs(5), manager.peer_rx.recv())
+            .await
+            .unwrap();
+        assert!(matches!(
+            msg,
+            Some(NetworkMessage::PeerDisconnected(addr)) if addr == transport_addr
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_malformed_message_disconnects_peer() {
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (transport_addr, _remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_addr) = transport_addr.clone() else {
+            unreachable!()
+        };
+
+        // Valid ping with a corrupted checksum
+        let mut data =
+            ProtocolParser::serialize_message(&ProtocolMessage::Ping(protocol::PingMessage {
+                nonce: 7,
+            }))
+            .unwrap();
+        data[20] ^= 0xff;
+        manager
+            .peer_tx
+            .send(NetworkMessage::RawMessageReceived(data, peer_addr))
+            .unwrap();
+
+        // process_messages runs until the channel closes; give it time to handle the message
+        let _ = tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            manager.process_messages(),
+        )
+        .await;
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 0);
     }
 
     #[tokio::test]
-    async fn test_peer_manager_max_peers() {
-        let mut manager = PeerManager::new(1);
-        let _addr1: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let _addr2: std::net::SocketAddr = "127.0.0.1:8081".parse().unwrap();
+    async fn test_dual_stack_listeners_accept_connections() {
+        use std::net::IpAddr;
 
-        // Test manager capacity without creating real peers
-        assert_eq!(manager.peer_count(), 0);
-        assert!(manager.can_accept_peer());
+        let v4_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let v6_addr: SocketAddr = "[::1]:0".parse().unwrap();
+        // Hosts without IPv6 loopback can't run this test
+        if std::net::TcpListener::bind(v6_addr).is_err() {
+            return;
+        }
 
-        // Test that we can't exceed max peers
-        // (In a real test, we'd create mock peers, but for now we test the logic)
-        assert_eq!(manager.peer_count(), 0);
+        let mut manager = NetworkManager::new(v4_addr).with_listen_addrs(vec![v6_addr]);
+        manager.start(v4_addr).await.unwrap();
+        let local_addrs = manager.local_addrs().to_vec();
+        assert_eq!(local_addrs.len(), 2);
+        assert!(local_addrs[0].is_ipv4());
+        assert!(local_addrs[1].is_ipv6());
+
+        // Both listeners feed the same peer handling
+        let mut _streams = Vec::new();
+        for addr in &local_addrs {
+            _streams.push(tokio::net::TcpStream::connect(addr).await.unwrap());
+        }
+        let mut connected = Vec::new();
+        while connected.len() < 2 {
+            let msg =
+                tokio::time::timeout(std::time::Duration::from_secs(5), manager.peer_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+            if let NetworkMessage::PeerConnected(TransportAddr::Tcp(addr)) = msg {
+                connected.push(addr.ip());
+            }
+        }
+        assert!(connected.iter().any(IpAddr::is_ipv4));
+        assert!(connected.iter().any(IpAddr::is_ipv6));
+
+        // Each connection is counted under its own address
+        let counts = manager.connections_per_ip.lock().await;
+        let v4_ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let v6_ip = dos_protection::connection_key("::1".parse().unwrap());
+        assert_eq!(counts.get(&v4_ip), Some(&1));
+        assert_eq!(counts.get(&v6_ip), Some(&1));
     }
 
     #[tokio::test]
-    async fn test_peer_manager_remove_peer() {
-        let mut manager = PeerManager::new(10);
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+    async fn test_whitelisted_peer_not_message_rate_limited() {
+        let config = crate::config::NodeConfig {
+            whitelist: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let trusted: SocketAddr = "10.0.0.5:8333".parse().unwrap();
+        let other: SocketAddr = "192.0.2.1:8333".parse().unwrap();
+
+        // Past the burst of 100, only the normal peer gets throttled
+        let mut other_allowed = 0;
+        for _ in 0..150 {
+            assert!(manager.check_message_rate(trusted).await);
+            if manager.check_message_rate(other).await {
+                other_allowed += 1;
+            }
+        }
+        assert!(other_allowed < 150);
+    }
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_persistent_peer_reconnects_with_backoff() {
+        let config = crate::config::NodeConfig {
+            network_timing: Some(crate::config::NetworkTimingConfig {
+                peer_connection_delay_seconds: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let peer_addr = listener.local_addr().unwrap();
+
+        manager.add_persistent_peer(peer_addr);
+        manager.maintain_persistent_peers().await.unwrap();
+        let state = manager.persistent_peer_state(peer_addr).await.unwrap();
+        assert!(state.connected);
+
+        // The peer drops and stops accepting connections
+        drop(listener);
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .remove_peer(&TransportAddr::Tcp(peer_addr));
+        let now = current_timestamp();
+        manager.maintain_persistent_peers().await.unwrap();
+        let state = manager.persistent_peer_state(peer_addr).await.unwrap();
+        assert!(!state.connected);
+        assert_eq!(state.attempts, 1);
+        assert!(state.next_attempt >= now + 2);
+        assert!(state.next_attempt <= current_timestamp() + 2);
+
+        // Once due, the failed reconnection backs off further
+        manager
+            .persistent_peers
+            .lock()
+            .await
+            .get_mut(&peer_addr)
+            .unwrap()
+            .next_attempt = 0;
+        let now = current_timestamp();
+        manager.maintain_persistent_peers().await.unwrap();
+        let state = manager.persistent_peer_state(peer_addr).await.unwrap();
+        assert_eq!(state.attempts, 2);
+        assert!(state.next_attempt >= now + 4);
+
+        // Banned peers are not retried
+        manager
+            .persistent_peers
+            .lock()
+            .await
+            .get_mut(&peer_addr)
+            .unwrap()
+            .next_attempt = 0;
+        manager.ban_list.write().await.insert(peer_addr, u64::MAX);
+        manager.maintain_persistent_peers().await.unwrap();
+        let state = manager.persistent_peer_state(peer_addr).await.unwrap();
+        assert_eq!(state.attempts, 2);
+        assert_eq!(state.next_attempt, 0);
+    }
 
-        // Test removing non-existent peer
-        let transport_addr = TransportAddr::Tcp(addr);
-        let removed_peer = manager.remove_peer(&transport_addr);
-        assert!(removed_peer.is_none());
-        assert_eq!(manager.peer_count(), 0);
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ban_list_merge_requires_trusted_signature() {
+        use crate::network::ban_list_merging::calculate_ban_list_hash;
+        use crate::network::ban_list_signing::sign_ban_list_message;
+        use crate::network::protocol::{BanEntry, BanListMessage, NetworkAddress};
+
+        let secp = secp256k1::Secp256k1::new();
+        let publisher = secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let forger = secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let publisher_key = secp256k1::PublicKey::from_secret_key(&secp, &publisher);
+
+        let config = crate::config::NodeConfig {
+            ban_list_sharing: Some(crate::config::BanListSharingConfig {
+                require_signature: true,
+                trusted_publishers: vec![hex::encode(publisher_key.serialize())],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+
+        let ban_list_for = |ip: [u8; 4]| {
+            let mut ip_bytes = [0u8; 16];
+            ip_bytes[12..].copy_from_slice(&ip);
+            let ban_entries = vec![BanEntry {
+                addr: NetworkAddress {
+                    services: 0,
+                    ip: ip_bytes,
+                    port: 8333,
+                },
+                unban_timestamp: u64::MAX,
+                reason: None,
+            }];
+            BanListMessage {
+                is_full: true,
+                ban_list_hash: calculate_ban_list_hash(&ban_entries),
+                ban_entries,
+                timestamp: current_timestamp(),
+                signature: None,
+            }
+        };
+        let sender: SocketAddr = "192.0.2.50:8333".parse().unwrap();
+        let is_banned = |ban_list: &HashMap<SocketAddr, u64>, addr: &str| {
+            ban_list.contains_key(&addr.parse::<SocketAddr>().unwrap())
+        };
+
+        // Signed by the trusted publisher: merged
+        let mut signed = ban_list_for([198, 51, 100, 1]);
+        sign_ban_list_message(&mut signed, &publisher).unwrap();
+        manager.handle_ban_list(sender, signed).await.unwrap();
+
+        // Signed by another key but claiming to be the publisher: rejected
+        let mut forged = ban_list_for([198, 51, 100, 2]);
+        sign_ban_list_message(&mut forged, &forger).unwrap();
+        forged.signature.as_mut().unwrap().public_key = publisher_key.serialize().to_vec();
+        manager.handle_ban_list(sender, forged).await.unwrap();
+
+        // Validly signed by an untrusted key: rejected
+        let mut untrusted = ban_list_for([198, 51, 100, 3]);
+        sign_ban_list_message(&mut untrusted, &forger).unwrap();
+        manager.handle_ban_list(sender, untrusted).await.unwrap();
+
+        // Unsigned: rejected
+        manager
+            .handle_ban_list(sender, ban_list_for([198, 51, 100, 4]))
+            .await
+            .unwrap();
+
+        let ban_list = manager.ban_list.read().await;
+        assert!(is_banned(&ban_list, "198.51.100.1:8333"));
+        assert!(!is_banned(&ban_list, "198.51.100.2:8333"));
+        assert!(!is_banned(&ban_list, "198.51.100.3:8333"));
+        assert!(!is_banned(&ban_list, "198.51.100.4:8333"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_periodic_ban_list_share_skips_short_bans() {
+        use tokio::io::AsyncReadExt;
+
+        let config = crate::config::NodeConfig {
+            ban_list_sharing: Some(crate::config::BanListSharingConfig {
+                share_mode: crate::config::BanShareMode::Periodic,
+                periodic_interval_seconds: 300,
+                min_ban_duration_to_share: 3600,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let listen_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let manager = NetworkManager::with_config(
+            listen_addr,
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let (_, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+
+        let now = current_timestamp();
+        let long_ban: SocketAddr = "198.51.100.1:8333".parse().unwrap();
+        let short_ban: SocketAddr = "198.51.100.2:8333".parse().unwrap();
+        {
+            let mut ban_list = manager.ban_list.write().await;
+            ban_list.insert(long_ban, now + 7200);
+            ban_list.insert(short_ban, now + 600);
+        }
+
+        // Not due until the interval has passed
+        assert!(!manager.maintain_ban_list_sharing().await.unwrap());
+        *manager.last_ban_list_share.lock().await = now - 300;
+        assert!(manager.maintain_ban_list_sharing().await.unwrap());
+        assert!(!manager.maintain_ban_list_sharing().await.unwrap());
+
+        let mut len = [0u8; 4];
+        remote_rd.read_exact(&mut len).await.unwrap();
+        let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+        remote_rd.read_exact(&mut frame).await.unwrap();
+        match ProtocolParser::parse_message(&frame).unwrap() {
+            ProtocolMessage::BanList(msg) => {
+                assert!(msg.is_full);
+                assert!(msg.signature.is_none());
+                assert_eq!(msg.ban_entries.len(), 1);
+                assert_eq!(msg.ban_entries[0].addr.ip[12..], [198, 51, 100, 1]);
+                assert_eq!(msg.ban_entries[0].unban_timestamp, now + 7200);
+            }
+            other => panic!("expected banlist, got {:?}", other),
+        }
+    }
+
+    /// Read one framed protocol message sent to a test peer
+    async fn read_peer_message(rd: &mut tokio::net::tcp::OwnedReadHalf) -> ProtocolMessage {
+        use tokio::io::AsyncReadExt;
+
+        let mut len = [0u8; 4];
+        rd.read_exact(&mut len).await.unwrap();
+        let mut frame = vec![0u8; u32::from_be_bytes(len) as usize];
+        rd.read_exact(&mut frame).await.unwrap();
+        ProtocolParser::parse_message(&frame).unwrap()
     }
 
     #[tokio::test]
-    async fn test_peer_manager_get_peer() {
-        let mut manager = PeerManager::new(10);
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+    async fn test_block_relayed_to_fibre_peers_and_reconstructed() {
+        use crate::network::protocol::NODE_FIBRE;
+
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (fibre_addr, fibre_remote) = add_connected_peer(&manager).await;
+        let (plain_addr, _plain_remote) = add_connected_peer(&manager).await;
+        let (TransportAddr::Tcp(fibre_sock), TransportAddr::Tcp(plain_sock)) =
+            (fibre_addr, plain_addr)
+        else {
+            unreachable!()
+        };
+        {
+            let mut peer_states = manager.peer_states.write().await;
+            let mut state = bllvm_protocol::network::PeerState::new();
+            state.services = NODE_FIBRE;
+            peer_states.insert(fibre_sock, state);
+            peer_states.insert(plain_sock, bllvm_protocol::network::PeerState::new());
+        }
+        let (mut fibre_rd, mut fibre_wr) = fibre_remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while fibre_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+        // Only the FIBRE peer gets the chunks, and they rebuild the block
+        let block_data: Vec<u8> = (0..3000u32).map(|i| (i * 7) as u8).collect();
+        let block_hash = fibre::block_hash(&block_data).unwrap();
+        assert_eq!(
+            manager
+                .relay_block_fibre(block_hash, &block_data)
+                .await
+                .unwrap(),
+            1
+        );
+        let mut receiver = fibre::FibreRelay::new();
+        let rebuilt = loop {
+            match read_peer_message(&mut fibre_rd).await {
+                ProtocolMessage::FibreChunk(chunk) => {
+                    if let Some(data) = receiver.receive_chunk(chunk).unwrap() {
+                        break data;
+                    }
+                }
+                other => panic!("expected fibrechunk, got {:?}", other),
+            }
+        };
+        assert_eq!(rebuilt, block_data);
+
+        // Chunks arriving from a peer are reconstructed, handed to the node
+        // and passed on to the FIBRE peer
+        let other_data: Vec<u8> = (0..3000u32).map(|i| (i * 13) as u8).collect();
+        let other_hash = fibre::block_hash(&other_data).unwrap();
+        let encoded = fibre::FibreRelay::new()
+            .encode_block(other_hash, &other_data)
+            .unwrap();
+        // Lose the first chunk on the way in
+        for chunk in encoded.chunks().iter().skip(1) {
+            manager
+                .handle_fibre_chunk(plain_sock, chunk.clone())
+                .await
+                .unwrap();
+        }
+        assert_eq!(manager.try_recv_block(), Some(other_data));
+        loop {
+            match read_peer_message(&mut fibre_rd).await {
+                // Leftover parity of the first block
+                ProtocolMessage::FibreChunk(chunk) if chunk.block_hash == block_hash => {}
+                ProtocolMessage::FibreChunk(chunk) => {
+                    assert_eq!(chunk.block_hash, other_hash);
+                    break;
+                }
+                other => panic!("expected fibrechunk, got {:?}", other),
+            }
+        }
+    }
 
-        // Test getting non-existent peer
-        let transport_addr = TransportAddr::Tcp(addr);
-        let retrieved_peer = manager.get_peer(&transport_addr);
-        assert!(retrieved_peer.is_none());
+    fn compact_test_block(tx_count: u8) -> bllvm_protocol::Block {
+        let transactions: Vec<bllvm_protocol::Transaction> = (0..tx_count)
+            .map(|seed| bllvm_protocol::Transaction {
+                version: 1,
+                inputs: bllvm_protocol::tx_inputs![bllvm_protocol::TransactionInput {
+                    prevout: bllvm_protocol::OutPoint {
+                        hash: [seed; 32],
+                        index: 0,
+                    },
+                    script_sig: vec![seed],
+                    sequence: 0xffffffff,
+                }],
+                outputs: bllvm_protocol::tx_outputs![bllvm_protocol::TransactionOutput {
+                    value: 1000,
+                    script_pubkey: vec![0x51],
+                }],
+                lock_time: 0,
+            })
+            .collect();
+        bllvm_protocol::Block {
+            header: bllvm_protocol::BlockHeader {
+                version: 1,
+                prev_block_hash: [0; 32],
+                merkle_root: bllvm_protocol::mining::calculate_merkle_root(&transactions).unwrap(),
+                timestamp: 0,
+                bits: 0,
+                nonce: 0,
+            },
+            transactions: transactions.into_boxed_slice(),
+        }
     }
 
     #[tokio::test]
-    async fn test_peer_manager_peer_addresses() {
-        let mut manager = PeerManager::new(10);
-        let _addr1: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let _addr2: std::net::SocketAddr = "127.0.0.1:8081".parse().unwrap();
+    async fn test_compact_block_reconstructed_from_orphan_pool() {
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        let (addr, remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_sock) = addr else {
+            unreachable!()
+        };
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+        // Transactions 1 and 3 are known orphans; 2 and 4 must be requested
+        let block = compact_test_block(5);
+        let block_hash = compact_blocks::calculate_block_hash(&block.header);
+        {
+            let mut orphans = manager.orphan_pool.lock().await;
+            orphans.add(
+                block.transactions[1].clone(),
+                peer_sock,
+                current_timestamp(),
+            );
+            orphans.add(
+                block.transactions[3].clone(),
+                peer_sock,
+                current_timestamp(),
+            );
+        }
+        let compact = compact_blocks::create_compact_block(&block, 11, &HashSet::from([0]));
+        manager
+            .handle_compact_block(peer_sock, compact.clone())
+            .await
+            .unwrap();
+        match read_peer_message(&mut remote_rd).await {
+            ProtocolMessage::GetBlockTxn(msg) => {
+                assert_eq!(msg.block_hash, block_hash);
+                assert_eq!(msg.indices, vec![2, 4]);
+            }
+            other => panic!("expected getblocktxn, got {:?}", other),
+        }
 
-        // Test getting addresses when no peers exist
-        let addresses = manager.peer_addresses();
-        assert_eq!(addresses.len(), 0);
+        manager
+            .handle_block_txn(
+                peer_sock,
+                crate::network::protocol::BlockTxnMessage {
+                    block_hash,
+                    transactions: vec![
+                        block.transactions[2].clone(),
+                        block.transactions[4].clone(),
+                    ],
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.try_recv_block(),
+            Some(crate::node::block_processor::serialize_block(&block, &[]))
+        );
+        // Orphans included in the block are dropped
+        assert!(manager.orphan_pool.lock().await.is_empty());
+
+        // Colliding short IDs fall back to requesting the full block
+        let mut colliding = compact;
+        colliding.short_ids[1] = colliding.short_ids[0];
+        manager
+            .handle_compact_block(peer_sock, colliding)
+            .await
+            .unwrap();
+        match read_peer_message(&mut remote_rd).await {
+            ProtocolMessage::GetData(msg) => {
+                assert_eq!(msg.inventory.len(), 1);
+                assert_eq!(msg.inventory[0].inv_type, inventory::MSG_BLOCK);
+                assert_eq!(msg.inventory[0].hash, block_hash);
+            }
+            other => panic!("expected getdata, got {:?}", other),
+        }
     }
 
     #[tokio::test]
-    async fn test_connection_manager_creation() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let manager = ConnectionManager::new(addr);
+    async fn test_addr_relay_honors_interval_and_message_cap() {
+        let config = crate::config::NodeConfig {
+            network_timing: Some(crate::config::NetworkTimingConfig {
+                addr_relay_min_interval_seconds: 3600,
+                max_addresses_per_addr_message: 3,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            8,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let (peer_addr, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, _remote_wr) = remote.into_split();
 
-        assert_eq!(manager.listen_addr, addr);
-    }
+        let known: Vec<NetworkAddress> = (1..=5u8)
+            .map(|i| to_network_address(SocketAddr::from(([8, 8, 8, i], 8333)), 1))
+            .collect();
+        {
+            let mut db = manager.address_database.write().await;
+            for addr in &known {
+                db.add_address(addr.clone(), 1);
+            }
+        }
+        // The peer told us about the last address itself
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer_mut(&peer_addr)
+            .unwrap()
+            .add_known_address(&known[4]);
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_creation() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let manager = NetworkManager::new(addr);
+        async fn read_addr(remote_rd: &mut tokio::net::tcp::OwnedReadHalf) -> Vec<NetworkAddress> {
+            match tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                read_peer_message(remote_rd),
+            )
+            .await
+            .unwrap()
+            {
+                ProtocolMessage::Addr(addr) => addr.addresses,
+                other => panic!("expected addr, got {:?}", other),
+            }
+        }
 
-        assert_eq!(manager.peer_count(), 0);
-        assert_eq!(manager.peer_addresses().len(), 0);
+        assert_eq!(manager.maintain_addr_relay().await.unwrap(), 1);
+        let first = read_addr(&mut remote_rd).await;
+        assert_eq!(first.len(), 3);
+        assert!(!first.contains(&known[4]));
+
+        // Too soon for another round
+        assert_eq!(manager.maintain_addr_relay().await.unwrap(), 0);
+
+        // Next round only carries the address the peer hasn't seen
+        *manager.last_addr_sent.lock().await = 0;
+        assert_eq!(manager.maintain_addr_relay().await.unwrap(), 1);
+        let second = read_addr(&mut remote_rd).await;
+        assert_eq!(second.len(), 1);
+        assert!(!first.contains(&second[0]));
+        assert_ne!(second[0], known[4]);
     }
 
     #[tokio::test]
-    async fn test_network_manager_with_config() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
+    async fn test_best_scored_local_address_is_advertised() {
+        let config = crate::config::NodeConfig {
+            external_ips: vec!["1.2.3.4".parse().unwrap()],
+            ..Default::default()
+        };
         let manager = NetworkManager::with_config(
-            addr,
-            5,
-            crate::network::transport::TransportPreference::TCP_ONLY,
-            None,
+            "0.0.0.0:8333".parse().unwrap(),
+            8,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
         );
+        manager.start_local_address_discovery().await;
+        let (_addr, remote) = add_connected_peer(&manager).await;
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
 
-        // peer_count() might not exist, check peer_manager instead
-        let peer_manager = manager.peer_manager().await;
-        assert_eq!(peer_manager.peer_count(), 0);
+        // Peers report seeing us at 5.6.7.8 (from ephemeral ports) and 9.9.9.9
+        let report = |peer: u8, ip: [u8; 4]| {
+            let mut msg = test_version_message(70015);
+            msg.addr_recv.ip[10..12].copy_from_slice(&[0xff, 0xff]);
+            msg.addr_recv.ip[12..].copy_from_slice(&ip);
+            msg.addr_recv.port = 50000;
+            (SocketAddr::from(([10, 0, 0, peer], 8333)), msg)
+        };
+        let (peer_addr, msg) = report(100, [9, 9, 9, 9]);
+        assert!(manager.handle_version(peer_addr, &msg).await);
+        for peer in 1..=local_address::LOCAL_SCORE_MANUAL as u8 {
+            let (peer_addr, msg) = report(peer, [5, 6, 7, 8]);
+            assert!(manager.handle_version(peer_addr, &msg).await);
+        }
+        // Tied with the configured address, which sorts first
+        let best = manager.local_addresses().await[0];
+        assert_eq!(best.addr, "1.2.3.4:8333".parse().unwrap());
+
+        let (peer_addr, msg) = report(50, [5, 6, 7, 8]);
+        assert!(manager.handle_version(peer_addr, &msg).await);
+        let local = manager.local_addresses().await;
+        assert_eq!(local.len(), 3);
+        assert_eq!(local[0].addr, "5.6.7.8:8333".parse().unwrap());
+        assert_eq!(local[0].score, local_address::LOCAL_SCORE_MANUAL + 1);
+        assert_eq!(local[2].score, 1);
+
+        assert!(manager.maintain_self_advertisement().await.unwrap());
+        match tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            read_peer_message(&mut remote_rd),
+        )
+        .await
+        .unwrap()
+        {
+            ProtocolMessage::Addr(addr) => {
+                assert_eq!(addr.addresses.len(), 1);
+                let advertised = &addr.addresses[0];
+                assert_eq!(
+                    local_address::network_address_ip(advertised),
+                    "5.6.7.8".parse::<std::net::IpAddr>().unwrap()
+                );
+                assert_eq!(advertised.port, 8333);
+            }
+            other => panic!("expected addr, got {:?}", other),
+        }
+        // Advertised at most once a day
+        assert!(!manager.maintain_self_advertisement().await.unwrap());
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_peer_count() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let mut manager = NetworkManager::new(addr);
+    async fn test_misbehavior_discourages_without_banning() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(discouragement::DISCOURAGED_FILE);
+        let manager =
+            NetworkManager::new("127.0.0.1:0".parse().unwrap()).with_discouraged_path(path.clone());
+        let (addr, _remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(sock) = addr else {
+            unreachable!()
+        };
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+        manager
+            .report_misbehavior(
+                sock,
+                dos_protection::MISBEHAVIOR_DISCONNECT_THRESHOLD - 1,
+                "test",
+            )
+            .await;
+        assert!(!manager.is_discouraged(sock.ip()).await);
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 1);
+
+        // Crossing the threshold discourages and disconnects, but neither
+        // bans nor queues the address for ban-list gossip
+        manager.report_misbehavior(sock, 1, "test").await;
+        assert!(manager.is_discouraged(sock.ip()).await);
+        assert!(!manager.is_banned(sock));
+        assert!(manager.pending_ban_shares.lock().await.is_empty());
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 0);
+
+        // Discouragement survives a restart
+        manager.save_discouraged().await.unwrap();
+        let restarted =
+            NetworkManager::new("127.0.0.1:0".parse().unwrap()).with_discouraged_path(path);
+        assert!(restarted.is_discouraged(sock.ip()).await);
+        assert!(!restarted.is_discouraged("127.0.0.2".parse().unwrap()).await);
+
+        // clearbanned forgets it
+        restarted.clear_bans();
+        assert!(!restarted.is_discouraged(sock.ip()).await);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_peer_addresses() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let mut manager = NetworkManager::new(addr);
-
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+    async fn test_invalid_pow_block_bans_peer_but_orphan_does_not() {
+        use crate::network::protocol::BlockMessage;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        manager.storage = Some(Arc::new(Storage::new(temp_dir.path()).unwrap()));
+        let (bad_addr, _bad_remote) = add_connected_peer(&manager).await;
+        let (orphan_addr, _orphan_remote) = add_connected_peer(&manager).await;
+        let (TransportAddr::Tcp(bad_sock), TransportAddr::Tcp(orphan_sock)) =
+            (bad_addr, orphan_addr)
+        else {
+            unreachable!()
+        };
+        let block_message = |block: bllvm_protocol::Block| {
+            ProtocolParser::serialize_message(&ProtocolMessage::Block(BlockMessage {
+                block,
+                witnesses: Vec::new(),
+            }))
+            .unwrap()
+        };
 
-        // Test getting addresses when no peers exist
-        let addresses = manager.peer_addresses();
-        assert_eq!(addresses.len(), 0);
+        // Well-formed block on top of a parent we don't have
+        let mut orphan = compact_test_block(2);
+        orphan.header.prev_block_hash = [0xab; 32];
+        orphan.header.bits = 0x207fffff;
+        while !bllvm_protocol::pow::check_proof_of_work(&orphan.header).unwrap() {
+            orphan.header.nonce += 1;
+        }
+        manager
+            .handle_incoming_wire_tcp(orphan_sock, block_message(orphan))
+            .await
+            .unwrap();
+        assert!(!manager.is_banned(orphan_sock));
+        assert_eq!(
+            manager
+                .dos_protection
+                .misbehavior_score(orphan_sock.ip())
+                .await,
+            0
+        );
+        assert_eq!(manager.peer_manager.lock().await.peer_count(), 2);
+
+        // Header hash far above the mainnet target
+        let mut high_hash = compact_test_block(2);
+        high_hash.header.bits = 0x1d00ffff;
+        assert!(!bllvm_protocol::pow::check_proof_of_work(&high_hash.header).unwrap_or(false));
+        manager
+            .handle_incoming_wire_tcp(bad_sock, block_message(high_hash))
+            .await
+            .unwrap();
+        assert!(manager.is_banned(bad_sock));
+        assert!(!manager.is_banned(orphan_sock));
+        {
+            let pm = manager.peer_manager.lock().await;
+            assert_eq!(pm.peer_count(), 1);
+            assert!(pm.find_transport_addr_by_socket(orphan_sock).is_some());
+        }
+        let shares = manager.pending_ban_shares.lock().await;
+        assert_eq!(shares.len(), 1);
+        assert_eq!(shares[0].0, bad_sock);
+        assert_eq!(shares[0].2, "high-hash");
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_broadcast() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let mut manager = NetworkManager::new(addr);
+    async fn test_mempool_request_respects_feefilter() {
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::{OutPoint, Transaction, TransactionInput, TransactionOutput, UTXO};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let mut mempool = MempoolManager::new();
+        let mut txids = Vec::new();
+        for (seed, fee) in [(1u8, 100), (2u8, 10_000)] {
+            let prevout = OutPoint {
+                hash: [seed; 32],
+                index: 0,
+            };
+            let coin = UTXO {
+                value: 100_000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            };
+            storage.utxos().add_utxo(&prevout, &coin).unwrap();
+            let tx = Transaction {
+                version: 1,
+                inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                    prevout,
+                    script_sig: vec![],
+                    sequence: 0xffffffff,
+                }],
+                outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                    value: 100_000 - fee,
+                    script_pubkey: vec![0x51],
+                }],
+                lock_time: 0,
+            };
+            txids.push(calculate_tx_id(&tx));
+            assert!(mempool.add_transaction(tx).await.unwrap());
+        }
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+        let config = crate::config::NodeConfig {
+            relay: Some(crate::config::RelayConfig {
+                accept_mempool_requests: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            8,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        manager.storage = Some(storage);
+        manager.mempool_manager = Some(Arc::new(mempool));
+        let (addr, remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_sock) = addr.clone() else {
+            unreachable!()
+        };
+        let (mut remote_rd, mut remote_wr) = remote.into_split();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        });
+        // Only transactions paying at least 10 sat/vB
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer_mut(&addr)
+            .unwrap()
+            .set_fee_filter(10_000);
 
-        // Test broadcast with no peers (should succeed)
-        let message = b"test message".to_vec();
-        let result = manager.broadcast(message).await;
-        assert!(result.is_ok());
+        let request = ProtocolParser::serialize_message(&ProtocolMessage::MemPool).unwrap();
+        manager
+            .handle_incoming_wire_tcp(peer_sock, request)
+            .await
+            .unwrap();
+        match tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            read_peer_message(&mut remote_rd),
+        )
+        .await
+        .unwrap()
+        {
+            ProtocolMessage::Inv(inv) => {
+                assert_eq!(inv.inventory.len(), 1);
+                assert_eq!(inv.inventory[0].inv_type, inventory::MSG_TX);
+                assert_eq!(inv.inventory[0].hash, txids[1]);
+            }
+            other => panic!("expected inv, got {:?}", other),
+        }
+
+        // Announced transactions are known to the peer; repeat requests are throttled
+        let mut pm = manager.peer_manager.lock().await;
+        let peer = pm.get_peer_mut(&addr).unwrap();
+        assert!(peer.has_known_inventory(&txids[1]));
+        assert!(!peer.has_known_inventory(&txids[0]));
+        assert!(!peer.allow_mempool_request(current_timestamp(), MEMPOOL_REQUEST_INTERVAL_SECONDS));
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_send_to_peer() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let mut manager = NetworkManager::new(addr);
+    async fn test_mempool_request_needs_mempool_permission() {
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::{OutPoint, Transaction, TransactionInput, TransactionOutput, UTXO};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let mut mempool = MempoolManager::new();
+        let prevout = OutPoint {
+            hash: [7; 32],
+            index: 0,
+        };
+        let coin = UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        };
+        storage.utxos().add_utxo(&prevout, &coin).unwrap();
+        let tx = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 90_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let txid = calculate_tx_id(&tx);
+        assert!(mempool.add_transaction(tx).await.unwrap());
+
+        // `relay.accept_mempool_requests` is off by default
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        manager.storage = Some(storage);
+        manager.mempool_manager = Some(Arc::new(mempool));
+        let (trusted_addr, trusted_remote) = add_connected_peer(&manager).await;
+        let (plain_addr, plain_remote) = add_connected_peer(&manager).await;
+        let (TransportAddr::Tcp(trusted_sock), TransportAddr::Tcp(plain_sock)) =
+            (trusted_addr.clone(), plain_addr)
+        else {
+            unreachable!()
+        };
+        manager
+            .peer_manager
+            .lock()
+            .await
+            .get_peer_mut(&trusted_addr)
+            .unwrap()
+            .set_permissions(NetPermissions::MEMPOOL);
+        let mut readers = Vec::new();
+        for remote in [trusted_remote, plain_remote] {
+            let (remote_rd, mut remote_wr) = remote.into_split();
+            tokio::spawn(async move {
+                use tokio::io::AsyncWriteExt;
+                while remote_wr.write_all(&[0, 0, 0, 1, 0]).await.is_ok() {
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                }
+            });
+            readers.push(remote_rd);
+        }
+        let request = ProtocolParser::serialize_message(&ProtocolMessage::MemPool).unwrap();
 
-        // Test manager logic without creating real peers
-        assert_eq!(manager.peer_count(), 0);
+        // The permissioned peer is answered every time, without the request
+        // interval applying
+        for _ in 0..2 {
+            manager
+                .handle_incoming_wire_tcp(trusted_sock, request.clone())
+                .await
+                .unwrap();
+            match tokio::time::timeout(
+                std::time::Duration::from_secs(5),
+                read_peer_message(&mut readers[0]),
+            )
+            .await
+            .unwrap()
+            {
+                ProtocolMessage::Inv(inv) => assert_eq!(inv.inventory[0].hash, txid),
+                other => panic!("expected inv, got {:?}", other),
+            }
+        }
 
-        // Test send to non-existent peer (should succeed but not actually send)
-        let peer_addr = "127.0.0.1:8081".parse().unwrap();
-        let message = b"test message".to_vec();
-        let result = manager.send_to_peer(peer_addr, message).await;
-        assert!(result.is_ok()); // Should succeed even for non-existent peer
+        // Any other peer is ignored
+        manager
+            .handle_incoming_wire_tcp(plain_sock, request)
+            .await
+            .unwrap();
+        assert!(tokio::time::timeout(
+            std::time::Duration::from_millis(300),
+            read_peer_message(&mut readers[1]),
+        )
+        .await
+        .is_err());
     }
 
     #[tokio::test]
-    async fn test_network_manager_send_to_nonexistent_peer() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let manager = NetworkManager::new(addr);
+    async fn test_upload_target_exempts_download_and_block_relay_peers() {
+        use crate::network::protocol::{GetDataMessage, InventoryItem};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let header = |prev_block_hash, timestamp| bllvm_protocol::BlockHeader {
+            version: 1,
+            prev_block_hash,
+            merkle_root: [0; 32],
+            timestamp,
+            bits: 0,
+            nonce: 0,
+        };
+        let old_header = header([0; 32], 1_000_000);
+        let old_hash = storage.blocks().store_header(&old_header).unwrap();
+        let tip_header = header(old_hash, 1_000_000 + 8 * 24 * 60 * 60);
+        let tip_hash = storage.blocks().store_header(&tip_header).unwrap();
+        storage.chain().initialize(&old_header).unwrap();
+        storage
+            .chain()
+            .update_tip(&tip_hash, &tip_header, 1)
+            .unwrap();
+
+        let config = crate::config::NodeConfig {
+            bandwidth: Some(crate::config::BandwidthConfig {
+                max_upload_target_mib: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            8,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        manager.storage = Some(storage);
+        let (download_addr, _download_remote) = add_connected_peer(&manager).await;
+        let (relay_addr, _relay_remote) = add_connected_peer(&manager).await;
+        let (plain_addr, _plain_remote) = add_connected_peer(&manager).await;
+        {
+            let mut pm = manager.peer_manager.lock().await;
+            pm.get_peer_mut(&download_addr)
+                .unwrap()
+                .set_permissions(NetPermissions::DOWNLOAD);
+            pm.get_peer_mut(&relay_addr)
+                .unwrap()
+                .set_block_relay_only(true);
+        }
+        let (
+            TransportAddr::Tcp(download_sock),
+            TransportAddr::Tcp(relay_sock),
+            TransportAddr::Tcp(plain_sock),
+        ) = (
+            download_addr.clone(),
+            relay_addr.clone(),
+            plain_addr.clone(),
+        )
+        else {
+            unreachable!()
+        };
+        let request = |inv_type, hash| GetDataMessage {
+            inventory: vec![InventoryItem { inv_type, hash }],
+        };
+        let witness_block = inventory::MSG_BLOCK | inventory::MSG_WITNESS_FLAG;
 
-        // Test send to non-existent peer
-        let peer_addr = "127.0.0.1:8081".parse().unwrap();
-        let message = b"test message".to_vec();
-        let result = manager.send_to_peer(peer_addr, message).await;
-        assert!(result.is_ok()); // Should not error, just do nothing
-    }
+        // Below the target, anyone gets historical blocks
+        assert!(manager
+            .check_upload_target(plain_sock, request(witness_block, old_hash))
+            .await
+            .is_some());
 
-    #[tokio::test]
-    async fn test_network_message_peer_connected() {
-        let socket_addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let transport_addr = TransportAddr::Tcp(socket_addr);
-        let message = NetworkMessage::PeerConnected(transport_addr.clone());
-        match message {
-            NetworkMessage::PeerConnected(addr) => {
-                assert_eq!(addr, transport_addr);
-            }
-            _ => panic!("Expected PeerConnected message"),
+        manager.bandwidth_limits.record_upload(1024 * 1024);
+        assert!(manager.bandwidth_limits.upload_target_reached());
+        for exempt in [download_sock, relay_sock] {
+            assert!(manager
+                .check_upload_target(exempt, request(witness_block, old_hash))
+                .await
+                .is_some());
         }
-    }
+        // Tip blocks and transactions are still served to everyone
+        assert!(manager
+            .check_upload_target(plain_sock, request(inventory::MSG_CMPCT_BLOCK, tip_hash))
+            .await
+            .is_some());
+        assert!(manager
+            .check_upload_target(plain_sock, request(inventory::MSG_TX, old_hash))
+            .await
+            .is_some());
 
-    #[tokio::test]
-    async fn test_network_message_peer_disconnected() {
-        let socket_addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let transport_addr = TransportAddr::Tcp(socket_addr);
-        let message = NetworkMessage::PeerDisconnected(transport_addr.clone());
-        match message {
-            NetworkMessage::PeerDisconnected(addr) => {
-                assert_eq!(addr, transport_addr);
-            }
-            _ => panic!("Expected PeerDisconnected message"),
-        }
+        // A normal peer asking for a historical block is refused and dropped
+        assert!(manager
+            .check_upload_target(plain_sock, request(witness_block, old_hash))
+            .await
+            .is_none());
+        let pm = manager.peer_manager.lock().await;
+        assert!(pm.get_peer(&plain_addr).is_none());
+        assert!(pm.get_peer(&download_addr).is_some());
     }
 
-    #[tokio::test]
-    async fn test_network_message_block_received() {
-        let data = b"block data".to_vec();
-        let message = NetworkMessage::BlockReceived(data.clone());
-        match message {
-            NetworkMessage::BlockReceived(msg_data) => {
-                assert_eq!(msg_data, data);
-            }
-            _ => panic!("Expected BlockReceived message"),
-        }
-    }
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fetch_block_from_peer_stores_served_block() {
+        use crate::network::protocol::BlockMessage;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let mut manager = NetworkManager::new("127.0.0.1:0".parse().unwrap());
+        manager.storage = Some(Arc::clone(&storage));
+        let manager = Arc::new(manager);
+        let (transport_addr, remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(peer_sock) = transport_addr else {
+            unreachable!()
+        };
 
-    #[tokio::test]
-    async fn test_network_message_transaction_received() {
-        let data = b"tx data".to_vec();
-        let message = NetworkMessage::TransactionReceived(data.clone());
-        match message {
-            NetworkMessage::TransactionReceived(msg_data) => {
-                assert_eq!(msg_data, data);
-            }
-            _ => panic!("Expected TransactionReceived message"),
+        // We have the header but not the body
+        let mut block = compact_test_block(2);
+        block.header.bits = 0x207fffff;
+        while !bllvm_protocol::pow::check_proof_of_work(&block.header).unwrap() {
+            block.header.nonce += 1;
         }
-    }
+        let block_hash = compact_blocks::calculate_block_hash(&block.header);
+        storage.blocks().store_header(&block.header).unwrap();
+        let timeout = std::time::Duration::from_secs(10);
+        assert!(manager
+            .fetch_block_from_peer(peer_sock, [0xcd; 32], timeout)
+            .await
+            .is_err());
 
-    #[tokio::test]
-    async fn test_network_message_inventory_received() {
-        let data = b"inv data".to_vec();
-        let message = NetworkMessage::InventoryReceived(data.clone());
-        match message {
-            NetworkMessage::InventoryReceived(msg_data) => {
-                assert_eq!(msg_data, data);
-            }
-            _ => panic!("Expected InventoryReceived message"),
-        }
-    }
+        // Mock peer: answer the getdata with the block
+        let (mut remote_rd, _remote_wr) = remote.into_split();
+        let mock_peer = {
+            let manager = Arc::clone(&manager);
+            let block = block.clone();
+            tokio::spawn(async move {
+                match read_peer_message(&mut remote_rd).await {
+                    ProtocolMessage::GetData(msg) => {
+                        assert_eq!(msg.inventory.len(), 1);
+                        assert_eq!(msg.inventory[0].inv_type, inventory::MSG_BLOCK);
+                        assert_eq!(msg.inventory[0].hash, block_hash);
+                    }
+                    other => panic!("expected getdata, got {:?}", other),
+                }
+                let data =
+                    ProtocolParser::serialize_message(&ProtocolMessage::Block(BlockMessage {
+                        block,
+                        witnesses: Vec::new(),
+                    }))
+                    .unwrap();
+                manager
+                    .handle_incoming_wire_tcp(peer_sock, data)
+                    .await
+                    .unwrap();
+            })
+        };
 
-    // NOTE: test_handle_incoming_wire_tcp_enqueues_pkgtxn was removed because it hangs
-    // Note: All Mutex usage is now tokio::sync::Mutex with async-safe .await calls.
-    // The handle_incoming_wire_tcp function uses async-safe track_bytes_received
-    // and is_banned) which blocks the async runtime when there's contention.
-    // Full message routing is tested in integration tests.
+        manager
+            .fetch_block_from_peer(peer_sock, block_hash, timeout)
+            .await
+            .unwrap();
+        mock_peer.await.unwrap();
+        let stored = storage.blocks().get_block(&block_hash).unwrap().unwrap();
+        assert_eq!(stored.header.merkle_root, block.header.merkle_root);
+        assert_eq!(stored.transactions.len(), 2);
+        assert!(manager.requested_blocks.lock().await.is_empty());
+
+        let err = manager
+            .fetch_block_from_peer(peer_sock, block_hash, timeout)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Block already downloaded");
+    }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_network_manager_peer_manager_access() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let manager = NetworkManager::new(addr);
+    async fn test_stalled_block_download_moves_to_another_peer() {
+        use crate::network::protocol::BlockMessage;
+
+        let config = crate::config::NodeConfig {
+            network_timing: Some(crate::config::NetworkTimingConfig {
+                block_stall_timeout_seconds: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let manager = NetworkManager::with_config(
+            "127.0.0.1:0".parse().unwrap(),
+            10,
+            TransportPreference::TCP_ONLY,
+            Some(&config),
+        );
+        let (staller, _staller_remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(staller_sock) = staller else {
+            unreachable!()
+        };
 
-        // Test immutable access - drop the guard immediately to avoid holding lock
-        {
-            let peer_manager = manager.peer_manager().await;
-            assert_eq!(peer_manager.peer_count(), 0);
-        } // Guard dropped here
+        let block = compact_test_block(2);
+        let block_hash = compact_blocks::calculate_block_hash(&block.header);
+        let mut response = manager.request_block_download(block_hash).await.unwrap();
+        assert_eq!(
+            manager.requested_blocks.lock().await[&block_hash].peer_addr,
+            staller_sock
+        );
 
-        // Test peer count access (this also locks the mutex, but guard is dropped immediately)
-        assert_eq!(manager.peer_count(), 0);
-    }
+        // A second peer connects; the staller never answers the getdata
+        let (alternate, alternate_remote) = add_connected_peer(&manager).await;
+        let TransportAddr::Tcp(alternate_sock) = alternate else {
+            unreachable!()
+        };
+        assert!(manager
+            .check_block_download_stalls()
+            .await
+            .unwrap()
+            .is_empty());
 
-    #[tokio::test]
-    async fn test_network_manager_transport_preference() {
-        let addr: std::net::SocketAddr = "127.0.0.1:8080".parse().unwrap();
-        let manager = NetworkManager::new(addr);
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        assert_eq!(
+            manager.check_block_download_stalls().await.unwrap(),
+            vec![block_hash]
+        );
+        assert_eq!(
+            manager.requested_blocks.lock().await[&block_hash].peer_addr,
+            alternate_sock
+        );
+        assert_eq!(
+            manager
+                .dos_protection
+                .misbehavior_score(staller_sock.ip())
+                .await,
+            dos_protection::MISBEHAVIOR_BLOCK_STALL
+        );
+
+        // The alternate is asked for the block and delivers it
+        let (mut alternate_rd, _alternate_wr) = alternate_remote.into_split();
+        match read_peer_message(&mut alternate_rd).await {
+            ProtocolMessage::GetData(msg) => {
+                assert_eq!(msg.inventory[0].inv_type, inventory::MSG_BLOCK);
+                assert_eq!(msg.inventory[0].hash, block_hash);
+            }
+            other => panic!("expected getdata, got {:?}", other),
+        }
+        let msg = BlockMessage {
+            block,
+            witnesses: Vec::new(),
+        };
+
+        // A late answer from the staller no longer counts
+        assert!(!manager.complete_block_request(staller_sock, &msg).await);
+        assert!(response.try_recv().is_err());
 
+        // Downloads go on to block processing rather than being consumed
+        assert!(!manager.complete_block_request(alternate_sock, &msg).await);
+        let data = response.await.unwrap();
         assert_eq!(
-            manager.transport_preference(),
-            TransportPreference::TCP_ONLY
+            data,
+            crate::node::block_processor::serialize_block(&msg.block, &msg.witnesses)
         );
+        assert!(manager.requested_blocks.lock().await.is_empty());
     }
 }
@@ -0,0 +1,172 @@
+//! Network groups
+//!
+//! Addresses in the same network group are assumed to be under the control of
+//! the same operator, so an attacker holding many addresses in one range counts
+//! as one source. Groups are computed the way Bitcoin Core does (without an
+//! ASN map): IPv4 addresses are grouped by /16, including IPv4 addresses
+//! embedded in IPv6 (mapped, SIIT, 6to4 and Teredo), IPv6 addresses by /32
+//! (/36 for Hurricane Electric, which hands out /48s to anyone), OnionCat
+//! addresses by their first four bits, and all local and unroutable addresses
+//! share one group.
+//!
+//! Used for inbound eviction ([`super::eviction`]), address manager buckets
+//! ([`super::address_db`]) and the per-group inbound peer limit.
+
+use crate::network::local_address::is_routable;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Network class of local and unroutable addresses
+pub const NET_UNROUTABLE: u8 = 0;
+/// Network class of IPv4 addresses (and IPv4 embedded in IPv6)
+pub const NET_IPV4: u8 = 1;
+/// Network class of IPv6 addresses
+pub const NET_IPV6: u8 = 2;
+/// Network class of onion addresses (OnionCat encoded)
+pub const NET_ONION: u8 = 3;
+
+/// OnionCat prefix (`fd87:d87e:eb43::/48`)
+const ONIONCAT_PREFIX: [u8; 6] = [0xfd, 0x87, 0xd8, 0x7e, 0xeb, 0x43];
+
+/// Network group of an IP address
+///
+/// The first byte is the network class, followed by the address prefix the
+/// group is keyed on. A prefix ending mid-byte has its remaining bits set.
+pub fn network_group(ip: IpAddr) -> Vec<u8> {
+    if let IpAddr::V6(v6) = ip {
+        if v6.octets().starts_with(&ONIONCAT_PREFIX) {
+            return prefix_group(NET_ONION, &v6.octets()[ONIONCAT_PREFIX.len()..], 4);
+        }
+    }
+    if !is_routable(&ip) {
+        return vec![NET_UNROUTABLE];
+    }
+    match ip {
+        IpAddr::V4(v4) => ipv4_group(v4),
+        IpAddr::V6(v6) => match linked_ipv4(&v6) {
+            Some(v4) => ipv4_group(v4),
+            None => {
+                let octets = v6.octets();
+                // Hurricane Electric (2001:470::/32)
+                let bits = if octets[..4] == [0x20, 0x01, 0x04, 0x70] {
+                    36
+                } else {
+                    32
+                };
+                prefix_group(NET_IPV6, &octets, bits)
+            }
+        },
+    }
+}
+
+/// IPv4 address embedded in an IPv6 address (mapped, SIIT, 6to4 or Teredo)
+pub fn linked_ipv4(v6: &Ipv6Addr) -> Option<Ipv4Addr> {
+    let octets = v6.octets();
+    let ipv4_at = |start: usize| {
+        Ipv4Addr::new(
+            octets[start],
+            octets[start + 1],
+            octets[start + 2],
+            octets[start + 3],
+        )
+    };
+    if let Some(v4) = v6.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    // SIIT (::ffff:0:0:0/96)
+    if octets[..12] == [0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0] {
+        return Some(ipv4_at(12));
+    }
+    // 6to4 (2002::/16): the IPv4 address follows the prefix
+    if octets[..2] == [0x20, 0x02] {
+        return Some(ipv4_at(2));
+    }
+    // Teredo (2001::/32): the client's IPv4 address is stored inverted
+    if octets[..4] == [0x20, 0x01, 0x00, 0x00] {
+        let inverted = u32::from(ipv4_at(12));
+        return Some(Ipv4Addr::from(!inverted));
+    }
+    None
+}
+
+fn ipv4_group(v4: Ipv4Addr) -> Vec<u8> {
+    let octets = v4.octets();
+    vec![NET_IPV4, octets[0], octets[1]]
+}
+
+/// `class` followed by the first `bits` bits of `bytes`
+fn prefix_group(class: u8, bytes: &[u8], bits: usize) -> Vec<u8> {
+    let mut group = vec![class];
+    group.extend_from_slice(&bytes[..bits / 8]);
+    let rest = bits % 8;
+    if rest > 0 {
+        group.push(bytes[bits / 8] | ((1u8 << (8 - rest)) - 1));
+    }
+    group
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(ip: &str) -> Vec<u8> {
+        network_group(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn test_ipv4_groups() {
+        assert_eq!(group("1.2.3.4"), vec![NET_IPV4, 1, 2]);
+        assert_eq!(group("1.2.200.1"), group("1.2.3.4"));
+        assert_ne!(group("1.3.3.4"), group("1.2.3.4"));
+
+        // Local and private addresses all share one group
+        assert_eq!(group("127.0.0.1"), vec![NET_UNROUTABLE]);
+        assert_eq!(group("10.1.2.3"), vec![NET_UNROUTABLE]);
+        assert_eq!(group("192.168.1.1"), vec![NET_UNROUTABLE]);
+    }
+
+    #[test]
+    fn test_ipv6_groups() {
+        assert_eq!(
+            group("2a01:4f8:1:2::1"),
+            vec![NET_IPV6, 0x2a, 0x01, 0x04, 0xf8]
+        );
+        assert_eq!(group("2a01:4f8:ffff::1"), group("2a01:4f8:1:2::1"));
+
+        // Hurricane Electric tunnels are grouped by /36
+        assert_eq!(
+            group("2001:470:abcd::1"),
+            vec![NET_IPV6, 0x20, 0x01, 0x04, 0x70, 0xaf]
+        );
+        assert_ne!(group("2001:470:1bcd::1"), group("2001:470:abcd::1"));
+
+        assert_eq!(group("::1"), vec![NET_UNROUTABLE]);
+        assert_eq!(group("fe80::1"), vec![NET_UNROUTABLE]);
+    }
+
+    #[test]
+    fn test_tunneled_addresses_use_ipv4_group() {
+        let v4 = group("1.2.3.4");
+        assert_eq!(group("::ffff:1.2.3.4"), v4);
+        assert_eq!(group("::ffff:0:1.2.99.99"), v4);
+        // 6to4
+        assert_eq!(group("2002:102:304::1"), v4);
+        // Teredo, client address 1.2.3.4 stored inverted
+        assert_eq!(group("2001:0:4136:e378:8000:63bf:fefd:fcfb"), v4);
+    }
+
+    #[test]
+    fn test_onion_groups() {
+        assert_eq!(
+            group("fd87:d87e:eb43:a1b2:c3d4:e5f6:a7b8:c9d0"),
+            vec![NET_ONION, 0xaf]
+        );
+        assert_eq!(
+            group("fd87:d87e:eb43:a000::"),
+            group("fd87:d87e:eb43:a1b2:c3d4:e5f6:a7b8:c9d0")
+        );
+        assert_ne!(
+            group("fd87:d87e:eb43:b1b2::"),
+            group("fd87:d87e:eb43:a1b2:c3d4:e5f6:a7b8:c9d0")
+        );
+    }
+}
@@ -3,18 +3,48 @@
 //! Handles individual peer connections, message parsing, and protocol state.
 
 use anyhow::Result;
+use bllvm_protocol::Hash;
+use std::collections::{HashSet, VecDeque};
 use std::net::SocketAddr;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, warn, Instrument, Span};
 
+use super::bandwidth::{PeerBandwidth, ThrottleState};
+use super::connection_manager::ConnectionType;
+use super::permissions::NetPermissions;
+use super::protocol::NetworkAddress;
 use super::transport::{TransportAddr, TransportConnection};
+use super::version_negotiation::VersionNegotiation;
 use super::NetworkMessage;
 
+/// Maximum number of inventory hashes remembered per peer
+pub const MAX_KNOWN_INVENTORY: usize = 50_000;
+
+/// Maximum number of addresses remembered per peer
+pub const MAX_KNOWN_ADDRESSES: usize = 5_000;
+
+/// Average delay between transaction announcements to an inbound peer
+pub const INBOUND_INVENTORY_BROADCAST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Average delay between transaction announcements to an outbound peer
+pub const OUTBOUND_INVENTORY_BROADCAST_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Maximum transactions announced to a peer in one `inv`
+pub const INVENTORY_BROADCAST_MAX: usize = 1000;
+
+/// Source of per-connection peer ids
+static NEXT_PEER_ID: AtomicU64 = AtomicU64::new(0);
+
 /// Peer connection state
 ///
 /// Supports multiple transport types (TCP, Quinn, Iroh) via TransportConnection trait
 pub struct Peer {
+    /// Connection id, unique for the life of the process
+    id: u64,
+    /// Tracing span carrying `peer_addr` and `peer_id` for this connection
+    span: Span,
     addr: SocketAddr,
     transport_addr: TransportAddr, // Full transport address (may differ from SocketAddr for Iroh)
     message_tx: mpsc::UnboundedSender<NetworkMessage>,
@@ -43,6 +73,45 @@ pub struct Peer {
     last_block_received: Option<u64>,
     /// Last successful transaction received (Unix timestamp)
     last_tx_received: Option<u64>,
+    /// Whether the peer connected to us (rather than us to it)
+    inbound: bool,
+    /// Permissions granted by `whitelist` / `whitebind`
+    permissions: NetPermissions,
+    /// Whether the peer's address is discouraged (evicted first when full)
+    discouraged: bool,
+    /// Lowest observed ping round-trip time (milliseconds)
+    min_ping_ms: Option<f64>,
+    /// Most recent ping round-trip time (milliseconds)
+    last_ping_ms: Option<f64>,
+    /// Nonce of the outstanding ping, if we are waiting for a pong
+    ping_nonce: Option<u64>,
+    /// When the last ping was sent (Unix timestamp, microseconds; 0 if never)
+    ping_start_micros: u64,
+    /// Handshake and negotiated protocol version
+    version_negotiation: VersionNegotiation,
+    /// Upload/download bandwidth limiters
+    bandwidth: PeerBandwidth,
+    /// Inventory the peer is known to have (announced by or to it)
+    known_inventory: HashSet<Hash>,
+    /// Insertion order of `known_inventory`, oldest first
+    known_inventory_order: VecDeque<Hash>,
+    /// Transactions waiting to be announced, oldest first
+    tx_inv_queue: VecDeque<Hash>,
+    /// When queued transactions are next announced (Unix timestamp,
+    /// microseconds; 0 before the first announcement)
+    next_inv_send_micros: u64,
+    /// Addresses (IP and port) the peer is known to have, sent by or to it
+    known_addresses: HashSet<([u8; 16], u16)>,
+    /// Insertion order of `known_addresses`, oldest first
+    known_addresses_order: VecDeque<([u8; 16], u16)>,
+    /// Minimum fee rate the peer wants announced (sat/kvB, BIP133)
+    fee_filter: u64,
+    /// Whether the peer wants transaction announcements (`relay` in its version)
+    relay_txs: bool,
+    /// Outbound connection that relays blocks only (no transactions or addresses)
+    block_relay_only: bool,
+    /// When the peer last sent a `mempool` request we answered (Unix timestamp)
+    last_mempool_request: Option<u64>,
 }
 
 impl Peer {
@@ -55,6 +124,26 @@ impl Peer {
         addr: SocketAddr,
         transport_addr: TransportAddr,
         message_tx: mpsc::UnboundedSender<NetworkMessage>,
+    ) -> Self {
+        Self::from_transport_connection_with_bandwidth(
+            conn,
+            addr,
+            transport_addr,
+            message_tx,
+            PeerBandwidth::unlimited(),
+        )
+    }
+
+    /// Create a new peer connection with bandwidth limits
+    ///
+    /// Sends over the upload limit wait in the send queue; reads over the
+    /// download limit delay reading the next message.
+    pub fn from_transport_connection_with_bandwidth<C: TransportConnection + 'static>(
+        conn: C,
+        addr: SocketAddr,
+        transport_addr: TransportAddr,
+        message_tx: mpsc::UnboundedSender<NetworkMessage>,
+        bandwidth: PeerBandwidth,
     ) -> Self {
         // Create channel for sending messages
         let (send_tx, send_rx) = mpsc::unbounded_channel::<Vec<u8>>();
@@ -68,9 +157,16 @@ impl Peer {
         let conn = Arc::new(Mutex::new(conn));
         let conn_read = Arc::clone(&conn);
         let conn_write = Arc::clone(&conn);
+        let bandwidth_read = bandwidth.clone();
+        let bandwidth_write = bandwidth.clone();
+
+        // Connection tasks log under the peer's span; it is a root span so it
+        // does not nest under whichever task accepted or dialed the connection
+        let id = NEXT_PEER_ID.fetch_add(1, Ordering::Relaxed);
+        let span = tracing::info_span!(parent: None, "peer", peer_addr = %addr, peer_id = id);
 
         // Spawn read task using TransportConnection::recv
-        tokio::spawn(async move {
+        let read_task = async move {
             loop {
                 let data = {
                     let mut conn_guard = conn_read.lock().await;
@@ -96,17 +192,27 @@ impl Peer {
                         std::net::SocketAddr::from(([0, 0, 0, 0], 0))
                     }
                 };
+                let len = data.len();
                 let _ = message_tx_clone.send(NetworkMessage::RawMessageReceived(data, peer_addr));
+
+                // Hold off reading the next message while over the download limit
+                bandwidth_read.throttle_download(len).await;
             }
-        });
+
+            // Connection closed or unreadable (e.g. oversized frame): drop the peer
+            let _ = message_tx_clone.send(NetworkMessage::PeerDisconnected(transport_addr_clone));
+        };
+        tokio::spawn(read_task.instrument(span.clone()));
 
         // Spawn write task using TransportConnection::send
-        tokio::spawn(async move {
+        let write_task = async move {
             let mut send_rx = send_rx;
 
             loop {
                 match send_rx.recv().await {
                     Some(data) => {
+                        // Wait out the upload limit; later messages stay queued
+                        bandwidth_write.throttle_upload(data.len()).await;
                         let mut conn_guard = conn_write.lock().await;
                         match conn_guard.send(&data).await {
                             Ok(_) => {
@@ -126,7 +232,8 @@ impl Peer {
 
             // Gracefully close connection on write task exit
             // Connection will be closed when conn_guard is dropped
-        });
+        };
+        tokio::spawn(write_task.instrument(span.clone()));
 
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -134,6 +241,8 @@ impl Peer {
             .as_secs();
 
         Self {
+            id,
+            span,
             addr,
             transport_addr,
             message_tx,
@@ -150,9 +259,38 @@ impl Peer {
             avg_response_time_ms: 0.0,
             last_block_received: None,
             last_tx_received: None,
+            inbound: false,
+            permissions: NetPermissions::empty(),
+            discouraged: false,
+            min_ping_ms: None,
+            last_ping_ms: None,
+            ping_nonce: None,
+            ping_start_micros: 0,
+            version_negotiation: VersionNegotiation::default(),
+            bandwidth,
+            known_inventory: HashSet::new(),
+            known_inventory_order: VecDeque::new(),
+            tx_inv_queue: VecDeque::new(),
+            next_inv_send_micros: 0,
+            known_addresses: HashSet::new(),
+            known_addresses_order: VecDeque::new(),
+            fee_filter: 0,
+            relay_txs: true,
+            block_relay_only: false,
+            last_mempool_request: None,
         }
     }
 
+    /// Connection id, unique for the life of the process
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Tracing span for this connection (fields `peer_addr`, `peer_id`)
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
     /// Create a new peer connection from a TCP stream (backward compatibility)
     ///
     /// This is a convenience method that wraps a TcpStream in a TcpConnection.
@@ -315,4 +453,284 @@ impl Peer {
     pub fn conntime(&self) -> u64 {
         self.conntime
     }
+
+    /// Mark the peer as inbound (it connected to us)
+    pub fn set_inbound(&mut self, inbound: bool) {
+        self.inbound = inbound;
+    }
+
+    /// Check if the peer connected to us
+    pub fn is_inbound(&self) -> bool {
+        self.inbound
+    }
+
+    /// Grant or revoke `noban` (exempt from DoS limits)
+    pub fn set_whitelisted(&mut self, whitelisted: bool) {
+        self.permissions.set(NetPermissions::NOBAN, whitelisted);
+    }
+
+    /// Set the permissions granted by `whitelist` / `whitebind`
+    pub fn set_permissions(&mut self, permissions: NetPermissions) {
+        self.permissions = permissions;
+    }
+
+    /// Permissions granted to the peer
+    pub fn permissions(&self) -> NetPermissions {
+        self.permissions
+    }
+
+    /// Check if the peer has every flag in `permission`
+    pub fn has_permission(&self, permission: NetPermissions) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// Check if the peer is whitelisted with `noban` (exempt from DoS limits)
+    pub fn is_whitelisted(&self) -> bool {
+        self.has_permission(NetPermissions::NOBAN)
+    }
+
+    /// Mark the peer as connecting from a discouraged address
+    pub fn set_discouraged(&mut self, discouraged: bool) {
+        self.discouraged = discouraged;
+    }
+
+    /// Check if the peer connects from a discouraged address
+    pub fn is_discouraged(&self) -> bool {
+        self.discouraged
+    }
+
+    /// Check whether a new ping should be sent
+    ///
+    /// A ping is due when none is outstanding and `interval` has passed since
+    /// the last one (or none was ever sent).
+    pub fn ping_due(&self, now_micros: u64, interval: Duration) -> bool {
+        self.ping_nonce.is_none()
+            && (self.ping_start_micros == 0
+                || now_micros.saturating_sub(self.ping_start_micros) >= interval.as_micros() as u64)
+    }
+
+    /// Record that a ping with `nonce` was sent
+    pub fn record_ping_sent(&mut self, nonce: u64, now_micros: u64) {
+        self.ping_nonce = Some(nonce);
+        self.ping_start_micros = now_micros;
+    }
+
+    /// Record a pong, returning the round-trip time (milliseconds)
+    ///
+    /// Pongs that don't match the outstanding ping are ignored.
+    pub fn record_pong(&mut self, nonce: u64, now_micros: u64) -> Option<f64> {
+        if self.ping_nonce != Some(nonce) {
+            return None;
+        }
+        self.ping_nonce = None;
+        let rtt_ms = now_micros.saturating_sub(self.ping_start_micros) as f64 / 1000.0;
+        self.last_ping_ms = Some(rtt_ms);
+        self.min_ping_ms = Some(match self.min_ping_ms {
+            Some(min) => min.min(rtt_ms),
+            None => rtt_ms,
+        });
+        Some(rtt_ms)
+    }
+
+    /// Check whether the outstanding ping has gone unanswered for longer than `timeout`
+    pub fn ping_timed_out(&self, now_micros: u64, timeout: Duration) -> bool {
+        self.ping_nonce.is_some()
+            && now_micros.saturating_sub(self.ping_start_micros) > timeout.as_micros() as u64
+    }
+
+    /// Time spent waiting for the outstanding pong (milliseconds)
+    pub fn ping_wait_ms(&self, now_micros: u64) -> Option<f64> {
+        self.ping_nonce
+            .map(|_| now_micros.saturating_sub(self.ping_start_micros) as f64 / 1000.0)
+    }
+
+    /// Check whether nothing was received from the peer for longer than `window_seconds`
+    pub fn is_inactive(&self, now: u64, window_seconds: u64) -> bool {
+        now.saturating_sub(self.last_recv) > window_seconds
+    }
+
+    /// Get most recent ping round-trip time
+    pub fn last_ping_ms(&self) -> Option<f64> {
+        self.last_ping_ms
+    }
+
+    /// Get lowest observed ping round-trip time
+    pub fn min_ping_ms(&self) -> Option<f64> {
+        self.min_ping_ms
+    }
+
+    /// Record that the peer delivered a block
+    pub fn record_block_received(&mut self) {
+        self.last_block_received = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        );
+    }
+
+    /// Get time the peer last delivered a block
+    pub fn last_block_time(&self) -> Option<u64> {
+        self.last_block_received
+    }
+
+    /// Get handshake and negotiated version state
+    pub fn version_negotiation(&self) -> &VersionNegotiation {
+        &self.version_negotiation
+    }
+
+    /// Get mutable handshake and negotiated version state
+    pub fn version_negotiation_mut(&mut self) -> &mut VersionNegotiation {
+        &mut self.version_negotiation
+    }
+
+    /// Current bandwidth throttle state
+    pub fn throttle_state(&self) -> ThrottleState {
+        self.bandwidth.state()
+    }
+
+    /// Remember that the peer has an inventory item
+    ///
+    /// Forgets the oldest entries beyond [`MAX_KNOWN_INVENTORY`].
+    pub fn add_known_inventory(&mut self, hash: Hash) {
+        if !self.known_inventory.insert(hash) {
+            return;
+        }
+        self.known_inventory_order.push_back(hash);
+        while self.known_inventory_order.len() > MAX_KNOWN_INVENTORY {
+            if let Some(oldest) = self.known_inventory_order.pop_front() {
+                self.known_inventory.remove(&oldest);
+            }
+        }
+    }
+
+    /// Check if the peer is known to have an inventory item
+    pub fn has_known_inventory(&self, hash: &Hash) -> bool {
+        self.known_inventory.contains(hash)
+    }
+
+    /// Queue a transaction announcement for the peer's next trickle
+    ///
+    /// The transaction is marked known right away so it is queued only once.
+    pub fn queue_tx_inv(&mut self, txid: Hash) {
+        if self.has_known_inventory(&txid) {
+            return;
+        }
+        self.add_known_inventory(txid);
+        self.tx_inv_queue.push_back(txid);
+    }
+
+    /// Number of transactions waiting to be announced
+    pub fn queued_tx_inv_count(&self) -> usize {
+        self.tx_inv_queue.len()
+    }
+
+    /// When queued transactions are next announced (Unix timestamp, microseconds)
+    pub fn next_inv_send_micros(&self) -> u64 {
+        self.next_inv_send_micros
+    }
+
+    /// Take the transactions due for announcement at `now_micros`
+    ///
+    /// Returns nothing until the trickle timer expires; `noban` peers are not
+    /// delayed. Taking a batch schedules the next one after a random delay
+    /// averaging [`INBOUND_INVENTORY_BROADCAST_INTERVAL`] for inbound peers and
+    /// [`OUTBOUND_INVENTORY_BROADCAST_INTERVAL`] for outbound ones, so the
+    /// timing of an announcement reveals little about when we first saw the
+    /// transaction. At most [`INVENTORY_BROADCAST_MAX`] are taken at once.
+    pub fn take_due_tx_invs(&mut self, now_micros: u64) -> Vec<Hash> {
+        if self.tx_inv_queue.is_empty()
+            || (now_micros < self.next_inv_send_micros
+                && !self.has_permission(NetPermissions::NOBAN))
+        {
+            return Vec::new();
+        }
+        let mean = if self.inbound {
+            INBOUND_INVENTORY_BROADCAST_INTERVAL
+        } else {
+            OUTBOUND_INVENTORY_BROADCAST_INTERVAL
+        };
+        // Exponentially distributed, as in Bitcoin Core
+        let delay = -(1.0 - rand::random::<f64>()).ln() * mean.as_micros() as f64;
+        self.next_inv_send_micros = now_micros.saturating_add(delay as u64);
+        let count = self.tx_inv_queue.len().min(INVENTORY_BROADCAST_MAX);
+        self.tx_inv_queue.drain(..count).collect()
+    }
+
+    /// Remember that the peer knows an address
+    ///
+    /// Forgets the oldest entries beyond [`MAX_KNOWN_ADDRESSES`].
+    pub fn add_known_address(&mut self, addr: &NetworkAddress) {
+        let key = (addr.ip, addr.port);
+        if !self.known_addresses.insert(key) {
+            return;
+        }
+        self.known_addresses_order.push_back(key);
+        while self.known_addresses_order.len() > MAX_KNOWN_ADDRESSES {
+            if let Some(oldest) = self.known_addresses_order.pop_front() {
+                self.known_addresses.remove(&oldest);
+            }
+        }
+    }
+
+    /// Check if the peer is known to have an address
+    pub fn has_known_address(&self, addr: &NetworkAddress) -> bool {
+        self.known_addresses.contains(&(addr.ip, addr.port))
+    }
+
+    /// Set the peer's minimum announced fee rate (sat/kvB)
+    pub fn set_fee_filter(&mut self, feerate: u64) {
+        self.fee_filter = feerate;
+    }
+
+    /// Minimum fee rate the peer wants announced (sat/kvB, 0 if unset)
+    pub fn fee_filter(&self) -> u64 {
+        self.fee_filter
+    }
+
+    /// Set whether the peer wants transaction announcements
+    pub fn set_relay_txs(&mut self, relay_txs: bool) {
+        self.relay_txs = relay_txs;
+    }
+
+    /// Whether the peer wants transaction announcements (true until its
+    /// version says otherwise)
+    pub fn relay_txs(&self) -> bool {
+        self.relay_txs
+    }
+
+    /// Mark the connection as block-relay-only
+    pub fn set_block_relay_only(&mut self, block_relay_only: bool) {
+        self.block_relay_only = block_relay_only;
+    }
+
+    /// Whether transactions and addresses are neither sent to nor accepted
+    /// from this peer
+    pub fn is_block_relay_only(&self) -> bool {
+        self.block_relay_only
+    }
+
+    /// Role of this connection (feelers are never registered as peers)
+    pub fn connection_type(&self) -> ConnectionType {
+        if self.inbound {
+            ConnectionType::Inbound
+        } else if self.block_relay_only {
+            ConnectionType::BlockRelayOnly
+        } else {
+            ConnectionType::OutboundFullRelay
+        }
+    }
+
+    /// Record a `mempool` request, unless the last one we answered was less
+    /// than `interval_seconds` ago; returns whether to answer it
+    pub fn allow_mempool_request(&mut self, now: u64, interval_seconds: u64) -> bool {
+        if self
+            .last_mempool_request
+            .is_some_and(|last| now.saturating_sub(last) < interval_seconds)
+        {
+            return false;
+        }
+        self.last_mempool_request = Some(now);
+        true
+    }
 }
@@ -0,0 +1,242 @@
+//! Peer permissions (`whitelist` / `whitebind`)
+//!
+//! Trusted peers are granted individual permission flags rather than a single
+//! "whitelisted" bit. Entries are written `flags@target`, e.g.
+//! `noban,mempool@127.0.0.1/32` for a `whitelist` subnet or
+//! `download@0.0.0.0:8334` for a `whitebind` listening address. An entry
+//! without flags grants [`NetPermissions::IMPLICIT`].
+
+use crate::network::subnet::Subnet;
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+bitflags! {
+    /// Permission flags granted to a connection
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct NetPermissions: u8 {
+        /// Never banned, discouraged or disconnected for misbehavior, and
+        /// exempt from connection and message rate limits, the peer cap and
+        /// inbound eviction
+        const NOBAN = 1 << 0;
+        /// May request our mempool (`mempool` message) at any time, even with
+        /// `relay.accept_mempool_requests` off
+        const MEMPOOL = 1 << 1;
+        /// Served blocks without upload bandwidth limits
+        const DOWNLOAD = 1 << 2;
+        /// Announced transactions regardless of its feefilter
+        const RELAY = 1 << 3;
+        /// Transactions it sends that are already in our mempool are
+        /// announced again; implies `RELAY`
+        const FORCERELAY = 1 << 4 | Self::RELAY.bits();
+    }
+}
+
+/// Flag names in `getpeerinfo` order
+const NAMES: &[(&str, NetPermissions)] = &[
+    ("noban", NetPermissions::NOBAN),
+    ("forcerelay", NetPermissions::FORCERELAY),
+    ("relay", NetPermissions::RELAY),
+    ("mempool", NetPermissions::MEMPOOL),
+    ("download", NetPermissions::DOWNLOAD),
+];
+
+impl NetPermissions {
+    /// Granted by entries that name no flags
+    pub const IMPLICIT: Self = Self::NOBAN.union(Self::MEMPOOL).union(Self::RELAY);
+
+    /// Names of the granted flags (as reported by `getpeerinfo`)
+    pub fn names(self) -> Vec<&'static str> {
+        NAMES
+            .iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Parse a comma-separated flag list (`noban,mempool`, or `all`)
+    pub fn parse_list(list: &str) -> Result<Self, String> {
+        let mut permissions = Self::empty();
+        for name in list.split(',').map(str::trim) {
+            permissions |= match name {
+                "all" => Self::all(),
+                "" => continue,
+                name => NAMES
+                    .iter()
+                    .find(|(flag_name, _)| *flag_name == name)
+                    .map(|(_, flag)| *flag)
+                    .ok_or_else(|| format!("unknown permission flag: {name}"))?,
+            };
+        }
+        Ok(permissions)
+    }
+}
+
+/// Split `flags@target` into the granted permissions and the target
+fn split_entry(s: &str) -> Result<(NetPermissions, &str), String> {
+    match s.split_once('@') {
+        Some((flags, target)) => Ok((NetPermissions::parse_list(flags)?, target)),
+        None => Ok((NetPermissions::IMPLICIT, s)),
+    }
+}
+
+fn fmt_entry(
+    f: &mut fmt::Formatter<'_>,
+    permissions: NetPermissions,
+    target: &dyn fmt::Display,
+) -> fmt::Result {
+    // `forcerelay` implies `relay`, so only name the former
+    let mut names = permissions.names();
+    if permissions.contains(NetPermissions::FORCERELAY) {
+        names.retain(|name| *name != "relay");
+    }
+    write!(f, "{}@{}", names.join(","), target)
+}
+
+/// A `whitelist` entry: peers connecting from `subnet` get `permissions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PermissionedSubnet {
+    pub permissions: NetPermissions,
+    pub subnet: Subnet,
+}
+
+impl FromStr for PermissionedSubnet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (permissions, subnet) = split_entry(s)?;
+        Ok(Self {
+            permissions,
+            subnet: subnet.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for PermissionedSubnet {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PermissionedSubnet> for String {
+    fn from(entry: PermissionedSubnet) -> Self {
+        entry.to_string()
+    }
+}
+
+impl fmt::Display for PermissionedSubnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_entry(f, self.permissions, &self.subnet)
+    }
+}
+
+/// A `whitebind` entry: the node also listens on `addr`, and peers
+/// connecting there get `permissions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PermissionedBind {
+    pub permissions: NetPermissions,
+    pub addr: SocketAddr,
+}
+
+impl FromStr for PermissionedBind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (permissions, addr) = split_entry(s)?;
+        Ok(Self {
+            permissions,
+            addr: addr
+                .parse()
+                .map_err(|_| format!("invalid whitebind address: {s}"))?,
+        })
+    }
+}
+
+impl TryFrom<String> for PermissionedBind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PermissionedBind> for String {
+    fn from(entry: PermissionedBind) -> Self {
+        entry.to_string()
+    }
+}
+
+impl fmt::Display for PermissionedBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_entry(f, self.permissions, &self.addr)
+    }
+}
+
+/// Permissions the whitelist grants a peer at `ip` (the union of every
+/// matching entry)
+pub fn whitelist_permissions(whitelist: &[PermissionedSubnet], ip: IpAddr) -> NetPermissions {
+    whitelist
+        .iter()
+        .filter(|entry| entry.subnet.contains(ip))
+        .fold(NetPermissions::empty(), |permissions, entry| {
+            permissions | entry.permissions
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_permission_entries() {
+        let entry: PermissionedSubnet = "noban,mempool@127.0.0.1/32".parse().unwrap();
+        assert_eq!(
+            entry.permissions,
+            NetPermissions::NOBAN | NetPermissions::MEMPOOL
+        );
+        assert!(entry.subnet.contains("127.0.0.1".parse().unwrap()));
+        assert_eq!(entry.to_string(), "noban,mempool@127.0.0.1/32");
+
+        // Bare subnets keep the old whitelist behavior
+        let bare: PermissionedSubnet = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(bare.permissions, NetPermissions::IMPLICIT);
+
+        let bind: PermissionedBind = "forcerelay,download@0.0.0.0:8334".parse().unwrap();
+        assert!(bind.permissions.contains(NetPermissions::RELAY));
+        assert_eq!(
+            bind.permissions.names(),
+            vec!["forcerelay", "relay", "download"]
+        );
+        assert_eq!(bind.to_string(), "forcerelay,download@0.0.0.0:8334");
+        assert_eq!(
+            "all@::1".parse::<PermissionedSubnet>().unwrap().permissions,
+            NetPermissions::all()
+        );
+
+        assert!("bogus@127.0.0.1".parse::<PermissionedSubnet>().is_err());
+        assert!("noban@127.0.0.1".parse::<PermissionedBind>().is_err());
+    }
+
+    #[test]
+    fn test_whitelist_permissions_union() {
+        let whitelist: Vec<PermissionedSubnet> = vec![
+            "noban@10.0.0.0/8".parse().unwrap(),
+            "mempool@10.1.0.0/16".parse().unwrap(),
+        ];
+        assert_eq!(
+            whitelist_permissions(&whitelist, "10.1.2.3".parse().unwrap()),
+            NetPermissions::NOBAN | NetPermissions::MEMPOOL
+        );
+        assert_eq!(
+            whitelist_permissions(&whitelist, "10.2.0.1".parse().unwrap()),
+            NetPermissions::NOBAN
+        );
+        assert!(whitelist_permissions(&whitelist, "192.168.0.1".parse().unwrap()).is_empty());
+    }
+}
@@ -10,12 +10,76 @@ use serde::{Deserialize, Serialize};
 /// Bitcoin protocol constants
 pub const BITCOIN_MAGIC_MAINNET: [u8; 4] = [0xf9, 0xbe, 0xb4, 0xd9];
 pub const BITCOIN_MAGIC_TESTNET: [u8; 4] = [0x0b, 0x11, 0x09, 0x07];
+pub const BITCOIN_MAGIC_TESTNET4: [u8; 4] = [0x1c, 0x16, 0x3f, 0x28];
 pub const BITCOIN_MAGIC_REGTEST: [u8; 4] = [0xfa, 0xbf, 0xb5, 0xda];
 
 /// Maximum protocol message size (32MB)
 pub const MAX_PROTOCOL_MESSAGE_LENGTH: usize = 32 * 1024 * 1024;
 
+/// Maximum size of messages that don't carry blocks or transactions (4MB)
+pub const MAX_NON_BLOCK_MESSAGE_LENGTH: usize = 4 * 1024 * 1024;
+
+/// Message header size (magic + command + length + checksum)
+pub const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// Commands that may carry blocks or transactions (allowed up to MAX_PROTOCOL_MESSAGE_LENGTH)
+const LARGE_MESSAGE_COMMANDS: &[&str] = &[
+    "block",
+    "tx",
+    "cmpctblock",
+    "blocktxn",
+    "utxoset",
+    "filteredblock",
+    "pkgtxn",
+];
+
+/// Maximum total message size (header included) for a command
+pub fn max_message_length(command: &str) -> usize {
+    if LARGE_MESSAGE_COMMANDS.contains(&command) {
+        MAX_PROTOCOL_MESSAGE_LENGTH
+    } else {
+        MAX_NON_BLOCK_MESSAGE_LENGTH
+    }
+}
+
+/// Errors from parsing a wire message
+///
+/// Any of these means the peer sent a malformed message and should be disconnected.
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("Message too short: {0} bytes")]
+    TooShort(usize),
+
+    #[error("{command} message too large: {size} bytes (max: {max} bytes)")]
+    TooLarge {
+        command: String,
+        size: usize,
+        max: usize,
+    },
+
+    #[error("Invalid magic number: {0:#010x}")]
+    InvalidMagic(u32),
+
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+
+    #[error("Incomplete message: expected {expected} bytes, got {actual}")]
+    Incomplete { expected: usize, actual: usize },
+
+    #[error("Invalid checksum")]
+    InvalidChecksum,
+
+    #[error("Malformed {command} payload: {reason}")]
+    Malformed { command: String, reason: String },
+}
+
 /// Service flags (bitfield in Version.services)
+/// Full block history served (NODE_NETWORK)
+pub const NODE_NETWORK: u64 = 1;
+/// Witness data served (NODE_WITNESS)
+pub const NODE_WITNESS: u64 = 1 << 3;
+/// Only recent blocks served (BIP159 NODE_NETWORK_LIMITED)
+pub const NODE_NETWORK_LIMITED: u64 = 1 << 10;
 #[cfg(feature = "dandelion")]
 pub const NODE_DANDELION: u64 = 1 << 24;
 pub const NODE_PACKAGE_RELAY: u64 = 1 << 25;
@@ -74,6 +138,8 @@ pub const ALLOWED_COMMANDS: &[&str] = &[
     // Ban List Sharing
     "getbanlist",
     "banlist",
+    // FIBRE fast block relay
+    "fibrechunk",
 ];
 
 /// Bitcoin protocol message types
@@ -119,9 +185,15 @@ pub enum ProtocolMessage {
     // Ban List Sharing
     GetBanList(GetBanListMessage),
     BanList(BanListMessage),
+    // FIBRE fast block relay
+    FibreChunk(FibreChunkMessage),
     // Address relay
     GetAddr,
     Addr(AddrMessage),
+    // Fee filtering (BIP133)
+    FeeFilter(FeeFilterMessage),
+    // Mempool contents request (BIP35)
+    MemPool,
 }
 
 /// Version message
@@ -219,10 +291,10 @@ pub struct GetBlocksMessage {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BlockMessage {
     pub block: Block,
-    /// Witness data for each transaction in the block (one Witness per transaction)
+    /// Witness data for each transaction in the block (one Witness per input)
     /// This is populated when parsing from Bitcoin wire format
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub witnesses: Vec<Vec<Vec<u8>>>,
+    pub witnesses: Vec<Vec<Vec<Vec<u8>>>>,
 }
 
 /// Get data message
@@ -599,114 +671,161 @@ pub struct SpamBreakdown {
 pub struct ProtocolParser;
 
 impl ProtocolParser {
-    /// Parse a raw message into a protocol message
-    pub fn parse_message(data: &[u8]) -> Result<ProtocolMessage> {
-        // Validate message size
-        if data.len() < 24 {
-            return Err(anyhow::anyhow!("Message too short"));
-        }
-
-        if data.len() > MAX_PROTOCOL_MESSAGE_LENGTH {
-            return Err(anyhow::anyhow!("Message too large"));
+    /// Parse a message header and check the message size for its command
+    ///
+    /// `total_len` is the full message length (header included). Used by
+    /// transports to reject oversized messages before buffering the payload.
+    pub fn check_header(header: &[u8], total_len: usize) -> Result<String, ProtocolError> {
+        if header.len() < MESSAGE_HEADER_SIZE {
+            return Err(ProtocolError::TooShort(header.len()));
         }
 
-        // Parse message header
-        let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
         if magic != 0xd9b4bef9 {
-            return Err(anyhow::anyhow!("Invalid magic number"));
+            return Err(ProtocolError::InvalidMagic(magic));
         }
 
-        let command = String::from_utf8_lossy(&data[4..12])
+        let command = String::from_utf8_lossy(&header[4..12])
             .trim_end_matches('\0')
             .to_string();
 
         // Validate command string
         if !ALLOWED_COMMANDS.contains(&command.as_str()) {
-            return Err(anyhow::anyhow!("Unknown command: {}", command));
+            return Err(ProtocolError::UnknownCommand(command));
         }
 
-        let payload_length = u32::from_le_bytes([data[16], data[17], data[18], data[19]]);
-        let checksum = &data[20..24];
-
-        // Validate payload length
-        if payload_length as usize > MAX_PROTOCOL_MESSAGE_LENGTH - 24 {
-            return Err(anyhow::anyhow!("Payload too large"));
+        // Validate declared and actual size against the per-command cap
+        let payload_length =
+            u32::from_le_bytes([header[16], header[17], header[18], header[19]]) as usize;
+        let max = max_message_length(&command);
+        let size = total_len.max(MESSAGE_HEADER_SIZE.saturating_add(payload_length));
+        if size > max {
+            return Err(ProtocolError::TooLarge { command, size, max });
         }
 
-        if data.len() < 24 + payload_length as usize {
-            return Err(anyhow::anyhow!("Incomplete message"));
+        Ok(command)
+    }
+
+    /// Parse a raw message into a protocol message
+    ///
+    /// Errors are [`ProtocolError`]s (wrapped in `anyhow`).
+    pub fn parse_message(data: &[u8]) -> Result<ProtocolMessage> {
+        let command = Self::check_header(data, data.len())?;
+
+        let payload_length = u32::from_le_bytes([data[16], data[17], data[18], data[19]]) as usize;
+        let checksum = &data[20..24];
+
+        if data.len() < MESSAGE_HEADER_SIZE + payload_length {
+            return Err(ProtocolError::Incomplete {
+                expected: MESSAGE_HEADER_SIZE + payload_length,
+                actual: data.len(),
+            }
+            .into());
         }
 
-        let payload = &data[24..24 + payload_length as usize];
+        let payload = &data[MESSAGE_HEADER_SIZE..MESSAGE_HEADER_SIZE + payload_length];
 
         // Verify checksum using Bitcoin double SHA256
         let calculated_checksum = Self::calculate_checksum(payload);
         if calculated_checksum != checksum {
-            return Err(anyhow::anyhow!("Invalid checksum"));
+            return Err(ProtocolError::InvalidChecksum.into());
         }
 
         // Parse payload based on command
         match command.as_str() {
-            "version" => Ok(ProtocolMessage::Version(bincode::deserialize(payload)?)),
+            "version" => Ok(ProtocolMessage::Version(Self::decode(&command, payload)?)),
             "verack" => Ok(ProtocolMessage::Verack),
-            "ping" => Ok(ProtocolMessage::Ping(bincode::deserialize(payload)?)),
-            "pong" => Ok(ProtocolMessage::Pong(bincode::deserialize(payload)?)),
-            "getheaders" => Ok(ProtocolMessage::GetHeaders(bincode::deserialize(payload)?)),
-            "headers" => Ok(ProtocolMessage::Headers(bincode::deserialize(payload)?)),
-            "getblocks" => Ok(ProtocolMessage::GetBlocks(bincode::deserialize(payload)?)),
-            "block" => Ok(ProtocolMessage::Block(bincode::deserialize(payload)?)),
-            "getdata" => Ok(ProtocolMessage::GetData(bincode::deserialize(payload)?)),
-            "inv" => Ok(ProtocolMessage::Inv(bincode::deserialize(payload)?)),
-            "tx" => Ok(ProtocolMessage::Tx(bincode::deserialize(payload)?)),
+            "ping" => Ok(ProtocolMessage::Ping(Self::decode(&command, payload)?)),
+            "pong" => Ok(ProtocolMessage::Pong(Self::decode(&command, payload)?)),
+            "getheaders" => Ok(ProtocolMessage::GetHeaders(Self::decode(
+                &command, payload,
+            )?)),
+            "headers" => Ok(ProtocolMessage::Headers(Self::decode(&command, payload)?)),
+            "getblocks" => Ok(ProtocolMessage::GetBlocks(Self::decode(&command, payload)?)),
+            "block" => Ok(ProtocolMessage::Block(Self::decode(&command, payload)?)),
+            "getdata" => Ok(ProtocolMessage::GetData(Self::decode(&command, payload)?)),
+            "inv" => Ok(ProtocolMessage::Inv(Self::decode(&command, payload)?)),
+            "tx" => Ok(ProtocolMessage::Tx(Self::decode(&command, payload)?)),
             // Compact Block Relay (BIP152)
-            "sendcmpct" => Ok(ProtocolMessage::SendCmpct(bincode::deserialize(payload)?)),
-            "cmpctblock" => Ok(ProtocolMessage::CmpctBlock(bincode::deserialize(payload)?)),
-            "getblocktxn" => Ok(ProtocolMessage::GetBlockTxn(bincode::deserialize(payload)?)),
-            "blocktxn" => Ok(ProtocolMessage::BlockTxn(bincode::deserialize(payload)?)),
+            "sendcmpct" => Ok(ProtocolMessage::SendCmpct(Self::decode(&command, payload)?)),
+            "cmpctblock" => Ok(ProtocolMessage::CmpctBlock(Self::decode(
+                &command, payload,
+            )?)),
+            "getblocktxn" => Ok(ProtocolMessage::GetBlockTxn(Self::decode(
+                &command, payload,
+            )?)),
+            "blocktxn" => Ok(ProtocolMessage::BlockTxn(Self::decode(&command, payload)?)),
             // UTXO commitment protocol extensions
-            "getutxoset" => Ok(ProtocolMessage::GetUTXOSet(bincode::deserialize(payload)?)),
-            "utxoset" => Ok(ProtocolMessage::UTXOSet(bincode::deserialize(payload)?)),
-            "getfilteredblock" => Ok(ProtocolMessage::GetFilteredBlock(bincode::deserialize(
-                payload,
+            "getutxoset" => Ok(ProtocolMessage::GetUTXOSet(Self::decode(
+                &command, payload,
+            )?)),
+            "utxoset" => Ok(ProtocolMessage::UTXOSet(Self::decode(&command, payload)?)),
+            "getfilteredblock" => Ok(ProtocolMessage::GetFilteredBlock(Self::decode(
+                &command, payload,
             )?)),
-            "filteredblock" => Ok(ProtocolMessage::FilteredBlock(bincode::deserialize(
-                payload,
+            "filteredblock" => Ok(ProtocolMessage::FilteredBlock(Self::decode(
+                &command, payload,
             )?)),
             // Block Filtering (BIP157)
-            "getcfilters" => Ok(ProtocolMessage::GetCfilters(bincode::deserialize(payload)?)),
-            "cfilter" => Ok(ProtocolMessage::Cfilter(bincode::deserialize(payload)?)),
-            "getcfheaders" => Ok(ProtocolMessage::GetCfheaders(bincode::deserialize(
-                payload,
+            "getcfilters" => Ok(ProtocolMessage::GetCfilters(Self::decode(
+                &command, payload,
             )?)),
-            "cfheaders" => Ok(ProtocolMessage::Cfheaders(bincode::deserialize(payload)?)),
-            "getcfcheckpt" => Ok(ProtocolMessage::GetCfcheckpt(bincode::deserialize(
-                payload,
+            "cfilter" => Ok(ProtocolMessage::Cfilter(Self::decode(&command, payload)?)),
+            "getcfheaders" => Ok(ProtocolMessage::GetCfheaders(Self::decode(
+                &command, payload,
             )?)),
-            "cfcheckpt" => Ok(ProtocolMessage::Cfcheckpt(bincode::deserialize(payload)?)),
+            "cfheaders" => Ok(ProtocolMessage::Cfheaders(Self::decode(&command, payload)?)),
+            "getcfcheckpt" => Ok(ProtocolMessage::GetCfcheckpt(Self::decode(
+                &command, payload,
+            )?)),
+            "cfcheckpt" => Ok(ProtocolMessage::Cfcheckpt(Self::decode(&command, payload)?)),
             // Payment Protocol (BIP70) - P2P variant
-            "getpaymentrequest" => Ok(ProtocolMessage::GetPaymentRequest(bincode::deserialize(
-                payload,
+            "getpaymentrequest" => Ok(ProtocolMessage::GetPaymentRequest(Self::decode(
+                &command, payload,
+            )?)),
+            "paymentrequest" => Ok(ProtocolMessage::PaymentRequest(Self::decode(
+                &command, payload,
             )?)),
-            "paymentrequest" => Ok(ProtocolMessage::PaymentRequest(bincode::deserialize(
-                payload,
+            "payment" => Ok(ProtocolMessage::Payment(Self::decode(&command, payload)?)),
+            "paymentack" => Ok(ProtocolMessage::PaymentACK(Self::decode(
+                &command, payload,
             )?)),
-            "payment" => Ok(ProtocolMessage::Payment(bincode::deserialize(payload)?)),
-            "paymentack" => Ok(ProtocolMessage::PaymentACK(bincode::deserialize(payload)?)),
             // Package Relay (BIP 331)
-            "sendpkgtxn" => Ok(ProtocolMessage::SendPkgTxn(bincode::deserialize(payload)?)),
-            "pkgtxn" => Ok(ProtocolMessage::PkgTxn(bincode::deserialize(payload)?)),
-            "pkgtxnreject" => Ok(ProtocolMessage::PkgTxnReject(bincode::deserialize(
-                payload,
+            "sendpkgtxn" => Ok(ProtocolMessage::SendPkgTxn(Self::decode(
+                &command, payload,
+            )?)),
+            "pkgtxn" => Ok(ProtocolMessage::PkgTxn(Self::decode(&command, payload)?)),
+            "pkgtxnreject" => Ok(ProtocolMessage::PkgTxnReject(Self::decode(
+                &command, payload,
             )?)),
             // Ban List Sharing
-            "getbanlist" => Ok(ProtocolMessage::GetBanList(bincode::deserialize(payload)?)),
-            "banlist" => Ok(ProtocolMessage::BanList(bincode::deserialize(payload)?)),
+            "getbanlist" => Ok(ProtocolMessage::GetBanList(Self::decode(
+                &command, payload,
+            )?)),
+            "banlist" => Ok(ProtocolMessage::BanList(Self::decode(&command, payload)?)),
+            // FIBRE fast block relay
+            "fibrechunk" => Ok(ProtocolMessage::FibreChunk(Self::decode(
+                &command, payload,
+            )?)),
             "getaddr" => Ok(ProtocolMessage::GetAddr),
-            "addr" => Ok(ProtocolMessage::Addr(bincode::deserialize(payload)?)),
-            _ => Err(anyhow::anyhow!("Unknown command: {}", command)),
+            "addr" => Ok(ProtocolMessage::Addr(Self::decode(&command, payload)?)),
+            "feefilter" => Ok(ProtocolMessage::FeeFilter(Self::decode(&command, payload)?)),
+            "mempool" => Ok(ProtocolMessage::MemPool),
+            _ => Err(ProtocolError::UnknownCommand(command).into()),
         }
     }
 
+    /// Decode a message payload
+    fn decode<T: serde::de::DeserializeOwned>(command: &str, payload: &[u8]) -> Result<T> {
+        bincode::deserialize(payload).map_err(|e| {
+            ProtocolError::Malformed {
+                command: command.to_string(),
+                reason: e.to_string(),
+            }
+            .into()
+        })
+    }
+
     /// Serialize a protocol message to bytes
     pub fn serialize_message(message: &ProtocolMessage) -> Result<Vec<u8>> {
         let (command, payload) = match message {
@@ -754,9 +873,15 @@ impl ProtocolParser {
             // Ban List Sharing
             ProtocolMessage::GetBanList(msg) => ("getbanlist", bincode::serialize(msg)?),
             ProtocolMessage::BanList(msg) => ("banlist", bincode::serialize(msg)?),
+            // FIBRE fast block relay
+            ProtocolMessage::FibreChunk(msg) => ("fibrechunk", bincode::serialize(msg)?),
             // Address relay
             ProtocolMessage::GetAddr => ("getaddr", vec![]),
             ProtocolMessage::Addr(msg) => ("addr", bincode::serialize(msg)?),
+            // Fee filtering (BIP133)
+            ProtocolMessage::FeeFilter(msg) => ("feefilter", bincode::serialize(msg)?),
+            // Mempool contents request (BIP35)
+            ProtocolMessage::MemPool => ("mempool", vec![]),
         };
 
         let mut message = Vec::new();
@@ -820,6 +945,17 @@ pub struct BanListMessage {
     pub ban_entries: Vec<BanEntry>,
     /// Timestamp when ban list was generated
     pub timestamp: u64,
+    /// Publisher signature over the rest of the message (optional)
+    pub signature: Option<BanListSignature>,
+}
+
+/// Signature of a ban list publisher
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanListSignature {
+    /// Compressed secp256k1 public key of the publisher
+    pub public_key: Vec<u8>,
+    /// Compact ECDSA signature (64 bytes)
+    pub signature: Vec<u8>,
 }
 
 /// Single ban entry
@@ -833,6 +969,33 @@ pub struct BanEntry {
     pub reason: Option<String>,
 }
 
+// FIBRE fast block relay
+
+/// FibreChunk message - One FEC-encoded chunk of a serialized block
+///
+/// The block is split into equal-size data chunks (the last one zero-padded),
+/// grouped into FEC groups, and each group is extended with Reed-Solomon
+/// parity chunks. Any `data_chunks` chunks of a group recover that group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FibreChunkMessage {
+    /// Hash of the block
+    pub block_hash: Hash,
+    /// Length of the serialized block in bytes
+    pub block_size: u32,
+    /// Number of FEC groups the block was split into
+    pub group_count: u16,
+    /// FEC group this chunk belongs to
+    pub group: u16,
+    /// Data chunks in this group
+    pub data_chunks: u16,
+    /// Parity chunks in this group
+    pub parity_chunks: u16,
+    /// Index within the group (data chunks first, then parity chunks)
+    pub index: u16,
+    /// Chunk payload
+    pub data: Vec<u8>,
+}
+
 // Address relay messages
 
 /// Addr message - Contains peer addresses
@@ -841,3 +1004,125 @@ pub struct AddrMessage {
     /// List of network addresses
     pub addresses: Vec<NetworkAddress>,
 }
+
+/// FeeFilter message (BIP133) - Minimum fee rate for transaction announcements
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeFilterMessage {
+    /// Minimum fee rate in satoshis per 1000 bytes
+    pub feerate: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn ping_frame() -> Vec<u8> {
+        ProtocolParser::serialize_message(&ProtocolMessage::Ping(PingMessage { nonce: 42 }))
+            .unwrap()
+    }
+
+    /// Build a frame with a valid header for `command` and the given payload
+    fn frame(command: &str, payload: &[u8]) -> Vec<u8> {
+        let mut data = 0xd9b4bef9u32.to_le_bytes().to_vec();
+        let mut cmd = [0u8; 12];
+        cmd[..command.len()].copy_from_slice(command.as_bytes());
+        data.extend_from_slice(&cmd);
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&ProtocolParser::calculate_checksum(payload));
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn protocol_error(data: &[u8]) -> ProtocolError {
+        ProtocolParser::parse_message(data)
+            .unwrap_err()
+            .downcast::<ProtocolError>()
+            .expect("parse errors are ProtocolErrors")
+    }
+
+    #[test]
+    fn test_truncated_frames_are_rejected() {
+        let data = ping_frame();
+        assert!(ProtocolParser::parse_message(&data).is_ok());
+
+        for len in 0..data.len() {
+            match protocol_error(&data[..len]) {
+                ProtocolError::TooShort(_) | ProtocolError::Incomplete { .. } => {}
+                other => panic!("unexpected error for {} byte prefix: {}", len, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_over_length_frames_are_rejected() {
+        // Declared payload length above the non-block cap
+        let mut data = ping_frame();
+        data[16..20].copy_from_slice(&(MAX_NON_BLOCK_MESSAGE_LENGTH as u32).to_le_bytes());
+        assert!(matches!(
+            protocol_error(&data),
+            ProtocolError::TooLarge { ref command, .. } if command == "ping"
+        ));
+
+        // Blocks may be larger, up to the hard cap
+        let header = frame("block", &[]);
+        let big = MAX_NON_BLOCK_MESSAGE_LENGTH * 2;
+        assert!(ProtocolParser::check_header(&header, big).is_ok());
+        assert!(matches!(
+            ProtocolParser::check_header(&header, MAX_PROTOCOL_MESSAGE_LENGTH + 1),
+            Err(ProtocolError::TooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_frames_return_typed_errors() {
+        let mut bad_checksum = ping_frame();
+        bad_checksum[20] ^= 0xff;
+        assert!(matches!(
+            protocol_error(&bad_checksum),
+            ProtocolError::InvalidChecksum
+        ));
+
+        let mut bad_magic = ping_frame();
+        bad_magic[0] = 0;
+        assert!(matches!(
+            protocol_error(&bad_magic),
+            ProtocolError::InvalidMagic(_)
+        ));
+
+        assert!(matches!(
+            protocol_error(&frame("bogus", &[])),
+            ProtocolError::UnknownCommand(_)
+        ));
+
+        // Valid header and checksum, but the payload doesn't decode
+        assert!(matches!(
+            protocol_error(&frame("ping", &[1, 2, 3])),
+            ProtocolError::Malformed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_random_corruption_never_panics() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x5eed);
+        let frames = [
+            ping_frame(),
+            frame(
+                "addr",
+                &bincode::serialize(&Vec::<NetworkAddress>::new()).unwrap(),
+            ),
+        ];
+        for _ in 0..2000 {
+            let mut data = frames[rng.gen_range(0..frames.len())].clone();
+            for _ in 0..rng.gen_range(1..4) {
+                let i = rng.gen_range(0..data.len());
+                data[i] = rng.gen();
+            }
+            let len = rng.gen_range(0..=data.len());
+            data.truncate(len);
+            if let Err(e) = ProtocolParser::parse_message(&data) {
+                assert!(e.downcast_ref::<ProtocolError>().is_some());
+            }
+        }
+    }
+}
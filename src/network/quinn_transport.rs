@@ -205,7 +205,9 @@ impl TransportConnection for QuinnConnection {
         }
 
         // Validate message size before allocation (DoS protection)
-        use crate::network::protocol::MAX_PROTOCOL_MESSAGE_LENGTH;
+        use crate::network::protocol::{
+            ProtocolError, ProtocolParser, MAX_PROTOCOL_MESSAGE_LENGTH, MESSAGE_HEADER_SIZE,
+        };
         if len > MAX_PROTOCOL_MESSAGE_LENGTH {
             return Err(anyhow::anyhow!(
                 "Message too large: {} bytes (max: {} bytes)",
@@ -214,9 +216,21 @@ impl TransportConnection for QuinnConnection {
             ));
         }
 
-        // Read data
-        let mut buffer = vec![0u8; len];
+        // Check per-command limits on the header before buffering the payload
+        let header_len = len.min(MESSAGE_HEADER_SIZE);
+        let mut buffer = vec![0u8; header_len];
         stream.read_exact(&mut buffer).await?;
+        if header_len == MESSAGE_HEADER_SIZE {
+            if let Err(e @ ProtocolError::TooLarge { .. }) =
+                ProtocolParser::check_header(&buffer, len)
+            {
+                return Err(e.into());
+            }
+        }
+
+        // Read the rest of the data
+        buffer.resize(len, 0);
+        stream.read_exact(&mut buffer[header_len..]).await?;
 
         Ok(buffer)
     }
@@ -8,7 +8,7 @@
 #[cfg(feature = "dandelion")]
 use super::dandelion::DandelionRelay;
 use crate::utils::current_timestamp;
-use bllvm_protocol::{Block, Hash};
+use bllvm_protocol::Hash;
 use std::collections::HashMap;
 use tracing::debug;
 #[cfg(feature = "dandelion")]
@@ -272,13 +272,14 @@ impl RelayManager {
     pub fn prioritize_block_via_fibre(
         &mut self,
         fibre: &mut crate::network::fibre::FibreRelay,
-        block: &Block,
+        block_hash: Hash,
+        block_data: &[u8],
     ) -> bool {
         if !self.policies.enable_block_relay {
             return false;
         }
-        // Encode and cache for FIBRE. Actual UDP send is out-of-scope here.
-        match fibre.encode_block(block.clone()) {
+        // Encode and cache for FIBRE; NetworkManager sends the chunks
+        match fibre.encode_block(block_hash, block_data) {
             Ok(_encoded) => {
                 debug!("Prepared FEC chunks for FIBRE relay");
                 true
@@ -367,18 +368,13 @@ mod tests {
     fn test_prioritize_block_via_fibre_encodes() {
         let mut relay = RelayManager::new();
         let mut fibre = FibreRelay::new();
-        let block = Block {
-            header: bllvm_protocol::BlockHeader {
-                version: 1,
-                prev_block_hash: [0u8; 32],
-                merkle_root: [0u8; 32],
-                timestamp: 0,
-                bits: 0,
-                nonce: 0,
-            },
-            transactions: vec![].into_boxed_slice(),
-        };
-        let ok = relay.prioritize_block_via_fibre(&mut fibre, &block);
+        // Serialized header and an empty transaction list
+        let mut block_data = vec![0u8; 80];
+        block_data[0] = 1;
+        block_data.push(0);
+        let block_hash = crate::network::fibre::block_hash(&block_data).unwrap();
+        let ok = relay.prioritize_block_via_fibre(&mut fibre, block_hash, &block_data);
         assert!(ok);
+        assert!(fibre.get_encoded_block(&block_hash).is_some());
     }
 }
@@ -0,0 +1,399 @@
+//! SOCKS5 proxy client (RFC 1928, RFC 1929)
+//!
+//! Routes outbound TCP connections through a SOCKS5 proxy such as Tor.
+//! Host names (including `.onion` addresses) are sent to the proxy unresolved,
+//! so they are never looked up through local DNS.
+//!
+//! With stream isolation enabled, every connection authenticates with unique
+//! random credentials. Tor places streams with different SOCKS credentials on
+//! different circuits, which prevents peers from being correlated by exit.
+
+use crate::config::ProxyConfig;
+use anyhow::Result;
+use std::fmt;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+const SOCKS_VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_USER_PASS: u8 = 0x02;
+const METHOD_NO_ACCEPTABLE: u8 = 0xFF;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Destination of a proxied connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Socks5Target {
+    /// IP address and port
+    Ip(SocketAddr),
+    /// Host name and port, resolved by the proxy
+    Domain(String, u16),
+}
+
+impl Socks5Target {
+    /// Check if this target is a Tor onion service
+    pub fn is_onion(&self) -> bool {
+        match self {
+            Self::Ip(_) => false,
+            Self::Domain(host, _) => host.to_ascii_lowercase().ends_with(".onion"),
+        }
+    }
+}
+
+impl fmt::Display for Socks5Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(addr) => write!(f, "{}", addr),
+            Self::Domain(host, port) => write!(f, "{}:{}", host, port),
+        }
+    }
+}
+
+/// SOCKS5 proxy used for outbound connections
+#[derive(Debug, Clone)]
+pub struct Socks5Proxy {
+    addr: SocketAddr,
+    credentials: Option<(String, String)>,
+    stream_isolation: bool,
+}
+
+impl Socks5Proxy {
+    /// Create a proxy client without authentication or stream isolation
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            credentials: None,
+            stream_isolation: false,
+        }
+    }
+
+    /// Create a proxy client from configuration
+    pub fn from_config(config: &ProxyConfig) -> Self {
+        let mut proxy = Self::new(config.addr).with_stream_isolation(config.stream_isolation);
+        if let Some(ref username) = config.username {
+            proxy = proxy.with_credentials(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            );
+        }
+        proxy
+    }
+
+    /// Authenticate with a fixed username and password
+    pub fn with_credentials(mut self, username: String, password: String) -> Self {
+        self.credentials = Some((username, password));
+        self
+    }
+
+    /// Use unique random credentials for every connection
+    pub fn with_stream_isolation(mut self, enabled: bool) -> Self {
+        self.stream_isolation = enabled;
+        self
+    }
+
+    /// Get proxy address
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Open a connection to `target` through the proxy
+    pub async fn connect(&self, target: &Socks5Target) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect(self.addr)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to connect to proxy {}: {}", self.addr, e))?;
+        let credentials = self.connection_credentials();
+        handshake(&mut stream, target, credentials.as_ref()).await?;
+        debug!("Connected to {} via SOCKS5 proxy {}", target, self.addr);
+        Ok(stream)
+    }
+
+    /// Credentials for a new connection
+    ///
+    /// With stream isolation, a random suffix is appended to the configured
+    /// username (or a default prefix) so no two connections share credentials.
+    fn connection_credentials(&self) -> Option<(String, String)> {
+        if self.stream_isolation {
+            let prefix = self
+                .credentials
+                .as_ref()
+                .map(|(user, _)| user.as_str())
+                .unwrap_or("bllvm");
+            Some((
+                format!("{}-{:016x}", prefix, rand::random::<u64>()),
+                format!("{:016x}", rand::random::<u64>()),
+            ))
+        } else {
+            self.credentials.clone()
+        }
+    }
+}
+
+/// Perform method negotiation, optional authentication and CONNECT
+async fn handshake<S>(
+    stream: &mut S,
+    target: &Socks5Target,
+    credentials: Option<&(String, String)>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Method negotiation
+    if credentials.is_some() {
+        stream
+            .write_all(&[SOCKS_VERSION, 2, METHOD_NO_AUTH, METHOD_USER_PASS])
+            .await?;
+    } else {
+        stream
+            .write_all(&[SOCKS_VERSION, 1, METHOD_NO_AUTH])
+            .await?;
+    }
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(anyhow::anyhow!(
+            "Invalid SOCKS version from proxy: {}",
+            reply[0]
+        ));
+    }
+    match reply[1] {
+        METHOD_NO_AUTH => {}
+        METHOD_USER_PASS => {
+            let (username, password) = credentials.ok_or_else(|| {
+                anyhow::anyhow!("Proxy requested authentication but none is configured")
+            })?;
+            authenticate(stream, username, password).await?;
+        }
+        METHOD_NO_ACCEPTABLE => {
+            return Err(anyhow::anyhow!("Proxy rejected all authentication methods"));
+        }
+        method => {
+            return Err(anyhow::anyhow!(
+                "Proxy selected unsupported method: {}",
+                method
+            ));
+        }
+    }
+
+    // CONNECT request
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0x00];
+    let port = match target {
+        Socks5Target::Ip(SocketAddr::V4(addr)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        Socks5Target::Ip(SocketAddr::V6(addr)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&addr.ip().octets());
+            addr.port()
+        }
+        Socks5Target::Domain(host, port) => {
+            if host.is_empty() || host.len() > 255 {
+                return Err(anyhow::anyhow!("Invalid proxy target host: {}", host));
+            }
+            request.push(ATYP_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+            *port
+        }
+    };
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    // CONNECT reply: VER REP RSV ATYP BND.ADDR BND.PORT
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    if header[0] != SOCKS_VERSION {
+        return Err(anyhow::anyhow!(
+            "Invalid SOCKS version from proxy: {}",
+            header[0]
+        ));
+    }
+    if header[1] != 0x00 {
+        return Err(anyhow::anyhow!(
+            "Proxy CONNECT to {} failed: {}",
+            target,
+            reply_error(header[1])
+        ));
+    }
+    let bound_len = match header[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        atyp => return Err(anyhow::anyhow!("Invalid address type from proxy: {}", atyp)),
+    };
+    let mut bound = vec![0u8; bound_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+/// Username/password sub-negotiation (RFC 1929)
+async fn authenticate<S>(stream: &mut S, username: &str, password: &str) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if username.len() > 255 || password.len() > 255 {
+        return Err(anyhow::anyhow!("Proxy credentials too long"));
+    }
+
+    let mut request = vec![AUTH_VERSION, username.len() as u8];
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0x00 {
+        return Err(anyhow::anyhow!("Proxy authentication failed"));
+    }
+    Ok(())
+}
+
+/// Describe a SOCKS5 reply code
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "connection not allowed",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Minimal SOCKS5 server that accepts one CONNECT and records it
+    async fn mock_socks5_server(
+        listener: TcpListener,
+    ) -> (Option<(String, String)>, u8, Vec<u8>, u16) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut greeting = [0u8; 2];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting[0], SOCKS_VERSION);
+        let mut methods = vec![0u8; greeting[1] as usize];
+        stream.read_exact(&mut methods).await.unwrap();
+
+        let mut credentials = None;
+        if methods.contains(&METHOD_USER_PASS) {
+            stream
+                .write_all(&[SOCKS_VERSION, METHOD_USER_PASS])
+                .await
+                .unwrap();
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).await.unwrap();
+            let mut username = vec![0u8; header[1] as usize];
+            stream.read_exact(&mut username).await.unwrap();
+            let mut plen = [0u8; 1];
+            stream.read_exact(&mut plen).await.unwrap();
+            let mut password = vec![0u8; plen[0] as usize];
+            stream.read_exact(&mut password).await.unwrap();
+            stream.write_all(&[AUTH_VERSION, 0x00]).await.unwrap();
+            credentials = Some((
+                String::from_utf8(username).unwrap(),
+                String::from_utf8(password).unwrap(),
+            ));
+        } else {
+            stream
+                .write_all(&[SOCKS_VERSION, METHOD_NO_AUTH])
+                .await
+                .unwrap();
+        }
+
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[..3], [SOCKS_VERSION, CMD_CONNECT, 0x00]);
+        let atyp = request[3];
+        let addr = match atyp {
+            ATYP_IPV4 => {
+                let mut addr = vec![0u8; 4];
+                stream.read_exact(&mut addr).await.unwrap();
+                addr
+            }
+            ATYP_DOMAIN => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).await.unwrap();
+                let mut addr = vec![0u8; len[0] as usize];
+                stream.read_exact(&mut addr).await.unwrap();
+                addr
+            }
+            _ => panic!("unexpected address type {}", atyp),
+        };
+        let port = stream.read_u16().await.unwrap();
+
+        stream
+            .write_all(&[SOCKS_VERSION, 0x00, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+
+        (credentials, atyp, addr, port)
+    }
+
+    #[tokio::test]
+    async fn test_connect_handshake_onion_with_stream_isolation() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(mock_socks5_server(listener));
+
+        let proxy = Socks5Proxy::new(proxy_addr).with_stream_isolation(true);
+        let onion = "pg6mmjiyjmcrsslvykfwnntlaru7p5svn6y2ymmju6nubxndf4pscryd.onion";
+        let target = Socks5Target::Domain(onion.to_string(), 8333);
+        assert!(target.is_onion());
+        proxy.connect(&target).await.unwrap();
+
+        let (credentials, atyp, addr, port) = server.await.unwrap();
+        // Onion host is passed to the proxy unresolved
+        assert_eq!(atyp, ATYP_DOMAIN);
+        assert_eq!(addr, onion.as_bytes());
+        assert_eq!(port, 8333);
+        let (username, _) = credentials.expect("isolated streams must authenticate");
+        assert!(username.starts_with("bllvm-"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_handshake_ipv4_without_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(mock_socks5_server(listener));
+
+        let proxy = Socks5Proxy::new(proxy_addr);
+        let target = Socks5Target::Ip("203.0.113.7:8333".parse().unwrap());
+        proxy.connect(&target).await.unwrap();
+
+        let (credentials, atyp, addr, port) = server.await.unwrap();
+        assert!(credentials.is_none());
+        assert_eq!(atyp, ATYP_IPV4);
+        assert_eq!(addr, vec![203, 0, 113, 7]);
+        assert_eq!(port, 8333);
+    }
+
+    #[test]
+    fn test_stream_isolation_credentials_are_unique() {
+        let proxy = Socks5Proxy::new("127.0.0.1:9050".parse().unwrap())
+            .with_credentials("node".to_string(), "secret".to_string())
+            .with_stream_isolation(true);
+        let first = proxy.connection_credentials().unwrap();
+        let second = proxy.connection_credentials().unwrap();
+        assert!(first.0.starts_with("node-"));
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,158 @@
+//! IP subnets
+//!
+//! Parses and matches CIDR subnets such as `192.168.0.0/16` or `fd00::/8`.
+//! A bare address is a single-host subnet.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IP subnet: a network address and prefix length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Subnet {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Subnet {
+    /// Create a subnet, masking off host bits of `addr`
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, String> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_len {
+            return Err(format!(
+                "prefix length {prefix_len} exceeds {max_len} for {addr}"
+            ));
+        }
+        Ok(Self {
+            network: mask(addr, prefix_len),
+            prefix_len,
+        })
+    }
+
+    /// Network address
+    pub fn network(&self) -> IpAddr {
+        self.network
+    }
+
+    /// Prefix length in bits
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Check whether `ip` is in this subnet
+    ///
+    /// IPv4-mapped IPv6 addresses match IPv4 subnets.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) if self.network.is_ipv4() => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => return false,
+            },
+            ip => ip,
+        };
+        ip.is_ipv4() == self.network.is_ipv4() && mask(ip, self.prefix_len) == self.network
+    }
+}
+
+/// Zero all but the first `prefix_len` bits of `addr`
+fn mask(addr: IpAddr, prefix_len: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let mask = if prefix_len == 0 {
+                0
+            } else {
+                !0u32 << (32 - prefix_len as u32)
+            };
+            IpAddr::V4((u32::from(v4) & mask).into())
+        }
+        IpAddr::V6(v6) => {
+            let mask = if prefix_len == 0 {
+                0
+            } else {
+                !0u128 << (128 - prefix_len as u32)
+            };
+            IpAddr::V6((u128::from(v6) & mask).into())
+        }
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, len)) => {
+                let len = len
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid prefix length in subnet: {s}"))?;
+                (addr, Some(len))
+            }
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid address in subnet: {s}"))?;
+        let prefix_len = prefix_len.unwrap_or(if addr.is_ipv4() { 32 } else { 128 });
+        Self::new(addr, prefix_len)
+    }
+}
+
+impl TryFrom<String> for Subnet {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Subnet> for String {
+    fn from(subnet: Subnet) -> Self {
+        subnet.to_string()
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_contains() {
+        let subnet: Subnet = "192.168.1.77/24".parse().unwrap();
+        assert_eq!(subnet.to_string(), "192.168.1.0/24");
+        assert!(subnet.contains("192.168.1.5".parse().unwrap()));
+        assert!(subnet.contains("::ffff:192.168.1.5".parse().unwrap()));
+        assert!(!subnet.contains("192.168.2.5".parse().unwrap()));
+        assert!(!subnet.contains("::1".parse().unwrap()));
+
+        let host: Subnet = "10.0.0.1".parse().unwrap();
+        assert_eq!(host.prefix_len(), 32);
+        assert!(host.contains("10.0.0.1".parse().unwrap()));
+        assert!(!host.contains("10.0.0.2".parse().unwrap()));
+
+        let v6: Subnet = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("fe80::1".parse().unwrap()));
+
+        let all: Subnet = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_subnet_parse_errors() {
+        assert!("10.0.0.0/33".parse::<Subnet>().is_err());
+        assert!("::/129".parse::<Subnet>().is_err());
+        assert!("example.com".parse::<Subnet>().is_err());
+        assert!("10.0.0.0/x".parse::<Subnet>().is_err());
+    }
+}
@@ -2,31 +2,96 @@
 //!
 //! Provides TCP-based transport for Bitcoin P2P protocol compatibility.
 
+use crate::network::socks5::{Socks5Proxy, Socks5Target};
 use crate::network::transport::{
     Transport, TransportAddr, TransportConnection, TransportListener, TransportType,
 };
 use anyhow::Result;
-use std::net::SocketAddr;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener as TokioTcpListener, TcpStream};
 use tracing::{debug, error};
 
+/// Prefix of the addresses standing in for host-name peers (the OnionCat
+/// range, fd87:d87e:eb43::/48)
+const HOST_ADDR_PREFIX: [u8; 6] = [0xfd, 0x87, 0xd8, 0x7e, 0xeb, 0x43];
+
+/// Address a peer reached by host name is known by
+///
+/// Peers are keyed by socket address, but a proxied host name (an onion
+/// service in particular) has no IP address we may look up. An IP literal
+/// stands for itself; any other host maps to a stable address in the
+/// OnionCat range derived from the lowercased host name, so every connection
+/// to the same target gets the same key.
+pub fn host_peer_addr(host: &str, port: u16) -> SocketAddr {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return SocketAddr::new(ip, port);
+    }
+    let digest = Sha256::digest(host.to_ascii_lowercase().as_bytes());
+    let mut octets = [0u8; 16];
+    octets[..6].copy_from_slice(&HOST_ADDR_PREFIX);
+    octets[6..].copy_from_slice(&digest[..10]);
+    SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port)
+}
+
 /// TCP transport implementation
 ///
 /// Implements the Transport trait for traditional TCP connections,
-/// providing Bitcoin P2P protocol compatibility.
-#[derive(Debug, Clone)]
-pub struct TcpTransport;
+/// providing Bitcoin P2P protocol compatibility. Outbound connections are
+/// routed through a SOCKS5 proxy (e.g. Tor) when one is configured.
+#[derive(Debug, Clone, Default)]
+pub struct TcpTransport {
+    proxy: Option<Socks5Proxy>,
+}
 
 impl TcpTransport {
     pub fn new() -> Self {
-        Self
+        Self { proxy: None }
+    }
+
+    /// Route outbound connections through a SOCKS5 proxy
+    pub fn with_proxy(proxy: Socks5Proxy) -> Self {
+        Self { proxy: Some(proxy) }
     }
-}
 
-impl Default for TcpTransport {
-    fn default() -> Self {
-        Self::new()
+    /// Get the configured outbound proxy
+    pub fn proxy(&self) -> Option<&Socks5Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// Connect to a peer by host name
+    ///
+    /// `.onion` hosts require a proxy and are always resolved by it, never by
+    /// local DNS. Other host names are resolved by the proxy when one is set.
+    ///
+    /// Through a proxy the connection's peer address is the requested target
+    /// (see [`host_peer_addr`]), not the proxy's; without one it is the
+    /// address the host resolved to.
+    pub async fn connect_host(&self, host: &str, port: u16) -> Result<TcpConnection> {
+        let target = Socks5Target::Domain(host.to_string(), port);
+        let Some(ref proxy) = self.proxy else {
+            if target.is_onion() {
+                return Err(anyhow::anyhow!(
+                    "Cannot connect to onion address {} without a proxy",
+                    host
+                ));
+            }
+            let stream = TcpStream::connect((host, port)).await?;
+            let peer_addr = stream.peer_addr()?;
+            return Ok(TcpConnection {
+                stream,
+                peer_addr: TransportAddr::Tcp(peer_addr),
+                connected: true,
+            });
+        };
+
+        let stream = proxy.connect(&target).await?;
+        Ok(TcpConnection {
+            stream,
+            peer_addr: TransportAddr::Tcp(host_peer_addr(host, port)),
+            connected: true,
+        })
     }
 }
 
@@ -53,8 +118,18 @@ impl Transport for TcpTransport {
             ));
         };
 
-        let stream = TcpStream::connect(socket_addr).await?;
-        let peer_addr = stream.peer_addr()?;
+        let (stream, peer_addr) = match self.proxy {
+            // The stream's peer is the proxy, so report the requested target instead
+            Some(ref proxy) => (
+                proxy.connect(&Socks5Target::Ip(socket_addr)).await?,
+                socket_addr,
+            ),
+            None => {
+                let stream = TcpStream::connect(socket_addr).await?;
+                let peer_addr = stream.peer_addr()?;
+                (stream, peer_addr)
+            }
+        };
 
         Ok(TcpConnection {
             stream,
@@ -148,7 +223,9 @@ impl TransportConnection for TcpConnection {
         }
 
         // Validate message size before allocation (DoS protection)
-        use crate::network::protocol::MAX_PROTOCOL_MESSAGE_LENGTH;
+        use crate::network::protocol::{
+            ProtocolError, ProtocolParser, MAX_PROTOCOL_MESSAGE_LENGTH, MESSAGE_HEADER_SIZE,
+        };
         if len > MAX_PROTOCOL_MESSAGE_LENGTH {
             return Err(anyhow::anyhow!(
                 "Message too large: {} bytes (max: {} bytes)",
@@ -157,18 +234,23 @@ impl TransportConnection for TcpConnection {
             ));
         }
 
-        // Read data
-        let mut buffer = vec![0u8; len];
-        let bytes_read = self.stream.read_exact(&mut buffer).await?;
-
-        if bytes_read != len {
-            return Err(anyhow::anyhow!(
-                "Incomplete read: expected {} bytes, got {}",
-                len,
-                bytes_read
-            ));
+        // Read the protocol message header first so per-command limits (lower
+        // for non-block messages) are enforced before buffering the payload
+        let header_len = len.min(MESSAGE_HEADER_SIZE);
+        let mut buffer = vec![0u8; header_len];
+        self.stream.read_exact(&mut buffer).await?;
+        if header_len == MESSAGE_HEADER_SIZE {
+            if let Err(e @ ProtocolError::TooLarge { .. }) =
+                ProtocolParser::check_header(&buffer, len)
+            {
+                return Err(e.into());
+            }
         }
 
+        // Read the rest of the data
+        buffer.resize(len, 0);
+        self.stream.read_exact(&mut buffer[header_len..]).await?;
+
         Ok(buffer)
     }
 
@@ -227,4 +309,77 @@ mod tests {
             assert!(result.is_err());
         }
     }
+
+    /// Connected (client, server) pair over loopback
+    async fn connection_pair() -> (TcpStream, TcpConnection) {
+        let transport = TcpTransport::new();
+        let mut listener = transport
+            .listen("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move { TcpStream::connect(addr).await.unwrap() });
+        let (server, _) = listener.accept().await.unwrap();
+        (client.await.unwrap(), server)
+    }
+
+    #[tokio::test]
+    async fn test_recv_rejects_over_length_frames() {
+        use crate::network::protocol::MAX_PROTOCOL_MESSAGE_LENGTH;
+
+        // Length prefix above the hard cap: rejected without reading further
+        let (mut client, mut server) = connection_pair().await;
+        client
+            .write_u32(MAX_PROTOCOL_MESSAGE_LENGTH as u32 + 1)
+            .await
+            .unwrap();
+        assert!(server.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recv_rejects_oversized_non_block_message_before_payload() {
+        use crate::network::protocol::{ProtocolError, MAX_NON_BLOCK_MESSAGE_LENGTH};
+
+        let (mut client, mut server) = connection_pair().await;
+        // A "ping" frame claiming 5MB; only the header is ever sent
+        let len = MAX_NON_BLOCK_MESSAGE_LENGTH + 1024;
+        let mut header = 0xd9b4bef9u32.to_le_bytes().to_vec();
+        header.extend_from_slice(b"ping\0\0\0\0\0\0\0\0");
+        header.extend_from_slice(&((len - 24) as u32).to_le_bytes());
+        header.extend_from_slice(&[0u8; 4]);
+        client.write_u32(len as u32).await.unwrap();
+        client.write_all(&header).await.unwrap();
+
+        let err = server.recv().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ProtocolError>(),
+            Some(ProtocolError::TooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_host_peer_addr_is_stable_per_target() {
+        let onion = "expyuzz4wqqyqhjn.onion";
+        let addr = host_peer_addr(onion, 8333);
+        assert_eq!(addr, host_peer_addr(&onion.to_uppercase(), 8333));
+        assert_ne!(addr, host_peer_addr("2bqghnldu6mcug4p.onion", 8333));
+        assert_eq!(host_peer_addr(onion, 18333).ip(), addr.ip());
+        match addr.ip() {
+            IpAddr::V6(ip) => assert_eq!(ip.octets()[..6], HOST_ADDR_PREFIX),
+            IpAddr::V4(_) => panic!("host names map into the OnionCat range"),
+        }
+        assert_eq!(
+            host_peer_addr("203.0.113.5", 8333),
+            "203.0.113.5:8333".parse().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv_truncated_frame_errors() {
+        let (mut client, mut server) = connection_pair().await;
+        client.write_u32(100).await.unwrap();
+        client.write_all(&[0u8; 10]).await.unwrap();
+        drop(client);
+        assert!(server.recv().await.is_err());
+    }
 }
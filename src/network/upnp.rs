@@ -0,0 +1,326 @@
+//! UPnP port mapping
+//!
+//! Finds the Internet Gateway Device with an SSDP search, maps the P2P
+//! listening port on it and asks for its external IP address, which becomes
+//! a local address candidate (see `local_address`).
+
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// SSDP multicast address
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+
+/// How long to wait for a gateway to answer the SSDP search
+pub const UPNP_DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Lease requested for port mappings (seconds)
+pub const UPNP_LEASE_SECONDS: u32 = 3600;
+
+/// Interval between port mapping renewals
+pub const UPNP_REFRESH_INTERVAL: Duration = Duration::from_secs(20 * 60);
+
+/// Largest HTTP response accepted from a gateway
+const MAX_RESPONSE_SIZE: u64 = 256 * 1024;
+
+/// WAN connection services able to map ports
+const WAN_SERVICE_TYPES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// A UPnP gateway's WAN connection service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gateway {
+    /// Gateway HTTP address
+    pub addr: SocketAddr,
+    /// Path of the service's control URL
+    pub control_path: String,
+    /// Service type used in SOAP actions
+    pub service_type: String,
+}
+
+impl Gateway {
+    /// Find the gateway on the local network
+    pub async fn discover(timeout: Duration) -> Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDR}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n"
+        );
+        socket.send_to(search.as_bytes(), SSDP_ADDR).await?;
+
+        let mut buf = [0u8; 2048];
+        let (len, _) = tokio::time::timeout(timeout, socket.recv_from(&mut buf))
+            .await
+            .map_err(|_| anyhow!("No UPnP gateway answered"))??;
+        let response = String::from_utf8_lossy(&buf[..len]);
+        let location = header_value(&response, "location")
+            .ok_or_else(|| anyhow!("SSDP response without a location"))?;
+        Self::from_location(location).await
+    }
+
+    /// Read the gateway's device description at `location`
+    pub async fn from_location(location: &str) -> Result<Self> {
+        let (addr, path) = parse_http_url(location)?;
+        let description = http_request(addr, "GET", &path, &[], "").await?;
+        let (service_type, control_path) = find_wan_service(&description)
+            .ok_or_else(|| anyhow!("Gateway has no WAN connection service"))?;
+        Ok(Self {
+            addr,
+            control_path,
+            service_type,
+        })
+    }
+
+    /// External IP address of the gateway
+    pub async fn external_ip(&self) -> Result<IpAddr> {
+        let response = self.soap_action("GetExternalIPAddress", "").await?;
+        xml_text(&response, "NewExternalIPAddress")
+            .ok_or_else(|| anyhow!("Gateway did not report an external IP"))?
+            .parse()
+            .map_err(|e| anyhow!("Invalid external IP from gateway: {}", e))
+    }
+
+    /// Map TCP `port` on the gateway to `local_addr`
+    pub async fn add_port_mapping(
+        &self,
+        port: u16,
+        local_addr: SocketAddr,
+        lease_seconds: u32,
+    ) -> Result<()> {
+        let args = format!(
+            "<NewRemoteHost></NewRemoteHost>\
+             <NewExternalPort>{port}</NewExternalPort>\
+             <NewProtocol>TCP</NewProtocol>\
+             <NewInternalPort>{}</NewInternalPort>\
+             <NewInternalClient>{}</NewInternalClient>\
+             <NewEnabled>1</NewEnabled>\
+             <NewPortMappingDescription>bllvm-node</NewPortMappingDescription>\
+             <NewLeaseDuration>{lease_seconds}</NewLeaseDuration>",
+            local_addr.port(),
+            local_addr.ip()
+        );
+        self.soap_action("AddPortMapping", &args).await?;
+        Ok(())
+    }
+
+    /// Local address used to reach the gateway
+    pub async fn local_ip(&self) -> Result<IpAddr> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(self.addr).await?;
+        Ok(socket.local_addr()?.ip())
+    }
+
+    async fn soap_action(&self, action: &str, args: &str) -> Result<String> {
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{}\">{args}</u:{action}></s:Body></s:Envelope>",
+            self.service_type
+        );
+        let soap_action = format!("\"{}#{action}\"", self.service_type);
+        http_request(
+            self.addr,
+            "POST",
+            &self.control_path,
+            &[
+                ("Content-Type", "text/xml; charset=\"utf-8\""),
+                ("SOAPAction", &soap_action),
+            ],
+            &body,
+        )
+        .await
+    }
+}
+
+/// Send an HTTP/1.1 request and return the body of a 200 response
+async fn http_request(
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Result<String> {
+    let mut request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream
+            .take(MAX_RESPONSE_SIZE)
+            .read_to_end(&mut response)
+            .await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = tokio::time::timeout(UPNP_DISCOVERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow!("Gateway at {} timed out", addr))??;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed HTTP response from gateway"))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(anyhow!("Gateway returned {}", status));
+    }
+    Ok(body.to_string())
+}
+
+/// Split `http://host:port/path` into the socket address and path
+fn parse_http_url(url: &str) -> Result<(SocketAddr, String)> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| anyhow!("Unsupported gateway URL: {}", url))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let addr = match host.parse::<SocketAddr>() {
+        Ok(addr) => addr,
+        Err(_) => SocketAddr::new(
+            host.parse()
+                .map_err(|_| anyhow!("Unsupported gateway host: {}", host))?,
+            80,
+        ),
+    };
+    Ok((addr, path.to_string()))
+}
+
+/// Value of an HTTP header (case-insensitive name)
+fn header_value<'a>(response: &'a str, name: &str) -> Option<&'a str> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Text of the first `<tag>` element (namespace prefixes ignored)
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("{tag}>");
+    let start = xml
+        .match_indices(&open)
+        .find(|(index, _)| {
+            let before = &xml[..*index];
+            before.ends_with('<') || before.rsplit('<').next().is_some_and(|p| p.ends_with(':'))
+        })
+        .map(|(index, _)| index + open.len())?;
+    let end = start + xml[start..].find("</")?;
+    Some(xml[start..end].trim())
+}
+
+/// Service type and control path of the first WAN connection service
+fn find_wan_service(description: &str) -> Option<(String, String)> {
+    description.split("<service>").skip(1).find_map(|service| {
+        let service_type = xml_text(service, "serviceType")?;
+        if !WAN_SERVICE_TYPES.contains(&service_type) {
+            return None;
+        }
+        let control_url = xml_text(service, "controlURL")?;
+        let control_path = match control_url.strip_prefix("http://") {
+            Some(rest) => rest.find('/').map_or("/", |index| &rest[index..]),
+            None => control_url,
+        };
+        let control_path = if control_path.starts_with('/') {
+            control_path.to_string()
+        } else {
+            format!("/{control_path}")
+        };
+        Some((service_type.to_string(), control_path))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><serviceList>\
+        <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+        <controlURL>/l3f</controlURL></service>\
+        <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+        <controlURL>/ctl/IPConn</controlURL></service>\
+        </serviceList></device></root>";
+
+    #[test]
+    fn test_parse_gateway_responses() {
+        let ssdp = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nLocation: http://192.168.1.1:5000/rootDesc.xml\r\n\r\n";
+        assert_eq!(
+            header_value(ssdp, "location"),
+            Some("http://192.168.1.1:5000/rootDesc.xml")
+        );
+        assert_eq!(
+            parse_http_url("http://192.168.1.1:5000/rootDesc.xml").unwrap(),
+            (
+                "192.168.1.1:5000".parse().unwrap(),
+                "/rootDesc.xml".to_string()
+            )
+        );
+        assert_eq!(
+            find_wan_service(DESCRIPTION),
+            Some((WAN_SERVICE_TYPES[0].to_string(), "/ctl/IPConn".to_string()))
+        );
+        let reply = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+            <NewExternalIPAddress>1.2.3.4</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(xml_text(reply, "NewExternalIPAddress"), Some("1.2.3.4"));
+    }
+
+    #[tokio::test]
+    async fn test_gateway_maps_port_and_reports_external_ip() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 8192];
+                let len = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..len]).to_string();
+                let body = if request.starts_with("GET") {
+                    DESCRIPTION.to_string()
+                } else if request.contains("GetExternalIPAddress") {
+                    "<NewExternalIPAddress>1.2.3.4</NewExternalIPAddress>".to_string()
+                } else {
+                    String::new()
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+
+        let gateway = Gateway::from_location(&format!("http://{addr}/rootDesc.xml"))
+            .await
+            .unwrap();
+        assert_eq!(gateway.control_path, "/ctl/IPConn");
+        gateway
+            .add_port_mapping(8333, "192.168.1.20:8333".parse().unwrap(), 3600)
+            .await
+            .unwrap();
+        assert_eq!(
+            gateway.external_ip().await.unwrap(),
+            "1.2.3.4".parse::<IpAddr>().unwrap()
+        );
+
+        let requests = server.await.unwrap();
+        assert!(requests[1].starts_with("POST /ctl/IPConn HTTP/1.1"));
+        assert!(requests[1].contains("WANIPConnection:1#AddPortMapping"));
+        assert!(requests[1].contains("<NewInternalClient>192.168.1.20</NewInternalClient>"));
+    }
+}
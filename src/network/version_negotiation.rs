@@ -0,0 +1,176 @@
+//! Protocol version negotiation
+//!
+//! Tracks the version a peer advertised in its `version` message, enforces a
+//! minimum peer protocol version, and gates optional feature messages on the
+//! negotiated version (the lower of ours and the peer's).
+
+use anyhow::{anyhow, Result};
+
+/// Protocol version we advertise
+pub const PROTOCOL_VERSION: i32 = 70016;
+
+/// Default minimum protocol version accepted from peers
+pub const DEFAULT_MIN_PEER_PROTOCOL_VERSION: i32 = 70001;
+
+/// Optional protocol features negotiated by version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// `sendheaders` (BIP130)
+    SendHeaders,
+    /// `feefilter` (BIP133)
+    FeeFilter,
+    /// `sendcmpct` and compact blocks (BIP152)
+    CompactBlocks,
+    /// `sendaddrv2` (BIP155)
+    AddrV2,
+}
+
+impl Feature {
+    /// Lowest negotiated version that supports the feature
+    pub fn min_version(self) -> i32 {
+        match self {
+            Feature::SendHeaders => 70012,
+            Feature::FeeFilter => 70013,
+            Feature::CompactBlocks => 70014,
+            Feature::AddrV2 => 70016,
+        }
+    }
+
+    /// Message command announcing the feature
+    pub fn command(self) -> &'static str {
+        match self {
+            Feature::SendHeaders => "sendheaders",
+            Feature::FeeFilter => "feefilter",
+            Feature::CompactBlocks => "sendcmpct",
+            Feature::AddrV2 => "sendaddrv2",
+        }
+    }
+
+    /// Whether the announcement belongs between `version` and `verack`
+    ///
+    /// BIP155 requires `sendaddrv2` before `verack` (Bitcoin Core disconnects
+    /// peers that send it later); everything else waits for the handshake.
+    pub fn sent_before_verack(self) -> bool {
+        matches!(self, Feature::AddrV2)
+    }
+}
+
+/// Per-peer handshake and version state
+#[derive(Debug, Clone, Default)]
+pub struct VersionNegotiation {
+    /// Version advertised by the peer
+    peer_version: Option<i32>,
+    /// Services advertised by the peer
+    peer_services: u64,
+    /// Whether the peer has sent `verack`
+    verack_received: bool,
+}
+
+impl VersionNegotiation {
+    /// Record the peer's `version` message
+    pub fn record_version(&mut self, version: i32, services: u64) {
+        self.peer_version = Some(version);
+        self.peer_services = services;
+    }
+
+    /// Record the peer's `verack`
+    pub fn record_verack(&mut self) {
+        self.verack_received = true;
+    }
+
+    /// Version advertised by the peer
+    pub fn peer_version(&self) -> Option<i32> {
+        self.peer_version
+    }
+
+    /// Services advertised by the peer
+    pub fn peer_services(&self) -> u64 {
+        self.peer_services
+    }
+
+    /// Negotiated version: the lower of ours and the peer's
+    pub fn negotiated_version(&self) -> Option<i32> {
+        self.peer_version.map(|v| v.min(PROTOCOL_VERSION))
+    }
+
+    /// Whether both `version` and `verack` have been received
+    pub fn handshake_complete(&self) -> bool {
+        self.peer_version.is_some() && self.verack_received
+    }
+
+    /// Whether the feature is supported by the negotiated version
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.negotiated_version()
+            .is_some_and(|v| v >= feature.min_version())
+    }
+
+    /// Whether we may send the feature's announcement now
+    pub fn can_send(&self, feature: Feature) -> bool {
+        if !self.supports(feature) {
+            return false;
+        }
+        if feature.sent_before_verack() {
+            !self.verack_received
+        } else {
+            self.verack_received
+        }
+    }
+}
+
+/// Reject peers advertising a version below `min_version`
+pub fn check_min_version(version: i32, min_version: i32) -> Result<()> {
+    if version < min_version {
+        return Err(anyhow!(
+            "peer protocol version {} is below minimum {}",
+            version,
+            min_version
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features_wait_for_verack() {
+        let mut state = VersionNegotiation::default();
+        assert!(!state.can_send(Feature::SendHeaders));
+
+        state.record_version(PROTOCOL_VERSION, 1);
+        assert!(!state.can_send(Feature::SendHeaders));
+        assert!(!state.can_send(Feature::CompactBlocks));
+        assert!(state.can_send(Feature::AddrV2));
+
+        state.record_verack();
+        assert!(state.handshake_complete());
+        assert!(state.can_send(Feature::SendHeaders));
+        assert!(state.can_send(Feature::FeeFilter));
+        assert!(state.can_send(Feature::CompactBlocks));
+        assert!(!state.can_send(Feature::AddrV2));
+    }
+
+    #[test]
+    fn test_features_gated_on_negotiated_version() {
+        let mut state = VersionNegotiation::default();
+        state.record_version(70012, 1);
+        state.record_verack();
+
+        assert_eq!(state.negotiated_version(), Some(70012));
+        assert!(state.can_send(Feature::SendHeaders));
+        assert!(!state.can_send(Feature::FeeFilter));
+        assert!(!state.can_send(Feature::CompactBlocks));
+
+        // A newer peer negotiates down to our version
+        state.record_version(80000, 1);
+        assert_eq!(state.negotiated_version(), Some(PROTOCOL_VERSION));
+    }
+
+    #[test]
+    fn test_check_min_version() {
+        assert!(check_min_version(70016, DEFAULT_MIN_PEER_PROTOCOL_VERSION).is_ok());
+        assert!(check_min_version(70001, DEFAULT_MIN_PEER_PROTOCOL_VERSION).is_ok());
+        assert!(check_min_version(209, DEFAULT_MIN_PEER_PROTOCOL_VERSION).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Assumed-valid block (`assumevalid`)
+//!
+//! Blocks that are ancestors of a known-good block skip script and signature
+//! verification during sync. Proof of work, merkle roots and UTXO bookkeeping
+//! are still checked for every block; only the script interpreter is bypassed.
+
+use crate::storage::blockstore::BlockStore;
+use anyhow::Result;
+use bllvm_protocol::{Hash, ProtocolVersion};
+
+/// Mainnet assumed-valid block (height 824000)
+pub const MAINNET_ASSUME_VALID: &str =
+    "000000000000000000035c3f0d31e71a5ee24c5aaf3354689f65bd7b07dee632";
+
+/// Testnet3 assumed-valid block (height 2500000)
+pub const TESTNET_ASSUME_VALID: &str =
+    "0000000000000093bcb68c03a9a168ae252572d348a2eaeba2cdf9231d73206f";
+
+/// Built-in assumed-valid block for a network (none on regtest)
+pub fn default_assume_valid(version: ProtocolVersion) -> Option<Hash> {
+    let hash = match version {
+        ProtocolVersion::BitcoinV1 => MAINNET_ASSUME_VALID,
+        ProtocolVersion::Testnet3 => TESTNET_ASSUME_VALID,
+        _ => return None,
+    };
+    parse_assume_valid(hash).ok().flatten()
+}
+
+/// Parse an `assumevalid` setting
+///
+/// The hash is given in display (big-endian) order like block explorers show
+/// it. `"0"` disables the optimisation and verifies every script.
+pub fn parse_assume_valid(value: &str) -> Result<Option<Hash>> {
+    let value = value.trim();
+    if value == "0" {
+        return Ok(None);
+    }
+    let bytes = hex::decode(value)
+        .map_err(|e| anyhow::anyhow!("Invalid assumevalid hash {}: {}", value, e))?;
+    let mut hash: Hash = bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Invalid assumevalid hash {}: expected 32 bytes", value))?;
+    hash.reverse();
+    Ok(Some(hash))
+}
+
+/// Tracks which blocks are covered by the assumed-valid block
+#[derive(Debug, Clone)]
+pub struct AssumeValid {
+    hash: Hash,
+    /// Height of the first unconnected ancestor and the ancestor hashes from
+    /// there up to (and including) the assumed-valid block. Resolved lazily
+    /// once its header is known.
+    ancestors: Option<(u64, Vec<Hash>)>,
+}
+
+impl AssumeValid {
+    /// Create a tracker for the given assumed-valid block
+    pub fn new(hash: Hash) -> Self {
+        Self {
+            hash,
+            ancestors: None,
+        }
+    }
+
+    /// Assumed-valid block hash
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    /// Whether `block_hash` at `height` is the assumed-valid block or one of
+    /// its ancestors
+    ///
+    /// Returns false until the assumed-valid header has been downloaded, so
+    /// blocks are fully verified whenever coverage cannot be established.
+    pub fn covers(&mut self, blockstore: &BlockStore, block_hash: &Hash, height: u64) -> bool {
+        if self.ancestors.is_none() {
+            self.ancestors = self.resolve(blockstore);
+        }
+        match &self.ancestors {
+            Some((base, hashes)) => {
+                height >= *base && hashes.get((height - base) as usize) == Some(block_hash)
+            }
+            None => false,
+        }
+    }
+
+    /// Walk headers back from the assumed-valid block to the first one whose
+    /// parent is already connected
+    fn resolve(&self, blockstore: &BlockStore) -> Option<(u64, Vec<Hash>)> {
+        if let Some(height) = blockstore.get_height_by_hash(&self.hash).ok()? {
+            return Some((height + 1, Vec::new()));
+        }
+        let mut hashes = vec![self.hash];
+        let mut header = blockstore.get_header(&self.hash).ok()??;
+        loop {
+            let prev = header.prev_block_hash;
+            if let Some(height) = blockstore.get_height_by_hash(&prev).ok()? {
+                hashes.reverse();
+                return Some((height + 1, hashes));
+            }
+            if prev == [0u8; 32] {
+                hashes.reverse();
+                return Some((0, hashes));
+            }
+            header = blockstore.get_header(&prev).ok()??;
+            hashes.push(prev);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assume_valid() {
+        assert_eq!(parse_assume_valid("0").unwrap(), None);
+        let hash = parse_assume_valid(MAINNET_ASSUME_VALID).unwrap().unwrap();
+        assert_eq!(hash[31], 0);
+        assert_eq!(hash[0], 0x32);
+        assert!(parse_assume_valid("abcd").is_err());
+        assert!(parse_assume_valid("not hex").is_err());
+        assert!(default_assume_valid(ProtocolVersion::BitcoinV1).is_some());
+        assert!(default_assume_valid(ProtocolVersion::Regtest).is_none());
+    }
+}
@@ -3,23 +3,192 @@
 //! Handles parsing blocks from wire format, storing witnesses, and validating
 //! blocks with proper witness data and median time-past.
 
+use crate::node::signet::check_block_solution;
 use crate::storage::blockstore::BlockStore;
+use crate::storage::Storage;
+use crate::utils::current_timestamp;
+use crate::validation::no_scripts::{connect_block_without_scripts, ChainContext, ConsensusParams};
+use crate::validation::witness::{
+    consensus_witnesses, decode_varint, encode_varint, transaction_witnesses,
+};
 use anyhow::Result;
 use bllvm_protocol::block::connect_block;
-use bllvm_protocol::serialization::deserialize_block_with_witnesses;
-use bllvm_protocol::{segwit::Witness, Block, BlockHeader, UtxoSet, ValidationResult};
+use bllvm_protocol::serialization::{deserialize_block_with_witnesses, serialize_block_header};
+use bllvm_protocol::{
+    segwit::Witness, Block, BlockHeader, Hash, OutPoint, Transaction, UtxoSet, ValidationResult,
+};
+
+pub use crate::validation::witness::{
+    calculate_wtxid, serialize_transaction_with_witness, transaction_vsize, transaction_weight,
+};
 
 /// Parse a block from Bitcoin wire format and extract witness data
-pub fn parse_block_from_wire(data: &[u8]) -> Result<(Block, Vec<Witness>)> {
-    deserialize_block_with_witnesses(data)
-        .map_err(|e| anyhow::anyhow!("Failed to parse block from wire format: {}", e))
+///
+/// The witnesses hold, for each transaction, one stack per input.
+pub fn parse_block_from_wire(data: &[u8]) -> Result<(Block, Vec<Vec<Witness>>)> {
+    let (block, _) = deserialize_block_with_witnesses(data)
+        .map_err(|e| anyhow::anyhow!("Failed to parse block from wire format: {}", e))?;
+    let mut reader = WireReader { data, pos: 80 };
+    let tx_count = reader.varint()?;
+    let mut witnesses = Vec::with_capacity(block.transactions.len());
+    for _ in 0..tx_count {
+        witnesses.push(read_transaction(&mut reader)?.witnesses);
+    }
+    Ok((block, witnesses))
+}
+
+/// Parse an 80-byte block header from Bitcoin wire format
+pub fn parse_header_from_wire(data: &[u8]) -> Result<BlockHeader> {
+    if data.len() != 80 {
+        return Err(anyhow::anyhow!(
+            "Block header must be 80 bytes, got {}",
+            data.len()
+        ));
+    }
+    let u32_at = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+    let mut prev_block_hash = [0u8; 32];
+    prev_block_hash.copy_from_slice(&data[4..36]);
+    let mut merkle_root = [0u8; 32];
+    merkle_root.copy_from_slice(&data[36..68]);
+    Ok(BlockHeader {
+        version: u32_at(0) as i32 as _,
+        prev_block_hash,
+        merkle_root,
+        timestamp: u32_at(68) as _,
+        bits: u32_at(72) as _,
+        nonce: u32_at(76) as _,
+    })
+}
+
+/// Serialize a block in wire format, with witness data (BIP 144)
+///
+/// `witnesses[i]` holds the per-input stacks of transaction `i`.
+pub fn serialize_block(block: &Block, witnesses: &[Vec<Witness>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&serialize_block_header(&block.header));
+    data.extend_from_slice(&encode_varint(block.transactions.len() as u64));
+    for (index, tx) in block.transactions.iter().enumerate() {
+        data.extend_from_slice(&serialize_transaction_with_witness(
+            tx,
+            transaction_witnesses(witnesses, index),
+        ));
+    }
+    data
+}
+
+/// Split a wire-format transaction into its witness-stripped serialization
+/// and its per-input witness stacks
+///
+/// Transactions without the BIP 144 marker are returned unchanged with no
+/// witnesses.
+pub fn split_transaction_witness(data: &[u8]) -> Result<(Vec<u8>, Vec<Witness>)> {
+    if data.len() < 6 || data[4] != 0x00 || data[5] != 0x01 {
+        return Ok((data.to_vec(), Vec::new()));
+    }
+    let mut reader = WireReader { data, pos: 0 };
+    let layout = read_transaction(&mut reader)?;
+    if reader.pos != data.len() {
+        return Err(anyhow::anyhow!("Trailing data after transaction"));
+    }
+    let body_end = layout.witness_start.unwrap_or(data.len() - 4);
+    if layout.witnesses.iter().all(|w| w.is_empty()) {
+        return Err(anyhow::anyhow!("Superfluous witness record"));
+    }
+
+    let mut stripped = data[..4].to_vec();
+    stripped.extend_from_slice(&data[6..body_end]);
+    stripped.extend_from_slice(&data[data.len() - 4..]);
+    Ok((stripped, layout.witnesses))
+}
+
+/// Where a wire-format transaction keeps its witness data
+struct TransactionLayout {
+    /// Offset of the witness section, if the transaction uses the BIP 144
+    /// format
+    witness_start: Option<usize>,
+    /// Per-input witness stacks (empty without witness data)
+    witnesses: Vec<Witness>,
+}
+
+/// Read one wire-format transaction, leaving `reader` just past it
+fn read_transaction(reader: &mut WireReader<'_>) -> Result<TransactionLayout> {
+    let start = reader.pos;
+    reader.skip(4)?;
+    let segwit = reader.data.get(start + 4..start + 6) == Some(&[0x00, 0x01][..]);
+    if segwit {
+        reader.skip(2)?;
+    }
+    let input_count = reader.varint()?;
+    for _ in 0..input_count {
+        reader.skip(36)?;
+        let script_len = reader.varint()?;
+        reader.skip(script_len)?;
+        reader.skip(4)?;
+    }
+    let output_count = reader.varint()?;
+    for _ in 0..output_count {
+        reader.skip(8)?;
+        let script_len = reader.varint()?;
+        reader.skip(script_len)?;
+    }
+
+    let mut layout = TransactionLayout {
+        witness_start: None,
+        witnesses: Vec::new(),
+    };
+    if segwit {
+        layout.witness_start = Some(reader.pos);
+        for _ in 0..input_count {
+            let item_count = reader.varint()?;
+            let mut stack = Vec::new();
+            for _ in 0..item_count {
+                let item_len = reader.varint()?;
+                let item_start = reader.pos;
+                reader.skip(item_len)?;
+                stack.push(reader.data[item_start..reader.pos].to_vec());
+            }
+            layout.witnesses.push(stack);
+        }
+    }
+    reader.skip(4)?;
+    Ok(layout)
+}
+
+/// Cursor over wire-format bytes
+struct WireReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl WireReader<'_> {
+    fn skip(&mut self, len: u64) -> Result<()> {
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.pos.checked_add(len))
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of transaction data"))?;
+        self.pos = end;
+        Ok(())
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        decode_varint(self.data, &mut self.pos)
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of transaction data"))
+    }
 }
 
 /// Store a block with its witnesses and update recent headers
 pub fn store_block_with_context(
     blockstore: &BlockStore,
     block: &Block,
-    witnesses: &[Witness],
+    witnesses: &[Vec<Witness>],
     height: u64,
 ) -> Result<()> {
     // Store block
@@ -46,7 +215,7 @@ pub fn prepare_block_validation_context(
     blockstore: &BlockStore,
     block: &Block,
     _current_height: u64,
-) -> Result<(Vec<Witness>, Option<Vec<BlockHeader>>)> {
+) -> Result<(Vec<Vec<Witness>>, Option<Vec<BlockHeader>>)> {
     // Get witnesses for this block
     let block_hash = blockstore.get_block_hash(block);
     let witnesses = blockstore
@@ -66,7 +235,7 @@ pub fn prepare_block_validation_context(
 pub fn validate_block_with_context(
     blockstore: &BlockStore,
     block: &Block,
-    witnesses: &[Witness],
+    witnesses: &[Vec<Witness>],
     utxo_set: &mut UtxoSet,
     height: u64,
 ) -> Result<ValidationResult> {
@@ -79,7 +248,7 @@ pub fn validate_block_with_context(
     // Validate block
     let (result, new_utxo_set) = connect_block(
         block,
-        witnesses,
+        &consensus_witnesses(witnesses),
         utxo_set.clone(),
         height,
         recent_headers.as_deref(),
@@ -92,3 +261,529 @@ pub fn validate_block_with_context(
 
     Ok(result)
 }
+
+/// Rebuild the chainstate from stored blocks (`reindexchainstate`)
+///
+/// Clears the UTXO set, undo data, block filters, coin statistics, transaction
+/// index and chain state, then validates and connects every block of the
+/// active chain again from genesis. This also builds an enabled
+/// coinstatsindex. Refuses to start if any block body has been pruned, since
+/// the chainstate could not be rebuilt. `progress` is called with each
+/// connected height and the final height. Returns the final height.
+pub fn reindex_chainstate(storage: &Storage, mut progress: impl FnMut(u64, u64)) -> Result<u64> {
+    let _chainstate = storage.lock_chainstate();
+    let blockstore = storage.blocks();
+    if let Some(height) = storage
+        .pruning()
+        .and_then(|pruning| pruning.get_stats().last_prune_height)
+    {
+        return Err(anyhow::anyhow!(
+            "Cannot reindex chainstate: blocks pruned up to height {}",
+            height
+        ));
+    }
+
+    // Check every block body is still there before touching anything
+    let limit = storage.chain().get_height().ok().flatten();
+    let mut hashes = Vec::new();
+    while limit.map_or(true, |limit| hashes.len() as u64 <= limit) {
+        let height = hashes.len() as u64;
+        let Some(hash) = blockstore.get_hash_by_height(height)? else {
+            break;
+        };
+        if !blockstore.has_block(&hash)? {
+            return Err(anyhow::anyhow!(
+                "Cannot reindex chainstate: block {} at height {} has been pruned",
+                hex::encode(hash),
+                height
+            ));
+        }
+        hashes.push(hash);
+    }
+    let Some(tip_height) = (hashes.len() as u64).checked_sub(1) else {
+        return Err(anyhow::anyhow!(
+            "Cannot reindex chainstate: no stored blocks"
+        ));
+    };
+
+    storage.clear_chainstate()?;
+    let mut utxo_set = UtxoSet::default();
+    for (height, hash) in hashes.iter().enumerate() {
+        let height = height as u64;
+        let block = blockstore
+            .get_block(hash)?
+            .ok_or_else(|| anyhow::anyhow!("Block {} disappeared", hex::encode(hash)))?;
+        let witnesses = blockstore
+            .get_witness(hash)?
+            .unwrap_or_else(|| block.transactions.iter().map(|_| Vec::new()).collect());
+
+        // Median time-past uses the headers before this block, not the tip's
+        let recent_headers = blockstore
+            .get_headers_by_height_range(height.saturating_sub(11), height.saturating_sub(1))?;
+        let recent_headers = if height > 0 {
+            Some(recent_headers)
+        } else {
+            None
+        };
+        let (result, new_utxo_set) = connect_block(
+            &block,
+            &consensus_witnesses(&witnesses),
+            utxo_set,
+            height,
+            recent_headers.as_deref(),
+        )?;
+        if let ValidationResult::Invalid(reason) = result {
+            return Err(anyhow::anyhow!(
+                "Block {} at height {} is invalid: {}",
+                hex::encode(hash),
+                height,
+                reason
+            ));
+        }
+        utxo_set = new_utxo_set;
+
+        storage.connect_block(&block, height)?;
+        for (index, tx) in block.transactions.iter().enumerate() {
+            storage.transactions().index_transaction_with_witness(
+                tx,
+                transaction_witnesses(&witnesses, index),
+                hash,
+                height,
+                index as u32,
+            )?;
+        }
+        if height == 0 {
+            storage.chain().initialize(&block.header)?;
+        } else {
+            storage.chain().update_tip(hash, &block.header, height)?;
+        }
+        progress(height, tip_height);
+    }
+
+    Ok(tip_height)
+}
+
+/// Changes made to the active chain by [`activate_best_chain`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainActivation {
+    /// Blocks disconnected from the previous active chain
+    pub disconnected: u64,
+    /// Blocks connected from the new active chain
+    pub connected: u64,
+    /// Height of the resulting tip
+    pub height: u64,
+    /// Non-coinbase transactions of the disconnected blocks, parents first
+    pub disconnected_transactions: Vec<Transaction>,
+    /// Non-coinbase transactions of the connected blocks
+    pub connected_transactions: Vec<Transaction>,
+}
+
+/// Mark a block and its descendants invalid and fall back to the best
+/// remaining valid chain (`invalidateblock`)
+///
+/// If the block is on the active chain, the chain is disconnected back to
+/// its parent and the old tip is kept as an `invalid` chain tip. The genesis
+/// block cannot be invalidated.
+pub fn invalidate_block(
+    storage: &Storage,
+    hash: &Hash,
+    signet_challenge: Option<&[u8]>,
+) -> Result<ChainActivation> {
+    let _chainstate = storage.lock_chainstate();
+    let blockstore = storage.blocks();
+    let chain = storage.chain();
+    let height = blockstore
+        .get_height_by_hash(hash)?
+        .ok_or_else(|| anyhow::anyhow!("Block {} not found", hex::encode(hash)))?;
+    if height == 0 {
+        return Err(anyhow::anyhow!("Cannot invalidate the genesis block"));
+    }
+    let info = chain
+        .load_chain_info()?
+        .ok_or_else(|| anyhow::anyhow!("Chain state not initialized"))?;
+
+    chain.mark_invalid(hash)?;
+    let mut utxo_set = storage.utxos().load_utxo_set()?;
+    let mut activation = ChainActivation::default();
+    if height <= info.height && blockstore.get_hash_by_height(height)? == Some(*hash) {
+        // Everything above it on the active chain descends from it
+        for descendant_height in height + 1..=info.height {
+            if let Some(descendant) = blockstore.get_hash_by_height(descendant_height)? {
+                chain.mark_invalid(&descendant)?;
+            }
+        }
+        chain.add_chain_tip(
+            &info.tip_hash,
+            info.height,
+            info.height - height + 1,
+            "invalid",
+        )?;
+        disconnect_to_height(storage, height - 1, &mut utxo_set, &mut activation)?;
+    }
+
+    activate_best_chain_locked(storage, utxo_set, activation, signet_challenge)
+}
+
+/// Clear the invalid mark from a block, its ancestors and its descendants,
+/// then switch to the best valid chain (`reconsiderblock`)
+pub fn reconsider_block(
+    storage: &Storage,
+    hash: &Hash,
+    signet_challenge: Option<&[u8]>,
+) -> Result<ChainActivation> {
+    let _chainstate = storage.lock_chainstate();
+    let blockstore = storage.blocks();
+    let chain = storage.chain();
+    if blockstore.get_header(hash)?.is_none() {
+        return Err(anyhow::anyhow!("Block {} not found", hex::encode(hash)));
+    }
+
+    // Ancestors, down to the active chain (which is never invalid)
+    let mut ancestor = *hash;
+    while !is_on_active_chain(storage, &ancestor)? {
+        chain.unmark_invalid(&ancestor)?;
+        match blockstore.get_header(&ancestor)? {
+            Some(header) => ancestor = header.prev_block_hash,
+            None => break,
+        }
+    }
+
+    // Descendants: invalid blocks and invalid tips that chain back to it
+    for invalid in chain.get_invalid_blocks()? {
+        if descends_from(storage, &invalid, hash)? {
+            chain.unmark_invalid(&invalid)?;
+        }
+    }
+    for (tip, height, branchlen, status) in chain.get_chain_tips()? {
+        if status == "invalid" && descends_from(storage, &tip, hash)? {
+            chain.add_chain_tip(&tip, height, branchlen, "valid-fork")?;
+        }
+    }
+
+    let utxo_set = storage.utxos().load_utxo_set()?;
+    activate_best_chain_locked(
+        storage,
+        utxo_set,
+        ChainActivation::default(),
+        signet_challenge,
+    )
+}
+
+/// Prefer a block over other equal-work tips and switch to it (`preciousblock`)
+///
+/// Has no effect if the block has less chainwork than the active tip.
+pub fn precious_block(
+    storage: &Storage,
+    hash: &Hash,
+    signet_challenge: Option<&[u8]>,
+) -> Result<ChainActivation> {
+    let _chainstate = storage.lock_chainstate();
+    let chain = storage.chain();
+    let work = chain
+        .get_chainwork(hash)?
+        .ok_or_else(|| anyhow::anyhow!("Block {} not found", hex::encode(hash)))?;
+    let info = chain
+        .load_chain_info()?
+        .ok_or_else(|| anyhow::anyhow!("Chain state not initialized"))?;
+    if work < chain.get_chainwork(&info.tip_hash)?.unwrap_or(0) {
+        return Ok(ChainActivation {
+            height: info.height,
+            ..Default::default()
+        });
+    }
+    chain.mark_precious(hash)?;
+    let utxo_set = storage.utxos().load_utxo_set()?;
+    activate_best_chain_locked(
+        storage,
+        utxo_set,
+        ChainActivation::default(),
+        signet_challenge,
+    )
+}
+
+/// Switch the active chain to the best valid chain tip
+///
+/// Every known chain tip that beats the active tip (see
+/// [`ChainState::is_better_tip`](crate::storage::chainstate::ChainState::is_better_tip))
+/// is a candidate, provided all its blocks since the fork point are stored and
+/// none is marked invalid. The best candidate's blocks are validated as they
+/// are connected, including their solution to `signet_challenge` on signet;
+/// one that fails is marked invalid and the choice is made again, so the
+/// node may end up back on the chain it started from.
+///
+/// Holds the chainstate lock throughout. The transactions of disconnected
+/// and connected blocks are reported in the result so the caller can update
+/// the mempool.
+pub fn activate_best_chain(
+    storage: &Storage,
+    signet_challenge: Option<&[u8]>,
+) -> Result<ChainActivation> {
+    let _chainstate = storage.lock_chainstate();
+    let utxo_set = storage.utxos().load_utxo_set()?;
+    activate_best_chain_locked(
+        storage,
+        utxo_set,
+        ChainActivation::default(),
+        signet_challenge,
+    )
+}
+
+/// [`activate_best_chain`] with the chainstate lock already held
+///
+/// `utxo_set` must match the stored UTXO set at the active tip; it is kept
+/// up to date across disconnects and connects instead of being reloaded.
+fn activate_best_chain_locked(
+    storage: &Storage,
+    mut utxo_set: UtxoSet,
+    mut activation: ChainActivation,
+    signet_challenge: Option<&[u8]>,
+) -> Result<ChainActivation> {
+    let blockstore = storage.blocks();
+    let chain = storage.chain();
+    'select: loop {
+        let info = chain
+            .load_chain_info()?
+            .ok_or_else(|| anyhow::anyhow!("Chain state not initialized"))?;
+        activation.height = info.height;
+        let tip_work = chain.get_chainwork(&info.tip_hash)?.unwrap_or(0);
+
+        let mut best: Option<(u128, Hash, u64, Vec<(Hash, BlockHeader)>)> = None;
+        for (tip, _, _, _) in chain.get_chain_tips()? {
+            let Some(work) = chain.get_chainwork(&tip)? else {
+                continue;
+            };
+            if !chain.is_better_tip(&tip, work, &info.tip_hash, tip_work)? {
+                continue;
+            }
+            if let Some((best_work, best_tip, ..)) = &best {
+                if !chain.is_better_tip(&tip, work, best_tip, *best_work)? {
+                    continue;
+                }
+            }
+            if let Some((fork_height, path)) = path_from_active_chain(storage, &tip)? {
+                best = Some((work, tip, fork_height, path));
+            }
+        }
+        let Some((_, new_tip, fork_height, path)) = best else {
+            return Ok(activation);
+        };
+
+        if fork_height < info.height {
+            disconnect_to_height(storage, fork_height, &mut utxo_set, &mut activation)?;
+            chain.add_chain_tip(
+                &info.tip_hash,
+                info.height,
+                info.height - fork_height,
+                "valid-fork",
+            )?;
+        }
+
+        for (offset, (hash, header)) in path.iter().enumerate() {
+            let height = fork_height + 1 + offset as u64;
+            let block = blockstore
+                .get_block(hash)?
+                .ok_or_else(|| anyhow::anyhow!("Block {} disappeared", hex::encode(hash)))?;
+            let witnesses = blockstore
+                .get_witness(hash)?
+                .unwrap_or_else(|| block.transactions.iter().map(|_| Vec::new()).collect());
+            let recent_headers =
+                blockstore.get_headers_by_height_range(height.saturating_sub(11), height - 1)?;
+            let signet_result = signet_challenge.map_or(Ok(()), |challenge| {
+                check_block_solution(&block, challenge, height)
+            });
+            let (result, new_utxo_set) = match signet_result {
+                Ok(()) => connect_block(
+                    &block,
+                    &consensus_witnesses(&witnesses),
+                    utxo_set,
+                    height,
+                    Some(recent_headers.as_slice()),
+                )?,
+                Err(reason) => (ValidationResult::Invalid(reason), UtxoSet::default()),
+            };
+            if let ValidationResult::Invalid(reason) = result {
+                tracing::warn!(
+                    "Block {} at height {} is invalid: {}",
+                    hex::encode(hash),
+                    height,
+                    reason
+                );
+                for (invalid, _) in &path[offset..] {
+                    chain.mark_invalid(invalid)?;
+                }
+                chain.add_chain_tip(
+                    &new_tip,
+                    fork_height + path.len() as u64,
+                    path.len() as u64,
+                    "invalid",
+                )?;
+                // The set handed to the failed block is gone; this reload
+                // only happens when a candidate turns out invalid
+                utxo_set = storage.utxos().load_utxo_set()?;
+                continue 'select;
+            }
+            utxo_set = new_utxo_set;
+
+            storage.connect_block(&block, height)?;
+            for (index, tx) in block.transactions.iter().enumerate() {
+                storage.transactions().index_transaction_with_witness(
+                    tx,
+                    transaction_witnesses(&witnesses, index),
+                    hash,
+                    height,
+                    index as u32,
+                )?;
+            }
+            blockstore.store_height(height, hash)?;
+            chain.update_tip(hash, header, height)?;
+            activation
+                .connected_transactions
+                .extend(block.transactions.into_iter().skip(1));
+            activation.connected += 1;
+            activation.height = height;
+        }
+    }
+}
+
+/// Whether a block is on the active chain
+fn is_on_active_chain(storage: &Storage, hash: &Hash) -> Result<bool> {
+    let Some(height) = storage.blocks().get_height_by_hash(hash)? else {
+        return Ok(false);
+    };
+    let tip_height = storage.chain().get_height()?.unwrap_or(0);
+    Ok(height <= tip_height && storage.blocks().get_hash_by_height(height)? == Some(*hash))
+}
+
+/// Whether `hash` is `ancestor` or one of its descendants
+fn descends_from(storage: &Storage, hash: &Hash, ancestor: &Hash) -> Result<bool> {
+    let blockstore = storage.blocks();
+    let ancestor_height = blockstore.get_height_by_hash(ancestor)?;
+    let mut current = *hash;
+    loop {
+        if current == *ancestor {
+            return Ok(true);
+        }
+        let height = blockstore.get_height_by_hash(&current)?;
+        if let (Some(height), Some(ancestor_height)) = (height, ancestor_height) {
+            if height <= ancestor_height {
+                return Ok(false);
+            }
+        }
+        match blockstore.get_header(&current)? {
+            Some(header) => current = header.prev_block_hash,
+            None => return Ok(false),
+        }
+    }
+}
+
+/// Blocks leading from the active chain to `tip`, with the fork height
+///
+/// `None` if any of them is invalid or has no stored body.
+fn path_from_active_chain(
+    storage: &Storage,
+    tip: &Hash,
+) -> Result<Option<(u64, Vec<(Hash, BlockHeader)>)>> {
+    let blockstore = storage.blocks();
+    let mut path = Vec::new();
+    let mut current = *tip;
+    while !is_on_active_chain(storage, &current)? {
+        if storage.chain().is_invalid(&current)? || !blockstore.has_block_body(&current)? {
+            return Ok(None);
+        }
+        let Some(header) = blockstore.get_header(&current)? else {
+            return Ok(None);
+        };
+        let prev = header.prev_block_hash;
+        path.push((current, header));
+        current = prev;
+    }
+    let Some(fork_height) = blockstore.get_height_by_hash(&current)? else {
+        return Ok(None);
+    };
+    path.reverse();
+    Ok(Some((fork_height, path)))
+}
+
+/// Disconnect active-chain blocks above `height`
+///
+/// `utxo_set` is rolled back with the stored one, and the disconnected blocks
+/// and their transactions are recorded in `activation`. The block store's
+/// caches are dropped (see [`BlockStore::clear_caches`]).
+fn disconnect_to_height(
+    storage: &Storage,
+    height: u64,
+    utxo_set: &mut UtxoSet,
+    activation: &mut ChainActivation,
+) -> Result<()> {
+    let blockstore = storage.blocks();
+    let tip_height = storage.chain().get_height()?.unwrap_or(0);
+    let mut disconnected_blocks = Vec::new();
+    for block_height in (height + 1..=tip_height).rev() {
+        let hash = blockstore
+            .get_hash_by_height(block_height)?
+            .ok_or_else(|| anyhow::anyhow!("No block at height {}", block_height))?;
+        let undo = storage.disconnect_block(&hash)?;
+        for outpoint in &undo.created {
+            utxo_set.remove(outpoint);
+        }
+        utxo_set.extend(undo.spent);
+        for tx in storage.transactions().get_block_transactions(&hash)? {
+            storage
+                .transactions()
+                .remove_transaction(&bllvm_protocol::block::calculate_tx_id(&tx))?;
+        }
+        if let Some(block) = blockstore.get_block(&hash)? {
+            disconnected_blocks.push(block.transactions.into_iter().skip(1));
+        }
+        activation.disconnected += 1;
+    }
+
+    // Lower blocks first, ahead of anything disconnected by an earlier call
+    let mut transactions: Vec<Transaction> =
+        disconnected_blocks.into_iter().rev().flatten().collect();
+    transactions.append(&mut activation.disconnected_transactions);
+    activation.disconnected_transactions = transactions;
+
+    let hash = blockstore
+        .get_hash_by_height(height)?
+        .ok_or_else(|| anyhow::anyhow!("No block at height {}", height))?;
+    let header = blockstore
+        .get_header(&hash)?
+        .ok_or_else(|| anyhow::anyhow!("No header for block {}", hex::encode(hash)))?;
+    storage.chain().update_tip(&hash, &header, height)?;
+    // Heights above the new tip will be rewritten by the replacing chain
+    blockstore.clear_caches();
+    Ok(())
+}
+
+/// Validate a block below the assumed-valid block without running scripts
+///
+/// Counterpart of [`validate_block_with_context`] for blocks covered by
+/// `assumevalid`: every consensus rule but script verification is checked
+/// (see [`connect_block_without_scripts`]), against the active chain in
+/// `blockstore`. `is_coinbase_output` tells coinbase outputs apart for the
+/// maturity rule.
+pub fn validate_block_without_scripts(
+    blockstore: &BlockStore,
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    utxo_set: &mut UtxoSet,
+    height: u64,
+    params: &ConsensusParams,
+    is_coinbase_output: &dyn Fn(&OutPoint) -> bool,
+) -> Result<ValidationResult> {
+    let hash_at = |height: u64| blockstore.get_hash_by_height(height).ok().flatten();
+    let header_at = |height: u64| blockstore.get_header(&hash_at(height)?).ok().flatten();
+    let context = ChainContext {
+        params: *params,
+        header_at: &header_at,
+        is_coinbase_output,
+        now: current_timestamp(),
+    };
+    let (result, new_utxo_set) =
+        connect_block_without_scripts(block, witnesses, utxo_set.clone(), height, &context)?;
+    if matches!(result, ValidationResult::Valid) {
+        *utxo_set = new_utxo_set;
+    }
+    Ok(result)
+}
@@ -0,0 +1,553 @@
+//! Block validation pipeline
+//!
+//! Blocks received from the network are validated and connected by a
+//! dedicated worker instead of inline in the node's main loop, so a slow
+//! block does not hold up peer messages, pings or maintenance. The main loop
+//! submits blocks over a bounded channel and picks up one
+//! [`BlockValidationEvent`] per block; the worker handles blocks strictly in
+//! submission order, one at a time.
+//!
+//! The worker is not the only writer of the active chain: `invalidateblock`,
+//! `reconsiderblock`, `preciousblock`, `reindexchainstate` and `submitblock`
+//! change it too. Each block is validated and connected under the storage's
+//! chainstate lock (see [`Storage::lock_chainstate`]), and the worker reloads
+//! its UTXO set and height whenever it finds the tip moved since its last
+//! block.
+
+use crate::node::block_processor;
+use crate::node::metrics::MetricsCollector;
+use crate::node::notifications::NotificationPublisher;
+use crate::node::performance::{OperationType, PerformanceProfiler, PerformanceTimer};
+use crate::node::sync;
+use crate::storage::Storage;
+use anyhow::Result;
+use bllvm_protocol::{BlockHeader, Hash, UtxoSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Blocks that may wait for the worker before the main loop stops taking
+/// more from the network
+pub const MAX_QUEUED_BLOCKS: usize = 16;
+
+/// A block waiting for validation
+#[derive(Debug, Clone)]
+pub struct QueuedBlock {
+    /// Block in wire format
+    pub data: Vec<u8>,
+    /// Peer that sent the block, if known
+    pub source: Option<SocketAddr>,
+}
+
+impl QueuedBlock {
+    pub fn new(data: Vec<u8>, source: Option<SocketAddr>) -> Self {
+        Self { data, source }
+    }
+
+    /// Header of the block, if the data holds one
+    pub fn header(&self) -> Option<BlockHeader> {
+        self.data
+            .get(..80)
+            .and_then(|header| block_processor::parse_header_from_wire(header).ok())
+    }
+}
+
+/// Outcome of validating a queued block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockValidationEvent {
+    /// The block was connected to the active chain
+    Accepted { height: u64, block_hash: Hash },
+    /// The block is invalid
+    ///
+    /// `builds_on_tip` is false when the block did not extend our tip, so it
+    /// was not validated in its own context and its sender is not to blame.
+    Rejected {
+        height: u64,
+        source: Option<SocketAddr>,
+        builds_on_tip: bool,
+    },
+    /// The block could not be processed (storage or parse error)
+    Failed { height: u64, error: String },
+}
+
+/// Validates and connects blocks for the pipeline worker
+///
+/// Runs on the blocking thread pool, one block at a time.
+pub trait BlockValidator: Send + 'static {
+    fn validate(&mut self, block: QueuedBlock) -> BlockValidationEvent;
+}
+
+/// Handle to the block validation worker
+pub struct BlockValidationPipeline {
+    blocks: mpsc::Sender<QueuedBlock>,
+    events: mpsc::UnboundedReceiver<BlockValidationEvent>,
+    worker: JoinHandle<()>,
+}
+
+impl BlockValidationPipeline {
+    /// Start a worker validating submitted blocks with `validator`
+    pub fn spawn<V: BlockValidator>(validator: V) -> Self {
+        let (blocks, block_rx) = mpsc::channel(MAX_QUEUED_BLOCKS);
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let worker = tokio::spawn(run_worker(validator, block_rx, event_tx));
+        Self {
+            blocks,
+            events,
+            worker,
+        }
+    }
+
+    /// Whether another block can be queued without waiting
+    pub fn has_capacity(&self) -> bool {
+        self.blocks.capacity() > 0
+    }
+
+    /// Queue a block for validation (non-blocking)
+    pub fn submit(&self, block: QueuedBlock) -> Result<()> {
+        self.blocks.try_send(block).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => anyhow::anyhow!("Block validation queue is full"),
+            mpsc::error::TrySendError::Closed(_) => {
+                anyhow::anyhow!("Block validation worker has stopped")
+            }
+        })
+    }
+
+    /// Next validation outcome, if one is ready (non-blocking)
+    pub fn try_recv_event(&mut self) -> Option<BlockValidationEvent> {
+        self.events.try_recv().ok()
+    }
+
+    /// Wait for the next validation outcome
+    pub async fn recv_event(&mut self) -> Option<BlockValidationEvent> {
+        self.events.recv().await
+    }
+
+    /// Stop taking blocks and wait for the worker to finish those queued
+    pub async fn shutdown(self) {
+        drop(self.blocks);
+        if let Err(e) = self.worker.await {
+            warn!("Block validation worker failed: {}", e);
+        }
+    }
+}
+
+async fn run_worker<V: BlockValidator>(
+    mut validator: V,
+    mut blocks: mpsc::Receiver<QueuedBlock>,
+    events: mpsc::UnboundedSender<BlockValidationEvent>,
+) {
+    while let Some(block) = blocks.recv().await {
+        let result = tokio::task::spawn_blocking(move || {
+            let event = validator.validate(block);
+            (validator, event)
+        })
+        .await;
+        match result {
+            Ok((returned, event)) => {
+                validator = returned;
+                if events.send(event).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                // The validator is gone with the panicked task; later blocks
+                // cannot be connected in order without it
+                warn!("Block validation task failed, stopping worker: {}", e);
+                break;
+            }
+        }
+    }
+    debug!("Block validation worker stopped");
+}
+
+/// Validates blocks against the node's storage and connects them to the
+/// active chain
+pub struct ChainBlockValidator {
+    storage: Arc<Storage>,
+    sync_coordinator: sync::SyncCoordinator,
+    current_height: u64,
+    utxo_set: UtxoSet,
+    /// Tip after the last block this worker connected
+    chain_tip: Option<Hash>,
+    metrics: Arc<MetricsCollector>,
+    profiler: Arc<PerformanceProfiler>,
+    notifications: Option<Arc<NotificationPublisher>>,
+    sync_reporter: sync::SyncProgressReporter,
+}
+
+impl ChainBlockValidator {
+    /// Validator connecting blocks from `current_height`
+    pub fn new(
+        storage: Arc<Storage>,
+        sync_coordinator: sync::SyncCoordinator,
+        current_height: u64,
+        metrics: Arc<MetricsCollector>,
+        profiler: Arc<PerformanceProfiler>,
+    ) -> Self {
+        Self {
+            storage,
+            sync_coordinator,
+            current_height,
+            utxo_set: UtxoSet::new(),
+            chain_tip: None,
+            metrics,
+            profiler,
+            notifications: None,
+            sync_reporter: sync::SyncProgressReporter::default(),
+        }
+    }
+
+    /// Pick up chain changes made by someone else since the last block
+    fn resync_with_chain(&mut self) {
+        let Ok(Some(info)) = self.storage.chain().load_chain_info() else {
+            return;
+        };
+        if self.chain_tip.is_none_or(|tip| tip == info.tip_hash) {
+            return;
+        }
+        match self.storage.utxos().load_utxo_set() {
+            Ok(utxo_set) => {
+                info!(
+                    "Active chain changed to height {}, resuming block validation from there",
+                    info.height
+                );
+                self.utxo_set = utxo_set;
+                self.current_height = info.height + 1;
+                self.chain_tip = Some(info.tip_hash);
+            }
+            Err(e) => warn!("Failed to reload UTXO set after chain change: {}", e),
+        }
+    }
+
+    /// Publish connected blocks to notification subscribers
+    pub fn with_notifications(mut self, notifications: Option<Arc<NotificationPublisher>>) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Finish connecting an accepted block: chain tip, caches, UTXO set,
+    /// notifications, commitments and pruning
+    fn connect_accepted(&mut self, block: &QueuedBlock, header: Option<&BlockHeader>) -> Hash {
+        let current_height = self.current_height;
+        let blocks_arc = self.storage.blocks();
+        let block_hash = if let Ok(Some(hash)) = blocks_arc.get_hash_by_height(current_height) {
+            hash
+        } else {
+            warn!("Failed to get block hash for height {}", current_height);
+            [0u8; 32]
+        };
+
+        // Update chain tip (for chainwork, etc.)
+        if let Ok(Some(stored)) = blocks_arc.get_block(&block_hash) {
+            if let Err(e) =
+                self.storage
+                    .chain()
+                    .update_tip(&block_hash, &stored.header, current_height)
+            {
+                warn!("Failed to update chain tip: {}", e);
+            }
+
+            // Update UTXO stats cache (for fast gettxoutsetinfo RPC)
+            let transaction_count = self.storage.transaction_count().unwrap_or(0) as u64;
+            if let Err(e) = self.storage.chain().update_utxo_stats_cache(
+                &block_hash,
+                current_height,
+                &self.utxo_set,
+                transaction_count,
+            ) {
+                warn!("Failed to update UTXO stats cache: {}", e);
+            }
+
+            // Update network hashrate cache (for fast getmininginfo RPC)
+            if let Err(e) = self
+                .storage
+                .chain()
+                .calculate_and_cache_network_hashrate(current_height, &*blocks_arc)
+            {
+                warn!("Failed to update network hashrate cache: {}", e);
+            }
+        }
+
+        // Persist the block's UTXO changes (with undo data) after validation
+        // This is critical for commitment generation, incremental pruning and reorgs
+        let utxo_timer =
+            PerformanceTimer::start(Arc::clone(&self.profiler), OperationType::UtxoApplication);
+        let utxo_result = match blocks_arc.get_block(&block_hash) {
+            Ok(Some(stored)) => self
+                .storage
+                .connect_block(&stored, current_height)
+                .map(|_| ()),
+            Ok(None) => Err(anyhow::anyhow!("block not found in store")),
+            Err(e) => Err(e),
+        };
+        utxo_timer.stop();
+        if let Err(e) = utxo_result {
+            warn!(
+                "Failed to persist UTXO set after block {}: {}",
+                current_height, e
+            );
+        }
+
+        // Tell notification subscribers once the block is fully connected
+        if let (Some(notifications), Some(header)) = (self.notifications.as_ref(), header) {
+            notifications.notify_block(
+                &crate::network::compact_blocks::calculate_block_hash(header),
+                &block.data,
+            );
+        }
+
+        // Generate UTXO commitment from current state (if enabled)
+        // Use current_height (the block that was just validated) before incrementing
+        #[cfg(feature = "utxo-commitments")]
+        {
+            if let Some(pruning_manager) = self.storage.pruning() {
+                if let (Some(commitment_store), Some(_utxostore)) = (
+                    pruning_manager.commitment_store(),
+                    pruning_manager.utxostore(),
+                ) {
+                    // Generate commitment from current UTXO set state
+                    if let Err(e) = pruning_manager.generate_commitment_from_current_state(
+                        &block_hash,
+                        current_height,
+                        &self.utxo_set,
+                        &commitment_store,
+                    ) {
+                        warn!(
+                            "Failed to generate commitment for block {}: {}",
+                            current_height, e
+                        );
+                    } else {
+                        debug!("Generated UTXO commitment for block {}", current_height);
+                    }
+                }
+            }
+        }
+
+        // Log IBD progress and the estimated time to reach the best header
+        let sync_progress = sync::SyncProgress {
+            headers_height: self
+                .storage
+                .chain()
+                .get_best_header_height()
+                .ok()
+                .flatten()
+                .unwrap_or(0),
+            blocks_height: current_height,
+            tip_time: header.map(|h| h.timestamp).unwrap_or(0),
+            chain_tx_count: self.storage.transaction_count().unwrap_or(0) as u64,
+            ..Default::default()
+        };
+        let now = crate::utils::current_timestamp();
+        self.sync_reporter.report(
+            &sync::SyncStatus::new(&sync_progress, sync::validation_rate(&self.profiler), now),
+            now,
+        );
+
+        // Increment height after processing
+        self.current_height += 1;
+        self.chain_tip = Some(block_hash);
+        self.prune_after_connect();
+        block_hash
+    }
+
+    /// Incremental pruning during IBD, then automatic pruning
+    fn prune_after_connect(&self) {
+        let current_height = self.current_height;
+        let Some(pruning_manager) = self.storage.pruning() else {
+            return;
+        };
+
+        // Consider IBD if we're still syncing (height < tip or no recent blocks)
+        let is_ibd = current_height < 1000; // Simple heuristic: consider IBD if < 1000 blocks
+        if let Ok(Some(prune_stats)) =
+            pruning_manager.incremental_prune_during_ibd(current_height, is_ibd)
+        {
+            info!(
+                "Incremental pruning during IBD: {} blocks pruned, {} bytes freed",
+                prune_stats.blocks_pruned, prune_stats.storage_freed
+            );
+            // Flush storage to persist pruning changes
+            if let Err(e) = self.storage.flush() {
+                warn!("Failed to flush storage after incremental pruning: {}", e);
+            }
+        }
+
+        let stats = pruning_manager.get_stats();
+        if !pruning_manager.should_auto_prune(current_height, stats.last_prune_height) {
+            return;
+        }
+        info!("Automatic pruning triggered at height {}", current_height);
+
+        // Calculate prune height based on configuration
+        let prune_height = match &pruning_manager.config.mode {
+            crate::config::PruningMode::Disabled => None,
+            crate::config::PruningMode::Normal {
+                keep_from_height, ..
+            } => {
+                // Prune to keep_from_height, but ensure we keep min_blocks
+                let min_keep = pruning_manager.config.min_blocks_to_keep;
+                Some((*keep_from_height).max(current_height.saturating_sub(min_keep)))
+            }
+            #[cfg(feature = "utxo-commitments")]
+            crate::config::PruningMode::Aggressive {
+                keep_from_height,
+                min_blocks,
+                ..
+            } => {
+                // Prune to keep_from_height, respecting min_blocks
+                Some((*keep_from_height).max(current_height.saturating_sub(*min_blocks)))
+            }
+            #[cfg(not(feature = "utxo-commitments"))]
+            crate::config::PruningMode::Aggressive { .. } => {
+                // Aggressive pruning requires utxo-commitments feature
+                // Fall back to no pruning if feature is disabled
+                None
+            }
+            crate::config::PruningMode::Custom {
+                keep_bodies_from_height,
+                ..
+            } => {
+                // Prune to keep_bodies_from_height, respecting min_blocks
+                let min_keep = pruning_manager.config.min_blocks_to_keep;
+                Some((*keep_bodies_from_height).max(current_height.saturating_sub(min_keep)))
+            }
+        };
+
+        if let Some(prune_to_height) = prune_height.filter(|h| *h < current_height) {
+            match pruning_manager.prune_to_height(prune_to_height, current_height, false) {
+                Ok(prune_stats) => {
+                    info!(
+                        "Automatic pruning completed: {} blocks pruned, {} blocks kept",
+                        prune_stats.blocks_pruned, prune_stats.blocks_kept
+                    );
+                    // Flush storage to persist pruning changes
+                    use crate::utils::log_error;
+                    log_error(
+                        || self.storage.flush(),
+                        "Failed to flush storage after automatic pruning",
+                    );
+                }
+                Err(e) => {
+                    warn!("Automatic pruning failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl BlockValidator for ChainBlockValidator {
+    fn validate(&mut self, block: QueuedBlock) -> BlockValidationEvent {
+        let storage = Arc::clone(&self.storage);
+        let _chainstate = storage.lock_chainstate();
+        self.resync_with_chain();
+        let height = self.current_height;
+        let header = block.header();
+        let blocks_arc = self.storage.blocks();
+        match self.sync_coordinator.process_block(
+            &*blocks_arc,
+            &block.data,
+            height,
+            &mut self.utxo_set,
+            Some(Arc::clone(&self.metrics)),
+            Some(Arc::clone(&self.profiler)),
+        ) {
+            Ok(true) => {
+                info!("Block accepted at height {}", height);
+                let block_hash = self.connect_accepted(&block, header.as_ref());
+                BlockValidationEvent::Accepted { height, block_hash }
+            }
+            Ok(false) => {
+                warn!("Block rejected at height {}", height);
+                let builds_on_tip = header.is_some_and(|header| match height.checked_sub(1) {
+                    Some(parent_height) => {
+                        blocks_arc.get_hash_by_height(parent_height).ok().flatten()
+                            == Some(header.prev_block_hash)
+                    }
+                    None => true,
+                });
+                BlockValidationEvent::Rejected {
+                    height,
+                    source: block.source,
+                    builds_on_tip,
+                }
+            }
+            Err(e) => {
+                warn!("Error processing block: {}", e);
+                BlockValidationEvent::Failed {
+                    height,
+                    error: e.to_string(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Accepts every block after sleeping, counting heights in order
+    struct SlowValidator {
+        delay: Duration,
+        height: u64,
+    }
+
+    impl BlockValidator for SlowValidator {
+        fn validate(&mut self, _block: QueuedBlock) -> BlockValidationEvent {
+            std::thread::sleep(self.delay);
+            let height = self.height;
+            self.height += 1;
+            BlockValidationEvent::Accepted {
+                height,
+                block_hash: [height as u8; 32],
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_block_does_not_block_control_messages() {
+        let mut pipeline = BlockValidationPipeline::spawn(SlowValidator {
+            delay: Duration::from_millis(500),
+            height: 0,
+        });
+        pipeline
+            .submit(QueuedBlock::new(vec![0; 80], None))
+            .unwrap();
+        pipeline
+            .submit(QueuedBlock::new(vec![1; 80], None))
+            .unwrap();
+
+        // A stand-in for the network loop: pings are answered while the
+        // worker is still busy with the first block
+        let (ping_tx, mut ping_rx) = mpsc::unbounded_channel::<u64>();
+        let (pong_tx, mut pong_rx) = mpsc::unbounded_channel::<u64>();
+        for nonce in 0..3 {
+            ping_tx.send(nonce).unwrap();
+            let ping = ping_rx.recv().await.unwrap();
+            pong_tx.send(ping).unwrap();
+            let pong = tokio::time::timeout(Duration::from_millis(50), pong_rx.recv())
+                .await
+                .expect("control message handled while a block validates");
+            assert_eq!(pong, Some(nonce));
+            assert!(pipeline.try_recv_event().is_none());
+        }
+
+        // Blocks are connected in submission order
+        for height in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), pipeline.recv_event())
+                .await
+                .unwrap();
+            assert_eq!(
+                event,
+                Some(BlockValidationEvent::Accepted {
+                    height,
+                    block_hash: [height as u8; 32],
+                })
+            );
+        }
+        pipeline.shutdown().await;
+    }
+}
@@ -0,0 +1,116 @@
+//! Background block filter index builder (`blockfilterindex`)
+//!
+//! Filters and filter headers are written as blocks connect, but blocks
+//! connected before the filter index existed have none, so a node that turns
+//! on BIP157/158 filter serving after syncing cannot serve them. The builder
+//! walks the stored blocks from the end of the filter header chain up to the
+//! tip and builds each missing filter from the block and its undo record.
+//!
+//! It works in batches on the blocking thread pool, so block processing is
+//! never held up, and it persists nothing besides the filters and headers
+//! themselves: after a restart it continues from the end of the stored header
+//! chain. Pruned blocks cannot be indexed, so the builder refuses to start
+//! when part of the remaining range has been pruned.
+
+use crate::storage::Storage;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+/// Blocks indexed per batch before yielding to the runtime
+pub const BUILD_BATCH_SIZE: u64 = 1000;
+
+/// Builder status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildStatus {
+    /// Not started or still catching up
+    Building,
+    /// Every connected block has a filter header
+    Synced,
+    /// Stopped on an error (for example a pruned range)
+    Failed(String),
+}
+
+/// Builds block filters for historical blocks
+pub struct BlockFilterIndexBuilder {
+    storage: Arc<Storage>,
+    status: Mutex<BuildStatus>,
+}
+
+impl BlockFilterIndexBuilder {
+    /// Create a builder for the given storage
+    pub fn new(storage: Arc<Storage>) -> Self {
+        Self {
+            storage,
+            status: Mutex::new(BuildStatus::Building),
+        }
+    }
+
+    /// Current status
+    pub fn status(&self) -> BuildStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Index up to `max_blocks` blocks past the end of the filter header chain
+    ///
+    /// Returns `true` once the index has reached the chain tip. Fails without
+    /// indexing anything if a block in the remaining range is pruned.
+    pub fn build_batch(&self, max_blocks: u64) -> Result<bool> {
+        let Some(tip_height) = self.storage.chain().get_height()? else {
+            return Ok(true);
+        };
+        let start = match self.storage.filter_index_height()? {
+            Some(height) => height + 1,
+            None => 0,
+        };
+        if start > tip_height {
+            return Ok(true);
+        }
+        let first_stored = self.storage.blocks().first_stored_height(tip_height)?;
+        if first_stored > start {
+            return Err(anyhow::anyhow!(
+                "Cannot build the block filter index: blocks {} to {} are pruned",
+                start,
+                first_stored - 1
+            ));
+        }
+
+        let end = tip_height.min(start + max_blocks.max(1) - 1);
+        for height in start..=end {
+            self.storage.index_block_filter(height)?;
+        }
+        debug!("Block filter index built to height {}", end);
+        Ok(end >= tip_height)
+    }
+
+    /// Build batches on the blocking thread pool until the index reaches the tip
+    ///
+    /// Blocks connected afterwards get their filter header as they connect,
+    /// so the index stays synced once this returns.
+    pub async fn run(self: Arc<Self>) {
+        info!("Building block filter index");
+        loop {
+            let builder = Arc::clone(&self);
+            let result =
+                tokio::task::spawn_blocking(move || builder.build_batch(BUILD_BATCH_SIZE)).await;
+            match result {
+                Ok(Ok(true)) => {
+                    info!("Block filter index synced");
+                    *self.status.lock().unwrap() = BuildStatus::Synced;
+                    return;
+                }
+                Ok(Ok(false)) => tokio::task::yield_now().await,
+                Ok(Err(e)) => {
+                    warn!("Block filter index build stopped: {}", e);
+                    *self.status.lock().unwrap() = BuildStatus::Failed(e.to_string());
+                    return;
+                }
+                Err(e) => {
+                    warn!("Block filter index build task failed: {}", e);
+                    *self.status.lock().unwrap() = BuildStatus::Failed(e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Per-network chain parameters (`protocol_version` / `signetchallenge`)
+//!
+//! The protocol engine only distinguishes mainnet, testnet3 and regtest
+//! consensus rules. Testnet4 and signet run on the test network rules of the
+//! engine, and this module supplies what differs between them at the node
+//! level: message start bytes, default P2P port, genesis block, DNS seeds and,
+//! for signet, the challenge script every block must satisfy (see
+//! [`super::signet`]).
+
+use crate::network::dns_seeds;
+use crate::network::protocol::{
+    BITCOIN_MAGIC_MAINNET, BITCOIN_MAGIC_REGTEST, BITCOIN_MAGIC_TESTNET, BITCOIN_MAGIC_TESTNET4,
+};
+use crate::validation::no_scripts::ConsensusParams;
+use crate::validation::witness::encode_varint;
+use anyhow::Result;
+use bllvm_protocol::{Hash, ProtocolVersion};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Challenge of the default signet (a 1-of-2 bare multisig)
+pub const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d8e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// Genesis block hashes (display order)
+pub const MAINNET_GENESIS: &str =
+    "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+pub const TESTNET3_GENESIS: &str =
+    "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943";
+pub const TESTNET4_GENESIS: &str =
+    "00000000da84f2bafbbc53dee25a72ae507ff4914b867c565be350b0da8bf043";
+pub const SIGNET_GENESIS: &str = "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6";
+pub const REGTEST_GENESIS: &str =
+    "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206";
+
+/// Networks the node can run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Network {
+    Mainnet,
+    Testnet3,
+    Testnet4,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Network name as used for DNS seeds and log messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet3 => "testnet",
+            Network::Testnet4 => "testnet4",
+            Network::Signet => "signet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    /// Protocol engine variant whose consensus rules the network follows
+    pub fn protocol_version(self) -> ProtocolVersion {
+        match self {
+            Network::Mainnet => ProtocolVersion::BitcoinV1,
+            Network::Testnet3 | Network::Testnet4 | Network::Signet => ProtocolVersion::Testnet3,
+            Network::Regtest => ProtocolVersion::Regtest,
+        }
+    }
+
+    /// Buried deployment heights and difficulty rules, for the consensus
+    /// checks the node makes itself (assumed-valid blocks)
+    pub fn consensus_params(self) -> ConsensusParams {
+        match self {
+            Network::Mainnet => ConsensusParams::MAINNET,
+            Network::Testnet3 => ConsensusParams::TESTNET3,
+            Network::Testnet4 => ConsensusParams::TESTNET4,
+            Network::Signet => ConsensusParams::SIGNET,
+            Network::Regtest => ConsensusParams::REGTEST,
+        }
+    }
+
+    /// Network a protocol engine variant runs on by default
+    pub fn from_protocol_version(version: ProtocolVersion) -> Self {
+        match version {
+            ProtocolVersion::BitcoinV1 => Network::Mainnet,
+            ProtocolVersion::Testnet3 => Network::Testnet3,
+            ProtocolVersion::Regtest => Network::Regtest,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    /// Accepts the `ProtocolVersion` names and the usual network names
+    fn from_str(s: &str) -> Result<Self> {
+        match s
+            .trim()
+            .to_ascii_lowercase()
+            .replace(['-', '_'], "")
+            .as_str()
+        {
+            "bitcoinv1" | "mainnet" | "main" => Ok(Network::Mainnet),
+            "testnet3" | "testnet" | "test" => Ok(Network::Testnet3),
+            "testnet4" => Ok(Network::Testnet4),
+            "signet" => Ok(Network::Signet),
+            "regtest" => Ok(Network::Regtest),
+            other => Err(anyhow::anyhow!("Unknown network: {}", other)),
+        }
+    }
+}
+
+/// Node-level parameters of a network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainParams {
+    pub network: Network,
+    /// Message start bytes
+    pub magic: [u8; 4],
+    /// Default P2P port
+    pub default_port: u16,
+    /// Genesis block hash (internal byte order)
+    pub genesis_hash: Hash,
+    /// Built-in DNS seeds
+    pub dns_seeds: &'static [&'static str],
+    /// Script every block must satisfy (signet only)
+    pub signet_challenge: Option<Vec<u8>>,
+}
+
+impl ChainParams {
+    /// Parameters of `network`, using the default signet challenge on signet
+    pub fn new(network: Network) -> Self {
+        let (magic, default_port, genesis) = match network {
+            Network::Mainnet => (BITCOIN_MAGIC_MAINNET, 8333, MAINNET_GENESIS),
+            Network::Testnet3 => (BITCOIN_MAGIC_TESTNET, 18333, TESTNET3_GENESIS),
+            Network::Testnet4 => (BITCOIN_MAGIC_TESTNET4, 48333, TESTNET4_GENESIS),
+            Network::Signet => ([0; 4], 38333, SIGNET_GENESIS),
+            Network::Regtest => (BITCOIN_MAGIC_REGTEST, 18444, REGTEST_GENESIS),
+        };
+        let params = Self {
+            network,
+            magic,
+            default_port,
+            genesis_hash: parse_display_hash(genesis),
+            dns_seeds: dns_seeds::default_seeds(network.name()),
+            signet_challenge: None,
+        };
+        if network == Network::Signet {
+            let challenge =
+                hex::decode(DEFAULT_SIGNET_CHALLENGE).expect("default signet challenge is hex");
+            params.with_signet_challenge(challenge)
+        } else {
+            params
+        }
+    }
+
+    /// Use a custom signet challenge (`signetchallenge`)
+    ///
+    /// A custom signet is a different network: its message start bytes are
+    /// derived from the challenge, and it has no built-in seeds.
+    pub fn with_signet_challenge(mut self, challenge: Vec<u8>) -> Self {
+        if self.network != Network::Signet {
+            return self;
+        }
+        let default =
+            hex::decode(DEFAULT_SIGNET_CHALLENGE).expect("default signet challenge is hex");
+        if challenge != default {
+            self.dns_seeds = &[];
+        }
+        self.magic = signet_magic(&challenge);
+        self.signet_challenge = Some(challenge);
+        self
+    }
+}
+
+/// Message start bytes of a signet: the first four bytes of the double
+/// SHA256 of the length-prefixed challenge
+pub fn signet_magic(challenge: &[u8]) -> [u8; 4] {
+    let mut data = encode_varint(challenge.len() as u64);
+    data.extend_from_slice(challenge);
+    let hash = Sha256::digest(Sha256::digest(&data));
+    let mut magic = [0u8; 4];
+    magic.copy_from_slice(&hash[..4]);
+    magic
+}
+
+/// Parse a `signetchallenge` setting (hex script)
+pub fn parse_signet_challenge(value: &str) -> Result<Vec<u8>> {
+    let challenge = hex::decode(value.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid signetchallenge {}: {}", value, e))?;
+    if challenge.is_empty() {
+        return Err(anyhow::anyhow!("signetchallenge must not be empty"));
+    }
+    Ok(challenge)
+}
+
+fn parse_display_hash(value: &str) -> Hash {
+    let mut hash: Hash = hex::decode(value)
+        .expect("genesis hash is hex")
+        .try_into()
+        .expect("genesis hash is 32 bytes");
+    hash.reverse();
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_names() {
+        assert_eq!("BitcoinV1".parse::<Network>().unwrap(), Network::Mainnet);
+        assert_eq!("bitcoin-v1".parse::<Network>().unwrap(), Network::Mainnet);
+        assert_eq!("testnet3".parse::<Network>().unwrap(), Network::Testnet3);
+        assert_eq!("testnet4".parse::<Network>().unwrap(), Network::Testnet4);
+        assert_eq!("Signet".parse::<Network>().unwrap(), Network::Signet);
+        assert!("testnet5".parse::<Network>().is_err());
+        assert_eq!(
+            Network::from_protocol_version(Network::Regtest.protocol_version()),
+            Network::Regtest
+        );
+    }
+
+    #[test]
+    fn test_chain_params() {
+        let testnet4 = ChainParams::new(Network::Testnet4);
+        assert_eq!(testnet4.magic, [0x1c, 0x16, 0x3f, 0x28]);
+        assert_eq!(testnet4.default_port, 48333);
+        assert_eq!(testnet4.genesis_hash[31], 0);
+        assert!(!testnet4.dns_seeds.is_empty());
+        assert!(testnet4.signet_challenge.is_none());
+
+        let signet = ChainParams::new(Network::Signet);
+        let challenge = signet.signet_challenge.clone().unwrap();
+        assert_eq!(signet.magic, signet_magic(&challenge));
+        assert!(!signet.dns_seeds.is_empty());
+
+        // A custom signet gets its own magic and no seeds
+        let custom = ChainParams::new(Network::Signet).with_signet_challenge(vec![0x51]);
+        assert_ne!(custom.magic, signet.magic);
+        assert!(custom.dns_seeds.is_empty());
+
+        // Challenges only apply to signet
+        let regtest = ChainParams::new(Network::Regtest).with_signet_challenge(vec![0x51]);
+        assert!(regtest.signet_challenge.is_none());
+        assert!(parse_signet_challenge("zz").is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Chainstates for assumeutxo
+//!
+//! After a UTXO snapshot is loaded the node follows two chainstates: the
+//! snapshot chainstate, which continues from the snapshot base block and
+//! becomes the active chain, and a background chainstate that validates every
+//! block from genesis up to that base. The snapshot is trusted until the
+//! background chainstate reaches it; without a snapshot only the active
+//! chainstate exists.
+
+use bllvm_protocol::Hash;
+use std::sync::Mutex;
+
+/// Background chainstate validating the blocks below a loaded snapshot
+#[derive(Debug)]
+pub struct BackgroundChainstate {
+    snapshot_blockhash: Hash,
+    snapshot_height: u64,
+    /// Height and hash of the last block fully validated in the background
+    validated_tip: Mutex<Option<(u64, Hash)>>,
+}
+
+impl BackgroundChainstate {
+    /// Start background validation for a snapshot based on the given block
+    pub fn new(snapshot_blockhash: Hash, snapshot_height: u64) -> Self {
+        Self {
+            snapshot_blockhash,
+            snapshot_height,
+            validated_tip: Mutex::new(None),
+        }
+    }
+
+    /// Base block of the loaded snapshot
+    pub fn snapshot_blockhash(&self) -> Hash {
+        self.snapshot_blockhash
+    }
+
+    /// Height of the snapshot base block
+    pub fn snapshot_height(&self) -> u64 {
+        self.snapshot_height
+    }
+
+    /// Record that the block at `height` has been fully validated
+    ///
+    /// Heights at or below the current validated tip are ignored.
+    pub fn record_validated(&self, height: u64, hash: Hash) {
+        let mut tip = self.validated_tip.lock().unwrap();
+        if tip.map_or(true, |(validated, _)| height > validated) {
+            *tip = Some((height, hash));
+        }
+    }
+
+    /// Height and hash of the last block validated in the background
+    pub fn validated_tip(&self) -> Option<(u64, Hash)> {
+        *self.validated_tip.lock().unwrap()
+    }
+
+    /// Whether background validation has reached the snapshot base block
+    pub fn is_complete(&self) -> bool {
+        self.validated_tip()
+            .is_some_and(|(height, _)| height >= self.snapshot_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_background_validation_progress() {
+        let chainstate = BackgroundChainstate::new([7; 32], 10);
+        assert_eq!(chainstate.validated_tip(), None);
+        assert!(!chainstate.is_complete());
+
+        chainstate.record_validated(4, [4; 32]);
+        chainstate.record_validated(3, [3; 32]);
+        assert_eq!(chainstate.validated_tip(), Some((4, [4; 32])));
+        assert!(!chainstate.is_complete());
+
+        chainstate.record_validated(10, [7; 32]);
+        assert!(chainstate.is_complete());
+    }
+}
@@ -98,4 +98,25 @@ impl EventPublisher {
             warn!("Failed to publish ChainReorg event: {}", e);
         }
     }
+
+    /// Publish mempool eviction event
+    pub async fn publish_mempool_eviction(&self, tx_hash: &Hash, reason: &str) {
+        debug!(
+            "Publishing MempoolEviction event for tx {:?} ({})",
+            tx_hash, reason
+        );
+
+        let payload = EventPayload::MempoolEviction {
+            tx_hash: *tx_hash,
+            reason: reason.to_string(),
+        };
+
+        if let Err(e) = self
+            .event_manager
+            .publish_event(EventType::MempoolEviction, payload)
+            .await
+        {
+            warn!("Failed to publish MempoolEviction event: {}", e);
+        }
+    }
 }
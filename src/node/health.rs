@@ -2,6 +2,9 @@
 //!
 //! Provides health status monitoring and alerting for node components.
 
+use crate::network::NetworkManager;
+use crate::node::metrics::{NetworkMetrics, StorageMetrics};
+use crate::storage::Storage;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -18,6 +21,18 @@ pub enum HealthStatus {
     Down,
 }
 
+impl HealthStatus {
+    /// Lowercase name, as reported by `gethealth` and the health endpoints
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Unhealthy => "unhealthy",
+            HealthStatus::Down => "down",
+        }
+    }
+}
+
 /// Component health check result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComponentHealth {
@@ -66,8 +81,8 @@ impl HealthChecker {
         network_healthy: bool,
         storage_healthy: bool,
         rpc_healthy: bool,
-        network_metrics: Option<&crate::node::metrics::NetworkMetrics>,
-        storage_metrics: Option<&crate::node::metrics::StorageMetrics>,
+        network_metrics: Option<&NetworkMetrics>,
+        storage_metrics: Option<&StorageMetrics>,
     ) -> HealthReport {
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -81,11 +96,14 @@ impl HealthChecker {
 
         let mut components = Vec::new();
 
-        // Check network component
-        let network_status = if network_healthy {
-            HealthStatus::Healthy
-        } else {
+        // Check network component; a node without peers still runs but
+        // cannot follow the chain
+        let network_status = if !network_healthy {
             HealthStatus::Unhealthy
+        } else if network_metrics.is_some_and(|m| m.peer_count == 0) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
         };
         components.push(ComponentHealth {
             component: "network".to_string(),
@@ -166,6 +184,41 @@ impl HealthChecker {
         }
     }
 
+    /// Check the health of a node's network and storage
+    ///
+    /// The network is unhealthy when disabled (`setnetworkactive false`), the
+    /// storage when its counts cannot be read. Either may be missing (e.g. an
+    /// RPC server started without a node) and is then reported healthy,
+    /// without metrics.
+    pub async fn check_node(
+        &self,
+        network: Option<&NetworkManager>,
+        storage: Option<&Storage>,
+        rpc_healthy: bool,
+    ) -> HealthReport {
+        let (network_healthy, network_metrics) = match network {
+            Some(network) => (
+                network.network_active().await,
+                Some(network.get_network_stats().await),
+            ),
+            None => (true, None),
+        };
+        let (storage_healthy, storage_metrics) = match storage {
+            Some(storage) => (
+                storage.blocks().block_count().is_ok(),
+                Some(sample_storage_metrics(storage)),
+            ),
+            None => (true, None),
+        };
+        self.check_health(
+            network_healthy,
+            storage_healthy,
+            rpc_healthy,
+            network_metrics.as_ref(),
+            storage_metrics.as_ref(),
+        )
+    }
+
     /// Quick health check (returns overall status only)
     pub fn quick_check(
         &self,
@@ -181,6 +234,21 @@ impl HealthChecker {
     }
 }
 
+/// Storage counts shown in a health report
+///
+/// Failed counts read as zero.
+pub fn sample_storage_metrics(storage: &Storage) -> StorageMetrics {
+    StorageMetrics {
+        chain_height: storage.chain().get_height().ok().flatten().unwrap_or(0),
+        block_count: storage.blocks().block_count().unwrap_or(0),
+        utxo_count: storage.utxos().utxo_count().unwrap_or(0),
+        transaction_count: storage.transaction_count().unwrap_or(0),
+        disk_size: storage.disk_size().unwrap_or(0),
+        within_bounds: storage.check_storage_bounds().unwrap_or(false),
+        ..Default::default()
+    }
+}
+
 impl Default for HealthChecker {
     fn default() -> Self {
         Self::new()
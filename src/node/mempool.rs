@@ -2,25 +2,343 @@
 //!
 //! Handles transaction mempool management, validation, and relay.
 
+use crate::node::block_processor::{split_transaction_witness, ChainActivation};
+use crate::node::event_publisher::EventPublisher;
+use crate::node::notifications::NotificationPublisher;
+use crate::validation::witness::{
+    calculate_wtxid, encode_varint, has_witness, serialize_transaction_with_witness,
+    transaction_vsize, transaction_witnesses,
+};
 use anyhow::Result;
 use bllvm_protocol::mempool::Mempool;
-use bllvm_protocol::{Hash, OutPoint, Transaction, UtxoSet};
+use bllvm_protocol::segwit::Witness;
+use bllvm_protocol::{Hash, OutPoint, Transaction, TransactionOutput, UtxoSet, UTXO};
 use std::cmp::Reverse;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use tracing::{debug, info};
 
+/// Mempool file name (stored in the data directory)
+pub const MEMPOOL_FILE: &str = "mempool.dat";
+
+/// Height reported for outputs of unconfirmed transactions
+pub const MEMPOOL_HEIGHT: u64 = 0x7FFF_FFFF;
+
+/// Maximum number of transactions a replacement may evict (BIP 125 rule 5)
+pub const MAX_REPLACEMENT_CANDIDATES: usize = 100;
+
+/// Minimum relay fee rate (satoshis per 1000 vbytes)
+pub const DEFAULT_MIN_RELAY_FEE_PER_KVB: u64 = 1000;
+
+/// Fee rate a replacement must add on top of the fees it replaces (satoshis per 1000 vbytes)
+pub const DEFAULT_INCREMENTAL_RELAY_FEE_PER_KVB: u64 = 1000;
+
+/// Default time a transaction may stay in the mempool (2 weeks, in seconds)
+pub const DEFAULT_MEMPOOL_EXPIRY_SECONDS: u64 = 336 * 60 * 60;
+
+/// Default interval between expiry sweeps (seconds)
+pub const DEFAULT_EXPIRY_SWEEP_INTERVAL_SECONDS: u64 = 600;
+
+/// Highest input sequence number that signals opt-in replaceability (BIP 125)
+pub const MAX_BIP125_RBF_SEQUENCE: u64 = 0xffff_fffd;
+
+/// Whether a transaction opts in to replacement (BIP 125)
+pub fn signals_replaceability(tx: &Transaction) -> bool {
+    tx.inputs
+        .iter()
+        .any(|input| input.sequence <= MAX_BIP125_RBF_SEQUENCE)
+}
+
+/// Dust relay fee rate (satoshis per 1000 vbytes), as in Bitcoin Core
+pub const DEFAULT_DUST_RELAY_FEE_PER_KVB: u64 = 3000;
+
+/// Maximum script size; larger scripts can never be spent
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// OP_RETURN opcode
+const OP_RETURN: u8 = 0x6a;
+
+/// Whether an output script is provably unspendable
+pub fn is_unspendable(script_pubkey: &[u8]) -> bool {
+    script_pubkey.first() == Some(&OP_RETURN) || script_pubkey.len() > MAX_SCRIPT_SIZE
+}
+
+/// Whether a script is a segwit witness program (BIP 141)
+pub fn is_witness_program(script_pubkey: &[u8]) -> bool {
+    let len = script_pubkey.len();
+    if !(4..=42).contains(&len) {
+        return false;
+    }
+    let version = script_pubkey[0];
+    (version == 0x00 || (0x51..=0x60).contains(&version)) && script_pubkey[1] as usize + 2 == len
+}
+
+/// Smallest value an output may carry without being dust
+///
+/// An output is dust when spending it would cost more than it is worth at
+/// `dust_relay_fee_per_kvb`: the fee for the output itself plus a typical
+/// input spending it (148 bytes, or 67 vbytes for witness programs).
+/// Unspendable outputs have no dust limit.
+pub fn dust_threshold(output: &TransactionOutput, dust_relay_fee_per_kvb: u64) -> u64 {
+    if is_unspendable(&output.script_pubkey) {
+        return 0;
+    }
+    let script_len = output.script_pubkey.len() as u64;
+    // value + script length varint + script
+    let output_size = 8 + encode_varint(script_len).len() as u64 + script_len;
+    let spend_size = if is_witness_program(&output.script_pubkey) {
+        // outpoint, empty scriptSig, sequence; witness discounted by 4
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    (output_size + spend_size) * dust_relay_fee_per_kvb / 1000
+}
+
+/// Reject dust outputs and more than one OP_RETURN output
+pub fn check_dust(
+    tx: &Transaction,
+    dust_relay_fee_per_kvb: u64,
+) -> std::result::Result<(), String> {
+    let mut data_outputs = 0;
+    for output in tx.outputs.iter() {
+        if output.script_pubkey.first() == Some(&OP_RETURN) {
+            data_outputs += 1;
+            continue;
+        }
+        if (output.value as u64) < dust_threshold(output, dust_relay_fee_per_kvb) {
+            return Err("dust".to_string());
+        }
+    }
+    if data_outputs > 1 {
+        return Err("multi-op-return".to_string());
+    }
+    Ok(())
+}
+
+pub use crate::validation::no_scripts::COINBASE_MATURITY;
+
+/// Reject spends of coinbase outputs with fewer than [`COINBASE_MATURITY`] confirmations
+///
+/// `spend_height` is the height of the block the transaction would be mined
+/// in (tip + 1), and `is_coinbase` tells whether an unspent outpoint was
+/// created by a coinbase transaction.
+pub fn check_coinbase_maturity(
+    tx: &Transaction,
+    utxo_set: &UtxoSet,
+    is_coinbase: &dyn Fn(&OutPoint) -> bool,
+    spend_height: u64,
+) -> std::result::Result<(), String> {
+    for input in &tx.inputs {
+        if !is_coinbase(&input.prevout) {
+            continue;
+        }
+        if let Some(coin) = utxo_set.get(&input.prevout) {
+            if spend_height.saturating_sub(coin.height) < COINBASE_MATURITY {
+                return Err("bad-txns-premature-spend-of-coinbase".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Default fee histogram bucket boundaries (sat/vB)
+pub const DEFAULT_FEE_HISTOGRAM_BOUNDARIES: &[u64] = &[
+    1, 2, 3, 4, 5, 6, 7, 8, 10, 12, 14, 17, 20, 25, 30, 40, 50, 60, 70, 80, 100, 120, 140, 170,
+    200, 250, 300, 400, 500, 600, 700, 800, 1000, 1200, 1400, 1700, 2000, 2500, 3000, 4000, 5000,
+    6000, 7000, 8000, 10000,
+];
+
+/// Pooled transactions within one fee rate range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeHistogramBucket {
+    /// Lowest fee rate in the bucket (sat/vB, inclusive)
+    pub from: u64,
+    /// Fee rate the next bucket starts at (sat/vB); `None` for the last bucket
+    pub to: Option<u64>,
+    /// Number of transactions
+    pub count: usize,
+    /// Total virtual size (vbytes)
+    pub vsize: u64,
+    /// Total fees (satoshis)
+    pub fees: u64,
+}
+
+/// A transaction that passed mempool acceptance checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptedTransaction {
+    pub txid: Hash,
+    /// Witness txid (equal to `txid` without witness data)
+    pub wtxid: Hash,
+    /// Virtual size (vbytes)
+    pub vsize: u64,
+    /// Fee paid (satoshis)
+    pub fee: u64,
+}
+
+impl AcceptedTransaction {
+    /// Fee rate in satoshis per 1000 vbytes
+    pub fn fee_rate_per_kvb(&self) -> u64 {
+        self.fee.saturating_mul(1000) / self.vsize.max(1)
+    }
+}
+
+/// Reject codes (BIP 61) classifying why a transaction was refused
+pub const REJECT_MALFORMED: u8 = 0x01;
+pub const REJECT_INVALID: u8 = 0x10;
+pub const REJECT_DUPLICATE: u8 = 0x12;
+pub const REJECT_NONSTANDARD: u8 = 0x40;
+pub const REJECT_DUST: u8 = 0x41;
+pub const REJECT_INSUFFICIENTFEE: u8 = 0x42;
+
+/// Outcome of offering a single transaction to the mempool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolAcceptResult {
+    /// Passed every check (and was inserted, unless only testing)
+    Accepted(AcceptedTransaction),
+    /// Already in the mempool
+    AlreadyInPool { txid: Hash },
+    /// Already confirmed in a block
+    AlreadyKnownInBlock { txid: Hash },
+    /// Refused
+    ///
+    /// `is_relayable` is true when the refusal is down to local policy or
+    /// the current chain and mempool state (fees, standardness, conflicts,
+    /// unknown inputs), so another node may well accept the transaction
+    /// and a peer relaying it did nothing wrong. It is false for
+    /// transactions no node should relay.
+    Rejected {
+        reason: String,
+        reject_code: u8,
+        is_relayable: bool,
+    },
+}
+
+impl MempoolAcceptResult {
+    /// Rejection for a Bitcoin Core reject reason, classified by the reason
+    pub fn rejected(reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        let (reject_code, is_relayable) = match reason.as_str() {
+            "txn-already-in-mempool" | "txn-already-known" => (REJECT_DUPLICATE, true),
+            "txn-mempool-conflict" | "conflict-in-package" => (REJECT_DUPLICATE, true),
+            "insufficient fee" | "min relay fee not met" | "package-fee-too-low" => {
+                (REJECT_INSUFFICIENTFEE, true)
+            }
+            "dust" => (REJECT_DUST, true),
+            "version"
+            | "tx-size"
+            | "scriptsig-size"
+            | "scriptsig-not-pushonly"
+            | "scriptsig-non-minimal-push"
+            | "scriptpubkey"
+            | "bare-multisig"
+            | "multi-op-return"
+            | "bad-txns-too-many-sigops"
+            | "too many potential replacements"
+            | "replacement-adds-unconfirmed" => (REJECT_NONSTANDARD, true),
+            // Depends on what else is pooled
+            "bad-txns-spends-conflicting-tx" => (REJECT_INVALID, true),
+            // Valid once the parents or the next blocks arrive
+            "missing-inputs" | "bad-txns-premature-spend-of-coinbase" => (REJECT_INVALID, true),
+            // Our own failure, not the transaction's
+            reason if reason.starts_with("validation error") => (REJECT_INVALID, true),
+            _ => (REJECT_INVALID, false),
+        };
+        Self::Rejected {
+            reason,
+            reject_code,
+            is_relayable,
+        }
+    }
+
+    /// Whether the transaction was accepted
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Accepted(_))
+    }
+
+    /// The accepted transaction, if accepted
+    pub fn accepted(&self) -> Option<&AcceptedTransaction> {
+        match self {
+            Self::Accepted(accepted) => Some(accepted),
+            _ => None,
+        }
+    }
+
+    /// Bitcoin Core reject reason, unless accepted
+    pub fn reject_reason(&self) -> Option<&str> {
+        match self {
+            Self::Accepted(_) => None,
+            Self::AlreadyInPool { .. } => Some("txn-already-in-mempool"),
+            Self::AlreadyKnownInBlock { .. } => Some("txn-already-known"),
+            Self::Rejected { reason, .. } => Some(reason),
+        }
+    }
+
+    /// Whether a peer sending this transaction should be penalized
+    ///
+    /// Only transactions no node would relay count against the sender;
+    /// duplicates and policy refusals never do.
+    pub fn is_misbehavior(&self) -> bool {
+        matches!(
+            self,
+            Self::Rejected {
+                is_relayable: false,
+                ..
+            }
+        )
+    }
+
+    /// The accepted transaction, or the reject reason
+    pub fn into_result(self) -> std::result::Result<AcceptedTransaction, String> {
+        match self {
+            Self::Accepted(accepted) => Ok(accepted),
+            other => Err(other.reject_reason().unwrap_or_default().to_string()),
+        }
+    }
+}
+
+/// Outcome of validating a package of transactions together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageAcceptance {
+    /// Txid and acceptance result of each transaction, in package order
+    pub results: Vec<(Hash, std::result::Result<AcceptedTransaction, String>)>,
+    /// Why the package as a whole was refused; `None` if it was accepted
+    pub package_error: Option<String>,
+}
+
+impl PackageAcceptance {
+    /// Whether every transaction in the package was accepted
+    pub fn is_accepted(&self) -> bool {
+        self.package_error.is_none()
+    }
+
+    /// Aggregate fee rate of the accepted transactions (satoshis per 1000 vbytes)
+    pub fn fee_rate_per_kvb(&self) -> u64 {
+        let (fee, vsize) = self
+            .results
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .fold((0u64, 0u64), |(fee, vsize), accepted| {
+                (fee + accepted.fee, vsize + accepted.vsize)
+            });
+        fee.saturating_mul(1000) / vsize.max(1)
+    }
+}
+
 /// Mempool manager
 pub struct MempoolManager {
     /// Transaction mempool - stores full transactions by hash
     pub(crate) transactions: HashMap<Hash, Transaction>,
+    /// Per-input witness stacks of pooled transactions carrying witness data
+    witnesses: HashMap<Hash, Vec<Witness>>,
     /// Legacy mempool (HashSet of hashes) for compatibility
     #[allow(dead_code)]
     mempool: Mempool,
     #[allow(dead_code)]
     utxo_set: UtxoSet,
-    /// Track spent outputs to detect conflicts
-    pub(crate) spent_outputs: HashSet<OutPoint>,
+    /// Outpoints spent by pooled transactions, mapped to the spending txid
+    pub(crate) spent_outputs: HashMap<OutPoint, Hash>,
     /// Sorted index by fee rate (descending) - Reverse<u64> for descending order
     /// Maps fee_rate -> Vec<Hash> (multiple transactions can have same fee rate)
     /// Uses RwLock for interior mutability to allow &self methods
@@ -28,6 +346,34 @@ pub struct MempoolManager {
     /// Cache fee rates per transaction hash
     /// Uses RwLock for interior mutability to allow &self methods
     fee_cache: RwLock<HashMap<Hash, u64>>,
+    /// Minimum fee rate for acceptance (satoshis per 1000 vbytes)
+    min_relay_fee_per_kvb: AtomicU64,
+    /// Minimum additional fee rate for replacements (satoshis per 1000 vbytes)
+    incremental_relay_fee_per_kvb: AtomicU64,
+    /// Fee rate outputs are valued at for the dust limit (satoshis per 1000 vbytes)
+    dust_relay_fee_per_kvb: AtomicU64,
+    /// Apply the standardness policy to new transactions
+    require_standard: AtomicBool,
+    /// Relay transactions with an OP_RETURN output (`datacarrier`)
+    datacarrier: AtomicBool,
+    /// Maximum size of a standard OP_RETURN output script (`datacarriersize`)
+    datacarrier_size: AtomicUsize,
+    /// Time each transaction entered the mempool (Unix seconds)
+    entry_times: HashMap<Hash, u64>,
+    /// Total serialized size of pooled transactions
+    total_bytes: usize,
+    /// Maximum time a transaction may stay in the mempool (seconds)
+    expiry_seconds: AtomicU64,
+    /// Interval between expiry sweeps (seconds)
+    expiry_sweep_interval_seconds: AtomicU64,
+    /// Time of the last expiry sweep (Unix seconds)
+    last_expiry_sweep: u64,
+    /// Clock used for entry times and expiry (Unix seconds)
+    clock: Arc<dyn Fn() -> u64 + Send + Sync>,
+    /// Publishes eviction events to modules
+    event_publisher: Option<EventPublisher>,
+    /// Publishes `hashtx`/`rawtx` notifications for accepted transactions
+    notifications: RwLock<Option<Arc<NotificationPublisher>>>,
 }
 
 impl MempoolManager {
@@ -35,14 +381,116 @@ impl MempoolManager {
     pub fn new() -> Self {
         Self {
             transactions: HashMap::new(),
+            witnesses: HashMap::new(),
             mempool: Mempool::new(),
             utxo_set: HashMap::new(),
-            spent_outputs: HashSet::new(),
+            spent_outputs: HashMap::new(),
             fee_index: RwLock::new(BTreeMap::new()),
             fee_cache: RwLock::new(HashMap::new()),
+            min_relay_fee_per_kvb: AtomicU64::new(DEFAULT_MIN_RELAY_FEE_PER_KVB),
+            incremental_relay_fee_per_kvb: AtomicU64::new(DEFAULT_INCREMENTAL_RELAY_FEE_PER_KVB),
+            dust_relay_fee_per_kvb: AtomicU64::new(DEFAULT_DUST_RELAY_FEE_PER_KVB),
+            require_standard: AtomicBool::new(false),
+            datacarrier: AtomicBool::new(true),
+            datacarrier_size: AtomicUsize::new(crate::node::policy::MAX_OP_RETURN_RELAY),
+            entry_times: HashMap::new(),
+            total_bytes: 0,
+            expiry_seconds: AtomicU64::new(DEFAULT_MEMPOOL_EXPIRY_SECONDS),
+            expiry_sweep_interval_seconds: AtomicU64::new(DEFAULT_EXPIRY_SWEEP_INTERVAL_SECONDS),
+            last_expiry_sweep: 0,
+            clock: Arc::new(crate::utils::current_timestamp),
+            event_publisher: None,
+            notifications: RwLock::new(None),
         }
     }
 
+    /// Use a custom clock for entry times and expiry (Unix seconds)
+    pub fn with_clock(mut self, clock: Arc<dyn Fn() -> u64 + Send + Sync>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Publish eviction events to modules
+    pub fn with_event_publisher(mut self, event_publisher: EventPublisher) -> Self {
+        self.event_publisher = Some(event_publisher);
+        self
+    }
+
+    /// Publish a notification for every transaction added to the mempool
+    pub fn set_notifications(&self, notifications: Arc<NotificationPublisher>) {
+        *self.notifications.write().unwrap() = Some(notifications);
+    }
+
+    /// Set the expiry policy (both in seconds)
+    pub fn set_expiry(&self, expiry_seconds: u64, sweep_interval_seconds: u64) {
+        self.expiry_seconds.store(expiry_seconds, Ordering::Relaxed);
+        self.expiry_sweep_interval_seconds
+            .store(sweep_interval_seconds, Ordering::Relaxed);
+    }
+
+    /// Set the relay fee policy (satoshis per 1000 vbytes)
+    pub fn set_relay_fees(&self, min_relay_fee_per_kvb: u64, incremental_relay_fee_per_kvb: u64) {
+        self.min_relay_fee_per_kvb
+            .store(min_relay_fee_per_kvb, Ordering::Relaxed);
+        self.incremental_relay_fee_per_kvb
+            .store(incremental_relay_fee_per_kvb, Ordering::Relaxed);
+    }
+
+    /// Set the fee rate used for the dust limit (satoshis per 1000 vbytes)
+    ///
+    /// Zero disables the dust check.
+    pub fn set_dust_relay_fee(&self, dust_relay_fee_per_kvb: u64) {
+        self.dust_relay_fee_per_kvb
+            .store(dust_relay_fee_per_kvb, Ordering::Relaxed);
+    }
+
+    /// Fee rate used for the dust limit (satoshis per 1000 vbytes)
+    pub fn dust_relay_fee_per_kvb(&self) -> u64 {
+        self.dust_relay_fee_per_kvb.load(Ordering::Relaxed)
+    }
+
+    /// Enable or disable the standardness policy (see [`crate::node::policy`])
+    ///
+    /// Off by default; the node enables it on mainnet.
+    pub fn set_require_standard(&self, require_standard: bool) {
+        self.require_standard
+            .store(require_standard, Ordering::Relaxed);
+    }
+
+    /// Whether new transactions must be standard
+    pub fn require_standard(&self) -> bool {
+        self.require_standard.load(Ordering::Relaxed)
+    }
+
+    /// Set the OP_RETURN policy: whether data carrier outputs are standard and
+    /// the maximum size of their script in bytes
+    ///
+    /// Part of the standardness policy, so it only applies while standardness
+    /// is required. Blocks containing such outputs are unaffected.
+    pub fn set_datacarrier(&self, datacarrier: bool, datacarrier_size: usize) {
+        self.datacarrier.store(datacarrier, Ordering::Relaxed);
+        self.datacarrier_size
+            .store(datacarrier_size, Ordering::Relaxed);
+    }
+
+    /// Maximum size of a standard OP_RETURN output script, or `None` if data
+    /// carrier outputs are not relayed
+    pub fn datacarrier_size(&self) -> Option<usize> {
+        self.datacarrier
+            .load(Ordering::Relaxed)
+            .then(|| self.datacarrier_size.load(Ordering::Relaxed))
+    }
+
+    /// Minimum fee rate for acceptance (satoshis per 1000 vbytes)
+    pub fn min_relay_fee_per_kvb(&self) -> u64 {
+        self.min_relay_fee_per_kvb.load(Ordering::Relaxed)
+    }
+
+    /// Minimum additional fee rate a replacement must pay (satoshis per 1000 vbytes)
+    pub fn incremental_relay_fee_per_kvb(&self) -> u64 {
+        self.incremental_relay_fee_per_kvb.load(Ordering::Relaxed)
+    }
+
     /// Start the mempool manager
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting mempool manager");
@@ -104,23 +552,100 @@ impl MempoolManager {
     }
 
     /// Clean up old transactions
+    ///
+    /// Runs an expiry sweep once per sweep interval and publishes an eviction
+    /// event for every removed transaction.
     async fn cleanup_old_transactions(&mut self) -> Result<()> {
-        // In a real implementation, this would:
-        // 1. Remove transactions that are too old
-        // 2. Remove transactions that conflict with new blocks
-        // 3. Update transaction priorities
+        let now = (self.clock)();
+        let interval = self.expiry_sweep_interval_seconds.load(Ordering::Relaxed);
+        if now.saturating_sub(self.last_expiry_sweep) < interval {
+            return Ok(());
+        }
+        self.last_expiry_sweep = now;
 
         debug!("Cleaning up old transactions");
+        let evicted = self.expire(now);
+        if !evicted.is_empty() {
+            info!(
+                "Evicted {} expired transactions from mempool",
+                evicted.len()
+            );
+        }
+        if let Some(ref publisher) = self.event_publisher {
+            for hash in &evicted {
+                publisher.publish_mempool_eviction(hash, "expiry").await;
+            }
+        }
         Ok(())
     }
 
+    /// Evict transactions that entered the mempool more than the expiry period before `now`
+    ///
+    /// In-pool descendants of an expired transaction are evicted with it, since
+    /// they spend outputs that are no longer available. Returns the evicted hashes.
+    pub fn expire(&mut self, now: u64) -> Vec<Hash> {
+        let expiry = self.expiry_seconds.load(Ordering::Relaxed);
+        let expired: Vec<Hash> = self
+            .entry_times
+            .iter()
+            .filter(|(_, &entry_time)| now.saturating_sub(entry_time) > expiry)
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        let mut evicted = Vec::new();
+        for hash in expired {
+            let mut family = self.descendants(&hash);
+            family.push(hash);
+            for member in family {
+                // Already gone if it descends from an earlier expired transaction
+                if self.remove_transaction(&member) {
+                    evicted.push(member);
+                }
+            }
+        }
+        evicted
+    }
+
+    /// In-pool transactions spending outputs of `hash`, directly or indirectly
+    pub fn descendants(&self, hash: &Hash) -> Vec<Hash> {
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![*hash];
+        while let Some(parent) = pending.pop() {
+            for (child_hash, child) in &self.transactions {
+                if child
+                    .inputs
+                    .iter()
+                    .any(|input| input.prevout.hash == parent)
+                    && seen.insert(*child_hash)
+                {
+                    found.push(*child_hash);
+                    pending.push(*child_hash);
+                }
+            }
+        }
+        found
+    }
+
     /// Add transaction to mempool
     pub async fn add_transaction(&mut self, tx: Transaction) -> Result<bool> {
+        self.add_transaction_with_witness(tx, Vec::new()).await
+    }
+
+    /// [`MempoolManager::add_transaction`] for a transaction carrying a witness
+    ///
+    /// The witness, one stack per input, is kept for block templates (see
+    /// [`MempoolManager::get_witness`]).
+    pub async fn add_transaction_with_witness(
+        &mut self,
+        tx: Transaction,
+        witnesses: Vec<Witness>,
+    ) -> Result<bool> {
         debug!("Adding transaction to mempool");
 
         // Check for conflicts with existing mempool transactions
         for input in &tx.inputs {
-            if self.spent_outputs.contains(&input.prevout) {
+            if self.spent_outputs.contains_key(&input.prevout) {
                 debug!("Transaction conflicts with existing mempool transaction");
                 return Ok(false);
             }
@@ -129,16 +654,24 @@ impl MempoolManager {
         // Add transaction to mempool (store full transaction)
         use bllvm_protocol::block::calculate_tx_id;
         let tx_hash = calculate_tx_id(&tx);
+        self.total_bytes += transaction_vsize(&tx, &witnesses) as usize;
+        self.entry_times.insert(tx_hash, (self.clock)());
         self.transactions.insert(tx_hash, tx.clone());
+        if has_witness(&witnesses) {
+            self.witnesses.insert(tx_hash, witnesses);
+        }
         self.mempool.insert(tx_hash);
 
         // Track spent outputs
         for input in &tx.inputs {
-            self.spent_outputs.insert(input.prevout.clone());
+            self.spent_outputs.insert(input.prevout.clone(), tx_hash);
+        }
+        if let Some(ref notifications) = *self.notifications.read().unwrap() {
+            notifications.notify_transaction(&tx);
         }
 
-        // Calculate and cache fee rate (will be updated when UTXO set is available)
-        // For now, set to 0 - will be recalculated in get_prioritized_transactions
+        // Fee rate is unknown without the UTXO set: 0 until acceptance records
+        // it or get_prioritized_transactions recalculates it
         let fee_rate = 0u64;
         self.fee_cache.write().unwrap().insert(tx_hash, fee_rate);
         self.fee_index
@@ -151,11 +684,509 @@ impl MempoolManager {
         Ok(true)
     }
 
+    /// Run mempool acceptance checks, inserting the transaction unless `test_accept`
+    ///
+    /// See [`MempoolManager::check_transaction`] for the possible outcomes.
+    pub async fn accept_to_memory_pool(
+        &mut self,
+        tx: Transaction,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+        test_accept: bool,
+    ) -> MempoolAcceptResult {
+        self.accept_to_memory_pool_with_witness(
+            tx,
+            Vec::new(),
+            utxo_set,
+            is_coinbase,
+            spend_height,
+            test_accept,
+        )
+        .await
+    }
+
+    /// [`MempoolManager::accept_to_memory_pool`] for a transaction carrying a witness
+    pub async fn accept_to_memory_pool_with_witness(
+        &mut self,
+        tx: Transaction,
+        witnesses: Vec<Witness>,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+        test_accept: bool,
+    ) -> MempoolAcceptResult {
+        let result = self.check_transaction_with_witness(
+            &tx,
+            &witnesses,
+            utxo_set,
+            is_coinbase,
+            spend_height,
+        );
+        if result.is_accepted() && !test_accept {
+            let conflicts = self.conflicting_transactions(&tx);
+            for replaced in self.replaced_transactions(&conflicts) {
+                self.remove_transaction(&replaced);
+            }
+            match self.add_transaction_with_witness(tx, witnesses).await {
+                Ok(true) => {
+                    if let Some(accepted) = result.accepted() {
+                        self.cache_fee_rate(accepted.txid, accepted.fee_rate_per_kvb());
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    return MempoolAcceptResult::rejected(format!("mempool insertion failed: {e}"));
+                }
+            }
+        }
+        result
+    }
+
+    /// Classify a transaction offered to the mempool, without inserting it
+    ///
+    /// Duplicates of pooled transactions, and transactions whose outputs are
+    /// already in `utxo_set` (so were confirmed), are told apart from
+    /// rejections; everything else goes through
+    /// [`MempoolManager::check_acceptance`].
+    pub fn check_transaction(
+        &self,
+        tx: &Transaction,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> MempoolAcceptResult {
+        self.check_transaction_with_witness(tx, &[], utxo_set, is_coinbase, spend_height)
+    }
+
+    /// [`MempoolManager::check_transaction`] for a transaction carrying a witness
+    ///
+    /// The witness, one stack per input, goes into the reported wtxid and
+    /// virtual size.
+    pub fn check_transaction_with_witness(
+        &self,
+        tx: &Transaction,
+        witnesses: &[Witness],
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> MempoolAcceptResult {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        let txid = calculate_tx_id(tx);
+        if self.transactions.contains_key(&txid) {
+            return MempoolAcceptResult::AlreadyInPool { txid };
+        }
+        let confirmed = (0..tx.outputs.len()).any(|index| {
+            utxo_set.contains_key(&OutPoint {
+                hash: txid,
+                index: index as u64,
+            })
+        });
+        if confirmed {
+            return MempoolAcceptResult::AlreadyKnownInBlock { txid };
+        }
+        match self.check_acceptance_with_witness(tx, witnesses, utxo_set, is_coinbase, spend_height)
+        {
+            Ok(accepted) => MempoolAcceptResult::Accepted(accepted),
+            Err(reason) => MempoolAcceptResult::rejected(reason),
+        }
+    }
+
+    /// Run mempool acceptance checks on a package, inserting it unless `test_accept`
+    ///
+    /// The package is all-or-nothing: nothing is inserted unless every
+    /// transaction is accepted (see [`MempoolManager::check_package`]).
+    pub async fn accept_package(
+        &mut self,
+        txs: Vec<Transaction>,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+        test_accept: bool,
+    ) -> PackageAcceptance {
+        self.accept_package_with_witnesses(
+            txs,
+            Vec::new(),
+            utxo_set,
+            is_coinbase,
+            spend_height,
+            test_accept,
+        )
+        .await
+    }
+
+    /// [`MempoolManager::accept_package`] for transactions carrying witnesses
+    ///
+    /// `witnesses[i]` holds the per-input stacks of `txs[i]`.
+    pub async fn accept_package_with_witnesses(
+        &mut self,
+        txs: Vec<Transaction>,
+        witnesses: Vec<Vec<Witness>>,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+        test_accept: bool,
+    ) -> PackageAcceptance {
+        let acceptance = self.check_package_with_witnesses(
+            &txs,
+            &witnesses,
+            utxo_set,
+            is_coinbase,
+            spend_height,
+        );
+        if acceptance.is_accepted() && !test_accept {
+            let mut witnesses = witnesses.into_iter();
+            for (tx, (_, result)) in txs.into_iter().zip(&acceptance.results) {
+                let tx_witnesses = witnesses.next().unwrap_or_default();
+                let conflicts = self.conflicting_transactions(&tx);
+                for replaced in self.replaced_transactions(&conflicts) {
+                    self.remove_transaction(&replaced);
+                }
+                match self.add_transaction_with_witness(tx, tx_witnesses).await {
+                    Ok(true) => {
+                        if let Ok(accepted) = result {
+                            self.cache_fee_rate(accepted.txid, accepted.fee_rate_per_kvb());
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => debug!("Package member insertion failed: {}", e),
+                }
+            }
+        }
+        acceptance
+    }
+
+    /// Check whether a package of transactions would be accepted, without
+    /// inserting it
+    ///
+    /// Transactions must be ordered parents first; each is checked like
+    /// [`MempoolManager::check_acceptance`] against `utxo_set` plus the
+    /// outputs of earlier package members. A transaction paying less than
+    /// the minimum relay fee is still accepted when it has a descendant in
+    /// the package (CPFP) and the package as a whole pays the minimum relay
+    /// fee rate.
+    pub fn check_package(
+        &self,
+        txs: &[Transaction],
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> PackageAcceptance {
+        self.check_package_with_witnesses(txs, &[], utxo_set, is_coinbase, spend_height)
+    }
+
+    /// [`MempoolManager::check_package`] for transactions carrying witnesses
+    ///
+    /// `witnesses[i]` holds the per-input stacks of `txs[i]`; missing or
+    /// empty entries mean no witness.
+    pub fn check_package_with_witnesses(
+        &self,
+        txs: &[Transaction],
+        witnesses: &[Vec<Witness>],
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> PackageAcceptance {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        let txids: Vec<Hash> = txs.iter().map(calculate_tx_id).collect();
+        let mut view = utxo_set.clone();
+        let mut package_spent = HashSet::new();
+        let mut below_min_fee = Vec::new();
+        let mut results = Vec::with_capacity(txs.len());
+        for (index, (tx, txid)) in txs.iter().zip(&txids).enumerate() {
+            let outcome = if tx
+                .inputs
+                .iter()
+                .any(|input| package_spent.contains(&input.prevout))
+            {
+                Err("conflict-in-package".to_string())
+            } else {
+                self.check_inputs_and_policy(
+                    tx,
+                    transaction_witnesses(witnesses, index),
+                    &view,
+                    is_coinbase,
+                    spend_height,
+                )
+                .and_then(|(accepted, conflicts)| {
+                    if !conflicts.is_empty() {
+                        self.check_replacement(tx, &accepted, &conflicts, &view)?;
+                    }
+                    Ok(accepted)
+                })
+            };
+            if let Ok(accepted) = &outcome {
+                if !self.meets_min_relay_fee(accepted) {
+                    below_min_fee.push(results.len());
+                }
+                for input in &tx.inputs {
+                    view.remove(&input.prevout);
+                    package_spent.insert(input.prevout.clone());
+                }
+                for (index, output) in tx.outputs.iter().enumerate() {
+                    view.insert(
+                        OutPoint {
+                            hash: *txid,
+                            index: index as u64,
+                        },
+                        UTXO {
+                            value: output.value,
+                            script_pubkey: output.script_pubkey.clone(),
+                            height: MEMPOOL_HEIGHT,
+                        },
+                    );
+                }
+            }
+            results.push((*txid, outcome));
+        }
+
+        let mut acceptance = PackageAcceptance {
+            results,
+            package_error: None,
+        };
+        if acceptance.results.iter().any(|(_, result)| result.is_err()) {
+            acceptance.package_error = Some("transaction failed".to_string());
+            return acceptance;
+        }
+        if below_min_fee.is_empty() {
+            return acceptance;
+        }
+
+        // Transactions below the minimum relay fee must be carried by a child
+        let package_meets_fee = acceptance.fee_rate_per_kvb() >= self.min_relay_fee_per_kvb();
+        for index in below_min_fee {
+            let has_child = txs[index + 1..].iter().any(|tx| {
+                tx.inputs
+                    .iter()
+                    .any(|input| input.prevout.hash == txids[index])
+            });
+            if !has_child || !package_meets_fee {
+                acceptance.results[index].1 = Err("min relay fee not met".to_string());
+            }
+        }
+        if acceptance.results.iter().any(|(_, result)| result.is_err()) {
+            acceptance.package_error = Some(if package_meets_fee {
+                "transaction failed".to_string()
+            } else {
+                "package-fee-too-low".to_string()
+            });
+        }
+        acceptance
+    }
+
+    /// Check whether a transaction would be accepted, without inserting it
+    ///
+    /// Inputs are resolved against `utxo_set`, so callers validating a package
+    /// can pass a view that includes the outputs of earlier package members.
+    /// Coinbase maturity is checked at `spend_height` (see
+    /// [`check_coinbase_maturity`]), outputs against the dust limit (see
+    /// [`check_dust`]) and, if required, the transaction against the
+    /// standardness policy (see [`crate::node::policy::check_standard`]). A transaction that double-spends in-pool
+    /// transactions is only accepted as a BIP 125 replacement (see
+    /// [`MempoolManager::check_replacement`]).
+    pub fn check_acceptance(
+        &self,
+        tx: &Transaction,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> std::result::Result<AcceptedTransaction, String> {
+        self.check_acceptance_with_witness(tx, &[], utxo_set, is_coinbase, spend_height)
+    }
+
+    /// [`MempoolManager::check_acceptance`] for a transaction carrying a witness
+    ///
+    /// The fee floor is applied to the witness-discounted virtual size.
+    pub fn check_acceptance_with_witness(
+        &self,
+        tx: &Transaction,
+        witnesses: &[Witness],
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> std::result::Result<AcceptedTransaction, String> {
+        let (accepted, conflicts) =
+            self.check_inputs_and_policy(tx, witnesses, utxo_set, is_coinbase, spend_height)?;
+        if !self.meets_min_relay_fee(&accepted) {
+            return Err("min relay fee not met".to_string());
+        }
+        if !conflicts.is_empty() {
+            self.check_replacement(tx, &accepted, &conflicts, utxo_set)?;
+        }
+        Ok(accepted)
+    }
+
+    /// Acceptance checks other than the fee floor and replacement rules
+    ///
+    /// Returns the accepted transaction and the in-pool transactions it
+    /// would replace.
+    fn check_inputs_and_policy(
+        &self,
+        tx: &Transaction,
+        witnesses: &[Witness],
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> std::result::Result<(AcceptedTransaction, Vec<Hash>), String> {
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::{ConsensusProof, ValidationResult};
+
+        match ConsensusProof::new().validate_transaction(tx) {
+            Ok(ValidationResult::Valid) => {}
+            Ok(ValidationResult::Invalid(reason)) => return Err(reason),
+            Err(e) => return Err(format!("validation error: {e}")),
+        }
+
+        if self.require_standard() {
+            crate::node::policy::check_standard(tx, utxo_set, self.datacarrier_size())?;
+        }
+        check_dust(tx, self.dust_relay_fee_per_kvb())?;
+
+        let txid = calculate_tx_id(tx);
+        if self.transactions.contains_key(&txid) {
+            return Err("txn-already-in-mempool".to_string());
+        }
+        let conflicts = self.conflicting_transactions(tx);
+        if conflicts
+            .iter()
+            .any(|hash| !signals_replaceability(&self.transactions[hash]))
+        {
+            return Err("txn-mempool-conflict".to_string());
+        }
+
+        let mut input_total = 0u64;
+        for input in &tx.inputs {
+            match utxo_set.get(&input.prevout) {
+                Some(utxo) => input_total += utxo.value as u64,
+                None => return Err("missing-inputs".to_string()),
+            }
+        }
+        check_coinbase_maturity(tx, utxo_set, is_coinbase, spend_height)?;
+        let output_total: u64 = tx.outputs.iter().map(|out| out.value as u64).sum();
+        if input_total < output_total {
+            return Err("bad-txns-in-belowout".to_string());
+        }
+
+        let accepted = AcceptedTransaction {
+            txid,
+            wtxid: calculate_wtxid(tx, witnesses),
+            vsize: transaction_vsize(tx, witnesses),
+            fee: input_total - output_total,
+        };
+        Ok((accepted, conflicts))
+    }
+
+    /// Whether a transaction pays at least the minimum relay fee rate
+    fn meets_min_relay_fee(&self, accepted: &AcceptedTransaction) -> bool {
+        accepted.fee.saturating_mul(1000) >= self.min_relay_fee_per_kvb() * accepted.vsize
+    }
+
+    /// In-pool transactions spending any of `tx`'s inputs
+    pub fn conflicting_transactions(&self, tx: &Transaction) -> Vec<Hash> {
+        let mut conflicts: Vec<Hash> = tx
+            .inputs
+            .iter()
+            .filter_map(|input| self.spent_outputs.get(&input.prevout).copied())
+            .collect();
+        conflicts.sort_unstable();
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// Transactions evicted by replacing `conflicts`: the conflicts and all
+    /// their in-pool descendants
+    pub fn replaced_transactions(&self, conflicts: &[Hash]) -> Vec<Hash> {
+        let mut seen = HashSet::new();
+        let mut replaced = Vec::new();
+        for hash in conflicts {
+            for hash in std::iter::once(*hash).chain(self.descendants(hash)) {
+                if seen.insert(hash) {
+                    replaced.push(hash);
+                }
+            }
+        }
+        replaced
+    }
+
+    /// Apply the BIP 125 rules to a replacement of `conflicts`
+    ///
+    /// The replacement may evict at most [`MAX_REPLACEMENT_CANDIDATES`]
+    /// transactions, counting the descendants of `conflicts`, and may not
+    /// spend any of them. Its only unconfirmed inputs may be ones the
+    /// conflicts already spent from. It must pay a higher fee rate than each
+    /// transaction it directly replaces, at least the combined fee of every
+    /// evicted transaction, and on top of that enough to cover its own size
+    /// at the incremental relay fee.
+    fn check_replacement(
+        &self,
+        tx: &Transaction,
+        replacement: &AcceptedTransaction,
+        conflicts: &[Hash],
+        utxo_set: &UtxoSet,
+    ) -> std::result::Result<(), String> {
+        let replaced = self.replaced_transactions(conflicts);
+        if replaced.len() > MAX_REPLACEMENT_CANDIDATES {
+            return Err("too many potential replacements".to_string());
+        }
+        if tx
+            .inputs
+            .iter()
+            .any(|input| replaced.contains(&input.prevout.hash))
+        {
+            return Err("bad-txns-spends-conflicting-tx".to_string());
+        }
+        let conflict_parents: HashSet<Hash> = conflicts
+            .iter()
+            .flat_map(|hash| self.transactions[hash].inputs.iter())
+            .map(|input| input.prevout.hash)
+            .collect();
+        let adds_unconfirmed = tx.inputs.iter().any(|input| {
+            let unconfirmed = self.transactions.contains_key(&input.prevout.hash)
+                || utxo_set
+                    .get(&input.prevout)
+                    .is_some_and(|coin| coin.height == MEMPOOL_HEIGHT);
+            unconfirmed && !conflict_parents.contains(&input.prevout.hash)
+        });
+        if adds_unconfirmed {
+            return Err("replacement-adds-unconfirmed".to_string());
+        }
+
+        for hash in conflicts {
+            let original = &self.transactions[hash];
+            let fee = self.pooled_fee(original, utxo_set);
+            let vsize = self.pooled_vsize(hash, original);
+            // Compare fee rates without dividing: fee / vsize <= original_fee / original_vsize
+            if replacement.fee.saturating_mul(vsize) <= fee.saturating_mul(replacement.vsize) {
+                return Err("insufficient fee".to_string());
+            }
+        }
+        let replaced_fees: u64 = replaced
+            .iter()
+            .map(|hash| self.pooled_fee(&self.transactions[hash], utxo_set))
+            .sum();
+        if replacement.fee < replaced_fees {
+            return Err("insufficient fee".to_string());
+        }
+        let additional_fee = replacement.fee - replaced_fees;
+        let required_fee = self.incremental_relay_fee_per_kvb() * replacement.vsize / 1000;
+        if additional_fee < required_fee {
+            return Err("insufficient fee".to_string());
+        }
+        Ok(())
+    }
+
     /// Get mempool size
     pub fn size(&self) -> usize {
         self.transactions.len()
     }
 
+    /// Total virtual size of pooled transactions in vbytes
+    pub fn bytes(&self) -> usize {
+        self.total_bytes
+    }
+
     /// Get mempool transaction hashes
     pub fn transaction_hashes(&self) -> Vec<Hash> {
         self.transactions.keys().cloned().collect()
@@ -166,6 +1197,35 @@ impl MempoolManager {
         self.transactions.get(hash).cloned()
     }
 
+    /// Per-input witness stacks of a pooled transaction (empty without witness data)
+    pub fn get_witness(&self, hash: &Hash) -> Vec<Witness> {
+        self.witnesses.get(hash).cloned().unwrap_or_default()
+    }
+
+    /// Check whether an in-pool transaction spends an outpoint
+    pub fn spends_outpoint(&self, outpoint: &OutPoint) -> bool {
+        self.spent_outputs.contains_key(outpoint)
+    }
+
+    /// Txid of the in-pool transaction spending an outpoint, if any
+    pub fn spending_transaction(&self, outpoint: &OutPoint) -> Option<Hash> {
+        self.spent_outputs.get(outpoint).copied()
+    }
+
+    /// Get an output created by an in-pool transaction
+    ///
+    /// The returned UTXO has height `MEMPOOL_HEIGHT`. Outputs already spent by
+    /// another in-pool transaction are still returned; check `spends_outpoint`.
+    pub fn get_mempool_output(&self, outpoint: &OutPoint) -> Option<UTXO> {
+        let tx = self.transactions.get(&outpoint.hash)?;
+        let output = tx.outputs.get(usize::try_from(outpoint.index).ok()?)?;
+        Some(UTXO {
+            value: output.value,
+            script_pubkey: output.script_pubkey.clone(),
+            height: MEMPOOL_HEIGHT,
+        })
+    }
+
     /// Get all transactions
     pub fn get_transactions(&self) -> Vec<Transaction> {
         self.transactions.values().cloned().collect()
@@ -252,15 +1312,8 @@ impl MempoolManager {
                 0
             };
 
-            // Calculate transaction size
-            let size = self.estimate_transaction_size(tx);
-
-            // Calculate fee rate (satoshis per vbyte)
-            let fee_rate = if size > 0 {
-                fee * 1000 / size as u64
-            } else {
-                0
-            };
+            // Calculate fee rate (satoshis per 1000 vbytes)
+            let fee_rate = fee * 1000 / self.pooled_vsize(tx_hash, tx).max(1);
 
             // Update cache
             fee_cache.insert(*tx_hash, fee_rate);
@@ -274,6 +1327,111 @@ impl MempoolManager {
         }
     }
 
+    /// Record a pooled transaction's fee rate in the fee cache and index
+    fn cache_fee_rate(&self, txid: Hash, fee_rate: u64) {
+        let previous = self.fee_cache.write().unwrap().insert(txid, fee_rate);
+        let mut fee_index = self.fee_index.write().unwrap();
+        if let Some(previous) = previous {
+            if let Some(tx_hashes) = fee_index.get_mut(&Reverse(previous)) {
+                tx_hashes.retain(|&h| h != txid);
+                if tx_hashes.is_empty() {
+                    fee_index.remove(&Reverse(previous));
+                }
+            }
+        }
+        fee_index
+            .entry(Reverse(fee_rate))
+            .or_insert_with(Vec::new)
+            .push(txid);
+    }
+
+    /// Cached fee rate of every pooled transaction (satoshis per 1000 vbytes)
+    ///
+    /// Rates are recorded on acceptance through
+    /// [`MempoolManager::accept_to_memory_pool`] and refreshed with the
+    /// prioritized transactions; transactions added directly with
+    /// [`MempoolManager::add_transaction`] read zero until then. Unlike
+    /// [`Self::fee_rates_per_kvb`], no UTXO set is needed.
+    pub fn cached_fee_rates_per_kvb(&self) -> Vec<(Hash, u64)> {
+        self.fee_cache
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(txid, fee_rate)| (*txid, *fee_rate))
+            .collect()
+    }
+
+    /// Fee rate of every pooled transaction (satoshis per 1000 vbytes)
+    ///
+    /// Inputs are resolved against `utxo_set` and the outputs of in-pool
+    /// parents; unresolved inputs count as zero.
+    pub fn fee_rates_per_kvb(&self, utxo_set: &UtxoSet) -> Vec<(Hash, u64)> {
+        self.transactions
+            .iter()
+            .map(|(tx_hash, tx)| {
+                let fee = self.pooled_fee(tx, utxo_set);
+                (*tx_hash, fee * 1000 / self.pooled_vsize(tx_hash, tx).max(1))
+            })
+            .collect()
+    }
+
+    /// Histogram of pooled transactions by fee rate
+    ///
+    /// `boundaries` are ascending fee rates in sat/vB; bucket `i` holds the
+    /// transactions paying at least `boundaries[i]` and less than the next
+    /// boundary. Transactions below the first boundary get a bucket starting
+    /// at zero, so the bucket sizes always add up to the mempool's total
+    /// vsize. Fees are resolved as in [`Self::fee_rates_per_kvb`].
+    pub fn fee_histogram(&self, utxo_set: &UtxoSet, boundaries: &[u64]) -> Vec<FeeHistogramBucket> {
+        let mut starts = boundaries.to_vec();
+        if starts.first() != Some(&0) {
+            starts.insert(0, 0);
+        }
+        let mut buckets: Vec<FeeHistogramBucket> = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &from)| FeeHistogramBucket {
+                from,
+                to: starts.get(i + 1).copied(),
+                count: 0,
+                vsize: 0,
+                fees: 0,
+            })
+            .collect();
+
+        for (tx_hash, tx) in &self.transactions {
+            let fee = self.pooled_fee(tx, utxo_set);
+            let vsize = self.pooled_vsize(tx_hash, tx);
+            // Compare in sat/kvB to avoid rounding the fee rate
+            let fee_rate_per_kvb = fee.saturating_mul(1000) / vsize.max(1);
+            let index =
+                starts.partition_point(|&from| from.saturating_mul(1000) <= fee_rate_per_kvb) - 1;
+            let bucket = &mut buckets[index];
+            bucket.count += 1;
+            bucket.vsize += vsize;
+            bucket.fees += fee;
+        }
+        buckets
+    }
+
+    /// Fee of a pooled transaction, resolving inputs against `utxo_set` and
+    /// in-pool parents (unresolved inputs count as zero)
+    fn pooled_fee(&self, tx: &Transaction, utxo_set: &UtxoSet) -> u64 {
+        let input_total: u64 = tx
+            .inputs
+            .iter()
+            .filter_map(|input| {
+                utxo_set
+                    .get(&input.prevout)
+                    .cloned()
+                    .or_else(|| self.get_mempool_output(&input.prevout))
+            })
+            .map(|utxo| utxo.value as u64)
+            .sum();
+        let output_total: u64 = tx.outputs.iter().map(|out| out.value as u64).sum();
+        input_total.saturating_sub(output_total)
+    }
+
     /// Calculate transaction fee
     ///
     /// Fee = sum of inputs - sum of outputs
@@ -303,34 +1461,20 @@ impl MempoolManager {
         }
     }
 
-    /// Estimate transaction size in vbytes
-    ///
-    /// Simplified estimation - in production, would use actual serialized size
-    fn estimate_transaction_size(&self, tx: &Transaction) -> usize {
-        // Base transaction size: version (4) + locktime (4) = 8 bytes
-        let mut size = 8;
-
-        // Input size: prevout (36) + script_sig (var) + sequence (4)
-        for input in &tx.inputs {
-            size += 36; // prevout
-            size += input.script_sig.len();
-            size += 4; // sequence
-        }
-
-        // Output size: value (8) + script_pubkey (var)
-        for output in &tx.outputs {
-            size += 8; // value
-            size += output.script_pubkey.len();
-        }
-
-        // Add witness discount if segwit (simplified - assume no witness for now)
-        size
+    /// Virtual size of a pooled transaction, counting its stored witness
+    fn pooled_vsize(&self, txid: &Hash, tx: &Transaction) -> u64 {
+        let witnesses = self.witnesses.get(txid).map_or(&[][..], Vec::as_slice);
+        transaction_vsize(tx, witnesses)
     }
 
     /// Remove transaction from mempool
     pub fn remove_transaction(&mut self, hash: &Hash) -> bool {
         if let Some(tx) = self.transactions.remove(hash) {
+            let vsize = self.pooled_vsize(hash, &tx) as usize;
             self.mempool.remove(hash);
+            self.witnesses.remove(hash);
+            self.entry_times.remove(hash);
+            self.total_bytes = self.total_bytes.saturating_sub(vsize);
 
             // Remove spent outputs tracking
             for input in &tx.inputs {
@@ -354,18 +1498,88 @@ impl MempoolManager {
         }
     }
 
+    /// Bring the pool in line with a changed active chain
+    ///
+    /// Transactions confirmed by the connected blocks leave the pool, and so
+    /// do pooled transactions spending the same coins, with their
+    /// descendants. The transactions of the disconnected blocks are then
+    /// offered again, parents first, against `utxo_set` at the new tip; those
+    /// the new chain confirmed or that no longer pass are dropped. Returns how
+    /// many were returned to the pool.
+    pub async fn update_for_reorg(
+        &mut self,
+        activation: &ChainActivation,
+        utxo_set: &UtxoSet,
+        is_coinbase: &dyn Fn(&OutPoint) -> bool,
+        spend_height: u64,
+    ) -> usize {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        for tx in &activation.connected_transactions {
+            self.remove_transaction(&calculate_tx_id(tx));
+            let conflicts = self.conflicting_transactions(tx);
+            for conflict in self.replaced_transactions(&conflicts) {
+                self.remove_transaction(&conflict);
+            }
+        }
+
+        let mut returned = 0;
+        for tx in &activation.disconnected_transactions {
+            // Coins spent, from the chain or earlier returned transactions,
+            // and the transaction's own outputs to spot a confirmed one
+            let txid = calculate_tx_id(tx);
+            let own_outputs = (0..tx.outputs.len()).map(|index| OutPoint {
+                hash: txid,
+                index: index as u64,
+            });
+            let mut view = UtxoSet::new();
+            for outpoint in tx
+                .inputs
+                .iter()
+                .map(|input| input.prevout.clone())
+                .chain(own_outputs)
+            {
+                let coin = utxo_set
+                    .get(&outpoint)
+                    .cloned()
+                    .or_else(|| self.get_mempool_output(&outpoint));
+                if let Some(coin) = coin {
+                    view.insert(outpoint, coin);
+                }
+            }
+            let result = self
+                .accept_to_memory_pool(tx.clone(), &view, is_coinbase, spend_height, false)
+                .await;
+            if result.is_accepted() {
+                returned += 1;
+            } else {
+                debug!(
+                    "Not returning transaction {} to the mempool: {}",
+                    hex::encode(txid),
+                    result.reject_reason().unwrap_or("already known")
+                );
+            }
+        }
+        returned
+    }
+
     /// Clear mempool
     pub fn clear(&mut self) {
         self.transactions.clear();
+        self.witnesses.clear();
         self.mempool.clear();
         self.spent_outputs.clear();
+        self.entry_times.clear();
+        self.total_bytes = 0;
         self.fee_index.write().unwrap().clear();
         self.fee_cache.write().unwrap().clear();
     }
 
     /// Save mempool to disk for persistence
+    ///
+    /// Transactions carrying witness data are written in the BIP 144 format.
     pub fn save_to_disk<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
-        use bllvm_protocol::serialization::transaction::serialize_transaction;
+        use bllvm_protocol::block::calculate_tx_id;
         use std::fs::File;
         use std::io::Write;
 
@@ -377,7 +1591,8 @@ impl MempoolManager {
 
         // Write each transaction
         for tx in transactions {
-            let serialized = serialize_transaction(&tx);
+            let serialized =
+                serialize_transaction_with_witness(&tx, &self.get_witness(&calculate_tx_id(&tx)));
             file.write_all(&(serialized.len() as u32).to_le_bytes())?;
             file.write_all(&serialized)?;
         }
@@ -444,8 +1659,9 @@ impl MempoolManager {
             let mut tx_bytes = vec![0u8; len];
             file.read_exact(&mut tx_bytes)?;
 
-            let tx = deserialize_transaction(&tx_bytes)?;
-            let _ = self.add_transaction(tx);
+            let (base_bytes, witnesses) = split_transaction_witness(&tx_bytes)?;
+            let tx = deserialize_transaction(&base_bytes)?;
+            let _ = self.add_transaction_with_witness(tx, witnesses);
         }
 
         Ok(())
@@ -100,18 +100,20 @@ mod kani_proofs {
         let tx1_hash = calculate_tx_id(&tx1);
         mempool.transactions.insert(tx1_hash, tx1.clone());
         for input in &tx1.inputs {
-            mempool.spent_outputs.insert(input.prevout.clone());
+            mempool
+                .spent_outputs
+                .insert(input.prevout.clone(), tx1_hash);
         }
 
         // Verify conflict detection: tx2 should be rejected because shared_outpoint is already spent
         let has_conflict = tx2
             .inputs
             .iter()
-            .any(|input| mempool.spent_outputs.contains(&input.prevout));
+            .any(|input| mempool.spent_outputs.contains_key(&input.prevout));
         assert!(has_conflict, "Conflicting transaction should be detected");
 
         // Verify spent output tracking
-        assert!(mempool.spent_outputs.contains(&shared_outpoint));
+        assert!(mempool.spent_outputs.contains_key(&shared_outpoint));
     }
 
     /// Verify conflict prevention
@@ -139,8 +141,8 @@ mod kani_proofs {
 
         // Verify all inputs are tracked as spent
         for input in &tx.inputs {
-            mempool.spent_outputs.insert(input.prevout.clone());
-            assert!(mempool.spent_outputs.contains(&input.prevout));
+            mempool.spent_outputs.insert(input.prevout.clone(), tx_hash);
+            assert!(mempool.spent_outputs.contains_key(&input.prevout));
         }
 
         // Verify conflict detection would reject conflicting transaction
@@ -156,7 +158,7 @@ mod kani_proofs {
             let would_be_rejected = conflicting_tx
                 .inputs
                 .iter()
-                .any(|input| mempool.spent_outputs.contains(&input.prevout));
+                .any(|input| mempool.spent_outputs.contains_key(&input.prevout));
             assert!(
                 would_be_rejected,
                 "Conflicting transaction should be rejected"
@@ -182,7 +184,7 @@ mod kani_proofs {
 
         // Initially, inputs should not be tracked as spent
         for input in &tx.inputs {
-            assert!(!mempool.spent_outputs.contains(&input.prevout));
+            assert!(!mempool.spent_outputs.contains_key(&input.prevout));
         }
 
         // Simulate adding transaction by manually updating state
@@ -193,12 +195,12 @@ mod kani_proofs {
 
         // Add all inputs to spent_outputs (as add_transaction does)
         for input in &tx.inputs {
-            mempool.spent_outputs.insert(input.prevout.clone());
+            mempool.spent_outputs.insert(input.prevout.clone(), tx_hash);
         }
 
         // All inputs should now be tracked as spent
         for input in &tx.inputs {
-            assert!(mempool.spent_outputs.contains(&input.prevout));
+            assert!(mempool.spent_outputs.contains_key(&input.prevout));
         }
     }
 
@@ -297,10 +299,14 @@ mod kani_proofs {
 
         // Add inputs to spent_outputs
         for input in &tx1.inputs {
-            mempool.spent_outputs.insert(input.prevout.clone());
+            mempool
+                .spent_outputs
+                .insert(input.prevout.clone(), tx1_hash);
         }
         for input in &tx2.inputs {
-            mempool.spent_outputs.insert(input.prevout.clone());
+            mempool
+                .spent_outputs
+                .insert(input.prevout.clone(), tx2_hash);
         }
 
         // Get prioritized transactions
@@ -310,12 +316,12 @@ mod kani_proofs {
         if prioritized.len() >= 2 {
             let fee1 = mempool.calculate_transaction_fee(&prioritized[0], &utxo_set);
             let fee2 = mempool.calculate_transaction_fee(&prioritized[1], &utxo_set);
-            let size1 = mempool.estimate_transaction_size(&prioritized[0]);
-            let size2 = mempool.estimate_transaction_size(&prioritized[1]);
+            let size1 = mempool.pooled_vsize(&calculate_tx_id(&prioritized[0]), &prioritized[0]);
+            let size2 = mempool.pooled_vsize(&calculate_tx_id(&prioritized[1]), &prioritized[1]);
 
             if size1 > 0 && size2 > 0 {
-                let fee_rate1 = fee1 * 1000 / size1 as u64;
-                let fee_rate2 = fee2 * 1000 / size2 as u64;
+                let fee_rate1 = fee1 * 1000 / size1;
+                let fee_rate2 = fee2 * 1000 / size2;
                 assert!(
                     fee_rate1 >= fee_rate2,
                     "Transactions must be sorted by fee rate (descending)"
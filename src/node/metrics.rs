@@ -18,6 +18,9 @@ pub struct NodeMetrics {
     pub rpc: RpcMetrics,
     /// Performance metrics
     pub performance: PerformanceMetrics,
+    /// Mempool metrics
+    #[serde(default)]
+    pub mempool: MempoolMetrics,
     /// System metrics
     pub system: SystemMetrics,
     /// Timestamp when metrics were collected
@@ -39,12 +42,30 @@ pub struct NetworkMetrics {
     pub messages_received: u64,
     /// Active connections
     pub active_connections: usize,
+    /// Inbound connections
+    #[serde(default)]
+    pub inbound_connections: usize,
+    /// Outbound full-relay connections
+    #[serde(default)]
+    pub outbound_full_relay_connections: usize,
+    /// Outbound block-relay-only connections
+    #[serde(default)]
+    pub block_relay_only_connections: usize,
+    /// Feeler connections in progress
+    #[serde(default)]
+    pub feeler_connections: usize,
     /// Banned peers count
     pub banned_peers: usize,
     /// Connection attempts (successful)
     pub connection_attempts: u64,
     /// Connection failures
     pub connection_failures: u64,
+    /// Outbound connections that timed out before the transport connected
+    #[serde(default)]
+    pub connect_timeouts: u64,
+    /// Peers disconnected for not completing the version handshake in time
+    #[serde(default)]
+    pub handshake_timeouts: u64,
     /// DoS protection metrics
     pub dos_protection: DosMetrics,
 }
@@ -67,6 +88,9 @@ pub struct DosMetrics {
 /// Storage layer metrics
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct StorageMetrics {
+    /// Current chain tip height
+    #[serde(default)]
+    pub chain_height: u64,
     /// Total blocks stored
     pub block_count: usize,
     /// Total UTXOs
@@ -79,6 +103,12 @@ pub struct StorageMetrics {
     pub within_bounds: bool,
     /// Pruning statistics (if pruning enabled)
     pub pruning: Option<PruningMetrics>,
+    /// Fraction of header lookups served from the block store cache
+    #[serde(default)]
+    pub header_cache_hit_rate: f64,
+    /// Fraction of block lookups served from the block store cache
+    #[serde(default)]
+    pub block_cache_hit_rate: f64,
 }
 
 /// Pruning metrics
@@ -109,6 +139,30 @@ pub struct RpcMetrics {
     pub avg_response_time_ms: f64,
     /// Active connections
     pub active_connections: usize,
+    /// Storage circuit breaker metrics
+    #[serde(default)]
+    pub storage_circuit_breaker: CircuitBreakerMetrics,
+    /// Requests waiting for an RPC worker
+    #[serde(default)]
+    pub work_queue_depth: usize,
+    /// Requests refused with "Work queue depth exceeded"
+    #[serde(default)]
+    pub work_queue_rejected_total: u64,
+}
+
+/// Circuit breaker state and transition counters
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CircuitBreakerMetrics {
+    /// Current state (0 = closed, 1 = open, 2 = half-open)
+    pub state: u8,
+    /// Times the breaker transitioned to open
+    pub opened_total: u64,
+    /// Times the breaker transitioned to half-open
+    pub half_opened_total: u64,
+    /// Times the breaker transitioned back to closed
+    pub closed_total: u64,
+    /// Requests rejected while the breaker was open
+    pub rejected_total: u64,
 }
 
 /// Performance metrics
@@ -124,6 +178,15 @@ pub struct PerformanceMetrics {
     pub transactions_per_second: f64,
 }
 
+/// Mempool metrics
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MempoolMetrics {
+    /// Transactions in the mempool
+    pub size: usize,
+    /// Approximate mempool size (bytes)
+    pub bytes: u64,
+}
+
 /// System metrics
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SystemMetrics {
@@ -145,6 +208,8 @@ pub struct MetricsCollector {
     rpc: Arc<Mutex<RpcMetrics>>,
     /// Performance metrics
     performance: Arc<Mutex<PerformanceMetrics>>,
+    /// Mempool metrics
+    mempool: Arc<Mutex<MempoolMetrics>>,
     /// System metrics
     system: Arc<Mutex<SystemMetrics>>,
     /// Start time for uptime calculation
@@ -159,6 +224,7 @@ impl MetricsCollector {
             storage: Arc::new(Mutex::new(StorageMetrics::default())),
             rpc: Arc::new(Mutex::new(RpcMetrics::default())),
             performance: Arc::new(Mutex::new(PerformanceMetrics::default())),
+            mempool: Arc::new(Mutex::new(MempoolMetrics::default())),
             system: Arc::new(Mutex::new(SystemMetrics::default())),
             start_time: SystemTime::now(),
         }
@@ -184,6 +250,7 @@ impl MetricsCollector {
             storage: self.storage.lock().unwrap().clone(),
             rpc: self.rpc.lock().unwrap().clone(),
             performance: self.performance.lock().unwrap().clone(),
+            mempool: self.mempool.lock().unwrap().clone(),
             system: system.clone(),
             timestamp,
         }
@@ -225,6 +292,15 @@ impl MetricsCollector {
         f(&mut metrics);
     }
 
+    /// Update mempool metrics
+    pub fn update_mempool<F>(&self, f: F)
+    where
+        F: FnOnce(&mut MempoolMetrics),
+    {
+        let mut metrics = self.mempool.lock().unwrap();
+        f(&mut metrics);
+    }
+
     /// Get network metrics reference
     pub fn network(&self) -> Arc<Mutex<NetworkMetrics>> {
         Arc::clone(&self.network)
@@ -244,6 +320,11 @@ impl MetricsCollector {
     pub fn performance(&self) -> Arc<Mutex<PerformanceMetrics>> {
         Arc::clone(&self.performance)
     }
+
+    /// Get mempool metrics reference
+    pub fn mempool(&self) -> Arc<Mutex<MempoolMetrics>> {
+        Arc::clone(&self.mempool)
+    }
 }
 
 impl Default for MetricsCollector {
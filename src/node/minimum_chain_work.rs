@@ -0,0 +1,72 @@
+//! Minimum chain work (`minimumchainwork`)
+//!
+//! A node fed only a low-work chain (e.g. by peers that are all attackers)
+//! could otherwise believe it has caught up. The node stays in initial block
+//! download until its active chain has at least this much cumulative work,
+//! which is known for the real network from the chain at release time.
+
+use anyhow::Result;
+use bllvm_protocol::ProtocolVersion;
+
+/// Mainnet minimum chain work (chainwork of block 824000)
+pub const MAINNET_MINIMUM_CHAIN_WORK: &str =
+    "000000000000000000000000000000000000000052b2559353df4117b7348b64";
+
+/// Testnet3 minimum chain work (chainwork of block 2500000)
+pub const TESTNET_MINIMUM_CHAIN_WORK: &str =
+    "000000000000000000000000000000000000000000000c59b14e264ba6c15db9";
+
+/// Built-in minimum chain work for a network (zero on regtest)
+pub fn default_minimum_chain_work(version: ProtocolVersion) -> u128 {
+    let work = match version {
+        ProtocolVersion::BitcoinV1 => MAINNET_MINIMUM_CHAIN_WORK,
+        ProtocolVersion::Testnet3 => TESTNET_MINIMUM_CHAIN_WORK,
+        _ => return 0,
+    };
+    parse_minimum_chain_work(work).unwrap_or(0)
+}
+
+/// Parse a `minimumchainwork` setting
+///
+/// The value is big-endian hex as `getblockchaininfo` reports `chainwork`,
+/// with or without a `0x` prefix and leading zeros. `"0"` disables the check.
+pub fn parse_minimum_chain_work(value: &str) -> Result<u128> {
+    let value = value.trim();
+    let digits = value
+        .strip_prefix("0x")
+        .unwrap_or(value)
+        .trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    if digits.len() > 32 {
+        return Err(anyhow::anyhow!(
+            "Invalid minimumchainwork {}: exceeds 128 bits",
+            value
+        ));
+    }
+    u128::from_str_radix(digits, 16)
+        .map_err(|e| anyhow::anyhow!("Invalid minimumchainwork {}: {}", value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_minimum_chain_work() {
+        assert_eq!(parse_minimum_chain_work("0").unwrap(), 0);
+        assert_eq!(
+            parse_minimum_chain_work("0x100010001").unwrap(),
+            0x100010001
+        );
+        assert_eq!(
+            parse_minimum_chain_work(MAINNET_MINIMUM_CHAIN_WORK).unwrap(),
+            0x52b2559353df4117b7348b64
+        );
+        assert!(parse_minimum_chain_work("not hex").is_err());
+        assert!(parse_minimum_chain_work(&"f".repeat(33)).is_err());
+        assert!(default_minimum_chain_work(ProtocolVersion::BitcoinV1) > 0);
+        assert_eq!(default_minimum_chain_work(ProtocolVersion::Regtest), 0);
+    }
+}
@@ -3,7 +3,12 @@
 //! This module provides sync coordination, mempool management,
 //! mining coordination, and overall node state management.
 
+pub mod assume_valid;
 pub mod block_processor;
+pub mod block_validation;
+pub mod blockfilterindex;
+pub mod chain_params;
+pub mod chainstates;
 pub mod event_publisher;
 pub mod health;
 pub mod mempool;
@@ -11,26 +16,40 @@ pub mod mempool;
 pub mod mempool_proofs;
 pub mod metrics;
 pub mod miner;
+pub mod minimum_chain_work;
+pub mod notifications;
+pub mod orphan_pool;
 pub mod performance;
+pub mod policy;
+pub mod signet;
 pub mod sync;
+pub mod versionbits;
 
 use anyhow::Result;
 use std::net::SocketAddr;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
 use crate::config::NodeConfig;
 use crate::module::api::NodeApiImpl;
 use crate::module::ModuleManager;
+use crate::network::address_db::PEERS_FILE;
+use crate::network::connection_manager::ANCHORS_FILE;
+use crate::network::discouragement::DISCOURAGED_FILE;
 use crate::network::NetworkManager;
 use crate::node::event_publisher::EventPublisher;
+use crate::node::mempool::MEMPOOL_FILE;
 use crate::node::metrics::MetricsCollector;
-use crate::node::performance::PerformanceProfiler;
+use crate::node::performance::{OperationType, PerformanceProfiler, PerformanceTimer};
 use crate::rpc::RpcManager;
 use crate::storage::Storage;
+use crate::utils::ShutdownController;
 use bllvm_protocol::{BitcoinProtocolEngine, ProtocolVersion};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// How long `Node::stop` waits for background tasks before aborting them
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
 /// Main node orchestrator
 pub struct Node {
     protocol: Arc<BitcoinProtocolEngine>,
@@ -54,6 +73,8 @@ pub struct Node {
     profiler: Arc<PerformanceProfiler>,
     /// Protocol version (for determining network type)
     protocol_version: ProtocolVersion,
+    /// Network parameters (magic, port, genesis, seeds, signet challenge)
+    chain_params: chain_params::ChainParams,
     /// Network address (for determining port)
     network_addr: SocketAddr,
     /// Node configuration (optional)
@@ -64,7 +85,15 @@ pub struct Node {
     disk_check_counter: std::sync::atomic::AtomicU64,
     /// Governance webhook client (for fee forwarding integration)
     #[cfg(feature = "governance")]
-    governance_webhook: Option<crate::governance::GovernanceWebhookClient>,
+    governance_webhook: Option<Arc<crate::governance::GovernanceWebhookClient>>,
+    /// Background chainstate validating a loaded UTXO snapshot (assumeutxo)
+    background_chainstate: Option<Arc<chainstates::BackgroundChainstate>>,
+    /// Block and transaction notification feeds (from `notifications` config)
+    notifications: Option<Arc<notifications::NotificationPublisher>>,
+    /// Shutdown broadcast shared with the network manager's tasks
+    shutdown: ShutdownController,
+    /// Set once `stop` has run, so repeated calls do nothing
+    stopped: bool,
 }
 
 impl Node {
@@ -84,13 +113,22 @@ impl Node {
         let storage = Storage::new(data_dir)?;
         let storage_arc = Arc::new(storage);
         let mempool_manager_arc = Arc::new(mempool::MempoolManager::new());
+        // Test networks accept non-standard transactions by default
+        mempool_manager_arc
+            .set_require_standard(matches!(protocol_version, ProtocolVersion::BitcoinV1));
 
         // Create network manager (config will be applied later if available)
-        let network = NetworkManager::new(network_addr).with_dependencies(
-            Arc::clone(&protocol_arc),
-            Arc::clone(&storage_arc),
-            Arc::clone(&mempool_manager_arc),
-        );
+        let shutdown = ShutdownController::new();
+        let network = NetworkManager::new(network_addr)
+            .with_dependencies(
+                Arc::clone(&protocol_arc),
+                Arc::clone(&storage_arc),
+                Arc::clone(&mempool_manager_arc),
+            )
+            .with_anchors_path(PathBuf::from(data_dir).join(ANCHORS_FILE))
+            .with_peers_path(PathBuf::from(data_dir).join(PEERS_FILE))
+            .with_discouraged_path(PathBuf::from(data_dir).join(DISCOURAGED_FILE))
+            .with_shutdown(shutdown.clone());
         let network_arc = Arc::new(network);
         let metrics_arc = Arc::new(MetricsCollector::new());
         let profiler_arc = Arc::new(PerformanceProfiler::new(1000));
@@ -98,8 +136,16 @@ impl Node {
             .with_metrics(Arc::clone(&metrics_arc))
             .with_profiler(Arc::clone(&profiler_arc))
             .with_dependencies(Arc::clone(&storage_arc), Arc::clone(&mempool_manager_arc))
-            .with_network_manager(Arc::clone(&network_arc));
-        let sync_coordinator = sync::SyncCoordinator::default();
+            .with_network_manager(Arc::clone(&network_arc))
+            .with_minimum_chain_work(minimum_chain_work::default_minimum_chain_work(
+                protocol_version,
+            ));
+        let sync_coordinator = sync::SyncCoordinator::default()
+            .with_assume_valid(assume_valid::default_assume_valid(protocol_version))
+            .with_consensus_params(
+                chain_params::Network::from_protocol_version(protocol_version).consensus_params(),
+            )
+            .with_utxo_store(Some(storage_arc.utxos_arc()));
         let mining_coordinator = miner::MiningCoordinator::new(
             Arc::clone(&mempool_manager_arc),
             Some(Arc::clone(&storage_arc)),
@@ -110,8 +156,13 @@ impl Node {
         Ok(Self {
             protocol: protocol_arc,
             storage: storage_arc,
-            network: Arc::try_unwrap(network_arc)
-                .unwrap_or_else(|_| NetworkManager::new(network_addr)),
+            network: Arc::try_unwrap(network_arc).unwrap_or_else(|_| {
+                NetworkManager::new(network_addr)
+                    .with_anchors_path(PathBuf::from(data_dir).join(ANCHORS_FILE))
+                    .with_peers_path(PathBuf::from(data_dir).join(PEERS_FILE))
+                    .with_discouraged_path(PathBuf::from(data_dir).join(DISCOURAGED_FILE))
+                    .with_shutdown(shutdown.clone())
+            }),
             rpc,
             data_dir: PathBuf::from(data_dir),
             sync_coordinator,
@@ -122,11 +173,18 @@ impl Node {
             metrics,
             profiler,
             protocol_version,
+            chain_params: chain_params::ChainParams::new(
+                chain_params::Network::from_protocol_version(protocol_version),
+            ),
             network_addr,
             config: None,
             disk_check_counter: std::sync::atomic::AtomicU64::new(0),
             #[cfg(feature = "governance")]
             governance_webhook: None,
+            background_chainstate: None,
+            notifications: None,
+            shutdown,
+            stopped: false,
         })
     }
 
@@ -142,6 +200,24 @@ impl Node {
         // Use existing storage Arc instead of creating a new one
         let storage_arc = Arc::clone(&self.storage);
         let mempool_manager_arc = Arc::clone(&self.mempool_manager);
+        if let Some(ref relay) = config.relay {
+            self.mempool_manager
+                .set_relay_fees(relay.min_relay_tx_fee, relay.incremental_relay_fee);
+            self.mempool_manager
+                .set_dust_relay_fee(relay.dust_relay_fee);
+            self.mempool_manager
+                .set_datacarrier(relay.datacarrier, relay.datacarrier_size);
+        }
+        if let Some(ref mempool) = config.mempool {
+            self.mempool_manager.set_expiry(
+                mempool.mempool_expiry_hours * 3600,
+                mempool.expiry_sweep_interval_seconds,
+            );
+            if let Some(accept_non_std_txn) = mempool.accept_non_std_txn {
+                self.mempool_manager
+                    .set_require_standard(!accept_non_std_txn);
+            }
+        }
 
         let network = NetworkManager::with_config(
             network_addr,
@@ -149,16 +225,108 @@ impl Node {
             transport_preference,
             Some(&config),
         )
-        .with_dependencies(protocol_arc, storage_arc, mempool_manager_arc);
+        .with_dependencies(protocol_arc, storage_arc, mempool_manager_arc)
+        .with_anchors_path(self.data_dir.join(ANCHORS_FILE))
+        .with_peers_path(self.data_dir.join(PEERS_FILE))
+        .with_discouraged_path(self.data_dir.join(DISCOURAGED_FILE))
+        .with_shutdown(self.shutdown.clone());
 
         // Initialize governance webhook client if configured (from environment variables)
         #[cfg(feature = "governance")]
         let governance_webhook = std::env::var("GOVERNANCE_WEBHOOK_URL").ok().map(|url| {
-            crate::governance::GovernanceWebhookClient::new(
+            Arc::new(crate::governance::GovernanceWebhookClient::new(
                 Some(url),
                 std::env::var("GOVERNANCE_NODE_ID").ok(),
-            )
+            ))
         });
+        #[cfg(feature = "governance")]
+        if let Some(ref webhook) = governance_webhook {
+            self.rpc.set_governance_webhook(Arc::clone(webhook));
+        }
+
+        if let Some(ref request_timeouts) = config.request_timeouts {
+            self.rpc.set_request_timeouts(request_timeouts.clone());
+        }
+        if let Some(ref rpc_config) = config.rpc {
+            self.rpc
+                .set_circuit_breaker_config(rpc_config.circuit_breaker.clone());
+            self.rpc.set_work_queue(
+                rpc_config.threads,
+                rpc_config.work_queue,
+                rpc_config.long_running_threads,
+            );
+            self.rpc.set_allowed_ips(rpc_config.allow_ip.clone());
+        }
+        if let Some(ref rpc_tls) = config.rpc_tls {
+            self.rpc.set_tls_config(rpc_tls.clone());
+        }
+        if let Some(ref fee_forwarding) = config.fee_forwarding {
+            self.rpc.set_fee_forwarding(fee_forwarding.clone());
+        }
+
+        if let Some(cache) = config.storage.as_ref().and_then(|s| s.cache.as_ref()) {
+            self.storage.blocks().configure_cache(cache);
+        }
+        if config.storage.as_ref().is_some_and(|s| s.coinstatsindex) {
+            self.storage.coin_stats().set_enabled(true);
+            let tip_hash = self.storage.chain().get_tip_hash()?;
+            let index_hash = self
+                .storage
+                .coin_stats()
+                .tip()?
+                .map(|(stats, _)| stats.block_hash);
+            if tip_hash.is_some() && index_hash != tip_hash {
+                warn!("coinstatsindex is not at the chain tip; run reindexchainstate to build it");
+            }
+        }
+        if config.storage.as_ref().is_some_and(|s| s.addressindex) {
+            self.storage.addresses().set_enabled(true);
+            let tip_height = self.storage.chain().get_height()?;
+            let index_height = self.storage.addresses().tip()?.map(|tip| tip.height);
+            if tip_height.is_some() && index_height != tip_height {
+                warn!(
+                    "addressindex is not at the chain tip; run rebuildutxoindex from height {} to build it",
+                    index_height.map_or(0, |height| height + 1)
+                );
+            }
+        }
+        if let Some(ref hash) = config.assume_valid {
+            self.sync_coordinator
+                .set_assume_valid(assume_valid::parse_assume_valid(hash)?);
+        }
+        if let Some(ref work) = config.minimum_chain_work {
+            self.rpc
+                .set_minimum_chain_work(minimum_chain_work::parse_minimum_chain_work(work)?);
+        }
+        if let Some(ref name) = config.protocol_version {
+            // Selects among the networks sharing the engine's rules (testnet4, signet)
+            let network: chain_params::Network = name.parse()?;
+            if network.protocol_version() == self.protocol_version {
+                self.chain_params = chain_params::ChainParams::new(network);
+            } else {
+                warn!(
+                    "protocol_version {} does not match the node's {:?} rules, running on {}",
+                    name,
+                    self.protocol_version,
+                    self.chain_params.network.name()
+                );
+            }
+        }
+        if let Some(ref challenge) = config.signet_challenge {
+            if self.chain_params.network != chain_params::Network::Signet {
+                warn!("signet_challenge is ignored outside signet");
+            }
+            self.chain_params = self
+                .chain_params
+                .clone()
+                .with_signet_challenge(chain_params::parse_signet_challenge(challenge)?);
+        }
+        self.sync_coordinator
+            .set_signet_challenge(self.chain_params.signet_challenge.clone());
+        self.rpc
+            .set_signet_challenge(self.chain_params.signet_challenge.clone());
+        self.sync_coordinator
+            .set_consensus_params(self.chain_params.network.consensus_params());
 
         self.network = network;
         self.config = Some(config);
@@ -233,6 +401,27 @@ impl Node {
     async fn start_components(&mut self) -> Result<()> {
         info!("Starting node components");
 
+        // Repair any UTXO/chain tip mismatch left by a crash before accepting
+        // blocks. A failed repair leaves the node usable for RPC, so it starts
+        // anyway and the operator is told how to rebuild the UTXO set.
+        if let Err(e) = self.storage.recover_utxo_consistency() {
+            error!(
+                "Could not bring the UTXO set in line with the chain tip: {}. \
+                 Run the reindexchainstate RPC to rebuild it",
+                e
+            );
+        }
+
+        // Bind notification feeds before any block or transaction is accepted
+        if let Some(config) = self.config.as_ref().and_then(|c| c.notifications.as_ref()) {
+            let publisher =
+                Arc::new(notifications::NotificationPublisher::from_config(config).await?);
+            self.mempool_manager
+                .set_notifications(Arc::clone(&publisher));
+            self.rpc.set_notifications(Arc::clone(&publisher));
+            self.notifications = Some(publisher);
+        }
+
         // Simplified component startup
         // In a real implementation, each component would be started in separate tasks
         // For now, we'll just initialize them
@@ -252,6 +441,33 @@ impl Node {
         // Initialize peer connections automatically
         self.initialize_peer_connections().await?;
 
+        // Start Prometheus metrics endpoint if configured
+        if let Some(metrics_addr) = self.config.as_ref().and_then(|c| c.metrics_addr) {
+            let metrics_server = crate::rpc::metrics_server::MetricsServer::new(
+                metrics_addr,
+                Arc::clone(&self.metrics),
+            )
+            .with_profiler(Arc::clone(&self.profiler));
+            tokio::spawn(async move {
+                if let Err(e) = metrics_server.start().await {
+                    warn!("Metrics endpoint error: {}", e);
+                }
+            });
+        }
+
+        // Fill in filters for historical blocks before any startup pruning
+        if self
+            .config
+            .as_ref()
+            .and_then(|c| c.storage.as_ref())
+            .is_some_and(|s| s.blockfilterindex)
+        {
+            let builder = Arc::new(blockfilterindex::BlockFilterIndexBuilder::new(Arc::clone(
+                &self.storage,
+            )));
+            self.shutdown.spawn(builder.run());
+        }
+
         // Prune on startup if configured
         if let Some(pruning_manager) = self.storage.pruning() {
             let config = &pruning_manager.config;
@@ -361,13 +577,10 @@ impl Node {
 
     /// Initialize peer connections automatically
     ///
-    /// Determines network type from protocol version and uses config if available.
+    /// Determines network type from the chain parameters and uses config if available.
     async fn initialize_peer_connections(&self) -> Result<()> {
-        // Determine network type from protocol version
-        let network = match self.protocol_version {
-            ProtocolVersion::BitcoinV1 => "mainnet",
-            ProtocolVersion::Testnet3 => "testnet",
-            ProtocolVersion::Regtest => {
+        let network = match self.chain_params.network {
+            chain_params::Network::Regtest => {
                 // Regtest doesn't use DNS seeds
                 info!("Regtest network: skipping DNS seed discovery");
                 // Still connect to persistent peers if configured
@@ -385,6 +598,7 @@ impl Node {
                 }
                 return Ok(());
             }
+            network => network.name(),
         };
 
         // Get port from network address
@@ -420,245 +634,73 @@ impl Node {
         info!("Node running - main loop started");
 
         // Set up graceful shutdown signal handling
-        let shutdown_rx = crate::utils::create_shutdown_receiver();
-
-        // Get initial state for block processing
-        let mut current_height = self.storage.chain().get_height()?.unwrap_or(0);
-        let mut utxo_set = bllvm_protocol::UtxoSet::new();
+        self.shutdown.listen_for_signals();
+
+        // Blocks are validated and connected off the main loop, in order
+        let current_height = self.storage.chain().get_height()?.unwrap_or(0);
+        let mut pipeline = block_validation::BlockValidationPipeline::spawn(
+            block_validation::ChainBlockValidator::new(
+                Arc::clone(&self.storage),
+                self.sync_coordinator.clone(),
+                current_height,
+                Arc::clone(&self.metrics),
+                Arc::clone(&self.profiler),
+            )
+            .with_notifications(self.notifications.clone()),
+        );
 
         // Main node loop - coordinates between all components and handles shutdown signals
         loop {
             // Check for shutdown signal (non-blocking)
-            if *shutdown_rx.borrow() {
+            if self.shutdown.is_triggered() {
                 info!("Shutdown signal received, stopping node gracefully...");
                 break;
             }
-            // Process any received blocks (non-blocking)
-            while let Some(block_data) = self.network.try_recv_block() {
-                info!("Processing block from network");
-                let blocks_arc = self.storage.blocks();
-                match self.sync_coordinator.process_block(
-                    &*blocks_arc,
-                    &block_data,
-                    current_height,
-                    &mut utxo_set,
-                    Some(Arc::clone(&self.metrics)),
-                    Some(Arc::clone(&self.profiler)),
-                ) {
-                    Ok(true) => {
-                        info!("Block accepted at height {}", current_height);
-
-                        // Parse block for governance webhook (need block object, not just block_data)
-                        // We'll get it from storage after it's stored
-                        let blocks_arc = self.storage.blocks();
-                        let block_hash =
-                            if let Ok(Some(hash)) = blocks_arc.get_hash_by_height(current_height) {
-                                hash
-                            } else {
-                                warn!("Failed to get block hash for height {}", current_height);
-                                [0u8; 32]
-                            };
-
-                        // Update chain tip (for chainwork, etc.)
-                        if let Ok(Some(block)) = blocks_arc.get_block(&block_hash) {
-                            if let Err(e) = self.storage.chain().update_tip(
-                                &block_hash,
-                                &block.header,
-                                current_height,
-                            ) {
-                                warn!("Failed to update chain tip: {}", e);
-                            }
-
-                            // Update UTXO stats cache (for fast gettxoutsetinfo RPC)
-                            let transaction_count =
-                                self.storage.transaction_count().unwrap_or(0) as u64;
-                            if let Err(e) = self.storage.chain().update_utxo_stats_cache(
-                                &block_hash,
-                                current_height,
-                                &utxo_set,
-                                transaction_count,
-                            ) {
-                                warn!("Failed to update UTXO stats cache: {}", e);
-                            }
-
-                            // Update network hashrate cache (for fast getmininginfo RPC)
-                            if let Err(e) = self
-                                .storage
-                                .chain()
-                                .calculate_and_cache_network_hashrate(current_height, &*blocks_arc)
-                            {
-                                warn!("Failed to update network hashrate cache: {}", e);
-                            }
-
-                            // Notify governance app about new block (for fee forwarding tracking)
-                            #[cfg(feature = "governance")]
-                            if let Some(ref webhook) = self.governance_webhook {
-                                if let Err(e) = webhook.notify_block(&block, current_height).await {
-                                    warn!("Failed to notify governance app about block at height {}: {}", current_height, e);
-                                }
-                            }
-                        }
-
-                        // Persist UTXO set to storage after block validation
-                        // This is critical for commitment generation and incremental pruning
-                        if let Err(e) = self.storage.utxos().store_utxo_set(&utxo_set) {
-                            warn!(
-                                "Failed to persist UTXO set after block {}: {}",
-                                current_height, e
-                            );
-                        }
-
-                        // Generate UTXO commitment from current state (if enabled)
-                        // Use current_height (the block that was just validated) before incrementing
-                        #[cfg(feature = "utxo-commitments")]
-                        {
-                            if let Some(pruning_manager) = self.storage.pruning() {
-                                if let (Some(commitment_store), Some(_utxostore)) = (
-                                    pruning_manager.commitment_store(),
-                                    pruning_manager.utxostore(),
-                                ) {
-                                    // Get block hash from storage (block was just stored at current_height)
-                                    let blocks_arc = self.storage.blocks();
-                                    if let Ok(Some(block_hash)) =
-                                        blocks_arc.get_hash_by_height(current_height)
-                                    {
-                                        // Generate commitment from current UTXO set state
-                                        if let Err(e) = pruning_manager
-                                            .generate_commitment_from_current_state(
-                                                &block_hash,
-                                                current_height,
-                                                &utxo_set,
-                                                &commitment_store,
-                                            )
-                                        {
-                                            warn!(
-                                                "Failed to generate commitment for block {}: {}",
-                                                current_height, e
-                                            );
-                                        } else {
-                                            debug!(
-                                                "Generated UTXO commitment for block {}",
-                                                current_height
-                                            );
-                                        }
-                                    } else {
-                                        warn!("Could not find block hash for height {} to generate commitment", current_height);
-                                    }
-                                }
-                            }
-                        }
-
-                        // Increment height after processing
-                        current_height += 1;
-
-                        // Check for incremental pruning during IBD
-                        // Consider IBD if we're still syncing (height < tip or no recent blocks)
-                        let is_ibd = current_height < 1000; // Simple heuristic: consider IBD if < 1000 blocks
-                        if let Some(pruning_manager) = self.storage.pruning() {
-                            if let Ok(Some(prune_stats)) =
-                                pruning_manager.incremental_prune_during_ibd(current_height, is_ibd)
-                            {
-                                info!("Incremental pruning during IBD: {} blocks pruned, {} bytes freed", 
-                                      prune_stats.blocks_pruned, prune_stats.storage_freed);
-                                // Flush storage to persist pruning changes
-                                if let Err(e) = self.storage.flush() {
-                                    warn!(
-                                        "Failed to flush storage after incremental pruning: {}",
-                                        e
-                                    );
-                                }
-                            }
-                        }
-
-                        // Check for automatic pruning after block acceptance
-                        if let Some(pruning_manager) = self.storage.pruning() {
-                            let stats = pruning_manager.get_stats();
-                            let should_prune = pruning_manager
-                                .should_auto_prune(current_height, stats.last_prune_height);
-
-                            if should_prune {
-                                info!("Automatic pruning triggered at height {}", current_height);
-
-                                // Calculate prune height based on configuration
-                                let prune_height = match &pruning_manager.config.mode {
-                                    crate::config::PruningMode::Disabled => None,
-                                    crate::config::PruningMode::Normal {
-                                        keep_from_height, ..
-                                    } => {
-                                        // Prune to keep_from_height, but ensure we keep min_blocks
-                                        let min_keep = pruning_manager.config.min_blocks_to_keep;
-                                        let effective_keep = (*keep_from_height)
-                                            .max(current_height.saturating_sub(min_keep));
-                                        Some(effective_keep)
-                                    }
-                                    #[cfg(feature = "utxo-commitments")]
-                                    crate::config::PruningMode::Aggressive {
-                                        keep_from_height,
-                                        min_blocks,
-                                        ..
-                                    } => {
-                                        // Prune to keep_from_height, respecting min_blocks
-                                        let effective_keep = (*keep_from_height)
-                                            .max(current_height.saturating_sub(*min_blocks));
-                                        Some(effective_keep)
-                                    }
-                                    #[cfg(not(feature = "utxo-commitments"))]
-                                    crate::config::PruningMode::Aggressive { .. } => {
-                                        // Aggressive pruning requires utxo-commitments feature
-                                        // Fall back to no pruning if feature is disabled
-                                        None
-                                    }
-                                    crate::config::PruningMode::Custom {
-                                        keep_bodies_from_height,
-                                        ..
-                                    } => {
-                                        // Prune to keep_bodies_from_height, respecting min_blocks
-                                        let min_keep = pruning_manager.config.min_blocks_to_keep;
-                                        let effective_keep = (*keep_bodies_from_height)
-                                            .max(current_height.saturating_sub(min_keep));
-                                        Some(effective_keep)
-                                    }
-                                };
-
-                                if let Some(prune_to_height) = prune_height {
-                                    if prune_to_height < current_height {
-                                        match pruning_manager.prune_to_height(
-                                            prune_to_height,
-                                            current_height,
-                                            false,
-                                        ) {
-                                            Ok(prune_stats) => {
-                                                info!("Automatic pruning completed: {} blocks pruned, {} blocks kept", 
-                                                      prune_stats.blocks_pruned, prune_stats.blocks_kept);
-                                                // Flush storage to persist pruning changes
-                                                use crate::utils::log_error;
-                                                log_error(|| self.storage.flush(), "Failed to flush storage after automatic pruning");
-                                            }
-                                            Err(e) => {
-                                                warn!("Automatic pruning failed: {}", e);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Ok(false) => {
-                        warn!("Block rejected at height {}", current_height);
-                    }
-                    Err(e) => {
-                        warn!("Error processing block: {}", e);
+            // Hand received blocks to the validation worker (non-blocking),
+            // leaving them queued in the network layer while the worker is behind
+            while pipeline.has_capacity() {
+                let Some(block_data) = self.network.try_recv_block() else {
+                    break;
+                };
+                debug!("Queueing block from network for validation");
+                let mut block = block_validation::QueuedBlock::new(block_data, None);
+                block.source = match block.header() {
+                    Some(ref header) => {
+                        self.network
+                            .take_block_source(
+                                &crate::network::compact_blocks::calculate_block_hash(header),
+                            )
+                            .await
                     }
+                    None => None,
+                };
+                if let Err(e) = pipeline.submit(block) {
+                    warn!("Failed to queue block for validation: {}", e);
                 }
             }
 
+            // Act on blocks the worker has finished with
+            while let Some(event) = pipeline.try_recv_event() {
+                self.handle_block_validation_event(event).await;
+            }
+
             // Process other network messages (non-blocking, processes one message if available)
             // Note: This is a simplified approach - in production, network processing
             // would run in a separate task
-            if let Err(e) = self.network.process_messages().await {
+            let message_timer = PerformanceTimer::start(
+                Arc::clone(&self.profiler),
+                OperationType::MessageProcessing,
+            );
+            let message_result = self.network.process_messages().await;
+            message_timer.stop();
+            if let Err(e) = message_result {
                 warn!("Error processing network messages: {}", e);
             }
 
+            if let Err(e) = self.network.flush_tx_inventory().await {
+                warn!("Failed to announce queued transactions: {}", e);
+            }
+
             tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
             // Check node health periodically
@@ -670,6 +712,40 @@ impl Node {
                 .disk_check_counter
                 .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             if counter % 10 == 0 {
+                self.refresh_metrics().await;
+
+                if let Err(e) = self.network.maintain_outbound_connections().await {
+                    warn!("Outbound connection maintenance failed: {}", e);
+                }
+
+                if let Err(e) = self.network.maintain_persistent_peers().await {
+                    warn!("Persistent peer maintenance failed: {}", e);
+                }
+
+                if let Err(e) = self.network.maintain_ban_list_sharing().await {
+                    warn!("Ban list sharing failed: {}", e);
+                }
+
+                if let Err(e) = self.network.maintain_self_advertisement().await {
+                    warn!("Self-advertisement failed: {}", e);
+                }
+
+                if let Err(e) = self.network.maintain_addr_relay().await {
+                    warn!("Address relay failed: {}", e);
+                }
+
+                if let Err(e) = self.network.check_peer_liveness().await {
+                    warn!("Peer liveness check failed: {}", e);
+                }
+
+                if let Err(e) = self.network.check_block_download_stalls().await {
+                    warn!("Block download stall check failed: {}", e);
+                }
+
+                if let Err(e) = self.network.process_dandelion_timeouts().await {
+                    warn!("Dandelion stem timeout processing failed: {}", e);
+                }
+
                 use crate::utils::with_storage_timeout;
                 match with_storage_timeout(async { self.check_disk_space().await }).await {
                     Ok(Ok(())) => {
@@ -689,10 +765,51 @@ impl Node {
 
         // Graceful shutdown - stop all components
         info!("Initiating graceful shutdown...");
+        pipeline.shutdown().await;
         self.stop().await?;
         Ok(())
     }
 
+    /// Follow up on a block the validation worker has finished with
+    async fn handle_block_validation_event(&self, event: block_validation::BlockValidationEvent) {
+        match event {
+            block_validation::BlockValidationEvent::Accepted { height, block_hash } => {
+                // Notify governance app about new block (for fee forwarding tracking)
+                #[cfg(feature = "governance")]
+                if let Some(ref webhook) = self.governance_webhook {
+                    if let Ok(Some(block)) = self.storage.blocks().get_block(&block_hash) {
+                        if let Err(e) = webhook.notify_block(&block, height).await {
+                            warn!(
+                                "Failed to notify governance app about block at height {}: {}",
+                                height, e
+                            );
+                        }
+                    }
+                }
+                #[cfg(not(feature = "governance"))]
+                let _ = (height, block_hash);
+            }
+            block_validation::BlockValidationEvent::Rejected {
+                source,
+                builds_on_tip,
+                ..
+            } => {
+                // Only blame the sender if the block builds on our tip;
+                // otherwise it wasn't validated in its own context
+                if let Some(peer_addr) = source.filter(|_| builds_on_tip) {
+                    self.network
+                        .report_misbehavior(
+                            peer_addr,
+                            crate::network::dos_protection::MISBEHAVIOR_INVALID_BLOCK,
+                            "consensus-invalid block",
+                        )
+                        .await;
+                }
+            }
+            block_validation::BlockValidationEvent::Failed { .. } => {}
+        }
+    }
+
     /// Run node processing once (for testing)
     pub async fn run_once(&mut self) -> Result<()> {
         info!("Running node processing once");
@@ -732,6 +849,29 @@ impl Node {
         Ok(())
     }
 
+    /// Refresh gauge metrics that are sampled rather than recorded on events
+    async fn refresh_metrics(&self) {
+        let network_stats = self.network.get_network_stats().await;
+        self.metrics.update_network(|m| *m = network_stats);
+
+        let chain_height = self.storage.chain().get_height().ok().flatten();
+        let cache_stats = self.storage.blocks().cache_stats();
+        self.metrics.update_storage(|m| {
+            if let Some(height) = chain_height {
+                m.chain_height = height;
+            }
+            m.header_cache_hit_rate = cache_stats.headers.hit_rate();
+            m.block_cache_hit_rate = cache_stats.blocks.hit_rate();
+        });
+
+        let mempool_size = self.mempool_manager.size();
+        let mempool_bytes = self.mempool_manager.bytes();
+        self.metrics.update_mempool(|m| {
+            m.size = mempool_size;
+            m.bytes = mempool_bytes as u64;
+        });
+    }
+
     /// Check disk space and trigger pruning if needed
     async fn check_disk_space(&self) -> Result<()> {
         // Check storage bounds (80% threshold)
@@ -816,25 +956,77 @@ impl Node {
         Ok(())
     }
 
+    /// Shutdown handle for this node
+    ///
+    /// Triggering it makes the run loop exit and stop the node; tasks spawned
+    /// through it are cancelled.
+    pub fn shutdown_handle(&self) -> ShutdownController {
+        self.shutdown.clone()
+    }
+
     /// Stop the node
+    ///
+    /// Stops the listeners and background tasks, disconnects peers, persists
+    /// the address database and mempool, then flushes storage. Tasks get
+    /// `SHUTDOWN_TIMEOUT` to finish before they are aborted. Calling `stop`
+    /// again does nothing.
     pub async fn stop(&mut self) -> Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+        self.stopped = true;
         info!("Stopping reference-node");
 
+        // Stop accepting connections and cancel background tasks
+        self.shutdown.trigger();
+
         // Stop module manager
         if let Some(ref mut module_manager) = self.module_manager {
-            module_manager
-                .shutdown()
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to shutdown module manager: {}", e))?;
+            if let Err(e) = module_manager.shutdown().await {
+                warn!("Failed to shutdown module manager: {}", e);
+            }
+        }
+
+        // Persist anchor peers for reconnection on restart
+        if let Err(e) = self.network.save_anchors().await {
+            warn!("Failed to save anchor peers: {}", e);
+        }
+        if let Err(e) = self.network.save_peers().await {
+            warn!("Failed to save peer addresses: {}", e);
+        }
+        if let Err(e) = self.network.save_discouraged().await {
+            warn!("Failed to save discouraged addresses: {}", e);
+        }
+        let disconnected = self.network.disconnect_all_peers().await;
+        if disconnected > 0 {
+            info!("Disconnected {} peer(s)", disconnected);
+        }
+
+        // Persist the mempool where `savemempool` writes it
+        if let Err(e) = self
+            .mempool_manager
+            .save_to_disk(self.data_dir.join(MEMPOOL_FILE))
+        {
+            warn!("Failed to save mempool: {}", e);
         }
 
         // Stop all components
         self.rpc.stop()?;
 
+        if !self.shutdown.wait_for_tasks(SHUTDOWN_TIMEOUT).await {
+            warn!(
+                "Background tasks did not stop within {:?}",
+                SHUTDOWN_TIMEOUT
+            );
+        }
+
         // Flush storage
         self.storage.flush()?;
 
         info!("Node stopped");
+
+        // Make sure buffered log lines reach the log file
+        crate::utils::flush_log_file();
         Ok(())
     }
 
@@ -863,6 +1055,11 @@ impl Node {
         &*self.protocol
     }
 
+    /// Get network parameters
+    pub fn chain_params(&self) -> &chain_params::ChainParams {
+        &self.chain_params
+    }
+
     /// Get storage
     pub fn storage(&self) -> &Storage {
         &self.storage
@@ -878,22 +1075,28 @@ impl Node {
         &self.rpc
     }
 
+    /// Start tracking background validation of a loaded UTXO snapshot
+    ///
+    /// The chainstate is reported by `getchainstates` until the node shuts
+    /// down or it is replaced.
+    pub fn set_background_chainstate(
+        &mut self,
+        chainstate: Arc<chainstates::BackgroundChainstate>,
+    ) {
+        self.rpc.set_background_chainstate(Arc::clone(&chainstate));
+        self.background_chainstate = Some(chainstate);
+    }
+
+    /// Background chainstate, if a UTXO snapshot is in use
+    pub fn background_chainstate(&self) -> Option<&Arc<chainstates::BackgroundChainstate>> {
+        self.background_chainstate.as_ref()
+    }
+
     /// Get health report
-    pub fn health_check(&self) -> health::HealthReport {
-        use crate::node::health::HealthChecker;
-
-        let checker = HealthChecker::new();
-        let network_healthy = self.network.is_network_active();
-        let storage_healthy = self.storage.check_storage_bounds().unwrap_or(false);
-        let rpc_healthy = true; // RPC is always healthy if node is running
-
-        // Get metrics if available (simplified for now)
-        checker.check_health(
-            network_healthy,
-            storage_healthy,
-            rpc_healthy,
-            None, // Network metrics - would need to be passed from NetworkManager
-            None, // Storage metrics - would need to be collected
-        )
+    pub async fn health_check(&self) -> health::HealthReport {
+        // RPC is always healthy if node is running
+        health::HealthChecker::new()
+            .check_node(Some(&self.network), Some(&self.storage), true)
+            .await
     }
 }
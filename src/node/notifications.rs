@@ -0,0 +1,291 @@
+//! Block and transaction notifications
+//!
+//! A ZMQ-style publish/subscribe feed for external tools. Each topic
+//! (`hashblock`, `hashtx`, `rawblock`, `rawtx`) is served on its own TCP
+//! address from the `notifications` configuration; a subscriber connects and
+//! receives every message published after it connected. Like ZMQ's three-part
+//! messages, each message carries the topic, a body and a per-topic sequence
+//! number:
+//!
+//! ```text
+//! topic length (1 byte) | topic | body length (u32 LE) | body | sequence (u32 LE)
+//! ```
+//!
+//! Hash bodies are 32 bytes in the byte order RPC methods use; raw bodies are
+//! the serialized block or transaction. Publishing never waits on
+//! subscribers: each one has a bounded queue and the oldest message is
+//! dropped when it is full, so a slow reader cannot hold up validation.
+
+use crate::config::NotificationsConfig;
+use anyhow::Result;
+use bllvm_protocol::{Hash, Transaction};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// Default number of messages queued per subscriber
+pub const DEFAULT_NOTIFICATION_QUEUE_SIZE: usize = 1000;
+
+/// Notification stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationTopic {
+    HashBlock,
+    HashTx,
+    RawBlock,
+    RawTx,
+}
+
+impl NotificationTopic {
+    /// All topics
+    pub const ALL: [NotificationTopic; 4] = [
+        NotificationTopic::HashBlock,
+        NotificationTopic::HashTx,
+        NotificationTopic::RawBlock,
+        NotificationTopic::RawTx,
+    ];
+
+    /// Topic name sent with every message
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationTopic::HashBlock => "hashblock",
+            NotificationTopic::HashTx => "hashtx",
+            NotificationTopic::RawBlock => "rawblock",
+            NotificationTopic::RawTx => "rawtx",
+        }
+    }
+}
+
+/// A connected subscriber's outgoing queue
+#[derive(Debug)]
+struct Subscriber {
+    queue: Mutex<VecDeque<Arc<Vec<u8>>>>,
+    ready: Notify,
+    closed: AtomicBool,
+}
+
+/// Subscribers and sequence number of one topic
+#[derive(Debug, Default)]
+struct TopicChannel {
+    sequence: AtomicU32,
+    subscribers: Mutex<Vec<Arc<Subscriber>>>,
+    bound: AtomicBool,
+}
+
+/// Publishes block and transaction notifications to TCP subscribers
+#[derive(Debug)]
+pub struct NotificationPublisher {
+    topics: HashMap<NotificationTopic, Arc<TopicChannel>>,
+    queue_size: usize,
+    dropped: AtomicU64,
+}
+
+impl NotificationPublisher {
+    /// Create a publisher with no topics bound
+    pub fn new(queue_size: usize) -> Self {
+        Self {
+            topics: NotificationTopic::ALL
+                .iter()
+                .map(|topic| (*topic, Arc::new(TopicChannel::default())))
+                .collect(),
+            queue_size: queue_size.max(1),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Create a publisher and bind every topic configured with an address
+    pub async fn from_config(config: &NotificationsConfig) -> Result<Self> {
+        let publisher = Self::new(config.queue_size);
+        let addresses = [
+            (NotificationTopic::HashBlock, config.hashblock),
+            (NotificationTopic::HashTx, config.hashtx),
+            (NotificationTopic::RawBlock, config.rawblock),
+            (NotificationTopic::RawTx, config.rawtx),
+        ];
+        for (topic, addr) in addresses {
+            if let Some(addr) = addr {
+                publisher.bind(topic, addr).await?;
+            }
+        }
+        Ok(publisher)
+    }
+
+    /// Serve a topic on `addr`, returning the bound address
+    ///
+    /// Each topic needs its own address.
+    pub async fn bind(&self, topic: NotificationTopic, addr: SocketAddr) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!(
+            "Publishing {} notifications on {}",
+            topic.as_str(),
+            local_addr
+        );
+
+        let channel = Arc::clone(&self.topics[&topic]);
+        channel.bound.store(true, Ordering::SeqCst);
+        let queue_size = self.queue_size;
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        debug!("New {} subscriber {}", topic.as_str(), peer);
+                        let subscriber = Arc::new(Subscriber {
+                            queue: Mutex::new(VecDeque::with_capacity(queue_size.min(64))),
+                            ready: Notify::new(),
+                            closed: AtomicBool::new(false),
+                        });
+                        channel
+                            .subscribers
+                            .lock()
+                            .unwrap()
+                            .push(Arc::clone(&subscriber));
+                        tokio::spawn(serve_subscriber(stream, subscriber));
+                    }
+                    Err(e) => {
+                        warn!("Failed to accept {} subscriber: {}", topic.as_str(), e);
+                    }
+                }
+            }
+        });
+        Ok(local_addr)
+    }
+
+    /// Whether a topic is being served
+    pub fn is_enabled(&self, topic: NotificationTopic) -> bool {
+        self.topics[&topic].bound.load(Ordering::SeqCst)
+    }
+
+    /// Number of connected subscribers to a topic
+    pub fn subscriber_count(&self, topic: NotificationTopic) -> usize {
+        let mut subscribers = self.topics[&topic].subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| !subscriber.closed.load(Ordering::SeqCst));
+        subscribers.len()
+    }
+
+    /// Messages dropped because a subscriber's queue was full
+    pub fn dropped_messages(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue a message for every subscriber to a topic
+    pub fn publish(&self, topic: NotificationTopic, body: &[u8]) {
+        let channel = &self.topics[&topic];
+        let mut subscribers = channel.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| !subscriber.closed.load(Ordering::SeqCst));
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let sequence = channel.sequence.fetch_add(1, Ordering::SeqCst);
+        let message = Arc::new(encode_message(topic, body, sequence));
+        for subscriber in subscribers.iter() {
+            {
+                let mut queue = subscriber.queue.lock().unwrap();
+                if queue.len() >= self.queue_size {
+                    queue.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                queue.push_back(Arc::clone(&message));
+            }
+            subscriber.ready.notify_one();
+        }
+    }
+
+    /// Publish `hashblock` and `rawblock` for a newly connected block
+    pub fn notify_block(&self, block_hash: &Hash, raw_block: &[u8]) {
+        self.publish(NotificationTopic::HashBlock, block_hash);
+        self.publish(NotificationTopic::RawBlock, raw_block);
+    }
+
+    /// Publish `hashtx` and `rawtx` for a transaction accepted to the mempool
+    pub fn notify_transaction(&self, tx: &Transaction) {
+        use bllvm_protocol::block::calculate_tx_id;
+        use bllvm_protocol::serialization::transaction::serialize_transaction;
+
+        if self.has_subscribers(NotificationTopic::HashTx) {
+            self.publish(NotificationTopic::HashTx, &calculate_tx_id(tx));
+        }
+        if self.has_subscribers(NotificationTopic::RawTx) {
+            self.publish(NotificationTopic::RawTx, &serialize_transaction(tx));
+        }
+    }
+
+    fn has_subscribers(&self, topic: NotificationTopic) -> bool {
+        !self.topics[&topic].subscribers.lock().unwrap().is_empty()
+    }
+}
+
+/// Frame a notification message (see the module documentation)
+pub fn encode_message(topic: NotificationTopic, body: &[u8], sequence: u32) -> Vec<u8> {
+    let name = topic.as_str().as_bytes();
+    let mut message = Vec::with_capacity(1 + name.len() + 4 + body.len() + 4);
+    message.push(name.len() as u8);
+    message.extend_from_slice(name);
+    message.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    message.extend_from_slice(body);
+    message.extend_from_slice(&sequence.to_le_bytes());
+    message
+}
+
+/// Write queued messages to a subscriber until it disconnects
+async fn serve_subscriber(mut stream: TcpStream, subscriber: Arc<Subscriber>) {
+    loop {
+        let next = subscriber.queue.lock().unwrap().pop_front();
+        match next {
+            Some(message) => {
+                if let Err(e) = stream.write_all(&message).await {
+                    debug!("Notification subscriber disconnected: {}", e);
+                    break;
+                }
+            }
+            None => subscriber.ready.notified().await,
+        }
+    }
+    subscriber.closed.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_message() {
+        let message = encode_message(NotificationTopic::HashTx, &[0xab; 32], 7);
+        assert_eq!(message[0], 6);
+        assert_eq!(&message[1..7], b"hashtx");
+        assert_eq!(&message[7..11], &32u32.to_le_bytes());
+        assert_eq!(&message[11..43], &[0xab; 32]);
+        assert_eq!(&message[43..], &7u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_full_queue_drops_oldest() {
+        let publisher = NotificationPublisher::new(2);
+        let subscriber = Arc::new(Subscriber {
+            queue: Mutex::new(VecDeque::new()),
+            ready: Notify::new(),
+            closed: AtomicBool::new(false),
+        });
+        publisher.topics[&NotificationTopic::HashBlock]
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Arc::clone(&subscriber));
+
+        for i in 0..3u8 {
+            publisher.publish(NotificationTopic::HashBlock, &[i; 32]);
+        }
+        let queue = subscriber.queue.lock().unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(
+            *queue[0],
+            encode_message(NotificationTopic::HashBlock, &[1; 32], 1)
+        );
+        assert_eq!(publisher.dropped_messages(), 1);
+    }
+}
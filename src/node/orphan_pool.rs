@@ -0,0 +1,276 @@
+//! Orphan transaction pool
+//!
+//! Holds transactions received from peers whose inputs are unknown (in neither
+//! the UTXO set nor the mempool), usually because their parents haven't
+//! arrived yet. Orphans are also a source of transactions for compact block
+//! reconstruction. The pool is bounded in count and entry age.
+
+use bllvm_protocol::block::calculate_tx_id;
+use bllvm_protocol::serialization::transaction::serialize_transaction;
+use bllvm_protocol::{Hash, OutPoint, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+/// Default maximum number of orphan transactions kept
+pub const DEFAULT_MAX_ORPHAN_TRANSACTIONS: usize = 100;
+
+/// Orphans older than this are dropped (seconds)
+pub const ORPHAN_TX_EXPIRE_SECONDS: u64 = 20 * 60;
+
+/// Largest transaction accepted as an orphan (serialized bytes)
+pub const MAX_ORPHAN_TX_SIZE: usize = 100_000;
+
+/// An orphan transaction and where it came from
+#[derive(Debug, Clone)]
+pub struct OrphanEntry {
+    /// The transaction
+    pub tx: Transaction,
+    /// Serialized size in bytes
+    pub bytes: usize,
+    /// When the orphan was added (Unix timestamp)
+    pub entry_time: u64,
+    /// Peer that sent the transaction
+    pub from_peer: SocketAddr,
+}
+
+/// Bounded pool of orphan transactions, keyed by txid
+#[derive(Debug)]
+pub struct OrphanPool {
+    orphans: HashMap<Hash, OrphanEntry>,
+    max_orphans: usize,
+    total_bytes: usize,
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrphanPool {
+    /// Create an empty pool holding up to `DEFAULT_MAX_ORPHAN_TRANSACTIONS`
+    pub fn new() -> Self {
+        Self {
+            orphans: HashMap::new(),
+            max_orphans: DEFAULT_MAX_ORPHAN_TRANSACTIONS,
+            total_bytes: 0,
+        }
+    }
+
+    /// Set the maximum number of orphans kept
+    pub fn with_max_orphans(mut self, max_orphans: usize) -> Self {
+        self.max_orphans = max_orphans;
+        self
+    }
+
+    /// Add an orphan received from `from_peer`
+    ///
+    /// Expired orphans are dropped first; if the pool is still full the
+    /// oldest orphan is evicted. Returns false if the transaction is already
+    /// pooled or too large.
+    pub fn add(&mut self, tx: Transaction, from_peer: SocketAddr, now: u64) -> bool {
+        let txid = calculate_tx_id(&tx);
+        if self.orphans.contains_key(&txid) || self.max_orphans == 0 {
+            return false;
+        }
+        let bytes = serialize_transaction(&tx).len();
+        if bytes > MAX_ORPHAN_TX_SIZE {
+            return false;
+        }
+
+        self.expire(now);
+        while self.orphans.len() >= self.max_orphans {
+            let Some(oldest) = self
+                .orphans
+                .iter()
+                .min_by_key(|(txid, entry)| (entry.entry_time, **txid))
+                .map(|(txid, _)| *txid)
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+
+        self.total_bytes += bytes;
+        self.orphans.insert(
+            txid,
+            OrphanEntry {
+                tx,
+                bytes,
+                entry_time: now,
+                from_peer,
+            },
+        );
+        true
+    }
+
+    /// Remove an orphan by txid
+    pub fn remove(&mut self, txid: &Hash) -> Option<OrphanEntry> {
+        let entry = self.orphans.remove(txid)?;
+        self.total_bytes -= entry.bytes;
+        Some(entry)
+    }
+
+    /// Check whether an orphan is pooled
+    pub fn contains(&self, txid: &Hash) -> bool {
+        self.orphans.contains_key(txid)
+    }
+
+    /// Get an orphan by txid
+    pub fn get(&self, txid: &Hash) -> Option<&OrphanEntry> {
+        self.orphans.get(txid)
+    }
+
+    /// Number of pooled orphans
+    pub fn len(&self) -> usize {
+        self.orphans.len()
+    }
+
+    /// Check whether the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.orphans.is_empty()
+    }
+
+    /// Maximum number of orphans kept
+    pub fn max_orphans(&self) -> usize {
+        self.max_orphans
+    }
+
+    /// Total serialized size of pooled orphans
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    /// Pooled orphans and their txids
+    pub fn entries(&self) -> impl Iterator<Item = (&Hash, &OrphanEntry)> {
+        self.orphans.iter()
+    }
+
+    /// Pooled orphan transactions
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.orphans.values().map(|entry| &entry.tx)
+    }
+
+    /// Drop orphans older than `ORPHAN_TX_EXPIRE_SECONDS`; returns how many
+    pub fn expire(&mut self, now: u64) -> usize {
+        self.remove_where(|entry| now.saturating_sub(entry.entry_time) >= ORPHAN_TX_EXPIRE_SECONDS)
+    }
+
+    /// Drop orphans sent by a peer; returns how many
+    pub fn remove_for_peer(&mut self, peer: SocketAddr) -> usize {
+        self.remove_where(|entry| entry.from_peer == peer)
+    }
+
+    /// Drop orphans included in a block or conflicting with it; returns how many
+    pub fn remove_for_block(&mut self, block_txs: &[Transaction]) -> usize {
+        let txids: HashSet<Hash> = block_txs.iter().map(calculate_tx_id).collect();
+        let spent: HashSet<&OutPoint> = block_txs
+            .iter()
+            .flat_map(|tx| tx.inputs.iter().map(|input| &input.prevout))
+            .collect();
+        let included: Vec<Hash> = self
+            .orphans
+            .iter()
+            .filter(|(txid, entry)| {
+                txids.contains(*txid)
+                    || entry
+                        .tx
+                        .inputs
+                        .iter()
+                        .any(|input| spent.contains(&input.prevout))
+            })
+            .map(|(txid, _)| *txid)
+            .collect();
+        for txid in &included {
+            self.remove(txid);
+        }
+        included.len()
+    }
+
+    fn remove_where(&mut self, predicate: impl Fn(&OrphanEntry) -> bool) -> usize {
+        let matching: Vec<Hash> = self
+            .orphans
+            .iter()
+            .filter(|(_, entry)| predicate(entry))
+            .map(|(txid, _)| *txid)
+            .collect();
+        for txid in &matching {
+            self.remove(txid);
+        }
+        matching.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bllvm_protocol::{TransactionInput, TransactionOutput};
+
+    fn orphan_tx(parent: u8) -> Transaction {
+        Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [parent; 32],
+                    index: 0,
+                },
+                script_sig: vec![0x51],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        }
+    }
+
+    #[test]
+    fn test_orphan_pool_evicts_oldest_when_full() {
+        let peer: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let mut pool = OrphanPool::new().with_max_orphans(2);
+        assert!(pool.add(orphan_tx(1), peer, 100));
+        assert!(!pool.add(orphan_tx(1), peer, 101));
+        assert!(pool.add(orphan_tx(2), peer, 102));
+        assert!(pool.add(orphan_tx(3), peer, 103));
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&calculate_tx_id(&orphan_tx(1))));
+        assert!(pool.contains(&calculate_tx_id(&orphan_tx(3))));
+        assert_eq!(
+            pool.total_bytes(),
+            2 * serialize_transaction(&orphan_tx(1)).len()
+        );
+    }
+
+    #[test]
+    fn test_orphan_pool_expiry_and_peer_removal() {
+        let peer_a: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let peer_b: SocketAddr = "127.0.0.2:8333".parse().unwrap();
+        let mut pool = OrphanPool::new();
+        pool.add(orphan_tx(1), peer_a, 100);
+        pool.add(orphan_tx(2), peer_b, 100 + ORPHAN_TX_EXPIRE_SECONDS / 2);
+        pool.add(orphan_tx(3), peer_b, 100 + ORPHAN_TX_EXPIRE_SECONDS / 2);
+
+        assert_eq!(pool.expire(100 + ORPHAN_TX_EXPIRE_SECONDS), 1);
+        assert_eq!(pool.remove_for_peer(peer_b), 2);
+        assert!(pool.is_empty());
+        assert_eq!(pool.total_bytes(), 0);
+    }
+
+    #[test]
+    fn test_orphan_pool_remove_for_block() {
+        let peer: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let mut pool = OrphanPool::new();
+        pool.add(orphan_tx(1), peer, 100);
+        pool.add(orphan_tx(2), peer, 100);
+        pool.add(orphan_tx(3), peer, 100);
+
+        // Includes orphan 1 and double-spends orphan 2's input
+        let mut conflicting = orphan_tx(2);
+        conflicting.lock_time = 1;
+        assert_eq!(pool.remove_for_block(&[orphan_tx(1), conflicting]), 2);
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(&calculate_tx_id(&orphan_tx(3))));
+    }
+}
@@ -3,20 +3,29 @@
 //! Provides performance tracking, profiling hooks, and performance metrics collection.
 
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 /// Performance profiler for tracking operation timings
+///
+/// Each operation type keeps a ring buffer of the most recent `max_samples`
+/// timings. Statistics are computed on demand by sorting a snapshot of the
+/// buffer, so recording stays O(1).
 pub struct PerformanceProfiler {
     /// Block processing times
-    block_processing_times: Arc<Mutex<Vec<Duration>>>,
+    block_processing_times: Arc<Mutex<VecDeque<Duration>>>,
     /// Transaction validation times
-    tx_validation_times: Arc<Mutex<Vec<Duration>>>,
+    tx_validation_times: Arc<Mutex<VecDeque<Duration>>>,
     /// Storage operation times
-    storage_operation_times: Arc<Mutex<Vec<Duration>>>,
+    storage_operation_times: Arc<Mutex<VecDeque<Duration>>>,
     /// Network operation times
-    network_operation_times: Arc<Mutex<Vec<Duration>>>,
+    network_operation_times: Arc<Mutex<VecDeque<Duration>>>,
+    /// UTXO set application times
+    utxo_application_times: Arc<Mutex<VecDeque<Duration>>>,
+    /// Network message processing times
+    message_processing_times: Arc<Mutex<VecDeque<Duration>>>,
     /// Maximum samples to keep per operation type
     max_samples: usize,
 }
@@ -25,48 +34,53 @@ impl PerformanceProfiler {
     /// Create a new performance profiler
     pub fn new(max_samples: usize) -> Self {
         Self {
-            block_processing_times: Arc::new(Mutex::new(Vec::new())),
-            tx_validation_times: Arc::new(Mutex::new(Vec::new())),
-            storage_operation_times: Arc::new(Mutex::new(Vec::new())),
-            network_operation_times: Arc::new(Mutex::new(Vec::new())),
+            block_processing_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
+            tx_validation_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
+            storage_operation_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
+            network_operation_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
+            utxo_application_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
+            message_processing_times: Arc::new(Mutex::new(VecDeque::with_capacity(max_samples))),
             max_samples,
         }
     }
 
+    /// Push a sample into a ring buffer, evicting the oldest when full
+    fn record_sample(&self, times: &Mutex<VecDeque<Duration>>, duration: Duration) {
+        let mut times = times.lock().unwrap();
+        if times.len() >= self.max_samples {
+            times.pop_front();
+        }
+        times.push_back(duration);
+    }
+
     /// Record block processing time
     pub fn record_block_processing(&self, duration: Duration) {
-        let mut times = self.block_processing_times.lock().unwrap();
-        times.push(duration);
-        if times.len() > self.max_samples {
-            times.remove(0);
-        }
+        self.record_sample(&self.block_processing_times, duration);
     }
 
     /// Record transaction validation time
     pub fn record_tx_validation(&self, duration: Duration) {
-        let mut times = self.tx_validation_times.lock().unwrap();
-        times.push(duration);
-        if times.len() > self.max_samples {
-            times.remove(0);
-        }
+        self.record_sample(&self.tx_validation_times, duration);
     }
 
     /// Record storage operation time
     pub fn record_storage_operation(&self, duration: Duration) {
-        let mut times = self.storage_operation_times.lock().unwrap();
-        times.push(duration);
-        if times.len() > self.max_samples {
-            times.remove(0);
-        }
+        self.record_sample(&self.storage_operation_times, duration);
     }
 
     /// Record network operation time
     pub fn record_network_operation(&self, duration: Duration) {
-        let mut times = self.network_operation_times.lock().unwrap();
-        times.push(duration);
-        if times.len() > self.max_samples {
-            times.remove(0);
-        }
+        self.record_sample(&self.network_operation_times, duration);
+    }
+
+    /// Record UTXO set application time
+    pub fn record_utxo_application(&self, duration: Duration) {
+        self.record_sample(&self.utxo_application_times, duration);
+    }
+
+    /// Record network message processing time
+    pub fn record_message_processing(&self, duration: Duration) {
+        self.record_sample(&self.message_processing_times, duration);
     }
 
     /// Get performance statistics
@@ -76,10 +90,13 @@ impl PerformanceProfiler {
             tx_validation: self.calculate_stats(&self.tx_validation_times.lock().unwrap()),
             storage_operations: self.calculate_stats(&self.storage_operation_times.lock().unwrap()),
             network_operations: self.calculate_stats(&self.network_operation_times.lock().unwrap()),
+            utxo_application: self.calculate_stats(&self.utxo_application_times.lock().unwrap()),
+            message_processing: self
+                .calculate_stats(&self.message_processing_times.lock().unwrap()),
         }
     }
 
-    fn calculate_stats(&self, times: &[Duration]) -> OperationStats {
+    fn calculate_stats(&self, times: &VecDeque<Duration>) -> OperationStats {
         if times.is_empty() {
             return OperationStats::default();
         }
@@ -88,23 +105,21 @@ impl PerformanceProfiler {
         let count = times.len();
         let avg = total / count as u32;
 
-        let mut sorted = times.to_vec();
-        sorted.sort();
+        let mut sorted: Vec<Duration> = times.iter().copied().collect();
+        sorted.sort_unstable();
 
-        let p50 = sorted[count / 2];
-        let p95 = sorted[(count * 95) / 100];
-        let p99 = sorted[(count * 99) / 100];
-        let min = sorted[0];
-        let max = sorted[count - 1];
+        // Nearest-rank percentile over the sorted samples
+        let percentile = |pct: usize| sorted[((count * pct).div_ceil(100)).max(1) - 1];
 
         OperationStats {
             count,
             avg_ms: avg.as_secs_f64() * 1000.0,
-            p50_ms: p50.as_secs_f64() * 1000.0,
-            p95_ms: p95.as_secs_f64() * 1000.0,
-            p99_ms: p99.as_secs_f64() * 1000.0,
-            min_ms: min.as_secs_f64() * 1000.0,
-            max_ms: max.as_secs_f64() * 1000.0,
+            p50_ms: percentile(50).as_secs_f64() * 1000.0,
+            p90_ms: percentile(90).as_secs_f64() * 1000.0,
+            p95_ms: percentile(95).as_secs_f64() * 1000.0,
+            p99_ms: percentile(99).as_secs_f64() * 1000.0,
+            min_ms: sorted[0].as_secs_f64() * 1000.0,
+            max_ms: sorted[count - 1].as_secs_f64() * 1000.0,
         }
     }
 }
@@ -116,6 +131,8 @@ pub struct PerformanceStats {
     pub tx_validation: OperationStats,
     pub storage_operations: OperationStats,
     pub network_operations: OperationStats,
+    pub utxo_application: OperationStats,
+    pub message_processing: OperationStats,
 }
 
 /// Statistics for a single operation type
@@ -127,6 +144,8 @@ pub struct OperationStats {
     pub avg_ms: f64,
     /// 50th percentile (median) time (milliseconds)
     pub p50_ms: f64,
+    /// 90th percentile time (milliseconds)
+    pub p90_ms: f64,
     /// 95th percentile time (milliseconds)
     pub p95_ms: f64,
     /// 99th percentile time (milliseconds)
@@ -138,10 +157,14 @@ pub struct OperationStats {
 }
 
 /// Performance timer for measuring operation duration
+///
+/// The duration is recorded when `stop` is called, or when the timer is dropped
+/// if it was never stopped explicitly.
 pub struct PerformanceTimer {
     start: Instant,
     profiler: Arc<PerformanceProfiler>,
     operation_type: OperationType,
+    recorded: bool,
 }
 
 /// Operation type for profiling
@@ -151,6 +174,8 @@ pub enum OperationType {
     TxValidation,
     StorageOperation,
     NetworkOperation,
+    UtxoApplication,
+    MessageProcessing,
 }
 
 impl PerformanceTimer {
@@ -160,12 +185,18 @@ impl PerformanceTimer {
             start: Instant::now(),
             profiler,
             operation_type,
+            recorded: false,
         }
     }
 
     /// Stop the timer and record the duration
-    pub fn stop(self) -> Duration {
+    pub fn stop(mut self) -> Duration {
+        self.record()
+    }
+
+    fn record(&mut self) -> Duration {
         let duration = self.start.elapsed();
+        self.recorded = true;
 
         match self.operation_type {
             OperationType::BlockProcessing => {
@@ -180,12 +211,26 @@ impl PerformanceTimer {
             OperationType::NetworkOperation => {
                 self.profiler.record_network_operation(duration);
             }
+            OperationType::UtxoApplication => {
+                self.profiler.record_utxo_application(duration);
+            }
+            OperationType::MessageProcessing => {
+                self.profiler.record_message_processing(duration);
+            }
         }
 
         duration
     }
 }
 
+impl Drop for PerformanceTimer {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.record();
+        }
+    }
+}
+
 impl Default for PerformanceProfiler {
     fn default() -> Self {
         Self::new(1000) // Keep last 1000 samples
@@ -0,0 +1,237 @@
+//! Transaction standardness policy
+//!
+//! Relay rules applied on top of consensus when accepting transactions to
+//! the mempool. They only affect what this node relays and mines; blocks
+//! from peers are never checked against them.
+
+use crate::node::mempool::is_witness_program;
+use crate::validation::sigops::{
+    parse_script, small_int, Op, OP_0, OP_1, OP_16, OP_CHECKMULTISIG, OP_CHECKSIG, OP_PUSHDATA1,
+    OP_PUSHDATA2, WITNESS_SCALE_FACTOR,
+};
+use bllvm_protocol::serialization::transaction::serialize_transaction;
+use bllvm_protocol::{Transaction, UtxoSet};
+
+pub use crate::validation::sigops::sigops_cost;
+
+/// Maximum weight of a standard transaction
+pub const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+
+/// Maximum signature operation cost of a standard transaction
+pub const MAX_STANDARD_TX_SIGOPS_COST: u64 = 16_000;
+
+/// Maximum scriptSig size of a standard input
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+
+/// Maximum public keys in a standard bare multisig output
+pub const MAX_STANDARD_BARE_MULTISIG_KEYS: u8 = 3;
+
+/// Default maximum size of a standard OP_RETURN output script
+/// (`datacarriersize`), counting the OP_RETURN and push opcodes
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+const OP_RETURN: u8 = 0x6a;
+
+/// Standard output script templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    PubKey,
+    PubKeyHash,
+    ScriptHash,
+    WitnessV0KeyHash,
+    WitnessV0ScriptHash,
+    WitnessV1Taproot,
+    /// Witness program of a version not yet defined
+    WitnessUnknown,
+    /// Bare `m`-of-`n` multisig
+    Multisig {
+        required: u8,
+        keys: u8,
+    },
+    /// OP_RETURN data carrier
+    NullData,
+    NonStandard,
+}
+
+/// Whether a push uses the smallest possible encoding
+fn is_minimal_push(op: &Op<'_>) -> bool {
+    let Some(data) = op.data else {
+        return true;
+    };
+    match data.len() {
+        // OP_0, OP_1..OP_16 and OP_1NEGATE push these without data
+        0 => op.opcode == OP_0,
+        1 if (1..=16).contains(&data[0]) || data[0] == 0x81 => false,
+        len @ 1..=75 => op.opcode as usize == len,
+        0x4c..=0xff => op.opcode == OP_PUSHDATA1,
+        0x100..=0xffff => op.opcode == OP_PUSHDATA2,
+        _ => true,
+    }
+}
+
+/// Classify an output script
+pub fn classify_script(script: &[u8]) -> ScriptType {
+    match script {
+        [0x76, 0xa9, 0x14, .., 0x88, 0xac] if script.len() == 25 => return ScriptType::PubKeyHash,
+        [0xa9, 0x14, .., 0x87] if script.len() == 23 => return ScriptType::ScriptHash,
+        [0x21, .., OP_CHECKSIG] if script.len() == 35 => return ScriptType::PubKey,
+        [0x41, .., OP_CHECKSIG] if script.len() == 67 => return ScriptType::PubKey,
+        _ => {}
+    }
+    if is_witness_program(script) {
+        return match (script[0], script.len() - 2) {
+            (OP_0, 20) => ScriptType::WitnessV0KeyHash,
+            (OP_0, 32) => ScriptType::WitnessV0ScriptHash,
+            (OP_0, _) => ScriptType::NonStandard,
+            (OP_1, 32) => ScriptType::WitnessV1Taproot,
+            _ => ScriptType::WitnessUnknown,
+        };
+    }
+
+    let Some(ops) = parse_script(script) else {
+        return ScriptType::NonStandard;
+    };
+    if script.first() == Some(&OP_RETURN) {
+        let push_only = ops[1..].iter().all(|op| op.opcode <= OP_16);
+        return if push_only {
+            ScriptType::NullData
+        } else {
+            ScriptType::NonStandard
+        };
+    }
+
+    // OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+    if let [first, keys @ .., last, checkmultisig] = ops.as_slice() {
+        let required = small_int(first.opcode);
+        let count = small_int(last.opcode);
+        if let (Some(required), Some(count)) = (required, count) {
+            let valid_keys = keys
+                .iter()
+                .all(|op| matches!(op.data, Some(key) if key.len() == 33 || key.len() == 65));
+            if checkmultisig.opcode == OP_CHECKMULTISIG
+                && valid_keys
+                && keys.len() == count as usize
+                && required <= count
+            {
+                return ScriptType::Multisig {
+                    required,
+                    keys: count,
+                };
+            }
+        }
+    }
+    ScriptType::NonStandard
+}
+
+/// Check a transaction against the standardness policy
+///
+/// Returns the reject reason of the first rule the transaction breaks.
+/// OP_RETURN outputs are standard only while `datacarrier_size` is set, up
+/// to that many script bytes, and at most one per transaction. Dust is
+/// checked separately (see [`crate::node::mempool::check_dust`]).
+pub fn check_standard(
+    tx: &Transaction,
+    utxo_set: &UtxoSet,
+    datacarrier_size: Option<usize>,
+) -> Result<(), String> {
+    if tx.version < 1 || tx.version > 2 {
+        return Err("version".to_string());
+    }
+    if serialize_transaction(tx).len() as u64 * WITNESS_SCALE_FACTOR > MAX_STANDARD_TX_WEIGHT {
+        return Err("tx-size".to_string());
+    }
+
+    for input in tx.inputs.iter() {
+        if input.script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            return Err("scriptsig-size".to_string());
+        }
+        let ops =
+            parse_script(&input.script_sig).ok_or_else(|| "scriptsig-not-pushonly".to_string())?;
+        if ops.iter().any(|op| op.opcode > OP_16) {
+            return Err("scriptsig-not-pushonly".to_string());
+        }
+        if !ops.iter().all(is_minimal_push) {
+            return Err("scriptsig-non-minimal-push".to_string());
+        }
+    }
+
+    let mut data_outputs = 0;
+    for output in tx.outputs.iter() {
+        match classify_script(&output.script_pubkey) {
+            ScriptType::NonStandard => return Err("scriptpubkey".to_string()),
+            ScriptType::NullData => {
+                if !datacarrier_size.is_some_and(|max| output.script_pubkey.len() <= max) {
+                    return Err("scriptpubkey".to_string());
+                }
+                data_outputs += 1;
+            }
+            ScriptType::Multisig { required, keys } => {
+                if required == 0 || keys > MAX_STANDARD_BARE_MULTISIG_KEYS {
+                    return Err("bare-multisig".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    if data_outputs > 1 {
+        return Err("multi-op-return".to_string());
+    }
+
+    if sigops_cost(tx, utxo_set) > MAX_STANDARD_TX_SIGOPS_COST {
+        return Err("bad-txns-too-many-sigops".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_script() {
+        let mut p2pkh = vec![0x76, 0xa9, 0x14];
+        p2pkh.extend([0u8; 20]);
+        p2pkh.extend([0x88, 0xac]);
+        assert_eq!(classify_script(&p2pkh), ScriptType::PubKeyHash);
+
+        let mut p2tr = vec![OP_1, 0x20];
+        p2tr.extend([0u8; 32]);
+        assert_eq!(classify_script(&p2tr), ScriptType::WitnessV1Taproot);
+
+        let mut multisig = vec![OP_1];
+        for _ in 0..2 {
+            multisig.push(0x21);
+            multisig.extend([0x02; 33]);
+        }
+        multisig.extend([0x52, OP_CHECKMULTISIG]);
+        assert_eq!(
+            classify_script(&multisig),
+            ScriptType::Multisig {
+                required: 1,
+                keys: 2
+            }
+        );
+
+        assert_eq!(
+            classify_script(&[OP_RETURN, 0x01, 0xff]),
+            ScriptType::NullData
+        );
+        assert_eq!(classify_script(&[OP_1]), ScriptType::NonStandard);
+        // Truncated push
+        assert_eq!(
+            classify_script(&[OP_RETURN, 0x05, 0x01]),
+            ScriptType::NonStandard
+        );
+    }
+
+    #[test]
+    fn test_minimal_push() {
+        let check = |script: &[u8]| parse_script(script).unwrap().iter().all(is_minimal_push);
+        assert!(check(&[0x01, 0x20]));
+        assert!(check(&[OP_0, 0x4f, OP_16]));
+        // 5 pushed as data instead of OP_5
+        assert!(!check(&[0x01, 0x05]));
+        // Three bytes via OP_PUSHDATA1
+        assert!(!check(&[OP_PUSHDATA1, 0x03, 0xaa, 0xbb, 0xcc]));
+    }
+}
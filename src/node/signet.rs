@@ -0,0 +1,408 @@
+//! Signet block signatures (BIP 325)
+//!
+//! On signet, proof of work alone does not make a block valid: the coinbase's
+//! witness commitment output must also carry a solution to the network's
+//! challenge script, pushed behind the `ecc7daa2` header. The solution signs
+//! the block's version, parent, time and merkle root (computed with the
+//! solution itself taken out of the coinbase) through a pair of virtual
+//! transactions, and the resulting spend is checked by the protocol engine's
+//! script interpreter like any other input.
+
+use crate::validation::witness::{decode_varint, WITNESS_COMMITMENT_HEADER};
+use bllvm_protocol::block::calculate_tx_id;
+use bllvm_protocol::mining::calculate_merkle_root;
+use bllvm_protocol::segwit::Witness;
+use bllvm_protocol::{
+    Block, ByteString, OutPoint, Transaction, TransactionInput, TransactionOutput,
+};
+use tracing::debug;
+
+/// Header marking the signet solution push in the witness commitment output
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// Bitcoin Core reject reason for blocks failing the signet check
+pub const BAD_SIGNET_BLKSIG: &str = "bad-signet-blksig";
+
+/// Script flags the solution is verified with
+const SCRIPT_VERIFY_P2SH: u32 = 1 << 0;
+const SCRIPT_VERIFY_DERSIG: u32 = 1 << 2;
+const SCRIPT_VERIFY_NULLDUMMY: u32 = 1 << 4;
+const SCRIPT_VERIFY_WITNESS: u32 = 1 << 11;
+const SIGNET_SCRIPT_FLAGS: u32 =
+    SCRIPT_VERIFY_P2SH | SCRIPT_VERIFY_DERSIG | SCRIPT_VERIFY_NULLDUMMY | SCRIPT_VERIFY_WITNESS;
+
+const OP_1: u8 = 0x51;
+const OP_16: u8 = 0x60;
+const OP_PUSHDATA1: u8 = 0x4c;
+const OP_PUSHDATA2: u8 = 0x4d;
+const OP_PUSHDATA4: u8 = 0x4e;
+const OP_RETURN: u8 = 0x6a;
+
+/// Virtual transactions the signet solution is checked against
+#[derive(Debug, Clone)]
+pub struct SignetTxs {
+    /// Pays the challenge; its input commits to the block
+    pub to_spend: Transaction,
+    /// Spends `to_spend` with the solution's scriptSig
+    pub to_sign: Transaction,
+    /// Witness stack of the solution
+    pub witness: Witness,
+}
+
+impl SignetTxs {
+    /// Build the virtual transactions for `block` and `challenge`
+    ///
+    /// Fails if the block has no witness commitment or the solution is
+    /// malformed. A block without a solution gets an empty scriptSig and
+    /// witness, which only an always-true challenge accepts.
+    pub fn new(block: &Block, challenge: &[u8]) -> Result<Self, String> {
+        let coinbase = block
+            .transactions
+            .first()
+            .ok_or_else(|| "block has no coinbase".to_string())?;
+        let commitment_index = coinbase
+            .outputs
+            .iter()
+            .rposition(|output| {
+                output.script_pubkey.len() >= 38
+                    && output.script_pubkey[..6] == WITNESS_COMMITMENT_HEADER
+            })
+            .ok_or_else(|| "no witness commitment".to_string())?;
+
+        let mut modified_coinbase = coinbase.clone();
+        let mut outputs: Vec<TransactionOutput> = coinbase.outputs.to_vec();
+        let (script, solution) = take_solution(&outputs[commitment_index].script_pubkey);
+        outputs[commitment_index].script_pubkey = script;
+        modified_coinbase.outputs = outputs.into();
+
+        let (script_sig, witness) = match solution {
+            Some(solution) => parse_solution(&solution)?,
+            None => (Vec::new(), Vec::new()),
+        };
+
+        let mut transactions = block.transactions.to_vec();
+        transactions[0] = modified_coinbase;
+        let merkle_root = calculate_merkle_root(&transactions)
+            .map_err(|e| format!("failed to compute signet merkle root: {e}"))?;
+
+        // version || prev block || modified merkle root || time
+        let mut block_data = Vec::with_capacity(72);
+        block_data.extend_from_slice(&(block.header.version as i32).to_le_bytes());
+        block_data.extend_from_slice(&block.header.prev_block_hash);
+        block_data.extend_from_slice(&merkle_root);
+        block_data.extend_from_slice(&(block.header.timestamp as u32).to_le_bytes());
+        let mut to_spend_script_sig = vec![0x00];
+        push_data(&mut to_spend_script_sig, &block_data);
+
+        let to_spend = Transaction {
+            version: 0,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig: to_spend_script_sig,
+                sequence: 0,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 0,
+                script_pubkey: challenge.to_vec(),
+            }],
+            lock_time: 0,
+        };
+        let to_sign = Transaction {
+            version: 0,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: calculate_tx_id(&to_spend),
+                    index: 0,
+                },
+                script_sig,
+                sequence: 0,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 0,
+                script_pubkey: vec![OP_RETURN],
+            }],
+            lock_time: 0,
+        };
+
+        Ok(Self {
+            to_spend,
+            to_sign,
+            witness,
+        })
+    }
+}
+
+/// Check that `block` carries a valid solution to the signet `challenge`
+///
+/// The genesis block is exempt. Script evaluation is left to the protocol
+/// engine. Returns [`BAD_SIGNET_BLKSIG`] on failure, as Bitcoin Core does.
+pub fn check_block_solution(block: &Block, challenge: &[u8], height: u64) -> Result<(), String> {
+    if height == 0 {
+        return Ok(());
+    }
+    let txs = SignetTxs::new(block, challenge).map_err(|reason| {
+        debug!("Signet block solution unusable: {}", reason);
+        BAD_SIGNET_BLKSIG.to_string()
+    })?;
+
+    let input = &txs.to_sign.inputs[0];
+    let prevouts = [txs.to_spend.outputs[0].clone()];
+    let witness = (!txs.witness.is_empty()).then_some(&txs.witness);
+    match bllvm_protocol::script::verify_script_with_context(
+        &input.script_sig,
+        &prevouts[0].script_pubkey,
+        witness,
+        SIGNET_SCRIPT_FLAGS,
+        &txs.to_sign,
+        0,
+        &prevouts,
+    ) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(BAD_SIGNET_BLKSIG.to_string()),
+        Err(e) => {
+            debug!("Signet block solution failed to verify: {}", e);
+            Err(BAD_SIGNET_BLKSIG.to_string())
+        }
+    }
+}
+
+/// Whether `challenge` is met by a block without a solution
+///
+/// True only for a lone `OP_TRUE` (or another `OP_1`..`OP_16`), the kind of
+/// challenge a private test signet uses so blocks can be mined without a key.
+pub fn is_trivial_challenge(challenge: &[u8]) -> bool {
+    matches!(challenge, [op] if (OP_1..=OP_16).contains(op))
+}
+
+/// Remove the signet solution from a witness commitment script
+///
+/// The first push starting with [`SIGNET_HEADER`] and carrying more data is
+/// cut down to the bare header; the data after the header is the solution.
+/// Every push is re-encoded minimally, matching Bitcoin Core. Returns the
+/// script unchanged if it has no solution.
+fn take_solution(script: &[u8]) -> (ByteString, Option<ByteString>) {
+    let mut replacement = Vec::with_capacity(script.len());
+    let mut solution = None;
+    let mut pos = 0;
+    while let Some((opcode, data, next)) = read_op(script, pos) {
+        pos = next;
+        match data {
+            Some(data) if !data.is_empty() => {
+                if solution.is_none()
+                    && data.len() > SIGNET_HEADER.len()
+                    && data[..SIGNET_HEADER.len()] == SIGNET_HEADER
+                {
+                    solution = Some(data[SIGNET_HEADER.len()..].to_vec());
+                    push_data(&mut replacement, &SIGNET_HEADER);
+                } else {
+                    push_data(&mut replacement, data);
+                }
+            }
+            _ => replacement.push(opcode),
+        }
+    }
+    match solution {
+        Some(solution) => (replacement, Some(solution)),
+        None => (script.to_vec(), None),
+    }
+}
+
+/// Read the opcode at `pos` with its push data
+///
+/// Returns `None` at the end of the script or on a truncated push.
+fn read_op(script: &[u8], pos: usize) -> Option<(u8, Option<&[u8]>, usize)> {
+    let opcode = *script.get(pos)?;
+    let mut pos = pos + 1;
+    let len = match opcode {
+        0x01..=0x4b => opcode as usize,
+        OP_PUSHDATA1 => {
+            let len = *script.get(pos)? as usize;
+            pos += 1;
+            len
+        }
+        OP_PUSHDATA2 => {
+            let len = u16::from_le_bytes(script.get(pos..pos + 2)?.try_into().ok()?) as usize;
+            pos += 2;
+            len
+        }
+        OP_PUSHDATA4 => {
+            let len = u32::from_le_bytes(script.get(pos..pos + 4)?.try_into().ok()?) as usize;
+            pos += 4;
+            len
+        }
+        _ => return Some((opcode, None, pos)),
+    };
+    let data = script.get(pos..pos.checked_add(len)?)?;
+    Some((opcode, Some(data), pos + len))
+}
+
+/// Append a push of `data` to `script`
+fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    match data.len() {
+        len if len < OP_PUSHDATA1 as usize => script.push(len as u8),
+        len if len <= 0xff => script.extend_from_slice(&[OP_PUSHDATA1, len as u8]),
+        len if len <= 0xffff => {
+            script.push(OP_PUSHDATA2);
+            script.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        len => {
+            script.push(OP_PUSHDATA4);
+            script.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+    }
+    script.extend_from_slice(data);
+}
+
+/// Split a solution into its scriptSig and witness stack
+fn parse_solution(solution: &[u8]) -> Result<(ByteString, Witness), String> {
+    let mut pos = 0;
+    let script_sig = read_bytes(solution, &mut pos)?;
+    let count = decode_varint(solution, &mut pos).ok_or_else(truncated)?;
+    let mut witness = Vec::new();
+    for _ in 0..count {
+        witness.push(read_bytes(solution, &mut pos)?);
+    }
+    if pos != solution.len() {
+        return Err("trailing data after signet solution".to_string());
+    }
+    Ok((script_sig, witness))
+}
+
+fn read_bytes(data: &[u8], pos: &mut usize) -> Result<ByteString, String> {
+    let len = decode_varint(data, pos).ok_or_else(truncated)? as usize;
+    let bytes = pos
+        .checked_add(len)
+        .and_then(|end| data.get(*pos..end))
+        .ok_or_else(truncated)?;
+    *pos += len;
+    Ok(bytes.to_vec())
+}
+
+fn truncated() -> String {
+    "truncated signet solution".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bllvm_protocol::BlockHeader;
+
+    fn block_with_commitment(commitment_script: ByteString) -> Block {
+        let coinbase = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig: vec![0x51, 0x00],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![
+                TransactionOutput {
+                    value: 5_000_000_000,
+                    script_pubkey: vec![0x51],
+                },
+                TransactionOutput {
+                    value: 0,
+                    script_pubkey: commitment_script,
+                }
+            ],
+            lock_time: 0,
+        };
+        Block {
+            header: BlockHeader {
+                version: 0x20000000,
+                prev_block_hash: [0x11; 32],
+                merkle_root: [0u8; 32],
+                timestamp: 1_700_000_000,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            transactions: vec![coinbase].into_boxed_slice(),
+        }
+    }
+
+    fn commitment_script() -> ByteString {
+        let mut script = WITNESS_COMMITMENT_HEADER.to_vec();
+        script.extend_from_slice(&[0x22; 32]);
+        script
+    }
+
+    #[test]
+    fn test_solution_is_taken_out_of_the_commitment() {
+        // A one-push scriptSig, then a single witness item
+        let solution = vec![0x02, 0x01, 0x51, 0x01, 0x01, 0xab];
+        let mut script = commitment_script();
+        let mut push = SIGNET_HEADER.to_vec();
+        push.extend_from_slice(&solution);
+        push_data(&mut script, &push);
+
+        let (stripped, taken) = take_solution(&script);
+        assert_eq!(taken, Some(solution.clone()));
+        let mut expected = commitment_script();
+        push_data(&mut expected, &SIGNET_HEADER);
+        assert_eq!(stripped, expected);
+
+        let (script_sig, witness) = parse_solution(&solution).unwrap();
+        assert_eq!(script_sig, vec![0x01, 0x51]);
+        assert_eq!(witness, vec![vec![0xab]]);
+        assert!(parse_solution(&[0x05, 0x01]).is_err());
+        assert!(parse_solution(&[0x00, 0x00, 0x00]).is_err());
+
+        // The signed data does not depend on the solution
+        let signed = SignetTxs::new(&block_with_commitment(script), &[0x51]).unwrap();
+        let mut unsigned_script = commitment_script();
+        push_data(&mut unsigned_script, &SIGNET_HEADER);
+        let unsigned = SignetTxs::new(&block_with_commitment(unsigned_script), &[0x51]).unwrap();
+        assert_eq!(
+            calculate_tx_id(&signed.to_spend),
+            calculate_tx_id(&unsigned.to_spend)
+        );
+        assert_eq!(signed.to_sign.inputs[0].script_sig, vec![0x01, 0x51]);
+
+        let to_spend_sig = &signed.to_spend.inputs[0].script_sig;
+        assert_eq!(to_spend_sig.len(), 2 + 72);
+        assert_eq!(to_spend_sig[..2], [0x00, 0x48]);
+        assert_eq!(to_spend_sig[6..38], [0x11; 32]);
+    }
+
+    #[test]
+    fn test_block_without_commitment_is_rejected() {
+        let mut block = block_with_commitment(commitment_script());
+        let mut coinbase = block.transactions[0].clone();
+        coinbase.outputs = coinbase.outputs[..1].to_vec().into();
+        block.transactions = vec![coinbase].into_boxed_slice();
+
+        assert!(SignetTxs::new(&block, &[0x51]).is_err());
+        assert_eq!(
+            check_block_solution(&block, &[0x51], 1),
+            Err(BAD_SIGNET_BLKSIG.to_string())
+        );
+        // The genesis block carries no solution
+        assert_eq!(check_block_solution(&block, &[0x51], 0), Ok(()));
+    }
+
+    #[test]
+    fn test_trivial_challenge_needs_no_solution() {
+        let block = block_with_commitment(commitment_script());
+        assert_eq!(check_block_solution(&block, &[0x51], 1), Ok(()));
+        // OP_FALSE cannot be satisfied
+        assert_eq!(
+            check_block_solution(&block, &[0x00], 1),
+            Err(BAD_SIGNET_BLKSIG.to_string())
+        );
+
+        assert!(is_trivial_challenge(&[0x51]));
+        assert!(!is_trivial_challenge(&[0x00]));
+        assert!(!is_trivial_challenge(&[0x51, 0x51, 0x87]));
+        let default_challenge =
+            crate::node::chain_params::ChainParams::new(crate::node::chain_params::Network::Signet)
+                .signet_challenge
+                .unwrap();
+        assert!(!is_trivial_challenge(&default_challenge));
+    }
+}
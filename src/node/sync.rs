@@ -3,15 +3,19 @@
 //! Handles blockchain synchronization, header download, block validation,
 //! and chain reorganization.
 
+use crate::node::assume_valid::AssumeValid;
 use crate::node::block_processor::{
     parse_block_from_wire, prepare_block_validation_context, store_block_with_context,
-    validate_block_with_context,
+    validate_block_with_context, validate_block_without_scripts,
 };
 use crate::node::metrics::MetricsCollector;
 use crate::node::performance::{OperationType, PerformanceProfiler, PerformanceTimer};
+use crate::node::signet::check_block_solution;
 use crate::storage::blockstore::BlockStore;
+use crate::storage::utxostore::UtxoStore;
+use crate::validation::no_scripts::ConsensusParams;
 use anyhow::Result;
-use bllvm_protocol::{Block, BlockHeader, UtxoSet, ValidationResult};
+use bllvm_protocol::{Block, BlockHeader, Hash, UtxoSet, ValidationResult};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
@@ -114,6 +118,197 @@ impl SyncStateMachine {
     }
 }
 
+/// Chain transaction statistics used to extrapolate verification progress
+#[derive(Debug, Clone, Copy)]
+pub struct ChainTxData {
+    /// Timestamp of the reference block
+    pub time: u64,
+    /// Total transactions up to the reference block
+    pub tx_count: u64,
+    /// Transactions per second after the reference block
+    pub tx_rate: f64,
+}
+
+/// Mainnet transaction statistics as of January 2024 (from `getchaintxstats`)
+pub const MAINNET_CHAIN_TX_DATA: ChainTxData = ChainTxData {
+    time: 1704194835,
+    tx_count: 946728933,
+    tx_rate: 6.569290261471664,
+};
+
+/// Tip age after which the node is considered to be in initial block download
+pub const MAX_TIP_AGE_SECONDS: u64 = 24 * 60 * 60;
+
+/// Snapshot of headers-first sync state
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SyncProgress {
+    /// Height of the best known header
+    pub headers_height: u64,
+    /// Height of the last validated block
+    pub blocks_height: u64,
+    /// Timestamp of the last validated block
+    pub tip_time: u64,
+    /// Total transactions up to the last validated block (0 if unknown)
+    pub chain_tx_count: u64,
+    /// Cumulative work of the active chain
+    pub chain_work: u128,
+    /// Work the active chain must reach before leaving IBD (0 disables)
+    pub minimum_chain_work: u128,
+}
+
+impl SyncProgress {
+    /// Estimated fraction of the chain verified (0.0 to 1.0)
+    ///
+    /// The lower of two estimates: validated blocks over known headers, and
+    /// (when the transaction count is known) validated transactions over the
+    /// expected total, extrapolated to `now` at the chain's transaction rate as
+    /// Bitcoin Core does. Blocks are a poor proxy early in the chain, where
+    /// they are nearly empty, so the transaction estimate usually dominates.
+    pub fn verification_progress(&self, now: u64, data: &ChainTxData) -> f64 {
+        let headers = self.headers_height.max(self.blocks_height);
+        let block_ratio = (self.blocks_height + 1) as f64 / (headers + 1) as f64;
+
+        if self.chain_tx_count == 0 {
+            return block_ratio.min(1.0);
+        }
+
+        let expected_total = if self.chain_tx_count <= data.tx_count {
+            data.tx_count as f64 + now.saturating_sub(data.time) as f64 * data.tx_rate
+        } else {
+            self.chain_tx_count as f64 + now.saturating_sub(self.tip_time) as f64 * data.tx_rate
+        };
+        let tx_ratio = self.chain_tx_count as f64 / expected_total;
+
+        block_ratio.min(tx_ratio).min(1.0)
+    }
+
+    /// Whether the node is still in initial block download
+    ///
+    /// True while the active chain has less than the minimum chain work,
+    /// validated blocks lag known headers, or the tip is more than a day old.
+    pub fn is_initial_block_download(&self, now: u64) -> bool {
+        self.chain_work < self.minimum_chain_work
+            || self.blocks_height < self.headers_height
+            || self.tip_time + MAX_TIP_AGE_SECONDS < now
+    }
+}
+
+/// Seconds between sync progress log lines during initial block download
+pub const SYNC_PROGRESS_LOG_INTERVAL_SECONDS: u64 = 30;
+
+/// Sync progress with a validation rate and time-to-tip estimate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncStatus {
+    /// Height of the last validated block
+    pub blocks: u64,
+    /// Height of the best known header
+    pub headers: u64,
+    /// Estimated fraction of the chain verified (0.0 to 1.0)
+    pub progress: f64,
+    /// Recent block validation rate
+    pub blocks_per_second: f64,
+    /// Estimated seconds until blocks reach headers (None without a rate)
+    pub eta_seconds: Option<u64>,
+    /// Whether the node is still in initial block download
+    pub initial_block_download: bool,
+}
+
+impl SyncStatus {
+    /// Estimate the time to sync the remaining headers at `blocks_per_second`
+    pub fn new(sync: &SyncProgress, blocks_per_second: f64, now: u64) -> Self {
+        let headers = sync.headers_height.max(sync.blocks_height);
+        let remaining = headers - sync.blocks_height;
+        let eta_seconds = if remaining == 0 {
+            Some(0)
+        } else if blocks_per_second > 0.0 {
+            Some((remaining as f64 / blocks_per_second).ceil() as u64)
+        } else {
+            None
+        };
+        Self {
+            blocks: sync.blocks_height,
+            headers,
+            progress: sync.verification_progress(now, &MAINNET_CHAIN_TX_DATA),
+            blocks_per_second,
+            eta_seconds,
+            initial_block_download: sync.is_initial_block_download(now),
+        }
+    }
+}
+
+/// Blocks validated per second, from the profiler's average block time
+///
+/// Zero until the profiler has recorded a block.
+pub fn validation_rate(profiler: &PerformanceProfiler) -> f64 {
+    let stats = profiler.get_stats().block_processing;
+    if stats.count == 0 || stats.avg_ms <= 0.0 {
+        return 0.0;
+    }
+    1000.0 / stats.avg_ms
+}
+
+/// Periodically logs sync progress while in initial block download
+#[derive(Debug)]
+pub struct SyncProgressReporter {
+    interval: u64,
+    last_log: Option<u64>,
+}
+
+impl Default for SyncProgressReporter {
+    fn default() -> Self {
+        Self::new(SYNC_PROGRESS_LOG_INTERVAL_SECONDS)
+    }
+}
+
+impl SyncProgressReporter {
+    /// Log at most once every `interval` seconds
+    pub fn new(interval: u64) -> Self {
+        Self {
+            interval,
+            last_log: None,
+        }
+    }
+
+    /// Log `status` if in IBD and the interval has passed; true if logged
+    pub fn report(&mut self, status: &SyncStatus, now: u64) -> bool {
+        if !status.initial_block_download {
+            return false;
+        }
+        if self
+            .last_log
+            .is_some_and(|last| now < last.saturating_add(self.interval))
+        {
+            return false;
+        }
+        self.last_log = Some(now);
+        let eta = status
+            .eta_seconds
+            .map(format_duration)
+            .unwrap_or_else(|| "unknown".to_string());
+        info!(
+            "Sync progress: {}/{} blocks ({:.2}%), {:.1} blocks/s, ETA {}",
+            status.blocks,
+            status.headers,
+            status.progress * 100.0,
+            status.blocks_per_second,
+            eta
+        );
+        true
+    }
+}
+
+/// Format seconds as e.g. "2h 05m 09s"
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m {seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
 /// Sync states
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SyncState {
@@ -128,6 +323,15 @@ pub enum SyncState {
 pub struct SyncCoordinator {
     state_machine: SyncStateMachine,
     block_provider: BlockProvider,
+    /// Blocks covered by this skip script verification
+    assume_valid: Option<AssumeValid>,
+    /// Signet challenge every block must satisfy (signet only)
+    signet_challenge: Option<Vec<u8>>,
+    /// Rules checked in place of the engine for assumed-valid blocks
+    consensus_params: ConsensusParams,
+    /// UTXO store whose coinbase-outputs index decides coinbase maturity
+    /// for assumed-valid blocks
+    utxo_store: Option<Arc<UtxoStore>>,
 }
 
 impl Default for SyncCoordinator {
@@ -139,6 +343,10 @@ impl Default for SyncCoordinator {
 impl Clone for SyncCoordinator {
     fn clone(&self) -> Self {
         Self::new()
+            .with_assume_valid(self.assume_valid_hash())
+            .with_signet_challenge(self.signet_challenge.clone())
+            .with_consensus_params(self.consensus_params)
+            .with_utxo_store(self.utxo_store.clone())
     }
 }
 
@@ -148,9 +356,60 @@ impl SyncCoordinator {
         Self {
             state_machine: SyncStateMachine::new(),
             block_provider: BlockProvider::new(),
+            assume_valid: None,
+            signet_challenge: None,
+            consensus_params: ConsensusParams::MAINNET,
+            utxo_store: None,
         }
     }
 
+    /// Skip script verification for ancestors of `hash` (`None` verifies all)
+    pub fn with_assume_valid(mut self, hash: Option<Hash>) -> Self {
+        self.set_assume_valid(hash);
+        self
+    }
+
+    /// Set the assumed-valid block (`None` verifies all scripts)
+    pub fn set_assume_valid(&mut self, hash: Option<Hash>) {
+        self.assume_valid = hash.map(AssumeValid::new);
+    }
+
+    /// Assumed-valid block hash, if any
+    pub fn assume_valid_hash(&self) -> Option<Hash> {
+        self.assume_valid.as_ref().map(AssumeValid::hash)
+    }
+
+    /// Require every block to carry a solution to this signet challenge
+    pub fn with_signet_challenge(mut self, challenge: Option<Vec<u8>>) -> Self {
+        self.set_signet_challenge(challenge);
+        self
+    }
+
+    /// Set the signet challenge (`None` outside signet)
+    pub fn set_signet_challenge(&mut self, challenge: Option<Vec<u8>>) {
+        self.signet_challenge = challenge;
+    }
+
+    /// Check assumed-valid blocks against these network rules (mainnet by default)
+    pub fn with_consensus_params(mut self, params: ConsensusParams) -> Self {
+        self.set_consensus_params(params);
+        self
+    }
+
+    /// Set the network rules assumed-valid blocks are checked against
+    pub fn set_consensus_params(&mut self, params: ConsensusParams) {
+        self.consensus_params = params;
+    }
+
+    /// Look up coinbase outputs for assumed-valid blocks in this UTXO store
+    ///
+    /// Without one every coin counts as a coinbase output, so assumed-valid
+    /// blocks spending coins younger than the maturity window are rejected.
+    pub fn with_utxo_store(mut self, utxo_store: Option<Arc<UtxoStore>>) -> Self {
+        self.utxo_store = utxo_store;
+        self
+    }
+
     /// Start sync process
     pub fn start_sync(&mut self) -> Result<()> {
         info!("Starting blockchain sync");
@@ -177,7 +436,9 @@ impl SyncCoordinator {
     ///
     /// This function:
     /// 1. Parses the block from wire format (extracting witness data)
-    /// 2. Validates the block with proper witnesses and headers
+    /// 2. Validates the block with proper witnesses and headers (skipping
+    ///    scripts for ancestors of the assumed-valid block); on signet the
+    ///    block signature is always checked
     /// 3. Stores the block with witnesses and updates headers
     pub fn process_block(
         &mut self,
@@ -215,14 +476,52 @@ impl SyncCoordinator {
             );
         }
 
+        let block_hash = blockstore.get_block_hash(&block);
+        if let Some(ref challenge) = self.signet_challenge {
+            if let Err(reason) = check_block_solution(&block, challenge, current_height) {
+                error!(
+                    "Block {} at height {} rejected: {}",
+                    hex::encode(block_hash),
+                    current_height,
+                    reason
+                );
+                return Ok(false);
+            }
+        }
+        let assumed_valid = self
+            .assume_valid
+            .as_mut()
+            .is_some_and(|av| av.covers(blockstore, &block_hash, current_height));
+
         // Validate block with witness data and headers
-        let validation_result = validate_block_with_context(
-            blockstore,
-            &block,
-            witnesses_to_use,
-            utxo_set,
-            current_height,
-        )?;
+        let validation_result = if assumed_valid {
+            debug!(
+                "Block {} at height {} is assumed valid, skipping script checks",
+                hex::encode(block_hash),
+                current_height
+            );
+            validate_block_without_scripts(
+                blockstore,
+                &block,
+                witnesses_to_use,
+                utxo_set,
+                current_height,
+                &self.consensus_params,
+                &|outpoint| {
+                    self.utxo_store
+                        .as_ref()
+                        .map_or(true, |store| store.is_coinbase_or_unknown(outpoint))
+                },
+            )?
+        } else {
+            validate_block_with_context(
+                blockstore,
+                &block,
+                witnesses_to_use,
+                utxo_set,
+                current_height,
+            )?
+        };
 
         let processing_time = start_time.elapsed();
 
@@ -433,4 +732,128 @@ mod tests {
         let coordinator = SyncCoordinator::new();
         assert_eq!(coordinator.progress(), 0.0);
     }
+
+    #[test]
+    fn test_verification_progress_is_monotonic_during_sync() {
+        let data = MAINNET_CHAIN_TX_DATA;
+        let now = data.time + 3600;
+        let headers_height = 800_000;
+
+        let mut last = 0.0;
+        for blocks_height in (0..headers_height).step_by(50_000) {
+            // Roughly linear transaction growth and 10-minute blocks
+            let sync = SyncProgress {
+                headers_height,
+                blocks_height,
+                tip_time: 1231006505 + blocks_height * 600,
+                chain_tx_count: 1 + blocks_height * 1000,
+                ..Default::default()
+            };
+            let progress = sync.verification_progress(now, &data);
+            assert!(progress > 0.0 && progress < 1.0, "progress {progress}");
+            assert!(progress >= last);
+            assert!(sync.is_initial_block_download(now));
+            last = progress;
+        }
+    }
+
+    #[test]
+    fn test_sync_eta_decreases_during_sync() {
+        let now = MAINNET_CHAIN_TX_DATA.time + 3600;
+        let headers_height = 800_000;
+
+        let mut last_eta = u64::MAX;
+        for blocks_height in (0..=headers_height).step_by(40_000) {
+            // Validation slows as blocks fill up, but never enough to outpace the remaining work
+            let rate = 2000.0 - blocks_height as f64 / 1000.0;
+            let sync = SyncProgress {
+                headers_height,
+                blocks_height,
+                tip_time: 1231006505 + blocks_height * 600,
+                ..Default::default()
+            };
+            let status = SyncStatus::new(&sync, rate, now);
+            let eta = status.eta_seconds.unwrap();
+            assert!(eta < last_eta, "eta {eta} after {last_eta}");
+            last_eta = eta;
+        }
+        assert_eq!(last_eta, 0);
+
+        let stalled = SyncStatus::new(
+            &SyncProgress {
+                headers_height,
+                ..Default::default()
+            },
+            0.0,
+            now,
+        );
+        assert_eq!(stalled.eta_seconds, None);
+    }
+
+    #[test]
+    fn test_sync_progress_reporter_interval() {
+        let status = SyncStatus::new(
+            &SyncProgress {
+                headers_height: 100,
+                blocks_height: 10,
+                ..Default::default()
+            },
+            5.0,
+            1_000,
+        );
+        let mut reporter = SyncProgressReporter::new(30);
+        assert!(reporter.report(&status, 1_000));
+        assert!(!reporter.report(&status, 1_029));
+        assert!(reporter.report(&status, 1_030));
+        assert_eq!(format_duration(7509), "2h 05m 09s");
+        assert_eq!(format_duration(61), "1m 01s");
+    }
+
+    #[test]
+    fn test_verification_progress_when_synced() {
+        let now = 1_800_000_000;
+        let synced = SyncProgress {
+            headers_height: 900_000,
+            blocks_height: 900_000,
+            tip_time: now - 600,
+            chain_tx_count: 1_200_000_000,
+            ..Default::default()
+        };
+        let progress = synced.verification_progress(now, &MAINNET_CHAIN_TX_DATA);
+        assert!(progress > 0.9999 && progress <= 1.0);
+        assert!(!synced.is_initial_block_download(now));
+
+        // Caught up with headers but the tip is stale
+        let stale = SyncProgress {
+            tip_time: now - 2 * MAX_TIP_AGE_SECONDS,
+            ..synced
+        };
+        assert!(stale.is_initial_block_download(now));
+
+        // Caught up and recent, but on a chain with too little work
+        let low_work = SyncProgress {
+            chain_work: 1_000,
+            minimum_chain_work: 1_001,
+            ..synced
+        };
+        assert!(low_work.is_initial_block_download(now));
+        assert!(!SyncProgress {
+            chain_work: 1_001,
+            ..low_work
+        }
+        .is_initial_block_download(now));
+
+        // Without a transaction count, fall back to blocks over headers
+        let partial = SyncProgress {
+            headers_height: 99,
+            blocks_height: 49,
+            tip_time: now,
+            chain_tx_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            partial.verification_progress(now, &MAINNET_CHAIN_TX_DATA),
+            0.5
+        );
+    }
 }
@@ -0,0 +1,348 @@
+//! BIP9 version bits deployment tracking
+//!
+//! Soft fork deployments signalled through block header version bits move
+//! through `defined` → `started` → `locked_in` → `active` (or `failed`), with
+//! transitions only at retarget period boundaries. The state for a block is
+//! derived by walking the chain one period at a time: median time past at each
+//! boundary decides start and timeout, and the number of signalling headers in
+//! the finished period decides lock-in.
+
+use anyhow::Result;
+use bllvm_protocol::BlockHeader;
+
+/// Version bits must use the top three bits `001`
+pub const VERSIONBITS_TOP_MASK: u32 = 0xE000_0000;
+
+/// Expected value of the top three bits
+pub const VERSIONBITS_TOP_BITS: u32 = 0x2000_0000;
+
+/// Blocks per signalling period (one difficulty retarget window)
+pub const MAINNET_PERIOD: u64 = 2016;
+
+/// Signalling blocks needed per period on mainnet (95%)
+pub const MAINNET_THRESHOLD: u64 = 1916;
+
+/// Start time marking a deployment as always active
+pub const ALWAYS_ACTIVE: i64 = -1;
+
+/// Start time marking a deployment as never active
+pub const NEVER_ACTIVE: i64 = -2;
+
+/// Number of headers used for median time past
+const MEDIAN_TIME_SPAN: u64 = 11;
+
+/// A BIP9 soft fork deployment
+#[derive(Debug, Clone)]
+pub struct Deployment {
+    /// Deployment name (e.g. "testdummy")
+    pub name: &'static str,
+    /// Version bit used for signalling (0-28)
+    pub bit: u8,
+    /// Median time past at which signalling starts (or `ALWAYS_ACTIVE`/`NEVER_ACTIVE`)
+    pub start_time: i64,
+    /// Median time past after which the deployment fails if not locked in
+    pub timeout: i64,
+    /// Earliest height at which a locked-in deployment becomes active
+    pub min_activation_height: u64,
+    /// Blocks per signalling period
+    pub period: u64,
+    /// Signalling blocks required in a period to lock in
+    pub threshold: u64,
+}
+
+/// Deployments tracked through version bits on mainnet
+pub fn mainnet_deployments() -> Vec<Deployment> {
+    vec![Deployment {
+        name: "testdummy",
+        bit: 28,
+        start_time: NEVER_ACTIVE,
+        timeout: i64::MAX,
+        min_activation_height: 0,
+        period: MAINNET_PERIOD,
+        threshold: MAINNET_THRESHOLD,
+    }]
+}
+
+/// BIP9 deployment state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeploymentState {
+    Defined,
+    Started,
+    LockedIn,
+    Active,
+    Failed,
+}
+
+impl DeploymentState {
+    /// Name used in RPC output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeploymentState::Defined => "defined",
+            DeploymentState::Started => "started",
+            DeploymentState::LockedIn => "locked_in",
+            DeploymentState::Active => "active",
+            DeploymentState::Failed => "failed",
+        }
+    }
+}
+
+/// Signalling statistics for the current period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    /// Blocks per period
+    pub period: u64,
+    /// Signalling blocks required to lock in
+    pub threshold: u64,
+    /// Blocks elapsed in the current period
+    pub elapsed: u64,
+    /// Signalling blocks so far in the current period
+    pub count: u64,
+    /// Whether the threshold can still be reached this period
+    pub possible: bool,
+}
+
+/// Deployment status for the block after the tip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeploymentStatus {
+    /// Current state
+    pub state: DeploymentState,
+    /// Height of the first block with this state
+    pub since: u64,
+    /// Signalling statistics (only while `started`)
+    pub statistics: Option<Statistics>,
+}
+
+/// Whether a header signals for `bit`
+pub fn signals(header: &BlockHeader, bit: u8) -> bool {
+    let version = header.version as u32;
+    version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS && (version >> bit) & 1 == 1
+}
+
+fn median_time_past(headers: &[BlockHeader]) -> i64 {
+    let mut timestamps: Vec<u64> = headers.iter().map(|h| h.timestamp).collect();
+    timestamps.sort_unstable();
+    timestamps.get(timestamps.len() / 2).copied().unwrap_or(0) as i64
+}
+
+/// Compute a deployment's status for the block after `tip_height`
+///
+/// `load_headers(start, end)` returns the headers at heights `start..=end` in
+/// order. Only the last few headers of each period are loaded until
+/// signalling starts; full periods are loaded only while `started`.
+pub fn deployment_status(
+    deployment: &Deployment,
+    tip_height: u64,
+    mut load_headers: impl FnMut(u64, u64) -> Result<Vec<BlockHeader>>,
+) -> Result<DeploymentStatus> {
+    let fixed = |state| DeploymentStatus {
+        state,
+        since: 0,
+        statistics: None,
+    };
+    if deployment.start_time == ALWAYS_ACTIVE {
+        return Ok(fixed(DeploymentState::Active));
+    }
+    if deployment.start_time == NEVER_ACTIVE {
+        return Ok(fixed(DeploymentState::Failed));
+    }
+
+    let period = deployment.period;
+    let next_height = tip_height + 1;
+    let mut state = DeploymentState::Defined;
+    let mut since = 0;
+
+    // Evaluate each period boundary up to the next block
+    let mut boundary = period;
+    while boundary <= next_height {
+        let next_state = match state {
+            DeploymentState::Defined => {
+                let mtp_start = boundary.saturating_sub(MEDIAN_TIME_SPAN);
+                let mtp = median_time_past(&load_headers(mtp_start, boundary - 1)?);
+                if mtp >= deployment.start_time {
+                    DeploymentState::Started
+                } else {
+                    state
+                }
+            }
+            DeploymentState::Started => {
+                let headers = load_headers(boundary - period, boundary - 1)?;
+                let count = headers
+                    .iter()
+                    .filter(|h| signals(h, deployment.bit))
+                    .count() as u64;
+                let mtp_start = headers.len().saturating_sub(MEDIAN_TIME_SPAN as usize);
+                if count >= deployment.threshold {
+                    DeploymentState::LockedIn
+                } else if median_time_past(&headers[mtp_start..]) >= deployment.timeout {
+                    DeploymentState::Failed
+                } else {
+                    state
+                }
+            }
+            DeploymentState::LockedIn => {
+                if boundary >= deployment.min_activation_height {
+                    DeploymentState::Active
+                } else {
+                    state
+                }
+            }
+            DeploymentState::Active | DeploymentState::Failed => break,
+        };
+        if next_state != state {
+            state = next_state;
+            since = boundary;
+        }
+        boundary += period;
+    }
+
+    let statistics = if state == DeploymentState::Started {
+        let period_start = next_height - next_height % period;
+        let elapsed = next_height - period_start;
+        let count = if elapsed > 0 {
+            load_headers(period_start, tip_height)?
+                .iter()
+                .filter(|h| signals(h, deployment.bit))
+                .count() as u64
+        } else {
+            0
+        };
+        Some(Statistics {
+            period,
+            threshold: deployment.threshold,
+            elapsed,
+            count,
+            possible: period - deployment.threshold >= elapsed - count,
+        })
+    } else {
+        None
+    };
+
+    Ok(DeploymentStatus {
+        state,
+        since,
+        statistics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD: u64 = 20;
+    const START_TIME: i64 = 1_000_000;
+
+    fn test_deployment() -> Deployment {
+        Deployment {
+            name: "test",
+            bit: 1,
+            start_time: START_TIME,
+            timeout: i64::MAX,
+            min_activation_height: 0,
+            period: PERIOD,
+            threshold: 15,
+        }
+    }
+
+    /// Build a chain; `signal(height)` decides whether each header sets the bit
+    fn chain(len: u64, signal: impl Fn(u64) -> bool) -> Vec<BlockHeader> {
+        (0..len)
+            .map(|height| BlockHeader {
+                version: if signal(height) {
+                    0x2000_0002
+                } else {
+                    0x2000_0000
+                },
+                prev_block_hash: [0u8; 32],
+                merkle_root: [0u8; 32],
+                // Median time past crosses START_TIME during the first period
+                timestamp: START_TIME as u64 - 10 + height,
+                bits: 0x1d00ffff,
+                nonce: 0,
+            })
+            .collect()
+    }
+
+    fn status(headers: &[BlockHeader], tip_height: u64) -> DeploymentStatus {
+        deployment_status(&test_deployment(), tip_height, |start, end| {
+            Ok(headers[start as usize..=end as usize].to_vec())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deployment_locks_in_and_activates() {
+        // Signalling in the second period (heights 20-39) crosses the threshold
+        let headers = chain(80, |h| (20..36).contains(&h));
+
+        let defined = status(&headers, 10);
+        assert_eq!(defined.state, DeploymentState::Defined);
+        assert_eq!(defined.statistics, None);
+
+        let started = status(&headers, 29);
+        assert_eq!(started.state, DeploymentState::Started);
+        assert_eq!(started.since, 20);
+        assert_eq!(
+            started.statistics,
+            Some(Statistics {
+                period: PERIOD,
+                threshold: 15,
+                elapsed: 10,
+                count: 10,
+                possible: true,
+            })
+        );
+
+        let locked_in = status(&headers, 39);
+        assert_eq!(locked_in.state, DeploymentState::LockedIn);
+        assert_eq!(locked_in.since, 40);
+
+        let active = status(&headers, 79);
+        assert_eq!(active.state, DeploymentState::Active);
+        assert_eq!(active.since, 60);
+    }
+
+    #[test]
+    fn test_deployment_below_threshold_stays_started() {
+        // 14 signalling blocks: one short of the threshold
+        let headers = chain(60, |h| (20..34).contains(&h));
+        let status = status(&headers, 59);
+        assert_eq!(status.state, DeploymentState::Started);
+        assert_eq!(status.since, 20);
+    }
+
+    #[test]
+    fn test_impossible_threshold_reported() {
+        // Six non-signalling blocks leave too few to reach 15 of 20
+        let headers = chain(30, |h| h >= 26);
+        let stats = status(&headers, 25).statistics.unwrap();
+        assert_eq!(stats.count, 0);
+        assert!(!stats.possible);
+    }
+
+    #[test]
+    fn test_deployment_times_out() {
+        let mut deployment = test_deployment();
+        deployment.timeout = START_TIME + 15;
+        let headers = chain(60, |_| false);
+        let status = deployment_status(&deployment, 59, |start, end| {
+            Ok(headers[start as usize..=end as usize].to_vec())
+        })
+        .unwrap();
+        assert_eq!(status.state, DeploymentState::Failed);
+        assert_eq!(status.since, 40);
+    }
+
+    #[test]
+    fn test_never_active_and_top_bits() {
+        let mut deployment = test_deployment();
+        deployment.start_time = NEVER_ACTIVE;
+        let status = deployment_status(&deployment, 100, |_, _| unreachable!()).unwrap();
+        assert_eq!(status.state, DeploymentState::Failed);
+
+        let mut header = chain(1, |_| true).remove(0);
+        assert!(signals(&header, 1));
+        // Bit set but wrong top bits: not a version bits signal
+        header.version = 0x4000_0002;
+        assert!(!signals(&header, 1));
+    }
+}
@@ -0,0 +1,275 @@
+//! Address decoding
+//!
+//! Converts Bitcoin addresses to the scriptPubKey they pay to. Supports
+//! base58check P2PKH/P2SH addresses and bech32/bech32m segwit addresses
+//! (BIP 173, BIP 350).
+
+use sha2::{Digest, Sha256};
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+
+/// Address prefixes for a network
+struct AddressParams {
+    pubkey_prefix: u8,
+    script_prefix: u8,
+    bech32_hrp: &'static str,
+}
+
+fn address_params(network: &str) -> AddressParams {
+    match network {
+        "mainnet" | "main" => AddressParams {
+            pubkey_prefix: 0x00,
+            script_prefix: 0x05,
+            bech32_hrp: "bc",
+        },
+        "regtest" => AddressParams {
+            pubkey_prefix: 0x6f,
+            script_prefix: 0xc4,
+            bech32_hrp: "bcrt",
+        },
+        // testnet and signet share prefixes
+        _ => AddressParams {
+            pubkey_prefix: 0x6f,
+            script_prefix: 0xc4,
+            bech32_hrp: "tb",
+        },
+    }
+}
+
+/// Decode an address for `network` into its scriptPubKey
+///
+/// Returns `None` if the address is malformed or belongs to another network.
+pub fn address_to_script(address: &str, network: &str) -> Option<Vec<u8>> {
+    let params = address_params(network);
+    if let Some(script) = decode_segwit(address, params.bech32_hrp) {
+        return Some(script);
+    }
+
+    let payload = decode_base58check(address)?;
+    if payload.len() != 21 {
+        return None;
+    }
+    let (version, hash) = (payload[0], &payload[1..]);
+    if version == params.pubkey_prefix {
+        // OP_DUP OP_HASH160 <20 bytes> OP_EQUALVERIFY OP_CHECKSIG
+        let mut script = vec![0x76, 0xa9, 0x14];
+        script.extend_from_slice(hash);
+        script.extend_from_slice(&[0x88, 0xac]);
+        Some(script)
+    } else if version == params.script_prefix {
+        // OP_HASH160 <20 bytes> OP_EQUAL
+        let mut script = vec![0xa9, 0x14];
+        script.extend_from_slice(hash);
+        script.push(0x87);
+        Some(script)
+    } else {
+        None
+    }
+}
+
+/// Decode a base58check string into its payload, verifying the checksum
+pub(crate) fn decode_base58check(address: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in address.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, (carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_zeros = address.bytes().take_while(|&c| c == b'1').count();
+    let mut data = vec![0u8; leading_zeros];
+    data.extend(bytes);
+
+    if data.len() < 4 {
+        return None;
+    }
+    let (payload, checksum) = data.split_at(data.len() - 4);
+    let hash = Sha256::digest(Sha256::digest(payload));
+    if &hash[..4] != checksum {
+        return None;
+    }
+    Some(payload.to_vec())
+}
+
+/// Encode a payload as base58check
+pub(crate) fn encode_base58check(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend_from_slice(&Sha256::digest(Sha256::digest(payload))[..4]);
+
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut().rev() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.insert(0, (carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading_zeros = data.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat(b'1')
+        .take(leading_zeros)
+        .chain(digits.iter().map(|&d| BASE58_ALPHABET[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    let mut chk: u32 = 1;
+    for &v in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x01ff_ffff) << 5) ^ v as u32;
+        for (i, g) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+fn decode_segwit(address: &str, expected_hrp: &str) -> Option<Vec<u8>> {
+    // Mixed case is invalid; otherwise compare in lowercase
+    if address.bytes().any(|c| c.is_ascii_lowercase())
+        && address.bytes().any(|c| c.is_ascii_uppercase())
+    {
+        return None;
+    }
+    let address = address.to_ascii_lowercase();
+    let separator = address.rfind('1')?;
+    let (hrp, data) = (&address[..separator], &address[separator + 1..]);
+    if hrp != expected_hrp || data.len() < 6 || address.len() > 90 {
+        return None;
+    }
+    let values: Vec<u8> = data
+        .bytes()
+        .map(|c| BECH32_CHARSET.iter().position(|&a| a == c).map(|v| v as u8))
+        .collect::<Option<_>>()?;
+
+    let mut checked: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    checked.push(0);
+    checked.extend(hrp.bytes().map(|c| c & 31));
+    checked.extend(&values);
+    let constant = bech32_polymod(&checked);
+
+    let (version, program) = values[..values.len() - 6].split_first()?;
+    // v0 uses bech32, v1+ uses bech32m (BIP 350)
+    let expected_constant = if *version == 0 {
+        BECH32_CONST
+    } else {
+        BECH32M_CONST
+    };
+    if *version > 16 || constant != expected_constant {
+        return None;
+    }
+    let program = convert_bits(program, 5, 8)?;
+    if program.len() < 2 || program.len() > 40 {
+        return None;
+    }
+    if *version == 0 && program.len() != 20 && program.len() != 32 {
+        return None;
+    }
+
+    // OP_n <program>
+    let opcode = if *version == 0 { 0x00 } else { 0x50 + version };
+    let mut script = vec![opcode, program.len() as u8];
+    script.extend(program);
+    Some(script)
+}
+
+/// Regroup bits without padding (decoding direction)
+fn convert_bits(data: &[u8], from: u32, to: u32) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let mut out = Vec::new();
+    let max = (1u32 << to) - 1;
+    for &value in data {
+        acc = (acc << from) | value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if bits >= from || (acc << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_p2pkh_and_p2sh() {
+        let script = address_to_script("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", "mainnet").unwrap();
+        assert_eq!(
+            hex::encode(script),
+            "76a91477bff20c60e522dfaa3350c39b030a5d004e839a88ac"
+        );
+        let script = address_to_script("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", "mainnet").unwrap();
+        assert_eq!(
+            hex::encode(script),
+            "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87"
+        );
+        // Corrupted checksum
+        assert!(address_to_script("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3", "mainnet").is_none());
+
+        let payload = decode_base58check("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").unwrap();
+        assert_eq!(
+            encode_base58check(&payload),
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"
+        );
+    }
+
+    #[test]
+    fn test_decode_segwit() {
+        let script =
+            address_to_script("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "mainnet").unwrap();
+        assert_eq!(
+            hex::encode(script),
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+        let script = address_to_script(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            "mainnet",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(script),
+            "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        // v0 program encoded with bech32m
+        assert!(
+            address_to_script("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh", "mainnet").is_none()
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_network() {
+        assert!(address_to_script("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", "regtest").is_none());
+        assert!(
+            address_to_script("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "regtest").is_none()
+        );
+    }
+}
@@ -11,6 +11,9 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
 use tracing::{debug, warn};
 
+/// Header carrying the client certificate fingerprint
+pub const CLIENT_CERT_FINGERPRINT_HEADER: &str = "x-client-cert-fingerprint";
+
 /// Authentication token (simple string-based for now)
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AuthToken(String);
@@ -129,6 +132,8 @@ pub struct RpcAuthManager {
     method_rate_limits: Arc<Mutex<HashMap<String, (u32, u32)>>>,
     /// Per-method rate limiters (method_name -> rate_limiter)
     method_rate_limiters: Arc<Mutex<HashMap<String, RpcRateLimiter>>>,
+    /// Allowed-method lists for scoped users (users not present have full access)
+    method_scopes: Arc<Mutex<HashMap<UserId, Vec<String>>>>,
 }
 
 impl RpcAuthManager {
@@ -145,6 +150,7 @@ impl RpcAuthManager {
             ip_rate_limit: (50, 5), // Stricter for unauthenticated: 50 burst, 5 req/sec
             method_rate_limits: Arc::new(Mutex::new(HashMap::new())),
             method_rate_limiters: Arc::new(Mutex::new(HashMap::new())),
+            method_scopes: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -161,6 +167,7 @@ impl RpcAuthManager {
             ip_rate_limit: (default_burst / 2, default_rate / 2), // Half of authenticated limit
             method_rate_limits: Arc::new(Mutex::new(HashMap::new())),
             method_rate_limiters: Arc::new(Mutex::new(HashMap::new())),
+            method_scopes: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -178,12 +185,45 @@ impl RpcAuthManager {
         Ok(())
     }
 
+    /// Add an authentication token limited to a set of RPC methods
+    ///
+    /// Entries are exact method names or prefixes ending in `*` (e.g. `getblock*`).
+    /// An empty list grants full access, same as `add_token`. A token already
+    /// added with full access keeps it.
+    pub async fn add_scoped_token(
+        &self,
+        token: String,
+        allowed_methods: Vec<String>,
+    ) -> Result<()> {
+        let user_id = UserId::Token(AuthToken::new(token.clone()));
+        {
+            let tokens = self.valid_tokens.lock().await;
+            let scopes = self.method_scopes.lock().await;
+            if tokens.contains_key(&token) && !scopes.contains_key(&user_id) {
+                warn!("RPC token is both unscoped and scoped; keeping full access");
+                return Ok(());
+            }
+        }
+        self.add_token(token).await?;
+
+        let mut scopes = self.method_scopes.lock().await;
+        if allowed_methods.is_empty() {
+            scopes.remove(&user_id);
+        } else {
+            scopes.insert(user_id, allowed_methods);
+        }
+
+        Ok(())
+    }
+
     /// Remove an authentication token
     pub async fn remove_token(&self, token: &str) -> Result<()> {
         let mut tokens = self.valid_tokens.lock().await;
         if let Some(user_id) = tokens.remove(token) {
             let mut limiters = self.rate_limiters.lock().await;
             limiters.remove(&user_id);
+            let mut scopes = self.method_scopes.lock().await;
+            scopes.remove(&user_id);
         }
         Ok(())
     }
@@ -265,9 +305,9 @@ impl RpcAuthManager {
         }
 
         // Try certificate-based authentication (from TLS connection)
-        // Note: This would need to be integrated with TLS connection handling
-        // For now, we'll check a custom header that could be set by TLS middleware
-        if let Some(cert_header) = headers.get("x-client-cert-fingerprint") {
+        // The fingerprint is set by the RPC server's TLS termination (`rpc_tls`)
+        // or by a TLS-terminating proxy in front of a plaintext server
+        if let Some(cert_header) = headers.get(CLIENT_CERT_FINGERPRINT_HEADER) {
             if let Ok(fingerprint) = cert_header.to_str() {
                 let certs = self.valid_certificates.lock().await;
                 if let Some(user_id) = certs.get(fingerprint) {
@@ -298,6 +338,20 @@ impl RpcAuthManager {
         }
     }
 
+    /// Check whether an authenticated user may call a method
+    ///
+    /// Users without a method scope (unscoped tokens, certificates, IP users)
+    /// are allowed to call every method.
+    pub async fn is_method_permitted(&self, user_id: &UserId, method_name: &str) -> bool {
+        let scopes = self.method_scopes.lock().await;
+        match scopes.get(user_id) {
+            Some(allowed) => allowed
+                .iter()
+                .any(|pattern| method_matches(pattern, method_name)),
+            None => true,
+        }
+    }
+
     /// Check rate limit for a user
     pub async fn check_rate_limit(&self, user_id: &UserId) -> bool {
         let mut limiters = self.rate_limiters.lock().await;
@@ -334,7 +388,7 @@ impl RpcAuthManager {
             // Default per-method limits (more restrictive for expensive methods)
             match method_name {
                 "getblock" | "getblockheader" | "getrawtransaction" => (20, 2), // Expensive queries
-                "sendrawtransaction" | "submitblock" => (10, 1),                // Write operations
+                "sendrawtransaction" | "submitblock" | "generatetoaddress" => (10, 1), // Write operations
                 _ => (100, 10), // Default for other methods
             }
         });
@@ -367,6 +421,14 @@ impl RpcAuthManager {
     }
 }
 
+/// Match a method name against an allowlist entry (exact or `prefix*`)
+fn method_matches(pattern: &str, method_name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => method_name.starts_with(prefix),
+        None => pattern == method_name,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -427,4 +489,69 @@ mod tests {
         assert!(!result.requires_auth);
         assert!(result.error.is_none());
     }
+
+    #[tokio::test]
+    async fn test_read_only_token_rejects_submitblock() {
+        let auth = RpcAuthManager::new(true);
+        auth.add_scoped_token(
+            "read-only".to_string(),
+            vec!["getblock*".to_string(), "getbestblockhash".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert("authorization", "Bearer read-only".parse().unwrap());
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let result = auth.authenticate_request(&headers, addr).await;
+        let user_id = result.user_id.unwrap();
+
+        assert!(auth.is_method_permitted(&user_id, "getblock").await);
+        assert!(auth.is_method_permitted(&user_id, "getblockheader").await);
+        assert!(auth.is_method_permitted(&user_id, "getbestblockhash").await);
+        assert!(!auth.is_method_permitted(&user_id, "submitblock").await);
+        assert!(
+            !auth
+                .is_method_permitted(&user_id, "sendrawtransaction")
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unscoped_token_has_full_access() {
+        let auth = RpcAuthManager::new(true);
+        auth.add_token("full-access".to_string()).await.unwrap();
+        auth.add_scoped_token("empty-scope".to_string(), vec![])
+            .await
+            .unwrap();
+
+        let full = UserId::Token(AuthToken::new("full-access".to_string()));
+        let empty = UserId::Token(AuthToken::new("empty-scope".to_string()));
+        assert!(auth.is_method_permitted(&full, "submitblock").await);
+        assert!(auth.is_method_permitted(&empty, "submitblock").await);
+    }
+
+    #[tokio::test]
+    async fn test_token_listed_as_scoped_and_unscoped_has_full_access() {
+        let auth = RpcAuthManager::new(true);
+        // The order RpcManager registers configured tokens in
+        auth.add_token("both".to_string()).await.unwrap();
+        auth.add_scoped_token("both".to_string(), vec!["getblock*".to_string()])
+            .await
+            .unwrap();
+
+        let both = UserId::Token(AuthToken::new("both".to_string()));
+        assert!(auth.is_method_permitted(&both, "submitblock").await);
+
+        // A scoped token can still have its scope changed
+        auth.add_scoped_token("scoped".to_string(), vec!["getblock*".to_string()])
+            .await
+            .unwrap();
+        auth.add_scoped_token("scoped".to_string(), vec!["submitblock".to_string()])
+            .await
+            .unwrap();
+        let scoped = UserId::Token(AuthToken::new("scoped".to_string()));
+        assert!(auth.is_method_permitted(&scoped, "submitblock").await);
+        assert!(!auth.is_method_permitted(&scoped, "getblock").await);
+    }
 }
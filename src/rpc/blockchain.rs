@@ -2,18 +2,84 @@
 //!
 //! Implements blockchain-related JSON-RPC methods for querying blockchain state.
 
+use crate::node::block_processor::{
+    invalidate_block, precious_block, reconsider_block, reindex_chainstate, serialize_block,
+    serialize_transaction_with_witness, ChainActivation,
+};
+use crate::node::chainstates::BackgroundChainstate;
+use crate::node::performance::PerformanceProfiler;
+use crate::node::sync::{validation_rate, SyncProgress, SyncStatus};
+use crate::rpc::circuit_breaker::StorageCircuitBreaker;
+use crate::rpc::descriptor::descriptor_script;
 use crate::rpc::errors::RpcError;
+use crate::rpc::rawtx::transaction_to_json;
+use crate::rpc::scan::{ScanController, ScanHandle};
+use crate::storage::blockstore::BlockAvailability;
+use crate::storage::coinstatsindex::serialize_coin;
+use crate::storage::hashing::MuHash3072;
 use crate::storage::Storage;
+use crate::validation::witness::transaction_witnesses;
 use anyhow::Result;
-use bllvm_protocol::BlockHeader;
+use bllvm_protocol::bip158::match_filter;
+use bllvm_protocol::block::calculate_tx_id;
+use bllvm_protocol::{Block, BlockHeader, Hash, OutPoint, Transaction};
 use serde_json::{json, Number, Value};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tracing::{debug, warn};
 
+/// A coin spent by a block, as reported in `getblock` verbosity 3
+struct SpentCoin {
+    value: i64,
+    script_pubkey: Vec<u8>,
+    height: u64,
+    generated: bool,
+}
+
 const ZERO_HASH_STR: &str = "0000000000000000000000000000000000000000000000000000000000000000";
 
+/// UTXO set hash reported by `gettxoutsetinfo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UtxoSetHashType {
+    HashSerialized2,
+    MuHash,
+    None,
+}
+
+/// Most headers returned by one `getblockheaders` call (as in a `headers` message)
+const MAX_HEADERS_RESULTS: u64 = 2000;
+
+/// Soft forks buried at a fixed activation height (name, height)
+const BURIED_DEPLOYMENTS: &[(&str, u64)] = &[
+    ("bip34", 227931),
+    ("bip66", 363725),
+    ("bip65", 388381),
+    ("csv", 419328),
+    ("segwit", 481824),
+    ("taproot", 709632),
+];
+
+/// Report the transactions a chain change moved in or out of the active chain
+///
+/// The shared mempool is read-only to RPC handlers (see `sendrawtransaction`),
+/// so they are not applied to it here; the pool's owner does that with
+/// [`MempoolManager::update_for_reorg`](crate::node::mempool::MempoolManager::update_for_reorg).
+fn log_reorg_transactions(method: &str, activation: &ChainActivation) {
+    if activation.disconnected_transactions.is_empty()
+        && activation.connected_transactions.is_empty()
+    {
+        return;
+    }
+    debug!(
+        "{}: {} transactions left and {} entered the active chain",
+        method,
+        activation.disconnected_transactions.len(),
+        activation.connected_transactions.len()
+    );
+}
+
 /// Helper function to decode a 32-byte hash from hex string
-fn decode_hash32(hex: &str) -> Result<[u8; 32], RpcError> {
+pub(crate) fn decode_hash32(hex: &str) -> Result<[u8; 32], RpcError> {
     let hash_bytes =
         hex::decode(hex).map_err(|e| RpcError::invalid_params(format!("Invalid hash: {}", e)))?;
     if hash_bytes.len() != 32 {
@@ -28,6 +94,19 @@ fn decode_hash32(hex: &str) -> Result<[u8; 32], RpcError> {
 #[derive(Clone)]
 pub struct BlockchainRpc {
     storage: Option<Arc<Storage>>,
+    /// Circuit breaker for storage-heavy methods (optional)
+    circuit_breaker: Option<Arc<StorageCircuitBreaker>>,
+    /// Running scans (scanblocks, verifychain, gettxoutsetinfo, reindexchainstate,
+    /// rebuildutxoindex)
+    scans: Arc<ScanController>,
+    /// Background chainstate validating a loaded UTXO snapshot (optional)
+    background_chainstate: Option<Arc<BackgroundChainstate>>,
+    /// Chain work required before `initialblockdownload` turns false
+    minimum_chain_work: u128,
+    /// Profiler supplying the block validation rate for `getsyncstatus` (optional)
+    profiler: Option<Arc<PerformanceProfiler>>,
+    /// Signet challenge blocks connected by a reorg must satisfy (signet only)
+    signet_challenge: Option<Vec<u8>>,
 }
 
 impl Default for BlockchainRpc {
@@ -39,38 +118,124 @@ impl Default for BlockchainRpc {
 impl BlockchainRpc {
     /// Create a new blockchain RPC handler
     pub fn new() -> Self {
-        Self { storage: None }
+        Self {
+            storage: None,
+            circuit_breaker: None,
+            scans: Arc::new(ScanController::new()),
+            background_chainstate: None,
+            minimum_chain_work: 0,
+            profiler: None,
+            signet_challenge: None,
+        }
     }
 
     /// Create with dependencies
     pub fn with_dependencies(storage: Arc<Storage>) -> Self {
         Self {
             storage: Some(storage),
+            circuit_breaker: None,
+            scans: Arc::new(ScanController::new()),
+            background_chainstate: None,
+            minimum_chain_work: 0,
+            profiler: None,
+            signet_challenge: None,
+        }
+    }
+
+    /// Share a scan controller with other RPC handlers
+    pub fn with_scan_controller(mut self, scans: Arc<ScanController>) -> Self {
+        self.scans = scans;
+        self
+    }
+
+    /// Controller tracking this handler's running scans
+    pub fn scan_controller(&self) -> Arc<ScanController> {
+        Arc::clone(&self.scans)
+    }
+
+    /// Report a background chainstate in `getchainstates` (assumeutxo)
+    pub fn with_background_chainstate(mut self, chainstate: Arc<BackgroundChainstate>) -> Self {
+        self.background_chainstate = Some(chainstate);
+        self
+    }
+
+    /// Stay in initial block download until the active chain has this much work
+    pub fn with_minimum_chain_work(mut self, minimum_chain_work: u128) -> Self {
+        self.minimum_chain_work = minimum_chain_work;
+        self
+    }
+
+    /// Estimate the sync ETA in `getsyncstatus` from the profiler's block timings
+    pub fn with_profiler(mut self, profiler: Arc<PerformanceProfiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Check the signet solution of blocks connected by invalidateblock,
+    /// reconsiderblock and preciousblock
+    pub fn with_signet_challenge(mut self, challenge: Option<Vec<u8>>) -> Self {
+        self.signet_challenge = challenge;
+        self
+    }
+
+    /// Guard storage-heavy methods with a circuit breaker
+    pub fn with_circuit_breaker(mut self, circuit_breaker: Arc<StorageCircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Run a scan on the blocking pool, through the circuit breaker if configured
+    ///
+    /// Dropping the returned future, as the server does when the request
+    /// times out, cancels `scan`; `op` stops at its next check of
+    /// [`ScanHandle::is_cancelled`]. The breaker counts storage errors and
+    /// timed-out requests, not scans the user aborted.
+    async fn run_storage_scan<T: Send + 'static>(
+        &self,
+        method: &'static str,
+        storage: &Arc<Storage>,
+        scan: ScanHandle,
+        op: impl FnOnce(&Storage, &ScanHandle) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let pending = match &self.circuit_breaker {
+            Some(breaker) => Some(breaker.start(method)?),
+            None => None,
+        };
+        let storage = Arc::clone(storage);
+        let _cancel = scan.cancel_on_drop();
+        let (result, aborted) = tokio::task::spawn_blocking(move || {
+            let result = op(&storage, &scan);
+            (result, scan.is_cancelled())
+        })
+        .await?;
+        match pending {
+            Some(pending) if aborted => pending.discard(),
+            Some(pending) => pending.finish(&result),
+            None => {}
         }
+        result
     }
 
     /// Calculate difficulty from bits (compact target format)
-    fn calculate_difficulty(bits: u64) -> f64 {
-        // Difficulty = MAX_TARGET / target
-        // MAX_TARGET for Bitcoin mainnet is 0x00000000FFFF0000000000000000000000000000000000000000000000000000
-        // But we use a simpler calculation: difficulty = 2^256 / (target + 1)
-        // For display purposes, we normalize to genesis difficulty = 1.0
-        // MAX_TARGET is 256 bits, use U256 from bllvm-consensus
-        // 0x00000000FFFF0000000000000000000000000000000000000000000000000000
-        // For now, use a placeholder - this should be calculated from difficulty bits
-        const MAX_TARGET: u64 = 0x00000000FFFF0000u64;
-
-        // Simplified difficulty calculation
-        // For display purposes, use a simple approximation based on bits
-        // Note: expand_target returns U256 which is private, so we just use bits directly
-        // Use bits directly for difficulty approximation
-        // Lower bits value = higher difficulty
+    ///
+    /// Difficulty is the difficulty-1 target (`0x1d00ffff`) divided by the
+    /// block's target, computed in floating point as Bitcoin Core does.
+    pub(crate) fn calculate_difficulty(bits: u64) -> f64 {
         let mantissa = (bits & 0x00ffffff) as f64;
         if mantissa == 0.0 {
             return 1.0;
         }
-        let max_mantissa = 0x00ffff00 as f64;
-        (max_mantissa / mantissa).max(1.0)
+        let mut exponent = (bits >> 24) & 0xff;
+        let mut difficulty = 0x0000ffff as f64 / mantissa;
+        while exponent < 29 {
+            difficulty *= 256.0;
+            exponent += 1;
+        }
+        while exponent > 29 {
+            difficulty /= 256.0;
+            exponent -= 1;
+        }
+        difficulty
     }
 
     /// Calculate median time from recent headers (BIP113)
@@ -157,18 +322,11 @@ impl BlockchainRpc {
         #[cfg(debug_assertions)]
         debug!("RPC: getblockchaininfo");
 
-        let softforks = json!({
-            "segwit": {
-                "type": "buried",
-                "active": true,
-                "height": 481824
-            },
-            "taproot": {
-                "type": "buried",
-                "active": true,
-                "height": 709632
-            }
-        });
+        let tip_height = match self.storage {
+            Some(ref storage) => storage.chain().get_height()?.unwrap_or(0),
+            None => 0,
+        };
+        let softforks = Value::Object(self.deployments(tip_height)?);
 
         if let Some(ref storage) = self.storage {
             use std::time::{Duration, Instant};
@@ -180,7 +338,6 @@ impl BlockchainRpc {
 
             let best_hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
             let height = storage.chain().get_height()?.unwrap_or(0);
-            let block_count = storage.blocks().block_count().unwrap_or(0);
 
             let best_hash_hex = {
                 let should_refresh = CACHED_TIP_HASH_HEX.with(|c| {
@@ -209,11 +366,35 @@ impl BlockchainRpc {
             };
 
             // Calculate difficulty from tip header (single lookup)
-            let difficulty = if let Ok(Some(tip_header)) = storage.chain().get_tip_header() {
-                Self::calculate_difficulty(tip_header.bits)
-            } else {
-                1.0
+            let tip_header = storage.chain().get_tip_header().ok().flatten();
+            let difficulty = tip_header
+                .as_ref()
+                .map(|h| Self::calculate_difficulty(h.bits))
+                .unwrap_or(1.0);
+
+            // Sync progress: validated blocks against the best known header
+            let headers_height = storage
+                .chain()
+                .get_best_header_height()?
+                .unwrap_or(0)
+                .max(height);
+            let chainwork = storage
+                .chain()
+                .get_chainwork(&best_hash)?
+                .unwrap_or_else(|| {
+                    // Fallback: calculate total work if cache miss
+                    storage.chain().calculate_total_work().unwrap_or(0)
+                });
+            let chainwork_hex = Self::format_chainwork(chainwork);
+            let sync = crate::node::sync::SyncProgress {
+                headers_height,
+                blocks_height: height,
+                tip_time: tip_header.as_ref().map(|h| h.timestamp).unwrap_or(0),
+                chain_tx_count: storage.transaction_count().unwrap_or(0) as u64,
+                chain_work: chainwork,
+                minimum_chain_work: self.minimum_chain_work,
             };
+            let now = crate::utils::current_timestamp();
 
             // Calculate mediantime from recent headers
             let mediantime = if let Ok(recent_headers) = storage.blocks().get_recent_headers(11) {
@@ -222,24 +403,15 @@ impl BlockchainRpc {
                 0
             };
 
-            let chainwork = storage
-                .chain()
-                .get_chainwork(&best_hash)?
-                .unwrap_or_else(|| {
-                    // Fallback: calculate total work if cache miss
-                    storage.chain().calculate_total_work().unwrap_or(0) as u128
-                });
-            let chainwork_hex = Self::format_chainwork(chainwork);
-
             Ok(json!({
                 "chain": "main",
                 "blocks": height,
-                "headers": block_count,
+                "headers": headers_height,
                 "bestblockhash": best_hash_hex,
                 "difficulty": difficulty,
                 "mediantime": mediantime,
-                "verificationprogress": if height > 0 { 1.0 } else { 0.0 },
-                "initialblockdownload": height == 0,
+                "verificationprogress": sync.verification_progress(now, &crate::node::sync::MAINNET_CHAIN_TX_DATA),
+                "initialblockdownload": sync.is_initial_block_download(now),
                 "chainwork": chainwork_hex,
                 "size_on_disk": if let Some(ref storage) = self.storage {
                     storage.disk_size().unwrap_or(0)
@@ -252,11 +424,7 @@ impl BlockchainRpc {
                     false
                 },
                 "pruneheight": if let Some(ref storage) = self.storage {
-                    if let Some(pruning_manager) = storage.pruning() {
-                        pruning_manager.get_stats().last_prune_height.unwrap_or(0)
-                    } else {
-                        0
-                    }
+                    storage.prune_height().unwrap_or(0)
                 } else {
                     0
                 },
@@ -297,9 +465,202 @@ impl BlockchainRpc {
         }
     }
 
+    /// Get initial block download progress and an estimated time to the tip
+    ///
+    /// Params: []
+    ///
+    /// `eta_seconds` divides the blocks remaining to the best known header by
+    /// the recent validation rate; it is null until a block has been timed.
+    pub async fn get_sync_status(&self) -> Result<Value> {
+        debug!("RPC: getsyncstatus");
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        let height = storage.chain().get_height()?.unwrap_or(0);
+        let best_hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
+        let tip_header = storage.chain().get_tip_header().ok().flatten();
+        let sync = SyncProgress {
+            headers_height: storage
+                .chain()
+                .get_best_header_height()?
+                .unwrap_or(0)
+                .max(height),
+            blocks_height: height,
+            tip_time: tip_header.as_ref().map(|h| h.timestamp).unwrap_or(0),
+            chain_tx_count: storage.transaction_count().unwrap_or(0) as u64,
+            chain_work: storage.chain().get_chainwork(&best_hash)?.unwrap_or(0),
+            minimum_chain_work: self.minimum_chain_work,
+        };
+        let blocks_per_second = self.profiler.as_deref().map_or(0.0, validation_rate);
+        let status = SyncStatus::new(&sync, blocks_per_second, crate::utils::current_timestamp());
+
+        Ok(json!({
+            "blocks": status.blocks,
+            "headers": status.headers,
+            "progress": status.progress,
+            "blocks_per_second": status.blocks_per_second,
+            "eta_seconds": status.eta_seconds,
+            "initialblockdownload": status.initial_block_download,
+        }))
+    }
+
+    /// Get the chainstates the node is following
+    ///
+    /// Params: []
+    ///
+    /// Without a UTXO snapshot this is the single, fully validated active
+    /// chainstate. While a snapshot is being validated in the background the
+    /// background chainstate is listed first and the snapshot chainstate
+    /// (the active chain, not yet validated) second.
+    pub async fn get_chain_states(&self) -> Result<Value> {
+        debug!("RPC: getchainstates");
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        let height = storage.chain().get_height()?;
+        let best_hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
+        let headers = storage
+            .chain()
+            .get_best_header_height()?
+            .unwrap_or(0)
+            .max(height.unwrap_or(0));
+
+        let mut chainstates = Vec::new();
+        let mut active = json!({
+            "blocks": height.unwrap_or(0),
+            "bestblockhash": hex::encode(best_hash),
+            "validated": true,
+        });
+        if let Some(ref background) = self.background_chainstate {
+            let validated = background.is_complete();
+            if !validated {
+                let (blocks, hash) = background.validated_tip().unwrap_or((0, [0u8; 32]));
+                chainstates.push(json!({
+                    "blocks": blocks,
+                    "bestblockhash": hex::encode(hash),
+                    "validated": true,
+                }));
+            }
+            active["validated"] = json!(validated);
+            active["snapshot_blockhash"] = json!(hex::encode(background.snapshot_blockhash()));
+        }
+        chainstates.push(active);
+
+        Ok(json!({
+            "headers": headers,
+            "chainstates": chainstates,
+        }))
+    }
+
+    /// Deployment status for the block after `tip_height`
+    ///
+    /// Buried deployments are active from a fixed height; BIP9 deployments
+    /// are evaluated from the version bits of stored headers.
+    fn deployments(&self, tip_height: u64) -> Result<serde_json::Map<String, Value>> {
+        use crate::node::versionbits::{self, DeploymentState};
+
+        let next_height = tip_height + 1;
+        let mut deployments = serde_json::Map::new();
+        for &(name, height) in BURIED_DEPLOYMENTS {
+            deployments.insert(
+                name.to_string(),
+                json!({
+                    "type": "buried",
+                    "active": next_height >= height,
+                    "height": height
+                }),
+            );
+        }
+
+        for deployment in versionbits::mainnet_deployments() {
+            let status = versionbits::deployment_status(&deployment, tip_height, |start, end| {
+                match self.storage {
+                    Some(ref storage) => storage.blocks().get_headers_by_height_range(start, end),
+                    None => Ok(Vec::new()),
+                }
+            })?;
+
+            let mut bip9 = json!({
+                "bit": deployment.bit,
+                "start_time": deployment.start_time,
+                "timeout": deployment.timeout,
+                "min_activation_height": deployment.min_activation_height,
+                "status": status.state.as_str(),
+                "since": status.since
+            });
+            if let Some(stats) = status.statistics {
+                bip9["statistics"] = json!({
+                    "period": stats.period,
+                    "threshold": stats.threshold,
+                    "elapsed": stats.elapsed,
+                    "count": stats.count,
+                    "possible": stats.possible
+                });
+            }
+
+            let active = status.state == DeploymentState::Active;
+            let mut info = json!({
+                "type": "bip9",
+                "bip9": bip9,
+                "active": active
+            });
+            if active {
+                info["height"] = json!(status.since);
+            }
+            deployments.insert(deployment.name.to_string(), info);
+        }
+
+        Ok(deployments)
+    }
+
+    /// Get soft fork deployment status
+    ///
+    /// Params: ["blockhash"] (optional, defaults to the chain tip)
+    pub async fn get_deployment_info(&self, params: &Value) -> Result<Value> {
+        debug!("RPC: getdeploymentinfo");
+
+        let storage = self.storage.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Storage not available. This operation requires storage to be initialized."
+            )
+        })?;
+
+        let (hash, height) = match params.get(0).and_then(|p| p.as_str()) {
+            Some(hash_hex) => {
+                let hash = decode_hash32(hash_hex)?;
+                let height = storage
+                    .blocks()
+                    .get_height_by_hash(&hash)?
+                    .ok_or_else(|| RpcError::invalid_params("Block not found"))?;
+                (hash, height)
+            }
+            None => (
+                storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]),
+                storage.chain().get_height()?.unwrap_or(0),
+            ),
+        };
+
+        Ok(json!({
+            "hash": hex::encode(hash),
+            "height": height,
+            "deployments": self.deployments(height)?
+        }))
+    }
+
     /// Get block by hash
-    pub async fn get_block(&self, hash: &str) -> Result<Value> {
-        debug!("RPC: getblock {}", hash);
+    ///
+    /// Params: ["blockhash", verbosity (optional, default: 1)]
+    ///
+    /// Verbosity 0 returns the serialized block, 1 adds txids, 2 decoded
+    /// transactions (with fees when the spent coins are known) and 3 each
+    /// input's `prevout`.
+    pub async fn get_block(&self, hash: &str, verbosity: u64) -> Result<Value> {
+        debug!("RPC: getblock {} verbosity={}", hash, verbosity);
+        if verbosity > 3 {
+            return Err(anyhow::anyhow!("Verbosity must be in range 0..=3"));
+        }
 
         // Decode hash first (before async operations)
         let hash_bytes = match hex::decode(hash) {
@@ -324,31 +685,20 @@ impl BlockchainRpc {
                 tokio::task::spawn_blocking({
                     let storage = storage.clone();
                     let hash_array = hash_array;
-                    move || storage.blocks().get_block(&hash_array)
+                    move || storage.blocks().get_block_availability(&hash_array)
                 })
                 .await
             })
             .await
             {
-                Ok(Ok(Ok(Some(block)))) => {
-                    // Block found - return it
-                    // TODO: Format block according to verbosity parameter
-                    return Ok(json!({
-                        "hash": hash,
-                        "confirmations": 1, // TODO: Calculate actual confirmations
-                        "size": 0, // TODO: Calculate size
-                        "height": 0, // TODO: Get height
-                        "version": block.header.version,
-                        "merkleroot": hex::encode(block.header.merkle_root),
-                        "tx": [], // TODO: Include transactions
-                        "time": block.header.timestamp,
-                        "bits": format!("{:08x}", block.header.bits),
-                        "difficulty": 1.0, // TODO: Calculate difficulty
-                    }));
+                Ok(Ok(Ok(BlockAvailability::Available(block)))) => {
+                    return self.block_to_json(storage, &block, &hash_array, verbosity);
                 }
-                Ok(Ok(Ok(None))) => {
-                    // Block not found - return error
-                    return Err(anyhow::anyhow!("Block not found"));
+                Ok(Ok(Ok(BlockAvailability::Pruned))) => {
+                    return Err(RpcError::block_pruned(hash).into());
+                }
+                Ok(Ok(Ok(BlockAvailability::NotFound))) => {
+                    return Err(RpcError::block_not_found(hash).into());
                 }
                 Ok(Ok(Err(e))) => {
                     // Storage error - log and fall through to graceful degradation
@@ -370,6 +720,162 @@ impl BlockchainRpc {
         Err(anyhow::anyhow!("Block not found or storage unavailable"))
     }
 
+    /// Format a stored block for `getblock`
+    fn block_to_json(
+        &self,
+        storage: &Storage,
+        block: &Block,
+        hash: &Hash,
+        verbosity: u64,
+    ) -> Result<Value> {
+        let witnesses = storage.blocks().get_witness(hash)?.unwrap_or_default();
+        let serialized = serialize_block(block, &witnesses);
+        if verbosity == 0 {
+            return Ok(json!(hex::encode(serialized)));
+        }
+
+        let height = storage.blocks().get_height_by_hash(hash)?;
+        let tip_height = storage.chain().get_height()?.unwrap_or(0);
+        let confirmations = height
+            .map(|h| Self::calculate_confirmations(h, tip_height))
+            .unwrap_or(0);
+        let stripped_size = serialize_block(block, &[]).len();
+
+        let tx: Vec<Value> = if verbosity == 1 {
+            block
+                .transactions
+                .iter()
+                .map(|tx| json!(hex::encode(calculate_tx_id(tx))))
+                .collect()
+        } else {
+            let prevouts = Self::resolve_prevouts(storage, block, hash, height);
+            block
+                .transactions
+                .iter()
+                .enumerate()
+                .map(|(index, tx)| {
+                    let tx_witnesses = transaction_witnesses(&witnesses, index);
+                    let wire = serialize_transaction_with_witness(tx, tx_witnesses);
+                    let mut entry =
+                        transaction_to_json(tx, tx_witnesses, &wire, hex::encode(&wire));
+                    if index == 0 {
+                        return entry;
+                    }
+
+                    // The fee is only known when every spent coin is
+                    let mut input_value = Some(0i64);
+                    for (i, input) in tx.inputs.iter().enumerate() {
+                        let prevout = prevouts.get(&input.prevout);
+                        input_value = input_value.zip(prevout).map(|(sum, p)| sum + p.value);
+                        if let (3, Some(prevout)) = (verbosity, prevout) {
+                            entry["vin"][i]["prevout"] = json!({
+                                "generated": prevout.generated,
+                                "height": prevout.height,
+                                "value": prevout.value as f64 / 100_000_000.0,
+                                "scriptPubKey": {
+                                    "asm": "",
+                                    "hex": hex::encode(&prevout.script_pubkey)
+                                }
+                            });
+                        }
+                    }
+                    if let Some(input_value) = input_value {
+                        let output_value: i64 = tx.outputs.iter().map(|o| o.value).sum();
+                        entry["fee"] = json!((input_value - output_value) as f64 / 100_000_000.0);
+                    }
+                    entry
+                })
+                .collect()
+        };
+
+        let mut result = json!({
+            "hash": hex::encode(hash),
+            "confirmations": confirmations,
+            "size": serialized.len(),
+            "strippedsize": stripped_size,
+            "weight": stripped_size * 3 + serialized.len(),
+            "height": height,
+            "version": block.header.version,
+            "versionHex": format!("{:08x}", block.header.version),
+            "merkleroot": hex::encode(block.header.merkle_root),
+            "tx": tx,
+            "time": block.header.timestamp,
+            "nonce": block.header.nonce,
+            "bits": format!("{:08x}", block.header.bits),
+            "difficulty": Self::calculate_difficulty(block.header.bits),
+            "nTx": block.transactions.len(),
+        });
+        if block.header.prev_block_hash != [0u8; 32] {
+            result["previousblockhash"] = json!(hex::encode(block.header.prev_block_hash));
+        }
+        Ok(result)
+    }
+
+    /// Coins spent by a block's transactions, keyed by outpoint
+    ///
+    /// Looks in the block itself (for chained spends), then the block's undo
+    /// record, then the txindex. Coins that can't be found (e.g. pruned undo
+    /// data without a txindex) are left out.
+    fn resolve_prevouts(
+        storage: &Storage,
+        block: &Block,
+        hash: &Hash,
+        height: Option<u64>,
+    ) -> HashMap<OutPoint, SpentCoin> {
+        let mut prevouts = HashMap::new();
+        if let Ok(Some(undo)) = storage.undo().get_undo(hash) {
+            let coinbase: HashSet<&OutPoint> = undo.spent_coinbase.iter().collect();
+            for (outpoint, utxo) in &undo.spent {
+                prevouts.insert(
+                    outpoint.clone(),
+                    SpentCoin {
+                        value: utxo.value,
+                        script_pubkey: utxo.script_pubkey.clone(),
+                        height: utxo.height,
+                        generated: coinbase.contains(outpoint),
+                    },
+                );
+            }
+        }
+
+        let txindex = storage.transactions();
+        let mut created: HashMap<Hash, &Transaction> = HashMap::new();
+        for (index, tx) in block.transactions.iter().enumerate() {
+            if index > 0 {
+                for input in tx.inputs.iter() {
+                    if prevouts.contains_key(&input.prevout) {
+                        continue;
+                    }
+                    let in_block = created.get(&input.prevout.hash).and_then(|parent| {
+                        let output = parent.outputs.get(input.prevout.index as usize)?;
+                        Some(SpentCoin {
+                            value: output.value,
+                            script_pubkey: output.script_pubkey.clone(),
+                            height: height.unwrap_or(0),
+                            generated: false,
+                        })
+                    });
+                    let coin = in_block.or_else(|| {
+                        let parent = txindex.get_transaction(&input.prevout.hash).ok()??;
+                        let metadata = txindex.get_metadata(&input.prevout.hash).ok()??;
+                        let output = parent.outputs.get(input.prevout.index as usize)?;
+                        Some(SpentCoin {
+                            value: output.value,
+                            script_pubkey: output.script_pubkey.clone(),
+                            height: metadata.block_height,
+                            generated: metadata.tx_index == 0,
+                        })
+                    });
+                    if let Some(coin) = coin {
+                        prevouts.insert(input.prevout.clone(), coin);
+                    }
+                }
+            }
+            created.insert(calculate_tx_id(tx), tx);
+        }
+        prevouts
+    }
+
     /// Get block hash by height
     pub async fn get_block_hash(&self, height: u64) -> Result<Value> {
         debug!("RPC: getblockhash {}", height);
@@ -659,52 +1165,131 @@ impl BlockchainRpc {
         }
     }
 
-    /// Get UTXO set information
+    /// Compute gettxoutsetinfo result from storage
     ///
-    /// Params: []
-    pub async fn get_txoutset_info(&self) -> Result<Value> {
-        debug!("RPC: gettxoutsetinfo");
-
-        if let Some(ref storage) = self.storage {
-            let (height, best_hash) = {
-                let h = storage.chain().get_height()?.unwrap_or(0);
-                let hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
-                (h, hash)
-            };
+    /// `muhash` and `none` are answered from the coinstatsindex when it is
+    /// enabled and at the tip; `hash_serialized_2` from the per-block stats
+    /// cache. Anything else falls back to scanning the UTXO set, which stops
+    /// early once `scan` is cancelled.
+    fn txoutset_info(
+        storage: &Storage,
+        hash_type: UtxoSetHashType,
+        scan: &ScanHandle,
+    ) -> Result<Value> {
+        let (height, best_hash) = {
+            let h = storage.chain().get_height()?.unwrap_or(0);
+            let hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
+            (h, hash)
+        };
 
-            if let Ok(Some(stats)) = storage.chain().get_latest_utxo_stats() {
-                // Use cached stats - much faster than loading entire UTXO set!
-                Ok(json!({
+        if hash_type != UtxoSetHashType::HashSerialized2 {
+            if let Some((stats, muhash)) = storage.coin_stats().stats_at(&best_hash)? {
+                let mut result = json!({
                     "height": stats.height,
                     "bestblock": hex::encode(best_hash),
-                    "transactions": stats.transactions,
+                    "transactions": storage.transaction_count().unwrap_or(0),
                     "txouts": stats.txouts,
-                    "bogosize": stats.txouts * 180, // Approximate
-                    "hash_serialized_2": hex::encode(stats.hash_serialized_2),
+                    "bogosize": stats.bogosize,
                     "disk_size": storage.disk_size().unwrap_or(0),
                     "total_amount": stats.total_amount as f64 / 100_000_000.0
-                }))
-            } else {
-                // Fallback: Calculate from UTXO set (expensive, but works if cache is missing)
-                // This will be slow with large UTXO sets, but ensures correctness
-                let utxos = storage.utxos().get_all_utxos()?;
-                let txouts = utxos.len();
-                let total_amount: u64 = utxos.values().map(|utxo| utxo.value as u64).sum();
+                });
+                if hash_type == UtxoSetHashType::MuHash {
+                    result["muhash"] = json!(Self::format_muhash(&muhash));
+                }
+                return Ok(result);
+            }
+        } else if let Ok(Some(stats)) = storage.chain().get_latest_utxo_stats() {
+            // Use cached stats - much faster than loading entire UTXO set!
+            return Ok(json!({
+                "height": stats.height,
+                "bestblock": hex::encode(best_hash),
+                "transactions": stats.transactions,
+                "txouts": stats.txouts,
+                "bogosize": stats.txouts * 180, // Approximate
+                "hash_serialized_2": hex::encode(stats.hash_serialized_2),
+                "disk_size": storage.disk_size().unwrap_or(0),
+                "total_amount": stats.total_amount as f64 / 100_000_000.0
+            }));
+        }
 
+        // Fallback: Calculate from UTXO set (expensive, but works if cache is missing)
+        // This will be slow with large UTXO sets, but ensures correctness
+        let utxos = storage.utxos().get_all_utxos()?;
+        if scan.is_cancelled() {
+            return Err(anyhow::anyhow!("gettxoutsetinfo aborted"));
+        }
+        let txouts = utxos.len();
+        let total_amount: u64 = utxos.values().map(|utxo| utxo.value as u64).sum();
+
+        let mut result = json!({
+            "height": height,
+            "bestblock": hex::encode(best_hash),
+            "transactions": storage.transaction_count().unwrap_or(0),
+            "txouts": txouts,
+            "bogosize": txouts * 180, // Approximate
+            "disk_size": storage.disk_size().unwrap_or(0),
+            "total_amount": total_amount as f64 / 100_000_000.0
+        });
+        match hash_type {
+            UtxoSetHashType::HashSerialized2 => {
                 // Calculate hash_serialized_2 (double SHA256 of serialized UTXO set)
                 let hash_serialized_2 = Self::calculate_utxo_set_hash(&utxos);
+                result["hash_serialized_2"] = json!(hex::encode(hash_serialized_2));
+            }
+            UtxoSetHashType::MuHash => {
+                let mut muhash = MuHash3072::new();
+                for (outpoint, utxo) in &utxos {
+                    if scan.is_cancelled() {
+                        return Err(anyhow::anyhow!("gettxoutsetinfo aborted"));
+                    }
+                    let coinbase = storage.utxos().is_coinbase(outpoint)?;
+                    muhash.insert(&serialize_coin(outpoint, utxo, coinbase));
+                }
+                result["muhash"] = json!(Self::format_muhash(&muhash));
+            }
+            UtxoSetHashType::None => {}
+        }
+        Ok(result)
+    }
 
-                Ok(json!({
-                    "height": height,
-                    "bestblock": hex::encode(best_hash),
-                    "transactions": storage.transaction_count().unwrap_or(0),
-                    "txouts": txouts,
-                    "bogosize": txouts * 180, // Approximate
-                    "hash_serialized_2": hex::encode(hash_serialized_2),
-                    "disk_size": storage.disk_size().unwrap_or(0),
-                    "total_amount": total_amount as f64 / 100_000_000.0
-                }))
+    /// Finalize a MuHash for display (byte-reversed, as Bitcoin Core shows it)
+    fn format_muhash(muhash: &MuHash3072) -> String {
+        let mut hash = muhash.finalize();
+        hash.reverse();
+        hex::encode(hash)
+    }
+
+    /// Get UTXO set information
+    ///
+    /// Params: ["hash_type"] (optional: "hash_serialized_2", "muhash" or
+    /// "none"; defaults to "muhash" when the coinstatsindex is enabled and
+    /// "hash_serialized_2" otherwise)
+    pub async fn get_txoutset_info(&self, params: &Value) -> Result<Value> {
+        debug!("RPC: gettxoutsetinfo");
+
+        let hash_type = match params.get(0).and_then(|p| p.as_str()) {
+            Some("hash_serialized_2") => Some(UtxoSetHashType::HashSerialized2),
+            Some("muhash") => Some(UtxoSetHashType::MuHash),
+            Some("none") => Some(UtxoSetHashType::None),
+            Some(other) => {
+                return Err(
+                    RpcError::invalid_params(format!("{} is not a valid hash_type", other)).into(),
+                )
             }
+            None => None,
+        };
+
+        if let Some(ref storage) = self.storage {
+            let hash_type = hash_type.unwrap_or(if storage.coin_stats().is_enabled() {
+                UtxoSetHashType::MuHash
+            } else {
+                UtxoSetHashType::HashSerialized2
+            });
+            let scan = self.scans.start("gettxoutsetinfo");
+            self.run_storage_scan("gettxoutsetinfo", storage, scan, move |storage, scan| {
+                Self::txoutset_info(storage, hash_type, scan)
+            })
+            .await
         } else {
             Ok(json!({
                 "height": 0,
@@ -719,143 +1304,270 @@ impl BlockchainRpc {
         }
     }
 
-    /// Verify blockchain database
-    ///
-    /// Params: [checklevel (optional, default: 3), numblocks (optional, default: 288)]
-    pub async fn verify_chain(
-        &self,
+    /// Verify the last `numblocks` blocks in storage
+    fn verify_chain_storage(
+        storage: &Storage,
         checklevel: Option<u64>,
         numblocks: Option<u64>,
+        scan: &ScanHandle,
     ) -> Result<Value> {
-        debug!(
-            "RPC: verifychain checklevel={:?} numblocks={:?}",
-            checklevel, numblocks
-        );
+        use bllvm_protocol::{BitcoinProtocolEngine, ProtocolVersion};
+        // Use protocol engine which provides the correct validate_block signature
+        let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest)
+            .map_err(|e| anyhow::anyhow!("Failed to create protocol engine: {}", e))?;
 
-        if let Some(ref storage) = self.storage {
-            use bllvm_protocol::{BitcoinProtocolEngine, ProtocolVersion};
-            // Use protocol engine which provides the correct validate_block signature
-            let engine = BitcoinProtocolEngine::new(ProtocolVersion::Regtest)
-                .map_err(|e| anyhow::anyhow!("Failed to create protocol engine: {}", e))?;
+        let check_level = checklevel.unwrap_or(3);
+        let num_blocks = numblocks.unwrap_or(288);
 
-            let check_level = checklevel.unwrap_or(3);
-            let num_blocks = numblocks.unwrap_or(288);
-
-            let tip_height = storage.chain().get_height()?.unwrap_or(0);
-            if tip_height == 0 {
-                return Ok(json!(true)); // Empty chain is valid
-            }
+        let tip_height = storage.chain().get_height()?.unwrap_or(0);
+        if tip_height == 0 {
+            return Ok(json!(true)); // Empty chain is valid
+        }
 
-            // Start from genesis or from (tip_height - num_blocks)
-            let start_height = if tip_height > num_blocks {
-                tip_height - num_blocks
-            } else {
-                0
-            };
+        // Start from genesis or from (tip_height - num_blocks)
+        let start_height = if tip_height > num_blocks {
+            tip_height - num_blocks
+        } else {
+            0
+        };
 
-            let mut errors = Vec::new();
-            let utxo_set = storage
-                .utxos()
-                .get_all_utxos()
-                .map_err(|e| anyhow::anyhow!("Failed to get UTXO set: {}", e))?;
+        let mut errors = Vec::new();
+        let utxo_set = storage
+            .utxos()
+            .get_all_utxos()
+            .map_err(|e| anyhow::anyhow!("Failed to get UTXO set: {}", e))?;
 
-            // Verify blocks from start_height to tip
-            for height in start_height..=tip_height {
-                if let Ok(Some(block_hash)) = storage.blocks().get_hash_by_height(height) {
-                    if let Ok(Some(block)) = storage.blocks().get_block(&block_hash) {
-                        // Validate block using protocol engine (expects &HashMap, returns Result<ValidationResult>)
-                        match engine.validate_block(&block, &utxo_set, height) {
-                            Ok(bllvm_protocol::ValidationResult::Valid) => {
-                                // Block is valid, update UTXO set for next block
-                                // (Simplified - in full implementation would apply block to UTXO set)
-                                // For now, just continue
+        // Verify blocks from start_height to tip
+        for height in start_height..=tip_height {
+            if scan.is_cancelled() {
+                return Err(anyhow::anyhow!("verifychain aborted at height {}", height));
+            }
+            scan.update(
+                (height - start_height) as f64 / (tip_height - start_height + 1) as f64,
+                height,
+            );
+            if let Ok(Some(block_hash)) = storage.blocks().get_hash_by_height(height) {
+                if let Ok(Some(block)) = storage.blocks().get_block(&block_hash) {
+                    // Validate block using protocol engine (expects &HashMap, returns Result<ValidationResult>)
+                    match engine.validate_block(&block, &utxo_set, height) {
+                        Ok(bllvm_protocol::ValidationResult::Valid) => {
+                            // Block is valid, update UTXO set for next block
+                            // (Simplified - in full implementation would apply block to UTXO set)
+                            // For now, just continue
+                        }
+                        Ok(bllvm_protocol::ValidationResult::Invalid(reason)) => {
+                            errors.push(format!("Block at height {} invalid: {}", height, reason));
+                            if check_level >= 4 {
+                                // Level 4: Stop on first error
+                                break;
                             }
-                            Ok(bllvm_protocol::ValidationResult::Invalid(reason)) => {
-                                errors.push(format!(
-                                    "Block at height {} invalid: {}",
-                                    height, reason
-                                ));
-                                if check_level >= 4 {
-                                    // Level 4: Stop on first error
-                                    break;
-                                }
+                        }
+                        Err(e) => {
+                            errors.push(format!(
+                                "Block at height {} validation error: {}",
+                                height, e
+                            ));
+                            if check_level >= 4 {
+                                break;
                             }
-                            Err(e) => {
+                        }
+                    }
+
+                    // Check level 3: Verify block header linkage
+                    if check_level >= 3 && height > 0 {
+                        if let Ok(Some(prev_hash)) = storage.blocks().get_hash_by_height(height - 1)
+                        {
+                            if block.header.prev_block_hash != prev_hash {
                                 errors.push(format!(
-                                    "Block at height {} validation error: {}",
-                                    height, e
+                                    "Block at height {} has incorrect prev_block_hash: expected {}, got {}",
+                                    height,
+                                    hex::encode(prev_hash),
+                                    hex::encode(block.header.prev_block_hash)
                                 ));
                                 if check_level >= 4 {
                                     break;
                                 }
                             }
                         }
+                    }
 
-                        // Check level 3: Verify block header linkage
-                        if check_level >= 3 && height > 0 {
-                            if let Ok(Some(prev_hash)) =
-                                storage.blocks().get_hash_by_height(height - 1)
-                            {
-                                if block.header.prev_block_hash != prev_hash {
-                                    errors.push(format!(
-                                        "Block at height {} has incorrect prev_block_hash: expected {}, got {}",
-                                        height,
-                                        hex::encode(prev_hash),
-                                        hex::encode(block.header.prev_block_hash)
-                                    ));
-                                    if check_level >= 4 {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
+                    // Check level 2: Verify merkle root
+                    if check_level >= 2 {
+                        use bllvm_protocol::mining::calculate_merkle_root;
 
-                        // Check level 2: Verify merkle root
-                        if check_level >= 2 {
-                            use bllvm_protocol::mining::calculate_merkle_root;
-
-                            if let Ok(calculated_root) = calculate_merkle_root(&block.transactions)
-                            {
-                                if calculated_root != block.header.merkle_root {
-                                    errors.push(format!(
-                                        "Block at height {} has incorrect merkle root",
-                                        height
-                                    ));
-                                    if check_level >= 4 {
-                                        break;
-                                    }
+                        if let Ok(calculated_root) = calculate_merkle_root(&block.transactions) {
+                            if calculated_root != block.header.merkle_root {
+                                errors.push(format!(
+                                    "Block at height {} has incorrect merkle root",
+                                    height
+                                ));
+                                if check_level >= 4 {
+                                    break;
                                 }
                             }
                         }
-                    } else {
-                        errors.push(format!("Block at height {height} not found in storage"));
-                        if check_level >= 4 {
-                            break;
-                        }
                     }
                 } else {
-                    errors.push(format!("Block hash at height {height} not found"));
+                    errors.push(format!("Block at height {height} not found in storage"));
                     if check_level >= 4 {
                         break;
                     }
                 }
-            }
-
-            if errors.is_empty() {
-                Ok(Value::Bool(true))
             } else {
-                Ok(json!({
-                    "valid": false,
-                    "errors": errors,
-                    "checked_blocks": (tip_height - start_height + 1)
-                }))
+                errors.push(format!("Block hash at height {height} not found"));
+                if check_level >= 4 {
+                    break;
+                }
             }
+        }
+
+        if errors.is_empty() {
+            Ok(Value::Bool(true))
+        } else {
+            Ok(json!({
+                "valid": false,
+                "errors": errors,
+                "checked_blocks": (tip_height - start_height + 1)
+            }))
+        }
+    }
+
+    /// Verify blockchain database
+    ///
+    /// Params: [checklevel (optional, default: 3), numblocks (optional, default: 288)]
+    pub async fn verify_chain(
+        &self,
+        checklevel: Option<u64>,
+        numblocks: Option<u64>,
+    ) -> Result<Value> {
+        debug!(
+            "RPC: verifychain checklevel={:?} numblocks={:?}",
+            checklevel, numblocks
+        );
+
+        if let Some(ref storage) = self.storage {
+            let scan = self.scans.start("verifychain");
+            self.run_storage_scan("verifychain", storage, scan, move |storage, scan| {
+                Self::verify_chain_storage(storage, checklevel, numblocks, scan)
+            })
+            .await
         } else {
             // No storage - return success (can't verify without storage)
             Ok(json!(true))
         }
     }
 
+    /// Get consecutive block headers starting at a hash
+    ///
+    /// Params: ["start_hash", count (optional, default: 2000), verbose (optional, default: true)]
+    ///
+    /// Returns up to `count` headers (capped at 2000) of the active chain,
+    /// starting with `start_hash`, each formatted as by `getblockheader`.
+    pub async fn get_block_headers(
+        &self,
+        start_hash: &str,
+        count: u64,
+        verbose: bool,
+    ) -> Result<Value> {
+        debug!(
+            "RPC: getblockheaders {} count={} verbose={}",
+            start_hash, count, verbose
+        );
+
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        let hash = decode_hash32(start_hash)?;
+        let start_height = storage
+            .blocks()
+            .get_height_by_hash(&hash)?
+            .filter(|height| {
+                storage.blocks().get_hash_by_height(*height).ok().flatten() == Some(hash)
+            })
+            .ok_or_else(|| anyhow::anyhow!("Block not found"))?;
+        let count = count.min(MAX_HEADERS_RESULTS);
+
+        // Stops at the tip: there is no hash above it
+        let mut headers = Vec::with_capacity(count as usize);
+        for height in start_height..start_height + count {
+            let Some(hash) = storage.blocks().get_hash_by_height(height)? else {
+                break;
+            };
+            headers.push(self.get_block_header(&hex::encode(hash), verbose).await?);
+        }
+        Ok(Value::Array(headers))
+    }
+
+    /// Rebuild the UTXO set, transaction index and chain state from stored blocks
+    ///
+    /// Params: [] (no parameters)
+    ///
+    /// Replays every block of the active chain through validation. Progress is
+    /// reported by `scanstatus`. Fails without changing anything if block
+    /// bodies have been pruned.
+    pub async fn reindex_chain_state(&self) -> Result<Value> {
+        debug!("RPC: reindexchainstate");
+
+        let storage = self
+            .storage
+            .as_ref()
+            .map(Arc::clone)
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        let scan = self
+            .scans
+            .start_exclusive("reindexchainstate")
+            .ok_or_else(|| anyhow::anyhow!("Chainstate reindex already in progress"))?;
+        let height = tokio::task::spawn_blocking(move || {
+            let height = reindex_chainstate(&storage, |height, tip_height| {
+                scan.update((height + 1) as f64 / (tip_height + 1) as f64, height)
+            })?;
+            storage.flush()?;
+            Ok::<_, anyhow::Error>(height)
+        })
+        .await??;
+
+        Ok(json!({
+            "height": height,
+            "blocks": height + 1,
+        }))
+    }
+
+    /// Re-derive the UTXO-derived indexes over a block range
+    ///
+    /// Params: [start_height, stop_height]
+    ///
+    /// Replays the range's UTXO changes into the address index and coin
+    /// statistics index, to repair damage without a full
+    /// `reindexchainstate`. Fails without changing anything if the range
+    /// leaves a gap after an index's tip or any block in it has been pruned.
+    pub async fn rebuild_utxo_index(&self, start_height: u64, stop_height: u64) -> Result<Value> {
+        debug!("RPC: rebuildutxoindex {} {}", start_height, stop_height);
+
+        let storage = self
+            .storage
+            .as_ref()
+            .map(Arc::clone)
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+        let _scan = self
+            .scans
+            .start_exclusive("rebuildutxoindex")
+            .ok_or_else(|| anyhow::anyhow!("Index rebuild already in progress"))?;
+        let outcome = tokio::task::spawn_blocking(move || {
+            let outcome = storage.rebuild_utxo_indexes(start_height, stop_height)?;
+            storage.flush()?;
+            Ok::<_, anyhow::Error>(outcome)
+        })
+        .await??;
+
+        Ok(json!({
+            "start_height": start_height,
+            "stop_height": stop_height,
+            "rebuilt": outcome.rebuilt,
+            "skipped": outcome.skipped,
+        }))
+    }
+
     /// Get chain tips
     ///
     /// Returns information about all known chain tips.
@@ -1056,94 +1768,99 @@ impl BlockchainRpc {
                     .ok_or_else(|| anyhow::anyhow!("Chain not initialized"))?
             };
 
-            if let Ok(Some(block)) = storage.blocks().get_block(&block_hash) {
-                let tx_count = block.transactions.len();
-
-                let block_size = {
-                    let serialized_size = bincode::serialize(&block).map(|b| b.len()).unwrap_or(0);
-                    storage
-                        .blocks()
-                        .get_block_metadata(&block_hash)
-                        .ok()
-                        .flatten()
-                        .map(|_m| serialized_size)
-                        .unwrap_or(serialized_size)
-                };
+            let block = match storage.blocks().get_block_availability(&block_hash)? {
+                BlockAvailability::Available(block) => block,
+                BlockAvailability::Pruned => {
+                    return Err(RpcError::block_pruned(&hex::encode(block_hash)).into())
+                }
+                BlockAvailability::NotFound => {
+                    return Err(RpcError::block_not_found(&hex::encode(block_hash)).into())
+                }
+            };
 
-                let block_weight = block_size; // Simplified - would calculate weight properly
+            let tx_count = block.transactions.len();
 
-                // Get block height
-                let height = storage
+            let block_size = {
+                let serialized_size = bincode::serialize(&block).map(|b| b.len()).unwrap_or(0);
+                storage
                     .blocks()
-                    .get_height_by_hash(&block_hash)?
-                    .unwrap_or(0);
-
-                // Count inputs and outputs
-                let input_count: usize = block.transactions.iter().map(|tx| tx.inputs.len()).sum();
-                let output_count: usize =
-                    block.transactions.iter().map(|tx| tx.outputs.len()).sum();
+                    .get_block_metadata(&block_hash)
+                    .ok()
+                    .flatten()
+                    .map(|_m| serialized_size)
+                    .unwrap_or(serialized_size)
+            };
 
-                // Sum output values
-                let total_out: u64 = block
-                    .transactions
+            let block_weight = block_size; // Simplified - would calculate weight properly
+
+            // Get block height
+            let height = storage
+                .blocks()
+                .get_height_by_hash(&block_hash)?
+                .unwrap_or(0);
+
+            // Count inputs and outputs
+            let input_count: usize = block.transactions.iter().map(|tx| tx.inputs.len()).sum();
+            let output_count: usize = block.transactions.iter().map(|tx| tx.outputs.len()).sum();
+
+            // Sum output values
+            let total_out: u64 = block
+                .transactions
+                .iter()
+                .flat_map(|tx| tx.outputs.iter())
+                .map(|out| out.value as u64)
+                .sum::<u64>();
+
+            // Calculate block subsidy
+            let subsidy = Self::calculate_block_subsidy(height);
+
+            // Calculate total fees (simplified - would need UTXO set for accurate calculation)
+            // For now, estimate: total_out - (subsidy * 100_000_000) if coinbase exists
+            let total_fees = if !block.transactions.is_empty() {
+                // Coinbase is first transaction
+                let coinbase_outputs: u64 = block.transactions[0]
+                    .outputs
                     .iter()
-                    .flat_map(|tx| tx.outputs.iter())
                     .map(|out| out.value as u64)
-                    .sum::<u64>();
-
-                // Calculate block subsidy
-                let subsidy = Self::calculate_block_subsidy(height);
-
-                // Calculate total fees (simplified - would need UTXO set for accurate calculation)
-                // For now, estimate: total_out - (subsidy * 100_000_000) if coinbase exists
-                let total_fees = if !block.transactions.is_empty() {
-                    // Coinbase is first transaction
-                    let coinbase_outputs: u64 = block.transactions[0]
-                        .outputs
-                        .iter()
-                        .map(|out| out.value as u64)
-                        .sum();
-                    // Fee = total outputs - (coinbase outputs which include subsidy)
-                    // This is simplified - real calculation needs UTXO set
-                    total_out.saturating_sub(coinbase_outputs)
-                } else {
-                    0
-                };
-
-                Ok(json!({
-                    "avgfee": if tx_count > 1 { total_fees as f64 / (tx_count - 1) as f64 / 100_000_000.0 } else { 0.0 },
-                    "avgfeerate": 0.0, // Would need to calculate from fees and sizes
-                    "avgtxsize": if tx_count > 0 { block_size / tx_count } else { 0 },
-                    "blockhash": hex::encode(block_hash),
-                    "feerate_percentiles": [0, 0, 0, 0, 0],
-                    "height": height,
-                    "ins": input_count,
-                    "maxfee": 0.0,
-                    "maxfeerate": 0.0,
-                    "maxtxsize": 0,
-                    "medianfee": 0.0,
-                    "mediantime": block.header.timestamp,
-                    "mediantxsize": 0,
-                    "minfee": 0.0,
-                    "minfeerate": 0.0,
-                    "mintxsize": 0,
-                    "outs": output_count,
-                    "subsidy": subsidy,
-                    "swtotal_size": 0,
-                    "swtotal_weight": 0,
-                    "swtxs": 0,
-                    "time": block.header.timestamp,
-                    "total_out": total_out,
-                    "total_size": block_size,
-                    "total_weight": block_weight,
-                    "totalfee": total_fees as f64 / 100_000_000.0,
-                    "txs": tx_count,
-                    "utxo_increase": 0,
-                    "utxo_size_inc": 0
-                }))
+                    .sum();
+                // Fee = total outputs - (coinbase outputs which include subsidy)
+                // This is simplified - real calculation needs UTXO set
+                total_out.saturating_sub(coinbase_outputs)
             } else {
-                Err(anyhow::anyhow!("Block not found"))
-            }
+                0
+            };
+
+            Ok(json!({
+                "avgfee": if tx_count > 1 { total_fees as f64 / (tx_count - 1) as f64 / 100_000_000.0 } else { 0.0 },
+                "avgfeerate": 0.0, // Would need to calculate from fees and sizes
+                "avgtxsize": if tx_count > 0 { block_size / tx_count } else { 0 },
+                "blockhash": hex::encode(block_hash),
+                "feerate_percentiles": [0, 0, 0, 0, 0],
+                "height": height,
+                "ins": input_count,
+                "maxfee": 0.0,
+                "maxfeerate": 0.0,
+                "maxtxsize": 0,
+                "medianfee": 0.0,
+                "mediantime": block.header.timestamp,
+                "mediantxsize": 0,
+                "minfee": 0.0,
+                "minfeerate": 0.0,
+                "mintxsize": 0,
+                "outs": output_count,
+                "subsidy": subsidy,
+                "swtotal_size": 0,
+                "swtotal_weight": 0,
+                "swtxs": 0,
+                "time": block.header.timestamp,
+                "total_out": total_out,
+                "total_size": block_size,
+                "total_weight": block_weight,
+                "totalfee": total_fees as f64 / 100_000_000.0,
+                "txs": tx_count,
+                "utxo_increase": 0,
+                "utxo_size_inc": 0
+            }))
         } else {
             // Graceful degradation: return informative error instead of failing silently
             Err(anyhow::anyhow!(
@@ -1264,6 +1981,9 @@ impl BlockchainRpc {
     /// Invalidate block
     ///
     /// Params: ["blockhash"] (block hash to invalidate)
+    ///
+    /// Marks the block and its descendants invalid and reorgs to the best
+    /// remaining valid chain.
     pub async fn invalidate_block(&self, params: &Value) -> Result<Value> {
         debug!("RPC: invalidateblock");
 
@@ -1276,15 +1996,18 @@ impl BlockchainRpc {
             decode_hash32(blockhash).map_err(|e| anyhow::anyhow!("Invalid block hash: {}", e))?;
 
         if let Some(ref storage) = self.storage {
-            // Mark block as invalid
-            storage.chain().mark_invalid(&hash)?;
-
-            // Check if this is the current tip - if so, we'd need to trigger a reorg
-            // For now, we just mark it as invalid and let the node handle it on next block
-            if let Ok(Some(tip_hash)) = storage.chain().get_tip_hash() {
-                if hash == tip_hash {
-                    warn!("Invalidated current chain tip - reorg may be needed");
-                }
+            let storage = Arc::clone(storage);
+            let challenge = self.signet_challenge.clone();
+            let activation = tokio::task::spawn_blocking(move || {
+                invalidate_block(&storage, &hash, challenge.as_deref())
+            })
+            .await??;
+            log_reorg_transactions("invalidateblock", &activation);
+            if activation.disconnected > 0 {
+                warn!(
+                    "invalidateblock: disconnected {} and connected {} blocks, tip now at height {}",
+                    activation.disconnected, activation.connected, activation.height
+                );
             }
 
             Ok(Value::Null)
@@ -1299,6 +2022,9 @@ impl BlockchainRpc {
     /// Reconsider block
     ///
     /// Params: ["blockhash"] (block hash to reconsider)
+    ///
+    /// Clears the invalid mark from the block, its ancestors and descendants
+    /// and reorgs to the best valid chain.
     pub async fn reconsider_block(&self, params: &Value) -> Result<Value> {
         debug!("RPC: reconsiderblock");
 
@@ -1311,11 +2037,61 @@ impl BlockchainRpc {
             decode_hash32(blockhash).map_err(|e| anyhow::anyhow!("Invalid block hash: {}", e))?;
 
         if let Some(ref storage) = self.storage {
-            // Remove from invalid blocks set
-            storage.chain().unmark_invalid(&hash)?;
+            let storage = Arc::clone(storage);
+            let challenge = self.signet_challenge.clone();
+            let activation = tokio::task::spawn_blocking(move || {
+                reconsider_block(&storage, &hash, challenge.as_deref())
+            })
+            .await??;
+            log_reorg_transactions("reconsiderblock", &activation);
+            if activation.connected > 0 {
+                warn!(
+                    "reconsiderblock: disconnected {} and connected {} blocks, tip now at height {}",
+                    activation.disconnected, activation.connected, activation.height
+                );
+            }
+
+            Ok(Value::Null)
+        } else {
+            // Graceful degradation: return informative error instead of failing silently
+            Err(anyhow::anyhow!(
+                "Storage not available. This operation requires storage to be initialized."
+            ))
+        }
+    }
 
-            // If this block is now valid, it may be reconsidered for chain inclusion
-            // The node will handle this on next block processing
+    /// Precious block
+    ///
+    /// Params: ["blockhash"] (block hash to prefer)
+    ///
+    /// Treats the block as if it were received before every other block of
+    /// equal chainwork, switching to it if it is a competing tip. Chainwork is
+    /// unchanged, so a chain with more work still takes over.
+    pub async fn precious_block(&self, params: &Value) -> Result<Value> {
+        debug!("RPC: preciousblock");
+
+        let blockhash = params
+            .get(0)
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Block hash parameter required"))?;
+
+        let hash =
+            decode_hash32(blockhash).map_err(|e| anyhow::anyhow!("Invalid block hash: {}", e))?;
+
+        if let Some(ref storage) = self.storage {
+            let storage = Arc::clone(storage);
+            let challenge = self.signet_challenge.clone();
+            let activation = tokio::task::spawn_blocking(move || {
+                precious_block(&storage, &hash, challenge.as_deref())
+            })
+            .await??;
+            log_reorg_transactions("preciousblock", &activation);
+            if activation.connected > 0 {
+                warn!(
+                    "preciousblock: disconnected {} and connected {} blocks, tip now at height {}",
+                    activation.disconnected, activation.connected, activation.height
+                );
+            }
 
             Ok(Value::Null)
         } else {
@@ -1475,9 +2251,20 @@ impl BlockchainRpc {
 
         if let Some(ref storage) = self.storage {
             // Get block from storage
+            if let Some(filter) = storage.filters().get_filter(&hash)? {
+                let header = storage
+                    .filters()
+                    .get_filter_header(&hash)?
+                    .map(|header| header.header_hash())
+                    .unwrap_or([0u8; 32]);
+                return Ok(json!({
+                    "filter": hex::encode(&filter.filter_data),
+                    "header": hex::encode(header),
+                }));
+            }
             if let Ok(Some(block)) = storage.blocks().get_block(&hash) {
-                // Get filter service from network manager (if available)
-                // For now, generate filter directly
+                // Blocks connected before the filter index existed: build
+                // the filter directly
                 use bllvm_protocol::bip158::build_block_filter;
 
                 // Get previous outpoint scripts from UTXO set
@@ -1513,12 +2300,197 @@ impl BlockchainRpc {
         }
     }
 
+    /// Find blocks relevant to a set of scripts using block filters (BIP158)
+    ///
+    /// Params: ["action", [scanobjects], start_height (optional, default: 0),
+    ///          stop_height (optional, default: tip), "filtertype" (optional, default: "basic")]
+    ///
+    /// `action` is "start", "status" or "abort". Scan objects are descriptors,
+    /// either as strings or as `{"desc": "..."}`. Matches are candidates: a
+    /// filter can match a block that doesn't touch any of the scripts.
+    pub async fn scan_blocks(&self, params: &Value) -> Result<Value> {
+        debug!("RPC: scanblocks");
+
+        let action = params
+            .get(0)
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Action parameter required"))?;
+        match action {
+            "status" => Ok(match self.scans.status_of("scanblocks") {
+                Some(status) => json!({
+                    "progress": status["progress"],
+                    "current_height": status["current_height"],
+                }),
+                None => Value::Null,
+            }),
+            "abort" => Ok(json!(self.scans.abort_kind("scanblocks"))),
+            "start" => self.start_block_scan(params).await,
+            other => Err(anyhow::anyhow!("Invalid action '{}'", other)),
+        }
+    }
+
+    /// List running scans (scanblocks, verifychain, gettxoutsetinfo, reindexchainstate,
+    /// rebuildutxoindex)
+    ///
+    /// Params: [] (no parameters)
+    pub async fn scan_status(&self) -> Result<Value> {
+        debug!("RPC: scanstatus");
+        Ok(self.scans.status())
+    }
+
+    /// Abort a running scan
+    ///
+    /// Params: [scan_id] (from `scanstatus`). Returns false if no scan with
+    /// that id is running.
+    pub async fn abort_scan(&self, params: &Value) -> Result<Value> {
+        debug!("RPC: abortscan");
+        let scan_id = params
+            .get(0)
+            .and_then(|p| p.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Scan id parameter required"))?;
+        Ok(json!(self.scans.abort(scan_id)))
+    }
+
+    /// Run a `scanblocks` "start" action
+    async fn start_block_scan(&self, params: &Value) -> Result<Value> {
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Storage not available"))?;
+
+        let network = storage
+            .chain()
+            .load_chain_info()?
+            .map(|info| info.chain_params.network)
+            .unwrap_or_else(|| "mainnet".to_string());
+        let scan_objects = params
+            .get(1)
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| anyhow::anyhow!("Scan objects parameter required"))?;
+        let mut scripts = Vec::with_capacity(scan_objects.len());
+        for object in scan_objects {
+            let descriptor = object
+                .as_str()
+                .or_else(|| object.get("desc").and_then(|d| d.as_str()))
+                .ok_or_else(|| anyhow::anyhow!("Scan object must be a descriptor"))?;
+            scripts.push(
+                descriptor_script(descriptor, &network)
+                    .map_err(|e| anyhow::anyhow!("Invalid descriptor: {}", e))?,
+            );
+        }
+
+        let tip_height = storage.chain().get_height()?.unwrap_or(0);
+        let start_height = params.get(2).and_then(|p| p.as_u64()).unwrap_or(0);
+        let stop_height = params.get(3).and_then(|p| p.as_u64()).unwrap_or(tip_height);
+        if start_height > stop_height || stop_height > tip_height {
+            return Err(anyhow::anyhow!(
+                "Invalid height range {}..={} (tip is {})",
+                start_height,
+                stop_height,
+                tip_height
+            ));
+        }
+        let filter_type = params.get(4).and_then(|p| p.as_str()).unwrap_or("basic");
+        if filter_type != "basic" {
+            return Err(anyhow::anyhow!("Unknown filtertype '{}'", filter_type));
+        }
+
+        let scan = self.scans.start_exclusive("scanblocks").ok_or_else(|| {
+            anyhow::anyhow!("Scan already in progress, use action \"abort\" or \"status\"")
+        })?;
+        // Runs on this task so status and abort requests can interleave; the
+        // pending breaker call is dropped, and counted, if the request times out
+        let pending = match &self.circuit_breaker {
+            Some(breaker) => Some(breaker.start("scanblocks")?),
+            None => None,
+        };
+        let result =
+            Self::run_block_scan(storage, &scripts, start_height, stop_height, &scan).await;
+        match pending {
+            Some(pending) if scan.is_cancelled() => pending.discard(),
+            Some(pending) => pending.finish(&result),
+            None => {}
+        }
+        result
+    }
+
+    async fn run_block_scan(
+        storage: &Storage,
+        scripts: &[Vec<u8>],
+        start_height: u64,
+        stop_height: u64,
+        scan: &ScanHandle,
+    ) -> Result<Value> {
+        let mut relevant_blocks = Vec::new();
+        let mut to_height = start_height;
+        let mut completed = true;
+        for height in start_height..=stop_height {
+            if scan.is_cancelled() {
+                completed = false;
+                break;
+            }
+            scan.update(
+                (height - start_height) as f64 / (stop_height - start_height + 1) as f64,
+                height,
+            );
+
+            let hash = storage
+                .blocks()
+                .get_hash_by_height(height)?
+                .ok_or_else(|| anyhow::anyhow!("No block at height {}", height))?;
+            if Self::block_matches_scripts(storage, &hash, scripts)? {
+                relevant_blocks.push(hex::encode(hash));
+            }
+            to_height = height;
+            // Let status and abort requests in
+            tokio::task::yield_now().await;
+        }
+
+        Ok(json!({
+            "from_height": start_height,
+            "to_height": to_height,
+            "relevant_blocks": relevant_blocks,
+            "completed": completed,
+        }))
+    }
+
+    /// Check a block against scripts using its filter
+    ///
+    /// Blocks connected before the filter index existed have no filter; they
+    /// are checked against their outputs and spent coins instead.
+    fn block_matches_scripts(storage: &Storage, hash: &Hash, scripts: &[Vec<u8>]) -> Result<bool> {
+        if let Some(filter) = storage.filters().get_filter(hash)? {
+            return Ok(scripts.iter().any(|script| match_filter(&filter, script)));
+        }
+
+        let block = storage.blocks().get_block(hash)?.ok_or_else(|| {
+            anyhow::anyhow!("No filter or block data for block {}", hex::encode(hash))
+        })?;
+        let outputs = block
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.outputs.iter().map(|output| &output.script_pubkey));
+        let spent = storage.undo().get_undo(hash)?.unwrap_or_default().spent;
+        let mut touched = outputs
+            .cloned()
+            .chain(spent.into_iter().map(|(_, coin)| coin.script_pubkey));
+        Ok(touched.any(|script| scripts.contains(&script)))
+    }
+
     /// Get index information
     ///
     /// Params: [] (no parameters)
     pub async fn get_index_info(&self, _params: &Value) -> Result<Value> {
         debug!("RPC: getindexinfo");
 
+        let (tip_height, filter_height) = match self.storage {
+            Some(ref storage) => (
+                storage.chain().get_height()?,
+                storage.filter_index_height()?,
+            ),
+            None => (None, None),
+        };
+
         // Return available indexes
         // In production, would check which indexes are actually built
         Ok(json!({
@@ -1531,12 +2503,8 @@ impl BlockchainRpc {
                 }
             },
             "basic block filter index": {
-                "synced": true,
-                "best_block_height": if let Some(ref storage) = self.storage {
-                    storage.chain().get_height()?.unwrap_or(0)
-                } else {
-                    0
-                }
+                "synced": filter_height == tip_height,
+                "best_block_height": filter_height.unwrap_or(0),
             }
         }))
     }
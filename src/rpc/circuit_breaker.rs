@@ -0,0 +1,248 @@
+//! Circuit breaker for storage-heavy RPC methods
+//!
+//! Expensive RPCs (gettxoutsetinfo, verifychain, scanblocks) walk the UTXO set
+//! or the block database. If the database starts failing or stalling, every new
+//! call piles up behind it. The breaker counts failed storage calls, including
+//! scans aborted because their request timed out; once it opens, these methods
+//! fail fast with a "node overloaded" error until a probe request succeeds
+//! again. How long a successful scan took is not held against it: a full UTXO
+//! set walk legitimately takes minutes.
+
+use crate::config::RpcCircuitBreakerConfig;
+use crate::node::metrics::MetricsCollector;
+use crate::rpc::errors::RpcError;
+use crate::utils::{CircuitBreaker, CircuitState};
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Circuit breaker guarding storage calls made by RPC handlers
+pub struct StorageCircuitBreaker {
+    breaker: CircuitBreaker,
+    metrics: Option<Arc<MetricsCollector>>,
+}
+
+impl StorageCircuitBreaker {
+    /// Create a breaker from configuration
+    pub fn new(config: &RpcCircuitBreakerConfig) -> Self {
+        Self {
+            breaker: CircuitBreaker::with_success_threshold(
+                config.failure_threshold,
+                config.success_threshold,
+                Duration::from_secs(config.open_timeout_seconds),
+            ),
+            metrics: None,
+        }
+    }
+
+    /// Record state transitions in the given metrics collector
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Get current breaker state
+    pub fn state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    /// Run a storage operation through the breaker
+    ///
+    /// Returns a [`RpcError::node_overloaded`] error (wrapped in `anyhow`) without
+    /// running `op` while the breaker is open. Errors count as failures.
+    pub fn call<T>(&self, method: &str, op: impl FnOnce() -> Result<T>) -> Result<T> {
+        self.admit(method)?;
+        let result = op();
+        self.record(method, &result);
+        result
+    }
+
+    /// Start a storage operation that runs outside the caller's stack
+    ///
+    /// Fails like [`Self::call`] while the breaker is open. The outcome is
+    /// reported through the returned [`PendingCall`]; dropping it unfinished,
+    /// as happens when the request times out, counts as a failure.
+    pub fn start(self: &Arc<Self>, method: &'static str) -> Result<PendingCall> {
+        self.admit(method)?;
+        Ok(PendingCall {
+            breaker: Arc::clone(self),
+            method,
+            finished: false,
+        })
+    }
+
+    fn admit(&self, method: &str) -> Result<()> {
+        let before = self.breaker.state();
+        let allowed = self.breaker.allow_request();
+        self.observe_transition(before);
+
+        if !allowed {
+            if let Some(ref metrics) = self.metrics {
+                metrics.update_rpc(|m| m.storage_circuit_breaker.rejected_total += 1);
+            }
+            return Err(RpcError::node_overloaded(format!(
+                "{method} temporarily unavailable: storage circuit breaker is open"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn record<T>(&self, method: &str, result: &Result<T>) {
+        let before = self.breaker.state();
+        match result {
+            Ok(_) => self.breaker.record_success(),
+            Err(e) => {
+                warn!("RPC {} storage call failed: {}", method, e);
+                self.breaker.record_failure();
+            }
+        }
+        self.observe_transition(before);
+    }
+
+    /// Log and count a state change, if one happened since `before`
+    fn observe_transition(&self, before: CircuitState) {
+        let after = self.breaker.state();
+        if after == before {
+            return;
+        }
+
+        match after {
+            CircuitState::Open => warn!("Storage circuit breaker opened"),
+            CircuitState::HalfOpen => info!("Storage circuit breaker half-open, probing storage"),
+            CircuitState::Closed => info!("Storage circuit breaker closed"),
+        }
+
+        if let Some(ref metrics) = self.metrics {
+            metrics.update_rpc(|m| {
+                let cb = &mut m.storage_circuit_breaker;
+                match after {
+                    CircuitState::Closed => {
+                        cb.state = 0;
+                        cb.closed_total += 1;
+                    }
+                    CircuitState::Open => {
+                        cb.state = 1;
+                        cb.opened_total += 1;
+                    }
+                    CircuitState::HalfOpen => {
+                        cb.state = 2;
+                        cb.half_opened_total += 1;
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Storage operation admitted by [`StorageCircuitBreaker::start`]
+pub struct PendingCall {
+    breaker: Arc<StorageCircuitBreaker>,
+    method: &'static str,
+    finished: bool,
+}
+
+impl PendingCall {
+    /// Record the operation's result
+    pub fn finish<T>(mut self, result: &Result<T>) {
+        self.finished = true;
+        self.breaker.record(self.method, result);
+    }
+
+    /// Record nothing, e.g. because the operation was aborted by the user
+    pub fn discard(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.breaker
+                .record::<()>(self.method, &Err(anyhow::anyhow!("request timed out")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::errors::RpcErrorCode;
+
+    fn test_config() -> RpcCircuitBreakerConfig {
+        RpcCircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 3,
+            success_threshold: 1,
+            open_timeout_seconds: 1,
+        }
+    }
+
+    #[test]
+    fn test_breaker_opens_then_half_opens() {
+        let metrics = Arc::new(MetricsCollector::new());
+        let breaker = StorageCircuitBreaker::new(&test_config()).with_metrics(Arc::clone(&metrics));
+
+        // Force storage failures until the breaker opens
+        for _ in 0..3 {
+            let result: Result<()> =
+                breaker.call("gettxoutsetinfo", || Err(anyhow::anyhow!("database error")));
+            assert!(result.is_err());
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // While open, calls are rejected without touching storage
+        let mut called = false;
+        let err = breaker
+            .call("gettxoutsetinfo", || {
+                called = true;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(!called);
+        let rpc_err = err.downcast::<RpcError>().unwrap();
+        assert_eq!(rpc_err.code, RpcErrorCode::ServerError(-32001));
+
+        // After the open timeout, the next call is let through as a probe
+        std::thread::sleep(Duration::from_millis(1100));
+        let state_during_probe = breaker
+            .call("gettxoutsetinfo", || Ok(breaker.state()))
+            .unwrap();
+        assert_eq!(state_during_probe, CircuitState::HalfOpen);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let cb = metrics.collect().rpc.storage_circuit_breaker;
+        assert_eq!(cb.opened_total, 1);
+        assert_eq!(cb.half_opened_total, 1);
+        assert_eq!(cb.closed_total, 1);
+        assert_eq!(cb.rejected_total, 1);
+        assert_eq!(cb.state, 0);
+    }
+
+    #[test]
+    fn test_only_errors_count_as_failures() {
+        let config = RpcCircuitBreakerConfig {
+            failure_threshold: 1,
+            ..test_config()
+        };
+        let breaker = StorageCircuitBreaker::new(&config);
+
+        // A long scan that succeeds leaves the breaker closed
+        let result = breaker.call("verifychain", || {
+            std::thread::sleep(Duration::from_millis(5));
+            Ok(true)
+        });
+        assert!(result.is_ok());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // A user abort is not held against storage either
+        let breaker = Arc::new(breaker);
+        breaker.start("scanblocks").unwrap().discard();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // A scan abandoned because its request timed out counts as a failure
+        drop(breaker.start("scanblocks").unwrap());
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
@@ -7,13 +7,23 @@
 //! - getrpcinfo: RPC server information
 //! - help: List available RPC methods
 //! - logging: Control logging levels
+//! - getvalidationstats: Critical-path timing percentiles
+//! - gethealth: Network, storage and RPC health
 
+use crate::network::NetworkManager;
+use crate::node::health::{sample_storage_metrics, HealthChecker, HealthStatus};
+use crate::node::mempool::MempoolManager;
+use crate::node::performance::{OperationStats, PerformanceProfiler};
 use crate::rpc::errors::{RpcError, RpcResult};
+use crate::storage::Storage;
+use crate::utils::logging::{log_filter, LOG_CATEGORIES};
 use serde_json::{json, Number, Value};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::mpsc;
 use tracing::debug;
+use tracing::level_filters::LevelFilter;
 
 /// Control RPC methods
 pub struct ControlRpc {
@@ -26,6 +36,12 @@ pub struct ControlRpc {
     /// Cached memory info (refreshed periodically, not every call)
     #[cfg(feature = "sysinfo")]
     cached_memory_info: Option<(Instant, Value)>,
+    /// Performance profiler (optional, for getvalidationstats)
+    profiler: Option<Arc<PerformanceProfiler>>,
+    /// Storage, mempool and network (optional, for gethealth)
+    storage: Option<Arc<Storage>>,
+    mempool: Option<Arc<MempoolManager>>,
+    network: Option<Arc<NetworkManager>>,
 }
 
 impl ControlRpc {
@@ -37,6 +53,10 @@ impl ControlRpc {
             node_shutdown: None,
             #[cfg(feature = "sysinfo")]
             cached_memory_info: None,
+            profiler: None,
+            storage: None,
+            mempool: None,
+            network: None,
         }
     }
 
@@ -51,9 +71,36 @@ impl ControlRpc {
             node_shutdown,
             #[cfg(feature = "sysinfo")]
             cached_memory_info: None,
+            profiler: None,
+            storage: None,
+            mempool: None,
+            network: None,
         }
     }
 
+    /// Set performance profiler
+    pub fn with_profiler(mut self, profiler: Arc<PerformanceProfiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Set storage and mempool (reported by gethealth)
+    pub fn with_dependencies(
+        mut self,
+        storage: Arc<Storage>,
+        mempool: Arc<MempoolManager>,
+    ) -> Self {
+        self.storage = Some(storage);
+        self.mempool = Some(mempool);
+        self
+    }
+
+    /// Set network manager (reported by gethealth)
+    pub fn with_network(mut self, network: Arc<NetworkManager>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
     /// Stop the node gracefully
     ///
     /// Params: [] (no parameters)
@@ -218,30 +265,44 @@ impl ControlRpc {
         // Zero allocation on hot path
         const ACTIVE_COMMANDS: &[&str] = &[
             "getblockchaininfo",
+            "getchainstates",
+            "getsyncstatus",
             "getblock",
             "getblockhash",
             "getblockheader",
+            "getblockheaders",
             "getbestblockhash",
             "getblockcount",
             "getdifficulty",
             "gettxoutsetinfo",
             "verifychain",
+            "scanblocks",
+            "scanstatus",
+            "abortscan",
+            "reindexchainstate",
+            "rebuildutxoindex",
             "getrawtransaction",
             "sendrawtransaction",
             "testmempoolaccept",
+            "submitpackage",
             "decoderawtransaction",
             "gettxout",
             "gettxoutproof",
             "verifytxoutproof",
             "getmempoolinfo",
+            "getmempoolfeehistogram",
             "getrawmempool",
             "savemempool",
+            "getorphaninfo",
+            "getorphantxs",
+            "gettxspendingprevout",
             "getnetworkinfo",
             "getpeerinfo",
             "getconnectioncount",
             "ping",
             "addnode",
             "disconnectnode",
+            "getblockfrompeer",
             "getnettotals",
             "clearbanned",
             "setban",
@@ -249,7 +310,11 @@ impl ControlRpc {
             "getmininginfo",
             "getblocktemplate",
             "submitblock",
+            "submitheader",
+            "generatetoaddress",
             "estimatesmartfee",
+            "getdescriptorinfo",
+            "verifymessage",
             "stop",
             "uptime",
             "getmemoryinfo",
@@ -284,7 +349,7 @@ impl ControlRpc {
                 "getmemoryinfo" => "Returns an object containing information about memory usage.\n\nArguments:\n1. mode (string, optional, default=\"stats\") determines what kind of information is returned.\n   - \"stats\" returns general statistics about memory usage in the daemon.\n   - \"mallocinfo\" returns an XML string describing low-level heap state (only available if compiled with glibc 2.10+).\n\nResult (mode \"stats\"):\n{\n  \"locked\": {               (json object) Information about locked memory manager\n    \"used\": xxxxx,          (numeric) Number of bytes used\n    \"free\": xxxxx,          (numeric) Number of bytes available in current arenas\n    \"total\": xxxxx,         (numeric) Total number of bytes managed\n    \"locked\": xxxxx,        (numeric) Amount of bytes that succeeded locking. If this number is smaller than total, locking pages failed at some point and key data could be swapped to disk.\n    \"chunks_used\": xxxxx,   (numeric) Number allocated chunks\n    \"chunks_free\": xxxxx,   (numeric) Number unused chunks\n  }\n}\n\nExamples:\n> bitcoin-cli getmemoryinfo",
                 "getrpcinfo" => "Returns details about the RPC server.\n\nResult:\n{\n  \"active_commands\" (array) All active commands\n  \"logpath\" (string) The complete file path to the debug log\n}\n\nExamples:\n> bitcoin-cli getrpcinfo",
                 "help" => "List all commands, or get help for a specified command.\n\nArguments:\n1. \"command\"     (string, optional) The command to get help on\n\nResult:\n\"text\"     (string) The help text\n\nExamples:\n> bitcoin-cli help\n> bitcoin-cli help getblock",
-                "logging" => "Gets and sets the logging configuration.\n\nArguments:\n1. \"include\" (array of strings, optional) A list of categories to add debug logging\n2. \"exclude\" (array of strings, optional) A list of categories to remove debug logging\n\nResult:\n{ (json object)\n  \"active\" (boolean) Whether debug logging is active\n}\n\nExamples:\n> bitcoin-cli logging [\"all\"]\n> bitcoin-cli logging [\"http\"] [\"net\"]",
+                "logging" => "Gets and sets the logging configuration.\n\nArguments:\n1. \"include\" (array of strings, optional) A list of categories to add debug logging\n2. \"exclude\" (array of strings, optional) A list of categories to remove debug logging\n\nAlternatively: logging \"enable\"|\"disable\" \"category\" ( \"level\" )\n\nResult:\n{ (json object) keys are the logging categories\n  \"category\" (boolean) Whether the category is being logged\n}\n\nExamples:\n> bitcoin-cli logging [\"all\"]\n> bitcoin-cli logging [\"rpc\"] [\"net\"]\n> bitcoin-cli logging enable mempool trace",
                 _ => return Err(RpcError::invalid_params(format!("Unknown command: {command}"))),
             };
             Ok(json!(help_text.to_string()))
@@ -292,30 +357,44 @@ impl ControlRpc {
             // No command specified, return list of all commands
             let commands = vec![
                 "getblockchaininfo",
+                "getchainstates",
+                "getsyncstatus",
                 "getblock",
                 "getblockhash",
                 "getblockheader",
+                "getblockheaders",
                 "getbestblockhash",
                 "getblockcount",
                 "getdifficulty",
                 "gettxoutsetinfo",
                 "verifychain",
+                "scanblocks",
+                "scanstatus",
+                "abortscan",
+                "reindexchainstate",
+                "rebuildutxoindex",
                 "getrawtransaction",
                 "sendrawtransaction",
                 "testmempoolaccept",
+                "submitpackage",
                 "decoderawtransaction",
                 "gettxout",
                 "gettxoutproof",
                 "verifytxoutproof",
                 "getmempoolinfo",
+                "getmempoolfeehistogram",
                 "getrawmempool",
                 "savemempool",
+                "getorphaninfo",
+                "getorphantxs",
+                "gettxspendingprevout",
                 "getnetworkinfo",
                 "getpeerinfo",
                 "getconnectioncount",
                 "ping",
                 "addnode",
                 "disconnectnode",
+                "getblockfrompeer",
                 "getnettotals",
                 "clearbanned",
                 "setban",
@@ -323,7 +402,11 @@ impl ControlRpc {
                 "getmininginfo",
                 "getblocktemplate",
                 "submitblock",
+                "submitheader",
+                "generatetoaddress",
                 "estimatesmartfee",
+                "getdescriptorinfo",
+                "verifymessage",
                 "stop",
                 "uptime",
                 "getmemoryinfo",
@@ -336,71 +419,132 @@ impl ControlRpc {
         }
     }
 
-    /// Control logging levels
+    /// Get and set the logging configuration
     ///
-    /// Params: ["include"], ["exclude"] (optional arrays of log categories)
+    /// Params: ["include"], ["exclude"] (optional arrays of log categories),
+    /// or [action, category, level] with action "enable" (level defaults to
+    /// "debug") or "disable". Returns whether each category is enabled.
     pub async fn logging(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: logging");
 
-        // Get include/exclude categories
-        let _include = params
-            .get(0)
-            .and_then(|p| p.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        let exclude = params
-            .get(1)
-            .and_then(|p| p.as_array())
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        // Basic logging control implementation
-        // Note: Full dynamic filter updates would require access to the global subscriber
-        // which is complex. This implementation provides basic status and documents
-        // the current filter state. For full control, the node would need to:
-        // 1. Store a reference to the EnvFilter layer
-        // 2. Provide methods to update the filter dynamically
-        // 3. Rebuild the subscriber with the new filter
-
-        // Check current filter state from environment
-        use crate::utils::env_or_default;
-        let current_filter = env_or_default("RUST_LOG", "info");
+        let changes = Self::logging_changes(params)?;
+        let Some(filter) = log_filter() else {
+            if changes.is_empty() {
+                let categories: BTreeMap<&str, bool> = LOG_CATEGORIES
+                    .iter()
+                    .map(|(name, _)| (*name, false))
+                    .collect();
+                return Ok(json!(categories));
+            }
+            return Err(RpcError::internal_error(
+                "Logging was not initialized with a reloadable filter",
+            ));
+        };
+
+        let mut filter = filter
+            .lock()
+            .map_err(|_| RpcError::internal_error("Log filter lock poisoned"))?;
+        for (category, level) in changes {
+            filter
+                .set_category(&category, level)
+                .map_err(RpcError::internal_error)?;
+        }
+        Ok(json!(filter.categories()))
+    }
 
-        // Determine if debug logging is active based on filter
-        let active = current_filter.contains("debug")
-            || current_filter.contains("trace")
-            || !exclude.contains(&"all".to_string());
+    /// Parse `logging` params into (category, level) changes; `None` disables
+    fn logging_changes(params: &Value) -> RpcResult<Vec<(String, Option<LevelFilter>)>> {
+        let check_category = |category: &str| {
+            if category == "all" || LOG_CATEGORIES.iter().any(|(name, _)| *name == category) {
+                Ok(category.to_string())
+            } else {
+                Err(RpcError::invalid_params(format!(
+                    "unknown logging category {category}"
+                )))
+            }
+        };
+
+        // Action form: [action, category, level]
+        if let Some(action) = params.get(0).and_then(|p| p.as_str()) {
+            let category = params
+                .get(1)
+                .and_then(|p| p.as_str())
+                .ok_or_else(|| RpcError::invalid_params("category is required"))?;
+            let category = check_category(category)?;
+            return match action {
+                "enable" => {
+                    let level = match params.get(2).and_then(|p| p.as_str()) {
+                        Some(level) => level.parse::<LevelFilter>().map_err(|_| {
+                            RpcError::invalid_params(format!("invalid log level {level}"))
+                        })?,
+                        None => LevelFilter::DEBUG,
+                    };
+                    Ok(vec![(category, Some(level))])
+                }
+                "disable" => Ok(vec![(category, None)]),
+                _ => Err(RpcError::invalid_params(format!(
+                    "unknown logging action {action}"
+                ))),
+            };
+        }
 
-        Ok(json!({
-            "active": active,
-            "current_filter": current_filter,
-            "note": "Full dynamic filter updates require subscriber access. Use RUST_LOG environment variable for full control."
-        }))
+        // Bitcoin Core form: include list, then exclude list
+        let list = |index: usize| -> RpcResult<Vec<String>> {
+            params
+                .get(index)
+                .and_then(|p| p.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(check_category)
+                        .collect()
+                })
+                .unwrap_or_else(|| Ok(Vec::new()))
+        };
+        let mut changes: Vec<_> = list(0)?
+            .into_iter()
+            .map(|category| (category, Some(LevelFilter::DEBUG)))
+            .collect();
+        changes.extend(list(1)?.into_iter().map(|category| (category, None)));
+        Ok(changes)
     }
 
     /// Get node health status
     ///
-    /// Returns comprehensive health report for all node components
+    /// Params: [] (no parameters)
+    ///
+    /// Reports the overall status (healthy, degraded, unhealthy or down),
+    /// whether each component is healthy, and basic metrics. A node without
+    /// peers is degraded.
     pub async fn gethealth(&self, _params: &Value) -> RpcResult<Value> {
         debug!("RPC: gethealth");
 
-        // This would need access to Node instance to get full health report
-        // For now, return basic health status
+        let report = HealthChecker::new()
+            .check_node(self.network.as_deref(), self.storage.as_deref(), true)
+            .await;
+        let component_healthy = |name: &str| {
+            report
+                .components
+                .iter()
+                .any(|c| c.component == name && c.status == HealthStatus::Healthy)
+        };
+        let peers = match self.network {
+            Some(ref network) => network.connection_count().await,
+            None => 0,
+        };
+        let storage_metrics = self.storage.as_deref().map(sample_storage_metrics);
+
         Ok(json!({
-            "status": "healthy",
-            "message": "Node is operational",
-            "note": "Full health check requires node instance access"
+            "status": report.overall_status.as_str(),
+            "network": component_healthy("network"),
+            "storage": component_healthy("storage"),
+            "rpc": component_healthy("rpc"),
+            "peers": peers,
+            "tip_height": storage_metrics.as_ref().map(|m| m.chain_height),
+            "mempool_size": self.mempool.as_ref().map(|m| m.size()),
+            "disk_usage": storage_metrics.as_ref().map(|m| m.disk_size),
+            "uptime": self.start_time.elapsed().as_secs(),
+            "components": report.components,
         }))
     }
 
@@ -418,6 +562,38 @@ impl ControlRpc {
             "note": "Full metrics require MetricsCollector integration"
         }))
     }
+
+    /// Get critical-path validation timing statistics
+    ///
+    /// Params: [] (no parameters)
+    ///
+    /// Percentiles are computed on demand over the profiler's most recent samples.
+    pub async fn getvalidationstats(&self, _params: &Value) -> RpcResult<Value> {
+        debug!("RPC: getvalidationstats");
+
+        let profiler = self
+            .profiler
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Performance profiler not available"))?;
+        let stats = profiler.get_stats();
+
+        Ok(json!({
+            "block_validation": Self::stage_stats_json(&stats.block_processing),
+            "utxo_application": Self::stage_stats_json(&stats.utxo_application),
+            "message_processing": Self::stage_stats_json(&stats.message_processing),
+        }))
+    }
+
+    /// Format per-stage timing statistics (milliseconds)
+    fn stage_stats_json(stats: &OperationStats) -> Value {
+        json!({
+            "count": stats.count,
+            "p50_ms": stats.p50_ms,
+            "p90_ms": stats.p90_ms,
+            "p99_ms": stats.p99_ms,
+            "max_ms": stats.max_ms,
+        })
+    }
 }
 
 impl Default for ControlRpc {
@@ -0,0 +1,431 @@
+//! Output script descriptors
+//!
+//! Descriptor checksums (BIP 380) and the `getdescriptorinfo` RPC. Parsing
+//! is limited to what the RPC reports: the top-level function, bracket
+//! structure, wildcards and key expressions. Private keys are replaced by
+//! their public keys in the normalized descriptor. Only non-ranged
+//! descriptors with literal keys can be turned into a script.
+
+use crate::rpc::address::{address_to_script, decode_base58check, encode_base58check};
+use crate::rpc::errors::{RpcError, RpcResult};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde_json::{json, Value};
+use tracing::debug;
+
+/// Characters allowed in a descriptor, in checksum symbol order
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Length of a descriptor checksum
+pub const DESCRIPTOR_CHECKSUM_LENGTH: usize = 8;
+
+/// Maximum descriptor length accepted by the RPC
+const MAX_DESCRIPTOR_LENGTH: usize = 10_000;
+
+/// Top-level and nested descriptor functions
+const DESCRIPTOR_FUNCTIONS: &[&str] = &[
+    "sh",
+    "wsh",
+    "pk",
+    "pkh",
+    "wpkh",
+    "combo",
+    "multi",
+    "sortedmulti",
+    "multi_a",
+    "sortedmulti_a",
+    "tr",
+    "addr",
+    "raw",
+    "rawtr",
+];
+
+/// Extended private key versions and their public counterparts
+const EXTENDED_KEY_VERSIONS: &[([u8; 4], [u8; 4])] = &[
+    // xprv -> xpub
+    ([0x04, 0x88, 0xad, 0xe4], [0x04, 0x88, 0xb2, 0x1e]),
+    // tprv -> tpub
+    ([0x04, 0x35, 0x83, 0x94], [0x04, 0x35, 0x87, 0xcf]),
+];
+
+fn polymod(c: u64, value: u64) -> u64 {
+    const GENERATOR: [u64; 5] = [
+        0xf5_dee5_1989,
+        0xa9_fdca_3312,
+        0x1b_ab10_e32d,
+        0x37_06b1_677a,
+        0x64_4d62_6ffd,
+    ];
+    let top = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+    for (i, g) in GENERATOR.iter().enumerate() {
+        if (top >> i) & 1 == 1 {
+            c ^= g;
+        }
+    }
+    c
+}
+
+/// Compute the checksum of a descriptor (without a `#` suffix)
+///
+/// Returns `None` if the descriptor contains characters outside the
+/// descriptor character set.
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch)? as u64;
+        // Low 5 bits are a symbol; groups of three high parts form another
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..DESCRIPTOR_CHECKSUM_LENGTH {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+    Some(
+        (0..DESCRIPTOR_CHECKSUM_LENGTH)
+            .map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+/// Split off and verify an optional `#checksum` suffix
+///
+/// Returns the descriptor without the suffix.
+pub fn verify_checksum(descriptor: &str) -> Result<&str, String> {
+    let mut parts = descriptor.split('#');
+    let payload = parts.next().unwrap_or_default();
+    let provided = parts.next();
+    if parts.next().is_some() {
+        return Err("Multiple '#' symbols".to_string());
+    }
+    let computed =
+        descriptor_checksum(payload).ok_or_else(|| "Invalid characters in payload".to_string())?;
+    if let Some(provided) = provided {
+        if provided.len() != DESCRIPTOR_CHECKSUM_LENGTH {
+            return Err(format!(
+                "Expected {} character checksum, not {} characters",
+                DESCRIPTOR_CHECKSUM_LENGTH,
+                provided.len()
+            ));
+        }
+        if provided != computed {
+            return Err(format!(
+                "Provided checksum '{provided}' does not match computed checksum '{computed}'"
+            ));
+        }
+    }
+    Ok(payload)
+}
+
+/// Check the function names and bracket nesting of a descriptor
+///
+/// Returns the top-level function name.
+fn check_structure(descriptor: &str) -> Result<&str, String> {
+    let mut stack = Vec::new();
+    let mut name_start = 0;
+    for (i, ch) in descriptor.char_indices() {
+        match ch {
+            '(' => {
+                let name = &descriptor[name_start..i];
+                if !DESCRIPTOR_FUNCTIONS.contains(&name) {
+                    return Err(format!("'{name}' is not a valid descriptor function"));
+                }
+                stack.push(')');
+            }
+            '[' => stack.push(']'),
+            '{' => stack.push('}'),
+            ')' | ']' | '}' => {
+                if stack.pop() != Some(ch) {
+                    return Err(format!("Unexpected '{ch}' at position {i}"));
+                }
+                if stack.is_empty() && i + 1 != descriptor.len() {
+                    return Err(format!(
+                        "Unexpected characters after descriptor: '{}'",
+                        &descriptor[i + 1..]
+                    ));
+                }
+            }
+            _ => {}
+        }
+        if matches!(ch, '(' | ',' | '{') {
+            name_start = i + 1;
+        }
+    }
+    if !stack.is_empty() {
+        return Err("Unbalanced brackets in descriptor".to_string());
+    }
+    match descriptor.find('(') {
+        Some(end) if descriptor.ends_with(')') => Ok(&descriptor[..end]),
+        _ => Err(format!("'{descriptor}' is not a valid descriptor function")),
+    }
+}
+
+/// Public form of a WIF or extended private key, if `token` is one
+fn public_key_for(token: &str) -> Option<String> {
+    let payload = decode_base58check(token)?;
+    let secp = Secp256k1::signing_only();
+
+    // WIF: version, 32-byte key, optional compression flag
+    if matches!(payload.first(), Some(0x80) | Some(0xef)) {
+        let compressed = match payload.len() {
+            33 => false,
+            34 if payload[33] == 0x01 => true,
+            _ => return None,
+        };
+        let secret = SecretKey::from_slice(&payload[1..33]).ok()?;
+        let public = PublicKey::from_secret_key(&secp, &secret);
+        return Some(if compressed {
+            hex::encode(public.serialize())
+        } else {
+            hex::encode(public.serialize_uncompressed())
+        });
+    }
+
+    // BIP 32: version, depth, fingerprint, child number, chain code, 0x00 || key
+    if payload.len() == 78 && payload[45] == 0x00 {
+        let (_, public_version) = EXTENDED_KEY_VERSIONS
+            .iter()
+            .find(|(private_version, _)| payload[..4] == private_version[..])?;
+        let secret = SecretKey::from_slice(&payload[46..78]).ok()?;
+        let public = PublicKey::from_secret_key(&secp, &secret);
+        let mut extended = public_version.to_vec();
+        extended.extend_from_slice(&payload[4..45]);
+        extended.extend_from_slice(&public.serialize());
+        return Some(encode_base58check(&extended));
+    }
+    None
+}
+
+/// Replace private keys with public keys
+///
+/// Returns the rewritten descriptor and whether any private key was found.
+fn strip_private_keys(descriptor: &str) -> (String, bool) {
+    let mut output = String::with_capacity(descriptor.len());
+    let mut has_private_keys = false;
+    let mut token = String::new();
+    let mut flush = |token: &mut String, output: &mut String| {
+        match public_key_for(token) {
+            Some(public) => {
+                has_private_keys = true;
+                output.push_str(&public);
+            }
+            None => output.push_str(token),
+        }
+        token.clear();
+    };
+    for ch in descriptor.chars() {
+        if BASE58_ALPHABET.contains(ch) {
+            token.push(ch);
+        } else {
+            flush(&mut token, &mut output);
+            output.push(ch);
+        }
+    }
+    flush(&mut token, &mut output);
+    (output, has_private_keys)
+}
+
+/// Analysis of a descriptor reported by `getdescriptorinfo`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorInfo {
+    /// Descriptor without private keys, with its checksum appended
+    pub descriptor: String,
+    /// Checksum of the descriptor as given
+    pub checksum: String,
+    /// Whether the descriptor has a `*` derivation wildcard
+    pub is_range: bool,
+    /// Whether the descriptor describes scripts that can be signed for
+    pub is_solvable: bool,
+    /// Whether the descriptor as given contains private keys
+    pub has_private_keys: bool,
+}
+
+/// Analyze a descriptor, verifying its checksum if one is attached
+pub fn analyze_descriptor(descriptor: &str) -> Result<DescriptorInfo, String> {
+    let payload = verify_checksum(descriptor)?;
+    let function = check_structure(payload)?;
+    let checksum = descriptor_checksum(payload).unwrap_or_default();
+    let (public, has_private_keys) = strip_private_keys(payload);
+    let public_checksum = descriptor_checksum(&public).unwrap_or_default();
+    Ok(DescriptorInfo {
+        descriptor: format!("{public}#{public_checksum}"),
+        checksum,
+        is_range: payload.contains('*'),
+        is_solvable: !matches!(function, "addr" | "raw"),
+        has_private_keys,
+    })
+}
+
+/// Output script of a non-ranged descriptor
+///
+/// Supports `addr(ADDRESS)`, `raw(HEX)`, and `pkh(KEY)` / `wpkh(KEY)` with a
+/// hex public key. `network` selects the address format.
+pub fn descriptor_script(descriptor: &str, network: &str) -> Result<Vec<u8>, String> {
+    let payload = verify_checksum(descriptor)?;
+    let function = check_structure(payload)?;
+    let argument = &payload[function.len() + 1..payload.len() - 1];
+    match function {
+        "addr" => address_to_script(argument, network)
+            .ok_or_else(|| format!("Invalid address for {network}: {argument}")),
+        "raw" => hex::decode(argument).map_err(|e| format!("Invalid script hex: {e}")),
+        "pkh" | "wpkh" => {
+            let key = hex::decode(argument)
+                .ok()
+                .and_then(|key| PublicKey::from_slice(&key).ok())
+                .ok_or_else(|| format!("Unsupported key expression: {argument}"))?;
+            let hash = crate::storage::hashing::hash160(&key.serialize());
+            let mut script = if function == "pkh" {
+                vec![0x76, 0xa9, 0x14]
+            } else {
+                vec![0x00, 0x14]
+            };
+            script.extend_from_slice(&hash);
+            if function == "pkh" {
+                script.extend_from_slice(&[0x88, 0xac]);
+            }
+            Ok(script)
+        }
+        _ => Err(format!("Descriptor function '{function}' can't be scanned")),
+    }
+}
+
+/// Analyze a descriptor
+///
+/// Params: ["descriptor"]
+pub async fn get_descriptor_info(params: &Value) -> RpcResult<Value> {
+    debug!("RPC: getdescriptorinfo");
+
+    use crate::rpc::validation::validate_string_param;
+    let descriptor = validate_string_param(params, 0, "descriptor", Some(MAX_DESCRIPTOR_LENGTH))?;
+    let info = analyze_descriptor(&descriptor).map_err(RpcError::invalid_address_or_key)?;
+    Ok(json!({
+        "descriptor": info.descriptor,
+        "checksum": info.checksum,
+        "isrange": info.is_range,
+        "issolvable": info.is_solvable,
+        "hasprivatekeys": info.has_private_keys,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bip380_checksum_vectors() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(
+            verify_checksum("raw(deadbeef)#89f8spxm"),
+            Ok("raw(deadbeef)")
+        );
+        assert_eq!(verify_checksum("raw(deadbeef)"), Ok("raw(deadbeef)"));
+
+        // Missing, too long and too short checksums
+        assert!(verify_checksum("raw(deadbeef)#").is_err());
+        assert!(verify_checksum("raw(deadbeef)#89f8spxmx").is_err());
+        assert!(verify_checksum("raw(deadbeef)#89f8spx").is_err());
+        // Errors in the payload or the checksum
+        assert!(verify_checksum("raw(deedbeef)#89f8spxm").is_err());
+        assert!(verify_checksum("raw(deedbeef)##9f8spxm").is_err());
+        assert!(verify_checksum("raw(deadbeef)#39f8spxm").is_err());
+        // Characters outside the descriptor charset
+        assert!(verify_checksum("raw(Ü)#00000000").is_err());
+    }
+
+    #[test]
+    fn test_descriptor_script() {
+        assert_eq!(
+            descriptor_script("raw(deadbeef)#89f8spxm", "mainnet").unwrap(),
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert_eq!(
+            descriptor_script(
+                "addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)",
+                "mainnet"
+            )
+            .unwrap(),
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+        );
+        assert_eq!(
+            descriptor_script(
+                "wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)",
+                "mainnet"
+            )
+            .unwrap(),
+            hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+        );
+        assert!(descriptor_script(
+            "addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)",
+            "regtest"
+        )
+        .is_err());
+        assert!(descriptor_script("pkh(xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)", "mainnet").is_err());
+        assert!(descriptor_script(
+            "sh(wpkh(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798))",
+            "mainnet"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_descriptor_info() {
+        let info = analyze_descriptor(
+            "pkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)",
+        )
+        .unwrap();
+        assert_eq!(info.checksum, "ml40v0wf");
+        assert!(info.descriptor.ends_with("#ml40v0wf"));
+        assert!(info.is_range);
+        assert!(info.is_solvable);
+        assert!(!info.has_private_keys);
+
+        let info = analyze_descriptor("addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)").unwrap();
+        assert_eq!(
+            info.descriptor,
+            "addr(bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4)#uyjndxcw"
+        );
+        assert!(!info.is_range);
+        assert!(!info.is_solvable);
+
+        assert!(analyze_descriptor("foo(deadbeef)").is_err());
+        assert!(analyze_descriptor("wpkh(02aa").is_err());
+        assert!(analyze_descriptor("raw(deadbeef))").is_err());
+    }
+
+    #[test]
+    fn test_private_keys_are_normalized() {
+        let info =
+            analyze_descriptor("combo(L4rK1yDtCWekvXuE6oXD9jCYfFNV2cWRpVuPLBcCU2z8TrisoyY1)")
+                .unwrap();
+        assert!(info.has_private_keys);
+        assert_eq!(
+            info.descriptor,
+            "combo(03a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)#jjh3jqzg"
+        );
+
+        // BIP 32 test vector 1 master key
+        let info = analyze_descriptor(
+            "pkh(xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi/1/*)",
+        )
+        .unwrap();
+        assert!(info.has_private_keys);
+        assert!(info.is_range);
+        assert_eq!(
+            info.descriptor,
+            "pkh(xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8/1/*)#hu9h76rn"
+        );
+    }
+}
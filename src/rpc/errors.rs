@@ -31,10 +31,16 @@ pub enum RpcErrorCode {
     TxAlreadyInMempool,
     /// Block not found (-5)
     BlockNotFound,
+    /// Block data pruned (-1)
+    BlockPruned,
     /// Transaction not found (-5)
     TxNotFound,
     /// UTXO not found (-5)
     UtxoNotFound,
+    /// Invalid address or key (-5)
+    InvalidAddressOrKey,
+    /// Error validating a submitted structure (-25)
+    VerifyError,
 }
 
 impl RpcErrorCode {
@@ -52,8 +58,11 @@ impl RpcErrorCode {
             RpcErrorCode::TxMissingInputs => -1,
             RpcErrorCode::TxAlreadyInMempool => -27,
             RpcErrorCode::BlockNotFound => -5,
+            RpcErrorCode::BlockPruned => -1,
             RpcErrorCode::TxNotFound => -5,
             RpcErrorCode::UtxoNotFound => -5,
+            RpcErrorCode::InvalidAddressOrKey => -5,
+            RpcErrorCode::VerifyError => -25,
         }
     }
 
@@ -71,8 +80,11 @@ impl RpcErrorCode {
             RpcErrorCode::TxMissingInputs => "Missing inputs",
             RpcErrorCode::TxAlreadyInMempool => "Transaction already in mempool",
             RpcErrorCode::BlockNotFound => "Block not found",
+            RpcErrorCode::BlockPruned => "Block not available (pruned data)",
             RpcErrorCode::TxNotFound => "Transaction not found",
             RpcErrorCode::UtxoNotFound => "No such UTXO",
+            RpcErrorCode::InvalidAddressOrKey => "Invalid address or key",
+            RpcErrorCode::VerifyError => "Error validating structure",
         }
     }
 }
@@ -140,6 +152,14 @@ impl RpcError {
         )
     }
 
+    /// Block known but its data has been pruned
+    pub fn block_pruned(hash: &str) -> Self {
+        Self::new(
+            RpcErrorCode::BlockPruned,
+            format!("Block not available (pruned data): {hash}"),
+        )
+    }
+
     /// Transaction not found
     pub fn tx_not_found(txid: &str) -> Self {
         Self::new(
@@ -153,6 +173,11 @@ impl RpcError {
         Self::new(RpcErrorCode::UtxoNotFound, "No such UTXO")
     }
 
+    /// Invalid address or key
+    pub fn invalid_address_or_key(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::InvalidAddressOrKey, message)
+    }
+
     /// Transaction already in mempool
     pub fn tx_already_in_mempool(txid: &str) -> Self {
         Self::new(
@@ -166,6 +191,22 @@ impl RpcError {
         Self::new(RpcErrorCode::TxRejected, reason)
     }
 
+    /// Node overloaded (storage circuit breaker open)
+    pub fn node_overloaded(message: impl Into<String>) -> Self {
+        Self::new(RpcErrorCode::ServerError(-32001), message)
+    }
+
+    /// Request timed out
+    pub fn request_timeout(method: &str, timeout: std::time::Duration) -> Self {
+        Self::new(
+            RpcErrorCode::ServerError(-32002),
+            format!(
+                "Request timed out: {method} exceeded {}s",
+                timeout.as_secs()
+            ),
+        )
+    }
+
     /// Convert to JSON-RPC error response
     pub fn to_json(&self, id: Option<Value>) -> Value {
         let mut error = json!({
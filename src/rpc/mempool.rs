@@ -2,9 +2,13 @@
 //!
 //! Implements mempool-related JSON-RPC methods:
 //! - getmempoolinfo
+//! - getmempoolfeehistogram
 //! - getrawmempool
 //! - savemempool
+//! - getorphaninfo / getorphantxs
+//! - gettxspendingprevout
 
+use crate::network::NetworkManager;
 use crate::node::mempool::MempoolManager;
 use crate::rpc::errors::RpcResult;
 use crate::storage::Storage;
@@ -19,6 +23,7 @@ use tracing::debug;
 pub struct MempoolRpc {
     mempool: Option<Arc<MempoolManager>>,
     storage: Option<Arc<Storage>>,
+    network: Option<Arc<NetworkManager>>,
 }
 
 impl MempoolRpc {
@@ -27,6 +32,7 @@ impl MempoolRpc {
         Self {
             mempool: None,
             storage: None,
+            network: None,
         }
     }
 
@@ -35,9 +41,16 @@ impl MempoolRpc {
         Self {
             mempool: Some(mempool),
             storage: Some(storage),
+            network: None,
         }
     }
 
+    /// Set the network manager whose orphan pool is reported
+    pub fn with_network(mut self, network: Arc<NetworkManager>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
     /// Get mempool information
     ///
     /// Params: []
@@ -47,15 +60,9 @@ impl MempoolRpc {
 
         if let Some(ref mempool) = self.mempool {
             let size = mempool.size();
-
-            // This is much faster for large mempools (approximate: avg tx size ~250 bytes)
-            let bytes = if size == 0 {
-                0
-            } else {
-                // Fast path: estimate from size (good enough for RPC)
-                // For exact calculation, would need to serialize all, but that's expensive
-                size * 250 // Approximate average transaction size
-            };
+            let bytes = mempool.bytes();
+            // Configured rate is sat/kvB; RPC reports BTC/kvB
+            let min_relay_fee = mempool.min_relay_fee_per_kvb() as f64 / 100_000_000.0;
 
             Ok(json!({
                 "loaded": true,
@@ -63,8 +70,8 @@ impl MempoolRpc {
                 "bytes": bytes,
                 "usage": bytes,
                 "maxmempool": 300000000,
-                "mempoolminfee": 0.00001000,
-                "minrelaytxfee": 0.00001000
+                "mempoolminfee": min_relay_fee,
+                "minrelaytxfee": min_relay_fee
             }))
         } else {
             // Graceful degradation: return empty mempool info when mempool unavailable
@@ -84,6 +91,151 @@ impl MempoolRpc {
         }
     }
 
+    /// Get the mempool's fee rate histogram
+    ///
+    /// Params: [boundaries (optional, ascending sat/vB fee rates)]
+    ///
+    /// Buckets are keyed by their lowest fee rate; `sizes` is the total
+    /// vsize of the transactions paying from that rate up to the next
+    /// boundary. The defaults follow Bitcoin Core (1, 2, 3, 4, 5, 6, 7, 8,
+    /// 10, ... 10000 sat/vB).
+    pub async fn getmempoolfeehistogram(&self, params: &Value) -> RpcResult<Value> {
+        use crate::node::mempool::DEFAULT_FEE_HISTOGRAM_BOUNDARIES;
+        use crate::rpc::errors::RpcError;
+
+        debug!("RPC: getmempoolfeehistogram");
+
+        let boundaries: Vec<u64> = match params.get(0) {
+            None | Some(Value::Null) => DEFAULT_FEE_HISTOGRAM_BOUNDARIES.to_vec(),
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|v| {
+                    v.as_u64().ok_or_else(|| {
+                        RpcError::invalid_params(
+                            "Fee rate boundaries must be non-negative integers (sat/vB)"
+                                .to_string(),
+                        )
+                    })
+                })
+                .collect::<RpcResult<_>>()?,
+            Some(_) => {
+                return Err(RpcError::invalid_params(
+                    "Boundaries must be an array of fee rates".to_string(),
+                ))
+            }
+        };
+        if boundaries.is_empty() || boundaries.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(RpcError::invalid_params(
+                "Fee rate boundaries must be non-empty and strictly increasing".to_string(),
+            ));
+        }
+
+        let buckets = match (&self.mempool, &self.storage) {
+            (Some(mempool), Some(storage)) => {
+                let utxo_set = storage.utxos().get_all_utxos().unwrap_or_default();
+                mempool.fee_histogram(&utxo_set, &boundaries)
+            }
+            (Some(mempool), None) => mempool.fee_histogram(&Default::default(), &boundaries),
+            (None, _) => Vec::new(),
+        };
+
+        let mut histogram = serde_json::Map::new();
+        let mut total_vsize = 0u64;
+        let mut total_fees = 0u64;
+        for bucket in &buckets {
+            total_vsize += bucket.vsize;
+            total_fees += bucket.fees;
+            histogram.insert(
+                bucket.from.to_string(),
+                json!({
+                    "sizes": bucket.vsize,
+                    "count": bucket.count,
+                    "fees": bucket.fees,
+                    "from_feerate": bucket.from,
+                    "to_feerate": bucket.to,
+                }),
+            );
+        }
+        Ok(json!({
+            "fee_histogram": histogram,
+            "total_vsize": total_vsize,
+            "total_fees": total_fees
+        }))
+    }
+
+    /// Get orphan pool summary
+    ///
+    /// Params: []
+    pub async fn getorphaninfo(&self, _params: &Value) -> RpcResult<Value> {
+        debug!("RPC: getorphaninfo");
+
+        let Some(ref network) = self.network else {
+            return Ok(json!({
+                "size": 0,
+                "bytes": 0,
+                "maxorphantxs": 0
+            }));
+        };
+        let pool = network.orphan_pool().lock().await;
+        Ok(json!({
+            "size": pool.len(),
+            "bytes": pool.total_bytes(),
+            "maxorphantxs": pool.max_orphans()
+        }))
+    }
+
+    /// List orphan transactions, oldest first
+    ///
+    /// Params: [verbosity (optional, default: 0)]
+    ///
+    /// Verbosity 0 returns txids; 1 adds `wtxid`, `bytes`, `entry_time` and
+    /// the announcing peer (`from`); 2 also adds the raw transaction `hex`.
+    /// Orphans are stored without witness data, so `wtxid` equals `txid`.
+    pub async fn getorphantxs(&self, params: &Value) -> RpcResult<Value> {
+        debug!("RPC: getorphantxs");
+
+        let verbosity = match params.get(0) {
+            None | Some(Value::Null) => 0,
+            Some(value) => value
+                .as_u64()
+                .filter(|verbosity| *verbosity <= 2)
+                .ok_or_else(|| {
+                    crate::rpc::errors::RpcError::invalid_params(
+                        "Verbosity must be 0, 1 or 2".to_string(),
+                    )
+                })?,
+        };
+        let Some(ref network) = self.network else {
+            return Ok(json!([]));
+        };
+
+        use bllvm_protocol::serialization::transaction::serialize_transaction;
+        let pool = network.orphan_pool().lock().await;
+        let mut entries: Vec<_> = pool.entries().collect();
+        entries.sort_by_key(|(txid, entry)| (entry.entry_time, **txid));
+        let orphans = entries
+            .into_iter()
+            .map(|(txid, entry)| {
+                let txid = hex::encode(txid);
+                if verbosity == 0 {
+                    return json!(txid);
+                }
+                let mut orphan = json!({
+                    "txid": txid,
+                    "wtxid": txid,
+                    "bytes": entry.bytes,
+                    "entry_time": entry.entry_time,
+                    "from": entry.from_peer.to_string()
+                });
+                if verbosity == 2 {
+                    orphan["hex"] = json!(hex::encode(serialize_transaction(&entry.tx)));
+                }
+                orphan
+            })
+            .collect();
+        Ok(Value::Array(orphans))
+    }
+
     /// Get all transaction IDs in mempool
     ///
     /// Params: [verbose (optional, default: false)]
@@ -197,7 +349,8 @@ impl MempoolRpc {
         if let Some(mempool) = &self.mempool {
             use crate::utils::env_or_default;
             let data_dir = env_or_default("DATA_DIR", "data");
-            let mempool_path = std::path::Path::new(&data_dir).join("mempool.dat");
+            let mempool_path =
+                std::path::Path::new(&data_dir).join(crate::node::mempool::MEMPOOL_FILE);
 
             // Arc implements Deref, so we can call methods directly
             if let Err(e) = mempool.save_to_disk(&mempool_path) {
@@ -492,6 +645,59 @@ impl MempoolRpc {
         }
     }
 
+    /// Get the mempool transactions spending the given outputs
+    ///
+    /// Params: [[{"txid": "hex", "vout": n}, ...]]
+    ///
+    /// `spendingtxid` is omitted for outputs no mempool transaction spends.
+    pub async fn gettxspendingprevout(&self, params: &Value) -> RpcResult<Value> {
+        use crate::rpc::errors::RpcError;
+        use bllvm_protocol::OutPoint;
+
+        debug!("RPC: gettxspendingprevout");
+
+        let outputs = params
+            .get(0)
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| RpcError::invalid_params("Outputs array required".to_string()))?;
+        if outputs.is_empty() {
+            return Err(RpcError::invalid_params(
+                "Invalid parameter, outputs are missing".to_string(),
+            ));
+        }
+        let mempool = self
+            .mempool
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Mempool not initialized".to_string()))?;
+
+        let mut result = Vec::with_capacity(outputs.len());
+        for output in outputs {
+            let txid = output
+                .get("txid")
+                .and_then(|t| t.as_str())
+                .ok_or_else(|| RpcError::invalid_params("Output txid required".to_string()))?;
+            let vout = output.get("vout").and_then(|v| v.as_u64()).ok_or_else(|| {
+                RpcError::invalid_params(
+                    "Invalid parameter, vout cannot be negative or missing".to_string(),
+                )
+            })?;
+            let hash_bytes = hex::decode(txid)
+                .map_err(|e| RpcError::invalid_params(format!("Invalid transaction ID: {e}")))?;
+            let hash: Hash = hash_bytes.try_into().map_err(|_| {
+                RpcError::invalid_params("Transaction ID must be 32 bytes".to_string())
+            })?;
+
+            let outpoint = OutPoint { hash, index: vout };
+            let mut entry = json!({ "txid": txid, "vout": vout });
+            if let Some(spender) = mempool.spending_transaction(&outpoint) {
+                entry["spendingtxid"] = json!(hex::encode(spender));
+            }
+            result.push(entry);
+        }
+
+        Ok(Value::Array(result))
+    }
+
     /// Helper: Get ancestors for a transaction
     fn get_ancestors(&self, mempool: &MempoolManager, tx_hash: &Hash) -> Vec<Hash> {
         let mut ancestors = Vec::new();
@@ -0,0 +1,249 @@
+//! Signed messages
+//!
+//! The message-signing hash convention (the "Bitcoin Signed Message" magic
+//! prefix followed by double SHA256) and the `verifymessage` RPC. The node
+//! holds no private keys, so signing is left to wallets; only verification of
+//! compact signatures against P2PKH addresses is provided here.
+
+use crate::rpc::address::decode_base58check;
+use crate::rpc::errors::{RpcError, RpcResult};
+use crate::storage::hashing::{double_sha256, hash160};
+use crate::validation::witness::encode_varint;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use serde_json::{json, Value};
+use tracing::debug;
+
+/// Prefix committed to by every signed message
+pub const MESSAGE_MAGIC: &str = "Bitcoin Signed Message:\n";
+
+/// P2PKH version bytes (mainnet, and testnet/signet/regtest)
+const PUBKEY_PREFIXES: [u8; 2] = [0x00, 0x6f];
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Why a signed message failed to verify
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum MessageVerificationError {
+    #[error("Invalid address")]
+    InvalidAddress,
+
+    #[error("Address does not refer to key")]
+    AddressNoKey,
+
+    #[error("Malformed base64 encoding")]
+    MalformedSignature,
+
+    #[error("Public key could not be recovered from the signature")]
+    PubkeyNotRecovered,
+
+    #[error("Message not signed by the address's key")]
+    NotSigned,
+}
+
+/// Hash a message the way `signmessage` signs it
+///
+/// The magic prefix and the message are each serialized with a compact-size
+/// length, then the whole is double-SHA256 hashed.
+pub fn message_magic_hash(message: &str) -> [u8; 32] {
+    let mut data = Vec::with_capacity(MESSAGE_MAGIC.len() + message.len() + 10);
+    for part in [MESSAGE_MAGIC.as_bytes(), message.as_bytes()] {
+        data.extend_from_slice(&encode_varint(part.len() as u64));
+        data.extend_from_slice(part);
+    }
+    double_sha256(&data)
+}
+
+/// Verify a base64 compact signature of `message` by a P2PKH `address`
+///
+/// The public key is recovered from the signature; the message is signed by
+/// the address if the key hashes to the address's key hash.
+pub fn verify_message(
+    address: &str,
+    signature: &str,
+    message: &str,
+) -> Result<(), MessageVerificationError> {
+    let payload = decode_base58check(address).ok_or(MessageVerificationError::InvalidAddress)?;
+    if payload.len() != 21 {
+        return Err(MessageVerificationError::InvalidAddress);
+    }
+    if !PUBKEY_PREFIXES.contains(&payload[0]) {
+        return Err(MessageVerificationError::AddressNoKey);
+    }
+
+    let signature = decode_base64(signature).ok_or(MessageVerificationError::MalformedSignature)?;
+    if signature.len() != 65 {
+        return Err(MessageVerificationError::PubkeyNotRecovered);
+    }
+    // Header byte: 27 + recovery id, plus 4 if the key is compressed
+    let header = signature[0];
+    if !(27..=34).contains(&header) {
+        return Err(MessageVerificationError::PubkeyNotRecovered);
+    }
+    let compressed = header >= 31;
+    let recovery_id = RecoveryId::from_i32(((header - 27) & 3) as i32)
+        .map_err(|_| MessageVerificationError::PubkeyNotRecovered)?;
+    let signature = RecoverableSignature::from_compact(&signature[1..], recovery_id)
+        .map_err(|_| MessageVerificationError::PubkeyNotRecovered)?;
+    let digest = Message::from_digest_slice(&message_magic_hash(message))
+        .map_err(|_| MessageVerificationError::PubkeyNotRecovered)?;
+    let pubkey = Secp256k1::verification_only()
+        .recover_ecdsa(&digest, &signature)
+        .map_err(|_| MessageVerificationError::PubkeyNotRecovered)?;
+
+    let key_hash = if compressed {
+        hash160(&pubkey.serialize())
+    } else {
+        hash160(&pubkey.serialize_uncompressed())
+    };
+    if key_hash[..] != payload[1..] {
+        return Err(MessageVerificationError::NotSigned);
+    }
+    Ok(())
+}
+
+/// Verify a signed message
+///
+/// Params: ["address", "signature", "message"]
+///
+/// Returns false if the signature is well-formed but not by the address.
+pub async fn verify_message_rpc(params: &Value) -> RpcResult<Value> {
+    debug!("RPC: verifymessage");
+
+    let param = |index: usize, name: &str| {
+        params
+            .get(index)
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| RpcError::invalid_params(format!("{name} parameter required")))
+    };
+    let address = param(0, "address")?;
+    let signature = param(1, "signature")?;
+    let message = param(2, "message")?;
+
+    match verify_message(address, signature, message) {
+        Ok(()) => Ok(json!(true)),
+        Err(MessageVerificationError::PubkeyNotRecovered | MessageVerificationError::NotSigned) => {
+            Ok(json!(false))
+        }
+        Err(e @ MessageVerificationError::InvalidAddress) => {
+            Err(RpcError::invalid_address_or_key(e.to_string()))
+        }
+        Err(e) => Err(RpcError::invalid_params(e.to_string())),
+    }
+}
+
+/// Decode standard, padded base64
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let input = input.as_bytes();
+    if input.len() % 4 != 0 {
+        return None;
+    }
+    let padding = input.iter().rev().take_while(|&&c| c == b'=').count();
+    if padding > 2 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0u32;
+    for &c in &input[..input.len() - padding] {
+        let value = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_magic_hash() {
+        assert_eq!(
+            hex::encode(message_magic_hash("Trust no one")),
+            "aa8215d723ecd2f14867eeb7e19f192be7bc15a2352a24b991d4f5870cbaf6e8"
+        );
+
+        // Messages of 253 bytes or more take a three-byte length
+        let long = "a".repeat(300);
+        let mut data = vec![MESSAGE_MAGIC.len() as u8];
+        data.extend_from_slice(MESSAGE_MAGIC.as_bytes());
+        data.extend_from_slice(&[0xfd, 0x2c, 0x01]);
+        data.extend_from_slice(long.as_bytes());
+        assert_eq!(message_magic_hash(&long), double_sha256(&data));
+    }
+
+    #[test]
+    fn test_verify_message_vectors() {
+        // Compressed key
+        assert_eq!(
+            verify_message(
+                "15CRxFdyRpGZLW9w8HnHvVduizdL5jKNbs",
+                "IPojfrX2dfPnH26UegfbGQQLrdK844DlHq5157/P6h57WyuS/Qsl+h/WSVGDF4MUi4rWSswW38oimDYfNNUBUOk=",
+                "Trust no one",
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify_message(
+                "15CRxFdyRpGZLW9w8HnHvVduizdL5jKNbs",
+                "IPojfrX2dfPnH26UegfbGQQLrdK844DlHq5157/P6h57WyuS/Qsl+h/WSVGDF4MUi4rWSswW38oimDYfNNUBUOk=",
+                "I just signed this message",
+            ),
+            Err(MessageVerificationError::NotSigned)
+        );
+        // Uncompressed key
+        assert_eq!(
+            verify_message(
+                "11canuhp9X2NocwCq7xNrQYTmUgZAnLK3",
+                "IIcaIENoYW5jZWxsb3Igb24gYnJpbmsgb2Ygc2Vjb25kIGJhaWxvdXQgZm9yIGJhbmtzIAaHRtbCeDZINyavx14=",
+                "Trust me",
+            ),
+            Ok(())
+        );
+        // Testnet address
+        assert_eq!(
+            verify_message(
+                "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB",
+                "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=",
+                "This is just a test message",
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_message("invalid address", "irrelevant", "irrelevant"),
+            Err(MessageVerificationError::InvalidAddress)
+        );
+        assert_eq!(
+            verify_message(
+                "3B5fQsEXEaV8v6U3ejYc8XaKXAkyQj2MjV",
+                "irrelevant",
+                "irrelevant"
+            ),
+            Err(MessageVerificationError::AddressNoKey)
+        );
+        assert_eq!(
+            verify_message(
+                "1KqbBpLy5FARmTPD4VZnDDpYjkUvkr82Pm",
+                "invalid signature, not in base64 encoding",
+                "irrelevant",
+            ),
+            Err(MessageVerificationError::MalformedSignature)
+        );
+        assert_eq!(
+            verify_message(
+                "1KqbBpLy5FARmTPD4VZnDDpYjkUvkr82Pm",
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                "irrelevant",
+            ),
+            Err(MessageVerificationError::PubkeyNotRecovered)
+        );
+    }
+}
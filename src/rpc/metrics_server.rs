@@ -0,0 +1,563 @@
+//! Prometheus metrics endpoint
+//!
+//! Serves collected node metrics in the Prometheus text exposition format on a
+//! dedicated address (`metrics_addr` in the node configuration). Only global
+//! series are exported - no per-peer labels - so label cardinality stays bounded.
+
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::{debug, error, info};
+
+use crate::node::metrics::{MetricsCollector, NodeMetrics};
+use crate::node::performance::{OperationStats, PerformanceProfiler, PerformanceStats};
+
+/// Content type for the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Standalone HTTP server exposing `/metrics`
+pub struct MetricsServer {
+    addr: SocketAddr,
+    metrics: Arc<MetricsCollector>,
+    profiler: Option<Arc<PerformanceProfiler>>,
+}
+
+impl MetricsServer {
+    /// Create a new metrics server
+    pub fn new(addr: SocketAddr, metrics: Arc<MetricsCollector>) -> Self {
+        Self {
+            addr,
+            metrics,
+            profiler: None,
+        }
+    }
+
+    /// Include validation timing summaries from the performance profiler
+    pub fn with_profiler(mut self, profiler: Arc<PerformanceProfiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Bind the configured address and serve scrapes until the task is aborted
+    pub async fn start(self) -> Result<()> {
+        let listener = TcpListener::bind(self.addr).await?;
+        self.serve(listener).await
+    }
+
+    /// Serve scrapes on an already bound listener
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        info!("Metrics endpoint listening on {}", listener.local_addr()?);
+        let server = Arc::new(self);
+
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    debug!("New metrics connection from {}", addr);
+                    let server = Arc::clone(&server);
+                    tokio::spawn(async move {
+                        let io = TokioIo::new(stream);
+                        let service = service_fn(move |req| {
+                            let server = Arc::clone(&server);
+                            async move { server.handle_request(req) }
+                        });
+                        if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
+                            debug!("Metrics connection from {} failed: {}", addr, e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to accept metrics connection: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Handle a single scrape request
+    fn handle_request(
+        &self,
+        req: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, hyper::Error> {
+        if req.method() != Method::GET || req.uri().path() != "/metrics" {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Full::new(Bytes::from_static(b"Not found\n")))
+                .expect("Failed to build metrics response"));
+        }
+
+        let performance = self.profiler.as_ref().map(|p| p.get_stats());
+        let body = render_prometheus(&self.metrics.collect(), performance.as_ref());
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", PROMETHEUS_CONTENT_TYPE)
+            .header("Content-Length", body.len())
+            .body(Full::new(Bytes::from(body)))
+            .expect("Failed to build metrics response"))
+    }
+}
+
+/// Append a single unlabeled metric with its HELP and TYPE lines
+fn write_metric(output: &mut String, name: &str, help: &str, kind: &str, value: impl Display) {
+    output.push_str(&format!("# HELP {name} {help}\n"));
+    output.push_str(&format!("# TYPE {name} {kind}\n"));
+    output.push_str(&format!("{name} {value}\n"));
+}
+
+/// Append a metric with one sample per value of `label`
+fn write_labeled_metric(
+    output: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    label: &str,
+    samples: &[(&str, impl Display)],
+) {
+    output.push_str(&format!("# HELP {name} {help}\n"));
+    output.push_str(&format!("# TYPE {name} {kind}\n"));
+    for (label_value, value) in samples {
+        output.push_str(&format!("{name}{{{label}=\"{label_value}\"}} {value}\n"));
+    }
+}
+
+/// Append an operation timing summary (quantiles in seconds plus sample count)
+fn write_summary(output: &mut String, name: &str, help: &str, stats: &OperationStats) {
+    output.push_str(&format!("# HELP {name} {help}\n"));
+    output.push_str(&format!("# TYPE {name} summary\n"));
+    for (quantile, value_ms) in [
+        ("0.5", stats.p50_ms),
+        ("0.95", stats.p95_ms),
+        ("0.99", stats.p99_ms),
+    ] {
+        output.push_str(&format!(
+            "{name}{{quantile=\"{quantile}\"}} {}\n",
+            value_ms / 1000.0
+        ));
+    }
+    output.push_str(&format!(
+        "{name}_sum {}\n",
+        stats.avg_ms * stats.count as f64 / 1000.0
+    ));
+    output.push_str(&format!("{name}_count {}\n", stats.count));
+}
+
+/// Render node metrics in the Prometheus text exposition format
+pub fn render_prometheus(metrics: &NodeMetrics, performance: Option<&PerformanceStats>) -> String {
+    let mut output = String::new();
+
+    // Chain and mempool
+    write_metric(
+        &mut output,
+        "bllvm_chain_height",
+        "Current chain tip height",
+        "gauge",
+        metrics.storage.chain_height,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_mempool_transactions",
+        "Transactions in the mempool",
+        "gauge",
+        metrics.mempool.size,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_mempool_bytes",
+        "Approximate mempool size in bytes",
+        "gauge",
+        metrics.mempool.bytes,
+    );
+
+    // Network metrics
+    write_metric(
+        &mut output,
+        "bllvm_network_peers_total",
+        "Total number of connected peers",
+        "gauge",
+        metrics.network.peer_count,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_bytes_sent_total",
+        "Total bytes sent",
+        "counter",
+        metrics.network.bytes_sent,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_bytes_received_total",
+        "Total bytes received",
+        "counter",
+        metrics.network.bytes_received,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_messages_sent_total",
+        "Total messages sent",
+        "counter",
+        metrics.network.messages_sent,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_messages_received_total",
+        "Total messages received",
+        "counter",
+        metrics.network.messages_received,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_active_connections",
+        "Active network connections",
+        "gauge",
+        metrics.network.active_connections,
+    );
+    write_labeled_metric(
+        &mut output,
+        "bllvm_network_connections",
+        "Open connections by connection type",
+        "gauge",
+        "type",
+        &[
+            ("inbound", metrics.network.inbound_connections),
+            (
+                "outbound-full-relay",
+                metrics.network.outbound_full_relay_connections,
+            ),
+            (
+                "block-relay-only",
+                metrics.network.block_relay_only_connections,
+            ),
+            ("feeler", metrics.network.feeler_connections),
+        ],
+    );
+    write_metric(
+        &mut output,
+        "bllvm_network_banned_peers",
+        "Banned peers count",
+        "gauge",
+        metrics.network.banned_peers,
+    );
+
+    // Storage metrics
+    write_metric(
+        &mut output,
+        "bllvm_storage_blocks_total",
+        "Total blocks stored",
+        "gauge",
+        metrics.storage.block_count,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_utxos_total",
+        "Total UTXOs",
+        "gauge",
+        metrics.storage.utxo_count,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_transactions_total",
+        "Total transactions indexed",
+        "gauge",
+        metrics.storage.transaction_count,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_disk_size_bytes",
+        "Estimated disk size in bytes",
+        "gauge",
+        metrics.storage.disk_size,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_within_bounds",
+        "Storage bounds status (1=within bounds, 0=exceeded)",
+        "gauge",
+        u8::from(metrics.storage.within_bounds),
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_header_cache_hit_rate",
+        "Fraction of header lookups served from cache",
+        "gauge",
+        metrics.storage.header_cache_hit_rate,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_storage_block_cache_hit_rate",
+        "Fraction of block lookups served from cache",
+        "gauge",
+        metrics.storage.block_cache_hit_rate,
+    );
+
+    // RPC metrics
+    write_metric(
+        &mut output,
+        "bllvm_rpc_requests_total",
+        "Total RPC requests",
+        "counter",
+        metrics.rpc.requests_total,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_requests_success_total",
+        "Successful RPC requests",
+        "counter",
+        metrics.rpc.requests_success,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_requests_failed_total",
+        "Failed RPC requests",
+        "counter",
+        metrics.rpc.requests_failed,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_requests_per_second",
+        "Current RPC requests per second",
+        "gauge",
+        metrics.rpc.requests_per_second,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_avg_response_time_ms",
+        "Average RPC response time in milliseconds",
+        "gauge",
+        metrics.rpc.avg_response_time_ms,
+    );
+
+    write_metric(
+        &mut output,
+        "bllvm_rpc_work_queue_depth",
+        "RPC requests waiting for a worker",
+        "gauge",
+        metrics.rpc.work_queue_depth,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_work_queue_rejected_total",
+        "RPC requests refused because the work queue was full",
+        "counter",
+        metrics.rpc.work_queue_rejected_total,
+    );
+
+    let breaker = &metrics.rpc.storage_circuit_breaker;
+    write_metric(
+        &mut output,
+        "bllvm_rpc_storage_circuit_breaker_state",
+        "Storage circuit breaker state (0 = closed, 1 = open, 2 = half-open)",
+        "gauge",
+        breaker.state,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_storage_circuit_breaker_opened_total",
+        "Storage circuit breaker transitions to open",
+        "counter",
+        breaker.opened_total,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_storage_circuit_breaker_half_opened_total",
+        "Storage circuit breaker transitions to half-open",
+        "counter",
+        breaker.half_opened_total,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_storage_circuit_breaker_closed_total",
+        "Storage circuit breaker transitions to closed",
+        "counter",
+        breaker.closed_total,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_rpc_storage_circuit_breaker_rejected_total",
+        "RPC requests rejected while the storage circuit breaker was open",
+        "counter",
+        breaker.rejected_total,
+    );
+
+    // Performance metrics
+    write_metric(
+        &mut output,
+        "bllvm_performance_avg_block_processing_time_ms",
+        "Average block processing time in milliseconds",
+        "gauge",
+        metrics.performance.avg_block_processing_time_ms,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_performance_avg_tx_validation_time_ms",
+        "Average transaction validation time in milliseconds",
+        "gauge",
+        metrics.performance.avg_tx_validation_time_ms,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_performance_blocks_per_second",
+        "Blocks processed per second",
+        "gauge",
+        metrics.performance.blocks_per_second,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_performance_transactions_per_second",
+        "Transactions processed per second",
+        "gauge",
+        metrics.performance.transactions_per_second,
+    );
+
+    // Validation timing summaries from the profiler ring buffers
+    if let Some(stats) = performance {
+        write_summary(
+            &mut output,
+            "bllvm_block_validation_seconds",
+            "Block validation time",
+            &stats.block_processing,
+        );
+        write_summary(
+            &mut output,
+            "bllvm_tx_validation_seconds",
+            "Transaction validation time",
+            &stats.tx_validation,
+        );
+        write_summary(
+            &mut output,
+            "bllvm_storage_operation_seconds",
+            "Storage operation time",
+            &stats.storage_operations,
+        );
+        write_summary(
+            &mut output,
+            "bllvm_network_operation_seconds",
+            "Network operation time",
+            &stats.network_operations,
+        );
+    }
+
+    // System metrics
+    write_metric(
+        &mut output,
+        "bllvm_system_uptime_seconds",
+        "Node uptime in seconds",
+        "gauge",
+        metrics.system.uptime_seconds,
+    );
+    if let Some(memory) = metrics.system.memory_usage_bytes {
+        write_metric(
+            &mut output,
+            "bllvm_system_memory_usage_bytes",
+            "Memory usage in bytes",
+            "gauge",
+            memory,
+        );
+    }
+    if let Some(cpu) = metrics.system.cpu_usage_percent {
+        write_metric(
+            &mut output,
+            "bllvm_system_cpu_usage_percent",
+            "CPU usage percentage",
+            "gauge",
+            cpu,
+        );
+    }
+
+    // DoS protection metrics
+    let dos = &metrics.network.dos_protection;
+    write_metric(
+        &mut output,
+        "bllvm_dos_connection_rate_violations_total",
+        "Connection rate violations",
+        "counter",
+        dos.connection_rate_violations,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_dos_auto_bans_total",
+        "Auto-bans triggered",
+        "counter",
+        dos.auto_bans,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_dos_message_queue_overflows_total",
+        "Message queue overflows",
+        "counter",
+        dos.message_queue_overflows,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_dos_active_connection_limit_hits_total",
+        "Active connection limit hits",
+        "counter",
+        dos.active_connection_limit_hits,
+    );
+    write_metric(
+        &mut output,
+        "bllvm_dos_resource_exhaustion_events_total",
+        "Resource exhaustion events",
+        "counter",
+        dos.resource_exhaustion_events,
+    );
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn test_scrape_metrics_endpoint() {
+        let metrics = Arc::new(MetricsCollector::new());
+        metrics.update_storage(|m| m.chain_height = 42);
+        metrics.update_network(|m| m.inbound_connections = 2);
+        metrics.update_mempool(|m| {
+            m.size = 3;
+            m.bytes = 750;
+        });
+        let profiler = Arc::new(PerformanceProfiler::new(1000));
+        profiler.record_block_processing(Duration::from_millis(20));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = MetricsServer::new(addr, metrics).with_profiler(profiler);
+        let handle = tokio::spawn(server.serve(listener));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), client.read_to_end(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        let response = String::from_utf8_lossy(&response);
+
+        assert!(response.contains("200 OK"), "Response: {}", response);
+        assert!(response.contains("bllvm_chain_height 42"));
+        assert!(response.contains("bllvm_mempool_transactions 3"));
+        assert!(response.contains("bllvm_network_peers_total"));
+        assert!(response.contains("bllvm_network_bytes_received_total"));
+        assert!(response.contains("bllvm_network_connections{type=\"inbound\"} 2"));
+        assert!(response.contains("bllvm_network_connections{type=\"feeler\"} 0"));
+        assert!(response.contains("bllvm_block_validation_seconds_count 1"));
+        assert!(response.contains("bllvm_dos_auto_bans_total"));
+
+        handle.abort();
+    }
+}
@@ -3,23 +3,332 @@
 //! Implements mining-related JSON-RPC methods for block template generation and mining.
 //! Uses formally verified consensus-proof mining functions.
 
-use crate::node::mempool::MempoolManager;
-use crate::rpc::errors::{RpcError, RpcResult};
+use crate::config::FeeForwardingConfig;
+use crate::network::NetworkManager;
+use crate::node::block_processor::{
+    parse_block_from_wire, parse_header_from_wire, serialize_block,
+};
+use crate::node::mempool::{is_witness_program, MempoolManager};
+use crate::node::notifications::NotificationPublisher;
+use crate::node::signet::{check_block_solution, is_trivial_challenge};
+use crate::node::versionbits::{self, DeploymentState};
+use crate::rpc::address::address_to_script;
+use crate::rpc::errors::{RpcError, RpcErrorCode, RpcResult};
+use crate::storage::hashing::double_sha256;
 use crate::storage::Storage;
 use crate::utils::current_timestamp;
-use bllvm_protocol::mining::BlockTemplate;
-use bllvm_protocol::serialization::deserialize_block_with_witnesses;
+use crate::validation::no_scripts::script_num_push;
+use crate::validation::witness::{
+    calculate_wtxid, check_witness_commitment, merkle_root, serialize_transaction_with_witness,
+    transaction_weight, transaction_witnesses, WITNESS_COMMITMENT_HEADER,
+};
+use bllvm_protocol::mining::{calculate_merkle_root, BlockTemplate};
+use bllvm_protocol::pow::check_proof_of_work;
+use bllvm_protocol::segwit::Witness;
 use bllvm_protocol::serialization::serialize_transaction;
 use bllvm_protocol::{
     types::{BlockHeader, ByteString, Natural, Transaction, UtxoSet},
-    ConsensusProof, ValidationResult,
+    Block, ConsensusProof, Hash, OutPoint, TransactionInput, TransactionOutput, ValidationResult,
 };
 use hex;
 use serde_json::{json, Value};
-use sha2::{Digest, Sha256};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::{debug, warn};
 
+pub use crate::validation::witness::find_witness_commitment;
+
+/// Number of blocks `networkhashps` is averaged over
+pub const NETWORK_HASHPS_LOOKUP_BLOCKS: u64 = 120;
+
+/// Expected hashes to find a block at difficulty 1
+const HASHES_PER_DIFFICULTY: f64 = 4294967296.0; // 2^32
+
+/// Estimate the network hash rate (hashes per second) at `height`
+///
+/// `lookup` returns the header at a height. Uses the last
+/// [`NETWORK_HASHPS_LOOKUP_BLOCKS`] blocks: the expected work to mine them
+/// divided by the time they took. Returns 0.0 with fewer than two blocks or
+/// no elapsed time.
+pub fn estimate_network_hashps<F>(lookup: F, height: u64) -> f64
+where
+    F: Fn(u64) -> Option<BlockHeader>,
+{
+    let start_height = height.saturating_sub(NETWORK_HASHPS_LOOKUP_BLOCKS);
+    let headers: Vec<BlockHeader> = (start_height..=height).filter_map(lookup).collect();
+    if headers.len() < 2 {
+        return 0.0;
+    }
+
+    let min_time = headers.iter().map(|h| h.timestamp).min().unwrap_or(0);
+    let max_time = headers.iter().map(|h| h.timestamp).max().unwrap_or(0);
+    if max_time <= min_time {
+        return 0.0;
+    }
+
+    // The first block only marks the start of the window
+    let work: f64 = headers[1..]
+        .iter()
+        .map(|h| MiningRpc::calculate_difficulty(h.bits) * HASHES_PER_DIFFICULTY)
+        .sum();
+    work / (max_time - min_time) as f64
+}
+
+/// Witness reserved value committed to alongside the witness root (BIP 141)
+pub const WITNESS_RESERVED_VALUE: [u8; 32] = [0u8; 32];
+
+/// Coinbase witness commitment for a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WitnessCommitment {
+    /// Merkle root of the block's wtxids, with the coinbase counted as zero
+    pub witness_root: Hash,
+    /// Double SHA256 of the witness root and the witness reserved value
+    pub commitment: Hash,
+    /// Zero-value coinbase output carrying the commitment
+    pub output: TransactionOutput,
+}
+
+/// Build the witness commitment for a block's non-coinbase transactions
+///
+/// The coinbase's wtxid is defined as zero, so it is not passed in.
+/// `witnesses[i]` holds the per-input stacks of `transactions[i]`; the wtxid
+/// of a transaction without them equals its txid. A coinbase-only block
+/// commits to a witness root of zero.
+pub fn build_witness_commitment(
+    transactions: &[Transaction],
+    witnesses: &[Vec<Witness>],
+) -> WitnessCommitment {
+    witness_commitment_from_wtxids(
+        transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| calculate_wtxid(tx, transaction_witnesses(witnesses, index))),
+    )
+}
+
+fn witness_commitment_from_wtxids(wtxids: impl Iterator<Item = Hash>) -> WitnessCommitment {
+    let witness_root = merkle_root(std::iter::once([0u8; 32]).chain(wtxids).collect());
+    let mut preimage = witness_root.to_vec();
+    preimage.extend_from_slice(&WITNESS_RESERVED_VALUE);
+    let commitment = double_sha256(&preimage);
+
+    let mut script_pubkey = WITNESS_COMMITMENT_HEADER.to_vec();
+    script_pubkey.extend_from_slice(&commitment);
+    WitnessCommitment {
+        witness_root,
+        commitment,
+        output: TransactionOutput {
+            value: 0,
+            script_pubkey,
+        },
+    }
+}
+
+/// Default nonce attempts per block for `generatetoaddress`
+pub const DEFAULT_GENERATE_MAX_TRIES: u64 = 1_000_000;
+
+/// Longest a getblocktemplate longpoll waits for new work (kept below the
+/// default RPC timeout so the client always gets a template back)
+pub const DEFAULT_LONGPOLL_TIMEOUT: Duration = Duration::from_secs(50);
+
+/// A changed mempool only ends a longpoll once it has waited this long, so
+/// miners aren't sent new work for every transaction
+const LONGPOLL_MEMPOOL_DELAY: Duration = Duration::from_secs(10);
+
+/// Maximum getblocktemplate longpolls waiting at once
+pub const MAX_LONGPOLL_WAITERS: usize = 64;
+
+/// Mainnet activation height of segwit (active from genesis on regtest)
+const SEGWIT_HEIGHT: Natural = 481824;
+
+/// Mainnet activation height of taproot (active from genesis on regtest)
+const TAPROOT_HEIGHT: Natural = 709632;
+
+/// Whether a rule activating at `mainnet_height` applies to the block at `height`
+fn rule_active(network: &str, mainnet_height: Natural, height: Natural) -> bool {
+    network == "regtest" || height >= mainnet_height
+}
+
+/// What a getblocktemplate client supports (BIP 9 rules negotiation)
+///
+/// Read from the `rules` and `capabilities` arrays of the template request.
+/// Rule names may carry the `!` prefix used in templates.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateRequest {
+    /// Soft fork rules the client understands (e.g. `segwit`)
+    pub rules: Vec<String>,
+    /// Optional features the client supports (e.g. `longpoll`, `proposal`)
+    pub capabilities: Vec<String>,
+}
+
+impl TemplateRequest {
+    /// Parse the template request from getblocktemplate params
+    pub fn from_params(params: &Value) -> RpcResult<Self> {
+        let Some(request) = params.get(0).filter(|request| !request.is_null()) else {
+            return Ok(Self::default());
+        };
+        let strings = |key: &str| -> RpcResult<Vec<String>> {
+            let invalid = || RpcError::invalid_params(format!("{key} must be an array of strings"));
+            match request.get(key) {
+                None | Some(Value::Null) => Ok(Vec::new()),
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string).ok_or_else(invalid))
+                    .collect(),
+                Some(_) => Err(invalid()),
+            }
+        };
+        Ok(Self {
+            rules: strings("rules")?,
+            capabilities: strings("capabilities")?,
+        })
+    }
+
+    /// Whether the client understands `rule`
+    pub fn supports_rule(&self, rule: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|supported| supported.trim_start_matches('!') == rule)
+    }
+}
+
+/// Transactions a client without segwit support can mine
+///
+/// Drops transactions spending witness program outputs, which cannot be
+/// valid without witness data, and then everything spending their outputs.
+/// Outputs of other transactions in `transactions` are looked up there,
+/// everything else in `utxo_set`.
+pub fn strip_witness_transactions(
+    transactions: Vec<Transaction>,
+    utxo_set: &UtxoSet,
+) -> Vec<Transaction> {
+    let txids: Vec<Hash> = transactions
+        .iter()
+        .map(bllvm_protocol::block::calculate_tx_id)
+        .collect();
+    let by_txid: HashMap<Hash, &Transaction> = txids.iter().copied().zip(&transactions).collect();
+    let spends_witness_program = |outpoint: &OutPoint| match utxo_set.get(outpoint) {
+        Some(coin) => is_witness_program(&coin.script_pubkey),
+        None => by_txid
+            .get(&outpoint.hash)
+            .and_then(|parent| parent.outputs.get(outpoint.index as usize))
+            .is_some_and(|output| is_witness_program(&output.script_pubkey)),
+    };
+
+    // Repeat until no more descendants drop out, as the mempool hands out
+    // transactions by fee rate rather than parents first
+    let mut dropped: HashSet<Hash> = HashSet::new();
+    loop {
+        let dropped_before = dropped.len();
+        for (txid, tx) in txids.iter().zip(&transactions) {
+            if !dropped.contains(txid)
+                && tx.inputs.iter().any(|input| {
+                    dropped.contains(&input.prevout.hash) || spends_witness_program(&input.prevout)
+                })
+            {
+                dropped.insert(*txid);
+            }
+        }
+        if dropped.len() == dropped_before {
+            break;
+        }
+    }
+    transactions
+        .into_iter()
+        .zip(txids)
+        .filter(|(_, txid)| !dropped.contains(txid))
+        .map(|(tx, _)| tx)
+        .collect()
+}
+
+/// Share of `total` forwarded at `percentage`, rounded down
+pub fn forwarded_value(total: u64, percentage: u8) -> u64 {
+    (total as u128 * percentage.min(100) as u128 / 100) as u64
+}
+
+/// Split a coinbase value between the miner and an optional commons output
+///
+/// `forwarding` is the commons scriptPubKey and the percentage of `total`
+/// it receives. The commons share is rounded down and the miner keeps the
+/// remainder, so the outputs always sum to exactly `total`. A zero-value
+/// commons output is omitted, as is the miner output when forwarding takes
+/// the whole value.
+pub fn split_coinbase_value(
+    total: u64,
+    miner_script: &[u8],
+    forwarding: Option<(&[u8], u8)>,
+) -> Vec<TransactionOutput> {
+    let commons_value = match forwarding {
+        Some((_, percentage)) => forwarded_value(total, percentage),
+        None => 0,
+    };
+    let mut outputs = Vec::with_capacity(2);
+    if commons_value < total || commons_value == 0 {
+        outputs.push(TransactionOutput {
+            value: (total - commons_value) as i64,
+            script_pubkey: miner_script.to_vec(),
+        });
+    }
+    if let Some((commons_script, _)) = forwarding {
+        if commons_value > 0 {
+            outputs.push(TransactionOutput {
+                value: commons_value as i64,
+                script_pubkey: commons_script.to_vec(),
+            });
+        }
+    }
+    outputs
+}
+
+/// Outcome of offering a block to the active chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BlockSubmission {
+    /// The block extended the tip and was connected
+    Connected(Hash),
+    /// The block extends the tip and is valid, but was not connected
+    /// (block proposals)
+    Valid,
+    /// The block is already stored
+    Duplicate,
+    /// The block is valid but does not extend the current tip
+    Inconclusive,
+    /// The block failed validation, with the reason
+    Invalid(String),
+}
+
+/// A waiting getblocktemplate longpoll, counted while alive
+///
+/// The count drops when the request finishes or its future is dropped
+/// because the client disconnected.
+struct LongpollWaiter<'a>(&'a AtomicUsize);
+
+impl<'a> LongpollWaiter<'a> {
+    fn register(waiters: &'a AtomicUsize) -> RpcResult<Self> {
+        if waiters.fetch_add(1, Ordering::SeqCst) >= MAX_LONGPOLL_WAITERS {
+            waiters.fetch_sub(1, Ordering::SeqCst);
+            return Err(RpcError::node_overloaded(
+                "Too many getblocktemplate longpoll requests",
+            ));
+        }
+        Ok(Self(waiters))
+    }
+}
+
+impl Drop for LongpollWaiter<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Size of the last generated block template
+#[derive(Debug, Clone, Copy)]
+struct TemplateStats {
+    weight: u64,
+    tx_count: usize,
+}
+
 /// Mining RPC methods with dependencies
 pub struct MiningRpc {
     /// Consensus proof instance for mining operations
@@ -28,6 +337,23 @@ pub struct MiningRpc {
     storage: Option<Arc<Storage>>,
     /// Mempool accessor for transaction retrieval
     mempool: Option<Arc<MempoolManager>>,
+    /// Network manager for announcing accepted blocks
+    network: Option<Arc<NetworkManager>>,
+    /// Last block template handed out by getblocktemplate
+    template_stats: Mutex<Option<TemplateStats>>,
+    /// Number of getblocktemplate longpolls waiting for new work
+    longpoll_waiters: AtomicUsize,
+    /// Longest a longpoll waits before returning the current template
+    longpoll_timeout: Duration,
+    /// Share of the block reward forwarded to the commons address
+    fee_forwarding: Option<FeeForwardingConfig>,
+    /// Reports forwarded amounts of connected blocks to bllvm-commons
+    #[cfg(feature = "governance")]
+    governance_webhook: Option<Arc<crate::governance::GovernanceWebhookClient>>,
+    /// Publishes `hashblock`/`rawblock` notifications for submitted blocks
+    notifications: Option<Arc<NotificationPublisher>>,
+    /// Signet challenge submitted blocks must satisfy (signet only)
+    signet_challenge: Option<Vec<u8>>,
 }
 
 impl MiningRpc {
@@ -37,6 +363,15 @@ impl MiningRpc {
             consensus: ConsensusProof::new(),
             storage: None,
             mempool: None,
+            network: None,
+            template_stats: Mutex::new(None),
+            longpoll_waiters: AtomicUsize::new(0),
+            longpoll_timeout: DEFAULT_LONGPOLL_TIMEOUT,
+            fee_forwarding: None,
+            #[cfg(feature = "governance")]
+            governance_webhook: None,
+            notifications: None,
+            signet_challenge: None,
         }
     }
 
@@ -46,144 +381,264 @@ impl MiningRpc {
             consensus: ConsensusProof::new(),
             storage: Some(storage),
             mempool: Some(mempool),
+            network: None,
+            template_stats: Mutex::new(None),
+            longpoll_waiters: AtomicUsize::new(0),
+            longpoll_timeout: DEFAULT_LONGPOLL_TIMEOUT,
+            fee_forwarding: None,
+            #[cfg(feature = "governance")]
+            governance_webhook: None,
+            notifications: None,
+            signet_challenge: None,
+        }
+    }
+
+    /// Set the network manager used to announce accepted blocks
+    pub fn with_network(mut self, network: Arc<NetworkManager>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Set how long a getblocktemplate longpoll waits for new work
+    pub fn with_longpoll_timeout(mut self, timeout: Duration) -> Self {
+        self.longpoll_timeout = timeout;
+        self
+    }
+
+    /// Set the publisher notified of blocks connected by submitblock
+    pub fn with_notifications(mut self, notifications: Arc<NotificationPublisher>) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    /// Require submitted blocks to carry a solution to this signet challenge
+    pub fn with_signet_challenge(mut self, challenge: Option<Vec<u8>>) -> Self {
+        self.signet_challenge = challenge;
+        self
+    }
+
+    /// Set the fee forwarding applied to assembled coinbases
+    pub fn with_fee_forwarding(mut self, config: FeeForwardingConfig) -> Self {
+        self.fee_forwarding = Some(config);
+        self
+    }
+
+    /// Set the governance webhook notified when a block forwards fees
+    #[cfg(feature = "governance")]
+    pub fn with_governance_webhook(
+        mut self,
+        webhook: Arc<crate::governance::GovernanceWebhookClient>,
+    ) -> Self {
+        self.governance_webhook = Some(webhook);
+        self
+    }
+
+    /// Resolve the commons scriptPubKey and percentage for `network`
+    ///
+    /// Returns `None` when fee forwarding is disabled. Fails if forwarding is
+    /// enabled without a commons address valid for the network.
+    fn commons_forwarding(&self, network: &str) -> RpcResult<Option<(String, Vec<u8>, u8)>> {
+        let config = match self.fee_forwarding {
+            Some(ref config) if config.enabled => config,
+            _ => return Ok(None),
+        };
+        if config.forwarding_percentage > 100 {
+            return Err(RpcError::internal_error(format!(
+                "Invalid fee forwarding percentage: {}",
+                config.forwarding_percentage
+            )));
         }
+        let address = config.commons_address.as_deref().ok_or_else(|| {
+            RpcError::invalid_address_or_key("Fee forwarding enabled without a commons address")
+        })?;
+        let script = address_to_script(address, network).ok_or_else(|| {
+            RpcError::invalid_address_or_key(format!(
+                "Invalid commons address for {network}: {address}"
+            ))
+        })?;
+        Ok(Some((
+            address.to_string(),
+            script,
+            config.forwarding_percentage,
+        )))
+    }
+
+    /// Network name recorded in the chain state
+    fn chain_network(storage: &Storage) -> RpcResult<String> {
+        Ok(storage
+            .chain()
+            .load_chain_info()
+            .map_err(|e| RpcError::internal_error(format!("Failed to load chain info: {e}")))?
+            .ok_or_else(|| RpcError::internal_error("Chain not initialized"))?
+            .chain_params
+            .network)
     }
 
     /// Get mining information
+    ///
+    /// `currentblockweight` and `currentblocktx` describe the last template
+    /// returned by `getblocktemplate`, and are null until one is generated.
     pub async fn get_mining_info(&self) -> RpcResult<Value> {
         #[cfg(debug_assertions)]
         debug!("RPC: getmininginfo");
 
         use std::time::{Duration, Instant};
 
-        // This avoids multiple storage lookups for height, tip_header, chain_info
+        // Chain-derived fields only change with the tip: cache them so
+        // repeated calls don't re-read headers for the hashrate estimate
         thread_local! {
-            static CACHED_MINING_INFO: std::cell::RefCell<(Option<Value>, Instant, Option<u64>)> = {
+            static CACHED_CHAIN_INFO: std::cell::RefCell<(Option<Value>, Instant, Option<u64>)> = {
                 std::cell::RefCell::new((None, Instant::now(), None))
             };
         }
 
-        // Check if we should refresh (cache miss, expired, or chain advanced)
-        let current_height = if let Some(ref storage) = self.storage {
-            storage.chain().get_height().ok().flatten().unwrap_or(0)
+        // Get current block height from storage
+        let blocks = if let Some(ref storage) = self.storage {
+            storage
+                .chain()
+                .get_height()
+                .map_err(|e| RpcError::internal_error(format!("Failed to get height: {e}")))?
+                .unwrap_or(0)
         } else {
             0
         };
 
-        let should_refresh = CACHED_MINING_INFO.with(|cache| {
+        let cached = CACHED_CHAIN_INFO.with(|cache| {
             let cache = cache.borrow();
-            cache.0.is_none()
-                || cache.1.elapsed() >= Duration::from_secs(1)
-                || cache.2 != Some(current_height)
+            match cache.0 {
+                Some(ref value)
+                    if cache.1.elapsed() < Duration::from_secs(1) && cache.2 == Some(blocks) =>
+                {
+                    Some(value.clone())
+                }
+                _ => None,
+            }
         });
+        let chain_info = match cached {
+            Some(value) => value,
+            None => {
+                let value = self.chain_mining_info(blocks);
+                CACHED_CHAIN_INFO.with(|cache| {
+                    *cache.borrow_mut() = (Some(value.clone()), Instant::now(), Some(blocks));
+                });
+                value
+            }
+        };
 
-        if should_refresh {
-            // Get current block height from storage
-            let blocks = if let Some(ref storage) = self.storage {
-                storage
-                    .chain()
-                    .get_height()
-                    .map_err(|e| RpcError::internal_error(format!("Failed to get height: {e}")))?
-                    .unwrap_or(0)
-            } else {
-                0
-            };
+        // Get mempool size
+        let pooledtx = if let Some(ref mempool) = self.mempool {
+            mempool.size()
+        } else {
+            0
+        };
 
-            // Get mempool size
-            let pooledtx = if let Some(ref mempool) = self.mempool {
-                mempool.size()
-            } else {
-                0
-            };
+        // Last generated block template, if any
+        let template_stats = *self
+            .template_stats
+            .lock()
+            .map_err(|_| RpcError::internal_error("Template stats lock poisoned"))?;
 
-            // Get difficulty from latest block's bits field (graceful degradation)
-            let difficulty = if let Some(ref storage) = self.storage {
-                if let Ok(Some(tip_header)) = storage.chain().get_tip_header() {
-                    Self::calculate_difficulty(tip_header.bits)
-                } else {
-                    tracing::debug!("No chain tip available, using default difficulty");
-                    1.0 // Graceful fallback if no tip
-                }
+        Ok(json!({
+            "blocks": blocks,
+            "currentblockweight": template_stats.map(|t| t.weight),
+            "currentblocktx": template_stats.map(|t| t.tx_count),
+            "difficulty": chain_info["difficulty"],
+            "networkhashps": chain_info["networkhashps"],
+            "pooledtx": pooledtx,
+            "chain": chain_info["chain"],
+            "warnings": ""
+        }))
+    }
+
+    /// Difficulty, hashrate estimate and chain name at the given tip height
+    fn chain_mining_info(&self, tip_height: u64) -> Value {
+        // Get difficulty from latest block's bits field (graceful degradation)
+        let difficulty = if let Some(ref storage) = self.storage {
+            if let Ok(Some(tip_header)) = storage.chain().get_tip_header() {
+                Self::calculate_difficulty(tip_header.bits)
             } else {
-                tracing::debug!("Storage not available, using default difficulty");
-                1.0 // Graceful fallback if no storage
-            };
+                tracing::debug!("No chain tip available, using default difficulty");
+                1.0 // Graceful fallback if no tip
+            }
+        } else {
+            tracing::debug!("Storage not available, using default difficulty");
+            1.0 // Graceful fallback if no storage
+        };
 
-            let networkhashps = if let Some(ref storage) = self.storage {
-                // Try cached hashrate first (O(1) lookup)
-                if let Ok(Some(cached_hashrate)) = storage.chain().get_network_hashrate() {
-                    cached_hashrate
-                } else {
-                    // Fallback: Calculate network hashrate (expensive - loads up to 144 blocks)
-                    self.calculate_network_hashrate(storage)
-                        .unwrap_or_else(|e| {
-                            tracing::debug!("Failed to calculate network hashrate: {e}, using 0.0");
-                            0.0
-                        })
-                }
+        let networkhashps = if let Some(ref storage) = self.storage {
+            // Try cached hashrate first (O(1) lookup)
+            if let Ok(Some(cached_hashrate)) = storage.chain().get_network_hashrate() {
+                cached_hashrate
             } else {
-                tracing::debug!("Storage not available, network hashrate unavailable");
-                0.0
-            };
+                estimate_network_hashps(
+                    |height| {
+                        let hash = storage.blocks().get_hash_by_height(height).ok()??;
+                        storage.blocks().get_header(&hash).ok()?
+                    },
+                    tip_height,
+                )
+            }
+        } else {
+            tracing::debug!("Storage not available, network hashrate unavailable");
+            0.0
+        };
 
-            // Get current block template info (if available)
-            let currentblocksize = 0;
-            let currentblockweight = 0;
-            let currentblocktx = 0;
-
-            // Determine chain name from storage chain params
-            let chain = if let Some(ref storage) = self.storage {
-                if let Ok(Some(info)) = storage.chain().load_chain_info() {
-                    match info.chain_params.network.as_str() {
-                        "mainnet" => "main",
-                        "testnet" => "test",
-                        "regtest" => "regtest",
-                        _ => "main",
-                    }
-                } else {
-                    "main" // Default
+        // Determine chain name from storage chain params
+        let chain = if let Some(ref storage) = self.storage {
+            if let Ok(Some(info)) = storage.chain().load_chain_info() {
+                match info.chain_params.network.as_str() {
+                    "mainnet" => "main",
+                    "testnet" => "test",
+                    "regtest" => "regtest",
+                    _ => "main",
                 }
             } else {
                 "main" // Default
-            };
-
-            let value = json!({
-                "blocks": blocks,
-                "currentblocksize": currentblocksize,
-                "currentblockweight": currentblockweight,
-                "currentblocktx": currentblocktx,
-                "difficulty": difficulty,
-                "networkhashps": networkhashps,
-                "pooledtx": pooledtx,
-                "chain": chain,
-                "warnings": ""
-            });
-
-            // Cache the result
-            CACHED_MINING_INFO.with(|cache| {
-                let mut cache = cache.borrow_mut();
-                *cache = (Some(value.clone()), Instant::now(), Some(current_height));
-            });
-
-            Ok(value)
+            }
         } else {
-            // Return cached value
-            CACHED_MINING_INFO.with(|cache| {
-                let cache = cache.borrow();
-                Ok(cache.0.as_ref().unwrap().clone())
-            })
-        }
+            "main" // Default
+        };
+
+        json!({
+            "difficulty": difficulty,
+            "networkhashps": networkhashps,
+            "chain": chain,
+        })
     }
 
     /// Get block template
     ///
     /// Params: [template_request (optional)]
     ///
+    /// If the request carries the `longpollid` of an earlier template, the call
+    /// blocks until a new block is connected, the mempool has changed, or the
+    /// longpoll timeout passes (BIP 22).
+    ///
+    /// Witness data is only handed to clients listing `segwit` in the
+    /// request's `rules` (BIP 145). Once segwit is active, other clients get a
+    /// stripped template: no transactions needing witnesses, no witness
+    /// commitment, and pre-segwit size and sigop limits.
+    ///
     /// Uses formally verified consensus-proof::mining::create_block_template() function
     /// which has Kani proofs ensuring correctness per Orange Paper Section 12.4
     pub async fn get_block_template(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: getblocktemplate");
 
+        match params.get(0).and_then(|request| request.get("mode")) {
+            None | Some(Value::Null) => {}
+            Some(mode) if mode == "template" => {}
+            Some(mode) if mode == "proposal" => return self.check_block_proposal(params).await,
+            Some(_) => return Err(RpcError::invalid_params("Invalid mode")),
+        }
+
+        let request = TemplateRequest::from_params(params)?;
+        if let Some(longpoll_id) = params.get(0).and_then(|request| request.get("longpollid")) {
+            let longpoll_id = longpoll_id
+                .as_str()
+                .ok_or_else(|| RpcError::invalid_params("longpollid must be a string"))?;
+            self.wait_for_new_work(longpoll_id).await?;
+        }
+
         // 1. Get current chainstate
         let height: Natural = self
             .get_current_height()?
@@ -194,11 +649,22 @@ impl MiningRpc {
         let prev_headers = self.get_headers_for_difficulty()?;
 
         // 2. Get mempool transactions
-        let mempool_txs: Vec<Transaction> = self.get_mempool_transactions()?;
+        let mut mempool_txs: Vec<Transaction> = self.get_mempool_transactions()?;
 
         // 3. Get UTXO set
         let utxo_set = self.get_utxo_set()?;
 
+        // Leave out transactions needing witnesses unless the client takes them
+        let network = match self.storage {
+            Some(ref storage) => Self::chain_network(storage).unwrap_or_else(|_| "mainnet".into()),
+            None => "mainnet".to_string(),
+        };
+        let segwit_active = rule_active(&network, SEGWIT_HEIGHT, height + 1);
+        let stripped = segwit_active && !request.supports_rule("segwit");
+        if stripped {
+            mempool_txs = strip_witness_transactions(mempool_txs, &utxo_set);
+        }
+
         // 4. Extract coinbase parameters from request or use defaults
         let coinbase_script = self.extract_coinbase_script(params).unwrap_or_default();
         let coinbase_address = self.extract_coinbase_address(params).unwrap_or_default();
@@ -224,16 +690,151 @@ impl MiningRpc {
             }
         };
 
-        // 6. Convert to JSON-RPC format (BIP 22/23)
-        self.template_to_json_rpc(&template, &prev_header, height)
+        // 6. Remember the template weight for getmininginfo (header, 4 weight
+        // units per byte, plus transactions with the witnesses they carry)
+        let witnesses = self.get_mempool_witnesses(&template.transactions);
+        let template_weight: u64 = 80 * 4
+            + template
+                .transactions
+                .iter()
+                .enumerate()
+                .map(|(index, tx)| transaction_weight(tx, transaction_witnesses(&witnesses, index)))
+                .sum::<u64>();
+        if let Ok(mut stats) = self.template_stats.lock() {
+            *stats = Some(TemplateStats {
+                weight: template_weight,
+                tx_count: template.transactions.len(),
+            });
+        }
+
+        // 7. Convert to JSON-RPC format (BIP 22/23)
+        let mut result = self.template_to_json_rpc(
+            &template,
+            &witnesses,
+            &prev_header,
+            height,
+            &network,
+            &request,
+        )?;
+        result["longpollid"] = json!(self.longpoll_id()?);
+
+        // 8. Describe the commons output the coinbase must carry
+        if let Some(ref storage) = self.storage {
+            let network = Self::chain_network(storage)?;
+            if let Some((address, script, percentage)) = self.commons_forwarding(&network)? {
+                let coinbase_value = self.calculate_coinbase_value(&template, height);
+                let value = forwarded_value(coinbase_value, percentage);
+                result["feeforwarding"] = json!({
+                    "address": address,
+                    "scriptPubKey": hex::encode(&script),
+                    "percentage": percentage,
+                    "value": value,
+                });
+            }
+        }
+        Ok(result)
+    }
+
+    /// Validate a candidate block without connecting it (BIP 23 proposals)
+    ///
+    /// Params: [{"mode": "proposal", "data": "hexdata"}]
+    ///
+    /// Returns null if the block would be accepted on the current tip, or
+    /// the rejection reason.
+    async fn check_block_proposal(&self, params: &Value) -> RpcResult<Value> {
+        let data = params
+            .get(0)
+            .and_then(|request| request.get("data"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::invalid_params("Missing data String key for proposal"))?;
+        let (block, witnesses) = Self::decode_block(data)?;
+
+        match self.process_block(block, witnesses, false).await? {
+            BlockSubmission::Connected(_) | BlockSubmission::Valid => Ok(Value::Null),
+            BlockSubmission::Duplicate => Ok(json!("duplicate")),
+            BlockSubmission::Inconclusive => Ok(json!("inconclusive-not-best-prevblk")),
+            BlockSubmission::Invalid(reason) => Ok(json!(reason)),
+        }
+    }
+
+    /// Identifier of the work a template was built from
+    ///
+    /// The tip hash in hex followed by the mempool transaction count, in the
+    /// style of Bitcoin Core.
+    fn longpoll_id(&self) -> RpcResult<String> {
+        let tip_hash = match self.storage {
+            Some(ref storage) => storage
+                .chain()
+                .get_tip_hash()
+                .map_err(|e| RpcError::internal_error(format!("Failed to get tip hash: {e}")))?
+                .unwrap_or([0u8; 32]),
+            None => [0u8; 32],
+        };
+        Ok(format!("{}{}", hex::encode(tip_hash), self.mempool_size()))
+    }
+
+    fn mempool_size(&self) -> usize {
+        self.mempool.as_ref().map_or(0, |mempool| mempool.size())
+    }
+
+    /// Number of getblocktemplate longpolls waiting for new work
+    pub fn longpoll_waiters(&self) -> usize {
+        self.longpoll_waiters.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the work behind `longpoll_id` is stale
+    ///
+    /// Returns when the chain tip moves, when the mempool has changed and
+    /// `LONGPOLL_MEMPOOL_DELAY` has passed, or after the longpoll timeout.
+    async fn wait_for_new_work(&self, longpoll_id: &str) -> RpcResult<()> {
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Storage not available"))?;
+        let (tip_hex, mempool_size) = match (longpoll_id.get(..64), longpoll_id.get(64..)) {
+            (Some(tip_hex), Some(size)) => (tip_hex, size.parse::<usize>().ok()),
+            _ => return Err(RpcError::invalid_params("Invalid longpollid")),
+        };
+
+        let _waiter = LongpollWaiter::register(&self.longpoll_waiters)?;
+        let mut tip = storage.chain().subscribe_tip();
+        let started = tokio::time::Instant::now();
+        let deadline = started + self.longpoll_timeout;
+        let mempool_check = started + LONGPOLL_MEMPOOL_DELAY;
+        loop {
+            if hex::encode(*tip.borrow_and_update()) != tip_hex {
+                return Ok(());
+            }
+            let now = tokio::time::Instant::now();
+            if now >= mempool_check && mempool_size != Some(self.mempool_size()) {
+                return Ok(());
+            }
+            if now >= deadline {
+                return Ok(());
+            }
+            let wake = if now < mempool_check {
+                mempool_check.min(deadline)
+            } else {
+                (now + Duration::from_secs(1)).min(deadline)
+            };
+            if let Ok(Err(_)) = tokio::time::timeout_at(wake, tip.changed()).await {
+                // Storage is shutting down
+                return Ok(());
+            }
+        }
     }
 
     /// Convert BlockTemplate to JSON-RPC format
+    ///
+    /// `witnesses[i]` holds the per-input stacks of `template.transactions[i]`.
     fn template_to_json_rpc(
         &self,
         template: &bllvm_protocol::mining::BlockTemplate,
+        witnesses: &[Vec<Witness>],
         prev_header: &BlockHeader,
         height: Natural,
+        network: &str,
+        request: &TemplateRequest,
     ) -> RpcResult<Value> {
         // Convert previous block hash to hex (big-endian)
         let prev_hash_hex = hex::encode(prev_header.prev_block_hash);
@@ -248,23 +849,29 @@ impl MiningRpc {
         let transactions_json: Vec<Value> = template
             .transactions
             .iter()
-            .map(|tx| self.transaction_to_json(tx))
+            .enumerate()
+            .map(|(index, tx)| {
+                self.transaction_to_json(tx, transaction_witnesses(witnesses, index))
+            })
             .collect();
 
         // Calculate coinbase value (subsidy + fees)
         let coinbase_value = self.calculate_coinbase_value(template, height);
 
-        // Get active rules (BIP 9 feature flags)
-        let rules = self.get_active_rules(height);
+        // Negotiate rules and version bits (BIP 9)
+        let (rules, vbavailable, version) =
+            self.template_rules(network, height + 1, template.header.version as i32, request)?;
+        let segwit_active = rule_active(network, SEGWIT_HEIGHT, height + 1);
+        let segwit = segwit_active && request.supports_rule("segwit");
 
         // Get minimum time (median time + 1)
         let min_time = self.get_min_time(height);
 
-        Ok(json!({
+        let mut result = json!({
             "capabilities": ["proposal"],
-            "version": template.header.version as i32,
+            "version": version,
             "rules": rules,
-            "vbavailable": {},
+            "vbavailable": vbavailable,
             "vbrequired": 0,
             "previousblockhash": prev_hash_hex,
             "transactions": transactions_json,
@@ -272,7 +879,6 @@ impl MiningRpc {
                 "flags": ""
             },
             "coinbasevalue": coinbase_value,
-            "longpollid": prev_hash_hex,
             "target": target_hex,
             "mintime": min_time,
             "mutable": ["time", "transactions", "prevblock"],
@@ -282,8 +888,21 @@ impl MiningRpc {
             "weightlimit": 4000000,
             "curtime": template.timestamp,
             "bits": bits_hex,
-            "height": template.height
-        }))
+            "height": template.height,
+        });
+        if segwit {
+            let witness_commitment = build_witness_commitment(&template.transactions, witnesses);
+            result["default_witness_commitment"] =
+                json!(hex::encode(&witness_commitment.output.script_pubkey));
+        } else if segwit_active {
+            // Stripped template: limits as a pre-segwit client counts them
+            result["sigoplimit"] = json!(20000);
+            result["sizelimit"] = json!(1000000);
+            if let Some(fields) = result.as_object_mut() {
+                fields.remove("weightlimit");
+            }
+        }
+        Ok(result)
     }
 
     // Helper methods - access chainstate and mempool
@@ -357,6 +976,17 @@ impl MiningRpc {
         }
     }
 
+    /// Witness stacks the mempool holds for `transactions`, one entry per transaction
+    fn get_mempool_witnesses(&self, transactions: &[Transaction]) -> Vec<Vec<Witness>> {
+        let Some(ref mempool) = self.mempool else {
+            return Vec::new();
+        };
+        transactions
+            .iter()
+            .map(|tx| mempool.get_witness(&bllvm_protocol::block::calculate_tx_id(tx)))
+            .collect()
+    }
+
     fn get_mempool_transactions(&self) -> RpcResult<Vec<Transaction>> {
         if let Some(ref mempool) = self.mempool {
             // Get UTXO set for fee calculation
@@ -371,89 +1001,8 @@ impl MiningRpc {
     }
 
     /// Calculate difficulty from bits (compact target format)
-    /// Uses the same logic as BlockchainRpc::calculate_difficulty
     fn calculate_difficulty(bits: u64) -> f64 {
-        // Difficulty = MAX_TARGET / target
-        // MAX_TARGET for Bitcoin mainnet is 0x00000000FFFF0000000000000000000000000000000000000000000000000000
-        // For display purposes, we normalize to genesis difficulty = 1.0
-        // MAX_TARGET is 256 bits, use U256 from bllvm-consensus
-        // 0x00000000FFFF0000000000000000000000000000000000000000000000000000
-        // For now, use a placeholder - this should be calculated from difficulty bits
-        const MAX_TARGET: u64 = 0x00000000FFFF0000u64;
-
-        // Simplified difficulty calculation using bits directly
-        // For display purposes, use a simple approximation based on bits
-        let mantissa = (bits & 0x00ffffff) as f64;
-        if mantissa == 0.0 {
-            return 1.0;
-        }
-        let max_mantissa = 0x00ffff00 as f64;
-        (max_mantissa / mantissa).max(1.0)
-    }
-
-    /// Calculate network hashrate from recent block timestamps
-    /// Estimates hashrate based on the time between recent blocks
-    fn calculate_network_hashrate(&self, storage: &Storage) -> Result<f64, anyhow::Error> {
-        // Get tip height
-        let tip_height = storage
-            .chain()
-            .get_height()?
-            .ok_or_else(|| anyhow::anyhow!("Chain not initialized"))?;
-
-        // Need at least 2 blocks to calculate hashrate
-        if tip_height < 1 {
-            return Ok(0.0);
-        }
-
-        // Get last 144 blocks (approximately 1 day at 10 min/block)
-        // Or use fewer blocks if chain is shorter
-        let num_blocks = (tip_height + 1).min(144);
-        let start_height = tip_height.saturating_sub(num_blocks - 1);
-
-        // Get timestamps from blocks
-        let mut timestamps = Vec::new();
-        for height in start_height..=tip_height {
-            if let Ok(Some(hash)) = storage.blocks().get_hash_by_height(height) {
-                if let Ok(Some(block)) = storage.blocks().get_block(&hash) {
-                    timestamps.push((height, block.header.timestamp));
-                }
-            }
-        }
-
-        if timestamps.len() < 2 {
-            return Ok(0.0);
-        }
-
-        // Calculate average time between blocks
-        let first_timestamp = timestamps[0].1;
-        let last_timestamp = timestamps[timestamps.len() - 1].1;
-        let time_span = last_timestamp.saturating_sub(first_timestamp);
-        let num_intervals = timestamps.len() - 1;
-
-        if time_span == 0 || num_intervals == 0 {
-            return Ok(0.0);
-        }
-
-        let avg_time_per_block = time_span as f64 / num_intervals as f64;
-
-        // Get difficulty from tip block
-        let tip_hash = storage
-            .blocks()
-            .get_hash_by_height(tip_height)?
-            .ok_or_else(|| anyhow::anyhow!("Tip block not found"))?;
-        let tip_block = storage
-            .blocks()
-            .get_block(&tip_hash)?
-            .ok_or_else(|| anyhow::anyhow!("Tip block not found"))?;
-        let difficulty = Self::calculate_difficulty(tip_block.header.bits);
-
-        // Calculate hashrate: difficulty * 2^32 / avg_time_per_block
-        // This estimates the network hashrate in hashes per second
-        // 2^32 is the number of hashes needed on average to find a block at difficulty 1.0
-        const HASHES_PER_DIFFICULTY: f64 = 4294967296.0; // 2^32
-        let hashrate = (difficulty * HASHES_PER_DIFFICULTY) / avg_time_per_block;
-
-        Ok(hashrate)
+        crate::rpc::blockchain::BlockchainRpc::calculate_difficulty(bits)
     }
 
     fn get_utxo_set(&self) -> RpcResult<UtxoSet> {
@@ -497,17 +1046,17 @@ impl MiningRpc {
         Some(vec![])
     }
 
-    fn transaction_to_json(&self, tx: &Transaction) -> Value {
-        // Convert transaction to JSON-RPC format
-        let tx_bytes = serialize_transaction(tx);
-        let tx_hash = self.calculate_tx_hash(&tx_bytes);
+    fn transaction_to_json(&self, tx: &Transaction, witnesses: &[Witness]) -> Value {
+        // Convert transaction to JSON-RPC format; `data` carries the witness
+        let tx_hash = self.calculate_tx_hash(&serialize_transaction(tx));
         let fee = self.calculate_transaction_fee(tx);
         let sigops = self.count_sigops(tx);
-        let weight = self.calculate_weight(tx);
+        let weight = transaction_weight(tx, witnesses);
 
         json!({
-            "data": hex::encode(&tx_bytes),
+            "data": hex::encode(serialize_transaction_with_witness(tx, witnesses)),
             "txid": hex::encode(tx_hash),
+            "hash": hex::encode(calculate_wtxid(tx, witnesses)),
             "fee": fee,
             "sigops": sigops,
             "weight": weight,
@@ -516,12 +1065,7 @@ impl MiningRpc {
 
     fn calculate_tx_hash(&self, tx_bytes: &[u8]) -> [u8; 32] {
         // Transaction hash is double SHA256 of transaction bytes
-        let hash1 = Sha256::digest(tx_bytes);
-        let hash2 = Sha256::digest(hash1);
-
-        let mut result = [0u8; 32];
-        result.copy_from_slice(&hash2);
-        result
+        double_sha256(tx_bytes)
     }
 
     fn calculate_transaction_fee(&self, tx: &Transaction) -> u64 {
@@ -599,21 +1143,55 @@ impl MiningRpc {
         subsidy + fees
     }
 
-    fn get_active_rules(&self, height: Natural) -> Vec<String> {
-        // Determine active BIP 9 rules based on height
+    /// Rules, available deployments and block version of a template
+    ///
+    /// Rules are the active soft forks; `segwit` is prefixed with `!` since a
+    /// client that does not understand it cannot use the template as is.
+    /// Deployments still signalling are listed in `vbavailable`, and their
+    /// version bit is only set if the client supports them.
+    fn template_rules(
+        &self,
+        network: &str,
+        height: Natural,
+        version: i32,
+        request: &TemplateRequest,
+    ) -> RpcResult<(Vec<String>, serde_json::Map<String, Value>, i32)> {
         let mut rules = vec!["csv".to_string()]; // CSV always active after height
-
-        if height >= 481824 {
-            // SegWit activation (mainnet)
-            rules.push("segwit".to_string());
+        if rule_active(network, SEGWIT_HEIGHT, height) {
+            rules.push("!segwit".to_string());
         }
-
-        if height >= 709632 {
-            // Taproot activation (mainnet)
+        if rule_active(network, TAPROOT_HEIGHT, height) {
             rules.push("taproot".to_string());
         }
 
-        rules
+        let mut vbavailable = serde_json::Map::new();
+        let mut version = version;
+        for deployment in versionbits::mainnet_deployments() {
+            let status = versionbits::deployment_status(
+                &deployment,
+                height.saturating_sub(1),
+                |start, end| match self.storage {
+                    Some(ref storage) => storage.blocks().get_headers_by_height_range(start, end),
+                    None => Ok(Vec::new()),
+                },
+            )
+            .map_err(|e| {
+                RpcError::internal_error(format!("Failed to get deployment status: {e}"))
+            })?;
+            match status.state {
+                DeploymentState::Active => rules.push(deployment.name.to_string()),
+                DeploymentState::Started | DeploymentState::LockedIn => {
+                    vbavailable.insert(deployment.name.to_string(), json!(deployment.bit));
+                    if request.supports_rule(deployment.name) {
+                        version |= 1 << deployment.bit;
+                    } else {
+                        version &= !(1 << deployment.bit);
+                    }
+                }
+                DeploymentState::Defined | DeploymentState::Failed => {}
+            }
+        }
+        Ok((rules, vbavailable, version))
     }
 
     fn get_min_time(&self, _height: Natural) -> Natural {
@@ -637,39 +1215,460 @@ impl MiningRpc {
             Some(8_000_000), // ~4MB block max
         )?;
 
-        // Decode hex
-        let block_bytes = hex::decode(&hex_data)
+        let (block, witnesses) = Self::decode_block(&hex_data)?;
+
+        match self.process_block(block, witnesses, true).await? {
+            BlockSubmission::Connected(_) | BlockSubmission::Valid => Ok(Value::Null),
+            BlockSubmission::Duplicate => Ok(json!("duplicate")),
+            BlockSubmission::Inconclusive => Ok(json!("inconclusive")),
+            BlockSubmission::Invalid(reason) => {
+                Err(RpcError::invalid_params(format!("Invalid block: {reason}")))
+            }
+        }
+    }
+
+    /// Decode a hex-encoded block with its witnesses
+    fn decode_block(hex_data: &str) -> RpcResult<(Block, Vec<Vec<Witness>>)> {
+        let block_bytes = hex::decode(hex_data)
             .map_err(|e| RpcError::invalid_params(format!("Invalid hex data: {e}")))?;
+        parse_block_from_wire(&block_bytes)
+            .map_err(|e| RpcError::invalid_params(format!("Failed to deserialize block: {e}")))
+    }
 
-        // Deserialize block
-        let (block, _witnesses) = deserialize_block_with_witnesses(&block_bytes)
-            .map_err(|e| RpcError::invalid_params(format!("Failed to deserialize block: {e}")))?;
+    /// Submit a block header without its body
+    ///
+    /// Params: ["hexdata"]
+    ///
+    /// The header must pass proof-of-work and extend a known header. It is
+    /// stored in the header chain as a `headers-only` chain tip; the block
+    /// body can be submitted later and connects normally. Returns null.
+    pub async fn submit_header(&self, params: &Value) -> RpcResult<Value> {
+        debug!("RPC: submitheader");
 
-        // Get current chain state
-        let height = self
-            .get_current_height()?
+        use crate::rpc::validation::validate_hex_string_param;
+        let hex_data = validate_hex_string_param(params, 0, "hexdata", Some(160))?;
+        let header_bytes = hex::decode(&hex_data)
+            .map_err(|e| RpcError::invalid_params(format!("Invalid hex data: {e}")))?;
+        let header = parse_header_from_wire(&header_bytes)
+            .map_err(|e| RpcError::invalid_params(format!("Failed to deserialize header: {e}")))?;
+
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Storage not available"))?;
+        let storage_error =
+            |e: anyhow::Error| RpcError::internal_error(format!("Storage error: {e}"));
+        let blocks = storage.blocks();
+        let chain = storage.chain();
+
+        let prev_hash = header.prev_block_hash;
+        if blocks
+            .get_header(&prev_hash)
+            .map_err(storage_error)?
+            .is_none()
+        {
+            return Err(RpcError::new(
+                RpcErrorCode::VerifyError,
+                format!(
+                    "Must submit previous header ({}) first",
+                    hex::encode(prev_hash)
+                ),
+            ));
+        }
+        if chain.is_invalid(&prev_hash).map_err(storage_error)? {
+            return Err(RpcError::new(RpcErrorCode::VerifyError, "bad-prevblk"));
+        }
+        match check_proof_of_work(&header) {
+            Ok(true) => {}
+            _ => return Err(RpcError::new(RpcErrorCode::VerifyError, "high-hash")),
+        }
+
+        let hash = blocks.store_header(&header).map_err(storage_error)?;
+        if blocks.has_block(&hash).map_err(storage_error)? {
+            // Already have the block
+            return Ok(Value::Null);
+        }
+        let (height, branchlen) = self.header_position(storage, &prev_hash)?;
+        chain
+            .add_header_tip(&hash, &header, height, branchlen)
+            .map_err(storage_error)?;
+        debug!("Accepted header {} at height {}", hex::encode(hash), height);
+        Ok(Value::Null)
+    }
+
+    /// Height and branch length of a header extending `prev_hash`
+    ///
+    /// The branch length counts headers since the fork from the active chain.
+    fn header_position(&self, storage: &Storage, prev_hash: &Hash) -> RpcResult<(u64, u64)> {
+        let storage_error =
+            |e: anyhow::Error| RpcError::internal_error(format!("Storage error: {e}"));
+        let tips = storage.chain().get_chain_tips().map_err(storage_error)?;
+        if let Some((_, height, branchlen, _)) = tips.iter().find(|(hash, ..)| hash == prev_hash) {
+            return Ok((height + 1, branchlen + 1));
+        }
+
+        // Walk back to the active chain
+        let blocks = storage.blocks();
+        let mut hash = *prev_hash;
+        let mut branchlen = 1;
+        loop {
+            if let Some(height) = blocks.get_height_by_hash(&hash).map_err(storage_error)? {
+                if blocks.get_hash_by_height(height).map_err(storage_error)? == Some(hash) {
+                    return Ok((height + branchlen, branchlen));
+                }
+            }
+            hash = blocks
+                .get_header(&hash)
+                .map_err(storage_error)?
+                .ok_or_else(|| RpcError::internal_error("Header chain is not connected"))?
+                .prev_block_hash;
+            branchlen += 1;
+        }
+    }
+
+    /// Mine blocks to an address (regtest and signet only)
+    ///
+    /// Params: [nblocks, "address", maxtries (optional, default: 1000000)]
+    ///
+    /// Each block takes mempool transactions and a coinbase paying the
+    /// subsidy plus fees to `address`, and is connected through the same
+    /// path as `submitblock`. Returns the hashes of the generated blocks.
+    /// Generated blocks carry no signet solution, so on signet the challenge
+    /// must accept blocks without one (e.g. `OP_TRUE`).
+    pub async fn generate_to_address(&self, params: &Value) -> RpcResult<Value> {
+        debug!("RPC: generatetoaddress");
+
+        use crate::rpc::validation::{
+            validate_numeric_param, validate_optional_numeric_param, validate_string_param,
+        };
+        let nblocks: u64 = validate_numeric_param(params, 0, "nblocks", None, None)?;
+        let address = validate_string_param(params, 1, "address", Some(128))?;
+        let max_tries: u64 = validate_optional_numeric_param(
+            params,
+            2,
+            "maxtries",
+            DEFAULT_GENERATE_MAX_TRIES,
+            Some(1),
+            None,
+        )?;
+
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Storage not available"))?;
+        let network = Self::chain_network(storage)?;
+        if network != "regtest" && network != "signet" {
+            return Err(RpcError::new(
+                RpcErrorCode::MethodNotFound,
+                "generatetoaddress is only available on regtest and signet",
+            ));
+        }
+        if network == "signet"
+            && !self
+                .signet_challenge
+                .as_deref()
+                .is_some_and(is_trivial_challenge)
+        {
+            return Err(RpcError::new(
+                RpcErrorCode::MethodNotFound,
+                "generatetoaddress cannot sign blocks; it needs a signet challenge met without a solution, such as OP_TRUE",
+            ));
+        }
+        let script_pubkey = address_to_script(&address, &network).ok_or_else(|| {
+            RpcError::invalid_address_or_key(format!("Invalid address: {address}"))
+        })?;
+        let forwarding = self.commons_forwarding(&network)?;
+        let forwarding = forwarding
+            .as_ref()
+            .map(|(_, script, percentage)| (script.as_slice(), *percentage));
+
+        let mut hashes = Vec::new();
+        for _ in 0..nblocks {
+            let (template, witnesses) =
+                self.build_block_to_script(storage, &script_pubkey, forwarding)?;
+            let (block, result) = tokio::task::spawn_blocking(move || {
+                ConsensusProof::new().mine_block(template, max_tries)
+            })
+            .await
+            .map_err(|e| RpcError::internal_error(format!("Mining task panicked: {e}")))?
+            .map_err(|e| RpcError::internal_error(format!("Mining failed: {e}")))?;
+            if !matches!(result, bllvm_protocol::mining::MiningResult::Success) {
+                // Out of attempts; return the blocks mined so far
+                break;
+            }
+
+            match self.process_block(block, witnesses, true).await? {
+                BlockSubmission::Connected(hash) => hashes.push(json!(hex::encode(hash))),
+                other => {
+                    return Err(RpcError::internal_error(format!(
+                        "Generated block was not connected: {other:?}"
+                    )))
+                }
+            }
+        }
+
+        Ok(Value::Array(hashes))
+    }
+
+    /// Assemble an unsolved block on the current tip paying to `script_pubkey`
+    ///
+    /// With `forwarding`, part of the coinbase value goes to the commons
+    /// script (see [`split_coinbase_value`]). The block's witnesses are
+    /// returned alongside it: the pooled transactions' own, and the reserved
+    /// value the commitment covers for the coinbase.
+    fn build_block_to_script(
+        &self,
+        storage: &Storage,
+        script_pubkey: &[u8],
+        forwarding: Option<(&[u8], u8)>,
+    ) -> RpcResult<(Block, Vec<Vec<Witness>>)> {
+        let tip_hash = storage
+            .chain()
+            .get_tip_hash()
+            .map_err(|e| RpcError::internal_error(format!("Failed to get tip hash: {e}")))?
             .ok_or_else(|| RpcError::internal_error("Chain not initialized"))?;
-        let utxo_set = self.get_utxo_set()?;
+        let tip_header = self
+            .get_tip_header()?
+            .ok_or_else(|| RpcError::internal_error("Chain not initialized"))?;
+        let height = self.get_current_height()?.unwrap_or(0) + 1;
+
+        // Skip pool entries already confirmed by an earlier generated block
+        let transactions: Vec<Transaction> = self
+            .get_mempool_transactions()?
+            .into_iter()
+            .filter(|tx| {
+                let txid = bllvm_protocol::block::calculate_tx_id(tx);
+                !storage
+                    .transactions()
+                    .has_transaction(&txid)
+                    .unwrap_or(false)
+            })
+            .collect();
+        let fees: u64 = transactions
+            .iter()
+            .map(|tx| self.calculate_transaction_fee(tx))
+            .sum();
+        let coinbase_value = (self.consensus.get_block_subsidy(height) as u64)
+            .checked_add(fees)
+            .ok_or_else(|| RpcError::internal_error("Coinbase value overflow"))?;
+
+        // BIP 34 height push followed by OP_0, as Bitcoin Core does
+        let mut script_sig = script_num_push(height);
+        script_sig.push(0x00);
+        let mut outputs = split_coinbase_value(coinbase_value, script_pubkey, forwarding);
+        let mempool_witnesses = self.get_mempool_witnesses(&transactions);
+        outputs.push(build_witness_commitment(&transactions, &mempool_witnesses).output);
+        let coinbase = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig,
+                sequence: 0xffffffff,
+            }],
+            outputs: outputs.into(),
+            lock_time: 0,
+        };
+
+        let mut witnesses = vec![vec![vec![WITNESS_RESERVED_VALUE.to_vec()]]];
+        witnesses.extend(mempool_witnesses);
+        let mut all_transactions = vec![coinbase];
+        all_transactions.extend(transactions);
+        let merkle_root = calculate_merkle_root(&mut all_transactions).map_err(|e| {
+            RpcError::internal_error(format!("Failed to calculate merkle root: {e}"))
+        })?;
+
+        let block = Block {
+            header: BlockHeader {
+                version: 0x20000000,
+                prev_block_hash: tip_hash,
+                merkle_root,
+                timestamp: current_timestamp().max(tip_header.timestamp + 1),
+                bits: tip_header.bits,
+                nonce: 0,
+            },
+            transactions: all_transactions.into_boxed_slice(),
+        };
+        Ok((block, witnesses))
+    }
 
-        // Validate block using consensus layer
-        // Note: This validates consensus rules, but doesn't check if block extends chain
-        // In production, would also check:
-        // 1. Block extends current tip
-        // 2. Block is not already in chain
-        // 3. Block is not orphaned
+    /// Validate a block and connect it if it extends the tip
+    ///
+    /// Connected blocks are stored with their witnesses, indexed, applied to
+    /// the UTXO set and announced to peers. With `connect` false (a BIP 23
+    /// proposal) the block is only validated, without its proof of work or
+    /// signet solution, and a valid block is reported as [`BlockSubmission::Valid`]. Blocks not
+    /// extending the tip are [`BlockSubmission::Inconclusive`] without further
+    /// checks.
+    async fn process_block(
+        &self,
+        block: Block,
+        witnesses: Vec<Vec<Witness>>,
+        connect: bool,
+    ) -> RpcResult<BlockSubmission> {
+        let storage = self
+            .storage
+            .as_ref()
+            .ok_or_else(|| RpcError::internal_error("Storage not available"))?;
+        let block_hash = storage.blocks().get_block_hash(&block);
+
+        // The tip must not move between validating the block and connecting it
+        let chainstate = storage.lock_chainstate();
+        if storage
+            .blocks()
+            .has_block(&block_hash)
+            .map_err(|e| RpcError::internal_error(format!("Failed to look up block: {e}")))?
+        {
+            return Ok(BlockSubmission::Duplicate);
+        }
+
+        let tip_hash = storage
+            .chain()
+            .get_tip_hash()
+            .map_err(|e| RpcError::internal_error(format!("Failed to get tip hash: {e}")))?
+            .ok_or_else(|| RpcError::internal_error("Chain not initialized"))?;
+        if block.header.prev_block_hash != tip_hash {
+            return Ok(BlockSubmission::Inconclusive);
+        }
+        let height = self.get_current_height()?.unwrap_or(0) + 1;
+
+        if block.transactions.is_empty() {
+            return Ok(BlockSubmission::Invalid("bad-blk-length".to_string()));
+        }
+        if calculate_merkle_root(&block.transactions).ok() != Some(block.header.merkle_root) {
+            return Ok(BlockSubmission::Invalid("bad-txnmrklroot".to_string()));
+        }
+        // Proposals are checked before the work is done, and so before the
+        // signet solution is added
+        if connect && !check_proof_of_work(&block.header).unwrap_or(false) {
+            return Ok(BlockSubmission::Invalid("high-hash".to_string()));
+        }
+        if let (true, Some(challenge)) = (connect, &self.signet_challenge) {
+            if let Err(reason) = check_block_solution(&block, challenge, height) {
+                return Ok(BlockSubmission::Invalid(reason));
+            }
+        }
+        let utxo_set = self.get_utxo_set()?;
         match self.consensus.validate_block(&block, utxo_set, height) {
-            Ok((ValidationResult::Valid, _)) => {
-                // Block is valid - in production would submit to block processor
-                // For now, just return success
-                debug!("Block submitted successfully");
-                Ok(Value::Null)
+            Ok((ValidationResult::Valid, _)) => {}
+            Ok((ValidationResult::Invalid(reason), _)) => {
+                return Ok(BlockSubmission::Invalid(reason.to_string()))
+            }
+            Err(e) => return Err(RpcError::internal_error(format!("Validation error: {e}"))),
+        }
+        if let Err(reason) = check_witness_commitment(&block, &witnesses) {
+            return Ok(BlockSubmission::Invalid(reason));
+        }
+        if !connect {
+            return Ok(BlockSubmission::Valid);
+        }
+
+        let connect = || -> anyhow::Result<()> {
+            crate::node::block_processor::store_block_with_context(
+                &storage.blocks(),
+                &block,
+                &witnesses,
+                height,
+            )?;
+            storage.connect_block(&block, height)?;
+            for (index, tx) in block.transactions.iter().enumerate() {
+                storage.transactions().index_transaction_with_witness(
+                    tx,
+                    transaction_witnesses(&witnesses, index),
+                    &block_hash,
+                    height,
+                    index as u32,
+                )?;
             }
-            Ok((ValidationResult::Invalid(reason), _)) => Err(RpcError::invalid_params(format!(
-                "Invalid block: {}",
-                reason
-            ))),
-            Err(e) => Err(RpcError::internal_error(format!("Validation error: {e}"))),
+            storage
+                .chain()
+                .update_tip(&block_hash, &block.header, height)?;
+            Ok(())
+        };
+        connect().map_err(|e| RpcError::internal_error(format!("Failed to connect block: {e}")))?;
+        drop(chainstate);
+        debug!(
+            "Connected block {} at height {}",
+            hex::encode(block_hash),
+            height
+        );
+
+        let block_data = serialize_block(&block, &witnesses);
+        if let Some(ref notifications) = self.notifications {
+            notifications.notify_block(&block_hash, &block_data);
         }
+        if let Some(ref network) = self.network {
+            if let Err(e) = network.relay_block_fibre(block_hash, &block_data).await {
+                warn!(
+                    "Failed to relay block {} via FIBRE: {}",
+                    hex::encode(block_hash),
+                    e
+                );
+            }
+            if let Err(e) = network.announce_block(block_hash).await {
+                warn!(
+                    "Failed to announce block {}: {}",
+                    hex::encode(block_hash),
+                    e
+                );
+            }
+        }
+        #[cfg(feature = "governance")]
+        self.report_fee_forwarding(storage, &block, &block_hash, height);
+        Ok(BlockSubmission::Connected(block_hash))
+    }
+
+    /// Queue a governance webhook event if the coinbase paid the commons address
+    #[cfg(feature = "governance")]
+    fn report_fee_forwarding(
+        &self,
+        storage: &Storage,
+        block: &Block,
+        block_hash: &Hash,
+        height: u64,
+    ) {
+        let Some(ref webhook) = self.governance_webhook else {
+            return;
+        };
+        let forwarding = match Self::chain_network(storage)
+            .and_then(|network| self.commons_forwarding(&network))
+        {
+            Ok(Some(forwarding)) => forwarding,
+            Ok(None) => return,
+            Err(e) => {
+                debug!("Skipping fee forwarding report: {}", e.message);
+                return;
+            }
+        };
+        let (address, script, _) = forwarding;
+        let forwarded_amount: u64 = block
+            .transactions
+            .first()
+            .map(|coinbase| {
+                coinbase
+                    .outputs
+                    .iter()
+                    .filter(|output| output.script_pubkey == script)
+                    .map(|output| output.value.max(0) as u64)
+                    .sum()
+            })
+            .unwrap_or(0);
+        if forwarded_amount == 0 {
+            return;
+        }
+
+        webhook.notify_fee_forwarding(crate::governance::FeeForwardingEvent {
+            block_hash: hex::encode(block_hash),
+            block_height: height,
+            forwarded_amount,
+            commons_address: address,
+            contributor_id: self
+                .fee_forwarding
+                .as_ref()
+                .and_then(|config| config.contributor_id.clone()),
+        });
     }
 
     /// Estimate smart fee rate
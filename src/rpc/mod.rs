@@ -3,33 +3,49 @@
 //! This module provides JSON-RPC server, blockchain query methods,
 //! network info methods, transaction submission, and mining methods.
 
+pub mod address;
 pub mod auth;
 pub mod blockchain;
+pub mod circuit_breaker;
 pub mod control;
+pub mod descriptor;
 pub mod errors;
 pub mod mempool;
+pub mod message;
+pub mod metrics_server;
 pub mod mining;
 pub mod network;
 pub mod rawtx;
 #[cfg(kani)]
 pub mod rpc_proofs;
+pub mod scan;
 pub mod server;
 pub mod types;
 pub mod validation;
+pub mod work_queue;
+
+#[cfg(feature = "rpc-tls")]
+pub mod tls;
 
 #[cfg(feature = "quinn")]
 pub mod quinn_server;
 
-use crate::config::RpcAuthConfig;
+use crate::config::{
+    FeeForwardingConfig, RequestTimeoutConfig, RpcAuthConfig, RpcCircuitBreakerConfig, RpcTlsConfig,
+};
+use crate::network::subnet::Subnet;
+use crate::node::chainstates::BackgroundChainstate;
 use crate::node::mempool::MempoolManager;
 use crate::node::metrics::MetricsCollector;
+use crate::node::notifications::NotificationPublisher;
 use crate::node::performance::PerformanceProfiler;
 use crate::storage::Storage;
 use anyhow::Result;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 /// RPC manager that coordinates all RPC operations
 ///
@@ -55,6 +71,33 @@ pub struct RpcManager {
     metrics: Option<Arc<MetricsCollector>>,
     /// Performance profiler (optional)
     profiler: Option<Arc<PerformanceProfiler>>,
+    /// Circuit breaker settings for storage-heavy methods
+    circuit_breaker_config: RpcCircuitBreakerConfig,
+    /// Per-request handler timeouts
+    request_timeouts: RequestTimeoutConfig,
+    /// Requests executed concurrently (`rpcthreads`)
+    rpc_threads: usize,
+    /// Requests allowed to wait for a worker (`rpcworkqueue`)
+    rpc_work_queue: usize,
+    /// Workers reserved for long-running methods (0 shares the main pool)
+    rpc_long_running_threads: usize,
+    /// TLS for the TCP server (plaintext if unset)
+    tls_config: Option<RpcTlsConfig>,
+    /// Subnets allowed to connect besides loopback (`rpcallowip`)
+    allowed_ips: Vec<Subnet>,
+    /// Coinbase fee forwarding applied by the mining methods
+    fee_forwarding: Option<FeeForwardingConfig>,
+    /// Governance webhook notified of forwarded fees
+    #[cfg(feature = "governance")]
+    governance_webhook: Option<Arc<crate::governance::GovernanceWebhookClient>>,
+    /// Background chainstate reported by `getchainstates` (assumeutxo)
+    background_chainstate: Option<Arc<BackgroundChainstate>>,
+    /// Chain work required before leaving initial block download
+    minimum_chain_work: u128,
+    /// Block notifications published by submitblock
+    notifications: Option<Arc<NotificationPublisher>>,
+    /// Signet challenge checked by submitblock and RPC-triggered reorgs
+    signet_challenge: Option<Vec<u8>>,
 }
 
 impl RpcManager {
@@ -77,6 +120,20 @@ impl RpcManager {
             quinn_shutdown_tx: None,
             auth_manager: None,
             node_shutdown: None,
+            circuit_breaker_config: RpcCircuitBreakerConfig::default(),
+            request_timeouts: RequestTimeoutConfig::default(),
+            rpc_threads: work_queue::DEFAULT_RPC_THREADS,
+            rpc_work_queue: work_queue::DEFAULT_RPC_WORK_QUEUE,
+            rpc_long_running_threads: work_queue::DEFAULT_LONG_RUNNING_RPC_THREADS,
+            tls_config: None,
+            allowed_ips: Vec::new(),
+            fee_forwarding: None,
+            #[cfg(feature = "governance")]
+            governance_webhook: None,
+            background_chainstate: None,
+            minimum_chain_work: 0,
+            notifications: None,
+            signet_challenge: None,
         }
     }
 
@@ -104,6 +161,14 @@ impl RpcManager {
                 error!("Failed to add RPC auth token: {}", e);
             }
         }
+        for scoped in auth_config.scoped_tokens {
+            if let Err(e) = auth_manager
+                .add_scoped_token(scoped.token, scoped.allowed_methods)
+                .await
+            {
+                error!("Failed to add scoped RPC auth token: {}", e);
+            }
+        }
         for cert in auth_config.certificates {
             if let Err(e) = auth_manager.add_certificate(cert).await {
                 error!("Failed to add RPC auth certificate: {}", e);
@@ -124,7 +189,8 @@ impl RpcManager {
         self.mining_rpc =
             mining::MiningRpc::with_dependencies(Arc::clone(&storage), Arc::clone(&mempool));
         use crate::utils::arc_clone;
-        self.blockchain_rpc = blockchain::BlockchainRpc::with_dependencies(arc_clone(&storage));
+        self.blockchain_rpc = blockchain::BlockchainRpc::with_dependencies(arc_clone(&storage))
+            .with_minimum_chain_work(self.minimum_chain_work);
         // Note: mempool_rpc is created later in with_dependencies_auth_and_metrics if needed
         // This early creation was unused - removed to avoid warning
         let _rawtx_rpc = rawtx::RawTxRpc::with_dependencies(
@@ -153,6 +219,77 @@ impl RpcManager {
         self
     }
 
+    /// Set circuit breaker configuration for storage-heavy methods
+    pub fn set_circuit_breaker_config(&mut self, config: RpcCircuitBreakerConfig) {
+        self.circuit_breaker_config = config;
+    }
+
+    /// Set per-request handler timeouts
+    pub fn set_request_timeouts(&mut self, config: RequestTimeoutConfig) {
+        self.request_timeouts = config;
+    }
+
+    /// Set the request work queue (see [`server::RpcServer::with_work_queues`])
+    pub fn set_work_queue(&mut self, threads: usize, depth: usize, long_running_threads: usize) {
+        self.rpc_threads = threads;
+        self.rpc_work_queue = depth;
+        self.rpc_long_running_threads = long_running_threads;
+    }
+
+    /// Allow RPC connections from these subnets besides loopback
+    pub fn set_allowed_ips(&mut self, subnets: Vec<Subnet>) {
+        self.allowed_ips = subnets;
+    }
+
+    /// Serve the TCP RPC server over TLS
+    pub fn set_tls_config(&mut self, config: RpcTlsConfig) {
+        self.tls_config = Some(config);
+    }
+
+    /// Set coinbase fee forwarding for block assembly
+    pub fn set_fee_forwarding(&mut self, config: FeeForwardingConfig) {
+        self.fee_forwarding = Some(config);
+    }
+
+    /// Set the governance webhook notified when mined blocks forward fees
+    #[cfg(feature = "governance")]
+    pub fn set_governance_webhook(
+        &mut self,
+        webhook: Arc<crate::governance::GovernanceWebhookClient>,
+    ) {
+        self.governance_webhook = Some(webhook);
+    }
+
+    /// Set the background chainstate validating a loaded UTXO snapshot
+    pub fn set_background_chainstate(&mut self, chainstate: Arc<BackgroundChainstate>) {
+        self.blockchain_rpc = std::mem::take(&mut self.blockchain_rpc)
+            .with_background_chainstate(Arc::clone(&chainstate));
+        self.background_chainstate = Some(chainstate);
+    }
+
+    /// Stay in initial block download until the active chain has this much work
+    pub fn with_minimum_chain_work(mut self, minimum_chain_work: u128) -> Self {
+        self.set_minimum_chain_work(minimum_chain_work);
+        self
+    }
+
+    /// Set the chain work required before leaving initial block download (0 disables)
+    pub fn set_minimum_chain_work(&mut self, minimum_chain_work: u128) {
+        self.blockchain_rpc =
+            std::mem::take(&mut self.blockchain_rpc).with_minimum_chain_work(minimum_chain_work);
+        self.minimum_chain_work = minimum_chain_work;
+    }
+
+    /// Set the publisher notified of blocks connected by submitblock
+    pub fn set_notifications(&mut self, notifications: Arc<NotificationPublisher>) {
+        self.notifications = Some(notifications);
+    }
+
+    /// Set the signet challenge blocks must satisfy (`None` outside signet)
+    pub fn set_signet_challenge(&mut self, challenge: Option<Vec<u8>>) {
+        self.signet_challenge = challenge;
+    }
+
     /// Set network manager dependency
     pub fn with_network_manager(
         mut self,
@@ -183,6 +320,20 @@ impl RpcManager {
             quinn_shutdown_tx: None,
             auth_manager: None,
             node_shutdown: None,
+            circuit_breaker_config: RpcCircuitBreakerConfig::default(),
+            request_timeouts: RequestTimeoutConfig::default(),
+            rpc_threads: work_queue::DEFAULT_RPC_THREADS,
+            rpc_work_queue: work_queue::DEFAULT_RPC_WORK_QUEUE,
+            rpc_long_running_threads: work_queue::DEFAULT_LONG_RUNNING_RPC_THREADS,
+            tls_config: None,
+            allowed_ips: Vec::new(),
+            fee_forwarding: None,
+            #[cfg(feature = "governance")]
+            governance_webhook: None,
+            background_chainstate: None,
+            minimum_chain_work: 0,
+            notifications: None,
+            signet_challenge: None,
         }
     }
 
@@ -203,32 +354,72 @@ impl RpcManager {
 
         // Create control RPC with shutdown capability
         use crate::utils::{arc_clone, arc_new};
-        let control_rpc = arc_new(control::ControlRpc::with_shutdown(
-            shutdown_tx.clone(),
-            self.node_shutdown.clone(),
-        ));
+        let mut control_rpc =
+            control::ControlRpc::with_shutdown(shutdown_tx.clone(), self.node_shutdown.clone());
+        if let Some(ref profiler) = self.profiler {
+            control_rpc = control_rpc.with_profiler(arc_clone(profiler));
+        }
+        if let (Some(storage), Some(mempool)) = (self.storage.as_ref(), self.mempool.as_ref()) {
+            control_rpc = control_rpc.with_dependencies(arc_clone(storage), arc_clone(mempool));
+        }
+        if let Some(ref network) = self.network_manager {
+            control_rpc = control_rpc.with_network(arc_clone(network));
+        }
+        let control_rpc = arc_new(control_rpc);
 
         // Create server with or without authentication
         let server = if let (Some(ref storage), Some(ref mempool)) =
             (self.storage.as_ref(), self.mempool.as_ref())
         {
-            let blockchain = arc_new(blockchain::BlockchainRpc::with_dependencies(arc_clone(
-                storage,
-            )));
-            let mempool_rpc = arc_new(mempool::MempoolRpc::with_dependencies(
-                arc_clone(mempool),
-                arc_clone(&storage),
-            ));
-            let rawtx_rpc = arc_new(rawtx::RawTxRpc::with_dependencies(
+            let mut blockchain_rpc =
+                blockchain::BlockchainRpc::with_dependencies(arc_clone(storage))
+                    .with_minimum_chain_work(self.minimum_chain_work)
+                    .with_signet_challenge(self.signet_challenge.clone());
+            if self.circuit_breaker_config.enabled {
+                let mut breaker =
+                    circuit_breaker::StorageCircuitBreaker::new(&self.circuit_breaker_config);
+                if let Some(ref metrics) = self.metrics {
+                    breaker = breaker.with_metrics(arc_clone(metrics));
+                }
+                blockchain_rpc = blockchain_rpc.with_circuit_breaker(arc_new(breaker));
+            }
+            if let Some(ref chainstate) = self.background_chainstate {
+                blockchain_rpc = blockchain_rpc.with_background_chainstate(arc_clone(chainstate));
+            }
+            if let Some(ref profiler) = self.profiler {
+                blockchain_rpc = blockchain_rpc.with_profiler(arc_clone(profiler));
+            }
+            let blockchain = arc_new(blockchain_rpc);
+            let mut mempool_rpc =
+                mempool::MempoolRpc::with_dependencies(arc_clone(mempool), arc_clone(&storage));
+            let mut rawtx_rpc = rawtx::RawTxRpc::with_dependencies(
                 arc_clone(storage),
                 arc_clone(mempool),
                 None,
                 None,
-            ));
-            let mining = arc_new(mining::MiningRpc::with_dependencies(
-                arc_clone(storage),
-                arc_clone(mempool),
-            ));
+            );
+            let mut mining_rpc =
+                mining::MiningRpc::with_dependencies(arc_clone(storage), arc_clone(mempool))
+                    .with_signet_challenge(self.signet_challenge.clone());
+            // Announce locally accepted transactions and blocks to peers
+            if let Some(ref network_manager) = self.network_manager {
+                mempool_rpc = mempool_rpc.with_network(arc_clone(network_manager));
+                rawtx_rpc = rawtx_rpc.with_network(arc_clone(network_manager));
+                mining_rpc = mining_rpc.with_network(arc_clone(network_manager));
+            }
+            if let Some(ref fee_forwarding) = self.fee_forwarding {
+                mining_rpc = mining_rpc.with_fee_forwarding(fee_forwarding.clone());
+            }
+            #[cfg(feature = "governance")]
+            if let Some(ref webhook) = self.governance_webhook {
+                mining_rpc = mining_rpc.with_governance_webhook(arc_clone(webhook));
+            }
+            if let Some(ref notifications) = self.notifications {
+                mining_rpc = mining_rpc.with_notifications(arc_clone(notifications));
+            }
+            let mempool_rpc = arc_new(mempool_rpc);
+            let rawtx_rpc = arc_new(rawtx_rpc);
+            let mining = arc_new(mining_rpc);
             let network = if let Some(ref network_manager) = self.network_manager {
                 arc_new(network::NetworkRpc::with_dependencies(arc_clone(
                     network_manager,
@@ -291,6 +482,39 @@ impl RpcManager {
                 server::RpcServer::new(self.server_addr)
             }
         };
+        let server = server
+            .with_request_timeouts(
+                Duration::from_secs(self.request_timeouts.rpc_timeout_seconds),
+                Duration::from_secs(self.request_timeouts.rpc_long_running_timeout_seconds),
+            )
+            .with_work_queues(
+                self.rpc_threads,
+                self.rpc_work_queue,
+                self.rpc_long_running_threads,
+            )
+            .with_allowed_ips(self.allowed_ips.clone());
+        let server = match self.tls_config {
+            #[cfg(feature = "rpc-tls")]
+            Some(ref tls_config) => {
+                info!("RPC server using TLS");
+                server.with_tls(Arc::new(tls::RpcTlsAcceptor::from_config(tls_config)?))
+            }
+            #[cfg(not(feature = "rpc-tls"))]
+            Some(_) => {
+                return Err(anyhow::anyhow!(
+                    "rpc_tls is configured but this build lacks the rpc-tls feature"
+                ))
+            }
+            None => {
+                if !self.server_addr.ip().is_loopback() {
+                    warn!(
+                        "RPC server on {} is not loopback-only and does not use TLS; configure rpc_tls for remote access",
+                        self.server_addr
+                    );
+                }
+                server
+            }
+        };
 
         // Start TCP server in a background task
         let tcp_handle = tokio::spawn(async move {
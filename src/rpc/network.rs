@@ -2,14 +2,55 @@
 //!
 //! Implements network-related JSON-RPC methods for querying and managing network state.
 
+use crate::network::local_address::NETWORKS;
 use crate::network::NetworkManager;
-use crate::rpc::errors::{RpcError, RpcResult};
+use crate::rpc::blockchain::decode_hash32;
+use crate::rpc::errors::{RpcError, RpcErrorCode, RpcResult};
 use crate::utils::current_timestamp;
 use serde_json::{json, Value};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::debug;
 
+/// How long `getblockfrompeer` waits for the peer to deliver the block
+pub const GET_BLOCK_FROM_PEER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most entries `getrawaddrman` returns from each address manager table
+pub const MAX_RAWADDRMAN_ENTRIES_PER_TABLE: usize = 1000;
+
+/// Display form and network name of an address in 16-byte IPv6 form
+fn address_and_network(ip: [u8; 16]) -> (String, &'static str) {
+    let ip = std::net::Ipv6Addr::from(ip);
+    match ip.to_ipv4_mapped() {
+        Some(v4) => (v4.to_string(), "ipv4"),
+        None => (ip.to_string(), "ipv6"),
+    }
+}
+
+/// Per-network reachability and proxy, as reported by `getnetworkinfo`
+///
+/// Without a network manager only IPv4 and IPv6 are reported reachable.
+fn networks_info(network: Option<&NetworkManager>) -> Value {
+    let proxy = network.and_then(|n| n.proxy_config());
+    NETWORKS
+        .iter()
+        .map(|name| {
+            let reachable = match network {
+                Some(network) => network.is_network_reachable(name),
+                None => matches!(*name, "ipv4" | "ipv6"),
+            };
+            json!({
+                "name": name,
+                "limited": !reachable,
+                "reachable": reachable,
+                "proxy": proxy.map(|p| p.addr.to_string()).unwrap_or_default(),
+                "proxy_randomize_credentials": proxy.is_some_and(|p| p.stream_isolation),
+            })
+        })
+        .collect()
+}
+
 /// Network RPC methods
 #[derive(Clone)]
 pub struct NetworkRpc {
@@ -54,22 +95,7 @@ impl NetworkRpc {
                     "timeoffset": 0,
                     "networkactive": true,
                     "connections": 0,
-                    "networks": [
-                        {
-                            "name": "ipv4",
-                            "limited": false,
-                            "reachable": true,
-                            "proxy": "",
-                            "proxy_randomize_credentials": false
-                        },
-                        {
-                            "name": "ipv6",
-                            "limited": false,
-                            "reachable": true,
-                            "proxy": "",
-                            "proxy_randomize_credentials": false
-                        }
-                    ],
+                    "networks": [],
                     "relayfee": 0.00001000,
                     "incrementalfee": 0.00001000,
                     "localaddresses": [],
@@ -80,6 +106,35 @@ impl NetworkRpc {
             // Clone and update only the dynamic field
             let mut result = base_info.clone();
             result["connections"] = json!(peer_count);
+            result["subversion"] = json!(network.user_agent());
+            let counts = network.connection_counts().await;
+            result["connections_in"] = json!(counts.inbound);
+            result["connections_out"] = json!(counts.outbound_full_relay + counts.block_relay_only);
+            result["connection_types"] = json!({
+                "inbound": counts.inbound,
+                "outbound-full-relay": counts.outbound_full_relay,
+                "block-relay-only": counts.block_relay_only,
+                "feeler": counts.feeler,
+            });
+            result["networks"] = networks_info(Some(network));
+            result["localaddresses"] = network
+                .local_addresses()
+                .await
+                .iter()
+                .map(|local| {
+                    json!({
+                        "address": local.addr.ip().to_string(),
+                        "port": local.addr.port(),
+                        "score": local.score,
+                    })
+                })
+                .collect();
+            // Relay fees are configurable; report them in BTC/kvB
+            if let Some(mempool) = network.mempool_manager() {
+                result["relayfee"] = json!(mempool.min_relay_fee_per_kvb() as f64 / 100_000_000.0);
+                result["incrementalfee"] =
+                    json!(mempool.incremental_relay_fee_per_kvb() as f64 / 100_000_000.0);
+            }
             Ok(result)
         } else {
             Ok(json!({
@@ -91,22 +146,9 @@ impl NetworkRpc {
                 "timeoffset": 0,
                 "networkactive": true,
                 "connections": 0,
-                "networks": [
-                    {
-                        "name": "ipv4",
-                        "limited": false,
-                        "reachable": true,
-                        "proxy": "",
-                        "proxy_randomize_credentials": false
-                    },
-                    {
-                        "name": "ipv6",
-                        "limited": false,
-                        "reachable": true,
-                        "proxy": "",
-                        "proxy_randomize_credentials": false
-                    }
-                ],
+                "connections_in": 0,
+                "connections_out": 0,
+                "networks": networks_info(None),
                 "relayfee": 0.00001000,
                 "incrementalfee": 0.00001000,
                 "localaddresses": [],
@@ -120,13 +162,30 @@ impl NetworkRpc {
         debug!("RPC: getpeerinfo");
 
         if let Some(ref network) = self.network_manager {
+            // Misbehavior state lives outside the peer manager; look it up first
+            let peer_ips: Vec<_> = network
+                .peer_manager()
+                .await
+                .peer_socket_addresses()
+                .iter()
+                .map(|addr| addr.ip())
+                .collect();
+            let mut ban_scores = std::collections::HashMap::new();
+            let mut discouraged = std::collections::HashSet::new();
+            for ip in peer_ips {
+                ban_scores.insert(ip, network.dos_protection().misbehavior_score(ip).await);
+                if network.is_discouraged(ip).await {
+                    discouraged.insert(ip);
+                }
+            }
             let peer_manager = network.peer_manager().await;
 
             // This avoids: 1) cloning all addresses, 2) looking up each peer again
+            let now_micros = crate::utils::current_timestamp_duration().as_micros() as u64;
             let mut peers = Vec::new();
             for addr in peer_manager.peer_addresses() {
                 if let Some(peer) = peer_manager.get_peer(&addr) {
-                    peers.push(json!({
+                    let mut info = json!({
                         "id": match addr {
                             crate::network::transport::TransportAddr::Tcp(sock) => sock.port() as u64,
                             #[cfg(feature = "quinn")]
@@ -137,28 +196,45 @@ impl NetworkRpc {
                         "addr": addr.to_string(),
                         "addrlocal": "",
                         "services": "0000000000000001",
-                        "relaytxes": true,
+                        "relaytxes": peer.relay_txs() && !peer.is_block_relay_only(),
                         "lastsend": peer.last_send(),
                         "lastrecv": peer.last_recv(),
                         "bytessent": peer.bytes_sent(),
                         "bytesrecv": peer.bytes_recv(),
                         "conntime": peer.conntime(),
                         "timeoffset": 0,
-                        "pingtime": 0.0,
-                        "minping": 0.0,
+                        "pingtime": peer.last_ping_ms().map(|ms| ms / 1000.0),
+                        "minping": peer.min_ping_ms().map(|ms| ms / 1000.0),
                         "version": 70015,
                         "subver": "/reference-node:0.1.0/",
-                        "inbound": false,
+                        "inbound": peer.is_inbound(),
+                        "connection_type": peer.connection_type().as_str(),
                         "addnode": false,
                         "startingheight": 0,
                         "synced_headers": -1,
                         "synced_blocks": -1,
                         "inflight": [],
-                        "whitelisted": false,
+                        "permissions": peer.permissions().names(),
+                        "whitelisted": peer.is_whitelisted(),
+                        "noban": peer.is_whitelisted(),
+                        "banscore": ban_scores.get(&peer.address().ip()).copied().unwrap_or(0),
+                        "discouraged": peer.is_discouraged()
+                            || discouraged.contains(&peer.address().ip()),
                         "minfeefilter": 0.00001000,
                         "bytessent_per_msg": {},
                         "bytesrecv_per_msg": {}
-                    }));
+                    });
+                    if let Some(wait_ms) = peer.ping_wait_ms(now_micros) {
+                        info["pingwait"] = json!(wait_ms / 1000.0);
+                    }
+                    let throttle = peer.throttle_state();
+                    info["bandwidth"] = json!({
+                        "upload_limit": throttle.upload_limit,
+                        "download_limit": throttle.download_limit,
+                        "upload_throttled": throttle.upload_throttled,
+                        "download_throttled": throttle.download_throttled,
+                    });
+                    peers.push(info);
                 }
             }
             Ok(json!(peers))
@@ -176,7 +252,7 @@ impl NetworkRpc {
 
         if let Some(ref network) = self.network_manager {
             Ok(Value::Number(serde_json::Number::from(
-                network.peer_count(),
+                network.connection_count().await,
             )))
         } else {
             Ok(Value::Number(serde_json::Number::from(0)))
@@ -200,6 +276,9 @@ impl NetworkRpc {
     ///
     /// Params: ["node", "command"]
     /// command can be: "add", "remove", "onetry"
+    ///
+    /// `onetry` also takes a `host:port` node such as an onion service, which
+    /// is connected through the proxy when one is configured.
     pub async fn add_node(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: addnode");
 
@@ -210,6 +289,22 @@ impl NetworkRpc {
 
         let command = params.get(1).and_then(|p| p.as_str()).unwrap_or("add");
 
+        // Host names can only be tried once: added nodes are kept by address
+        if command == "onetry" && node.parse::<SocketAddr>().is_err() {
+            let (host, port) = node
+                .rsplit_once(':')
+                .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+                .filter(|(host, _)| !host.is_empty())
+                .ok_or_else(|| RpcError::invalid_params(format!("Invalid node address: {node}")))?;
+            if let Some(ref network) = self.network_manager {
+                let addr = network.connect_to_host(host, port).await.map_err(|e| {
+                    RpcError::internal_error(format!("Failed to connect to {}: {}", node, e))
+                })?;
+                debug!("Connected to node {} as {} (onetry)", node, addr);
+            }
+            return Ok(Value::Null);
+        }
+
         // Parse node address
         let addr: SocketAddr = node
             .parse()
@@ -465,96 +560,93 @@ impl NetworkRpc {
 
     /// Get added node information
     ///
-    /// Params: ["node", "dns"] (node address, optional dns flag)
+    /// Params: ["node"] (optional; all added nodes if omitted)
+    ///
+    /// Besides the Bitcoin Core fields, each entry reports the reconnection
+    /// state: `attempts` since the node was last connected and `nextattempt`
+    /// (Unix timestamp) while it is disconnected.
     pub async fn getaddednodeinfo(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: getaddednodeinfo");
 
-        let node = params
-            .get(0)
-            .and_then(|p| p.as_str())
-            .ok_or_else(|| RpcError::invalid_params("Node address required".to_string()))?;
-
-        let dns = params.get(1).and_then(|p| p.as_bool()).unwrap_or(false);
-
-        if let Some(ref network) = self.network_manager {
-            // Parse node address
-            let addr: SocketAddr = node
-                .parse()
-                .map_err(|e| RpcError::invalid_params(format!("Invalid node address: {e}")))?;
-
-            // Check if node is in persistent peer list
-            let persistent_peers = network.get_persistent_peers().await;
-            let _is_added = persistent_peers.contains(&addr);
-
-            // Get connection status
-            let peer_count = network.peer_count();
-            let is_connected = peer_count > 0; // Simplified - would check actual connection
-
-            Ok(json!([{
-                "addednode": node,
-                "connected": is_connected,
-                "addresses": if dns {
-                    vec![json!({
-                        "address": node,
-                        "connected": is_connected
-                    })]
-                } else {
+        let node = match params.get(0).and_then(|p| p.as_str()) {
+            Some(node) => Some(
+                node.parse::<SocketAddr>()
+                    .map_err(|e| RpcError::invalid_params(format!("Invalid node address: {e}")))?,
+            ),
+            None => None,
+        };
+
+        let Some(ref network) = self.network_manager else {
+            return Ok(json!([]));
+        };
+
+        let mut added: Vec<SocketAddr> = match node {
+            Some(addr) => {
+                if !network.get_persistent_peers().await.contains(&addr) {
+                    return Err(RpcError::new(
+                        RpcErrorCode::ServerError(-24),
+                        "Error: Node has not been added.",
+                    ));
+                }
+                vec![addr]
+            }
+            None => network.get_persistent_peers().await.into_iter().collect(),
+        };
+        added.sort();
+
+        let mut result = Vec::new();
+        for addr in added {
+            let Some(state) = network.persistent_peer_state(addr).await else {
+                continue;
+            };
+            let mut info = json!({
+                "addednode": addr.to_string(),
+                "connected": state.connected,
+                "addresses": if state.connected {
                     vec![json!({
                         "address": addr.to_string(),
-                        "connected": is_connected
+                        "connected": "outbound"
                     })]
-                }
-            }]))
-        } else {
-            Ok(json!([{
-                "addednode": node,
-                "connected": false,
-                "addresses": []
-            }]))
+                } else {
+                    vec![]
+                },
+                "attempts": state.attempts,
+            });
+            if !state.connected {
+                info["nextattempt"] = json!(state.next_attempt);
+            }
+            result.push(info);
         }
+        Ok(json!(result))
     }
 
     /// Get node addresses
     ///
     /// Params: ["count"] (optional, default: 1)
+    ///
+    /// Returns addresses from the address database, ones we have connected
+    /// to (tried) first.
     pub async fn getnodeaddresses(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: getnodeaddresses");
 
         let count = params.get(0).and_then(|p| p.as_u64()).unwrap_or(1).min(100) as usize; // Limit to 100
 
         if let Some(ref network) = self.network_manager {
-            // Get peer addresses
-            let peer_addrs = network.get_peer_addresses().await;
-
-            // Convert to node address format
-            let mut addresses = Vec::new();
-            for addr in peer_addrs.into_iter().take(count) {
-                match addr {
-                    crate::network::transport::TransportAddr::Tcp(sock) => {
-                        addresses.push(json!({
-                            "time": current_timestamp(),
-                            "services": "0000000000000001",
-                            "address": sock.ip().to_string(),
-                            "port": sock.port(),
-                            "network": if sock.is_ipv4() { "ipv4" } else { "ipv6" }
-                        }));
-                    }
-                    #[cfg(feature = "quinn")]
-                    crate::network::transport::TransportAddr::Quinn(sock) => {
-                        addresses.push(json!({
-                            "time": current_timestamp(),
-                            "services": "0000000000000001",
-                            "address": sock.ip().to_string(),
-                            "port": sock.port(),
-                            "network": if sock.is_ipv4() { "ipv4" } else { "ipv6" }
-                        }));
-                    }
-                    #[cfg(feature = "iroh")]
-                    crate::network::transport::TransportAddr::Iroh(_) => {
-                        // Skip Iroh peers for this method (no SocketAddr)
-                    }
-                }
-            }
+            let addresses: Vec<Value> = network
+                .node_addresses(count)
+                .await
+                .into_iter()
+                .map(|entry| {
+                    let (address, network) = address_and_network(entry.addr.ip);
+                    json!({
+                        "time": entry.last_seen,
+                        "services": format!("{:016x}", entry.services),
+                        "address": address,
+                        "port": entry.addr.port,
+                        "network": network
+                    })
+                })
+                .collect();
 
             Ok(json!(addresses))
         } else {
@@ -562,6 +654,56 @@ impl NetworkRpc {
         }
     }
 
+    /// Dump the address manager's new and tried tables
+    ///
+    /// Params: ["count"] (optional, default and maximum: 1000)
+    ///
+    /// Returns `{"new": {...}, "tried": {...}}`, each keyed by
+    /// `"bucket/position"` in slot order and holding at most `count` entries.
+    pub async fn getrawaddrman(&self, params: &Value) -> RpcResult<Value> {
+        debug!("RPC: getrawaddrman");
+
+        let count = params
+            .get(0)
+            .and_then(|p| p.as_u64())
+            .map_or(MAX_RAWADDRMAN_ENTRIES_PER_TABLE, |count| {
+                (count as usize).min(MAX_RAWADDRMAN_ENTRIES_PER_TABLE)
+            });
+
+        let Some(ref network) = self.network_manager else {
+            return Ok(json!({ "new": {}, "tried": {} }));
+        };
+        let (new, tried) = network.address_table_slots().await;
+        let table = |slots: Vec<crate::network::address_db::TableSlot>| {
+            let mut table = serde_json::Map::new();
+            for slot in slots.into_iter().take(count) {
+                let entry = slot.entry;
+                let (address, network) = address_and_network(entry.addr.ip);
+                let mut info = json!({
+                    "address": address,
+                    "port": entry.addr.port,
+                    "services": format!("{:016x}", entry.services),
+                    "network": network,
+                    "time": entry.last_seen,
+                    "last_try": entry.last_try,
+                    "attempts": entry.attempts,
+                });
+                if let Some(source) = entry.source {
+                    let source = match source {
+                        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+                        IpAddr::V6(v6) => v6,
+                    };
+                    let (source, source_network) = address_and_network(source.octets());
+                    info["source"] = json!(source);
+                    info["source_network"] = json!(source_network);
+                }
+                table.insert(format!("{}/{}", slot.bucket, slot.position), info);
+            }
+            Value::Object(table)
+        };
+        Ok(json!({ "new": table(new), "tried": table(tried) }))
+    }
+
     /// Set network active state
     ///
     /// Params: ["state"] (true to enable, false to disable)
@@ -583,6 +725,48 @@ impl NetworkRpc {
             ))
         }
     }
+
+    /// Fetch a block from a specific peer
+    ///
+    /// Params: ["blockhash", peer_id]
+    ///
+    /// `peer_id` is the `id` reported by `getpeerinfo`, or the peer's address.
+    /// The header must already be known. Returns once the block is stored.
+    pub async fn getblockfrompeer(&self, params: &Value) -> RpcResult<Value> {
+        debug!("RPC: getblockfrompeer");
+
+        let blockhash = params
+            .get(0)
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| RpcError::invalid_params("Block hash parameter required"))?;
+        let block_hash = decode_hash32(blockhash)?;
+        let peer = params
+            .get(1)
+            .ok_or_else(|| RpcError::invalid_params("Peer id parameter required"))?;
+
+        let Some(ref network) = self.network_manager else {
+            return Err(RpcError::internal_error(
+                "Network manager not available".to_string(),
+            ));
+        };
+        let peer_addr = match (peer.as_u64(), peer.as_str()) {
+            (Some(id), _) => network
+                .peer_manager()
+                .await
+                .peer_socket_addresses()
+                .into_iter()
+                .find(|addr| addr.port() as u64 == id),
+            (None, Some(address)) => address.parse::<SocketAddr>().ok(),
+            (None, None) => None,
+        }
+        .ok_or_else(|| RpcError::new(RpcErrorCode::ServerError(-1), "Peer does not exist"))?;
+
+        network
+            .fetch_block_from_peer(peer_addr, block_hash, GET_BLOCK_FROM_PEER_TIMEOUT)
+            .await
+            .map_err(|e| RpcError::new(RpcErrorCode::ServerError(-1), e.to_string()))?;
+        Ok(json!({}))
+    }
 }
 
 impl Default for NetworkRpc {
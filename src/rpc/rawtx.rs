@@ -3,29 +3,190 @@
 //! Implements raw transaction-related JSON-RPC methods:
 //! - sendrawtransaction
 //! - testmempoolaccept
+//! - submitpackage
 //! - decoderawtransaction
 //! - getrawtransaction (enhanced)
 //! - gettxout
 //! - gettxoutproof
 //! - verifytxoutproof
 
-use crate::node::mempool::MempoolManager;
+use crate::network::NetworkManager;
+use crate::node::block_processor::{serialize_transaction_with_witness, split_transaction_witness};
+use crate::node::mempool::{
+    is_unspendable, AcceptedTransaction, MempoolAcceptResult, MempoolManager,
+};
 use crate::node::metrics::MetricsCollector;
 use crate::node::performance::{OperationType, PerformanceProfiler, PerformanceTimer};
-use crate::rpc::errors::{RpcError, RpcResult};
+use crate::rpc::errors::{RpcError, RpcErrorCode, RpcResult};
+use crate::storage::hashing::double_sha256;
 use crate::storage::Storage;
+use crate::validation::witness::{calculate_wtxid, transaction_witnesses};
+use bllvm_protocol::segwit::Witness;
+use bllvm_protocol::{OutPoint, Transaction};
 use hex;
 use serde_json::{json, Value};
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 use tracing::{debug, warn};
 
+/// Default `maxfeerate` for transaction submission (BTC/kvB)
+pub const DEFAULT_MAX_RAW_TX_FEE_RATE: f64 = 0.10;
+
+/// Maximum number of transactions in a testmempoolaccept or submitpackage package
+pub const MAX_PACKAGE_COUNT: usize = 25;
+
+/// Decoded transaction as returned by `decoderawtransaction` and verbose
+/// `getrawtransaction`
+///
+/// `wire` is the full serialization (with witness, if any); `witnesses` are
+/// the per-input witness stacks. `txid` commits to the witness-stripped
+/// serialization, `hash` (the wtxid) to `wire`.
+pub(crate) fn transaction_to_json(
+    tx: &Transaction,
+    witnesses: &[Witness],
+    wire: &[u8],
+    tx_hex: String,
+) -> Value {
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::serialization::transaction::serialize_transaction;
+
+    let txid = calculate_tx_id(tx);
+    let has_witness = witnesses.iter().any(|w| !w.is_empty());
+    let hash = if has_witness {
+        double_sha256(wire)
+    } else {
+        txid
+    };
+    let base_size = serialize_transaction(tx).len();
+    let weight = base_size * 3 + wire.len();
+
+    // Pre-allocate and build vin
+    let mut vin = Vec::with_capacity(tx.inputs.len());
+    for (i, input) in tx.inputs.iter().enumerate() {
+        let mut entry = json!({
+            "txid": hex::encode(input.prevout.hash),
+            "vout": input.prevout.index,
+            "scriptSig": {
+                "asm": "",
+                "hex": hex::encode(&input.script_sig)
+            },
+            "sequence": input.sequence
+        });
+        if let Some(stack) = witnesses.get(i).filter(|w| !w.is_empty()) {
+            entry["txinwitness"] = json!(stack.iter().map(hex::encode).collect::<Vec<_>>());
+        }
+        vin.push(entry);
+    }
+
+    // Pre-allocate and build vout
+    let mut vout = Vec::with_capacity(tx.outputs.len());
+    for (i, output) in tx.outputs.iter().enumerate() {
+        vout.push(json!({
+            "value": output.value as f64 / 100_000_000.0,
+            "n": i,
+            "scriptPubKey": {
+                "asm": "",
+                "hex": hex::encode(&output.script_pubkey),
+                "reqSigs": 1,
+                "type": "pubkeyhash",
+                "addresses": []
+            }
+        }));
+    }
+
+    json!({
+        "txid": hex::encode(txid),
+        "hash": hex::encode(hash),
+        "version": tx.version,
+        "size": wire.len(),
+        "vsize": weight.div_ceil(4),
+        "weight": weight,
+        "locktime": tx.lock_time,
+        "vin": vin,
+        "vout": vout,
+        "hex": tx_hex
+    })
+}
+
+/// Parse an optional `maxfeerate` (BTC/kvB) parameter into satoshis per kvB
+///
+/// Returns `None` when the limit is disabled with 0.
+fn parse_max_fee_rate(params: &Value, index: usize) -> RpcResult<Option<u64>> {
+    let rate = match params.get(index) {
+        None | Some(Value::Null) => DEFAULT_MAX_RAW_TX_FEE_RATE,
+        Some(value) => value
+            .as_f64()
+            .filter(|rate| *rate >= 0.0)
+            .ok_or_else(|| RpcError::invalid_params("maxfeerate must be a non-negative number"))?,
+    };
+    let sats_per_kvb = (rate * 100_000_000.0).round() as u64;
+    Ok((sats_per_kvb > 0).then_some(sats_per_kvb))
+}
+
+/// Parse an optional `maxburnamount` (BTC) parameter into satoshis
+fn parse_max_burn_amount(params: &Value, index: usize) -> RpcResult<u64> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(0),
+        Some(value) => value
+            .as_f64()
+            .filter(|amount| *amount >= 0.0)
+            .map(|amount| (amount * 100_000_000.0).round() as u64)
+            .ok_or_else(|| RpcError::invalid_params("maxburnamount must be a non-negative number")),
+    }
+}
+
+/// Parse a raw transaction, in the BIP 144 witness format or not
+///
+/// Returns the witness stack of every input (empty without witness data).
+fn parse_raw_transaction(tx_bytes: &[u8]) -> RpcResult<(Transaction, Vec<Witness>)> {
+    use bllvm_protocol::serialization::transaction::deserialize_transaction;
+
+    let (base_bytes, witnesses) = split_transaction_witness(tx_bytes)
+        .map_err(|e| RpcError::invalid_params(format!("Failed to parse transaction: {e}")))?;
+    let tx = deserialize_transaction(&base_bytes)
+        .map_err(|e| RpcError::invalid_params(format!("Failed to parse transaction: {e}")))?;
+    Ok((tx, witnesses))
+}
+
+/// Reject a transaction paying more than `max_fee_rate` (satoshis per kvB)
+fn check_max_fee_rate(
+    accepted: &AcceptedTransaction,
+    max_fee_rate: Option<u64>,
+) -> Result<(), String> {
+    match max_fee_rate {
+        Some(max) if accepted.fee_rate_per_kvb() > max => Err("max-fee-exceeded".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// RPC error for a transaction the mempool did not accept
+fn accept_result_error(result: &MempoolAcceptResult) -> RpcError {
+    match result {
+        MempoolAcceptResult::AlreadyInPool { txid } => {
+            RpcError::tx_already_in_mempool(&hex::encode(txid))
+        }
+        MempoolAcceptResult::AlreadyKnownInBlock { .. } => RpcError::new(
+            RpcErrorCode::TxAlreadyInChain,
+            "Transaction already in block chain",
+        ),
+        MempoolAcceptResult::Rejected { reason, .. } if reason == "missing-inputs" => {
+            RpcError::new(
+                RpcErrorCode::TxMissingInputs,
+                "bad-txns-inputs-missingorspent",
+            )
+        }
+        other => RpcError::tx_rejected(other.reject_reason().unwrap_or_default()),
+    }
+}
+
 /// Raw Transaction RPC methods
 pub struct RawTxRpc {
     storage: Option<Arc<Storage>>,
     mempool: Option<Arc<MempoolManager>>,
     metrics: Option<Arc<MetricsCollector>>,
     profiler: Option<Arc<PerformanceProfiler>>,
+    network: Option<Arc<NetworkManager>>,
 }
 
 impl RawTxRpc {
@@ -36,6 +197,7 @@ impl RawTxRpc {
             mempool: None,
             metrics: None,
             profiler: None,
+            network: None,
         }
     }
 
@@ -51,12 +213,22 @@ impl RawTxRpc {
             mempool: Some(mempool),
             metrics,
             profiler,
+            network: None,
         }
     }
 
+    /// Set the network manager used to announce accepted transactions
+    pub fn with_network(mut self, network: Arc<NetworkManager>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
     /// Send a raw transaction to the network
     ///
-    /// Params: ["hexstring", maxfeerate (optional), maxtime (optional)]
+    /// Params: ["hexstring", maxfeerate (optional, BTC/kvB, default: 0.10),
+    /// maxburnamount (optional, BTC, default: 0)]
+    ///
+    /// A `maxfeerate` of 0 disables the fee rate guard.
     pub async fn sendrawtransaction(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: sendrawtransaction");
 
@@ -71,30 +243,27 @@ impl RawTxRpc {
 
         let tx_bytes = hex::decode(&hex_string)
             .map_err(|e| RpcError::invalid_params(format!("Invalid hex string: {e}")))?;
+        let max_fee_rate = parse_max_fee_rate(params, 1)?;
+        let max_burn_amount = parse_max_burn_amount(params, 2)?;
 
         if let (Some(ref storage), Some(ref mempool)) =
             (self.storage.as_ref(), self.mempool.as_ref())
         {
-            use bllvm_protocol::serialization::transaction::deserialize_transaction;
-            let tx = deserialize_transaction(&tx_bytes).map_err(|e| {
-                RpcError::invalid_params(format!("Failed to parse transaction: {e}"))
-            })?;
+            let (tx, witnesses) = parse_raw_transaction(&tx_bytes)?;
 
             use bllvm_protocol::block::calculate_tx_id;
             let txid = calculate_tx_id(&tx);
 
-            // Check if already in mempool
-            if mempool.get_transaction(&txid).is_some() {
-                return Err(RpcError::invalid_params("Transaction already in mempool"));
-            }
-
-            // Check if in chain
+            // Confirmed transactions are told apart from rejections (the
+            // UTXO check in `check_transaction` misses fully spent ones)
             if storage
                 .transactions()
                 .has_transaction(&txid)
                 .unwrap_or(false)
             {
-                return Err(RpcError::invalid_params("Transaction already in chain"));
+                return Err(accept_result_error(
+                    &MempoolAcceptResult::AlreadyKnownInBlock { txid },
+                ));
             }
 
             // Validate transaction using consensus layer
@@ -129,14 +298,43 @@ impl RawTxRpc {
                         RpcError::internal_error(format!("Failed to get UTXO set: {e}"))
                     })?;
 
-                    // Check if all inputs exist in UTXO set
-                    for input in &tx.inputs {
-                        if !utxo_set.contains_key(&input.prevout) {
-                            return Err(RpcError::invalid_params(format!(
-                                "Input {}:{} not found in UTXO set",
-                                hex::encode(input.prevout.hash),
-                                input.prevout.index
-                            )));
+                    // Guard against fat-fingered burns and fees
+                    if let Some(output) = tx.outputs.iter().find(|output| {
+                        is_unspendable(&output.script_pubkey)
+                            && output.value as u64 > max_burn_amount
+                    }) {
+                        return Err(RpcError::tx_rejected(format!(
+                            "Unspendable output exceeds maximum configured by user (maxburnamount): {} BTC",
+                            output.value as f64 / 100_000_000.0
+                        )));
+                    }
+
+                    let spend_height = Self::spend_height(storage)?;
+                    let is_coinbase =
+                        |outpoint: &OutPoint| storage.utxos().is_coinbase_or_unknown(outpoint);
+                    let result = mempool.check_transaction_with_witness(
+                        &tx,
+                        &witnesses,
+                        &utxo_set,
+                        &is_coinbase,
+                        spend_height,
+                    );
+                    let Some(accepted) = result.accepted() else {
+                        return Err(accept_result_error(&result));
+                    };
+                    check_max_fee_rate(accepted, max_fee_rate).map_err(RpcError::tx_rejected)?;
+
+                    // Announce to peers
+                    if let Some(ref network) = self.network {
+                        if let Err(e) = network
+                            .announce_transaction(txid, accepted.fee_rate_per_kvb())
+                            .await
+                        {
+                            warn!(
+                                "Failed to announce transaction {}: {}",
+                                hex::encode(txid),
+                                e
+                            );
                         }
                     }
 
@@ -170,80 +368,267 @@ impl RawTxRpc {
         }
     }
 
-    /// Test if a raw transaction would be accepted to the mempool
+    /// Test if raw transactions would be accepted to the mempool
     ///
-    /// Params: ["hexstring", maxfeerate (optional)]
+    /// Params: [["hexstring", ...], maxfeerate (optional, BTC/kvB, default: 0.10)]
+    ///
+    /// Multiple transactions are validated together as a package, in order:
+    /// later transactions may spend outputs of earlier ones. Nothing is added
+    /// to the mempool.
     pub async fn testmempoolaccept(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: testmempoolaccept");
 
-        let hex_string = params
-            .get(0)
-            .and_then(|p| p.as_str())
-            .ok_or_else(|| RpcError::invalid_params("Missing hexstring parameter"))?;
+        let raw_txs: Vec<&str> = match params.get(0) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .ok_or_else(|| RpcError::invalid_params("rawtxs must contain hex strings"))
+                })
+                .collect::<Result<_, _>>()?,
+            Some(Value::String(hex_string)) => vec![hex_string.as_str()],
+            _ => return Err(RpcError::invalid_params("Missing rawtxs parameter")),
+        };
+        if raw_txs.is_empty() || raw_txs.len() > MAX_PACKAGE_COUNT {
+            return Err(RpcError::invalid_params(format!(
+                "Array must contain between 1 and {MAX_PACKAGE_COUNT} transactions."
+            )));
+        }
+        let max_fee_rate = parse_max_fee_rate(params, 1)?;
 
-        // Decode hex string
-        let tx_bytes = hex::decode(hex_string)
-            .map_err(|e| RpcError::invalid_params(format!("Invalid hex string: {e}")))?;
+        let mut txs = Vec::with_capacity(raw_txs.len());
+        for hex_string in raw_txs {
+            let tx_bytes = hex::decode(hex_string)
+                .map_err(|e| RpcError::invalid_params(format!("Invalid hex string: {e}")))?;
+            txs.push(parse_raw_transaction(&tx_bytes)?);
+        }
 
-        use bllvm_protocol::serialization::transaction::deserialize_transaction;
-        let tx = deserialize_transaction(&tx_bytes)
-            .map_err(|e| RpcError::invalid_params(format!("Failed to parse transaction: {}", e)))?;
+        let (Some(storage), Some(mempool)) = (self.storage.as_ref(), self.mempool.as_ref()) else {
+            return Err(RpcError::invalid_params(
+                "RPC not initialized with dependencies",
+            ));
+        };
+        // Package view: confirmed coins plus outputs of accepted package members
+        let mut view = storage
+            .utxos()
+            .get_all_utxos()
+            .map_err(|e| RpcError::internal_error(format!("Failed to get UTXO set: {e}")))?;
+        let spend_height = Self::spend_height(storage)?;
+        let is_coinbase = |outpoint: &OutPoint| storage.utxos().is_coinbase_or_unknown(outpoint);
+        let mut package_spent = HashSet::new();
+
+        let mut results = Vec::with_capacity(txs.len());
+        for (tx, witnesses) in &txs {
+            use bllvm_protocol::block::calculate_tx_id;
+            let txid = calculate_tx_id(tx);
 
-        use bllvm_protocol::block::calculate_tx_id;
-        let txid = calculate_tx_id(&tx);
-        let txid_hex = hex::encode(txid);
+            let outcome = if tx
+                .inputs
+                .iter()
+                .any(|input| package_spent.contains(&input.prevout))
+            {
+                Err("conflict-in-package".to_string())
+            } else {
+                mempool
+                    .check_transaction_with_witness(
+                        tx,
+                        witnesses,
+                        &view,
+                        &is_coinbase,
+                        spend_height,
+                    )
+                    .into_result()
+                    .and_then(|accepted| {
+                        check_max_fee_rate(&accepted, max_fee_rate).map(|_| accepted)
+                    })
+            };
+
+            match outcome {
+                Ok(accepted) => {
+                    for input in &tx.inputs {
+                        view.remove(&input.prevout);
+                        package_spent.insert(input.prevout.clone());
+                    }
+                    for (index, output) in tx.outputs.iter().enumerate() {
+                        use bllvm_protocol::UTXO;
+                        view.insert(
+                            OutPoint {
+                                hash: txid,
+                                index: index as u64,
+                            },
+                            UTXO {
+                                value: output.value,
+                                script_pubkey: output.script_pubkey.clone(),
+                                height: crate::node::mempool::MEMPOOL_HEIGHT,
+                            },
+                        );
+                    }
+                    results.push(json!({
+                        "txid": hex::encode(accepted.txid),
+                        "wtxid": hex::encode(accepted.wtxid),
+                        "allowed": true,
+                        "vsize": accepted.vsize,
+                        "fees": {
+                            "base": accepted.fee as f64 / 100_000_000.0
+                        }
+                    }));
+                }
+                Err(reason) => {
+                    results.push(json!({
+                        "txid": hex::encode(txid),
+                        "wtxid": hex::encode(calculate_wtxid(tx, witnesses)),
+                        "allowed": false,
+                        "reject-reason": reason
+                    }));
+                }
+            }
+        }
+
+        Ok(Value::Array(results))
+    }
+
+    /// Submit a package of dependent raw transactions to the mempool
+    ///
+    /// Params: [["hexstring", ...], maxfeerate (optional, BTC/kvB, default: 0.10)]
+    ///
+    /// Transactions must be ordered parents first. The package is validated as
+    /// a unit, so a parent paying less than the minimum relay fee is accepted
+    /// when a child in the package brings the aggregate fee rate above it
+    /// (CPFP). Either every transaction is accepted or none is.
+    pub async fn submitpackage(&self, params: &Value) -> RpcResult<Value> {
+        use crate::network::package_relay::{
+            PackageError, PackageRejectReason, PackageRelay, TransactionPackage,
+        };
+        debug!("RPC: submitpackage");
+
+        let raw_txs = params
+            .get(0)
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| RpcError::invalid_params("Missing package parameter"))?;
+        if raw_txs.is_empty() || raw_txs.len() > MAX_PACKAGE_COUNT {
+            return Err(RpcError::invalid_params(format!(
+                "Array must contain between 1 and {MAX_PACKAGE_COUNT} transactions."
+            )));
+        }
+        let max_fee_rate = parse_max_fee_rate(params, 1)?;
+
+        let mut txs = Vec::with_capacity(raw_txs.len());
+        let mut witnesses = Vec::with_capacity(raw_txs.len());
+        for raw in raw_txs {
+            let hex_string = raw
+                .as_str()
+                .ok_or_else(|| RpcError::invalid_params("package must contain hex strings"))?;
+            let tx_bytes = hex::decode(hex_string)
+                .map_err(|e| RpcError::invalid_params(format!("Invalid hex string: {e}")))?;
+            let (tx, tx_witnesses) = parse_raw_transaction(&tx_bytes)?;
+            txs.push(tx);
+            witnesses.push(tx_witnesses);
+        }
 
-        // Validate transaction using consensus layer
-        use bllvm_protocol::ConsensusProof;
-        let consensus = ConsensusProof::new();
-        let validation_result = consensus.validate_transaction(&tx);
+        // Package structure and size limits (BIP 331)
+        let structure = TransactionPackage::new(txs.clone())
+            .map_err(|e| match e {
+                PackageError::InvalidOrder => PackageRejectReason::InvalidOrder,
+                _ => PackageRejectReason::InvalidStructure,
+            })
+            .and_then(|package| PackageRelay::new().validate_package(&package));
+        if let Err(reason) = structure {
+            let message = match reason {
+                PackageRejectReason::TooManyTransactions => "package-too-many-transactions",
+                PackageRejectReason::WeightExceedsLimit => "package-too-large",
+                PackageRejectReason::InvalidOrder => "package-not-sorted",
+                PackageRejectReason::DuplicateTransactions => "package-contains-duplicates",
+                _ => "package-invalid",
+            };
+            return Err(RpcError::invalid_params(format!(
+                "package topology disallowed: {message}"
+            )));
+        }
 
-        let allowed = matches!(
-            validation_result,
-            Ok(bllvm_protocol::ValidationResult::Valid)
+        let (Some(storage), Some(mempool)) = (self.storage.as_ref(), self.mempool.as_ref()) else {
+            return Err(RpcError::invalid_params(
+                "RPC not initialized with dependencies",
+            ));
+        };
+        let utxo_set = storage
+            .utxos()
+            .get_all_utxos()
+            .map_err(|e| RpcError::internal_error(format!("Failed to get UTXO set: {e}")))?;
+        let spend_height = Self::spend_height(storage)?;
+        let is_coinbase = |outpoint: &OutPoint| storage.utxos().is_coinbase_or_unknown(outpoint);
+
+        let mut acceptance = mempool.check_package_with_witnesses(
+            &txs,
+            &witnesses,
+            &utxo_set,
+            &is_coinbase,
+            spend_height,
         );
-        let reject_reason = if !allowed {
-            match validation_result {
-                Ok(bllvm_protocol::ValidationResult::Invalid(reason)) => Some(reason),
-                Err(e) => Some(format!("Validation error: {e}")),
-                _ => None,
+        if acceptance.is_accepted() {
+            for (_, result) in acceptance.results.iter_mut() {
+                if let Ok(accepted) = result {
+                    if let Err(reason) = check_max_fee_rate(accepted, max_fee_rate) {
+                        *result = Err(reason);
+                    }
+                }
             }
-        } else {
-            None
-        };
+            if acceptance.results.iter().any(|(_, result)| result.is_err()) {
+                acceptance.package_error = Some("transaction failed".to_string());
+            }
+        }
 
-        // Calculate transaction size
-        use bllvm_protocol::serialization::transaction::serialize_transaction;
-        let size = serialize_transaction(&tx).len();
-        let vsize = size; // Simplified - in real implementation would use weight/4
-
-        // Calculate fee using mempool manager if available
-        let fee = if let Some(ref mempool) = self.mempool {
-            if let Some(ref storage) = self.storage {
-                let utxo_set = storage.utxos().get_all_utxos().unwrap_or_default();
-                let fee_satoshis = mempool.calculate_transaction_fee(&tx, &utxo_set);
-                fee_satoshis as f64 / 100_000_000.0 // Convert to BTC
-            } else {
-                0.00001000 // Default if no storage
+        let mut tx_results = serde_json::Map::new();
+        for (index, (txid, result)) in acceptance.results.iter().enumerate() {
+            let entry = match result {
+                Ok(accepted) => json!({
+                    "txid": hex::encode(accepted.txid),
+                    "vsize": accepted.vsize,
+                    "fees": {
+                        "base": accepted.fee as f64 / 100_000_000.0
+                    }
+                }),
+                Err(reason) => json!({
+                    "txid": hex::encode(txid),
+                    "error": reason
+                }),
+            };
+            let wtxid = result.as_ref().map_or_else(
+                |_| calculate_wtxid(&txs[index], transaction_witnesses(&witnesses, index)),
+                |accepted| accepted.wtxid,
+            );
+            tx_results.insert(hex::encode(wtxid), entry);
+        }
+
+        if acceptance.is_accepted() {
+            // Announce with the package fee rate so parents carried by their
+            // child are not filtered out by peers' fee filters
+            if let Some(ref network) = self.network {
+                let fee_rate = acceptance.fee_rate_per_kvb();
+                for (txid, _) in &acceptance.results {
+                    if let Err(e) = network.announce_transaction(*txid, fee_rate).await {
+                        warn!(
+                            "Failed to announce transaction {}: {}",
+                            hex::encode(txid),
+                            e
+                        );
+                    }
+                }
             }
-        } else {
-            0.00001000 // Default if no mempool
-        };
+            // Note: insertion requires mutable access to the shared mempool
+            // (see MempoolManager::accept_package), as in sendrawtransaction
+            debug!("Package validated but not added to mempool (requires mutable access)");
+        }
 
-        Ok(json!([{
-            "txid": txid_hex,
-            "allowed": allowed,
-            "vsize": vsize,
-            "fees": {
-                "base": fee
-            },
-            "reject-reason": reject_reason
-        }]))
+        Ok(json!({
+            "package_msg": acceptance.package_error.as_deref().unwrap_or("success"),
+            "tx-results": tx_results,
+            "replaced-transactions": []
+        }))
     }
 
     /// Decode a raw transaction
     ///
-    /// Params: ["hexstring", iswitness (optional, default: try both)]
+    /// Params: ["hexstring", iswitness (optional, default: true)]
     pub async fn decoderawtransaction(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: decoderawtransaction");
 
@@ -259,62 +644,30 @@ impl RawTxRpc {
         let tx_bytes = hex::decode(&hex_string)
             .map_err(|e| RpcError::invalid_params(format!("Invalid hex string: {e}")))?;
 
+        // Witness data is parsed unless the caller says the hex has none
+        let iswitness = params.get(1).and_then(|p| p.as_bool()).unwrap_or(true);
+        let (base_bytes, witnesses) = if iswitness {
+            split_transaction_witness(&tx_bytes).map_err(|e| {
+                RpcError::invalid_params(format!("Failed to parse transaction: {e}"))
+            })?
+        } else {
+            (tx_bytes.clone(), Vec::new())
+        };
+
         use bllvm_protocol::serialization::transaction::deserialize_transaction;
-        let tx = deserialize_transaction(&tx_bytes)
+        let tx = deserialize_transaction(&base_bytes)
             .map_err(|e| RpcError::invalid_params(format!("Failed to parse transaction: {}", e)))?;
 
-        use bllvm_protocol::block::calculate_tx_id;
-        let txid = calculate_tx_id(&tx);
-        let txid_hex = hex::encode(txid);
-        let size = tx_bytes.len();
-
-        // Pre-allocate and build vin
-        let mut vin = Vec::with_capacity(tx.inputs.len());
-        for input in &tx.inputs {
-            vin.push(json!({
-                "txid": hex::encode(input.prevout.hash),
-                "vout": input.prevout.index,
-                "scriptSig": {
-                    "asm": "",
-                    "hex": hex::encode(&input.script_sig)
-                },
-                "sequence": input.sequence
-            }));
-        }
-
-        // Pre-allocate and build vout
-        let mut vout = Vec::with_capacity(tx.outputs.len());
-        for (i, output) in tx.outputs.iter().enumerate() {
-            vout.push(json!({
-                "value": output.value as f64 / 100_000_000.0,
-                "n": i,
-                "scriptPubKey": {
-                    "asm": "",
-                    "hex": hex::encode(&output.script_pubkey),
-                    "reqSigs": 1,
-                    "type": "pubkeyhash",
-                    "addresses": []
-                }
-            }));
-        }
-
-        Ok(json!({
-            "txid": txid_hex.clone(),
-            "hash": txid_hex,
-            "version": tx.version,
-            "size": size,
-            "vsize": size,
-            "weight": size * 4, // Simplified
-            "locktime": tx.lock_time,
-            "vin": vin,
-            "vout": vout,
-            "hex": hex_string
-        }))
+        Ok(transaction_to_json(&tx, &witnesses, &tx_bytes, hex_string))
     }
 
     /// Get raw transaction by txid
     ///
-    /// Params: ["txid", verbose (optional, default: false), blockhash (optional)]
+    /// Params: ["txid", verbose (optional, default: false), blockhash (optional),
+    ///          witness (optional, default: true)]
+    ///
+    /// `hex` is the witness serialization unless `witness` is false, in which
+    /// case it is the witness-stripped serialization the txid commits to.
     pub async fn getrawtransaction(&self, params: &Value) -> RpcResult<Value> {
         debug!("RPC: getrawtransaction");
 
@@ -324,6 +677,7 @@ impl RawTxRpc {
             .ok_or_else(|| RpcError::invalid_params("Missing txid parameter"))?;
 
         let verbose = params.get(1).and_then(|p| p.as_bool()).unwrap_or(false);
+        let include_witness = params.get(3).and_then(|p| p.as_bool()).unwrap_or(true);
 
         let txid_bytes = hex::decode(txid)
             .map_err(|e| RpcError::invalid_params(format!("Invalid txid: {e}")))?;
@@ -335,42 +689,21 @@ impl RawTxRpc {
 
         if let Some(ref storage) = self.storage {
             if let Ok(Some(tx)) = storage.transactions().get_transaction(&txid_array) {
-                use bllvm_protocol::serialization::transaction::serialize_transaction;
-                let tx_hex = hex::encode(serialize_transaction(&tx));
+                let witnesses = storage
+                    .transactions()
+                    .get_witness(&txid_array)
+                    .map_err(|e| RpcError::internal_error(format!("Failed to get witness: {e}")))?
+                    .unwrap_or_default();
+                let wire = serialize_transaction_with_witness(&tx, &witnesses);
+                let tx_hex = if include_witness {
+                    hex::encode(&wire)
+                } else {
+                    use bllvm_protocol::serialization::transaction::serialize_transaction;
+                    hex::encode(serialize_transaction(&tx))
+                };
 
                 if verbose {
-                    use bllvm_protocol::block::calculate_tx_id;
-                    let calculated_txid = calculate_tx_id(&tx);
-                    Ok(json!({
-                        "txid": hex::encode(calculated_txid),
-                        "hash": hex::encode(calculated_txid),
-                        "version": tx.version,
-                        "size": serialize_transaction(&tx).len(),
-                        "vsize": serialize_transaction(&tx).len(),
-                        "weight": serialize_transaction(&tx).len() * 4,
-                        "locktime": tx.lock_time,
-                        "vin": tx.inputs.iter().map(|input| json!({
-                            "txid": hex::encode(input.prevout.hash),
-                            "vout": input.prevout.index,
-                            "scriptSig": {
-                                "asm": "",
-                                "hex": hex::encode(&input.script_sig)
-                            },
-                            "sequence": input.sequence
-                        })).collect::<Vec<_>>(),
-                        "vout": tx.outputs.iter().enumerate().map(|(i, output)| json!({
-                            "value": output.value as f64 / 100_000_000.0,
-                            "n": i,
-                            "scriptPubKey": {
-                                "asm": "",
-                                "hex": hex::encode(&output.script_pubkey),
-                                "reqSigs": 1,
-                                "type": "pubkeyhash",
-                                "addresses": []
-                            }
-                        })).collect::<Vec<_>>(),
-                        "hex": tx_hex
-                    }))
+                    Ok(transaction_to_json(&tx, &witnesses, &wire, tx_hex))
                 } else {
                     Ok(json!(tx_hex))
                 }
@@ -397,6 +730,16 @@ impl RawTxRpc {
         }
     }
 
+    /// Height a new transaction would confirm at
+    fn spend_height(storage: &Storage) -> RpcResult<u64> {
+        let tip_height = storage
+            .chain()
+            .get_height()
+            .map_err(|e| RpcError::internal_error(format!("Failed to get height: {e}")))?
+            .unwrap_or(0);
+        Ok(tip_height + 1)
+    }
+
     /// Get transaction output information
     ///
     /// Params: ["txid", n, includemempool (optional, default: true)]
@@ -423,7 +766,6 @@ impl RawTxRpc {
         let mut txid_array = [0u8; 32];
         txid_array.copy_from_slice(&txid_bytes);
 
-        use bllvm_protocol::OutPoint;
         let outpoint = OutPoint {
             hash: txid_array,
             index: n as u64,
@@ -433,24 +775,25 @@ impl RawTxRpc {
             // Check mempool first if requested
             if include_mempool {
                 if let Some(ref mempool) = self.mempool {
-                    if let Some(tx) = mempool.get_transaction(&txid_array) {
-                        if (n as usize) < tx.outputs.len() {
-                            let output = &tx.outputs[n as usize];
-                            let best_hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
-                            return Ok(json!({
-                                "bestblock": hex::encode(best_hash),
-                                "confirmations": 0,
-                                "value": output.value as f64 / 100_000_000.0,
-                                "scriptPubKey": {
-                                    "asm": "",
-                                    "hex": hex::encode(&output.script_pubkey),
-                                    "reqSigs": 1,
-                                    "type": "pubkeyhash",
-                                    "addresses": []
-                                },
-                                "coinbase": false
-                            }));
-                        }
+                    // Spent by an unconfirmed transaction
+                    if mempool.spends_outpoint(&outpoint) {
+                        return Ok(Value::Null);
+                    }
+                    if let Some(output) = mempool.get_mempool_output(&outpoint) {
+                        let best_hash = storage.chain().get_tip_hash()?.unwrap_or([0u8; 32]);
+                        return Ok(json!({
+                            "bestblock": hex::encode(best_hash),
+                            "confirmations": 0,
+                            "value": output.value as f64 / 100_000_000.0,
+                            "scriptPubKey": {
+                                "asm": "",
+                                "hex": hex::encode(&output.script_pubkey),
+                                "reqSigs": 1,
+                                "type": "pubkeyhash",
+                                "addresses": []
+                            },
+                            "coinbase": false
+                        }));
                     }
                 }
             }
@@ -492,56 +835,13 @@ impl RawTxRpc {
                         _ => ([0u8; 32], 0), // Fallback on error/timeout
                     };
 
-                    // Find block height containing this transaction (with timeout protection)
-                    // Use blocking task with timeout to prevent hanging
-                    let tx_height = match with_storage_timeout(async {
-                        tokio::task::spawn_blocking({
-                            let storage = storage.clone();
-                            let outpoint_hash = outpoint.hash;
-                            let search_limit = tip_height.min(1000); // Limit search
-                            move || -> Result<Option<u64>, anyhow::Error> {
-                                let mut tx_height: Option<u64> = None;
-                                for h in 0..=search_limit {
-                                    if let Ok(Some(block_hash)) =
-                                        storage.blocks().get_hash_by_height(h)
-                                    {
-                                        if let Ok(Some(block)) =
-                                            storage.blocks().get_block(&block_hash)
-                                        {
-                                            for tx in &block.transactions {
-                                                use bllvm_protocol::block::calculate_tx_id;
-                                                let txid = calculate_tx_id(tx);
-                                                if txid == outpoint_hash {
-                                                    tx_height = Some(h);
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                        if tx_height.is_some() {
-                                            break;
-                                        }
-                                    }
-                                }
-                                Ok(tx_height)
-                            }
-                        })
-                        .await
-                    })
-                    .await
-                    {
-                        Ok(Ok(Ok(height))) => height,
-                        _ => None, // Fallback on error/timeout
+                    // Coins record the height of the block that created them
+                    let confirmations = if utxo.height > tip_height {
+                        0
+                    } else {
+                        tip_height - utxo.height + 1
                     };
-
-                    let confirmations = tx_height
-                        .map(|h| {
-                            if h > tip_height {
-                                0
-                            } else {
-                                (tip_height - h + 1) as i64
-                            }
-                        })
-                        .unwrap_or(0);
+                    let coinbase = storage.utxos().is_coinbase(&outpoint).unwrap_or(false);
 
                     return Ok(json!({
                         "bestblock": hex::encode(best_hash),
@@ -554,7 +854,7 @@ impl RawTxRpc {
                             "type": "pubkeyhash",
                             "addresses": []
                         },
-                        "coinbase": false
+                        "coinbase": coinbase
                     }));
                 }
                 Ok(Ok(Ok(None))) | Ok(Ok(Err(_))) | Ok(Err(_)) => {
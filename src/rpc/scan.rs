@@ -0,0 +1,236 @@
+//! Progress and cancellation for long-running scans
+//!
+//! Scanning RPCs (scanblocks, verifychain, gettxoutsetinfo, reindexchainstate)
+//! register with a shared [`ScanController`] while they run. The scan loop
+//! reports progress through its [`ScanHandle`] and stops once the handle is
+//! cancelled; `scanstatus` lists running scans and `abortscan` cancels one by
+//! id. A scan is also cancelled when the request running it times out (see
+//! [`ScanHandle::cancel_on_drop`]).
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// State of one running scan
+#[derive(Debug)]
+struct ActiveScan {
+    kind: &'static str,
+    start_time: u64,
+    /// Progress fraction (0.0 to 1.0) stored as `f64` bits
+    progress: AtomicU64,
+    current_height: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+/// Registry of running scans shared by the scanning RPCs
+#[derive(Debug, Default)]
+pub struct ScanController {
+    next_id: AtomicU64,
+    scans: Mutex<BTreeMap<u64, Arc<ActiveScan>>>,
+}
+
+impl ScanController {
+    /// Create an empty controller
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new scan of the given kind (e.g. "scanblocks")
+    ///
+    /// The scan stays listed until the returned handle is dropped.
+    pub fn start(self: &Arc<Self>, kind: &'static str) -> ScanHandle {
+        let mut scans = self.scans.lock().unwrap();
+        self.register(&mut scans, kind)
+    }
+
+    /// Register a scan unless one of the same kind is already running
+    pub fn start_exclusive(self: &Arc<Self>, kind: &'static str) -> Option<ScanHandle> {
+        let mut scans = self.scans.lock().unwrap();
+        if scans.values().any(|scan| scan.kind == kind) {
+            return None;
+        }
+        Some(self.register(&mut scans, kind))
+    }
+
+    fn register(
+        self: &Arc<Self>,
+        scans: &mut BTreeMap<u64, Arc<ActiveScan>>,
+        kind: &'static str,
+    ) -> ScanHandle {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let scan = Arc::new(ActiveScan {
+            kind,
+            start_time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            progress: AtomicU64::new(0f64.to_bits()),
+            current_height: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+        });
+        scans.insert(id, Arc::clone(&scan));
+        ScanHandle {
+            id,
+            scan,
+            controller: Arc::clone(self),
+        }
+    }
+
+    /// Whether a scan of the given kind is running
+    pub fn is_running(&self, kind: &str) -> bool {
+        self.scans
+            .lock()
+            .unwrap()
+            .values()
+            .any(|scan| scan.kind == kind)
+    }
+
+    /// Cancel the scan with the given id; false if no such scan is running
+    pub fn abort(&self, id: u64) -> bool {
+        match self.scans.lock().unwrap().get(&id) {
+            Some(scan) => {
+                scan.cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every running scan of the given kind; false if there was none
+    pub fn abort_kind(&self, kind: &str) -> bool {
+        let scans = self.scans.lock().unwrap();
+        let mut aborted = false;
+        for scan in scans.values().filter(|scan| scan.kind == kind) {
+            scan.cancelled.store(true, Ordering::SeqCst);
+            aborted = true;
+        }
+        aborted
+    }
+
+    /// Status of the first running scan of the given kind
+    pub fn status_of(&self, kind: &str) -> Option<Value> {
+        let scans = self.scans.lock().unwrap();
+        scans
+            .iter()
+            .find(|(_, scan)| scan.kind == kind)
+            .map(|(id, scan)| Self::scan_json(*id, scan))
+    }
+
+    /// Status of all running scans, oldest first
+    pub fn status(&self) -> Value {
+        let scans = self.scans.lock().unwrap();
+        Value::Array(
+            scans
+                .iter()
+                .map(|(id, scan)| Self::scan_json(*id, scan))
+                .collect(),
+        )
+    }
+
+    fn scan_json(id: u64, scan: &ActiveScan) -> Value {
+        let progress = f64::from_bits(scan.progress.load(Ordering::SeqCst));
+        json!({
+            "scan_id": id,
+            "type": scan.kind,
+            "progress": (progress * 100.0).floor() as u64,
+            "current_height": scan.current_height.load(Ordering::SeqCst),
+            "start_time": scan.start_time,
+            "aborted": scan.cancelled.load(Ordering::SeqCst),
+        })
+    }
+}
+
+/// A running scan's link to its [`ScanController`]
+///
+/// Dropping the handle removes the scan from the controller.
+#[derive(Debug)]
+pub struct ScanHandle {
+    id: u64,
+    scan: Arc<ActiveScan>,
+    controller: Arc<ScanController>,
+}
+
+impl ScanHandle {
+    /// Id used by `abortscan`
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Report progress (fraction from 0.0 to 1.0) and the height being scanned
+    pub fn update(&self, progress: f64, current_height: u64) {
+        self.scan
+            .progress
+            .store(progress.clamp(0.0, 1.0).to_bits(), Ordering::SeqCst);
+        self.scan
+            .current_height
+            .store(current_height, Ordering::SeqCst);
+    }
+
+    /// Whether the scan has been asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.scan.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Guard that cancels the scan when dropped
+    ///
+    /// Held by the request future while the scan runs on the blocking pool,
+    /// so aborting the request stops the scan at its next check instead of
+    /// leaving it running unobserved.
+    pub fn cancel_on_drop(&self) -> CancelOnDrop {
+        CancelOnDrop(Arc::clone(&self.scan))
+    }
+}
+
+/// Cancels a scan when dropped (see [`ScanHandle::cancel_on_drop`])
+#[derive(Debug)]
+pub struct CancelOnDrop(Arc<ActiveScan>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for ScanHandle {
+    fn drop(&mut self) {
+        self.controller.scans.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_registration_and_abort() {
+        let controller = Arc::new(ScanController::new());
+        let handle = controller.start("verifychain");
+        handle.update(0.5, 10);
+
+        let status = controller.status();
+        assert_eq!(status.as_array().unwrap().len(), 1);
+        assert_eq!(status[0]["progress"], 50);
+        assert_eq!(status[0]["current_height"], 10);
+        assert!(controller.is_running("verifychain"));
+        assert!(controller.start_exclusive("verifychain").is_none());
+
+        assert!(!controller.abort(handle.id() + 1));
+        assert!(controller.abort(handle.id()));
+        assert!(handle.is_cancelled());
+
+        drop(handle);
+        assert_eq!(controller.status(), json!([]));
+        assert!(!controller.abort_kind("verifychain"));
+    }
+    #[test]
+    fn test_dropped_guard_cancels_scan() {
+        let controller = Arc::new(ScanController::new());
+        let handle = controller.start("gettxoutsetinfo");
+        let guard = handle.cancel_on_drop();
+        assert!(!handle.is_cancelled());
+        drop(guard);
+        assert!(handle.is_cancelled());
+    }
+}
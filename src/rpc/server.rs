@@ -7,23 +7,46 @@ use anyhow::Result;
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
 use hyper::body::Incoming;
+use hyper::header::HeaderValue;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use serde_json::{json, Value};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::net::TcpListener;
-use tracing::{debug, error, info, warn, Span};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn, Instrument, Span};
 use uuid::Uuid;
 
-use super::{auth, blockchain, control, errors, mempool, mining, network, rawtx};
+use super::work_queue::{
+    WorkQueue, DEFAULT_LONG_RUNNING_RPC_THREADS, DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE,
+    WORK_QUEUE_FULL_MESSAGE,
+};
+use super::{
+    auth, blockchain, control, descriptor, errors, mempool, message, mining, network, rawtx,
+};
+use crate::network::subnet::Subnet;
 use crate::node::metrics::MetricsCollector;
+use crate::utils::{with_custom_timeout, DEFAULT_RPC_TIMEOUT};
 
 /// Maximum request body size (1MB)
 const MAX_REQUEST_SIZE: usize = 1_048_576;
 
+/// Methods that legitimately run long and get `long_running_rpc_timeout`
+const LONG_RUNNING_METHODS: &[&str] = &[
+    "verifychain",
+    "scantxoutset",
+    "scanblocks",
+    "reindexchainstate",
+    "rebuildutxoindex",
+];
+
+/// Default timeout for long-running methods (1 hour)
+const DEFAULT_LONG_RUNNING_RPC_TIMEOUT: Duration = Duration::from_secs(3600);
+
 /// JSON-RPC server
 #[derive(Clone)]
 pub struct RpcServer {
@@ -39,6 +62,19 @@ pub struct RpcServer {
     auth_manager: Option<Arc<auth::RpcAuthManager>>,
     // Metrics collector (optional, for Prometheus export)
     metrics: Option<Arc<MetricsCollector>>,
+    // Per-request handler timeout
+    rpc_timeout: Duration,
+    // Timeout for methods listed in LONG_RUNNING_METHODS
+    long_running_rpc_timeout: Duration,
+    // Bounds concurrently executing and waiting requests
+    work_queue: Arc<WorkQueue>,
+    // Separate pool for LONG_RUNNING_METHODS (None: they share work_queue)
+    long_running_queue: Option<Arc<WorkQueue>>,
+    // Subnets allowed to connect besides loopback (`rpcallowip`)
+    allowed_ips: Arc<Vec<Subnet>>,
+    // TLS termination (None: plaintext)
+    #[cfg(feature = "rpc-tls")]
+    tls: Option<Arc<super::tls::RpcTlsAcceptor>>,
 }
 
 impl RpcServer {
@@ -55,6 +91,16 @@ impl RpcServer {
             control: arc_new(control::ControlRpc::new()),
             auth_manager: None,
             metrics: None,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
         }
     }
 
@@ -71,6 +117,16 @@ impl RpcServer {
             control: arc_new(control::ControlRpc::new()),
             auth_manager: Some(auth_manager),
             metrics: None,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
         }
     }
 
@@ -94,6 +150,16 @@ impl RpcServer {
             control,
             auth_manager: None,
             metrics: None,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
         }
     }
 
@@ -118,6 +184,16 @@ impl RpcServer {
             control,
             auth_manager: None,
             metrics: Some(metrics),
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
         }
     }
 
@@ -142,6 +218,16 @@ impl RpcServer {
             control,
             auth_manager: Some(auth_manager),
             metrics: None,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
         }
     }
 
@@ -167,6 +253,100 @@ impl RpcServer {
             control,
             auth_manager: Some(auth_manager),
             metrics: Some(metrics),
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            long_running_rpc_timeout: DEFAULT_LONG_RUNNING_RPC_TIMEOUT,
+            work_queue: Arc::new(WorkQueue::new(DEFAULT_RPC_THREADS, DEFAULT_RPC_WORK_QUEUE)),
+            long_running_queue: Some(Arc::new(WorkQueue::new(
+                DEFAULT_LONG_RUNNING_RPC_THREADS,
+                DEFAULT_RPC_WORK_QUEUE,
+            ))),
+            allowed_ips: Arc::new(Vec::new()),
+            #[cfg(feature = "rpc-tls")]
+            tls: None,
+        }
+    }
+
+    /// Set per-request handler timeouts
+    ///
+    /// `long_running_timeout` applies to methods that legitimately take longer
+    /// (verifychain, scantxoutset, scanblocks, reindexchainstate); every other
+    /// method is bounded by `rpc_timeout`.
+    pub fn with_request_timeouts(
+        mut self,
+        rpc_timeout: Duration,
+        long_running_timeout: Duration,
+    ) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self.long_running_rpc_timeout = long_running_timeout;
+        self
+    }
+
+    /// Set the work queue (`rpcthreads`, `rpcworkqueue`)
+    ///
+    /// At most `threads` requests run at once and `depth` more may wait;
+    /// further requests get HTTP 503 "Work queue depth exceeded". Long-running
+    /// methods get their own pool of `long_running_threads` workers so they
+    /// cannot starve cheap calls, or share the main pool when it is 0.
+    pub fn with_work_queues(
+        mut self,
+        threads: usize,
+        depth: usize,
+        long_running_threads: usize,
+    ) -> Self {
+        self.work_queue = Arc::new(WorkQueue::new(threads, depth));
+        self.long_running_queue = (long_running_threads > 0)
+            .then(|| Arc::new(WorkQueue::new(long_running_threads, depth)));
+        self
+    }
+
+    /// Allow connections from these subnets (`rpcallowip`)
+    ///
+    /// Loopback is always allowed; connections from any other address not in
+    /// `subnets` are closed before TLS or authentication.
+    pub fn with_allowed_ips(mut self, subnets: Vec<Subnet>) -> Self {
+        self.allowed_ips = Arc::new(subnets);
+        self
+    }
+
+    /// Check whether `ip` may connect
+    fn is_ip_allowed(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        ip.is_loopback() || self.allowed_ips.iter().any(|subnet| subnet.contains(ip))
+    }
+
+    /// Serve RPC over TLS (see [`super::tls`])
+    ///
+    /// The fingerprint of a certificate presented by the client replaces any
+    /// `x-client-cert-fingerprint` header the client sent, so certificate auth
+    /// cannot be claimed without the certificate's key.
+    #[cfg(feature = "rpc-tls")]
+    pub fn with_tls(mut self, acceptor: Arc<super::tls::RpcTlsAcceptor>) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Queue that runs `method`
+    fn queue_for(&self, method: &str) -> &Arc<WorkQueue> {
+        match &self.long_running_queue {
+            Some(queue) if LONG_RUNNING_METHODS.contains(&method) => queue,
+            _ => &self.work_queue,
+        }
+    }
+
+    /// Publish work queue depth and rejections to the metrics collector
+    fn record_work_queue_metrics(&self) {
+        if let Some(ref metrics) = self.metrics {
+            let queues = std::iter::once(&self.work_queue).chain(&self.long_running_queue);
+            let (depth, rejected) = queues.fold((0, 0), |(depth, rejected), queue| {
+                (depth + queue.queued(), rejected + queue.rejected())
+            });
+            metrics.update_rpc(|m| {
+                m.work_queue_depth = depth;
+                m.work_queue_rejected_total = rejected;
+            });
         }
     }
 
@@ -176,7 +356,11 @@ impl RpcServer {
     pub async fn start(&self) -> Result<()> {
         let listener = TcpListener::bind(self.addr).await?;
         info!("RPC server listening on {}", self.addr);
+        self.serve(listener).await
+    }
 
+    /// Serve RPC connections accepted on an already bound listener
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
         // Wrap server in Arc to share across connections
         // Create a new server instance with cloned Arc handlers
         use crate::utils::{arc_clone, arc_new};
@@ -190,45 +374,24 @@ impl RpcServer {
             control: arc_clone(&self.control),
             auth_manager: self.auth_manager.clone(),
             metrics: self.metrics.clone(),
+            rpc_timeout: self.rpc_timeout,
+            long_running_rpc_timeout: self.long_running_rpc_timeout,
+            work_queue: arc_clone(&self.work_queue),
+            long_running_queue: self.long_running_queue.clone(),
+            allowed_ips: arc_clone(&self.allowed_ips),
+            #[cfg(feature = "rpc-tls")]
+            tls: self.tls.clone(),
         });
 
         loop {
             match listener.accept().await {
                 Ok((stream, addr)) => {
+                    if !server.is_ip_allowed(addr.ip()) {
+                        warn!("RPC connection from {} refused: not in rpcallowip", addr);
+                        continue;
+                    }
                     debug!("New RPC connection from {}", addr);
-                    let peer_addr = addr;
-                    let server = Arc::clone(&server);
-
-                    // Spawn task to handle connection
-                    // Clone values before moving into async block to ensure Send
-                    let server_for_spawn = Arc::clone(&server);
-                    let peer_addr_for_spawn = peer_addr;
-                    tokio::spawn(async move {
-                        // Use hyper for HTTP - it will handle protocol detection and parsing
-                        let io = TokioIo::new(stream);
-                        let server_clone = Arc::clone(&server_for_spawn);
-                        let peer_addr_clone = peer_addr_for_spawn;
-                        let service = service_fn({
-                            let server_for_service = Arc::clone(&server_clone);
-                            let addr_for_service = peer_addr_clone;
-                            move |req| {
-                                let server_inner = Arc::clone(&server_for_service);
-                                let addr_inner = addr_for_service;
-                                Self::handle_http_request_with_server(server_inner, req, addr_inner)
-                            }
-                        });
-
-                        // Try to serve as HTTP
-                        if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
-                            // If hyper fails, it might be raw TCP
-                            // But we can't recover here since hyper consumed the connection
-                            // For now, log and continue - raw TCP support would need separate port
-                            debug!(
-                                "HTTP connection failed from {} (might be raw TCP): {}",
-                                peer_addr_clone, e
-                            );
-                        }
-                    });
+                    tokio::spawn(Self::handle_connection(Arc::clone(&server), stream, addr));
                 }
                 Err(e) => {
                     error!("Failed to accept RPC connection: {}", e);
@@ -237,6 +400,62 @@ impl RpcServer {
         }
     }
 
+    /// Handle one accepted connection, completing the TLS handshake first if
+    /// TLS is enabled
+    async fn handle_connection(server: Arc<Self>, stream: TcpStream, peer_addr: SocketAddr) {
+        #[cfg(feature = "rpc-tls")]
+        if let Some(tls) = server.tls.clone() {
+            match tls.accept(stream).await {
+                Ok((stream, client_cert)) => {
+                    Self::serve_http(server, stream, peer_addr, Some(client_cert)).await
+                }
+                Err(e) => debug!("RPC TLS handshake failed from {}: {}", peer_addr, e),
+            }
+            return;
+        }
+        Self::serve_http(server, stream, peer_addr, None).await;
+    }
+
+    /// Serve HTTP JSON-RPC on a connection
+    ///
+    /// `tls_client_cert` is `Some` for TLS connections and holds the client
+    /// certificate fingerprint, if one was presented.
+    async fn serve_http<S>(
+        server: Arc<Self>,
+        stream: S,
+        peer_addr: SocketAddr,
+        tls_client_cert: Option<Option<String>>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        // Use hyper for HTTP - it will handle protocol detection and parsing
+        let io = TokioIo::new(stream);
+        let service = service_fn(move |mut req: Request<Incoming>| {
+            if let Some(ref client_cert) = tls_client_cert {
+                let headers = req.headers_mut();
+                headers.remove(auth::CLIENT_CERT_FINGERPRINT_HEADER);
+                if let Some(value) = client_cert
+                    .as_deref()
+                    .and_then(|fingerprint| HeaderValue::from_str(fingerprint).ok())
+                {
+                    headers.insert(auth::CLIENT_CERT_FINGERPRINT_HEADER, value);
+                }
+            }
+            Self::handle_http_request_with_server(Arc::clone(&server), req, peer_addr)
+        });
+
+        // Try to serve as HTTP
+        if let Err(e) = http1::Builder::new().serve_connection(io, service).await {
+            // If hyper fails, it might be raw TCP
+            // But we can't recover here since hyper consumed the connection
+            // For now, log and continue - raw TCP support would need separate port
+            debug!(
+                "HTTP connection failed from {} (might be raw TCP): {}",
+                peer_addr, e
+            );
+        }
+    }
+
     /// Handle HTTP request using hyper (with server instance for cached handlers)
     async fn handle_http_request_with_server(
         server: Arc<Self>,
@@ -301,18 +520,31 @@ impl RpcServer {
         let request_id = Uuid::new_v4().to_string();
         let request_id_short = request_id.chars().take(8).collect::<String>();
 
-        // Create tracing span with request context
-        let span = tracing::span!(
-            tracing::Level::DEBUG,
-            "rpc_request",
+        // Create tracing span with request context. Instrument the handler
+        // rather than entering the span, since the guard must not be held
+        // across awaits.
+        let span = tracing::info_span!(
+            "http_request",
             request_id = %request_id_short,
-            method = tracing::field::Empty,
             client_addr = %addr,
-            request_size = json_body.len()
+            request_size = json_body.len(),
+            duration_ms = tracing::field::Empty,
+            response_size = tracing::field::Empty
         );
 
-        let _guard = span.enter();
+        Self::handle_json_rpc(server, headers, addr, json_body, request_id_short)
+            .instrument(span)
+            .await
+    }
 
+    /// Authenticate, rate limit and dispatch a JSON-RPC request body
+    async fn handle_json_rpc(
+        server: Arc<Self>,
+        headers: hyper::HeaderMap,
+        addr: SocketAddr,
+        json_body: String,
+        request_id_short: String,
+    ) -> Result<Response<Full<Bytes>>, hyper::Error> {
         debug!("HTTP RPC request from {}: {} bytes", addr, json_body.len());
 
         // Extract method name for per-method rate limiting (before authentication)
@@ -325,9 +557,6 @@ impl RpcServer {
             })
             .unwrap_or_else(|| "unknown".to_string());
 
-        // Record method in span
-        Span::current().record("method", &method_name);
-
         // Authenticate request if authentication is enabled
         let auth_result = if let Some(ref auth_manager) = server.auth_manager {
             Some(auth_manager.authenticate_request(&headers, addr).await)
@@ -345,6 +574,21 @@ impl RpcServer {
 
                 // Check per-user rate limiting (for authenticated users)
                 if let Some(ref user_id) = auth_result.user_id {
+                    // Enforce token method scope before spending rate limit budget
+                    if !auth_manager
+                        .is_method_permitted(user_id, &method_name)
+                        .await
+                    {
+                        warn!(
+                            "Method '{}' not permitted for token from {}",
+                            method_name, addr
+                        );
+                        return Ok(Self::http_error_response(
+                            StatusCode::FORBIDDEN,
+                            &format!("Method '{}' not permitted for this token", method_name),
+                        ));
+                    }
+
                     if !auth_manager.check_rate_limit(user_id).await {
                         return Ok(Self::http_error_response(
                             StatusCode::TOO_MANY_REQUESTS,
@@ -371,9 +615,30 @@ impl RpcServer {
             }
         }
 
+        // Wait for a worker; refuse the request if too many are already waiting
+        let queued = server.queue_for(&method_name).enqueue();
+        server.record_work_queue_metrics();
+        let Ok(queued) = queued else {
+            warn!(
+                "RPC work queue full, refusing '{}' from {}",
+                method_name, addr
+            );
+            return Ok(Self::http_error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                WORK_QUEUE_FULL_MESSAGE,
+            ));
+        };
+
         // Process JSON-RPC request (reuse server instance with cached handlers)
         let start_time = std::time::Instant::now();
-        let response_json = Self::process_request_with_server(server, &json_body).await;
+        let response_json = queued
+            .execute(Self::process_request_with_server(
+                Arc::clone(&server),
+                &json_body,
+                &request_id_short,
+            ))
+            .await;
+        server.record_work_queue_metrics();
         let duration = start_time.elapsed();
 
         // Record response metrics in span
@@ -404,7 +669,7 @@ impl RpcServer {
     ) -> Result<Response<Full<Bytes>>, hyper::Error> {
         // Get metrics if available
         let metrics_text = if let Some(ref metrics_collector) = server.metrics {
-            Self::format_prometheus_metrics(metrics_collector.collect())
+            super::metrics_server::render_prometheus(&metrics_collector.collect(), None)
         } else {
             // Return empty metrics if collector not available
             "# No metrics available\n".to_string()
@@ -418,205 +683,6 @@ impl RpcServer {
             .expect("Failed to build metrics response"))
     }
 
-    /// Format NodeMetrics as Prometheus text format
-    fn format_prometheus_metrics(metrics: crate::node::metrics::NodeMetrics) -> String {
-        let mut output = String::new();
-
-        // Network metrics
-        output.push_str("# HELP bllvm_network_peers_total Total number of connected peers\n");
-        output.push_str("# TYPE bllvm_network_peers_total gauge\n");
-        output.push_str(&format!(
-            "bllvm_network_peers_total {}\n",
-            metrics.network.peer_count
-        ));
-
-        output.push_str("# HELP bllvm_network_bytes_sent_total Total bytes sent\n");
-        output.push_str("# TYPE bllvm_network_bytes_sent_total counter\n");
-        output.push_str(&format!(
-            "bllvm_network_bytes_sent_total {}\n",
-            metrics.network.bytes_sent
-        ));
-
-        output.push_str("# HELP bllvm_network_bytes_received_total Total bytes received\n");
-        output.push_str("# TYPE bllvm_network_bytes_received_total counter\n");
-        output.push_str(&format!(
-            "bllvm_network_bytes_received_total {}\n",
-            metrics.network.bytes_received
-        ));
-
-        output.push_str("# HELP bllvm_network_messages_sent_total Total messages sent\n");
-        output.push_str("# TYPE bllvm_network_messages_sent_total counter\n");
-        output.push_str(&format!(
-            "bllvm_network_messages_sent_total {}\n",
-            metrics.network.messages_sent
-        ));
-
-        output.push_str("# HELP bllvm_network_messages_received_total Total messages received\n");
-        output.push_str("# TYPE bllvm_network_messages_received_total counter\n");
-        output.push_str(&format!(
-            "bllvm_network_messages_received_total {}\n",
-            metrics.network.messages_received
-        ));
-
-        output.push_str("# HELP bllvm_network_active_connections Active network connections\n");
-        output.push_str("# TYPE bllvm_network_active_connections gauge\n");
-        output.push_str(&format!(
-            "bllvm_network_active_connections {}\n",
-            metrics.network.active_connections
-        ));
-
-        output.push_str("# HELP bllvm_network_banned_peers Banned peers count\n");
-        output.push_str("# TYPE bllvm_network_banned_peers gauge\n");
-        output.push_str(&format!(
-            "bllvm_network_banned_peers {}\n",
-            metrics.network.banned_peers
-        ));
-
-        // Storage metrics
-        output.push_str("# HELP bllvm_storage_blocks_total Total blocks stored\n");
-        output.push_str("# TYPE bllvm_storage_blocks_total gauge\n");
-        output.push_str(&format!(
-            "bllvm_storage_blocks_total {}\n",
-            metrics.storage.block_count
-        ));
-
-        output.push_str("# HELP bllvm_storage_utxos_total Total UTXOs\n");
-        output.push_str("# TYPE bllvm_storage_utxos_total gauge\n");
-        output.push_str(&format!(
-            "bllvm_storage_utxos_total {}\n",
-            metrics.storage.utxo_count
-        ));
-
-        output.push_str("# HELP bllvm_storage_transactions_total Total transactions indexed\n");
-        output.push_str("# TYPE bllvm_storage_transactions_total gauge\n");
-        output.push_str(&format!(
-            "bllvm_storage_transactions_total {}\n",
-            metrics.storage.transaction_count
-        ));
-
-        output.push_str("# HELP bllvm_storage_disk_size_bytes Estimated disk size in bytes\n");
-        output.push_str("# TYPE bllvm_storage_disk_size_bytes gauge\n");
-        output.push_str(&format!(
-            "bllvm_storage_disk_size_bytes {}\n",
-            metrics.storage.disk_size
-        ));
-
-        output.push_str("# HELP bllvm_storage_within_bounds Storage bounds status (1=within bounds, 0=exceeded)\n");
-        output.push_str("# TYPE bllvm_storage_within_bounds gauge\n");
-        output.push_str(&format!(
-            "bllvm_storage_within_bounds {}\n",
-            if metrics.storage.within_bounds { 1 } else { 0 }
-        ));
-
-        // RPC metrics
-        output.push_str("# HELP bllvm_rpc_requests_total Total RPC requests\n");
-        output.push_str("# TYPE bllvm_rpc_requests_total counter\n");
-        output.push_str(&format!(
-            "bllvm_rpc_requests_total {}\n",
-            metrics.rpc.requests_total
-        ));
-
-        output.push_str("# HELP bllvm_rpc_requests_success_total Successful RPC requests\n");
-        output.push_str("# TYPE bllvm_rpc_requests_success_total counter\n");
-        output.push_str(&format!(
-            "bllvm_rpc_requests_success_total {}\n",
-            metrics.rpc.requests_success
-        ));
-
-        output.push_str("# HELP bllvm_rpc_requests_failed_total Failed RPC requests\n");
-        output.push_str("# TYPE bllvm_rpc_requests_failed_total counter\n");
-        output.push_str(&format!(
-            "bllvm_rpc_requests_failed_total {}\n",
-            metrics.rpc.requests_failed
-        ));
-
-        output.push_str("# HELP bllvm_rpc_requests_per_second Current RPC requests per second\n");
-        output.push_str("# TYPE bllvm_rpc_requests_per_second gauge\n");
-        output.push_str(&format!(
-            "bllvm_rpc_requests_per_second {}\n",
-            metrics.rpc.requests_per_second
-        ));
-
-        output.push_str(
-            "# HELP bllvm_rpc_avg_response_time_ms Average RPC response time in milliseconds\n",
-        );
-        output.push_str("# TYPE bllvm_rpc_avg_response_time_ms gauge\n");
-        output.push_str(&format!(
-            "bllvm_rpc_avg_response_time_ms {}\n",
-            metrics.rpc.avg_response_time_ms
-        ));
-
-        // Performance metrics
-        output.push_str("# HELP bllvm_performance_avg_block_processing_time_ms Average block processing time in milliseconds\n");
-        output.push_str("# TYPE bllvm_performance_avg_block_processing_time_ms gauge\n");
-        output.push_str(&format!(
-            "bllvm_performance_avg_block_processing_time_ms {}\n",
-            metrics.performance.avg_block_processing_time_ms
-        ));
-
-        output.push_str("# HELP bllvm_performance_avg_tx_validation_time_ms Average transaction validation time in milliseconds\n");
-        output.push_str("# TYPE bllvm_performance_avg_tx_validation_time_ms gauge\n");
-        output.push_str(&format!(
-            "bllvm_performance_avg_tx_validation_time_ms {}\n",
-            metrics.performance.avg_tx_validation_time_ms
-        ));
-
-        output.push_str("# HELP bllvm_performance_blocks_per_second Blocks processed per second\n");
-        output.push_str("# TYPE bllvm_performance_blocks_per_second gauge\n");
-        output.push_str(&format!(
-            "bllvm_performance_blocks_per_second {}\n",
-            metrics.performance.blocks_per_second
-        ));
-
-        output.push_str(
-            "# HELP bllvm_performance_transactions_per_second Transactions processed per second\n",
-        );
-        output.push_str("# TYPE bllvm_performance_transactions_per_second gauge\n");
-        output.push_str(&format!(
-            "bllvm_performance_transactions_per_second {}\n",
-            metrics.performance.transactions_per_second
-        ));
-
-        // System metrics
-        output.push_str("# HELP bllvm_system_uptime_seconds Node uptime in seconds\n");
-        output.push_str("# TYPE bllvm_system_uptime_seconds gauge\n");
-        output.push_str(&format!(
-            "bllvm_system_uptime_seconds {}\n",
-            metrics.system.uptime_seconds
-        ));
-
-        if let Some(memory) = metrics.system.memory_usage_bytes {
-            output.push_str("# HELP bllvm_system_memory_usage_bytes Memory usage in bytes\n");
-            output.push_str("# TYPE bllvm_system_memory_usage_bytes gauge\n");
-            output.push_str(&format!("bllvm_system_memory_usage_bytes {}\n", memory));
-        }
-
-        if let Some(cpu) = metrics.system.cpu_usage_percent {
-            output.push_str("# HELP bllvm_system_cpu_usage_percent CPU usage percentage\n");
-            output.push_str("# TYPE bllvm_system_cpu_usage_percent gauge\n");
-            output.push_str(&format!("bllvm_system_cpu_usage_percent {}\n", cpu));
-        }
-
-        // DoS protection metrics
-        output.push_str(
-            "# HELP bllvm_dos_connection_rate_violations_total Connection rate violations\n",
-        );
-        output.push_str("# TYPE bllvm_dos_connection_rate_violations_total counter\n");
-        output.push_str(&format!(
-            "bllvm_dos_connection_rate_violations_total {}\n",
-            metrics.network.dos_protection.connection_rate_violations
-        ));
-
-        output.push_str("# HELP bllvm_dos_auto_bans_total Auto-bans triggered\n");
-        output.push_str("# TYPE bllvm_dos_auto_bans_total counter\n");
-        output.push_str(&format!(
-            "bllvm_dos_auto_bans_total {}\n",
-            metrics.network.dos_protection.auto_bans
-        ));
-
-        output
-    }
-
     /// Handle health check endpoints
     async fn handle_health_endpoint(
         server: Arc<Self>,
@@ -759,11 +825,19 @@ impl RpcServer {
             .expect("127.0.0.1:0 should always parse as valid SocketAddr");
         use crate::utils::arc_new;
         let server = arc_new(Self::new(addr));
-        Self::process_request_with_server(server, request).await
+        let request_id = Uuid::new_v4().to_string();
+        Self::process_request_with_server(server, request, &request_id[..8]).await
     }
 
     /// Process a JSON-RPC request with a server instance (reuses cached handlers)
-    async fn process_request_with_server(server: Arc<Self>, request: &str) -> String {
+    ///
+    /// The call runs in an `rpc_request` span carrying `method` and
+    /// `request_id`, so structured logs can be filtered per request.
+    async fn process_request_with_server(
+        server: Arc<Self>,
+        request: &str,
+        request_id: &str,
+    ) -> String {
         let request: Value = match serde_json::from_str(request) {
             Ok(req) => req,
             Err(e) => {
@@ -778,7 +852,10 @@ impl RpcServer {
         let params = request.get("params").cloned().unwrap_or_else(|| json!([]));
         let id = request.get("id");
 
-        let result = server.call_method(method, params).await;
+        let span = tracing::info_span!("rpc_request", method = %method, request_id = %request_id);
+        let result = Self::call_method_with_timeout(server, method, params)
+            .instrument(span)
+            .await;
 
         match result {
             Ok(response) => {
@@ -801,6 +878,68 @@ impl RpcServer {
         }
     }
 
+    /// Call an RPC method bounded by the configured request timeout
+    async fn call_method_with_timeout(
+        server: Arc<Self>,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, errors::RpcError> {
+        let timeout = if LONG_RUNNING_METHODS.contains(&method) {
+            server.long_running_rpc_timeout
+        } else {
+            server.rpc_timeout
+        };
+        let method_owned = method.to_string();
+        Self::bounded_call(method, timeout, async move {
+            server.call_method(&method_owned, params).await
+        })
+        .await
+    }
+
+    /// Run a handler on its own task, aborting it if it exceeds `timeout`
+    ///
+    /// Aborting drops the handler future at its next await point, which is
+    /// enough for handlers that yield as they go (`scanblocks`). Work handed
+    /// to the blocking pool is not stopped by that: `verifychain` and
+    /// `gettxoutsetinfo` are cancelled through their
+    /// [`ScanHandle`](crate::rpc::scan::ScanHandle) and stop at their next
+    /// check, while chainstate changes such as `reindexchainstate` and
+    /// `invalidateblock` run to completion after the timeout is reported, so
+    /// the chain is never left half rewritten.
+    async fn bounded_call<F>(
+        method: &str,
+        timeout: Duration,
+        handler: F,
+    ) -> Result<Value, errors::RpcError>
+    where
+        F: std::future::Future<Output = Result<Value, errors::RpcError>> + Send + 'static,
+    {
+        let mut handle = tokio::spawn(handler.in_current_span());
+        match with_custom_timeout(&mut handle, timeout).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(errors::RpcError::internal_error(format!(
+                "RPC handler for {method} failed: {e}"
+            ))),
+            Err(_) => {
+                handle.abort();
+                warn!("RPC {} timed out after {:?}", method, timeout);
+                Err(errors::RpcError::request_timeout(method, timeout))
+            }
+        }
+    }
+
+    /// Map errors from storage-backed methods
+    ///
+    /// Preserves typed RPC errors, such as "node overloaded" raised when the
+    /// circuit breaker is open or "block pruned"; anything else is reported
+    /// as an internal error.
+    fn storage_rpc_error(e: anyhow::Error) -> errors::RpcError {
+        match e.downcast::<errors::RpcError>() {
+            Ok(rpc_error) => rpc_error,
+            Err(e) => errors::RpcError::internal_error(e.to_string()),
+        }
+    }
+
     /// Call a specific RPC method
     async fn call_method(&self, method: &str, params: Value) -> Result<Value, errors::RpcError> {
         match method {
@@ -810,12 +949,32 @@ impl RpcServer {
                 .get_blockchain_info()
                 .await
                 .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "getchainstates" => self
+                .blockchain
+                .get_chain_states()
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "getsyncstatus" => self
+                .blockchain
+                .get_sync_status()
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "getdeploymentinfo" => self
+                .blockchain
+                .get_deployment_info(&params)
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
             "getblock" => {
                 let hash = params.get(0).and_then(|p| p.as_str()).unwrap_or("");
+                // Older clients pass a bool: false = 0, true = 1
+                let verbosity = params
+                    .get(1)
+                    .and_then(|p| p.as_u64().or_else(|| p.as_bool().map(u64::from)))
+                    .unwrap_or(1);
                 self.blockchain
-                    .get_block(hash)
+                    .get_block(hash, verbosity)
                     .await
-                    .map_err(|e| errors::RpcError::internal_error(e.to_string()))
+                    .map_err(Self::storage_rpc_error)
             }
             "getblockhash" => {
                 let height = params.get(0).and_then(|p| p.as_u64()).unwrap_or(0);
@@ -832,6 +991,15 @@ impl RpcServer {
                     .await
                     .map_err(|e| errors::RpcError::internal_error(e.to_string()))
             }
+            "getblockheaders" => {
+                let hash = params.get(0).and_then(|p| p.as_str()).unwrap_or("");
+                let count = params.get(1).and_then(|p| p.as_u64()).unwrap_or(2000);
+                let verbose = params.get(2).and_then(|p| p.as_bool()).unwrap_or(true);
+                self.blockchain
+                    .get_block_headers(hash, count, verbose)
+                    .await
+                    .map_err(|e| errors::RpcError::internal_error(e.to_string()))
+            }
             "getbestblockhash" => self
                 .blockchain
                 .get_best_block_hash()
@@ -849,16 +1017,16 @@ impl RpcServer {
                 .map_err(|e| errors::RpcError::internal_error(e.to_string())),
             "gettxoutsetinfo" => self
                 .blockchain
-                .get_txoutset_info()
+                .get_txoutset_info(&params)
                 .await
-                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+                .map_err(Self::storage_rpc_error),
             "verifychain" => {
                 let checklevel = params.get(0).and_then(|p| p.as_u64());
                 let numblocks = params.get(1).and_then(|p| p.as_u64());
                 self.blockchain
                     .verify_chain(checklevel, numblocks)
                     .await
-                    .map_err(|e| errors::RpcError::internal_error(e.to_string()))
+                    .map_err(Self::storage_rpc_error)
             }
             "getchaintips" => self
                 .blockchain
@@ -874,7 +1042,7 @@ impl RpcServer {
                 .blockchain
                 .get_block_stats(&params)
                 .await
-                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+                .map_err(Self::storage_rpc_error),
             "pruneblockchain" => self
                 .blockchain
                 .prune_blockchain(&params)
@@ -895,6 +1063,11 @@ impl RpcServer {
                 .reconsider_block(&params)
                 .await
                 .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "preciousblock" => self
+                .blockchain
+                .precious_block(&params)
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
             "waitfornewblock" => self
                 .blockchain
                 .wait_for_new_block(&params)
@@ -915,6 +1088,7 @@ impl RpcServer {
             "getrawtransaction" => self.rawtx.getrawtransaction(&params).await,
             "sendrawtransaction" => self.rawtx.sendrawtransaction(&params).await,
             "testmempoolaccept" => self.rawtx.testmempoolaccept(&params).await,
+            "submitpackage" => self.rawtx.submitpackage(&params).await,
             "decoderawtransaction" => self.rawtx.decoderawtransaction(&params).await,
             "gettxout" => self.rawtx.gettxout(&params).await,
             "gettxoutproof" => self.rawtx.gettxoutproof(&params).await,
@@ -922,11 +1096,15 @@ impl RpcServer {
 
             // Mempool methods
             "getmempoolinfo" => self.mempool.getmempoolinfo(&params).await,
+            "getmempoolfeehistogram" => self.mempool.getmempoolfeehistogram(&params).await,
             "getrawmempool" => self.mempool.getrawmempool(&params).await,
             "savemempool" => self.mempool.savemempool(&params).await,
+            "getorphaninfo" => self.mempool.getorphaninfo(&params).await,
+            "getorphantxs" => self.mempool.getorphantxs(&params).await,
             "getmempoolancestors" => self.mempool.getmempoolancestors(&params).await,
             "getmempooldescendants" => self.mempool.getmempooldescendants(&params).await,
             "getmempoolentry" => self.mempool.getmempoolentry(&params).await,
+            "gettxspendingprevout" => self.mempool.gettxspendingprevout(&params).await,
 
             // Network methods
             "getnetworkinfo" => self.network.get_network_info().await,
@@ -935,18 +1113,22 @@ impl RpcServer {
             "ping" => self.network.ping(&params).await,
             "addnode" => self.network.add_node(&params).await,
             "disconnectnode" => self.network.disconnect_node(&params).await,
+            "getblockfrompeer" => self.network.getblockfrompeer(&params).await,
             "getnettotals" => self.network.get_net_totals(&params).await,
             "clearbanned" => self.network.clear_banned(&params).await,
             "setban" => self.network.set_ban(&params).await,
             "listbanned" => self.network.list_banned(&params).await,
             "getaddednodeinfo" => self.network.getaddednodeinfo(&params).await,
             "getnodeaddresses" => self.network.getnodeaddresses(&params).await,
+            "getrawaddrman" => self.network.getrawaddrman(&params).await,
             "setnetworkactive" => self.network.setnetworkactive(&params).await,
 
             // Mining methods
             "getmininginfo" => self.mining.get_mining_info().await,
             "getblocktemplate" => self.mining.get_block_template(&params).await,
             "submitblock" => self.mining.submit_block(&params).await,
+            "submitheader" => self.mining.submit_header(&params).await,
+            "generatetoaddress" => self.mining.generate_to_address(&params).await,
             "estimatesmartfee" => self.mining.estimate_smart_fee(&params).await,
             "prioritisetransaction" => self.mining.prioritise_transaction(&params).await,
             "getblockfilter" => self
@@ -954,6 +1136,40 @@ impl RpcServer {
                 .get_block_filter(&params)
                 .await
                 .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "scanblocks" => self
+                .blockchain
+                .scan_blocks(&params)
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "reindexchainstate" => self
+                .blockchain
+                .reindex_chain_state()
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "rebuildutxoindex" => {
+                let start = params.get(0).and_then(|p| p.as_u64());
+                let stop = params.get(1).and_then(|p| p.as_u64());
+                match (start, stop) {
+                    (Some(start), Some(stop)) => self
+                        .blockchain
+                        .rebuild_utxo_index(start, stop)
+                        .await
+                        .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+                    _ => Err(errors::RpcError::invalid_params(
+                        "rebuildutxoindex requires start_height and stop_height",
+                    )),
+                }
+            }
+            "scanstatus" => self
+                .blockchain
+                .scan_status()
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
+            "abortscan" => self
+                .blockchain
+                .abort_scan(&params)
+                .await
+                .map_err(|e| errors::RpcError::internal_error(e.to_string())),
             "getindexinfo" => self
                 .blockchain
                 .get_index_info(&params)
@@ -969,6 +1185,11 @@ impl RpcServer {
             "logging" => self.control.logging(&params).await,
             "gethealth" => self.control.gethealth(&params).await,
             "getmetrics" => self.control.getmetrics(&params).await,
+            "getvalidationstats" => self.control.getvalidationstats(&params).await,
+
+            // Util methods
+            "getdescriptorinfo" => descriptor::get_descriptor_info(&params).await,
+            "verifymessage" => message::verify_message_rpc(&params).await,
 
             _ => Err(errors::RpcError::method_not_found(method)),
         }
@@ -1397,4 +1618,193 @@ mod tests {
             assert_eq!(response["id"], 1);
         }
     }
+
+    #[tokio::test]
+    async fn test_slow_handler_times_out_and_server_stays_responsive() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        // Stands in for a lock guard or database cursor held by the handler
+        struct Resource(Arc<AtomicBool>);
+        impl Drop for Resource {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let released = Arc::new(AtomicBool::new(false));
+        let resource = Resource(Arc::clone(&released));
+        let result = RpcServer::bounded_call("slowmethod", Duration::from_millis(50), async move {
+            let _resource = resource;
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(json!(null))
+        })
+        .await;
+
+        let err = result.unwrap_err();
+        assert_eq!(err.code, errors::RpcErrorCode::ServerError(-32002));
+
+        // The aborted handler drops its resources
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(released.load(Ordering::SeqCst));
+
+        // Subsequent requests are still served
+        let server = Arc::new(
+            RpcServer::new("127.0.0.1:0".parse().unwrap())
+                .with_request_timeouts(Duration::from_secs(5), Duration::from_secs(5)),
+        );
+        let request = r#"{"jsonrpc":"2.0","method":"getblockchaininfo","params":[],"id":1}"#;
+        let response_str = RpcServer::process_request_with_server(server, request, "test").await;
+        let response: Value = serde_json::from_str(&response_str).unwrap();
+        assert!(response["result"].is_object());
+    }
+
+    /// Records span fields and the spans enclosing each event
+    #[derive(Clone, Default)]
+    struct SpanCapture {
+        spans: Arc<std::sync::Mutex<Vec<(String, Vec<(String, String)>)>>>,
+        event_scopes: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for SpanCapture
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Vec::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.spans
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name().to_string(), fields));
+        }
+
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let scope = ctx
+                .event_scope(event)
+                .map(|scope| scope.map(|span| span.name().to_string()).collect())
+                .unwrap_or_default();
+            self.event_scopes.lock().unwrap().push(scope);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rpc_request_span_carries_method_and_request_id() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = SpanCapture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let server = Arc::new(RpcServer::new("127.0.0.1:0".parse().unwrap()));
+        let request = r#"{"jsonrpc":"2.0","method":"getpeerinfo","params":[],"id":1}"#;
+        RpcServer::process_request_with_server(server, request, "abcd1234").await;
+
+        let spans = capture.spans.lock().unwrap();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "rpc_request")
+            .expect("rpc_request span recorded");
+        assert!(fields.contains(&("method".to_string(), "getpeerinfo".to_string())));
+        assert!(fields.contains(&("request_id".to_string(), "abcd1234".to_string())));
+
+        // Handler logs run inside the request span, even on the spawned task
+        let scopes = capture.event_scopes.lock().unwrap();
+        assert!(scopes
+            .iter()
+            .any(|scope| scope.iter().any(|name| name == "rpc_request")));
+    }
+
+    #[tokio::test]
+    async fn test_full_work_queue_returns_503() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let server = Arc::new(RpcServer::new(addr).with_work_queues(1, 1, 0));
+
+        // Occupy the only worker, then fill the single queue slot
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let busy = tokio::spawn(server.work_queue.enqueue().unwrap().execute(async move {
+            started_tx.send(()).unwrap();
+            release_rx.await.ok();
+        }));
+        started_rx.await.unwrap();
+        let waiting = server.work_queue.enqueue().unwrap();
+
+        let body = json!({"jsonrpc": "2.0", "method": "getblockcount", "id": 1}).to_string();
+        let response = RpcServer::handle_json_rpc(
+            Arc::clone(&server),
+            hyper::HeaderMap::new(),
+            addr,
+            body,
+            "test".to_string(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains(WORK_QUEUE_FULL_MESSAGE));
+        assert_eq!(server.work_queue.rejected(), 1);
+
+        drop(waiting);
+        release_tx.send(()).unwrap();
+        busy.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rpcallowip_defaults_to_loopback() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let external: IpAddr = "203.0.113.5".parse().unwrap();
+
+        let server = RpcServer::new(addr);
+        assert!(server.is_ip_allowed("127.0.0.1".parse().unwrap()));
+        assert!(server.is_ip_allowed("::1".parse().unwrap()));
+        assert!(server.is_ip_allowed("::ffff:127.0.0.1".parse().unwrap()));
+        assert!(!server.is_ip_allowed(external));
+
+        let server = server.with_allowed_ips(vec!["203.0.113.0/24".parse().unwrap()]);
+        assert!(server.is_ip_allowed(external));
+        assert!(server.is_ip_allowed("::ffff:203.0.113.5".parse().unwrap()));
+        assert!(!server.is_ip_allowed("198.51.100.1".parse().unwrap()));
+        assert!(server.is_ip_allowed("127.0.0.1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_loopback_connection_allowed_by_default() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let server = RpcServer::new(server_addr);
+        let server_handle = tokio::spawn(async move { server.serve(listener).await });
+
+        let body = json!({"jsonrpc": "2.0", "method": "uptime", "params": [], "id": 1}).to_string();
+        let request = format!(
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let mut stream = TokioTcpStream::connect(server_addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+        server_handle.abort();
+    }
 }
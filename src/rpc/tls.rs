@@ -0,0 +1,163 @@
+//! TLS termination for the TCP RPC server (`rpc_tls`)
+//!
+//! When an `rpc_tls` block is configured, every RPC connection is wrapped in
+//! TLS before hyper sees it and the same JSON-RPC is spoken over the encrypted
+//! stream. Plaintext stays the default, which is fine for a loopback-only RPC
+//! port but not for one reachable over an untrusted network.
+//!
+//! Clients may present a certificate. It is not checked against a CA: the
+//! handshake only proves the client holds the matching private key, and the
+//! certificate's SHA-256 fingerprint is handed to [`super::auth`], where it
+//! must match one of the fingerprints in `RpcAuthConfig::certificates`.
+
+use crate::config::RpcTlsConfig;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::{
+    DigitallySignedStruct, DistinguishedName, ServerConfig, SignatureScheme,
+};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Fingerprint of a DER-encoded certificate as used for certificate auth
+///
+/// Lowercase hex of the SHA-256 digest, the same value `openssl x509
+/// -fingerprint -sha256` prints without the colons.
+pub fn certificate_fingerprint(der: &[u8]) -> String {
+    hex::encode(Sha256::digest(der))
+}
+
+/// Accepts TLS connections for the RPC server
+pub struct RpcTlsAcceptor {
+    acceptor: TlsAcceptor,
+}
+
+impl RpcTlsAcceptor {
+    /// Load the certificate chain and private key named in `config`
+    pub fn from_config(config: &RpcTlsConfig) -> Result<Self> {
+        let certs = load_certs(&config.cert_file)?;
+        let key = load_private_key(&config.key_file)?;
+
+        let provider = Arc::new(crypto::ring::default_provider());
+        let verifier = Arc::new(FingerprintClientVerifier {
+            mandatory: config.require_client_cert,
+            provider: Arc::clone(&provider),
+        });
+        let server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)
+            .context("Invalid RPC TLS certificate or key")?;
+
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+        })
+    }
+
+    /// Complete the TLS handshake on an accepted connection
+    ///
+    /// Returns the encrypted stream and the fingerprint of the client
+    /// certificate, if the client presented one.
+    pub async fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> Result<(TlsStream<TcpStream>, Option<String>)> {
+        let stream = self.acceptor.accept(stream).await?;
+        let fingerprint = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| certificate_fingerprint(cert.as_ref()));
+        Ok((stream, fingerprint))
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read RPC TLS certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed to read RPC TLS key {}", path.display()))?;
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .with_context(|| format!("Invalid PEM in {}", path.display()))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", path.display()))
+}
+
+/// Accepts any client certificate whose handshake signature verifies
+///
+/// Whether the certificate grants access is decided later, by fingerprint.
+#[derive(Debug)]
+struct FingerprintClientVerifier {
+    mandatory: bool,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ClientCertVerifier for FingerprintClientVerifier {
+    fn client_auth_mandatory(&self) -> bool {
+        self.mandatory
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
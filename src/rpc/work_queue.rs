@@ -0,0 +1,148 @@
+//! Bounded RPC work queue (`rpcthreads` / `rpcworkqueue`)
+//!
+//! Every connection gets its own task, so without a bound a client flooding
+//! the server could keep any number of handlers running at once. As in Bitcoin
+//! Core, at most `threads` requests execute at a time and at most `max_depth`
+//! more wait for a free worker; anything beyond that is refused straight away
+//! with "Work queue depth exceeded" (HTTP 503).
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Default number of requests executed concurrently (`rpcthreads`)
+pub const DEFAULT_RPC_THREADS: usize = 16;
+
+/// Default number of requests that may wait for a worker (`rpcworkqueue`)
+pub const DEFAULT_RPC_WORK_QUEUE: usize = 64;
+
+/// Default workers reserved for long-running methods (verifychain, scans, ...)
+pub const DEFAULT_LONG_RUNNING_RPC_THREADS: usize = 2;
+
+/// Error message returned when a queue is full
+pub const WORK_QUEUE_FULL_MESSAGE: &str = "Work queue depth exceeded";
+
+/// The queue already holds its maximum number of waiting requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkQueueFull;
+
+impl std::fmt::Display for WorkQueueFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(WORK_QUEUE_FULL_MESSAGE)
+    }
+}
+
+impl std::error::Error for WorkQueueFull {}
+
+/// Fixed pool of RPC workers with a bounded wait queue
+pub struct WorkQueue {
+    workers: Semaphore,
+    max_depth: usize,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl WorkQueue {
+    /// Create a queue running `threads` requests at once with up to
+    /// `max_depth` waiting (both at least 1)
+    pub fn new(threads: usize, max_depth: usize) -> Self {
+        Self {
+            workers: Semaphore::new(threads.max(1)),
+            max_depth: max_depth.max(1),
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Requests currently waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Requests refused because the queue was full
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Take a place in the queue, or fail if it is full
+    pub fn enqueue(self: &Arc<Self>) -> Result<QueuedRequest, WorkQueueFull> {
+        let admitted = self
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                (queued < self.max_depth).then_some(queued + 1)
+            });
+        if admitted.is_err() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(WorkQueueFull);
+        }
+        Ok(QueuedRequest {
+            queue: Arc::clone(self),
+        })
+    }
+}
+
+/// A request holding a place in a [`WorkQueue`]
+///
+/// Dropping it before [`QueuedRequest::execute`] gives the place back.
+pub struct QueuedRequest {
+    queue: Arc<WorkQueue>,
+}
+
+impl QueuedRequest {
+    /// Wait for a free worker, leave the queue and run `task` on it
+    pub async fn execute<F: Future>(self, task: F) -> F::Output {
+        let queue = Arc::clone(&self.queue);
+        let _worker = queue
+            .workers
+            .acquire()
+            .await
+            .expect("work queue semaphore is never closed");
+        drop(self);
+        task.await
+    }
+}
+
+impl Drop for QueuedRequest {
+    fn drop(&mut self) {
+        self.queue.queued.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_requests_beyond_queue_depth_are_refused() {
+        let queue = Arc::new(WorkQueue::new(1, 2));
+
+        // Occupy the only worker
+        let (started_tx, started_rx) = oneshot::channel();
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let busy = queue.enqueue().unwrap().execute(async move {
+            started_tx.send(()).unwrap();
+            release_rx.await.ok();
+        });
+        let busy = tokio::spawn(busy);
+        started_rx.await.unwrap();
+        assert_eq!(queue.queued(), 0);
+
+        // Two requests wait, the third is refused
+        let waiting: Vec<_> = (0..2)
+            .map(|i| tokio::spawn(queue.enqueue().unwrap().execute(async move { i })))
+            .collect();
+        assert_eq!(queue.queued(), 2);
+        assert_eq!(queue.enqueue().err(), Some(WorkQueueFull));
+        assert_eq!(queue.rejected(), 1);
+
+        release_tx.send(()).unwrap();
+        busy.await.unwrap();
+        for (i, handle) in waiting.into_iter().enumerate() {
+            assert_eq!(handle.await.unwrap(), i);
+        }
+        assert_eq!(queue.queued(), 0);
+        assert!(queue.enqueue().is_ok());
+    }
+}
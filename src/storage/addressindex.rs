@@ -0,0 +1,333 @@
+//! Address index (`addressindex`)
+//!
+//! Records, for every output script, the transactions that paid to it or
+//! spent a coin locked by it, so a script's history can be read without
+//! scanning the chain. Scripts are keyed by their SHA-256 (the Electrum
+//! "scripthash"). Like the coin statistics index, updates are written in the
+//! same batch as the block's UTXO changes, and the index only advances when
+//! it is at the parent of the connected block. An index that fell behind, or
+//! was damaged over some range of blocks, is brought back with
+//! `rebuildutxoindex` (see `Storage::rebuild_utxo_indexes`).
+
+use crate::storage::database::{Database, Tree, WriteBatch};
+use anyhow::Result;
+use bllvm_protocol::block::calculate_tx_id;
+use bllvm_protocol::{Block, Hash, OutPoint, UTXO};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Tree holding script histories (scripthash → `Vec<AddressHistoryEntry>`)
+pub(crate) const ADDRESS_INDEX_TREE: &str = "address_index";
+
+/// Tree holding the index tip
+pub(crate) const ADDRESS_INDEX_STATE_TREE: &str = "address_index_state";
+
+/// Key of the index tip record
+const TIP_KEY: &[u8] = b"tip";
+
+/// Scripts touched by a block: scripthash → txids
+pub type BlockTouches = BTreeMap<Hash, BTreeSet<Hash>>;
+
+/// A transaction in a script's history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct AddressHistoryEntry {
+    /// Height of the block containing the transaction
+    pub height: u64,
+    /// Transaction id
+    pub txid: Hash,
+}
+
+/// Last block the index reflects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressIndexTip {
+    pub block_hash: Hash,
+    pub height: u64,
+}
+
+/// Script history index
+pub struct AddressIndex {
+    #[allow(dead_code)]
+    db: Arc<dyn Database>,
+    histories: Arc<dyn Tree>,
+    state: Arc<dyn Tree>,
+    enabled: AtomicBool,
+}
+
+impl AddressIndex {
+    /// Create a disabled address index
+    pub fn new(db: Arc<dyn Database>) -> Result<Self> {
+        let histories = Arc::from(db.open_tree(ADDRESS_INDEX_TREE)?);
+        let state = Arc::from(db.open_tree(ADDRESS_INDEX_STATE_TREE)?);
+        Ok(Self {
+            db,
+            histories,
+            state,
+            enabled: AtomicBool::new(false),
+        })
+    }
+
+    /// Enable or disable maintaining the index on block connect and disconnect
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Whether the index is maintained
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Key a script is indexed under
+    pub fn script_hash(script_pubkey: &[u8]) -> Hash {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&Sha256::digest(script_pubkey));
+        hash
+    }
+
+    /// Transactions touching `script_pubkey`, oldest first
+    pub fn history(&self, script_pubkey: &[u8]) -> Result<Vec<AddressHistoryEntry>> {
+        self.history_by_hash(&Self::script_hash(script_pubkey))
+    }
+
+    /// Overwrite the history stored for `script_pubkey`
+    ///
+    /// Low-level access for repair tools; normal updates go through block
+    /// connect and disconnect.
+    pub fn set_history(&self, script_pubkey: &[u8], entries: &[AddressHistoryEntry]) -> Result<()> {
+        let key = Self::script_hash(script_pubkey);
+        if entries.is_empty() {
+            self.histories.remove(&key)
+        } else {
+            self.histories.insert(&key, &bincode::serialize(entries)?)
+        }
+    }
+
+    /// Last block the index reflects
+    pub fn tip(&self) -> Result<Option<AddressIndexTip>> {
+        match self.state.get(TIP_KEY)? {
+            Some(data) => Ok(Some(bincode::deserialize(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Queue the index update for a connected block
+    ///
+    /// Skipped unless the index is at the block's parent (or empty and the
+    /// block is at height 0).
+    pub fn batch_connect(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        prev_block_hash: &Hash,
+        height: u64,
+        touches: &BlockTouches,
+    ) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let extends_tip = match self.tip()? {
+            Some(tip) => tip.block_hash == *prev_block_hash,
+            None => height == 0,
+        };
+        if !extends_tip {
+            debug!(
+                "addressindex not at the parent of block {}, not updating",
+                hex::encode(block_hash)
+            );
+            return Ok(());
+        }
+        for (script_hash, txids) in touches {
+            let mut entries = self.history_by_hash(script_hash)?;
+            entries.retain(|entry| entry.height != height);
+            entries.extend(txids.iter().map(|txid| AddressHistoryEntry {
+                height,
+                txid: *txid,
+            }));
+            entries.sort_unstable();
+            self.batch_put_history(batch, script_hash, &entries)?;
+        }
+        self.batch_put_tip(
+            batch,
+            Some(&AddressIndexTip {
+                block_hash: *block_hash,
+                height,
+            }),
+        )
+    }
+
+    /// Queue the index update for a disconnected block
+    ///
+    /// Skipped unless the index is at the block.
+    pub fn batch_disconnect(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        prev_block_hash: &Hash,
+        height: u64,
+        touches: &BlockTouches,
+    ) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.tip()?.map(|tip| tip.block_hash) != Some(*block_hash) {
+            return Ok(());
+        }
+        for script_hash in touches.keys() {
+            let mut entries = self.history_by_hash(script_hash)?;
+            entries.retain(|entry| entry.height != height);
+            self.batch_put_history(batch, script_hash, &entries)?;
+        }
+        let parent = height.checked_sub(1).map(|height| AddressIndexTip {
+            block_hash: *prev_block_hash,
+            height,
+        });
+        self.batch_put_tip(batch, parent.as_ref())
+    }
+
+    /// Queue replacing every history entry in `heights` with `touches`
+    ///
+    /// `touches` holds the scripts touched by each block in the range. All
+    /// histories are scanned, so entries that should not be there at all are
+    /// dropped too. Moves the tip to `tip` if given.
+    pub fn batch_replace_range(
+        &self,
+        batch: &mut WriteBatch,
+        heights: RangeInclusive<u64>,
+        touches: &BTreeMap<u64, BlockTouches>,
+        tip: Option<&AddressIndexTip>,
+    ) -> Result<()> {
+        let mut rebuilt: HashMap<Hash, Vec<AddressHistoryEntry>> = HashMap::new();
+        for item in self.histories.iter() {
+            let (key, data) = item?;
+            let Ok(script_hash) = Hash::try_from(key.as_slice()) else {
+                continue;
+            };
+            let entries: Vec<AddressHistoryEntry> = bincode::deserialize(&data).map_err(|e| {
+                anyhow::anyhow!(
+                    "Undecodable addressindex history for {} ({}); run reindexchainstate",
+                    hex::encode(script_hash),
+                    e
+                )
+            })?;
+            if entries.iter().any(|entry| heights.contains(&entry.height)) {
+                let kept = entries
+                    .into_iter()
+                    .filter(|entry| !heights.contains(&entry.height))
+                    .collect();
+                rebuilt.insert(script_hash, kept);
+            }
+        }
+        for (height, block_touches) in touches {
+            for (script_hash, txids) in block_touches {
+                let entries = match rebuilt.entry(*script_hash) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(self.history_by_hash(script_hash)?),
+                };
+                entries.extend(txids.iter().map(|txid| AddressHistoryEntry {
+                    height: *height,
+                    txid: *txid,
+                }));
+            }
+        }
+        for (script_hash, mut entries) in rebuilt {
+            entries.sort_unstable();
+            entries.dedup();
+            self.batch_put_history(batch, &script_hash, &entries)?;
+        }
+        if let Some(tip) = tip {
+            self.batch_put_tip(batch, Some(tip))?;
+        }
+        Ok(())
+    }
+
+    /// Remove all index state
+    pub fn clear(&self) -> Result<()> {
+        self.histories.clear()?;
+        self.state.clear()
+    }
+
+    fn history_by_hash(&self, script_hash: &Hash) -> Result<Vec<AddressHistoryEntry>> {
+        match self.histories.get(script_hash)? {
+            Some(data) => Ok(bincode::deserialize(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn batch_put_history(
+        &self,
+        batch: &mut WriteBatch,
+        script_hash: &Hash,
+        entries: &[AddressHistoryEntry],
+    ) -> Result<()> {
+        if entries.is_empty() {
+            batch.remove(ADDRESS_INDEX_TREE, script_hash);
+        } else {
+            batch.insert(
+                ADDRESS_INDEX_TREE,
+                script_hash,
+                &bincode::serialize(entries)?,
+            );
+        }
+        Ok(())
+    }
+
+    fn batch_put_tip(&self, batch: &mut WriteBatch, tip: Option<&AddressIndexTip>) -> Result<()> {
+        match tip {
+            Some(tip) => batch.insert(ADDRESS_INDEX_STATE_TREE, TIP_KEY, &bincode::serialize(tip)?),
+            None => batch.remove(ADDRESS_INDEX_STATE_TREE, TIP_KEY),
+        }
+        Ok(())
+    }
+}
+
+/// Scripts a block touches and the transactions touching them
+///
+/// Every output's script is touched by the transaction creating it, and
+/// every spent coin's script by the transaction spending it. `spent` holds
+/// the coins the block spent from the UTXO set (its undo record); coins
+/// created and spent within the block are looked up in the block itself.
+pub fn block_touches(block: &Block, spent: &[(OutPoint, UTXO)]) -> BlockTouches {
+    let spent_scripts: HashMap<&OutPoint, &[u8]> = spent
+        .iter()
+        .map(|(outpoint, coin)| (outpoint, coin.script_pubkey.as_slice()))
+        .collect();
+    let mut block_outputs: HashMap<OutPoint, &[u8]> = HashMap::new();
+    let mut touches = BlockTouches::new();
+
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        let txid = calculate_tx_id(tx);
+        if tx_index > 0 {
+            for input in tx.inputs.iter() {
+                let script = spent_scripts
+                    .get(&input.prevout)
+                    .copied()
+                    .or_else(|| block_outputs.get(&input.prevout).copied());
+                if let Some(script) = script {
+                    touches
+                        .entry(AddressIndex::script_hash(script))
+                        .or_default()
+                        .insert(txid);
+                }
+            }
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            block_outputs.insert(
+                OutPoint {
+                    hash: txid,
+                    index: index as u64,
+                },
+                &output.script_pubkey,
+            );
+            touches
+                .entry(AddressIndex::script_hash(&output.script_pubkey))
+                .or_default()
+                .insert(txid);
+        }
+    }
+    touches
+}
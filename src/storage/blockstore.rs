@@ -2,12 +2,24 @@
 //!
 //! Stores blocks by hash and maintains block index by height.
 
+use crate::config::StorageCacheConfig;
+use crate::storage::cache::{CacheCounters, CacheStats, LruCache};
 use crate::storage::database::{Database, Tree};
 use anyhow::Result;
 use bllvm_protocol::segwit::Witness;
 use bllvm_protocol::{Block, BlockHeader, Hash};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+/// Approximate in-memory size of a cached header (bytes)
+const HEADER_CACHE_ENTRY_BYTES: usize = 128;
+
+/// Approximate in-memory size of a cached block (bytes)
+const BLOCK_CACHE_ENTRY_BYTES: usize = 1024 * 1024;
+
+fn cache_entries(megabytes: usize, entry_bytes: usize) -> usize {
+    megabytes * 1024 * 1024 / entry_bytes
+}
 
 /// Block metadata stored separately from block data for fast RPC lookups
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +28,28 @@ pub struct BlockMetadata {
     // Could add more metadata here: size, weight, etc.
 }
 
+/// Result of looking up a block body
+#[derive(Debug, Clone)]
+pub enum BlockAvailability {
+    /// The full block is stored
+    Available(Block),
+    /// The block was connected to the active chain but its body has since
+    /// been pruned (the header is kept)
+    Pruned,
+    /// The block is unknown, or only its header has been seen
+    NotFound,
+}
+
+impl BlockAvailability {
+    /// The block, if available
+    pub fn into_block(self) -> Option<Block> {
+        match self {
+            BlockAvailability::Available(block) => Some(block),
+            _ => None,
+        }
+    }
+}
+
 /// Block storage manager
 pub struct BlockStore {
     #[allow(dead_code)]
@@ -27,6 +61,20 @@ pub struct BlockStore {
     witnesses: Arc<dyn Tree>,
     recent_headers: Arc<dyn Tree>, // For median time-past: stores last 11+ headers by height
     block_metadata: Arc<dyn Tree>, // hash → BlockMetadata (for fast TX count lookup)
+    header_cache: Mutex<LruCache<Hash, BlockHeader>>,
+    height_cache: Mutex<LruCache<u64, Hash>>,
+    block_cache: Mutex<LruCache<Hash, Block>>,
+    header_cache_counters: CacheCounters,
+    block_cache_counters: CacheCounters,
+}
+
+/// Hit/miss statistics for the block store caches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockStoreCacheStats {
+    /// Header and height-index lookups
+    pub headers: CacheStats,
+    /// Full block lookups
+    pub blocks: CacheStats,
 }
 
 impl BlockStore {
@@ -39,6 +87,9 @@ impl BlockStore {
         let witnesses = Arc::from(db.open_tree("witnesses")?);
         let recent_headers = Arc::from(db.open_tree("recent_headers")?);
         let block_metadata = Arc::from(db.open_tree("block_metadata")?);
+        let cache_config = StorageCacheConfig::default();
+        let header_entries = cache_entries(cache_config.header_cache_mb, HEADER_CACHE_ENTRY_BYTES);
+        let block_entries = cache_entries(cache_config.block_cache_mb, BLOCK_CACHE_ENTRY_BYTES);
 
         Ok(Self {
             db,
@@ -49,9 +100,51 @@ impl BlockStore {
             witnesses,
             recent_headers,
             block_metadata,
+            header_cache: Mutex::new(LruCache::new(header_entries)),
+            height_cache: Mutex::new(LruCache::new(header_entries)),
+            block_cache: Mutex::new(LruCache::new(block_entries)),
+            header_cache_counters: CacheCounters::default(),
+            block_cache_counters: CacheCounters::default(),
         })
     }
 
+    /// Resize the in-memory caches from configuration
+    ///
+    /// A size of 0 MB disables the corresponding cache.
+    pub fn configure_cache(&self, config: &StorageCacheConfig) {
+        let header_entries = cache_entries(config.header_cache_mb, HEADER_CACHE_ENTRY_BYTES);
+        self.header_cache
+            .lock()
+            .unwrap()
+            .set_capacity(header_entries);
+        self.height_cache
+            .lock()
+            .unwrap()
+            .set_capacity(header_entries);
+        self.block_cache.lock().unwrap().set_capacity(cache_entries(
+            config.block_cache_mb,
+            BLOCK_CACHE_ENTRY_BYTES,
+        ));
+    }
+
+    /// Drop all cached headers, blocks and height mappings
+    ///
+    /// Called when active-chain blocks are disconnected, since the height
+    /// index above the new tip is about to be rewritten.
+    pub fn clear_caches(&self) {
+        self.header_cache.lock().unwrap().clear();
+        self.height_cache.lock().unwrap().clear();
+        self.block_cache.lock().unwrap().clear();
+    }
+
+    /// Cache hit/miss statistics
+    pub fn cache_stats(&self) -> BlockStoreCacheStats {
+        BlockStoreCacheStats {
+            headers: self.header_cache_counters.stats(),
+            blocks: self.block_cache_counters.stats(),
+        }
+    }
+
     /// Store a block
     pub fn store_block(&self, block: &Block) -> Result<()> {
         let block_hash = self.block_hash(block);
@@ -60,6 +153,12 @@ impl BlockStore {
         self.blocks.insert(block_hash.as_slice(), &block_data)?;
         let header_data = bincode::serialize(&block.header)?;
         self.headers.insert(block_hash.as_slice(), &header_data)?;
+        self.header_cache
+            .lock()
+            .unwrap()
+            .insert(block_hash, block.header.clone());
+        // Stale if the block is being re-stored (e.g. with different witness data)
+        self.block_cache.lock().unwrap().remove(&block_hash);
 
         // Store block metadata separately for fast RPC lookups (TX count, etc.)
         let metadata = BlockMetadata {
@@ -80,7 +179,7 @@ impl BlockStore {
     pub fn store_block_with_witness(
         &self,
         block: &Block,
-        witnesses: &[Witness],
+        witnesses: &[Vec<Witness>],
         height: u64,
     ) -> Result<()> {
         let block_hash = self.block_hash(block);
@@ -99,8 +198,23 @@ impl BlockStore {
         Ok(())
     }
 
-    /// Store witness data for a block
-    pub fn store_witness(&self, block_hash: &Hash, witness: &[Witness]) -> Result<()> {
+    /// Store a header without its block body
+    ///
+    /// Used for headers submitted ahead of their blocks; the body is stored
+    /// later with `store_block`.
+    pub fn store_header(&self, header: &BlockHeader) -> Result<Hash> {
+        let block_hash = self.header_hash(header);
+        let header_data = bincode::serialize(header)?;
+        self.headers.insert(block_hash.as_slice(), &header_data)?;
+        self.header_cache
+            .lock()
+            .unwrap()
+            .insert(block_hash, header.clone());
+        Ok(block_hash)
+    }
+
+    /// Store witness data for a block (per transaction, one stack per input)
+    pub fn store_witness(&self, block_hash: &Hash, witness: &[Vec<Witness>]) -> Result<()> {
         let witness_data = bincode::serialize(witness)?;
         self.witnesses
             .insert(block_hash.as_slice(), &witness_data)?;
@@ -108,9 +222,9 @@ impl BlockStore {
     }
 
     /// Get witness data for a block
-    pub fn get_witness(&self, block_hash: &Hash) -> Result<Option<Vec<Witness>>> {
+    pub fn get_witness(&self, block_hash: &Hash) -> Result<Option<Vec<Vec<Witness>>>> {
         if let Some(data) = self.witnesses.get(block_hash.as_slice())? {
-            let witnesses: Vec<Witness> = bincode::deserialize(&data)?;
+            let witnesses: Vec<Vec<Witness>> = bincode::deserialize(&data)?;
             Ok(Some(witnesses))
         } else {
             Ok(None)
@@ -176,8 +290,17 @@ impl BlockStore {
 
     /// Get a block by hash
     pub fn get_block(&self, hash: &Hash) -> Result<Option<Block>> {
+        if let Some(block) = self.block_cache.lock().unwrap().get(hash) {
+            self.block_cache_counters.record(true);
+            return Ok(Some(block));
+        }
+        self.block_cache_counters.record(false);
         if let Some(data) = self.blocks.get(hash.as_slice())? {
             let block: Block = bincode::deserialize(&data)?;
+            self.block_cache
+                .lock()
+                .unwrap()
+                .insert(*hash, block.clone());
             Ok(Some(block))
         } else {
             Ok(None)
@@ -186,8 +309,17 @@ impl BlockStore {
 
     /// Get a block header by hash
     pub fn get_header(&self, hash: &Hash) -> Result<Option<BlockHeader>> {
+        if let Some(header) = self.header_cache.lock().unwrap().get(hash) {
+            self.header_cache_counters.record(true);
+            return Ok(Some(header));
+        }
+        self.header_cache_counters.record(false);
         if let Some(data) = self.headers.get(hash.as_slice())? {
             let header: BlockHeader = bincode::deserialize(&data)?;
+            self.header_cache
+                .lock()
+                .unwrap()
+                .insert(*hash, header.clone());
             Ok(Some(header))
         } else {
             Ok(None)
@@ -202,15 +334,23 @@ impl BlockStore {
         self.height_index.insert(&height_bytes, hash.as_slice())?;
         // Store hash → height reverse mapping for O(1) lookup
         self.hash_to_height.insert(hash.as_slice(), &height_bytes)?;
+        // Overwrites any stale mapping left by a reorg
+        self.height_cache.lock().unwrap().insert(height, *hash);
         Ok(())
     }
 
     /// Get block hash by height
     pub fn get_hash_by_height(&self, height: u64) -> Result<Option<Hash>> {
+        if let Some(hash) = self.height_cache.lock().unwrap().get(&height) {
+            self.header_cache_counters.record(true);
+            return Ok(Some(hash));
+        }
+        self.header_cache_counters.record(false);
         let height_bytes = height.to_be_bytes();
         if let Some(data) = self.height_index.get(&height_bytes)? {
             let mut hash = [0u8; 32];
             hash.copy_from_slice(&data);
+            self.height_cache.lock().unwrap().insert(height, hash);
             Ok(Some(hash))
         } else {
             Ok(None)
@@ -254,6 +394,23 @@ impl BlockStore {
         Ok(blocks)
     }
 
+    /// Get headers in a height range (inclusive), ordered by height
+    ///
+    /// Heights without a stored header are skipped.
+    pub fn get_headers_by_height_range(&self, start: u64, end: u64) -> Result<Vec<BlockHeader>> {
+        let mut headers = Vec::new();
+
+        for height in start..=end {
+            if let Some(hash) = self.get_hash_by_height(height)? {
+                if let Some(header) = self.get_header(&hash)? {
+                    headers.push(header);
+                }
+            }
+        }
+
+        Ok(headers)
+    }
+
     /// Check if a block exists
     pub fn has_block(&self, hash: &Hash) -> Result<bool> {
         Ok(self.blocks.contains_key(hash.as_slice())?)
@@ -271,16 +428,20 @@ impl BlockStore {
     }
 
     fn block_hash(&self, block: &Block) -> Hash {
+        self.header_hash(&block.header)
+    }
+
+    fn header_hash(&self, header: &BlockHeader) -> Hash {
         use crate::storage::hashing::double_sha256;
 
         // Serialize block header for hashing
         let mut header_data = Vec::new();
-        header_data.extend_from_slice(&block.header.version.to_le_bytes());
-        header_data.extend_from_slice(&block.header.prev_block_hash);
-        header_data.extend_from_slice(&block.header.merkle_root);
-        header_data.extend_from_slice(&block.header.timestamp.to_le_bytes());
-        header_data.extend_from_slice(&block.header.bits.to_le_bytes());
-        header_data.extend_from_slice(&block.header.nonce.to_le_bytes());
+        header_data.extend_from_slice(&header.version.to_le_bytes());
+        header_data.extend_from_slice(&header.prev_block_hash);
+        header_data.extend_from_slice(&header.merkle_root);
+        header_data.extend_from_slice(&header.timestamp.to_le_bytes());
+        header_data.extend_from_slice(&header.bits.to_le_bytes());
+        header_data.extend_from_slice(&header.nonce.to_le_bytes());
 
         // Calculate Bitcoin double SHA256 hash
         double_sha256(&header_data)
@@ -289,6 +450,7 @@ impl BlockStore {
     /// Remove block body (keep header for PoW verification)
     pub fn remove_block_body(&self, hash: &Hash) -> Result<()> {
         self.blocks.remove(hash.as_slice())?;
+        self.block_cache.lock().unwrap().remove(hash);
         Ok(())
     }
 
@@ -321,4 +483,40 @@ impl BlockStore {
     pub fn has_block_body(&self, hash: &Hash) -> Result<bool> {
         Ok(self.blocks.contains_key(hash.as_slice())?)
     }
+
+    /// Look up a block body, telling pruned blocks apart from unknown ones
+    ///
+    /// Only connected blocks are indexed by height, so a block with a height
+    /// but no body has been pruned rather than not yet downloaded.
+    pub fn get_block_availability(&self, hash: &Hash) -> Result<BlockAvailability> {
+        if let Some(block) = self.get_block(hash)? {
+            return Ok(BlockAvailability::Available(block));
+        }
+        if self.get_height_by_hash(hash)?.is_some() {
+            Ok(BlockAvailability::Pruned)
+        } else {
+            Ok(BlockAvailability::NotFound)
+        }
+    }
+
+    /// Lowest height at or below `tip_height` whose block body is stored
+    ///
+    /// Pruning removes bodies from genesis upwards, so this is the first
+    /// height above the pruned range (0 when nothing has been pruned).
+    pub fn first_stored_height(&self, tip_height: u64) -> Result<u64> {
+        let (mut low, mut high) = (0, tip_height);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let stored = match self.get_hash_by_height(mid)? {
+                Some(hash) => self.has_block_body(&hash)?,
+                None => false,
+            };
+            if stored {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Ok(low)
+    }
 }
@@ -0,0 +1,185 @@
+//! In-memory caches for the storage layer
+//!
+//! Small LRU caches placed in front of the database for hot lookups (recent
+//! headers, blocks being relayed or served), with hit/miss counters for
+//! metrics.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Least-recently-used cache with a fixed entry capacity
+///
+/// A capacity of 0 disables the cache.
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    /// Monotonic access counter
+    tick: u64,
+    /// key → (value, last access tick)
+    entries: HashMap<K, (V, u64)>,
+    /// last access tick → key (oldest first)
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    /// Create a cache holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    /// Look up a value, marking it as recently used
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        *last_used = tick;
+        self.order.insert(tick, key.clone());
+        Some(value.clone())
+    }
+
+    /// Insert or replace a value, evicting the least recently used entry if full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&last_used);
+        }
+        self.order.insert(self.tick, key);
+        self.evict();
+    }
+
+    /// Remove a value
+    pub fn remove(&mut self, key: &K) {
+        if let Some((_, last_used)) = self.entries.remove(key) {
+            self.order.remove(&last_used);
+        }
+    }
+
+    /// Remove all values
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Change the capacity, evicting entries if it shrank
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Number of cached entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            match self.order.pop_first() {
+                Some((_, key)) => {
+                    self.entries.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Hit/miss counters for a cache
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    /// Record a lookup
+    pub fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current counts
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Snapshot of a cache's hit/miss counts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups served from the cache (0.0 if none yet)
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // Touch 1 so 2 becomes the oldest
+        assert_eq!(cache.get(&1), Some("a"));
+        cache.insert(3, "c");
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&3), Some("c"));
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_cache() {
+        let mut cache = LruCache::new(0);
+        cache.insert(1, "a");
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn test_hit_rate() {
+        let counters = CacheCounters::default();
+        assert_eq!(counters.stats().hit_rate(), 0.0);
+        counters.record(true);
+        counters.record(true);
+        counters.record(true);
+        counters.record(false);
+        assert_eq!(counters.stats().hit_rate(), 0.75);
+    }
+}
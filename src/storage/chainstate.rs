@@ -6,7 +6,9 @@ use crate::storage::database::{Database, Tree};
 use anyhow::Result;
 use bllvm_protocol::{BlockHeader, Hash};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::sync::Arc;
+use tokio::sync::watch;
 
 /// UTXO set statistics (cached for fast RPC lookups)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +50,15 @@ impl Default for ChainParams {
     }
 }
 
+/// Result of offering a block as the new best tip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BestChainUpdate {
+    /// The candidate became the tip, replacing `previous_tip`
+    Advanced { previous_tip: Hash },
+    /// The current tip has at least as much work, or the candidate is invalid
+    Unchanged,
+}
+
 /// Chain state storage manager
 pub struct ChainState {
     #[allow(dead_code)]
@@ -59,6 +70,10 @@ pub struct ChainState {
     network_hashrate_cache: Arc<dyn Tree>, // Network hashrate cache (for fast getmininginfo)
     invalid_blocks: Arc<dyn Tree>,
     chain_tips: Arc<dyn Tree>,
+    first_seen: Arc<dyn Tree>, // hash → sequence number (tiebreak between equal-work tips)
+    precious: Arc<dyn Tree>,   // hash → precious counter (`preciousblock`, overrides first seen)
+    /// Notifies subscribers when the chain tip changes
+    tip_notify: watch::Sender<Hash>,
 }
 
 impl ChainState {
@@ -71,8 +86,12 @@ impl ChainState {
         let network_hashrate_cache = Arc::from(db.open_tree("network_hashrate_cache")?);
         let invalid_blocks = Arc::from(db.open_tree("invalid_blocks")?);
         let chain_tips = Arc::from(db.open_tree("chain_tips")?);
+        let first_seen = Arc::from(db.open_tree("first_seen")?);
+        let precious = Arc::from(db.open_tree("precious_blocks")?);
+
+        let (tip_notify, _) = watch::channel([0u8; 32]);
 
-        Ok(Self {
+        let state = Self {
             db,
             chain_info,
             work_cache,
@@ -81,7 +100,14 @@ impl ChainState {
             network_hashrate_cache,
             invalid_blocks,
             chain_tips,
-        })
+            first_seen,
+            precious,
+            tip_notify,
+        };
+        if let Some(info) = state.load_chain_info()? {
+            state.tip_notify.send_replace(info.tip_hash);
+        }
+        Ok(state)
     }
 
     /// Initialize chain state with genesis block
@@ -102,9 +128,22 @@ impl ChainState {
     pub fn store_chain_info(&self, info: &ChainInfo) -> Result<()> {
         let data = bincode::serialize(info)?;
         self.chain_info.insert(b"current", &data)?;
+        self.tip_notify.send_if_modified(|tip| {
+            let changed = *tip != info.tip_hash;
+            *tip = info.tip_hash;
+            changed
+        });
         Ok(())
     }
 
+    /// Subscribe to chain tip changes
+    ///
+    /// The receiver holds the current tip hash and is notified whenever a
+    /// different tip is stored (block connected, reorg or initialization).
+    pub fn subscribe_tip(&self) -> watch::Receiver<Hash> {
+        self.tip_notify.subscribe()
+    }
+
     /// Load current chain information
     pub fn load_chain_info(&self) -> Result<Option<ChainInfo>> {
         if let Some(data) = self.chain_info.get(b"current")? {
@@ -145,44 +184,45 @@ impl ChainState {
     }
 
     /// Calculate work from block bits (compact target format)
-    /// Work = 2^256 / (target + 1) for Bitcoin
-    /// Simplified: work = u128::MAX / (target + 1)
-    fn calculate_work_from_bits(bits: u64) -> u64 {
-        // Expand target from compact format
-        let exponent = (bits >> 24) as u8;
-        let mantissa = bits & 0x00ffffff;
-
-        if mantissa == 0 {
+    ///
+    /// Work = 2^256 / (target + 1), as in Bitcoin Core, so cumulative
+    /// chainwork can be compared against values published for the network.
+    /// Negative, zero and overflowing targets have no work; targets so small
+    /// that the work exceeds `u128` saturate.
+    fn calculate_work_from_bits(bits: u64) -> u128 {
+        let exponent = ((bits >> 24) & 0xff) as u32;
+        let mantissa = (bits & 0x007f_ffff) as u128;
+        if mantissa == 0 || bits & 0x0080_0000 != 0 {
             return 0;
         }
-
-        // Calculate target: target = mantissa * 2^(8*(exponent-3))
-        // For simplicity, use a simplified calculation
-        // Prevent overflow by capping the shift amount
-        let target = if exponent <= 3 {
-            let shift = 8 * (3 - exponent);
-            if shift >= 64 {
-                0 // Shift would overflow, return 0
-            } else {
-                mantissa >> shift
-            }
-        } else {
-            let shift = 8 * (exponent - 3);
-            if shift >= 64 {
-                u64::MAX // Shift would overflow, return max value
+        if exponent <= 3 {
+            // Target below 2^24: work is far beyond u128
+            return if mantissa >> (8 * (3 - exponent)) == 0 {
+                0
             } else {
-                mantissa << shift
-            }
-        };
-
-        // Work = MAX_TARGET / (target + 1)
-        // Use u64::MAX as approximation for MAX_TARGET
-        if target == 0 || target == u64::MAX {
-            return 1; // Minimum work
+                u128::MAX
+            };
         }
 
-        // Prevent division by zero
-        u64::MAX / (target + 1).max(1)
+        // target = mantissa * 2^shift
+        let shift = 8 * (exponent - 3);
+        let mantissa_bits = 128 - mantissa.leading_zeros();
+        if shift + mantissa_bits > 256 {
+            return 0;
+        }
+        let numerator_bits = 256 - shift;
+        if numerator_bits >= 128 {
+            return u128::MAX;
+        }
+        // floor(2^256 / (target + 1)) is floor(2^(256 - shift) / mantissa),
+        // one less when the mantissa divides it exactly
+        let numerator = 1u128 << numerator_bits;
+        let work = numerator / mantissa;
+        if numerator % mantissa == 0 {
+            work - 1
+        } else {
+            work
+        }
     }
 
     /// Update chain tip and calculate incremental chainwork
@@ -206,17 +246,188 @@ impl ChainState {
                 0
             };
 
-            let new_chainwork = prev_chainwork + block_work as u128;
+            let new_chainwork = prev_chainwork.saturating_add(block_work);
             self.store_chainwork(tip_hash, new_chainwork)?;
 
             info.tip_hash = *tip_hash;
             info.tip_header = tip_header.clone();
             info.height = height;
             self.store_chain_info(&info)?;
+
+            // A connected block is also a known header
+            self.update_best_header_height(height)?;
+
+            // Its header may have been a headers-only tip
+            self.remove_chain_tip(tip_hash)?;
+        }
+        Ok(())
+    }
+
+    /// Offer a block as the new best tip
+    ///
+    /// The tip only advances when the candidate's cumulative chainwork is
+    /// strictly greater than the current tip's. Between equal-work tips the
+    /// one seen first wins, so every node that saw the same blocks in the same
+    /// order agrees, unless `preciousblock` chose one (see
+    /// [`ChainState::is_better_tip`]). On success the replaced tip is recorded (see
+    /// `get_previous_tip`) so the caller can reorg if the candidate does not
+    /// extend it.
+    pub fn update_best_chain(
+        &self,
+        new_tip: &Hash,
+        new_header: &BlockHeader,
+        height: u64,
+    ) -> Result<BestChainUpdate> {
+        let info = self
+            .load_chain_info()?
+            .ok_or_else(|| anyhow::anyhow!("Chain state not initialized"))?;
+        if *new_tip == info.tip_hash || self.is_invalid(new_tip)? {
+            return Ok(BestChainUpdate::Unchanged);
+        }
+
+        self.record_first_seen(new_tip)?;
+        let candidate_work = self
+            .get_chainwork(&new_header.prev_block_hash)?
+            .unwrap_or(0)
+            .saturating_add(Self::calculate_work_from_bits(new_header.bits));
+        self.store_chainwork(new_tip, candidate_work)?;
+
+        let tip_work = self.get_chainwork(&info.tip_hash)?.unwrap_or(0);
+        if !self.is_better_tip(new_tip, candidate_work, &info.tip_hash, tip_work)? {
+            return Ok(BestChainUpdate::Unchanged);
+        }
+
+        self.chain_info.insert(b"previous_tip", &info.tip_hash)?;
+        self.update_tip(new_tip, new_header, height)?;
+        Ok(BestChainUpdate::Advanced {
+            previous_tip: info.tip_hash,
+        })
+    }
+
+    /// Tip replaced by the most recent `update_best_chain` advance
+    pub fn get_previous_tip(&self) -> Result<Option<Hash>> {
+        if let Some(data) = self.chain_info.get(b"previous_tip")? {
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&data);
+            Ok(Some(hash))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Record when a block was first seen, returning its sequence number
+    fn record_first_seen(&self, hash: &Hash) -> Result<u64> {
+        if let Some(seq) = self.get_first_seen(hash)? {
+            return Ok(seq);
+        }
+        let seq = match self.chain_info.get(b"first_seen_seq")? {
+            Some(data) => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&data[..8]);
+                u64::from_be_bytes(bytes) + 1
+            }
+            None => 1,
+        };
+        self.chain_info
+            .insert(b"first_seen_seq", &seq.to_be_bytes())?;
+        self.first_seen
+            .insert(hash.as_slice(), &seq.to_be_bytes())?;
+        Ok(seq)
+    }
+
+    /// Whether `candidate` should replace `current` as the tip
+    ///
+    /// More chainwork always wins. Between equal-work blocks the one marked
+    /// precious most recently wins, then the one seen first; a block never
+    /// offered here (e.g. genesis) counts as seen first.
+    pub fn is_better_tip(
+        &self,
+        candidate: &Hash,
+        candidate_work: u128,
+        current: &Hash,
+        current_work: u128,
+    ) -> Result<bool> {
+        if candidate_work != current_work {
+            return Ok(candidate_work > current_work);
         }
+        let tiebreak = |hash: &Hash| -> Result<(Reverse<u64>, u64)> {
+            Ok((
+                Reverse(self.get_precious(hash)?.unwrap_or(0)),
+                self.get_first_seen(hash)?.unwrap_or(0),
+            ))
+        };
+        Ok(tiebreak(candidate)? < tiebreak(current)?)
+    }
+
+    /// Prefer a block over every other block of equal chainwork (`preciousblock`)
+    ///
+    /// Each call takes a higher precious counter, so the latest call wins.
+    /// Chainwork itself is unchanged.
+    pub fn mark_precious(&self, hash: &Hash) -> Result<()> {
+        let counter = match self.chain_info.get(b"precious_seq")? {
+            Some(data) => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&data[..8]);
+                u64::from_be_bytes(bytes) + 1
+            }
+            None => 1,
+        };
+        self.chain_info
+            .insert(b"precious_seq", &counter.to_be_bytes())?;
+        self.precious
+            .insert(hash.as_slice(), &counter.to_be_bytes())?;
         Ok(())
     }
 
+    /// Precious counter of a block, if it was ever marked precious
+    fn get_precious(&self, hash: &Hash) -> Result<Option<u64>> {
+        if let Some(data) = self.precious.get(hash.as_slice())? {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[..8]);
+            Ok(Some(u64::from_be_bytes(bytes)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Sequence number of when a block was first offered as a tip
+    fn get_first_seen(&self, hash: &Hash) -> Result<Option<u64>> {
+        if let Some(data) = self.first_seen.get(hash.as_slice())? {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[..8]);
+            Ok(Some(u64::from_be_bytes(bytes)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Record the height of a known (not necessarily validated) header
+    ///
+    /// Keeps the highest height seen, so headers-first sync can report how far
+    /// block validation lags behind the best known header.
+    pub fn update_best_header_height(&self, height: u64) -> Result<()> {
+        if self
+            .get_best_header_height()?
+            .is_some_and(|best| best >= height)
+        {
+            return Ok(());
+        }
+        self.chain_info
+            .insert(b"best_header_height", &height.to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Height of the best known header
+    pub fn get_best_header_height(&self) -> Result<Option<u64>> {
+        if let Some(data) = self.chain_info.get(b"best_header_height")? {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[..8]);
+            Ok(Some(u64::from_be_bytes(bytes)))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Get previous block hash from header
     fn get_prev_block_hash(&self, header: &BlockHeader) -> Result<Option<Hash>> {
         Ok(Some(header.prev_block_hash))
@@ -250,7 +461,7 @@ impl ChainState {
     }
 
     /// Store work for a block
-    pub fn store_work(&self, hash: &Hash, work: u64) -> Result<()> {
+    pub fn store_work(&self, hash: &Hash, work: u128) -> Result<()> {
         let key = hash.as_slice();
         let value = work.to_be_bytes();
         self.work_cache.insert(key, &value)?;
@@ -258,16 +469,21 @@ impl ChainState {
     }
 
     /// Get work for a block
-    pub fn get_work(&self, hash: &Hash) -> Result<Option<u64>> {
+    pub fn get_work(&self, hash: &Hash) -> Result<Option<u128>> {
         let key = hash.as_slice();
-        if let Some(data) = self.work_cache.get(key)? {
-            let work = u64::from_be_bytes([
-                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-            ]);
-            Ok(Some(work))
-        } else {
-            Ok(None)
-        }
+        Ok(self
+            .work_cache
+            .get(key)?
+            .map(|data| Self::decode_work(&data)))
+    }
+
+    /// Decode a stored block work value (16 bytes, or 8 bytes when written
+    /// by older versions)
+    fn decode_work(data: &[u8]) -> u128 {
+        let mut bytes = [0u8; 16];
+        let len = data.len().min(16);
+        bytes[16 - len..].copy_from_slice(&data[..len]);
+        u128::from_be_bytes(bytes)
     }
 
     /// Store cumulative chainwork for a block
@@ -305,15 +521,12 @@ impl ChainState {
     }
 
     /// Calculate total chain work
-    pub fn calculate_total_work(&self) -> Result<u64> {
-        let mut total = 0u64;
+    pub fn calculate_total_work(&self) -> Result<u128> {
+        let mut total = 0u128;
 
         for result in self.work_cache.iter() {
             let (_, data) = result?;
-            let work = u64::from_be_bytes([
-                data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
-            ]);
-            total += work;
+            total = total.saturating_add(Self::decode_work(&data));
         }
 
         Ok(total)
@@ -510,6 +723,8 @@ impl ChainState {
         self.network_hashrate_cache.clear()?;
         self.invalid_blocks.clear()?;
         self.chain_tips.clear()?;
+        self.first_seen.clear()?;
+        self.precious.clear()?;
         Ok(())
     }
 
@@ -580,6 +795,27 @@ impl ChainState {
         Ok(())
     }
 
+    /// Record a header received without its block as a `headers-only` tip
+    ///
+    /// Stores the header's work and cumulative chainwork, replaces its parent
+    /// as a tip and raises the best known header height.
+    pub fn add_header_tip(
+        &self,
+        hash: &Hash,
+        header: &BlockHeader,
+        height: u64,
+        branchlen: u64,
+    ) -> Result<()> {
+        let block_work = Self::calculate_work_from_bits(header.bits);
+        self.store_work(hash, block_work)?;
+        let prev_chainwork = self.get_chainwork(&header.prev_block_hash)?.unwrap_or(0);
+        self.store_chainwork(hash, prev_chainwork.saturating_add(block_work))?;
+
+        self.remove_chain_tip(&header.prev_block_hash)?;
+        self.add_chain_tip(hash, height, branchlen, "headers-only")?;
+        self.update_best_header_height(height)
+    }
+
     /// Remove a chain tip
     pub fn remove_chain_tip(&self, hash: &Hash) -> Result<()> {
         self.chain_tips.remove(hash.as_slice())?;
@@ -626,3 +862,32 @@ impl ChainState {
         double_sha256(&header_data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_work_from_bits() {
+        // Difficulty 1 (mainnet genesis) and regtest
+        assert_eq!(
+            ChainState::calculate_work_from_bits(0x1d00ffff),
+            0x100010001
+        );
+        assert_eq!(ChainState::calculate_work_from_bits(0x207fffff), 2);
+        // Mainnet block 800000
+        assert_eq!(
+            ChainState::calculate_work_from_bits(0x17053894),
+            0x31085d594cb7e26e94b5
+        );
+        // A power-of-two mantissa divides 2^256 exactly
+        assert_eq!(
+            ChainState::calculate_work_from_bits(0x1d008000),
+            0x1ffffffff
+        );
+        // Negative, zero and overflowing targets carry no work
+        assert_eq!(ChainState::calculate_work_from_bits(0x1d800001), 0);
+        assert_eq!(ChainState::calculate_work_from_bits(0x1d000000), 0);
+        assert_eq!(ChainState::calculate_work_from_bits(0x23000001), 0);
+    }
+}
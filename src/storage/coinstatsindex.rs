@@ -0,0 +1,254 @@
+//! UTXO set statistics index (`coinstatsindex`)
+//!
+//! Keeps the totals `gettxoutsetinfo` reports (output count, total amount,
+//! bogosize and the MuHash of the UTXO set) up to date as blocks connect and
+//! disconnect, so the RPC does not have to scan the UTXO set. Updates are
+//! written in the same batch as the block's UTXO changes. The index follows
+//! the UTXO set from genesis; a node that enables it later builds it with
+//! `reindexchainstate`, and until then the index is behind the tip and is
+//! not used.
+
+use crate::storage::database::{Database, Tree, WriteBatch};
+use crate::storage::muhash::MuHash3072;
+use anyhow::Result;
+use bllvm_protocol::{Hash, OutPoint, UTXO};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Tree holding the index state
+pub(crate) const COIN_STATS_TREE: &str = "coin_stats";
+
+/// Key of the index tip record
+const TIP_KEY: &[u8] = b"tip";
+
+/// A coin and whether it is a coinbase output
+pub type IndexedCoin<'a> = (&'a OutPoint, &'a UTXO, bool);
+
+/// UTXO set statistics as of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinStats {
+    /// Block the statistics reflect
+    pub block_hash: Hash,
+    /// Height of that block
+    pub height: u64,
+    /// Number of unspent outputs
+    pub txouts: u64,
+    /// Estimated database size of the UTXO set, as Bitcoin Core computes it
+    pub bogosize: u64,
+    /// Total value of unspent outputs in satoshis
+    pub total_amount: u64,
+}
+
+/// Persisted tip: the statistics and the running MuHash state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexTip {
+    stats: CoinStats,
+    muhash: Vec<u8>,
+}
+
+/// Coin statistics index
+pub struct CoinStatsIndex {
+    #[allow(dead_code)]
+    db: Arc<dyn Database>,
+    state: Arc<dyn Tree>,
+    enabled: AtomicBool,
+}
+
+impl CoinStatsIndex {
+    /// Create a disabled coin statistics index
+    pub fn new(db: Arc<dyn Database>) -> Result<Self> {
+        let state = Arc::from(db.open_tree(COIN_STATS_TREE)?);
+        Ok(Self {
+            db,
+            state,
+            enabled: AtomicBool::new(false),
+        })
+    }
+
+    /// Enable or disable maintaining the index on block connect and disconnect
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Whether the index is maintained
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Statistics and MuHash as of the index tip
+    pub fn tip(&self) -> Result<Option<(CoinStats, MuHash3072)>> {
+        let Some(data) = self.state.get(TIP_KEY)? else {
+            return Ok(None);
+        };
+        let tip: IndexTip = bincode::deserialize(&data)?;
+        let muhash = MuHash3072::from_bytes(&tip.muhash)
+            .ok_or_else(|| anyhow::anyhow!("Corrupt coinstatsindex MuHash state"))?;
+        Ok(Some((tip.stats, muhash)))
+    }
+
+    /// Statistics as of `block_hash`, if the index is enabled and at that block
+    pub fn stats_at(&self, block_hash: &Hash) -> Result<Option<(CoinStats, MuHash3072)>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        Ok(self
+            .tip()?
+            .filter(|(stats, _)| stats.block_hash == *block_hash))
+    }
+
+    /// Queue the index update for a connected block
+    ///
+    /// Skipped unless the index is at the block's parent (or empty and the
+    /// block is at height 0), so an index that fell behind stays behind
+    /// rather than drifting from the UTXO set.
+    pub fn batch_connect(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        prev_block_hash: &Hash,
+        height: u64,
+        spent: &[IndexedCoin],
+        created: &[IndexedCoin],
+    ) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let (mut stats, mut muhash) = match self.tip()? {
+            Some((stats, muhash)) if stats.block_hash == *prev_block_hash => (stats, muhash),
+            None if height == 0 => (
+                CoinStats {
+                    block_hash: [0u8; 32],
+                    height: 0,
+                    txouts: 0,
+                    bogosize: 0,
+                    total_amount: 0,
+                },
+                MuHash3072::new(),
+            ),
+            _ => {
+                debug!(
+                    "coinstatsindex not at the parent of block {}, not updating",
+                    hex::encode(block_hash)
+                );
+                return Ok(());
+            }
+        };
+
+        for coin in spent {
+            remove_coin(&mut stats, &mut muhash, coin);
+        }
+        for coin in created {
+            add_coin(&mut stats, &mut muhash, coin);
+        }
+        stats.block_hash = *block_hash;
+        stats.height = height;
+        self.batch_put_tip(batch, &stats, &muhash)
+    }
+
+    /// Queue the index update for a disconnected block
+    ///
+    /// `spent` and `created` are the coins the block spent and created, as
+    /// recorded in its undo data. Skipped unless the index is at the block.
+    pub fn batch_disconnect(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        prev_block_hash: &Hash,
+        spent: &[IndexedCoin],
+        created: &[IndexedCoin],
+    ) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let Some((mut stats, mut muhash)) = self.tip()? else {
+            return Ok(());
+        };
+        if stats.block_hash != *block_hash {
+            return Ok(());
+        }
+        if stats.height == 0 {
+            batch.remove(COIN_STATS_TREE, TIP_KEY);
+            return Ok(());
+        }
+
+        for coin in created {
+            remove_coin(&mut stats, &mut muhash, coin);
+        }
+        for coin in spent {
+            add_coin(&mut stats, &mut muhash, coin);
+        }
+        stats.block_hash = *prev_block_hash;
+        stats.height -= 1;
+        self.batch_put_tip(batch, &stats, &muhash)
+    }
+
+    fn batch_put_tip(
+        &self,
+        batch: &mut WriteBatch,
+        stats: &CoinStats,
+        muhash: &MuHash3072,
+    ) -> Result<()> {
+        let tip = IndexTip {
+            stats: *stats,
+            muhash: muhash.to_bytes(),
+        };
+        batch.insert(COIN_STATS_TREE, TIP_KEY, &bincode::serialize(&tip)?);
+        Ok(())
+    }
+
+    /// Remove all index state
+    pub fn clear(&self) -> Result<()> {
+        self.state.clear()
+    }
+}
+
+fn add_coin(stats: &mut CoinStats, muhash: &mut MuHash3072, coin: &IndexedCoin) {
+    let (outpoint, utxo, coinbase) = *coin;
+    muhash.insert(&serialize_coin(outpoint, utxo, coinbase));
+    stats.txouts += 1;
+    stats.total_amount = stats.total_amount.saturating_add(utxo.value as u64);
+    stats.bogosize += coin_bogosize(utxo);
+}
+
+fn remove_coin(stats: &mut CoinStats, muhash: &mut MuHash3072, coin: &IndexedCoin) {
+    let (outpoint, utxo, coinbase) = *coin;
+    muhash.remove(&serialize_coin(outpoint, utxo, coinbase));
+    stats.txouts = stats.txouts.saturating_sub(1);
+    stats.total_amount = stats.total_amount.saturating_sub(utxo.value as u64);
+    stats.bogosize = stats.bogosize.saturating_sub(coin_bogosize(utxo));
+}
+
+/// Bitcoin Core's per-coin size estimate: outpoint, height, value and script
+fn coin_bogosize(utxo: &UTXO) -> u64 {
+    32 + 4 + 4 + 8 + 2 + utxo.script_pubkey.len() as u64
+}
+
+/// Serialize a coin as it is hashed into the MuHash
+///
+/// Outpoint (txid, u32 index), `height * 2 + coinbase` as u32, then the
+/// output (i64 value, compact-size script length, script).
+pub fn serialize_coin(outpoint: &OutPoint, utxo: &UTXO, coinbase: bool) -> Vec<u8> {
+    let script = &utxo.script_pubkey;
+    let mut data = Vec::with_capacity(32 + 4 + 4 + 8 + 9 + script.len());
+    data.extend_from_slice(&outpoint.hash);
+    data.extend_from_slice(&(outpoint.index as u32).to_le_bytes());
+    data.extend_from_slice(&(((utxo.height as u32) << 1) | coinbase as u32).to_le_bytes());
+    data.extend_from_slice(&(utxo.value as i64).to_le_bytes());
+    let len = script.len() as u64;
+    if len < 0xfd {
+        data.push(len as u8);
+    } else if len <= 0xffff {
+        data.push(0xfd);
+        data.extend_from_slice(&(len as u16).to_le_bytes());
+    } else if len <= 0xffff_ffff {
+        data.push(0xfe);
+        data.extend_from_slice(&(len as u32).to_le_bytes());
+    } else {
+        data.push(0xff);
+        data.extend_from_slice(&len.to_le_bytes());
+    }
+    data.extend_from_slice(script);
+    data
+}
@@ -16,6 +16,93 @@ pub trait Database: Send + Sync {
 
     /// Flush all pending writes
     fn flush(&self) -> Result<()>;
+
+    /// Apply a batch of writes across trees
+    ///
+    /// Backends apply the batch atomically. The default implementation applies
+    /// the writes one at a time and is only suitable for wrappers and tests.
+    fn write_batch(&self, batch: WriteBatch) -> Result<()> {
+        for op in batch.ops {
+            match op {
+                BatchOp::Insert { tree, key, value } => {
+                    self.open_tree(tree)?.insert(&key, &value)?
+                }
+                BatchOp::Remove { tree, key } => self.open_tree(tree)?.remove(&key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single write in a `WriteBatch`
+#[derive(Debug, Clone)]
+pub enum BatchOp {
+    Insert {
+        tree: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Remove {
+        tree: &'static str,
+        key: Vec<u8>,
+    },
+}
+
+impl BatchOp {
+    /// Name of the tree this write targets
+    pub fn tree(&self) -> &'static str {
+        match self {
+            BatchOp::Insert { tree, .. } | BatchOp::Remove { tree, .. } => tree,
+        }
+    }
+}
+
+/// Writes to one or more trees, applied together by `Database::write_batch`
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+    /// Queue an insert
+    pub fn insert(&mut self, tree: &'static str, key: &[u8], value: &[u8]) {
+        self.ops.push(BatchOp::Insert {
+            tree,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        });
+    }
+
+    /// Queue a removal
+    pub fn remove(&mut self, tree: &'static str, key: &[u8]) {
+        self.ops.push(BatchOp::Remove {
+            tree,
+            key: key.to_vec(),
+        });
+    }
+
+    /// Queued writes, in order
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+
+    /// Distinct tree names touched by the batch, sorted
+    pub fn trees(&self) -> Vec<&'static str> {
+        let mut trees: Vec<&'static str> = self.ops.iter().map(BatchOp::tree).collect();
+        trees.sort_unstable();
+        trees.dedup();
+        trees
+    }
+
+    /// Number of queued writes
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Whether the batch is empty
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
 }
 
 /// Tree/Table abstraction trait
@@ -131,8 +218,9 @@ pub fn fallback_backend(primary: DatabaseBackend) -> Option<DatabaseBackend> {
 // Sled implementation
 #[cfg(feature = "sled")]
 mod sled_impl {
-    use super::{Database, Tree};
+    use super::{BatchOp, Database, Tree, WriteBatch};
     use anyhow::Result;
+    use sled::transaction::{TransactionError, Transactional};
     use sled::Db;
     use std::path::Path;
     use std::sync::Arc;
@@ -160,6 +248,35 @@ mod sled_impl {
             self.db.flush()?;
             Ok(())
         }
+
+        fn write_batch(&self, batch: WriteBatch) -> Result<()> {
+            let names = batch.trees();
+            let trees = names
+                .iter()
+                .map(|name| self.db.open_tree(name))
+                .collect::<sled::Result<Vec<_>>>()?;
+            let tree_refs: Vec<&sled::Tree> = trees.iter().collect();
+
+            tree_refs
+                .as_slice()
+                .transaction(|views| {
+                    for op in batch.ops() {
+                        let view = &views[names.binary_search(&op.tree()).unwrap()];
+                        match op {
+                            BatchOp::Insert { key, value, .. } => {
+                                view.insert(key.as_slice(), value.as_slice())?;
+                            }
+                            BatchOp::Remove { key, .. } => {
+                                view.remove(key.as_slice())?;
+                            }
+                        }
+                    }
+                    Ok(())
+                })
+                .map_err(|e: TransactionError<()>| {
+                    anyhow::anyhow!("Sled batch write failed: {:?}", e)
+                })
+        }
     }
 
     struct SledTree {
@@ -206,7 +323,7 @@ mod sled_impl {
 // Redb implementation
 #[cfg(feature = "redb")]
 mod redb_impl {
-    use super::{Database, Tree};
+    use super::{BatchOp, Database, Tree, WriteBatch};
     use anyhow::Result;
     use redb::{Database as RedbDb, ReadableTable, TableDefinition};
     use std::path::Path;
@@ -245,6 +362,22 @@ mod redb_impl {
         TableDefinition::new("utxo_commitments");
     static COMMITMENT_HEIGHT_INDEX_TABLE: TableDefinition<&[u8], &[u8]> =
         TableDefinition::new("commitment_height_index");
+    static BLOCK_UNDO_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("block_undo");
+    static BLOCK_FILTERS_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("block_filters");
+    static COIN_STATS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("coin_stats");
+    static BLOCK_FILTER_HEADERS_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("block_filter_headers");
+    static PRECIOUS_BLOCKS_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("precious_blocks");
+    static TX_WITNESS_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("tx_witness");
+    static FIRST_SEEN_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("first_seen");
+    static COINBASE_OUTPUTS_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("coinbase_outputs");
+    static ADDRESS_INDEX_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("address_index");
+    static ADDRESS_INDEX_STATE_TABLE: TableDefinition<&[u8], &[u8]> =
+        TableDefinition::new("address_index_state");
 
     pub struct RedbDatabase {
         db: Arc<RedbDb>,
@@ -288,6 +421,16 @@ mod redb_impl {
                             let _ = write_txn.open_table(NETWORK_HASHRATE_CACHE_TABLE)?;
                             let _ = write_txn.open_table(UTXO_COMMITMENTS_TABLE)?;
                             let _ = write_txn.open_table(COMMITMENT_HEIGHT_INDEX_TABLE)?;
+                            let _ = write_txn.open_table(BLOCK_UNDO_TABLE)?;
+                            let _ = write_txn.open_table(BLOCK_FILTERS_TABLE)?;
+                            let _ = write_txn.open_table(COIN_STATS_TABLE)?;
+                            let _ = write_txn.open_table(BLOCK_FILTER_HEADERS_TABLE)?;
+                            let _ = write_txn.open_table(PRECIOUS_BLOCKS_TABLE)?;
+                            let _ = write_txn.open_table(TX_WITNESS_TABLE)?;
+                            let _ = write_txn.open_table(FIRST_SEEN_TABLE)?;
+                            let _ = write_txn.open_table(COINBASE_OUTPUTS_TABLE)?;
+                            let _ = write_txn.open_table(ADDRESS_INDEX_TABLE)?;
+                            let _ = write_txn.open_table(ADDRESS_INDEX_STATE_TABLE)?;
                         }
                         write_txn.commit()?;
                         db
@@ -327,6 +470,16 @@ mod redb_impl {
                 let _ = write_txn.open_table(NETWORK_HASHRATE_CACHE_TABLE)?;
                 let _ = write_txn.open_table(UTXO_COMMITMENTS_TABLE)?;
                 let _ = write_txn.open_table(COMMITMENT_HEIGHT_INDEX_TABLE)?;
+                let _ = write_txn.open_table(BLOCK_UNDO_TABLE)?;
+                let _ = write_txn.open_table(BLOCK_FILTERS_TABLE)?;
+                let _ = write_txn.open_table(COIN_STATS_TABLE)?;
+                let _ = write_txn.open_table(BLOCK_FILTER_HEADERS_TABLE)?;
+                let _ = write_txn.open_table(PRECIOUS_BLOCKS_TABLE)?;
+                let _ = write_txn.open_table(TX_WITNESS_TABLE)?;
+                let _ = write_txn.open_table(FIRST_SEEN_TABLE)?;
+                let _ = write_txn.open_table(COINBASE_OUTPUTS_TABLE)?;
+                let _ = write_txn.open_table(ADDRESS_INDEX_TABLE)?;
+                let _ = write_txn.open_table(ADDRESS_INDEX_STATE_TABLE)?;
             }
             write_txn.commit()?;
 
@@ -359,6 +512,16 @@ mod redb_impl {
                 "network_hashrate_cache" => Some(&NETWORK_HASHRATE_CACHE_TABLE),
                 "utxo_commitments" => Some(&UTXO_COMMITMENTS_TABLE),
                 "commitment_height_index" => Some(&COMMITMENT_HEIGHT_INDEX_TABLE),
+                "block_undo" => Some(&BLOCK_UNDO_TABLE),
+                "block_filters" => Some(&BLOCK_FILTERS_TABLE),
+                "coin_stats" => Some(&COIN_STATS_TABLE),
+                "block_filter_headers" => Some(&BLOCK_FILTER_HEADERS_TABLE),
+                "precious_blocks" => Some(&PRECIOUS_BLOCKS_TABLE),
+                "tx_witness" => Some(&TX_WITNESS_TABLE),
+                "first_seen" => Some(&FIRST_SEEN_TABLE),
+                "coinbase_outputs" => Some(&COINBASE_OUTPUTS_TABLE),
+                "address_index" => Some(&ADDRESS_INDEX_TABLE),
+                "address_index_state" => Some(&ADDRESS_INDEX_STATE_TABLE),
                 _ => None,
             }
         }
@@ -387,6 +550,27 @@ mod redb_impl {
             write_txn.commit()?;
             Ok(())
         }
+
+        fn write_batch(&self, batch: WriteBatch) -> Result<()> {
+            // All writes share one transaction, so they commit or fail together
+            let write_txn = self.db.begin_write()?;
+            for op in batch.ops() {
+                let table_def = self
+                    .get_table_def(op.tree())
+                    .ok_or_else(|| anyhow::anyhow!("Unknown table name in batch: {}", op.tree()))?;
+                let mut table = write_txn.open_table(*table_def)?;
+                match op {
+                    BatchOp::Insert { key, value, .. } => {
+                        table.insert(key.as_slice(), value.as_slice())?;
+                    }
+                    BatchOp::Remove { key, .. } => {
+                        table.remove(key.as_slice())?;
+                    }
+                }
+            }
+            write_txn.commit()?;
+            Ok(())
+        }
     }
 
     struct RedbTree {
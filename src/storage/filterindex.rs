@@ -0,0 +1,143 @@
+//! Block filter index (BIP158)
+//!
+//! Stores the basic compact block filter of every connected block, keyed by
+//! block hash. Filters are written in the same batch as the block's UTXO
+//! changes, since building one needs the scripts of the coins the block
+//! spends, which are only at hand while connecting it (or later from its undo
+//! record). Each filter's BIP157 filter header is stored alongside once its
+//! parent's header is known, so stored headers always chain back to genesis.
+
+use crate::storage::database::{Database, Tree, WriteBatch};
+use anyhow::Result;
+use bllvm_protocol::bip157::FilterHeader;
+use bllvm_protocol::bip158::CompactBlockFilter;
+use bllvm_protocol::Hash;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Tree holding block filters (block hash → `StoredFilter`)
+pub(crate) const BLOCK_FILTER_TREE: &str = "block_filters";
+
+/// Tree holding filter headers (block hash → `StoredFilterHeader`)
+pub(crate) const BLOCK_FILTER_HEADER_TREE: &str = "block_filter_headers";
+
+/// Serialized form of a `CompactBlockFilter`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredFilter {
+    filter_data: Vec<u8>,
+    num_elements: u32,
+}
+
+/// Serialized form of a `FilterHeader`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredFilterHeader {
+    filter_hash: Hash,
+    prev_header_hash: Hash,
+}
+
+/// Block filter storage manager
+pub struct FilterIndex {
+    #[allow(dead_code)]
+    db: Arc<dyn Database>,
+    filters: Arc<dyn Tree>,
+    headers: Arc<dyn Tree>,
+}
+
+impl FilterIndex {
+    /// Create a new filter index
+    pub fn new(db: Arc<dyn Database>) -> Result<Self> {
+        let filters = Arc::from(db.open_tree(BLOCK_FILTER_TREE)?);
+        let headers = Arc::from(db.open_tree(BLOCK_FILTER_HEADER_TREE)?);
+        Ok(Self {
+            db,
+            filters,
+            headers,
+        })
+    }
+
+    /// Get the basic filter of a connected block
+    pub fn get_filter(&self, block_hash: &Hash) -> Result<Option<CompactBlockFilter>> {
+        if let Some(data) = self.filters.get(block_hash.as_slice())? {
+            let stored: StoredFilter = bincode::deserialize(&data)?;
+            Ok(Some(CompactBlockFilter {
+                filter_data: stored.filter_data,
+                num_elements: stored.num_elements,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check if a block has a stored filter
+    pub fn has_filter(&self, block_hash: &Hash) -> Result<bool> {
+        self.filters.contains_key(block_hash.as_slice())
+    }
+
+    /// Get the BIP157 filter header of a block
+    pub fn get_filter_header(&self, block_hash: &Hash) -> Result<Option<FilterHeader>> {
+        if let Some(data) = self.headers.get(block_hash.as_slice())? {
+            let stored: StoredFilterHeader = bincode::deserialize(&data)?;
+            Ok(Some(FilterHeader {
+                filter_hash: stored.filter_hash,
+                prev_header_hash: stored.prev_header_hash,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check if a block has a stored filter header
+    pub fn has_filter_header(&self, block_hash: &Hash) -> Result<bool> {
+        self.headers.contains_key(block_hash.as_slice())
+    }
+
+    /// Queue storing a block's filter header in a write batch
+    pub fn batch_put_filter_header(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        header: &FilterHeader,
+    ) -> Result<()> {
+        let stored = StoredFilterHeader {
+            filter_hash: header.filter_hash,
+            prev_header_hash: header.prev_header_hash,
+        };
+        batch.insert(
+            BLOCK_FILTER_HEADER_TREE,
+            block_hash.as_slice(),
+            &bincode::serialize(&stored)?,
+        );
+        Ok(())
+    }
+
+    /// Queue storing a block's filter in a write batch
+    pub fn batch_put_filter(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        filter: &CompactBlockFilter,
+    ) -> Result<()> {
+        let stored = StoredFilter {
+            filter_data: filter.filter_data.clone(),
+            num_elements: filter.num_elements,
+        };
+        batch.insert(
+            BLOCK_FILTER_TREE,
+            block_hash.as_slice(),
+            &bincode::serialize(&stored)?,
+        );
+        Ok(())
+    }
+
+    /// Remove every stored filter and filter header
+    pub fn clear(&self) -> Result<()> {
+        self.filters.clear()?;
+        self.headers.clear()
+    }
+
+    /// Queue removing a block's filter and filter header in a write batch
+    pub fn batch_remove_filter(&self, batch: &mut WriteBatch, block_hash: &Hash) {
+        batch.remove(BLOCK_FILTER_TREE, block_hash.as_slice());
+        batch.remove(BLOCK_FILTER_HEADER_TREE, block_hash.as_slice());
+    }
+}
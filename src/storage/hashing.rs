@@ -2,9 +2,13 @@
 //!
 //! Implements proper Bitcoin double SHA256 hashing for all storage operations.
 //! This replaces the incorrect DefaultHasher usage throughout the storage layer.
+//! The incremental UTXO set hash (MuHash3072) lives in `storage::muhash` and
+//! is re-exported here.
 
 use sha2::{Digest, Sha256};
 
+pub use crate::storage::muhash::MuHash3072;
+
 /// Calculate Bitcoin double SHA256 hash
 ///
 /// This is the standard Bitcoin hashing algorithm used for:
@@ -3,20 +3,26 @@
 //! This module provides persistent storage for blocks, UTXO set, and chain state.
 //! Supports multiple database backends via feature flags (sled, redb).
 
+pub mod addressindex;
 pub mod blockstore;
+pub mod cache;
 pub mod chainstate;
 #[cfg(kani)]
 pub mod chainstate_proofs;
+pub mod coinstatsindex;
 #[cfg(feature = "utxo-commitments")]
 pub mod commitment_store;
 #[cfg(kani)]
 pub mod cryptographic_proofs;
 pub mod database;
+pub mod filterindex;
 pub mod hashing;
 #[cfg(kani)]
 pub mod kani_helpers;
+pub mod muhash;
 pub mod pruning;
 pub mod txindex;
+pub mod undostore;
 pub mod utxostore;
 #[cfg(kani)]
 pub mod utxostore_proofs;
@@ -24,11 +30,38 @@ pub mod utxostore_proofs;
 use crate::config::PruningConfig;
 use crate::utils::arc_clone;
 use anyhow::Result;
-use database::{create_database, default_backend, fallback_backend, Database, DatabaseBackend};
+use bllvm_protocol::bip157::FilterHeader;
+use bllvm_protocol::{Block, Hash, OutPoint, UTXO};
+use database::{
+    create_database, default_backend, fallback_backend, Database, DatabaseBackend, WriteBatch,
+};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use tracing::{info, warn};
 
+/// Result of `Storage::recover_utxo_consistency`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecoveryOutcome {
+    /// Blocks disconnected from the UTXO set
+    pub disconnected: usize,
+    /// Blocks connected to the UTXO set
+    pub connected: usize,
+    /// Chain tip height the UTXO set now reflects
+    pub height: u64,
+}
+
+/// Result of `Storage::rebuild_utxo_indexes`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexRebuildOutcome {
+    /// Indexes rebuilt over the range
+    pub rebuilt: Vec<&'static str>,
+    /// Enabled indexes left alone because they already cover the range and
+    /// only keep totals as of their tip, which cannot be redone in part
+    pub skipped: Vec<&'static str>,
+}
+
 /// Storage manager that coordinates all storage operations
 pub struct Storage {
     db: Arc<dyn Database>,
@@ -36,7 +69,15 @@ pub struct Storage {
     utxostore: Arc<utxostore::UtxoStore>,
     chainstate: chainstate::ChainState,
     txindex: Arc<txindex::TxIndex>,
+    undostore: undostore::UndoStore,
+    filterindex: filterindex::FilterIndex,
+    coinstatsindex: coinstatsindex::CoinStatsIndex,
+    addressindex: addressindex::AddressIndex,
     pruning_manager: Option<Arc<pruning::PruningManager>>,
+    /// Number of completed `flush` calls
+    flush_count: AtomicU64,
+    /// Held while the active chain is being changed
+    chainstate_lock: Mutex<()>,
 }
 
 impl Storage {
@@ -91,6 +132,10 @@ impl Storage {
         let utxostore = arc_new(utxostore::UtxoStore::new(Arc::clone(&db))?);
         let chainstate = chainstate::ChainState::new(Arc::clone(&db))?;
         let txindex = arc_new(txindex::TxIndex::new(Arc::clone(&db))?);
+        let undostore = undostore::UndoStore::new(Arc::clone(&db))?;
+        let filterindex = filterindex::FilterIndex::new(Arc::clone(&db))?;
+        let coinstatsindex = coinstatsindex::CoinStatsIndex::new(Arc::clone(&db))?;
+        let addressindex = addressindex::AddressIndex::new(Arc::clone(&db))?;
 
         let pruning_manager = pruning_config.map(|config| {
             use crate::utils::{arc_clone, arc_new};
@@ -130,7 +175,13 @@ impl Storage {
             utxostore,
             chainstate,
             txindex,
+            undostore,
+            filterindex,
+            coinstatsindex,
+            addressindex,
             pruning_manager,
+            flush_count: AtomicU64::new(0),
+            chainstate_lock: Mutex::new(()),
         })
     }
 
@@ -150,6 +201,18 @@ impl Storage {
         arc_clone(&self.utxostore)
     }
 
+    /// Take the chainstate lock
+    ///
+    /// Everything that connects or disconnects blocks of the active chain
+    /// (block validation, reorgs, `invalidateblock` and friends, mined
+    /// blocks, reindexing) holds this for the whole change, so the UTXO set,
+    /// undo data and tip move together.
+    pub fn lock_chainstate(&self) -> MutexGuard<'_, ()> {
+        self.chainstate_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     /// Get the chain state
     pub fn chain(&self) -> &chainstate::ChainState {
         &self.chainstate
@@ -160,9 +223,636 @@ impl Storage {
         arc_clone(&self.txindex)
     }
 
+    /// Get the undo store
+    pub fn undo(&self) -> &undostore::UndoStore {
+        &self.undostore
+    }
+
+    /// Get the block filter index
+    pub fn filters(&self) -> &filterindex::FilterIndex {
+        &self.filterindex
+    }
+
+    /// Get the coin statistics index
+    pub fn coin_stats(&self) -> &coinstatsindex::CoinStatsIndex {
+        &self.coinstatsindex
+    }
+
+    /// Get the address index
+    pub fn addresses(&self) -> &addressindex::AddressIndex {
+        &self.addressindex
+    }
+
+    /// Apply a validated block's UTXO changes and record its undo data
+    ///
+    /// Spent coins are removed and new outputs added in a single atomic batch
+    /// together with the block's undo record, BIP158 filter and (when enabled)
+    /// coin statistics and address index. Outputs created and spent within
+    /// the block never touch the UTXO set. The first transaction is treated as
+    /// the coinbase: it spends nothing and its outputs are marked as coinbase
+    /// outputs for the maturity check.
+    pub fn connect_block(&self, block: &Block, height: u64) -> Result<undostore::BlockUndo> {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        let block_hash = self.blockstore.get_block_hash(block);
+        let mut spent = Vec::new();
+        let mut spent_coinbase = Vec::new();
+        let mut created: Vec<OutPoint> = Vec::new();
+        let mut coinbase_outputs: HashSet<OutPoint> = HashSet::new();
+        let mut new_coins: HashMap<OutPoint, UTXO> = HashMap::new();
+
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            if tx_index > 0 {
+                for input in tx.inputs.iter() {
+                    if new_coins.remove(&input.prevout).is_some() {
+                        continue;
+                    }
+                    let coin = self.utxostore.get_utxo(&input.prevout)?.ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Block {} spends missing coin {}:{}",
+                            hex::encode(block_hash),
+                            hex::encode(input.prevout.hash),
+                            input.prevout.index
+                        )
+                    })?;
+                    if self.utxostore.is_coinbase(&input.prevout)? {
+                        spent_coinbase.push(input.prevout.clone());
+                    }
+                    spent.push((input.prevout.clone(), coin));
+                }
+            }
+
+            let txid = calculate_tx_id(tx);
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let outpoint = OutPoint {
+                    hash: txid,
+                    index: index as u64,
+                };
+                created.push(outpoint.clone());
+                if tx_index == 0 {
+                    coinbase_outputs.insert(outpoint.clone());
+                }
+                new_coins.insert(
+                    outpoint,
+                    UTXO {
+                        value: output.value,
+                        script_pubkey: output.script_pubkey.clone(),
+                        height,
+                    },
+                );
+            }
+        }
+        created.retain(|outpoint| new_coins.contains_key(outpoint));
+        let undo = undostore::BlockUndo {
+            prev_block_hash: block.header.prev_block_hash,
+            height,
+            spent,
+            spent_coinbase,
+            created,
+        };
+
+        let mut batch = WriteBatch::default();
+        for (outpoint, _) in &undo.spent {
+            self.utxostore.batch_remove_utxo(&mut batch, outpoint);
+        }
+        for outpoint in &undo.created {
+            self.utxostore
+                .batch_add_utxo(&mut batch, outpoint, &new_coins[outpoint])?;
+            if coinbase_outputs.contains(outpoint) {
+                self.utxostore.batch_mark_coinbase(&mut batch, outpoint);
+            }
+        }
+        self.undostore
+            .batch_put_undo(&mut batch, &block_hash, &undo)?;
+        // Coins created and spent within the block are already covered by
+        // its outputs
+        let spent_scripts: Vec<Vec<u8>> = undo
+            .spent
+            .iter()
+            .map(|(_, coin)| coin.script_pubkey.clone())
+            .collect();
+        match bllvm_protocol::bip158::build_block_filter(&block.transactions, &spent_scripts) {
+            Ok(filter) => {
+                self.filterindex
+                    .batch_put_filter(&mut batch, &block_hash, &filter)?;
+                // Until the filter index is built for earlier blocks the
+                // parent has no header; the builder fills this one in later
+                let prev_header = match height {
+                    0 => None,
+                    _ => self
+                        .filterindex
+                        .get_filter_header(&block.header.prev_block_hash)?,
+                };
+                if height == 0 || prev_header.is_some() {
+                    let header = FilterHeader::new(&filter, prev_header.as_ref());
+                    self.filterindex
+                        .batch_put_filter_header(&mut batch, &block_hash, &header)?;
+                }
+            }
+            Err(e) => warn!(
+                "Failed to build filter for block {}: {}",
+                hex::encode(block_hash),
+                e
+            ),
+        }
+        if self.coinstatsindex.is_enabled() {
+            let spent_coinbase: HashSet<&OutPoint> = undo.spent_coinbase.iter().collect();
+            let spent: Vec<_> = undo
+                .spent
+                .iter()
+                .map(|(outpoint, coin)| (outpoint, coin, spent_coinbase.contains(outpoint)))
+                .collect();
+            let created: Vec<_> = undo
+                .created
+                .iter()
+                .map(|outpoint| {
+                    (
+                        outpoint,
+                        &new_coins[outpoint],
+                        coinbase_outputs.contains(outpoint),
+                    )
+                })
+                .collect();
+            self.coinstatsindex.batch_connect(
+                &mut batch,
+                &block_hash,
+                &block.header.prev_block_hash,
+                height,
+                &spent,
+                &created,
+            )?;
+        }
+        if self.addressindex.is_enabled() {
+            self.addressindex.batch_connect(
+                &mut batch,
+                &block_hash,
+                &block.header.prev_block_hash,
+                height,
+                &addressindex::block_touches(block, &undo.spent),
+            )?;
+        }
+        let tip = undostore::UtxoTip {
+            hash: block_hash,
+            height,
+        };
+        self.undostore.batch_set_utxo_tip(&mut batch, Some(&tip))?;
+        self.db.write_batch(batch)?;
+
+        Ok(undo)
+    }
+
+    /// Height of the last active-chain block with a stored filter header
+    ///
+    /// Filter headers chain back to genesis, so every block below this
+    /// height has one too. `None` if not even genesis has a header.
+    pub fn filter_index_height(&self) -> Result<Option<u64>> {
+        let Some(tip_height) = self.chainstate.get_height()? else {
+            return Ok(None);
+        };
+        let has_header = |height: u64| -> Result<bool> {
+            match self.blockstore.get_hash_by_height(height)? {
+                Some(hash) => self.filterindex.has_filter_header(&hash),
+                None => Ok(false),
+            }
+        };
+        if !has_header(0)? {
+            return Ok(None);
+        }
+        // Highest height with a header, which is the end of the header chain
+        let (mut low, mut high) = (0, tip_height);
+        while low < high {
+            let mid = low + (high - low + 1) / 2;
+            if has_header(mid)? {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
+    /// Build and store the filter and filter header of the active-chain block
+    /// at `height` from its stored body and undo record
+    ///
+    /// A filter already stored for the block is reused. The parent's filter
+    /// header must be stored. Fails if the block body has been pruned.
+    pub fn index_block_filter(&self, height: u64) -> Result<()> {
+        let block_hash = self
+            .blockstore
+            .get_hash_by_height(height)?
+            .ok_or_else(|| anyhow::anyhow!("No block at height {}", height))?;
+        let block = self.blockstore.get_block(&block_hash)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Block {} at height {} is pruned; cannot build its filter",
+                hex::encode(block_hash),
+                height
+            )
+        })?;
+        let prev_header = match height {
+            0 => None,
+            _ => Some(
+                self.filterindex
+                    .get_filter_header(&block.header.prev_block_hash)?
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No filter header for the parent of height {}", height)
+                    })?,
+            ),
+        };
+
+        let mut batch = WriteBatch::default();
+        let filter = match self.filterindex.get_filter(&block_hash)? {
+            Some(filter) => filter,
+            None => {
+                let spent_scripts: Vec<Vec<u8>> = match self.undostore.get_undo(&block_hash)? {
+                    Some(undo) => undo
+                        .spent
+                        .into_iter()
+                        .map(|(_, coin)| coin.script_pubkey)
+                        .collect(),
+                    // Only the genesis block spends nothing
+                    None if height == 0 => Vec::new(),
+                    None => {
+                        return Err(anyhow::anyhow!(
+                            "No undo data for block {}; cannot build its filter",
+                            hex::encode(block_hash)
+                        ))
+                    }
+                };
+                let filter =
+                    bllvm_protocol::bip158::build_block_filter(&block.transactions, &spent_scripts)
+                        .map_err(|e| anyhow::anyhow!("Failed to build filter: {}", e))?;
+                self.filterindex
+                    .batch_put_filter(&mut batch, &block_hash, &filter)?;
+                filter
+            }
+        };
+        let header = FilterHeader::new(&filter, prev_header.as_ref());
+        self.filterindex
+            .batch_put_filter_header(&mut batch, &block_hash, &header)?;
+        self.db.write_batch(batch)
+    }
+
+    /// Revert a connected block's UTXO changes using its undo record
+    ///
+    /// Removes the outputs the block created, restores the coins it spent and
+    /// deletes the undo record, all in one atomic batch. Fails if the block
+    /// has no undo record (never connected, or already disconnected) or is
+    /// not the block the UTXO set currently reflects.
+    pub fn disconnect_block(&self, block_hash: &Hash) -> Result<undostore::BlockUndo> {
+        let undo = self
+            .undostore
+            .get_undo(block_hash)?
+            .ok_or_else(|| anyhow::anyhow!("No undo data for block {}", hex::encode(block_hash)))?;
+        if let Some(tip) = self.undostore.get_utxo_tip()? {
+            if tip.hash != *block_hash {
+                return Err(anyhow::anyhow!(
+                    "Cannot disconnect block {}: UTXO set is at {}",
+                    hex::encode(block_hash),
+                    hex::encode(tip.hash)
+                ));
+            }
+        }
+
+        let mut batch = WriteBatch::default();
+        if self.coinstatsindex.is_enabled() {
+            // Created coins are read back before the batch removes them
+            let mut created_coins = Vec::with_capacity(undo.created.len());
+            for outpoint in &undo.created {
+                let coin = self.utxostore.get_utxo(outpoint)?.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Block {} created coin {}:{} missing from the UTXO set",
+                        hex::encode(block_hash),
+                        hex::encode(outpoint.hash),
+                        outpoint.index
+                    )
+                })?;
+                created_coins.push((outpoint, coin, self.utxostore.is_coinbase(outpoint)?));
+            }
+            let created: Vec<_> = created_coins
+                .iter()
+                .map(|(outpoint, coin, coinbase)| (*outpoint, coin, *coinbase))
+                .collect();
+            let spent: Vec<_> = undo
+                .spent
+                .iter()
+                .map(|(outpoint, coin)| (outpoint, coin, undo.spent_coinbase.contains(outpoint)))
+                .collect();
+            self.coinstatsindex.batch_disconnect(
+                &mut batch,
+                block_hash,
+                &undo.prev_block_hash,
+                &spent,
+                &created,
+            )?;
+        }
+        if self.addressindex.is_enabled() {
+            match self.blockstore.get_block(block_hash)? {
+                Some(block) => self.addressindex.batch_disconnect(
+                    &mut batch,
+                    block_hash,
+                    &undo.prev_block_hash,
+                    undo.height,
+                    &addressindex::block_touches(&block, &undo.spent),
+                )?,
+                None => warn!(
+                    "Block {} not stored, addressindex left at it; rebuild with rebuildutxoindex",
+                    hex::encode(block_hash)
+                ),
+            }
+        }
+        for outpoint in &undo.created {
+            self.utxostore.batch_remove_utxo(&mut batch, outpoint);
+        }
+        for (outpoint, coin) in &undo.spent {
+            self.utxostore.batch_add_utxo(&mut batch, outpoint, coin)?;
+        }
+        for outpoint in &undo.spent_coinbase {
+            self.utxostore.batch_mark_coinbase(&mut batch, outpoint);
+        }
+        self.undostore.batch_remove_undo(&mut batch, block_hash);
+        self.filterindex.batch_remove_filter(&mut batch, block_hash);
+        let parent = undo.height.checked_sub(1).map(|height| undostore::UtxoTip {
+            hash: undo.prev_block_hash,
+            height,
+        });
+        self.undostore
+            .batch_set_utxo_tip(&mut batch, parent.as_ref())?;
+        self.db.write_batch(batch)?;
+
+        Ok(undo)
+    }
+
+    /// Bring the UTXO set back in line with the chain tip after an unclean shutdown
+    ///
+    /// A crash between persisting a block's UTXO changes and updating the
+    /// chain tip leaves the two disagreeing. Blocks the UTXO set reflects that
+    /// are above the chain tip or off the active chain are disconnected using
+    /// their undo data, then active-chain blocks up to the tip are connected.
+    /// Nodes without a recorded UTXO tip are left untouched.
+    pub fn recover_utxo_consistency(&self) -> Result<RecoveryOutcome> {
+        let mut outcome = RecoveryOutcome::default();
+        let Some(chain_tip) = self.chainstate.load_chain_info()? else {
+            return Ok(outcome);
+        };
+        let mut utxo_tip = self.undostore.get_utxo_tip()?;
+        if utxo_tip.is_none() {
+            return Ok(outcome);
+        }
+
+        // Roll back until the UTXO set is on the active chain, at or below the tip
+        while let Some(tip) = utxo_tip {
+            let on_active_chain = tip.height <= chain_tip.height
+                && self.blockstore.get_hash_by_height(tip.height)? == Some(tip.hash);
+            if on_active_chain {
+                break;
+            }
+            self.disconnect_block(&tip.hash)?;
+            outcome.disconnected += 1;
+            utxo_tip = self.undostore.get_utxo_tip()?;
+        }
+
+        // Roll forward along the active chain up to the tip
+        let start = utxo_tip.map(|tip| tip.height + 1).unwrap_or(0);
+        for height in start..=chain_tip.height {
+            let hash = self.blockstore.get_hash_by_height(height)?.ok_or_else(|| {
+                anyhow::anyhow!("Cannot roll forward: no block at height {}", height)
+            })?;
+            let block = self.blockstore.get_block(&hash)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Cannot roll forward: block {} at height {} not stored",
+                    hex::encode(hash),
+                    height
+                )
+            })?;
+            self.connect_block(&block, height)?;
+            outcome.connected += 1;
+        }
+
+        outcome.height = chain_tip.height;
+        if outcome.disconnected > 0 || outcome.connected > 0 {
+            warn!(
+                "Recovered UTXO set: disconnected {} and connected {} blocks to reach height {}",
+                outcome.disconnected, outcome.connected, outcome.height
+            );
+        } else {
+            info!(
+                "UTXO set consistent with chain tip at height {}",
+                outcome.height
+            );
+        }
+        Ok(outcome)
+    }
+
+    /// Drop all state derived from block data
+    ///
+    /// Clears the UTXO set, undo records, block filters, coin statistics,
+    /// address index, transaction index and chain state. Block bodies, headers and the height index are kept so the
+    /// rest can be rebuilt by replaying them (see `reindexchainstate`).
+    pub fn clear_chainstate(&self) -> Result<()> {
+        self.utxostore.clear()?;
+        self.undostore.clear()?;
+        self.filterindex.clear()?;
+        self.coinstatsindex.clear()?;
+        self.addressindex.clear()?;
+        self.txindex.clear()?;
+        self.chainstate.reset()?;
+        Ok(())
+    }
+
+    /// Re-derive the UTXO-derived indexes over active-chain blocks
+    /// `start..=stop` (`rebuildutxoindex`)
+    ///
+    /// Replays the blocks' UTXO changes from their undo records, so no
+    /// validation is repeated. The address index drops whatever it holds for
+    /// the range and records it again. The coin statistics index only keeps
+    /// totals as of its tip, so it is advanced over the part of the range past
+    /// its tip and skipped if it already covers the whole range. Each index
+    /// must reach at least `start - 1`, and every block in the range must
+    /// still have its body and undo record; nothing is written otherwise.
+    pub fn rebuild_utxo_indexes(&self, start: u64, stop: u64) -> Result<IndexRebuildOutcome> {
+        if start > stop {
+            return Err(anyhow::anyhow!(
+                "start_height {} is above stop_height {}",
+                start,
+                stop
+            ));
+        }
+        let address_enabled = self.addressindex.is_enabled();
+        let coinstats_enabled = self.coinstatsindex.is_enabled();
+        if !address_enabled && !coinstats_enabled {
+            return Err(anyhow::anyhow!("No UTXO-derived index is enabled"));
+        }
+        let tip_height = self
+            .chainstate
+            .get_height()?
+            .ok_or_else(|| anyhow::anyhow!("Chain not initialized"))?;
+        if stop > tip_height {
+            return Err(anyhow::anyhow!(
+                "stop_height {} is above the chain tip at height {}",
+                stop,
+                tip_height
+            ));
+        }
+
+        // Each index must already reach the block before the range
+        let check_contiguous = |name: &str, next: u64| {
+            if start > next {
+                Err(anyhow::anyhow!(
+                    "{} has no data before height {}, so the range must start at or below it",
+                    name,
+                    next
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        let address_next = self.addressindex.tip()?.map_or(0, |tip| tip.height + 1);
+        if address_enabled {
+            check_contiguous("addressindex", address_next)?;
+        }
+        let coinstats_next = self
+            .coinstatsindex
+            .tip()?
+            .map_or(0, |(stats, _)| stats.height + 1);
+        if coinstats_enabled && stop >= coinstats_next {
+            check_contiguous("coinstatsindex", coinstats_next)?;
+        }
+        let replay_coinstats = coinstats_enabled && stop >= coinstats_next;
+
+        let mut hashes = Vec::with_capacity((stop - start + 1) as usize);
+        for height in start..=stop {
+            let hash = self.blockstore.get_hash_by_height(height)?.ok_or_else(|| {
+                anyhow::anyhow!("Cannot rebuild indexes: no block at height {}", height)
+            })?;
+            if !self.blockstore.has_block_body(&hash)? {
+                return Err(anyhow::anyhow!(
+                    "Cannot rebuild indexes: block {} at height {} has been pruned",
+                    hex::encode(hash),
+                    height
+                ));
+            }
+            if !self.undostore.has_undo(&hash)? {
+                return Err(anyhow::anyhow!(
+                    "Cannot rebuild indexes: no undo data for block {} at height {}",
+                    hex::encode(hash),
+                    height
+                ));
+            }
+            hashes.push(hash);
+        }
+
+        let mut touches = std::collections::BTreeMap::new();
+        for (height, hash) in (start..=stop).zip(&hashes) {
+            let block = self
+                .blockstore
+                .get_block(hash)?
+                .ok_or_else(|| anyhow::anyhow!("Block {} disappeared", hex::encode(hash)))?;
+            let undo = self.undostore.get_undo(hash)?.ok_or_else(|| {
+                anyhow::anyhow!("Undo data for {} disappeared", hex::encode(hash))
+            })?;
+            if address_enabled {
+                touches.insert(height, addressindex::block_touches(&block, &undo.spent));
+            }
+            if replay_coinstats && height >= coinstats_next {
+                let mut batch = WriteBatch::default();
+                self.batch_replay_coin_stats(&mut batch, &block, hash, &undo)?;
+                self.db.write_batch(batch)?;
+            }
+        }
+
+        let mut outcome = IndexRebuildOutcome::default();
+        if address_enabled {
+            let tip = (stop >= address_next).then(|| addressindex::AddressIndexTip {
+                block_hash: hashes[hashes.len() - 1],
+                height: stop,
+            });
+            let mut batch = WriteBatch::default();
+            self.addressindex.batch_replace_range(
+                &mut batch,
+                start..=stop,
+                &touches,
+                tip.as_ref(),
+            )?;
+            self.db.write_batch(batch)?;
+            outcome.rebuilt.push("addressindex");
+        }
+        if replay_coinstats {
+            outcome.rebuilt.push("coinstatsindex");
+        } else if coinstats_enabled {
+            outcome.skipped.push("coinstatsindex");
+        }
+        info!(
+            "Rebuilt {:?} over heights {} to {}",
+            outcome.rebuilt, start, stop
+        );
+        Ok(outcome)
+    }
+
+    /// Queue the coin statistics update for a block from its undo record
+    fn batch_replay_coin_stats(
+        &self,
+        batch: &mut WriteBatch,
+        block: &Block,
+        block_hash: &Hash,
+        undo: &undostore::BlockUndo,
+    ) -> Result<()> {
+        use bllvm_protocol::block::calculate_tx_id;
+
+        let mut outputs = HashMap::new();
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            let txid = calculate_tx_id(tx);
+            for (index, output) in tx.outputs.iter().enumerate() {
+                let coin = UTXO {
+                    value: output.value,
+                    script_pubkey: output.script_pubkey.clone(),
+                    height: undo.height,
+                };
+                outputs.insert(
+                    OutPoint {
+                        hash: txid,
+                        index: index as u64,
+                    },
+                    (coin, tx_index == 0),
+                );
+            }
+        }
+        let mut created = Vec::with_capacity(undo.created.len());
+        for outpoint in &undo.created {
+            let (coin, coinbase) = outputs.get(outpoint).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Undo data for block {} lists unknown output {}:{}",
+                    hex::encode(block_hash),
+                    hex::encode(outpoint.hash),
+                    outpoint.index
+                )
+            })?;
+            created.push((outpoint, coin, *coinbase));
+        }
+        let spent: Vec<_> = undo
+            .spent
+            .iter()
+            .map(|(outpoint, coin)| (outpoint, coin, undo.spent_coinbase.contains(outpoint)))
+            .collect();
+        self.coinstatsindex.batch_connect(
+            batch,
+            block_hash,
+            &undo.prev_block_hash,
+            undo.height,
+            &spent,
+            &created,
+        )
+    }
+
     /// Flush all pending writes to disk
     pub fn flush(&self) -> Result<()> {
-        self.db.flush()
+        self.db.flush()?;
+        self.flush_count.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Number of completed flushes since the storage was opened
+    pub fn flush_count(&self) -> u64 {
+        self.flush_count.load(Ordering::SeqCst)
     }
 
     /// Get approximate disk size used by storage (in bytes)
@@ -258,4 +948,12 @@ impl Storage {
             .map(|pm| pm.is_enabled())
             .unwrap_or(false)
     }
+
+    /// Lowest height with a stored block body (0 if nothing has been pruned)
+    pub fn prune_height(&self) -> Result<u64> {
+        match self.chainstate.get_height()? {
+            Some(tip_height) => self.blockstore.first_stored_height(tip_height),
+            None => Ok(0),
+        }
+    }
 }
@@ -0,0 +1,327 @@
+//! MuHash3072 rolling set hash
+//!
+//! The UTXO set hash `gettxoutsetinfo` reports as `muhash`, as specified for
+//! Bitcoin Core's coinstatsindex. Each element is hashed with SHA256 and
+//! expanded with ChaCha20 into a 3072-bit number; the set hash is the product
+//! of those numbers modulo the prime 2^3072 - 1103717. Multiplication is
+//! commutative and invertible, so elements can be added and removed in any
+//! order, which keeps the hash current across block connects and disconnects
+//! without rescanning the set.
+
+use crate::storage::hashing::sha256;
+
+/// 64-bit limbs in a `Num3072`
+const LIMBS: usize = 48;
+
+/// Size of a serialized `Num3072`
+pub const NUM3072_BYTES: usize = LIMBS * 8;
+
+/// The modulus is 2^3072 - MODULUS_OFFSET
+const MODULUS_OFFSET: u64 = 1103717;
+
+/// A 3072-bit number modulo 2^3072 - 1103717 (little-endian limbs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Num3072 {
+    limbs: [u64; LIMBS],
+}
+
+impl Num3072 {
+    /// The multiplicative identity
+    pub fn one() -> Self {
+        let mut limbs = [0u64; LIMBS];
+        limbs[0] = 1;
+        Self { limbs }
+    }
+
+    /// Parse 384 little-endian bytes
+    pub fn from_bytes(bytes: &[u8; NUM3072_BYTES]) -> Self {
+        let mut limbs = [0u64; LIMBS];
+        for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Self { limbs }
+    }
+
+    /// Serialize as 384 little-endian bytes
+    pub fn to_bytes(&self) -> [u8; NUM3072_BYTES] {
+        let mut bytes = [0u8; NUM3072_BYTES];
+        for (chunk, limb) in bytes.chunks_exact_mut(8).zip(self.limbs.iter()) {
+            chunk.copy_from_slice(&limb.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Multiply by `other` modulo the prime
+    pub fn multiply(&mut self, other: &Num3072) {
+        let mut product = [0u64; 2 * LIMBS];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = product[i + j] as u128 + a as u128 * b as u128 + carry;
+                product[i + j] = cur as u64;
+                carry = cur >> 64;
+            }
+            product[i + LIMBS] = carry as u64;
+        }
+
+        // 2^3072 is congruent to MODULUS_OFFSET, so fold the high half down
+        let mut carry = 0u128;
+        for i in 0..LIMBS {
+            let cur =
+                product[i] as u128 + product[i + LIMBS] as u128 * MODULUS_OFFSET as u128 + carry;
+            self.limbs[i] = cur as u64;
+            carry = cur >> 64;
+        }
+        while carry > 0 {
+            let mut add = carry * MODULUS_OFFSET as u128;
+            carry = 0;
+            for limb in self.limbs.iter_mut() {
+                if add == 0 {
+                    break;
+                }
+                let cur = *limb as u128 + add;
+                *limb = cur as u64;
+                add = cur >> 64;
+            }
+            carry += add;
+        }
+        self.reduce();
+    }
+
+    /// Divide by `other` modulo the prime
+    pub fn divide(&mut self, other: &Num3072) {
+        self.multiply(&other.inverse());
+    }
+
+    /// Multiplicative inverse (by Fermat's little theorem: self^(p-2))
+    fn inverse(&self) -> Num3072 {
+        // p - 2 = 2^3072 - (MODULUS_OFFSET + 2): every limb is all ones except the lowest
+        let mut exponent = [u64::MAX; LIMBS];
+        exponent[0] = 0u64.wrapping_sub(MODULUS_OFFSET + 2);
+
+        let mut result = Num3072::one();
+        for limb in exponent.iter().rev() {
+            for bit in (0..64).rev() {
+                let square = result;
+                result.multiply(&square);
+                if (limb >> bit) & 1 == 1 {
+                    result.multiply(self);
+                }
+            }
+        }
+        result
+    }
+
+    /// Subtract the prime once if the value is not below it
+    fn reduce(&mut self) {
+        // self >= p exactly when self + MODULUS_OFFSET overflows 3072 bits
+        let mut reduced = self.limbs;
+        let mut carry = MODULUS_OFFSET as u128;
+        for limb in reduced.iter_mut() {
+            let cur = *limb as u128 + carry;
+            *limb = cur as u64;
+            carry = cur >> 64;
+        }
+        if carry > 0 {
+            self.limbs = reduced;
+        }
+    }
+}
+
+/// A multiset hash supporting insertion and removal
+///
+/// Kept as a fraction so removals do not need a modular inverse until the
+/// hash is finalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MuHash3072 {
+    numerator: Num3072,
+    denominator: Num3072,
+}
+
+impl Default for MuHash3072 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MuHash3072 {
+    /// Hash of the empty set
+    pub fn new() -> Self {
+        Self {
+            numerator: Num3072::one(),
+            denominator: Num3072::one(),
+        }
+    }
+
+    /// Add an element to the set
+    pub fn insert(&mut self, data: &[u8]) {
+        self.numerator.multiply(&to_num3072(data));
+    }
+
+    /// Remove an element from the set
+    pub fn remove(&mut self, data: &[u8]) {
+        self.denominator.multiply(&to_num3072(data));
+    }
+
+    /// Combine with another set's hash (set union)
+    pub fn combine(&mut self, other: &MuHash3072) {
+        self.numerator.multiply(&other.numerator);
+        self.denominator.multiply(&other.denominator);
+    }
+
+    /// 32-byte set hash: SHA256 of the normalized 3072-bit value
+    ///
+    /// Like Bitcoin Core's `uint256`, RPC displays it byte-reversed.
+    pub fn finalize(&self) -> [u8; 32] {
+        let mut value = self.numerator;
+        value.divide(&self.denominator);
+        sha256(&value.to_bytes())
+    }
+
+    /// Serialize the numerator and denominator (768 bytes)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 * NUM3072_BYTES);
+        bytes.extend_from_slice(&self.numerator.to_bytes());
+        bytes.extend_from_slice(&self.denominator.to_bytes());
+        bytes
+    }
+
+    /// Parse the output of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 2 * NUM3072_BYTES {
+            return None;
+        }
+        let (numerator, denominator) = bytes.split_at(NUM3072_BYTES);
+        Some(Self {
+            numerator: Num3072::from_bytes(numerator.try_into().ok()?),
+            denominator: Num3072::from_bytes(denominator.try_into().ok()?),
+        })
+    }
+}
+
+/// Map an element to a 3072-bit number: SHA256, then a ChaCha20 keystream
+fn to_num3072(data: &[u8]) -> Num3072 {
+    let key = sha256(data);
+    let mut bytes = [0u8; NUM3072_BYTES];
+    for (counter, block) in bytes.chunks_exact_mut(64).enumerate() {
+        block.copy_from_slice(&chacha20_block(&key, counter as u32));
+    }
+    Num3072::from_bytes(&bytes)
+}
+
+/// One 64-byte ChaCha20 keystream block with a zero nonce
+fn chacha20_block(key: &[u8; 32], counter: u32) -> [u8; 64] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for (word, chunk) in state[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    state[12] = counter;
+
+    let mut working = state;
+    for _ in 0..10 {
+        quarter_round(&mut working, 0, 4, 8, 12);
+        quarter_round(&mut working, 1, 5, 9, 13);
+        quarter_round(&mut working, 2, 6, 10, 14);
+        quarter_round(&mut working, 3, 7, 11, 15);
+        quarter_round(&mut working, 0, 5, 10, 15);
+        quarter_round(&mut working, 1, 6, 11, 12);
+        quarter_round(&mut working, 2, 7, 8, 13);
+        quarter_round(&mut working, 3, 4, 9, 14);
+    }
+
+    let mut block = [0u8; 64];
+    for (i, chunk) in block.chunks_exact_mut(4).enumerate() {
+        chunk.copy_from_slice(&working[i].wrapping_add(state[i]).to_le_bytes());
+    }
+    block
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_int(i: u8) -> MuHash3072 {
+        let mut data = [0u8; 32];
+        data[0] = i;
+        let mut hash = MuHash3072::new();
+        hash.insert(&data);
+        hash
+    }
+
+    #[test]
+    fn test_muhash_vector() {
+        // Bitcoin Core's crypto_tests: FromInt(0) * FromInt(1) / FromInt(2)
+        let mut hash = from_int(0);
+        hash.combine(&from_int(1));
+        let mut two = [0u8; 32];
+        two[0] = 2;
+        hash.remove(&two);
+        let mut out = hash.finalize();
+        out.reverse();
+        assert_eq!(
+            hex::encode(out),
+            "10d312b100cbd32ada024a6646e40d3482fcff103668d2625f10002a607d5863"
+        );
+    }
+
+    #[test]
+    fn test_muhash_add_then_remove_restores_hash() {
+        let mut hash = MuHash3072::new();
+        hash.insert(b"coin a");
+        hash.insert(b"coin b");
+        let before = hash.finalize();
+
+        hash.insert(b"coin c");
+        assert_ne!(hash.finalize(), before);
+        hash.remove(b"coin c");
+        assert_eq!(hash.finalize(), before);
+
+        // Removing before adding cancels out as well
+        hash.remove(b"coin d");
+        hash.insert(b"coin d");
+        assert_eq!(hash.finalize(), before);
+        assert_eq!(MuHash3072::new().finalize(), {
+            let mut empty = MuHash3072::new();
+            empty.insert(b"coin e");
+            empty.remove(b"coin e");
+            empty.finalize()
+        });
+    }
+
+    #[test]
+    fn test_muhash_insert_remove_is_order_independent() {
+        let mut forward = MuHash3072::new();
+        let mut backward = MuHash3072::new();
+        for i in 0..4u8 {
+            forward.insert(&[i]);
+            backward.insert(&[3 - i]);
+        }
+        forward.remove(&[2]);
+        backward.remove(&[2]);
+        assert_eq!(forward.finalize(), backward.finalize());
+
+        let mut expected = MuHash3072::new();
+        for i in [0u8, 1, 3] {
+            expected.insert(&[i]);
+        }
+        assert_eq!(forward.finalize(), expected.finalize());
+        assert_eq!(
+            MuHash3072::from_bytes(&forward.to_bytes())
+                .unwrap()
+                .finalize(),
+            forward.finalize()
+        );
+    }
+}
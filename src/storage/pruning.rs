@@ -743,7 +743,10 @@ impl PruningManager {
                     // Apply block to UTXO set using connect_block
                     // This properly handles coinbase transactions and input/output processing
                     let (validation_result, new_utxo_set) = connect_block(
-                        &block, &witnesses, utxo_set, height,
+                        &block,
+                        &crate::validation::witness::consensus_witnesses(&witnesses),
+                        utxo_set,
+                        height,
                         None, // No recent headers needed for historical replay
                     )?;
 
@@ -3,7 +3,11 @@
 //! Provides fast lookup of transactions by hash and maintains transaction metadata.
 
 use crate::storage::database::{Database, Tree};
+use crate::validation::witness::{
+    encode_varint, has_witness, serialize_transaction_with_witness, transaction_weight,
+};
 use anyhow::Result;
+use bllvm_protocol::segwit::Witness;
 use bllvm_protocol::{Hash, Transaction};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -26,6 +30,7 @@ pub struct TxIndex {
     tx_by_hash: Arc<dyn Tree>,
     tx_by_block: Arc<dyn Tree>,
     tx_metadata: Arc<dyn Tree>,
+    tx_witness: Arc<dyn Tree>,
 }
 
 impl TxIndex {
@@ -34,12 +39,14 @@ impl TxIndex {
         let tx_by_hash = Arc::from(db.open_tree("tx_by_hash")?);
         let tx_by_block = Arc::from(db.open_tree("tx_by_block")?);
         let tx_metadata = Arc::from(db.open_tree("tx_metadata")?);
+        let tx_witness = Arc::from(db.open_tree("tx_witness")?);
 
         Ok(Self {
             db,
             tx_by_hash,
             tx_by_block,
             tx_metadata,
+            tx_witness,
         })
     }
 
@@ -50,6 +57,22 @@ impl TxIndex {
         block_hash: &Hash,
         block_height: u64,
         tx_index: u32,
+    ) -> Result<()> {
+        self.index_transaction_with_witness(tx, &[], block_hash, block_height, tx_index)
+    }
+
+    /// Index a transaction along with its witness
+    ///
+    /// `witnesses` holds one stack per input. The transaction is keyed by its
+    /// txid (witness-stripped); size and weight in the metadata include the
+    /// witness.
+    pub fn index_transaction_with_witness(
+        &self,
+        tx: &Transaction,
+        witnesses: &[Witness],
+        block_hash: &Hash,
+        block_height: u64,
+        tx_index: u32,
     ) -> Result<()> {
         // Use the standard transaction ID calculation from bllvm-protocol
         let tx_hash = bllvm_protocol::block::calculate_tx_id(tx);
@@ -57,6 +80,12 @@ impl TxIndex {
 
         // Store transaction by hash
         self.tx_by_hash.insert(tx_hash.as_slice(), &tx_data)?;
+        if has_witness(witnesses) {
+            self.tx_witness
+                .insert(tx_hash.as_slice(), &bincode::serialize(witnesses)?)?;
+        } else {
+            self.tx_witness.remove(tx_hash.as_slice())?;
+        }
 
         // Store transaction metadata
         let metadata = TxMetadata {
@@ -64,8 +93,8 @@ impl TxIndex {
             block_hash: *block_hash,
             block_height,
             tx_index,
-            size: self.calculate_tx_size(tx),
-            weight: self.calculate_tx_weight(tx),
+            size: serialize_transaction_with_witness(tx, witnesses).len() as u32,
+            weight: transaction_weight(tx, witnesses) as u32,
         };
 
         let metadata_data = bincode::serialize(&metadata)?;
@@ -89,6 +118,17 @@ impl TxIndex {
         }
     }
 
+    /// Get the per-input witness stacks of an indexed transaction (None if it
+    /// has no witness)
+    pub fn get_witness(&self, tx_hash: &Hash) -> Result<Option<Vec<Witness>>> {
+        if let Some(data) = self.tx_witness.get(tx_hash.as_slice())? {
+            let witnesses: Vec<Witness> = bincode::deserialize(&data)?;
+            Ok(Some(witnesses))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Get transaction metadata
     pub fn get_metadata(&self, tx_hash: &Hash) -> Result<Option<TxMetadata>> {
         if let Some(data) = self.tx_metadata.get(tx_hash.as_slice())? {
@@ -192,6 +232,7 @@ impl TxIndex {
 
         self.tx_by_hash.remove(tx_hash.as_slice())?;
         self.tx_metadata.remove(tx_hash.as_slice())?;
+        self.tx_witness.remove(tx_hash.as_slice())?;
 
         Ok(())
     }
@@ -201,6 +242,7 @@ impl TxIndex {
         self.tx_by_hash.clear()?;
         self.tx_by_block.clear()?;
         self.tx_metadata.clear()?;
+        self.tx_witness.clear()?;
         Ok(())
     }
 
@@ -213,20 +255,20 @@ impl TxIndex {
         tx_data.extend_from_slice(&tx.version.to_le_bytes());
 
         // Input count (varint)
-        tx_data.extend_from_slice(&Self::encode_varint(tx.inputs.len() as u64));
+        tx_data.extend_from_slice(&encode_varint(tx.inputs.len() as u64));
         for input in &tx.inputs {
             tx_data.extend_from_slice(&input.prevout.hash);
             tx_data.extend_from_slice(&input.prevout.index.to_le_bytes());
-            tx_data.extend_from_slice(&Self::encode_varint(input.script_sig.len() as u64));
+            tx_data.extend_from_slice(&encode_varint(input.script_sig.len() as u64));
             tx_data.extend_from_slice(&input.script_sig);
             tx_data.extend_from_slice(&input.sequence.to_le_bytes());
         }
 
         // Output count (varint)
-        tx_data.extend_from_slice(&Self::encode_varint(tx.outputs.len() as u64));
+        tx_data.extend_from_slice(&encode_varint(tx.outputs.len() as u64));
         for output in &tx.outputs {
             tx_data.extend_from_slice(&output.value.to_le_bytes());
-            tx_data.extend_from_slice(&Self::encode_varint(output.script_pubkey.len() as u64));
+            tx_data.extend_from_slice(&encode_varint(output.script_pubkey.len() as u64));
             tx_data.extend_from_slice(&output.script_pubkey);
         }
 
@@ -236,52 +278,6 @@ impl TxIndex {
         double_sha256(&tx_data)
     }
 
-    /// Encode integer as Bitcoin varint
-    fn encode_varint(value: u64) -> Vec<u8> {
-        if value < 0xfd {
-            vec![value as u8]
-        } else if value <= 0xffff {
-            let mut result = vec![0xfd];
-            result.extend_from_slice(&(value as u16).to_le_bytes());
-            result
-        } else if value <= 0xffffffff {
-            let mut result = vec![0xfe];
-            result.extend_from_slice(&(value as u32).to_le_bytes());
-            result
-        } else {
-            let mut result = vec![0xff];
-            result.extend_from_slice(&value.to_le_bytes());
-            result
-        }
-    }
-
-    /// Calculate transaction size
-    fn calculate_tx_size(&self, tx: &Transaction) -> u32 {
-        // Simplified size calculation
-        let mut size = 4; // version
-        size += 1; // input count
-        for input in &tx.inputs {
-            size += 32; // previous output
-            size += 1; // script length
-            size += input.script_sig.len() as u32;
-            size += 4; // sequence
-        }
-        size += 1; // output count
-        for output in &tx.outputs {
-            size += 8; // value
-            size += 1; // script length
-            size += output.script_pubkey.len() as u32;
-        }
-        size += 4; // lock time
-        size
-    }
-
-    /// Calculate transaction weight
-    fn calculate_tx_weight(&self, tx: &Transaction) -> u32 {
-        // Simplified weight calculation (4x for witness data)
-        self.calculate_tx_size(tx) * 4
-    }
-
     /// Create block transaction key
     fn block_tx_key(&self, block_hash: &Hash, tx_index: u32) -> Vec<u8> {
         let mut key = Vec::new();
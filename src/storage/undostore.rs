@@ -0,0 +1,116 @@
+//! Block undo data storage
+//!
+//! For each connected block, records the coins it spent (so they can be
+//! restored) and the outpoints it created (so they can be removed). Undo
+//! records are written in the same batch as the block's UTXO changes, so the
+//! UTXO set can always be rolled back to the parent block. The same batch
+//! records which block the UTXO set currently reflects, so a crash between
+//! updating the UTXO set and the chain tip can be detected on restart.
+
+use crate::storage::database::{Database, Tree, WriteBatch};
+use anyhow::Result;
+use bllvm_protocol::{Hash, OutPoint, UTXO};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Tree holding undo records (block hash → `BlockUndo`)
+pub(crate) const BLOCK_UNDO_TREE: &str = "block_undo";
+
+/// Key of the UTXO tip record (distinct from the 32-byte block hash keys)
+const UTXO_TIP_KEY: &[u8] = b"utxo_tip";
+
+/// Block the persisted UTXO set reflects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoTip {
+    pub hash: Hash,
+    pub height: u64,
+}
+
+/// UTXO changes made by connecting a block
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockUndo {
+    /// Parent of the connected block
+    pub prev_block_hash: Hash,
+    /// Height the block was connected at
+    pub height: u64,
+    /// Coins spent by the block, in spend order
+    pub spent: Vec<(OutPoint, UTXO)>,
+    /// Spent coins that were coinbase outputs
+    pub spent_coinbase: Vec<OutPoint>,
+    /// Outpoints created by the block and still unspent after it
+    pub created: Vec<OutPoint>,
+}
+
+/// Undo record storage manager
+pub struct UndoStore {
+    #[allow(dead_code)]
+    db: Arc<dyn Database>,
+    undo: Arc<dyn Tree>,
+}
+
+impl UndoStore {
+    /// Create a new undo store
+    pub fn new(db: Arc<dyn Database>) -> Result<Self> {
+        let undo = Arc::from(db.open_tree(BLOCK_UNDO_TREE)?);
+        Ok(Self { db, undo })
+    }
+
+    /// Get the undo record for a connected block
+    pub fn get_undo(&self, block_hash: &Hash) -> Result<Option<BlockUndo>> {
+        if let Some(data) = self.undo.get(block_hash.as_slice())? {
+            let undo: BlockUndo = bincode::deserialize(&data)?;
+            Ok(Some(undo))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Check if a block has an undo record
+    pub fn has_undo(&self, block_hash: &Hash) -> Result<bool> {
+        self.undo.contains_key(block_hash.as_slice())
+    }
+
+    /// Queue storing an undo record in a write batch
+    pub fn batch_put_undo(
+        &self,
+        batch: &mut WriteBatch,
+        block_hash: &Hash,
+        undo: &BlockUndo,
+    ) -> Result<()> {
+        batch.insert(
+            BLOCK_UNDO_TREE,
+            block_hash.as_slice(),
+            &bincode::serialize(undo)?,
+        );
+        Ok(())
+    }
+
+    /// Get the block the persisted UTXO set reflects, if recorded
+    pub fn get_utxo_tip(&self) -> Result<Option<UtxoTip>> {
+        if let Some(data) = self.undo.get(UTXO_TIP_KEY)? {
+            let tip: UtxoTip = bincode::deserialize(&data)?;
+            Ok(Some(tip))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Queue updating the UTXO tip in a write batch (`None` clears it)
+    pub fn batch_set_utxo_tip(&self, batch: &mut WriteBatch, tip: Option<&UtxoTip>) -> Result<()> {
+        match tip {
+            Some(tip) => batch.insert(BLOCK_UNDO_TREE, UTXO_TIP_KEY, &bincode::serialize(tip)?),
+            None => batch.remove(BLOCK_UNDO_TREE, UTXO_TIP_KEY),
+        }
+        Ok(())
+    }
+
+    /// Remove every undo record and the UTXO tip
+    pub fn clear(&self) -> Result<()> {
+        self.undo.clear()
+    }
+
+    /// Queue removing an undo record in a write batch
+    pub fn batch_remove_undo(&self, batch: &mut WriteBatch, block_hash: &Hash) {
+        batch.remove(BLOCK_UNDO_TREE, block_hash.as_slice());
+    }
+}
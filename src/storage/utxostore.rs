@@ -2,30 +2,39 @@
 //!
 //! Stores and manages the UTXO set for efficient transaction validation.
 
-use crate::storage::database::{Database, Tree};
+use crate::storage::database::{Database, Tree, WriteBatch};
 use anyhow::Result;
 use bllvm_protocol::{OutPoint, UtxoSet, UTXO};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Tree holding the UTXO set
+pub(crate) const UTXOS_TREE: &str = "utxos";
+
+/// Tree marking which UTXOs were created by a coinbase transaction
+pub(crate) const COINBASE_OUTPUTS_TREE: &str = "coinbase_outputs";
+
 /// UTXO set storage manager
 pub struct UtxoStore {
     #[allow(dead_code)]
     db: Arc<dyn Database>,
     utxos: Arc<dyn Tree>,
     spent_outputs: Arc<dyn Tree>,
+    coinbase_outputs: Arc<dyn Tree>,
 }
 
 impl UtxoStore {
     /// Create a new UTXO store
     pub fn new(db: Arc<dyn Database>) -> Result<Self> {
-        let utxos = Arc::from(db.open_tree("utxos")?);
+        let utxos = Arc::from(db.open_tree(UTXOS_TREE)?);
         let spent_outputs = Arc::from(db.open_tree("spent_outputs")?);
+        let coinbase_outputs = Arc::from(db.open_tree(COINBASE_OUTPUTS_TREE)?);
 
         Ok(Self {
             db,
             utxos,
             spent_outputs,
+            coinbase_outputs,
         })
     }
 
@@ -44,6 +53,14 @@ impl UtxoStore {
         Ok(())
     }
 
+    /// Remove every UTXO and coinbase marker
+    pub fn clear(&self) -> Result<()> {
+        self.utxos.clear()?;
+        self.spent_outputs.clear()?;
+        self.coinbase_outputs.clear()?;
+        Ok(())
+    }
+
     /// Load the entire UTXO set
     pub fn load_utxo_set(&self) -> Result<UtxoSet> {
         let mut utxo_set = HashMap::new();
@@ -70,9 +87,64 @@ impl UtxoStore {
     pub fn remove_utxo(&self, outpoint: &OutPoint) -> Result<()> {
         let key = self.outpoint_key(outpoint);
         self.utxos.remove(&key)?;
+        self.coinbase_outputs.remove(&key)?;
         Ok(())
     }
 
+    /// Queue adding a UTXO to a write batch
+    pub fn batch_add_utxo(
+        &self,
+        batch: &mut WriteBatch,
+        outpoint: &OutPoint,
+        utxo: &UTXO,
+    ) -> Result<()> {
+        batch.insert(
+            UTXOS_TREE,
+            &self.outpoint_key(outpoint),
+            &bincode::serialize(utxo)?,
+        );
+        Ok(())
+    }
+
+    /// Queue removing a UTXO (and its coinbase mark) in a write batch
+    pub fn batch_remove_utxo(&self, batch: &mut WriteBatch, outpoint: &OutPoint) {
+        let key = self.outpoint_key(outpoint);
+        batch.remove(UTXOS_TREE, &key);
+        batch.remove(COINBASE_OUTPUTS_TREE, &key);
+    }
+
+    /// Queue marking a UTXO as a coinbase output in a write batch
+    pub fn batch_mark_coinbase(&self, batch: &mut WriteBatch, outpoint: &OutPoint) {
+        batch.insert(COINBASE_OUTPUTS_TREE, &self.outpoint_key(outpoint), &[]);
+    }
+
+    /// Check if a UTXO was created by a coinbase transaction
+    pub fn is_coinbase(&self, outpoint: &OutPoint) -> Result<bool> {
+        let key = self.outpoint_key(outpoint);
+        self.coinbase_outputs.contains_key(&key)
+    }
+
+    /// [`Self::is_coinbase`] for coinbase maturity checks
+    ///
+    /// A failed lookup counts as a coinbase output, so a storage error can
+    /// only reject a spend as premature, never let an immature one through.
+    pub fn is_coinbase_or_unknown(&self, outpoint: &OutPoint) -> bool {
+        self.is_coinbase(outpoint).unwrap_or_else(|e| {
+            tracing::warn!("Coinbase lookup for {:?} failed: {}", outpoint, e);
+            true
+        })
+    }
+
+    /// Get all unspent coinbase outputs
+    pub fn get_coinbase_outputs(&self) -> Result<HashSet<OutPoint>> {
+        let mut outpoints = HashSet::new();
+        for result in self.coinbase_outputs.iter() {
+            let (key, _) = result?;
+            outpoints.insert(self.outpoint_from_key(&key)?);
+        }
+        Ok(outpoints)
+    }
+
     /// Get a UTXO by outpoint
     pub fn get_utxo(&self, outpoint: &OutPoint) -> Result<Option<UTXO>> {
         let key = self.outpoint_key(outpoint);
@@ -21,8 +21,30 @@
 //!
 //! init_module_logging("my_module", None); // Module gets its own filter
 //! ```
+//!
+//! ## Log File
+//! Setting `LoggingConfig::file` additionally writes logs to a rotating file
+//! from a background thread (see [`init_logging_with_file`]).
+
+use crate::config::{LogFileConfig, LogRotation};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+/// Guard of the log file writer installed by [`init_logging_with_file`]
+static LOG_FILE_GUARD: OnceLock<LogFileGuard> = OnceLock::new();
 
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+/// Filter handle installed by the node's logging initializers
+static LOG_FILTER: OnceLock<Mutex<LogFilterHandle>> = OnceLock::new();
 
 /// Initialize logging for the main node
 ///
@@ -30,7 +52,7 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 /// - Respects RUST_LOG environment variable (standard practice)
 /// - Falls back to config filter if provided
 /// - Defaults to "info" level
-/// - Uses `EnvFilter::from_default_env()` for proper RUST_LOG handling
+/// - Filter can be changed at runtime through [`log_filter`] (the `logging` RPC)
 ///
 /// # Arguments
 /// * `filter` - Optional log filter from config (e.g., "info", "debug", "bllvm_node=debug,network=trace")
@@ -47,37 +69,22 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 /// init_logging(Some("debug"));
 /// ```
 pub fn init_logging(filter: Option<&str>) {
-    // Standard practice: Use EnvFilter::from_default_env() which properly handles RUST_LOG
-    // This respects the standard RUST_LOG environment variable format
-    let mut env_filter = EnvFilter::from_default_env();
-
-    // If config provides a filter and RUST_LOG is not set, use config filter
-    // RUST_LOG always takes precedence (standard practice)
-    if filter.is_some() && std::env::var("RUST_LOG").is_err() {
-        if let Some(f) = filter {
-            env_filter = EnvFilter::new(f);
-        }
-    }
-
-    // If neither RUST_LOG nor config filter is set, default to "info"
-    if std::env::var("RUST_LOG").is_err() && filter.is_none() {
-        env_filter = EnvFilter::new("info");
-    }
-
     // Standard setup following Rust logging best practices:
     // - Human-readable format (default)
     // - Output to stderr (standard for logs)
     // - Include target (module path) for better debugging
     // - Thread IDs disabled by default (can be noisy)
     // - ANSI colors enabled (can be disabled via NO_COLOR env var)
+    // - Reloadable filter so the `logging` RPC can change it at runtime
     tracing_subscriber::registry()
+        .with(reloadable_filter(filter))
         .with(
             fmt::layer()
                 .with_target(true) // Include module path - useful for debugging
                 .with_thread_ids(false) // Disable by default (can be noisy)
-                .with_ansi(!std::env::var("NO_COLOR").is_ok()), // Respect NO_COLOR standard
+                .with_ansi(!std::env::var("NO_COLOR").is_ok()) // Respect NO_COLOR standard
+                .with_writer(io::stderr),
         )
-        .with(env_filter)
         .init();
 }
 
@@ -166,34 +173,20 @@ pub fn init_module_logging(module_name: &str, filter: Option<&str>) {
 /// ```
 #[cfg(feature = "json-logging")]
 pub fn init_json_logging(filter: Option<&str>) {
-    // Standard practice: Use EnvFilter::from_default_env() for RUST_LOG
-    let mut env_filter = EnvFilter::from_default_env();
-
-    // If config provides a filter and RUST_LOG is not set, use config filter
-    if filter.is_some() && std::env::var("RUST_LOG").is_err() {
-        if let Some(f) = filter {
-            env_filter = EnvFilter::new(f);
-        }
-    }
-
-    // If neither RUST_LOG nor config filter is set, default to "info"
-    if std::env::var("RUST_LOG").is_err() && filter.is_none() {
-        env_filter = EnvFilter::new("info");
-    }
-
     // JSON logging for structured logging (production/monitoring):
     // - JSON format for log aggregation systems
     // - Include target, spans, and span lists for full context
     // - Standard structured logging practice
     tracing_subscriber::registry()
+        .with(reloadable_filter(filter))
         .with(
             fmt::layer()
                 .json()
                 .with_target(true) // Include module path in JSON
                 .with_current_span(true) // Include current span context
-                .with_span_list(true), // Include span list for full context
+                .with_span_list(true) // Include span list for full context
+                .with_writer(io::stderr),
         )
-        .with(env_filter)
         .init();
 }
 
@@ -215,6 +208,21 @@ pub fn init_json_logging(filter: Option<&str>) {
 /// ```
 pub fn init_logging_from_config(config: Option<&crate::config::LoggingConfig>) {
     let filter = config.and_then(|c| c.filter.as_deref());
+    let json_format = config.map(|c| c.json_format).unwrap_or(false);
+
+    if let Some(file) = config.and_then(|c| c.file.as_ref()) {
+        match init_logging_with_file(filter, json_format, file) {
+            Ok(()) => return,
+            Err(e) => {
+                // Logging is not up yet, so report on stderr
+                eprintln!(
+                    "Failed to open log file {}: {}; logging to stderr only",
+                    file.path.display(),
+                    e
+                );
+            }
+        }
+    }
 
     if config.map(|c| c.json_format).unwrap_or(false) {
         #[cfg(feature = "json-logging")]
@@ -231,6 +239,393 @@ pub fn init_logging_from_config(config: Option<&crate::config::LoggingConfig>) {
     }
 }
 
+/// Initialize logging to stderr and a rotating log file
+///
+/// Both sinks use the same filter and format (the file without ANSI colors).
+/// The file is written from a background thread; call [`flush_log_file`]
+/// before exiting so buffered lines reach the disk.
+pub fn init_logging_with_file(
+    filter: Option<&str>,
+    json_format: bool,
+    file: &LogFileConfig,
+) -> io::Result<()> {
+    type BoxedLayer = Box<dyn Layer<Layered<FilterLayer, Registry>> + Send + Sync>;
+
+    let (writer, guard) = non_blocking(RotatingFileWriter::new(file)?);
+    let _ = LOG_FILE_GUARD.set(guard);
+
+    let ansi = std::env::var("NO_COLOR").is_err();
+    #[cfg(feature = "json-logging")]
+    let layers: Vec<BoxedLayer> = if json_format {
+        vec![
+            fmt::layer()
+                .json()
+                .with_target(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(io::stderr)
+                .boxed(),
+            fmt::layer()
+                .json()
+                .with_target(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_writer(writer)
+                .boxed(),
+        ]
+    } else {
+        vec![
+            fmt::layer()
+                .with_target(true)
+                .with_ansi(ansi)
+                .with_writer(io::stderr)
+                .boxed(),
+            fmt::layer()
+                .with_target(true)
+                .with_ansi(false)
+                .with_writer(writer)
+                .boxed(),
+        ]
+    };
+    #[cfg(not(feature = "json-logging"))]
+    let layers: Vec<BoxedLayer> = {
+        // JSON output needs the json-logging feature; fall back to text
+        let _ = json_format;
+        vec![
+            fmt::layer()
+                .with_target(true)
+                .with_ansi(ansi)
+                .with_writer(io::stderr)
+                .boxed(),
+            fmt::layer()
+                .with_target(true)
+                .with_ansi(false)
+                .with_writer(writer)
+                .boxed(),
+        ]
+    };
+
+    tracing_subscriber::registry()
+        .with(reloadable_filter(filter))
+        .with(layers)
+        .init();
+    Ok(())
+}
+
+/// Wait until everything logged so far has been written to the log file
+///
+/// No-op if file logging is not enabled.
+pub fn flush_log_file() {
+    if let Some(guard) = LOG_FILE_GUARD.get() {
+        guard.flush();
+    }
+}
+
+/// Base filter directives: RUST_LOG if set (standard practice, takes
+/// precedence), otherwise the config filter, otherwise "info"
+fn filter_directives(filter: Option<&str>) -> String {
+    std::env::var("RUST_LOG").unwrap_or_else(|_| filter.unwrap_or("info").to_string())
+}
+
+/// Build the node's filter and register it for runtime changes
+fn reloadable_filter(filter: Option<&str>) -> FilterLayer {
+    let (layer, handle) = LogFilterHandle::new(filter_directives(filter));
+    let _ = LOG_FILTER.set(Mutex::new(handle));
+    layer
+}
+
+/// Reloadable filter layer installed at the bottom of the node's subscriber
+pub type FilterLayer = reload::Layer<EnvFilter, Registry>;
+
+/// Log categories (Bitcoin Core `logging` names) and the targets they cover
+pub const LOG_CATEGORIES: &[(&str, &[&str])] = &[
+    ("db", &["bllvm_node::storage"]),
+    ("mempool", &["bllvm_node::node::mempool"]),
+    ("mining", &["bllvm_node::node::miner"]),
+    ("module", &["bllvm_node::module"]),
+    ("net", &["bllvm_node::network"]),
+    ("rpc", &["bllvm_node::rpc"]),
+    ("sync", &["bllvm_node::node::sync"]),
+    (
+        "validation",
+        &[
+            "bllvm_node::validation",
+            "bllvm_node::node::block_processor",
+        ],
+    ),
+];
+
+/// Handle to the node's log filter for per-category overrides
+///
+/// Categories are logged at their override level on top of the base filter;
+/// disabling a category drops the override.
+pub struct LogFilterHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    base: String,
+    overrides: BTreeMap<&'static str, LevelFilter>,
+}
+
+impl LogFilterHandle {
+    /// Create a reloadable filter from base directives
+    pub fn new(base: String) -> (FilterLayer, Self) {
+        let (layer, handle) = reload::Layer::new(EnvFilter::new(&base));
+        (
+            layer,
+            Self {
+                handle,
+                base,
+                overrides: BTreeMap::new(),
+            },
+        )
+    }
+
+    /// Log `category` at `level`, or drop its override when `level` is `None`
+    ///
+    /// `category` may be "all" to change every category.
+    pub fn set_category(
+        &mut self,
+        category: &str,
+        level: Option<LevelFilter>,
+    ) -> Result<(), String> {
+        let names: Vec<&'static str> = if category == "all" {
+            LOG_CATEGORIES.iter().map(|(name, _)| *name).collect()
+        } else {
+            let (name, _) = LOG_CATEGORIES
+                .iter()
+                .find(|(name, _)| *name == category)
+                .ok_or_else(|| format!("unknown logging category {category}"))?;
+            vec![*name]
+        };
+        for name in names {
+            match level {
+                Some(level) => self.overrides.insert(name, level),
+                None => self.overrides.remove(name),
+            };
+        }
+        self.handle
+            .reload(EnvFilter::new(self.directives()))
+            .map_err(|e| format!("failed to reload log filter: {e}"))
+    }
+
+    /// Whether each category currently has an override
+    pub fn categories(&self) -> BTreeMap<&'static str, bool> {
+        LOG_CATEGORIES
+            .iter()
+            .map(|(name, _)| (*name, self.overrides.contains_key(name)))
+            .collect()
+    }
+
+    /// Current filter directives (base plus category overrides)
+    pub fn directives(&self) -> String {
+        let mut directives = self.base.clone();
+        for (name, level) in &self.overrides {
+            let (_, targets) = LOG_CATEGORIES
+                .iter()
+                .find(|(category, _)| category == name)
+                .expect("overrides only hold known categories");
+            for target in *targets {
+                directives.push_str(&format!(",{target}={level}"));
+            }
+        }
+        directives
+    }
+}
+
+/// The node's log filter, if logging was initialized by this module
+pub fn log_filter() -> Option<&'static Mutex<LogFilterHandle>> {
+    LOG_FILTER.get()
+}
+
+/// Log file writer that rotates according to a [`LogRotation`] policy
+///
+/// The active file lives at the configured path. On rotation it is renamed to
+/// `<path>.1`, older files shift up by one, and files beyond `max_files` are
+/// deleted.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    rotation: LogRotation,
+    max_files: usize,
+    file: File,
+    size: u64,
+    day: u64,
+}
+
+impl RotatingFileWriter {
+    /// Open (or append to) the active log file, creating parent directories
+    pub fn new(config: &LogFileConfig) -> io::Result<Self> {
+        if let Some(parent) = config.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let file = Self::open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: config.path.clone(),
+            rotation: config.rotation,
+            max_files: config.max_files,
+            file,
+            size,
+            day: current_day(),
+        })
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Path of the `index`-th most recent rotated file
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn should_rotate(&self, incoming: usize) -> bool {
+        match self.rotation {
+            LogRotation::Daily => current_day() != self.day,
+            // Never rotate an empty file, so oversized lines still get written
+            LogRotation::Size { max_bytes } => {
+                self.size > 0 && self.size + incoming as u64 > max_bytes
+            }
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                fs::remove_file(oldest)?;
+            }
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = Self::open(&self.path)?;
+        self.size = 0;
+        self.day = current_day();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn current_day() -> u64 {
+    crate::utils::current_timestamp() / 86_400
+}
+
+enum LogCommand {
+    Write(Vec<u8>),
+    Flush(mpsc::Sender<()>),
+    Shutdown,
+}
+
+/// Writer that hands log lines to a background thread
+///
+/// Logging never blocks on disk I/O; lines written after the guard is dropped
+/// are discarded.
+#[derive(Clone)]
+pub struct NonBlockingWriter {
+    sender: mpsc::Sender<LogCommand>,
+}
+
+impl Write for NonBlockingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.sender.send(LogCommand::Write(buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlockingWriter {
+    type Writer = NonBlockingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Owns the background writer thread; flushes and stops it when dropped
+pub struct LogFileGuard {
+    sender: mpsc::Sender<LogCommand>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl LogFileGuard {
+    /// Block until every line sent so far has been written and flushed
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+        if self.sender.send(LogCommand::Flush(done_tx)).is_ok() {
+            let _ = done_rx.recv();
+        }
+    }
+}
+
+impl Drop for LogFileGuard {
+    fn drop(&mut self) {
+        let _ = self.sender.send(LogCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Move `writer` to a background thread, returning a cloneable handle to it
+pub fn non_blocking<W: Write + Send + 'static>(mut writer: W) -> (NonBlockingWriter, LogFileGuard) {
+    let (sender, receiver) = mpsc::channel();
+    let worker = std::thread::Builder::new()
+        .name("log-writer".to_string())
+        .spawn(move || {
+            for command in receiver {
+                match command {
+                    LogCommand::Write(buf) => {
+                        // Nowhere to report a failed log write
+                        let _ = writer.write_all(&buf);
+                    }
+                    LogCommand::Flush(done) => {
+                        let _ = writer.flush();
+                        let _ = done.send(());
+                    }
+                    LogCommand::Shutdown => break,
+                }
+            }
+            let _ = writer.flush();
+        })
+        .expect("failed to spawn log writer thread");
+    (
+        NonBlockingWriter {
+            sender: sender.clone(),
+        },
+        LogFileGuard {
+            sender,
+            worker: Some(worker),
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -257,4 +652,88 @@ mod tests {
         // If we can reference the functions, they compile correctly
         // This test will fail if function signatures change
     }
+
+    #[test]
+    fn test_size_rotation_starts_new_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = LogFileConfig {
+            path: dir.path().join("node.log"),
+            rotation: LogRotation::Size { max_bytes: 256 },
+            max_files: 2,
+        };
+        let (writer, guard) = non_blocking(RotatingFileWriter::new(&config).unwrap());
+        let subscriber =
+            tracing_subscriber::registry().with(fmt::layer().with_ansi(false).with_writer(writer));
+
+        // One line stays in the active file
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first line");
+            guard.flush();
+            assert!(!dir.path().join("node.log.1").exists());
+
+            for i in 0..40 {
+                tracing::info!("log line {}", i);
+            }
+        });
+        drop(guard);
+
+        let active = fs::read_to_string(dir.path().join("node.log")).unwrap();
+        let rotated = fs::read_to_string(dir.path().join("node.log.1")).unwrap();
+        assert!(active.contains("log line 39"));
+        assert!(!rotated.is_empty());
+        assert!(active.len() <= 256 && rotated.len() <= 256);
+        // Only max_files rotated files are kept
+        assert!(dir.path().join("node.log.2").exists());
+        assert!(!dir.path().join("node.log.3").exists());
+    }
+
+    #[test]
+    fn test_log_category_toggle() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Counts events that pass the filter
+        struct CountEvents(Arc<AtomicUsize>);
+
+        impl<S: tracing::Subscriber> Layer<S> for CountEvents {
+            fn on_event(
+                &self,
+                _event: &tracing::Event<'_>,
+                _ctx: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let (filter, mut handle) = LogFilterHandle::new("info".to_string());
+        let subscriber = tracing_subscriber::registry()
+            .with(filter)
+            .with(CountEvents(Arc::clone(&count)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let net_debug = || tracing::debug!(target: "bllvm_node::network::peer", "net debug");
+
+            net_debug();
+            assert_eq!(count.load(Ordering::SeqCst), 0);
+
+            handle
+                .set_category("net", Some(LevelFilter::DEBUG))
+                .unwrap();
+            net_debug();
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+            assert!(handle.categories()["net"]);
+
+            // Other categories keep the base level
+            tracing::debug!(target: "bllvm_node::rpc", "rpc debug");
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+            assert!(!handle.categories()["rpc"]);
+
+            handle.set_category("net", None).unwrap();
+            net_debug();
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+        });
+
+        assert!(handle.set_category("bogus", None).is_err());
+    }
 }
@@ -26,10 +26,13 @@ pub use error::{
 pub use lock::{try_with_lock_timeout, with_lock, with_read_lock, with_write_lock};
 #[cfg(feature = "json-logging")]
 pub use logging::init_json_logging;
-pub use logging::{init_logging, init_logging_from_config, init_module_logging};
+pub use logging::{
+    flush_log_file, init_logging, init_logging_from_config, init_logging_with_file,
+    init_module_logging,
+};
 pub use option::{map_or_default, option_to_result, or_else, unwrap_or_default_with};
 pub use retry::{retry_async_with_backoff, retry_with_backoff, RetryConfig};
-pub use signal::{create_shutdown_receiver, wait_for_shutdown_signal};
+pub use signal::{create_shutdown_receiver, wait_for_shutdown_signal, ShutdownController};
 pub use time::{current_timestamp, current_timestamp_duration};
 pub use timeout::{
     with_custom_timeout, with_network_timeout, with_rpc_timeout, with_storage_timeout,
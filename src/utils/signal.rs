@@ -2,7 +2,12 @@
 //!
 //! Provides signal handlers for SIGTERM, SIGINT, and other termination signals.
 
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::signal;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 use tracing::{info, warn};
 
 /// Wait for shutdown signal (SIGTERM, SIGINT, or Ctrl+C)
@@ -81,3 +86,139 @@ pub fn create_shutdown_receiver() -> tokio::sync::watch::Receiver<bool> {
 
     rx
 }
+
+/// Shared shutdown broadcast for the node's long-running tasks
+///
+/// Cloning yields another handle to the same broadcast. Tasks started with
+/// [`ShutdownController::spawn`] are cancelled when shutdown is triggered,
+/// and [`ShutdownController::wait_for_tasks`] waits (bounded) for them to
+/// finish.
+#[derive(Debug, Clone)]
+pub struct ShutdownController {
+    sender: Arc<watch::Sender<bool>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Default for ShutdownController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownController {
+    /// Create a controller that has not been triggered
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Request shutdown of every task observing this controller
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether shutdown has been requested
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Receiver that observes the shutdown flag
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.sender.subscribe()
+    }
+
+    /// Wait until shutdown is requested
+    pub async fn triggered(&self) {
+        let mut rx = self.subscribe();
+        let _ = rx.wait_for(|stop| *stop).await;
+    }
+
+    /// Spawn a task that is cancelled when shutdown is triggered
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut rx = self.subscribe();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = future => {}
+                _ = rx.wait_for(|stop| *stop) => {}
+            }
+        });
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(handle);
+    }
+
+    /// Number of spawned tasks that are still running
+    pub fn active_tasks(&self) -> usize {
+        let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.iter().filter(|task| !task.is_finished()).count()
+    }
+
+    /// Wait up to `timeout` for spawned tasks to finish, aborting the rest
+    ///
+    /// Returns `true` if every task finished in time.
+    pub async fn wait_for_tasks(&self, timeout: Duration) -> bool {
+        let handles: Vec<_> = {
+            let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+            tasks.drain(..).collect()
+        };
+        let aborts: Vec<_> = handles.iter().map(|h| h.abort_handle()).collect();
+        let finished = tokio::time::timeout(timeout, async {
+            for handle in handles {
+                let _ = handle.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !finished {
+            warn!("Shutdown timed out, aborting remaining tasks");
+            for abort in aborts {
+                abort.abort();
+            }
+        }
+        finished
+    }
+
+    /// Trigger shutdown when the process receives SIGINT, SIGTERM or Ctrl+C
+    pub fn listen_for_signals(&self) {
+        let controller = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = wait_for_shutdown_signal() => controller.trigger(),
+                _ = controller.triggered() => {}
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_controller_cancels_spawned_tasks() {
+        let shutdown = ShutdownController::new();
+        for _ in 0..3 {
+            shutdown.spawn(std::future::pending());
+        }
+        shutdown.spawn(async {});
+        tokio::task::yield_now().await;
+        assert_eq!(shutdown.active_tasks(), 3);
+        assert!(!shutdown.is_triggered());
+
+        let observer = shutdown.clone();
+        observer.trigger();
+        assert!(shutdown.is_triggered());
+        assert!(shutdown.wait_for_tasks(Duration::from_secs(5)).await);
+        assert_eq!(shutdown.active_tasks(), 0);
+
+        // Tasks spawned after the trigger stop immediately
+        shutdown.spawn(std::future::pending());
+        assert!(shutdown.wait_for_tasks(Duration::from_secs(5)).await);
+    }
+}
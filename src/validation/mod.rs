@@ -10,10 +10,17 @@
 //!
 //! Reference: Bitcoin Core's parallel block validation for IBD
 
+pub mod no_scripts;
+pub mod sigops;
+pub mod witness;
+
+use crate::utils::current_timestamp;
 use anyhow::Result;
 use bllvm_protocol::block::connect_block;
 use bllvm_protocol::segwit::Witness;
-use bllvm_protocol::{Block, UtxoSet, ValidationResult};
+use bllvm_protocol::{Block, BlockHeader, OutPoint, UtxoSet, ValidationResult};
+use no_scripts::{connect_block_without_scripts, ChainContext, ConsensusParams};
+use std::collections::HashSet;
 
 /// Block validation context
 #[derive(Debug, Clone)]
@@ -22,6 +29,60 @@ pub struct BlockValidationContext {
     pub height: u64,
     pub prev_utxo_set: UtxoSet,
     pub prev_block_hash: [u8; 32],
+    /// Witnesses of the block's transactions, one stack per input
+    pub witnesses: Vec<Vec<Witness>>,
+    /// Rules of the network the block belongs to
+    pub params: ConsensusParams,
+    /// Active chain headers up to the parent, oldest first; enough for
+    /// median time past and, at retarget heights, the difficulty period
+    pub prev_headers: Vec<BlockHeader>,
+    /// Coins in `prev_utxo_set` created by coinbase transactions (the UTXO
+    /// store's coinbase-outputs index)
+    pub coinbase_outputs: HashSet<OutPoint>,
+    /// Block is an ancestor of the assumed-valid block (skip script checks)
+    pub assume_valid: bool,
+}
+
+impl BlockValidationContext {
+    /// Connect the block on top of `prev_utxo_set`
+    fn connect(&self) -> Result<(ValidationResult, UtxoSet)> {
+        if self.assume_valid {
+            let context = ChainContext {
+                params: self.params,
+                header_at: &|height| self.header_at(height),
+                is_coinbase_output: &|outpoint| self.coinbase_outputs.contains(outpoint),
+                now: current_timestamp(),
+            };
+            return connect_block_without_scripts(
+                &self.block,
+                &self.witnesses,
+                self.prev_utxo_set.clone(),
+                self.height,
+                &context,
+            );
+        }
+        // Median time past needs the last 11 headers
+        let recent_headers = &self.prev_headers[self.prev_headers.len().saturating_sub(11)..];
+        connect_block(
+            &self.block,
+            &witness::consensus_witnesses(&self.witnesses),
+            self.prev_utxo_set.clone(),
+            self.height,
+            (!recent_headers.is_empty()).then_some(recent_headers),
+        )
+        .map_err(|e| anyhow::anyhow!("Block validation error: {}", e))
+    }
+
+    /// Header of the active chain at `height`, if within `prev_headers`
+    fn header_at(&self, height: u64) -> Option<BlockHeader> {
+        let back = self.height.checked_sub(height)?.checked_sub(1)?;
+        let index = self
+            .prev_headers
+            .len()
+            .checked_sub(1)?
+            .checked_sub(back as usize)?;
+        self.prev_headers.get(index).cloned()
+    }
 }
 
 /// Parallel block validator
@@ -49,21 +110,7 @@ impl ParallelBlockValidator {
         &self,
         context: &BlockValidationContext,
     ) -> Result<(ValidationResult, UtxoSet)> {
-        // Create empty witnesses for each transaction
-        let witnesses: Vec<Witness> = context
-            .block
-            .transactions
-            .iter()
-            .map(|_| Vec::new())
-            .collect();
-        connect_block(
-            &context.block,
-            &witnesses,
-            context.prev_utxo_set.clone(),
-            context.height,
-            None, // No recent headers for single block validation
-        )
-        .map_err(|e| anyhow::anyhow!("Block validation error: {}", e))
+        context.connect()
     }
 
     /// Validate multiple blocks in parallel (Phase 4.2)
@@ -92,49 +139,14 @@ impl ParallelBlockValidator {
             use rayon::prelude::*;
             contexts
                 .par_iter()
-                .map(|context| {
-                    // Create empty witnesses for each transaction
-                    let witnesses: Vec<Witness> = context
-                        .block
-                        .transactions
-                        .iter()
-                        .map(|_| Vec::new())
-                        .collect();
-                    connect_block(
-                        &context.block,
-                        &witnesses,
-                        context.prev_utxo_set.clone(),
-                        context.height,
-                        None, // No recent headers for parallel validation
-                    )
-                    .map_err(|e| anyhow::anyhow!("Block validation error: {}", e))
-                })
+                .map(|context| context.connect())
                 .collect()
         };
 
         #[cfg(not(feature = "production"))]
         let results: Vec<_> = {
             // Fallback to sequential validation if production feature not enabled
-            contexts
-                .iter()
-                .map(|context| {
-                    // Create empty witnesses for each transaction
-                    let witnesses: Vec<Witness> = context
-                        .block
-                        .transactions
-                        .iter()
-                        .map(|_| Vec::new())
-                        .collect();
-                    connect_block(
-                        &context.block,
-                        &witnesses,
-                        context.prev_utxo_set.clone(),
-                        context.height,
-                        None, // No recent headers for parallel validation
-                    )
-                    .map_err(|e| anyhow::anyhow!("Block validation error: {}", e))
-                })
-                .collect()
+            contexts.iter().map(|context| context.connect()).collect()
         };
 
         // Collect results and check for errors
@@ -153,21 +165,7 @@ impl ParallelBlockValidator {
         let mut results = Vec::new();
 
         for context in contexts {
-            // Create empty witnesses for each transaction
-            let witnesses: Vec<Witness> = context
-                .block
-                .transactions
-                .iter()
-                .map(|_| Vec::new())
-                .collect();
-            let result = connect_block(
-                &context.block,
-                &witnesses,
-                context.prev_utxo_set.clone(),
-                context.height,
-                None, // No recent headers for sequential validation
-            )
-            .map_err(|e| anyhow::anyhow!("Block validation error: {}", e))?;
+            let result = context.connect()?;
             results.push(result);
         }
 
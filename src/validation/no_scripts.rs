@@ -0,0 +1,672 @@
+//! Block connection without script verification (`assumevalid`)
+//!
+//! Blocks below the assumed-valid block skip script and signature checks
+//! only. Every other consensus rule is enforced here: proof of work and the
+//! header's context, the coinbase rules, transaction finality and sequence
+//! locks, amounts, coinbase maturity, BIP 30, the sigop and weight limits and
+//! the witness commitment.
+
+use crate::validation::sigops::{sigops_cost, witness_sigops_cost};
+use crate::validation::witness::{
+    check_witness_commitment, encode_varint, has_witness, transaction_weight, transaction_witnesses,
+};
+use anyhow::Result;
+use bllvm_protocol::block::calculate_tx_id;
+use bllvm_protocol::mining::calculate_merkle_root;
+use bllvm_protocol::{
+    segwit::Witness, Block, BlockHeader, ConsensusProof, Hash, OutPoint, Transaction, UtxoSet,
+    ValidationResult, UTXO,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Confirmations a coinbase output needs before it can be spent
+pub const COINBASE_MATURITY: u64 = 100;
+
+/// Maximum block weight (BIP 141)
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// Maximum signature operation cost of a block (BIP 141)
+pub const MAX_BLOCK_SIGOPS_COST: u64 = 80_000;
+
+/// Maximum amount of any output or sum of outputs, in satoshis
+pub const MAX_MONEY: i64 = 21_000_000 * 100_000_000;
+
+/// How far a block timestamp may be ahead of the current time
+const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
+/// Blocks in the median time past window (BIP 113)
+const MEDIAN_TIME_SPAN: u64 = 11;
+
+const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 2016;
+const TARGET_TIMESPAN: u64 = 14 * 24 * 60 * 60;
+
+/// Lock times below this are heights, above it timestamps
+const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+
+const SEQUENCE_FINAL: u64 = 0xffffffff;
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: u64 = 1 << 31;
+const SEQUENCE_LOCKTIME_TYPE_FLAG: u64 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u64 = 0x0000ffff;
+const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
+/// Blocks that duplicate an earlier coinbase before BIP 34 (mainnet)
+const BIP30_EXCEPTION_HEIGHTS: [u64; 2] = [91842, 91880];
+
+/// How a network sets the proof-of-work target of each block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifficultyRules {
+    /// Retarget every 2016 blocks, never above `pow_limit` (compact bits)
+    Retarget { pow_limit: u32 },
+    /// Every block keeps the target of its parent (regtest)
+    Fixed,
+    /// Minimum-difficulty blocks are allowed, so the target is not checked
+    Unchecked,
+}
+
+/// Buried deployment heights and difficulty rules of a network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsensusParams {
+    pub bip34_height: u64,
+    pub bip66_height: u64,
+    pub bip65_height: u64,
+    /// Relative lock times and median time past lock times (BIP 68, 112, 113)
+    pub csv_height: u64,
+    pub segwit_height: u64,
+    pub difficulty: DifficultyRules,
+}
+
+impl ConsensusParams {
+    pub const MAINNET: Self = Self {
+        bip34_height: 227_931,
+        bip66_height: 363_725,
+        bip65_height: 388_381,
+        csv_height: 419_328,
+        segwit_height: 481_824,
+        difficulty: DifficultyRules::Retarget {
+            pow_limit: 0x1d00ffff,
+        },
+    };
+    pub const TESTNET3: Self = Self {
+        bip34_height: 21_111,
+        bip66_height: 330_776,
+        bip65_height: 581_885,
+        csv_height: 770_112,
+        segwit_height: 834_624,
+        difficulty: DifficultyRules::Unchecked,
+    };
+    pub const TESTNET4: Self = Self {
+        bip34_height: 1,
+        bip66_height: 1,
+        bip65_height: 1,
+        csv_height: 1,
+        segwit_height: 1,
+        difficulty: DifficultyRules::Unchecked,
+    };
+    pub const SIGNET: Self = Self {
+        bip34_height: 1,
+        bip66_height: 1,
+        bip65_height: 1,
+        csv_height: 1,
+        segwit_height: 1,
+        difficulty: DifficultyRules::Retarget {
+            pow_limit: 0x1e0377ae,
+        },
+    };
+    pub const REGTEST: Self = Self {
+        bip34_height: 1,
+        bip66_height: 1,
+        bip65_height: 1,
+        csv_height: 1,
+        segwit_height: 0,
+        difficulty: DifficultyRules::Fixed,
+    };
+}
+
+/// The active chain below a block being connected
+///
+/// Rules that need chain history are skipped where a lookup returns `None`,
+/// as the protocol engine skips median time past checks without headers.
+pub struct ChainContext<'a> {
+    pub params: ConsensusParams,
+    /// Header of the active chain at a height
+    pub header_at: &'a dyn Fn(u64) -> Option<BlockHeader>,
+    /// Whether a coin was created by a coinbase transaction (the UTXO
+    /// store's coinbase-outputs index, see
+    /// [`crate::storage::utxostore::UtxoStore::is_coinbase_or_unknown`])
+    pub is_coinbase_output: &'a dyn Fn(&OutPoint) -> bool,
+    /// Current time, for the limit on future block timestamps
+    pub now: u64,
+}
+
+impl ChainContext<'_> {
+    /// Median timestamp of up to 11 blocks ending at `height`
+    fn median_time_past(&self, height: u64) -> Option<u64> {
+        let mut timestamps: Vec<u64> = (0..MEDIAN_TIME_SPAN)
+            .map_while(|back| height.checked_sub(back))
+            .map_while(self.header_at)
+            .map(|header| header.timestamp)
+            .collect();
+        timestamps.sort_unstable();
+        timestamps.get(timestamps.len() / 2).copied()
+    }
+}
+
+/// Connect a block without script or signature verification
+///
+/// Enforces every consensus rule except script execution. The UTXO set is
+/// returned unchanged if the block is invalid.
+pub fn connect_block_without_scripts(
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    mut utxo_set: UtxoSet,
+    height: u64,
+    context: &ChainContext<'_>,
+) -> Result<(ValidationResult, UtxoSet)> {
+    match check_block_without_scripts(block, witnesses, &utxo_set, height, context)? {
+        Ok((spent, created)) => {
+            for outpoint in &spent {
+                utxo_set.remove(outpoint);
+            }
+            utxo_set.extend(created);
+            Ok((ValidationResult::Valid, utxo_set))
+        }
+        Err(reason) => Ok((ValidationResult::Invalid(reason), utxo_set)),
+    }
+}
+
+/// Minimal script push of a block height (the BIP 34 coinbase prefix)
+pub fn script_num_push(height: u64) -> Vec<u8> {
+    match height {
+        0 => vec![0x00],
+        // OP_1 .. OP_16
+        1..=16 => vec![0x50 + height as u8],
+        _ => {
+            let mut bytes = Vec::new();
+            let mut n = height;
+            while n > 0 {
+                bytes.push((n & 0xff) as u8);
+                n >>= 8;
+            }
+            // Keep the number positive
+            if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+                bytes.push(0x00);
+            }
+            let mut push = vec![bytes.len() as u8];
+            push.extend(bytes);
+            push
+        }
+    }
+}
+
+type UtxoChanges = (HashSet<OutPoint>, HashMap<OutPoint, UTXO>);
+
+fn check_block_without_scripts(
+    block: &Block,
+    witnesses: &[Vec<Witness>],
+    utxo_set: &UtxoSet,
+    height: u64,
+    context: &ChainContext<'_>,
+) -> Result<std::result::Result<UtxoChanges, String>> {
+    let params = &context.params;
+    if !bllvm_protocol::pow::check_proof_of_work(&block.header)? {
+        return Ok(Err("high-hash".to_string()));
+    }
+    if let Err(reason) = check_header_context(&block.header, height, context) {
+        return Ok(Err(reason));
+    }
+    let Some((coinbase, transactions)) = block.transactions.split_first() else {
+        return Ok(Err("bad-blk-length".to_string()));
+    };
+    if calculate_merkle_root(&block.transactions)? != block.header.merkle_root {
+        return Ok(Err("bad-txnmrklroot".to_string()));
+    }
+    for tx in block.transactions.iter() {
+        if tx.inputs.is_empty() {
+            return Ok(Err("bad-txns-vin-empty".to_string()));
+        }
+        if tx.outputs.is_empty() {
+            return Ok(Err("bad-txns-vout-empty".to_string()));
+        }
+    }
+    if !is_coinbase(coinbase) {
+        return Ok(Err("bad-cb-missing".to_string()));
+    }
+    if !(2..=100).contains(&coinbase.inputs[0].script_sig.len()) {
+        return Ok(Err("bad-cb-length".to_string()));
+    }
+    if height >= params.bip34_height
+        && !coinbase.inputs[0]
+            .script_sig
+            .starts_with(&script_num_push(height))
+    {
+        return Ok(Err("bad-cb-height".to_string()));
+    }
+
+    let header_weight = (80 + encode_varint(block.transactions.len() as u64).len() as u64) * 4;
+    let weight = block
+        .transactions
+        .iter()
+        .enumerate()
+        .fold(header_weight, |sum, (index, tx)| {
+            sum.saturating_add(transaction_weight(
+                tx,
+                transaction_witnesses(witnesses, index),
+            ))
+        });
+    if weight > MAX_BLOCK_WEIGHT {
+        return Ok(Err("bad-blk-weight".to_string()));
+    }
+    if height >= params.segwit_height {
+        if let Err(reason) = check_witness_commitment(block, witnesses) {
+            return Ok(Err(reason));
+        }
+    } else if witnesses.iter().any(|stacks| has_witness(stacks)) {
+        return Ok(Err("unexpected-witness".to_string()));
+    }
+
+    // Lock times are checked against the median time past once BIP 113 is active
+    let prev_median_time = height
+        .checked_sub(1)
+        .and_then(|prev| context.median_time_past(prev));
+    let lock_time_cutoff = match prev_median_time {
+        Some(median_time) if height >= params.csv_height => median_time,
+        _ => block.header.timestamp,
+    };
+    let enforce_bip30 = height < params.bip34_height && !BIP30_EXCEPTION_HEIGHTS.contains(&height);
+
+    let mut spent = HashSet::new();
+    let mut created: HashMap<OutPoint, UTXO> = HashMap::new();
+    let mut fees: i64 = 0;
+    let mut sigops = sigops_cost(coinbase, &UtxoSet::new());
+    for (index, tx) in transactions.iter().enumerate() {
+        if is_coinbase(tx) {
+            return Ok(Err("bad-cb-multiple".to_string()));
+        }
+        if !is_final(tx, height, lock_time_cutoff) {
+            return Ok(Err("bad-txns-nonfinal".to_string()));
+        }
+        let mut coins = UtxoSet::new();
+        let mut input_value: i64 = 0;
+        for input in tx.inputs.iter() {
+            if !spent.insert(input.prevout.clone()) {
+                return Ok(Err("bad-txns-inputs-missingorspent".to_string()));
+            }
+            let Some(coin) = created
+                .remove(&input.prevout)
+                .or_else(|| utxo_set.get(&input.prevout).cloned())
+            else {
+                return Ok(Err("bad-txns-inputs-missingorspent".to_string()));
+            };
+            if height.saturating_sub(coin.height) < COINBASE_MATURITY
+                && (context.is_coinbase_output)(&input.prevout)
+            {
+                return Ok(Err("bad-txns-premature-spend-of-coinbase".to_string()));
+            }
+            if !money_range(coin.value) {
+                return Ok(Err("bad-txns-inputvalues-outofrange".to_string()));
+            }
+            input_value = input_value.saturating_add(coin.value);
+            coins.insert(input.prevout.clone(), coin);
+        }
+        if !money_range(input_value) {
+            return Ok(Err("bad-txns-inputvalues-outofrange".to_string()));
+        }
+        if height >= params.csv_height
+            && !sequence_locks_satisfied(tx, &coins, height, prev_median_time, context)
+        {
+            return Ok(Err("bad-txns-nonfinal".to_string()));
+        }
+        let output_value = match check_outputs(tx) {
+            Ok(value) => value,
+            Err(reason) => return Ok(Err(reason)),
+        };
+        if output_value > input_value {
+            return Ok(Err("bad-txns-in-belowout".to_string()));
+        }
+        fees = fees.saturating_add(input_value - output_value);
+        if !money_range(fees) {
+            return Ok(Err("bad-txns-accumulated-fee-outofrange".to_string()));
+        }
+
+        sigops = sigops.saturating_add(sigops_cost(tx, &coins));
+        if height >= params.segwit_height {
+            let tx_witnesses = transaction_witnesses(witnesses, index + 1);
+            sigops = sigops.saturating_add(witness_sigops_cost(tx, tx_witnesses, &coins));
+        }
+        if sigops > MAX_BLOCK_SIGOPS_COST {
+            return Ok(Err("bad-blk-sigops".to_string()));
+        }
+
+        let txid = calculate_tx_id(tx);
+        if enforce_bip30 && overwrites_unspent(tx, txid, utxo_set) {
+            return Ok(Err("bad-txns-BIP30".to_string()));
+        }
+        add_outputs(&mut created, tx, txid, height);
+    }
+
+    if !is_final(coinbase, height, lock_time_cutoff) {
+        return Ok(Err("bad-txns-nonfinal".to_string()));
+    }
+    let coinbase_value = match check_outputs(coinbase) {
+        Ok(value) => value,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let subsidy = ConsensusProof::new().get_block_subsidy(height) as i64;
+    if coinbase_value > subsidy.saturating_add(fees) {
+        return Ok(Err("bad-cb-amount".to_string()));
+    }
+    let coinbase_txid = calculate_tx_id(coinbase);
+    if enforce_bip30 && overwrites_unspent(coinbase, coinbase_txid, utxo_set) {
+        return Ok(Err("bad-txns-BIP30".to_string()));
+    }
+    add_outputs(&mut created, coinbase, coinbase_txid, height);
+
+    Ok(Ok((spent, created)))
+}
+
+/// Check the header's version, timestamp and target against its ancestors
+fn check_header_context(
+    header: &BlockHeader,
+    height: u64,
+    context: &ChainContext<'_>,
+) -> std::result::Result<(), String> {
+    let params = &context.params;
+    if let Some(prev_height) = height.checked_sub(1) {
+        if let Some(prev) = (context.header_at)(prev_height) {
+            if let Some(expected) = expected_bits(&prev, height, context) {
+                if header.bits as u32 != expected {
+                    return Err("bad-diffbits".to_string());
+                }
+            }
+        }
+        if let Some(median_time) = context.median_time_past(prev_height) {
+            if header.timestamp <= median_time {
+                return Err("time-too-old".to_string());
+            }
+        }
+    }
+    if header.timestamp > context.now.saturating_add(MAX_FUTURE_BLOCK_TIME) {
+        return Err("time-too-new".to_string());
+    }
+
+    let version = header.version as i32;
+    let outdated = (version < 2 && height >= params.bip34_height)
+        || (version < 3 && height >= params.bip66_height)
+        || (version < 4 && height >= params.bip65_height);
+    if outdated {
+        return Err(format!("bad-version(0x{:08x})", version));
+    }
+    Ok(())
+}
+
+/// Compact target the block at `height` must carry, if the network checks it
+fn expected_bits(prev: &BlockHeader, height: u64, context: &ChainContext<'_>) -> Option<u32> {
+    let prev_bits = prev.bits as u32;
+    match context.params.difficulty {
+        DifficultyRules::Unchecked => None,
+        DifficultyRules::Fixed => Some(prev_bits),
+        DifficultyRules::Retarget { .. } if height % DIFFICULTY_ADJUSTMENT_INTERVAL != 0 => {
+            Some(prev_bits)
+        }
+        DifficultyRules::Retarget { pow_limit } => {
+            let first = (context.header_at)(height - DIFFICULTY_ADJUSTMENT_INTERVAL)?;
+            let timespan = prev
+                .timestamp
+                .saturating_sub(first.timestamp)
+                .clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4);
+            Some(retarget(prev_bits, timespan, pow_limit))
+        }
+    }
+}
+
+/// Scale a compact target by `timespan / TARGET_TIMESPAN`, capped at `pow_limit`
+fn retarget(bits: u32, timespan: u64, pow_limit: u32) -> u32 {
+    let exponent = (bits >> 24) as i32;
+    let mantissa = (bits & 0x007f_ffff) as u128;
+    // The target is mantissa * 256^(exponent - 3); 64 extra bits of precision
+    // survive the division, more than the compact form keeps
+    let scaled = ((mantissa * timespan as u128) << 64) / TARGET_TIMESPAN as u128;
+    let new_bits = compact_from_scaled(scaled, 8 * (exponent - 3) - 64);
+    let size_and_mantissa = |bits: u32| (bits >> 24, bits & 0x00ff_ffff);
+    if size_and_mantissa(new_bits) > size_and_mantissa(pow_limit) {
+        pow_limit
+    } else {
+        new_bits
+    }
+}
+
+/// Compact encoding of `value * 2^shift`, truncated like Bitcoin Core's `GetCompact`
+fn compact_from_scaled(value: u128, shift: i32) -> u32 {
+    let bit_length = 128 - value.leading_zeros() as i32 + shift;
+    if value == 0 || bit_length <= 0 {
+        return 0;
+    }
+    let mut size = (bit_length + 7) / 8;
+    let down = 8 * (size - 3) - shift;
+    let mut mantissa = if down >= 0 {
+        (value >> down) as u32
+    } else {
+        (value << -down) as u32
+    };
+    // The mantissa's top bit is a sign bit
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    ((size as u32) << 24) | mantissa
+}
+
+/// Whether a transaction's lock time allows it into the block (BIP 113 cutoff)
+fn is_final(tx: &Transaction, height: u64, lock_time_cutoff: u64) -> bool {
+    let lock_time = tx.lock_time;
+    if lock_time == 0 {
+        return true;
+    }
+    let limit = if lock_time < LOCKTIME_THRESHOLD {
+        height
+    } else {
+        lock_time_cutoff
+    };
+    lock_time < limit
+        || tx
+            .inputs
+            .iter()
+            .all(|input| input.sequence == SEQUENCE_FINAL)
+}
+
+/// Whether a version 2 transaction's relative lock times have passed (BIP 68)
+///
+/// `coins` holds the coins the transaction spends. Time-based locks are
+/// skipped where the median time past is unknown.
+fn sequence_locks_satisfied(
+    tx: &Transaction,
+    coins: &UtxoSet,
+    height: u64,
+    prev_median_time: Option<u64>,
+    context: &ChainContext<'_>,
+) -> bool {
+    if (tx.version as i32) < 2 {
+        return true;
+    }
+    for input in tx.inputs.iter() {
+        let sequence = input.sequence;
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            continue;
+        }
+        let Some(coin) = coins.get(&input.prevout) else {
+            continue;
+        };
+        let value = sequence & SEQUENCE_LOCKTIME_MASK;
+        if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            let coin_time = context.median_time_past(coin.height.saturating_sub(1));
+            if let (Some(coin_time), Some(median_time)) = (coin_time, prev_median_time) {
+                let lock_time = coin_time + (value << SEQUENCE_LOCKTIME_GRANULARITY);
+                if lock_time > median_time {
+                    return false;
+                }
+            }
+        } else if coin.height + value > height {
+            return false;
+        }
+    }
+    true
+}
+
+/// Check output amounts, returning their sum
+fn check_outputs(tx: &Transaction) -> std::result::Result<i64, String> {
+    let mut total: i64 = 0;
+    for output in tx.outputs.iter() {
+        if output.value < 0 {
+            return Err("bad-txns-vout-negative".to_string());
+        }
+        if output.value > MAX_MONEY {
+            return Err("bad-txns-vout-toolarge".to_string());
+        }
+        total += output.value;
+        if total > MAX_MONEY {
+            return Err("bad-txns-txouttotal-toolarge".to_string());
+        }
+    }
+    Ok(total)
+}
+
+fn money_range(value: i64) -> bool {
+    (0..=MAX_MONEY).contains(&value)
+}
+
+/// Whether a transaction would create an output that was unspent before the
+/// block (BIP 30)
+fn overwrites_unspent(tx: &Transaction, txid: Hash, utxo_set: &UtxoSet) -> bool {
+    (0..tx.outputs.len()).any(|index| {
+        let outpoint = OutPoint {
+            hash: txid,
+            index: index as u64,
+        };
+        utxo_set.contains_key(&outpoint)
+    })
+}
+
+fn is_coinbase(tx: &Transaction) -> bool {
+    tx.inputs.len() == 1
+        && tx.inputs[0].prevout.hash == [0u8; 32]
+        && tx.inputs[0].prevout.index == 0xffffffff
+}
+
+fn add_outputs(created: &mut HashMap<OutPoint, UTXO>, tx: &Transaction, txid: Hash, height: u64) {
+    for (index, output) in tx.outputs.iter().enumerate() {
+        created.insert(
+            OutPoint {
+                hash: txid,
+                index: index as u64,
+            },
+            UTXO {
+                value: output.value,
+                script_pubkey: output.script_pubkey.clone(),
+                height,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bllvm_protocol::{TransactionInput, TransactionOutput};
+
+    /// Mine a regtest block at height 1 holding a coinbase and `tx`
+    fn block_with(tx: Transaction) -> Block {
+        let coinbase = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: OutPoint {
+                    hash: [0u8; 32],
+                    index: 0xffffffff,
+                },
+                script_sig: vec![0x51, 0x00],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 5_000_000_000,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        let transactions = vec![coinbase, tx];
+        let mut block = Block {
+            header: BlockHeader {
+                version: 4,
+                prev_block_hash: [0u8; 32],
+                merkle_root: calculate_merkle_root(&transactions).unwrap(),
+                timestamp: 1_600_000_000,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            transactions: transactions.into_boxed_slice(),
+        };
+        while !bllvm_protocol::pow::check_proof_of_work(&block.header).unwrap() {
+            block.header.nonce += 1;
+        }
+        block
+    }
+
+    fn reject_reason(block: &Block, utxo_set: UtxoSet) -> Option<String> {
+        let context = ChainContext {
+            params: ConsensusParams::REGTEST,
+            header_at: &|_| None,
+            is_coinbase_output: &|_| false,
+            now: 1_600_000_000,
+        };
+        match connect_block_without_scripts(block, &[], utxo_set, 1, &context).unwrap() {
+            (ValidationResult::Valid, _) => None,
+            (ValidationResult::Invalid(reason), _) => Some(reason),
+        }
+    }
+
+    #[test]
+    fn test_rejects_transactions_without_inputs_or_outputs() {
+        let coin = OutPoint {
+            hash: [7u8; 32],
+            index: 0,
+        };
+        let mut utxo_set = UtxoSet::new();
+        utxo_set.insert(
+            coin.clone(),
+            UTXO {
+                value: 1_000,
+                script_pubkey: vec![0x51],
+                height: 0,
+            },
+        );
+        let spend = Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: coin,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 900,
+                script_pubkey: vec![0x51],
+            }],
+            lock_time: 0,
+        };
+        assert_eq!(
+            reject_reason(&block_with(spend.clone()), utxo_set.clone()),
+            None
+        );
+
+        let mut no_inputs = spend.clone();
+        no_inputs.inputs = bllvm_protocol::tx_inputs![];
+        assert_eq!(
+            reject_reason(&block_with(no_inputs), utxo_set.clone()).as_deref(),
+            Some("bad-txns-vin-empty")
+        );
+
+        let mut no_outputs = spend;
+        no_outputs.outputs = bllvm_protocol::tx_outputs![];
+        assert_eq!(
+            reject_reason(&block_with(no_outputs), utxo_set).as_deref(),
+            Some("bad-txns-vout-empty")
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Signature operation counting
+//!
+//! Legacy, P2SH and witness sigops as counted against the block sigop cost
+//! limit (BIP 141). The mempool applies the same counts with a lower
+//! per-transaction policy limit.
+
+use bllvm_protocol::{segwit::Witness, Transaction, UtxoSet};
+
+/// Sigop cost of a legacy signature operation
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+pub(crate) const OP_0: u8 = 0x00;
+pub(crate) const OP_PUSHDATA1: u8 = 0x4c;
+pub(crate) const OP_PUSHDATA2: u8 = 0x4d;
+pub(crate) const OP_PUSHDATA4: u8 = 0x4e;
+pub(crate) const OP_1: u8 = 0x51;
+pub(crate) const OP_16: u8 = 0x60;
+pub(crate) const OP_CHECKSIG: u8 = 0xac;
+pub(crate) const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub(crate) const OP_CHECKMULTISIG: u8 = 0xae;
+pub(crate) const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+
+/// One script operation: the opcode and its pushed data, if any
+pub(crate) struct Op<'a> {
+    pub opcode: u8,
+    pub data: Option<&'a [u8]>,
+}
+
+/// Parse a script into operations, or `None` if a push runs past the end
+pub(crate) fn parse_script(script: &[u8]) -> Option<Vec<Op<'_>>> {
+    let mut ops = Vec::new();
+    let mut pos = 0;
+    while pos < script.len() {
+        let opcode = script[pos];
+        pos += 1;
+        let len = match opcode {
+            0x01..=0x4b => opcode as usize,
+            OP_PUSHDATA1 => {
+                let len = *script.get(pos)? as usize;
+                pos += 1;
+                len
+            }
+            OP_PUSHDATA2 => {
+                let bytes = script.get(pos..pos + 2)?;
+                pos += 2;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            }
+            OP_PUSHDATA4 => {
+                let bytes = script.get(pos..pos + 4)?;
+                pos += 4;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+            }
+            _ => {
+                ops.push(Op { opcode, data: None });
+                continue;
+            }
+        };
+        let data = script.get(pos..pos.checked_add(len)?)?;
+        pos += len;
+        ops.push(Op {
+            opcode,
+            data: Some(data),
+        });
+    }
+    Some(ops)
+}
+
+/// Small integer pushed by OP_1..OP_16
+pub(crate) fn small_int(opcode: u8) -> Option<u8> {
+    if (OP_1..=OP_16).contains(&opcode) {
+        Some(opcode - OP_1 + 1)
+    } else {
+        None
+    }
+}
+
+/// Legacy signature operations in a script
+///
+/// With `accurate`, OP_CHECKMULTISIG counts the keys given by the preceding
+/// OP_n instead of the maximum of 20, as for P2SH redeem scripts.
+fn count_sigops(script: &[u8], accurate: bool) -> u64 {
+    let Some(ops) = parse_script(script) else {
+        return 0;
+    };
+    let mut count = 0;
+    let mut last_opcode = None;
+    for op in &ops {
+        count += match op.opcode {
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => 1,
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => match last_opcode.and_then(small_int) {
+                Some(keys) if accurate => keys as u64,
+                _ => 20,
+            },
+            _ => 0,
+        };
+        last_opcode = Some(op.opcode);
+    }
+    count
+}
+
+fn is_p2sh(script: &[u8]) -> bool {
+    matches!(script, [0xa9, 0x14, .., 0x87] if script.len() == 23)
+}
+
+/// Last push of a scriptSig: the redeem script of a P2SH spend
+fn redeem_script(script_sig: &[u8]) -> Option<&[u8]> {
+    parse_script(script_sig).and_then(|ops| ops.last().and_then(|op| op.data))
+}
+
+/// Signature operation cost of a transaction's legacy and P2SH sigops
+///
+/// P2SH redeem scripts are found through `utxo_set`; inputs spending coins
+/// missing from it are skipped.
+pub fn sigops_cost(tx: &Transaction, utxo_set: &UtxoSet) -> u64 {
+    let mut sigops: u64 = tx
+        .inputs
+        .iter()
+        .map(|input| count_sigops(&input.script_sig, false))
+        .sum();
+    sigops += tx
+        .outputs
+        .iter()
+        .map(|output| count_sigops(&output.script_pubkey, false))
+        .sum::<u64>();
+    for input in tx.inputs.iter() {
+        let Some(coin) = utxo_set.get(&input.prevout) else {
+            continue;
+        };
+        if !is_p2sh(&coin.script_pubkey) {
+            continue;
+        }
+        if let Some(redeem_script) = redeem_script(&input.script_sig) {
+            sigops += count_sigops(redeem_script, true);
+        }
+    }
+    sigops * WITNESS_SCALE_FACTOR
+}
+
+/// Signature operation cost of a transaction's version 0 witness spends
+///
+/// P2WPKH spends, bare or nested in P2SH, count one sigop. P2WSH spends count
+/// the sigops of their witness script, the last item of the input's stack in
+/// `witnesses`.
+pub fn witness_sigops_cost(tx: &Transaction, witnesses: &[Witness], utxo_set: &UtxoSet) -> u64 {
+    let mut sigops = 0;
+    for (index, input) in tx.inputs.iter().enumerate() {
+        let Some(coin) = utxo_set.get(&input.prevout) else {
+            continue;
+        };
+        let program = if is_p2sh(&coin.script_pubkey) {
+            match redeem_script(&input.script_sig) {
+                Some(script) => script,
+                None => continue,
+            }
+        } else {
+            &coin.script_pubkey[..]
+        };
+        sigops += match program {
+            [OP_0, 0x14, ..] if program.len() == 22 => 1,
+            [OP_0, 0x20, ..] if program.len() == 34 => witnesses
+                .get(index)
+                .and_then(|stack| stack.last())
+                .map_or(0, |script| count_sigops(script, true)),
+            _ => 0,
+        };
+    }
+    sigops
+}
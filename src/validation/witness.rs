@@ -0,0 +1,271 @@
+//! Segregated witness serialization and commitments (BIP 141 / BIP 144)
+//!
+//! Transaction weight, wtxids and the coinbase witness commitment, shared by
+//! block validation, the mempool and mining. A transaction's witness is one
+//! stack per input; a block's witnesses are one such list per transaction.
+
+use crate::storage::hashing::double_sha256;
+use bllvm_protocol::serialization::transaction::serialize_transaction;
+use bllvm_protocol::{segwit::Witness, Block, Hash, Transaction};
+
+/// Start of a witness commitment script: OP_RETURN, a 36-byte push and the
+/// 0xaa21a9ed commitment header (BIP 141)
+pub const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Whether any input of a transaction has witness data
+pub fn has_witness(witnesses: &[Witness]) -> bool {
+    witnesses.iter().any(|stack| !stack.is_empty())
+}
+
+/// Per-input stacks of transaction `index` in a block's witnesses (empty if
+/// it has none)
+pub fn transaction_witnesses(witnesses: &[Vec<Witness>], index: usize) -> &[Witness] {
+    witnesses.get(index).map_or(&[], Vec::as_slice)
+}
+
+/// Serialize a transaction, in the BIP 144 witness format if it has a witness
+///
+/// `witnesses[i]` is the stack of input `i`; inputs without one get an empty
+/// stack.
+pub fn serialize_transaction_with_witness(tx: &Transaction, witnesses: &[Witness]) -> Vec<u8> {
+    let base = serialize_transaction(tx);
+    if !has_witness(witnesses) {
+        return base;
+    }
+    let (body, lock_time) = base.split_at(base.len() - 4);
+
+    // version | marker | flag | inputs and outputs | witnesses | lock_time
+    let mut data = body[..4].to_vec();
+    data.extend_from_slice(&[0x00, 0x01]);
+    data.extend_from_slice(&body[4..]);
+    for index in 0..tx.inputs.len() {
+        let stack = witnesses
+            .get(index)
+            .map_or(&[][..], |stack| stack.as_slice());
+        data.extend_from_slice(&encode_varint(stack.len() as u64));
+        for item in stack {
+            data.extend_from_slice(&encode_varint(item.len() as u64));
+            data.extend_from_slice(item);
+        }
+    }
+    data.extend_from_slice(lock_time);
+    data
+}
+
+/// wtxid of a transaction (BIP 141)
+///
+/// Equal to the txid without witness data.
+pub fn calculate_wtxid(tx: &Transaction, witnesses: &[Witness]) -> Hash {
+    if !has_witness(witnesses) {
+        return bllvm_protocol::block::calculate_tx_id(tx);
+    }
+    double_sha256(&serialize_transaction_with_witness(tx, witnesses))
+}
+
+/// Weight of a transaction (BIP 141): base size * 3 + total size
+pub fn transaction_weight(tx: &Transaction, witnesses: &[Witness]) -> u64 {
+    let base_size = serialize_transaction(tx).len() as u64;
+    let total_size = serialize_transaction_with_witness(tx, witnesses).len() as u64;
+    base_size * 3 + total_size
+}
+
+/// Virtual size of a transaction: weight / 4, rounded up
+pub fn transaction_vsize(tx: &Transaction, witnesses: &[Witness]) -> u64 {
+    transaction_weight(tx, witnesses).div_ceil(4)
+}
+
+/// Block witnesses in the form bllvm-protocol's `connect_block` takes
+///
+/// The consensus crate carries one witness stack per transaction; each
+/// transaction's first-input stack is passed.
+pub fn consensus_witnesses(witnesses: &[Vec<Witness>]) -> Vec<Witness> {
+    witnesses
+        .iter()
+        .map(|stacks| stacks.first().cloned().unwrap_or_default())
+        .collect()
+}
+
+/// Commitment hash in a coinbase, if it has one (the last matching output wins)
+pub fn find_witness_commitment(coinbase: &Transaction) -> Option<Hash> {
+    coinbase.outputs.iter().rev().find_map(|output| {
+        let script = &output.script_pubkey;
+        if script.len() >= 38 && script[..6] == WITNESS_COMMITMENT_HEADER {
+            let mut commitment = [0u8; 32];
+            commitment.copy_from_slice(&script[6..38]);
+            Some(commitment)
+        } else {
+            None
+        }
+    })
+}
+
+/// Check a block's witness commitment against its transactions and witnesses
+///
+/// `witnesses[i]` holds the per-input stacks of transaction `i`. Returns the
+/// Bitcoin Core reject reason on failure. Blocks without a commitment must
+/// not carry witness data.
+pub fn check_witness_commitment(block: &Block, witnesses: &[Vec<Witness>]) -> Result<(), String> {
+    let tx_has_witness = |index: usize| has_witness(transaction_witnesses(witnesses, index));
+    let Some(coinbase) = block.transactions.first() else {
+        return Ok(());
+    };
+    let Some(expected) = find_witness_commitment(coinbase) else {
+        if (0..block.transactions.len()).any(tx_has_witness) {
+            return Err("unexpected-witness".to_string());
+        }
+        return Ok(());
+    };
+
+    // The coinbase input's witness must be the single 32-byte reserved value
+    let reserved = match transaction_witnesses(witnesses, 0)
+        .first()
+        .map(|stack| stack.as_slice())
+    {
+        Some([value]) if value.len() == 32 => value.clone(),
+        _ => return Err("bad-witness-nonce-size".to_string()),
+    };
+    let wtxids = block.transactions[1..]
+        .iter()
+        .enumerate()
+        .map(|(i, tx)| calculate_wtxid(tx, transaction_witnesses(witnesses, i + 1)));
+    let witness_root = merkle_root(std::iter::once([0u8; 32]).chain(wtxids).collect());
+    let mut preimage = witness_root.to_vec();
+    preimage.extend_from_slice(&reserved);
+    if double_sha256(&preimage) != expected {
+        return Err("bad-witness-merkle-match".to_string());
+    }
+    Ok(())
+}
+
+/// Merkle root of a list of hashes (duplicating the last hash of odd levels)
+pub fn merkle_root(mut level: Vec<Hash>) -> Hash {
+    if level.is_empty() {
+        return [0u8; 32];
+    }
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1]);
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let mut data = pair[0].to_vec();
+                data.extend_from_slice(&pair[1]);
+                double_sha256(&data)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Encode a number as a Bitcoin varint
+pub(crate) fn encode_varint(value: u64) -> Vec<u8> {
+    if value < 0xfd {
+        vec![value as u8]
+    } else if value <= 0xffff {
+        let mut result = vec![0xfd];
+        result.extend_from_slice(&(value as u16).to_le_bytes());
+        result
+    } else if value <= 0xffffffff {
+        let mut result = vec![0xfe];
+        result.extend_from_slice(&(value as u32).to_le_bytes());
+        result
+    } else {
+        let mut result = vec![0xff];
+        result.extend_from_slice(&value.to_le_bytes());
+        result
+    }
+}
+
+/// Decode a Bitcoin varint at `pos`, advancing past it
+///
+/// Returns `None`, leaving `pos` unchanged, if the data ends first.
+pub(crate) fn decode_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let width = match *data.get(*pos)? {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        value => {
+            *pos += 1;
+            return Some(value as u64);
+        }
+    };
+    let bytes = data.get(*pos + 1..*pos + 1 + width)?;
+    let mut value = [0u8; 8];
+    value[..width].copy_from_slice(bytes);
+    *pos += 1 + width;
+    Some(u64::from_le_bytes(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bllvm_protocol::{OutPoint, TransactionInput, TransactionOutput};
+
+    #[test]
+    fn test_two_input_segwit_transaction() {
+        let input = |hash: u8, index: u64| TransactionInput {
+            prevout: OutPoint {
+                hash: [hash; 32],
+                index,
+            },
+            script_sig: vec![],
+            sequence: 0xffffffff,
+        };
+        let mut script_pubkey = vec![0x00, 0x14];
+        script_pubkey.extend([0xab; 20]);
+        let tx = Transaction {
+            version: 2,
+            inputs: bllvm_protocol::tx_inputs![input(1, 0), input(2, 1)],
+            outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+                value: 50_000,
+                script_pubkey,
+            }],
+            lock_time: 0,
+        };
+        let witnesses = vec![
+            vec![vec![0x30; 71], vec![0x02; 33]],
+            vec![vec![0x31; 72], vec![0x03; 33]],
+        ];
+
+        // Both stacks are serialized, each on its own input
+        let wire = serialize_transaction_with_witness(&tx, &witnesses);
+        assert_eq!(wire.len(), 340);
+        assert_eq!(transaction_weight(&tx, &witnesses), 709);
+        assert_eq!(transaction_vsize(&tx, &witnesses), 178);
+        assert_eq!(
+            hex::encode(calculate_wtxid(&tx, &witnesses)),
+            "5e14718df32e3c5ae89dbc1a78a9decde14e0056c46c4cd971d6e4f6de91f604"
+        );
+        assert_eq!(
+            hex::encode(calculate_wtxid(&tx, &[])),
+            "9b93a72a6cf7d13d98ca50c0631c92297509088fb15c26b337e7ab46070d088b"
+        );
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [
+            0,
+            0xfc,
+            0xfd,
+            0xffff,
+            0x10000,
+            0xffff_ffff,
+            0x1_0000_0000,
+            u64::MAX,
+        ] {
+            let mut data = encode_varint(value);
+            data.push(0xaa);
+            let mut pos = 0;
+            assert_eq!(decode_varint(&data, &mut pos), Some(value));
+            assert_eq!(pos, data.len() - 1);
+        }
+
+        // Truncated data leaves the position alone
+        let mut pos = 0;
+        assert_eq!(decode_varint(&[0xfe, 0x01, 0x02], &mut pos), None);
+        assert_eq!(decode_varint(&[], &mut pos), None);
+        assert_eq!(pos, 0);
+    }
+}
@@ -40,6 +40,7 @@ fn test_merge_ban_lists() {
             ),
         ],
         timestamp: now,
+        signature: None,
     };
 
     let list2 = BanListMessage {
@@ -60,6 +61,7 @@ fn test_merge_ban_lists() {
             ),
         ],
         timestamp: now,
+        signature: None,
     };
 
     let merged = merge_ban_lists(vec![&list1, &list2]);
@@ -133,3 +135,49 @@ fn test_calculate_ban_list_hash() {
     // Verify hash
     assert!(verify_ban_list_hash(&entries, &hash1));
 }
+
+#[test]
+fn test_ban_list_signature_status() {
+    use bllvm_node::network::ban_list_signing::{
+        check_ban_list_signature, sign_ban_list_message, BanListSignatureStatus,
+    };
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    let secp = Secp256k1::new();
+    let publisher = SecretKey::from_slice(&[0x11; 32]).unwrap();
+    let trusted = vec![PublicKey::from_secret_key(&secp, &publisher)];
+
+    let entries = vec![create_test_ban_entry(
+        [10, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        8333,
+        u64::MAX,
+    )];
+    let mut ban_list = BanListMessage {
+        is_full: true,
+        ban_list_hash: calculate_ban_list_hash(&entries),
+        ban_entries: entries,
+        timestamp: 1_700_000_000,
+        signature: None,
+    };
+    assert_eq!(
+        check_ban_list_signature(&ban_list, &trusted),
+        BanListSignatureStatus::Unsigned
+    );
+
+    sign_ban_list_message(&mut ban_list, &publisher).unwrap();
+    assert_eq!(
+        check_ban_list_signature(&ban_list, &trusted),
+        BanListSignatureStatus::Valid
+    );
+    assert_eq!(
+        check_ban_list_signature(&ban_list, &[]),
+        BanListSignatureStatus::UntrustedPublisher
+    );
+
+    // Tampering with the entries after signing invalidates the signature
+    ban_list.ban_entries[0].unban_timestamp = 1_800_000_000;
+    assert_eq!(
+        check_ban_list_signature(&ban_list, &trusted),
+        BanListSignatureStatus::Invalid
+    );
+}
@@ -156,7 +156,7 @@ impl TestBlockBuilder {
                     hash: [0u8; 32],
                     index: 0xffffffff,
                 },
-                script_sig: vec![0x51], // OP_1
+                script_sig: vec![0x51, 0x00], // OP_1 OP_0 (coinbase scripts are 2-100 bytes)
                 sequence: 0xffffffff,
             }],
             outputs: bllvm_protocol::tx_outputs![TransactionOutput {
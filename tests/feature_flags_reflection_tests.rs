@@ -40,13 +40,13 @@ async fn getblocktemplate_rules_include_feature_flags() {
     );
 
     let rules = result["rules"].as_array().expect("rules array");
-    // Placeholders currently include csv, segwit, taproot
+    // csv, segwit (prefixed with `!` as a required rule) and taproot
     let rule_set: std::collections::HashSet<String> = rules
         .iter()
         .filter_map(|v| v.as_str().map(|s| s.to_string()))
         .collect();
 
     assert!(rule_set.contains("csv"));
-    assert!(rule_set.contains("segwit"));
+    assert!(rule_set.contains("!segwit"));
     assert!(rule_set.contains("taproot"));
 }
@@ -0,0 +1,126 @@
+#![cfg(feature = "governance")]
+//! Tests for governance webhook fee forwarding reports
+
+use bllvm_node::governance::{FeeForwardingEvent, GovernanceWebhookClient};
+use bllvm_node::utils::retry::RetryConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Mutex};
+
+/// Minimal HTTP server answering requests with scripted status codes
+///
+/// Each request body is sent on the returned channel. Once the script runs
+/// out, requests are answered with 200.
+async fn mock_server(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<serde_json::Value>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+    let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let body = loop {
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                if n == 0 {
+                    break None;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break Some(request[end + 4..end + 4 + length].to_vec());
+                    }
+                }
+            };
+            let Some(body) = body else { continue };
+
+            let status = statuses.lock().await.next().unwrap_or(200);
+            let _ = tx.send(serde_json::from_slice(&body).unwrap());
+            let response = format!(
+                "HTTP/1.1 {status} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+
+    (url, rx)
+}
+
+fn event(height: u64) -> FeeForwardingEvent {
+    FeeForwardingEvent {
+        block_hash: hex::encode([height as u8; 32]),
+        block_height: height,
+        forwarded_amount: 1_650_000_000,
+        commons_address: "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c".to_string(),
+        contributor_id: Some("contributor-1".to_string()),
+    }
+}
+
+async fn wait_for_empty_queue(client: &GovernanceWebhookClient) {
+    for _ in 0..200 {
+        if client.pending_events() == 0 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("fee forwarding event was not delivered");
+}
+
+#[tokio::test]
+async fn test_fee_forwarding_payload_retried_until_delivered() {
+    let (url, mut requests) = mock_server(vec![503]).await;
+    let client = GovernanceWebhookClient::new(Some(url), None)
+        .with_retry_config(RetryConfig::new(3, Duration::from_millis(10)));
+
+    client.notify_fee_forwarding(event(7));
+    wait_for_empty_queue(&client).await;
+
+    // First attempt fails with 503, the retry succeeds with the same payload
+    let first = requests.recv().await.unwrap();
+    let second = requests.recv().await.unwrap();
+    assert_eq!(first, second);
+    assert_eq!(
+        second,
+        serde_json::json!({
+            "block_hash": hex::encode([7u8; 32]),
+            "block_height": 7,
+            "forwarded_amount": 1_650_000_000u64,
+            "commons_address": "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c",
+            "contributor_id": "contributor-1",
+        })
+    );
+    assert!(requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_fee_forwarding_queued_while_endpoint_down() {
+    // Both attempts of the first delivery fail
+    let (url, mut requests) = mock_server(vec![500, 500]).await;
+    let client = GovernanceWebhookClient::new(Some(url), None)
+        .with_retry_config(RetryConfig::new(2, Duration::from_millis(10)));
+
+    client.notify_fee_forwarding(event(1));
+    requests.recv().await.unwrap();
+    requests.recv().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(client.pending_events(), 1);
+
+    // The endpoint is back; the queued event goes out first
+    client.notify_fee_forwarding(event(2));
+    wait_for_empty_queue(&client).await;
+    assert_eq!(requests.recv().await.unwrap()["block_height"], 1);
+    assert_eq!(requests.recv().await.unwrap()["block_height"], 2);
+    assert_eq!(client.flush_pending().await, 0);
+}
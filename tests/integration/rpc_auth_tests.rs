@@ -16,6 +16,7 @@ async fn test_rpc_auth_token_required() {
         required: true,
         tokens: vec!["test-token-123".to_string()],
         certificates: vec![],
+        scoped_tokens: vec![],
         rate_limit_burst: 10,
         rate_limit_rate: 5,
     };
@@ -35,6 +36,7 @@ async fn test_rpc_auth_token_valid() {
         required: true,
         tokens: vec!["valid-token".to_string()],
         certificates: vec![],
+        scoped_tokens: vec![],
         rate_limit_burst: 10,
         rate_limit_rate: 5,
     };
@@ -53,6 +55,7 @@ async fn test_rpc_rate_limiting() {
         required: false, // Don't require auth, but still rate limit
         tokens: vec![],
         certificates: vec![],
+        scoped_tokens: vec![],
         rate_limit_burst: 5,
         rate_limit_rate: 2, // 2 requests per second
     };
@@ -72,6 +75,7 @@ async fn test_rpc_auth_optional() {
         required: false, // Optional auth
         tokens: vec!["optional-token".to_string()],
         certificates: vec![],
+        scoped_tokens: vec![],
         rate_limit_burst: 10,
         rate_limit_rate: 5,
     };
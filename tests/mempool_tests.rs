@@ -145,3 +145,1566 @@ async fn test_mempool_remove_transaction() {
     assert!(removed);
     assert_eq!(mempool.size(), 0);
 }
+
+fn spending_tx(prevout: OutPoint) -> Transaction {
+    Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout,
+            script_sig: vec![],
+            sequence: 0xffffffff,
+        }],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 900,
+            script_pubkey: vec![0x51],
+        }],
+        lock_time: 0,
+    }
+}
+
+#[tokio::test]
+async fn test_mempool_spent_and_created_outpoints() {
+    use bllvm_node::node::mempool::MEMPOOL_HEIGHT;
+    use bllvm_protocol::mempool::calculate_tx_id;
+
+    let mut mempool = MempoolManager::new();
+    let prevout = OutPoint {
+        hash: [7u8; 32],
+        index: 1,
+    };
+    let tx = spending_tx(prevout.clone());
+    let tx_hash = calculate_tx_id(&tx);
+    let created = OutPoint {
+        hash: tx_hash,
+        index: 0,
+    };
+
+    assert!(!mempool.spends_outpoint(&prevout));
+    mempool.add_transaction(tx).await.unwrap();
+    assert!(mempool.spends_outpoint(&prevout));
+    assert!(!mempool.spends_outpoint(&created));
+
+    let output = mempool.get_mempool_output(&created).unwrap();
+    assert_eq!(output.value, 900);
+    assert_eq!(output.height, MEMPOOL_HEIGHT);
+    assert!(mempool
+        .get_mempool_output(&OutPoint {
+            hash: tx_hash,
+            index: 1,
+        })
+        .is_none());
+
+    mempool.remove_transaction(&tx_hash);
+    assert!(!mempool.spends_outpoint(&prevout));
+    assert!(mempool.get_mempool_output(&created).is_none());
+}
+
+#[tokio::test]
+async fn test_gettxout_reports_mempool_spends_only_when_requested() {
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let prevout = OutPoint {
+        hash: [9u8; 32],
+        index: 0,
+    };
+    let coin = UTXO {
+        value: 1000,
+        script_pubkey: vec![0x51],
+        height: 1,
+    };
+    storage.utxos().add_utxo(&prevout, &coin).unwrap();
+
+    let mut mempool = MempoolManager::new();
+    mempool
+        .add_transaction(spending_tx(prevout.clone()))
+        .await
+        .unwrap();
+    let rpc = RawTxRpc::with_dependencies(storage, Arc::new(mempool), None, None);
+
+    let txid = hex::encode(prevout.hash);
+    let with_mempool = rpc.gettxout(&json!([txid, 0, true])).await.unwrap();
+    assert!(with_mempool.is_null());
+
+    let confirmed_only = rpc.gettxout(&json!([txid, 0, false])).await.unwrap();
+    assert_eq!(confirmed_only["value"], json!(0.00001));
+}
+
+fn tx_paying(prevout: OutPoint, value: i64) -> Transaction {
+    let mut tx = spending_tx(prevout);
+    tx.outputs[0].value = value;
+    tx
+}
+
+fn raw_hex(tx: &Transaction) -> String {
+    use bllvm_protocol::serialization::transaction::serialize_transaction;
+    hex::encode(serialize_transaction(tx))
+}
+
+#[tokio::test]
+async fn test_testmempoolaccept_checks_without_inserting() {
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    for hash in [1u8, 2, 3] {
+        let utxo = UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        };
+        storage.utxos().add_utxo(&coin(hash), &utxo).unwrap();
+    }
+
+    // Coin 2 is already spent by a mempool transaction
+    let mut mempool = MempoolManager::new();
+    mempool
+        .add_transaction(tx_paying(coin(2), 90_000))
+        .await
+        .unwrap();
+    let mempool = Arc::new(mempool);
+    let rpc = RawTxRpc::with_dependencies(storage, Arc::clone(&mempool), None, None);
+
+    // Valid: 10,000 sat fee
+    let valid = tx_paying(coin(1), 90_000);
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&valid)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(true));
+    assert_eq!(
+        result[0]["txid"],
+        json!(hex::encode(calculate_tx_id(&valid)))
+    );
+    assert_eq!(result[0]["fees"]["base"], json!(0.0001));
+    assert!(result[0]["vsize"].as_u64().unwrap() > 0);
+    assert_eq!(mempool.size(), 1);
+
+    // Double spend of a mempool input
+    let double_spend = tx_paying(coin(2), 80_000);
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&double_spend)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(false));
+    assert_eq!(result[0]["reject-reason"], json!("txn-mempool-conflict"));
+
+    // Fee below the minimum relay fee
+    let low_fee = tx_paying(coin(3), 99_999);
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&low_fee)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(false));
+    assert_eq!(result[0]["reject-reason"], json!("min relay fee not met"));
+
+    // Package: the child spends the parent's output
+    let child = tx_paying(
+        OutPoint {
+            hash: calculate_tx_id(&valid),
+            index: 0,
+        },
+        80_000,
+    );
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&valid), raw_hex(&child)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(true));
+    assert_eq!(result[1]["allowed"], json!(true));
+
+    // Without the parent the child has no inputs
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&child)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["reject-reason"], json!("missing-inputs"));
+    assert_eq!(mempool.size(), 1);
+}
+
+#[tokio::test]
+async fn test_sendrawtransaction_fee_and_burn_guards() {
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let prevout = OutPoint {
+        hash: [4u8; 32],
+        index: 0,
+    };
+    let coin = UTXO {
+        value: 100_000_000,
+        script_pubkey: vec![0x51],
+        height: 1,
+    };
+    storage.utxos().add_utxo(&prevout, &coin).unwrap();
+    let rpc = RawTxRpc::with_dependencies(storage, Arc::new(MempoolManager::new()), None, None);
+
+    // Spends 1 BTC, pays almost all of it as fee
+    let absurd_fee = raw_hex(&tx_paying(prevout.clone(), 1000));
+    let err = rpc
+        .sendrawtransaction(&json!([absurd_fee]))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("max-fee-exceeded"));
+    assert!(rpc
+        .sendrawtransaction(&json!([absurd_fee, 0]))
+        .await
+        .is_ok());
+
+    // Burning value in an OP_RETURN output needs maxburnamount
+    let mut burn = tx_paying(prevout, 99_990_000);
+    burn.outputs[0].script_pubkey = vec![0x6a, 0x01, 0x00];
+    let burn = raw_hex(&burn);
+    let err = rpc.sendrawtransaction(&json!([burn])).await.unwrap_err();
+    assert!(err.message.contains("maxburnamount"));
+    assert!(rpc
+        .sendrawtransaction(&json!([burn, 0.10, 1.0]))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_immature_coinbase_spend_rejected() {
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use bllvm_protocol::{Block, BlockHeader};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+
+    // Coinbase created at height 1
+    let coinbase = Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: [0u8; 32],
+                index: 0xffffffff,
+            },
+            script_sig: vec![0x51, 0x00],
+            sequence: 0xffffffff,
+        }],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 5_000_000_000,
+            script_pubkey: vec![0x51],
+        }],
+        lock_time: 0,
+    };
+    let header = BlockHeader {
+        version: 1,
+        prev_block_hash: [0u8; 32],
+        merkle_root: [0u8; 32],
+        timestamp: 1_600_000_000,
+        bits: 0x207fffff,
+        nonce: 0,
+    };
+    let block = Block {
+        header: header.clone(),
+        transactions: vec![coinbase.clone()].into_boxed_slice(),
+    };
+    storage.connect_block(&block, 1).unwrap();
+    let coinbase_out = OutPoint {
+        hash: calculate_tx_id(&coinbase),
+        index: 0,
+    };
+    assert!(storage.utxos().is_coinbase(&coinbase_out).unwrap());
+
+    let set_tip_height = |height: u64| {
+        storage
+            .chain()
+            .store_chain_info(&ChainInfo {
+                tip_hash: [height as u8; 32],
+                tip_header: header.clone(),
+                height,
+                total_work: 0,
+                chain_params: ChainParams::default(),
+            })
+            .unwrap();
+    };
+    let rpc = RawTxRpc::with_dependencies(
+        Arc::clone(&storage),
+        Arc::new(MempoolManager::new()),
+        None,
+        None,
+    );
+    let spend = tx_paying(coinbase_out.clone(), 4_999_990_000);
+    let txid = hex::encode(coinbase_out.hash);
+
+    // 50 confirmations: too early
+    set_tip_height(50);
+    let txout = rpc.gettxout(&json!([txid, 0, false])).await.unwrap();
+    assert_eq!(txout["confirmations"], json!(50));
+    assert_eq!(txout["coinbase"], json!(true));
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&spend)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(false));
+    assert_eq!(
+        result[0]["reject-reason"],
+        json!("bad-txns-premature-spend-of-coinbase")
+    );
+
+    // 100 confirmations: mature
+    set_tip_height(100);
+    let txout = rpc.gettxout(&json!([txid, 0, false])).await.unwrap();
+    assert_eq!(txout["confirmations"], json!(100));
+    let result = rpc
+        .testmempoolaccept(&json!([[raw_hex(&spend)]]))
+        .await
+        .unwrap();
+    assert_eq!(result[0]["allowed"], json!(true));
+
+    // Disconnecting the block removes the coin and its coinbase mark
+    let block_hash = storage.blocks().get_block_hash(&block);
+    storage.disconnect_block(&block_hash).unwrap();
+    assert!(!storage.utxos().is_coinbase(&coinbase_out).unwrap());
+}
+
+#[tokio::test]
+async fn test_mempool_accept_result_variants() {
+    use bllvm_node::node::mempool::{
+        MempoolAcceptResult, REJECT_DUPLICATE, REJECT_INSUFFICIENTFEE,
+    };
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    for hash in [1u8, 2] {
+        utxo_set.insert(
+            coin(hash),
+            UTXO {
+                value: 100_000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        );
+    }
+    let no_coinbase = |_: &OutPoint| false;
+    let mut mempool = MempoolManager::new();
+
+    let tx = tx_paying(coin(1), 90_000);
+    let result = mempool
+        .accept_to_memory_pool(tx.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await;
+    assert!(result.is_accepted());
+    // Acceptance caches the fee rate, so it can be read without the UTXO set
+    assert_eq!(
+        mempool.cached_fee_rates_per_kvb(),
+        vec![(
+            calculate_tx_id(&tx),
+            result.accepted().unwrap().fee_rate_per_kvb()
+        )]
+    );
+
+    // Duplicate
+    let result = mempool
+        .accept_to_memory_pool(tx.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await;
+    assert_eq!(
+        result,
+        MempoolAcceptResult::AlreadyInPool {
+            txid: calculate_tx_id(&tx)
+        }
+    );
+    assert_eq!(result.reject_reason(), Some("txn-already-in-mempool"));
+    assert!(!result.is_misbehavior());
+
+    // Double spend of a non-replaceable transaction
+    let double_spend = tx_paying(coin(1), 80_000);
+    match mempool.check_transaction(&double_spend, &utxo_set, &no_coinbase, 2) {
+        MempoolAcceptResult::Rejected {
+            reason,
+            reject_code,
+            is_relayable,
+        } => {
+            assert_eq!(reason, "txn-mempool-conflict");
+            assert_eq!(reject_code, REJECT_DUPLICATE);
+            assert!(is_relayable);
+        }
+        other => panic!("expected a rejection, got {other:?}"),
+    }
+
+    // Fee below the minimum relay fee
+    let low_fee = tx_paying(coin(2), 99_999);
+    let result = mempool.check_transaction(&low_fee, &utxo_set, &no_coinbase, 2);
+    assert!(matches!(
+        &result,
+        MempoolAcceptResult::Rejected {
+            reason,
+            reject_code: REJECT_INSUFFICIENTFEE,
+            is_relayable: true,
+        } if reason == "min relay fee not met"
+    ));
+    assert!(!result.is_misbehavior());
+
+    // Outputs already in the UTXO set: confirmed
+    let mut confirmed_view = utxo_set.clone();
+    let confirmed = tx_paying(coin(2), 90_000);
+    confirmed_view.insert(
+        OutPoint {
+            hash: calculate_tx_id(&confirmed),
+            index: 0,
+        },
+        UTXO {
+            value: 90_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    assert!(matches!(
+        mempool.check_transaction(&confirmed, &confirmed_view, &no_coinbase, 2),
+        MempoolAcceptResult::AlreadyKnownInBlock { .. }
+    ));
+
+    // Creating value is invalid everywhere
+    let inflating = tx_paying(coin(2), 200_000);
+    assert!(mempool
+        .check_transaction(&inflating, &utxo_set, &no_coinbase, 2)
+        .is_misbehavior());
+}
+
+#[tokio::test]
+async fn test_configured_relay_fees_and_replacement_bump() {
+    use bllvm_node::rpc::mempool::MempoolRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    for hash in [1u8, 2] {
+        utxo_set.insert(
+            coin(hash),
+            UTXO {
+                value: 100_000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        );
+    }
+    let no_coinbase = |_: &OutPoint| false;
+
+    // 5 sat/vB minimum, 2 sat/vB incremental
+    let mut mempool = MempoolManager::new();
+    mempool.set_relay_fees(5000, 2000);
+
+    // 200 sat for a ~60 vB transaction is below 5 sat/vB
+    let below_min = tx_paying(coin(1), 99_800);
+    assert_eq!(
+        mempool
+            .check_acceptance(&below_min, &utxo_set, &no_coinbase, 2)
+            .unwrap_err(),
+        "min relay fee not met"
+    );
+
+    // Opt-in replaceable original paying 10,000 sat
+    let mut original = tx_paying(coin(2), 90_000);
+    original.inputs[0].sequence = 0xfffffffd;
+    mempool
+        .accept_to_memory_pool(original.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await
+        .into_result()
+        .unwrap();
+
+    // Higher fee rate, but 50 extra sat does not cover the incremental fee
+    let small_bump = tx_paying(coin(2), 89_950);
+    assert_eq!(
+        mempool
+            .check_acceptance(&small_bump, &utxo_set, &no_coinbase, 2)
+            .unwrap_err(),
+        "insufficient fee"
+    );
+
+    // 1,000 extra sat is enough; the original is evicted
+    let replacement = tx_paying(coin(2), 89_000);
+    mempool
+        .accept_to_memory_pool(replacement.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await
+        .into_result()
+        .unwrap();
+    assert_eq!(mempool.size(), 1);
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&replacement))
+        .is_some());
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&original))
+        .is_none());
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let rpc = MempoolRpc::with_dependencies(Arc::new(mempool), storage);
+    let info = rpc.getmempoolinfo(&json!([])).await.unwrap();
+    assert_eq!(info["minrelaytxfee"], json!(0.00005));
+}
+
+#[tokio::test]
+async fn test_replacement_compares_witness_discounted_vsizes() {
+    use bllvm_node::node::block_processor::transaction_vsize;
+    use bllvm_protocol::mempool::calculate_tx_id;
+
+    let coin = OutPoint {
+        hash: [1u8; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        coin.clone(),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    let no_coinbase = |_: &OutPoint| false;
+    let mut mempool = MempoolManager::new();
+
+    // The original pays 10,000 sat, most of its size in witness data
+    let mut original = tx_paying(coin.clone(), 90_000);
+    original.inputs[0].sequence = 0xfffffffd;
+    let witness = vec![vec![0xab; 72]; 5];
+    mempool
+        .accept_to_memory_pool_with_witness(
+            original.clone(),
+            vec![witness.clone()],
+            &utxo_set,
+            &no_coinbase,
+            2,
+            false,
+        )
+        .await
+        .into_result()
+        .unwrap();
+
+    // The replacement carries no witness but is bigger, and pays 12,000 sat:
+    // a higher rate than the original's witness-discounted vsize gives, but
+    // not than its stripped size would
+    let mut replacement = tx_paying(coin, 88_000);
+    let mut outputs = replacement.outputs.to_vec();
+    let mut data = vec![0x6a, 0x4c, 80];
+    data.extend([0u8; 80]);
+    outputs.push(TransactionOutput {
+        value: 0,
+        script_pubkey: data,
+    });
+    replacement.outputs = outputs.into();
+    let original_vsize = transaction_vsize(&original, &[witness]);
+    let replacement_vsize = transaction_vsize(&replacement, &[]);
+    assert!(12_000 * original_vsize > 10_000 * replacement_vsize);
+    assert!(12_000 * transaction_vsize(&original, &[]) <= 10_000 * replacement_vsize);
+
+    mempool
+        .accept_to_memory_pool(replacement.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await
+        .into_result()
+        .unwrap();
+    assert_eq!(mempool.size(), 1);
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&replacement))
+        .is_some());
+    assert_eq!(mempool.bytes() as u64, replacement_vsize);
+}
+
+#[tokio::test]
+async fn test_replacement_pays_for_and_evicts_descendants() {
+    use bllvm_node::node::mempool::MEMPOOL_HEIGHT;
+    use bllvm_protocol::mempool::calculate_tx_id;
+
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    for hash in [1u8, 2] {
+        utxo_set.insert(
+            coin(hash),
+            UTXO {
+                value: 100_000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        );
+    }
+    let no_coinbase = |_: &OutPoint| false;
+    let mut mempool = MempoolManager::new();
+
+    // Replaceable original paying 10,000 sat, and a child paying another 10,000
+    let mut original = tx_paying(coin(2), 90_000);
+    original.inputs[0].sequence = 0xfffffffd;
+    mempool
+        .accept_to_memory_pool(original.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await
+        .into_result()
+        .unwrap();
+    let child = tx_paying(
+        OutPoint {
+            hash: calculate_tx_id(&original),
+            index: 0,
+        },
+        80_000,
+    );
+    mempool.add_transaction(child.clone()).await.unwrap();
+    let unrelated = tx_paying(coin(1), 90_000);
+    mempool.add_transaction(unrelated.clone()).await.unwrap();
+
+    // Outbidding the original alone does not pay for the evicted child
+    let outbids_original = tx_paying(coin(2), 89_000);
+    assert_eq!(
+        mempool
+            .check_acceptance(&outbids_original, &utxo_set, &no_coinbase, 2)
+            .unwrap_err(),
+        "insufficient fee"
+    );
+
+    // New unconfirmed inputs are not allowed
+    let unrelated_output = OutPoint {
+        hash: calculate_tx_id(&unrelated),
+        index: 0,
+    };
+    let mut view = utxo_set.clone();
+    view.insert(
+        unrelated_output.clone(),
+        UTXO {
+            value: 90_000,
+            script_pubkey: vec![0x51],
+            height: MEMPOOL_HEIGHT,
+        },
+    );
+    let adds_unconfirmed = Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![
+            TransactionInput {
+                prevout: coin(2),
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            },
+            TransactionInput {
+                prevout: unrelated_output,
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }
+        ],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 150_000,
+            script_pubkey: vec![0x51],
+        }],
+        lock_time: 0,
+    };
+    assert_eq!(
+        mempool
+            .check_acceptance(&adds_unconfirmed, &view, &no_coinbase, 2)
+            .unwrap_err(),
+        "replacement-adds-unconfirmed"
+    );
+
+    // Paying for both evicts both
+    let replacement = tx_paying(coin(2), 79_000);
+    mempool
+        .accept_to_memory_pool(replacement.clone(), &utxo_set, &no_coinbase, 2, false)
+        .await
+        .into_result()
+        .unwrap();
+    assert_eq!(mempool.size(), 2);
+    assert!(mempool.get_transaction(&calculate_tx_id(&child)).is_none());
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&original))
+        .is_none());
+    assert!(!mempool.spends_outpoint(&OutPoint {
+        hash: calculate_tx_id(&original),
+        index: 0,
+    }));
+}
+
+#[tokio::test]
+async fn test_reorg_returns_disconnected_and_removes_confirmed_transactions() {
+    use bllvm_node::node::block_processor::ChainActivation;
+    use bllvm_protocol::mempool::calculate_tx_id;
+
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    let output_of = |tx: &Transaction| OutPoint {
+        hash: calculate_tx_id(tx),
+        index: 0,
+    };
+    let confirmed = |value: i64| UTXO {
+        value,
+        script_pubkey: vec![0x51],
+        height: 1,
+    };
+    let no_coinbase = |_: &OutPoint| false;
+    let mut mempool = MempoolManager::new();
+
+    // Pooled: one transaction the new chain confirms, one it double-spends
+    let confirmed_in_new_chain = tx_paying(coin(2), 90_000);
+    let double_spent = tx_paying(coin(3), 90_000);
+    mempool
+        .add_transaction(confirmed_in_new_chain.clone())
+        .await
+        .unwrap();
+    mempool.add_transaction(double_spent.clone()).await.unwrap();
+
+    // The old chain confirmed a parent and child the new chain does not
+    let parent = tx_paying(coin(1), 90_000);
+    let child = tx_paying(output_of(&parent), 80_000);
+    let double_spend = tx_paying(coin(3), 85_000);
+
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(coin(1), confirmed(100_000));
+    utxo_set.insert(output_of(&confirmed_in_new_chain), confirmed(90_000));
+    utxo_set.insert(output_of(&double_spend), confirmed(85_000));
+
+    let activation = ChainActivation {
+        disconnected: 1,
+        connected: 1,
+        height: 1,
+        disconnected_transactions: vec![parent.clone(), child.clone()],
+        connected_transactions: vec![confirmed_in_new_chain.clone(), double_spend],
+    };
+    let returned = mempool
+        .update_for_reorg(&activation, &utxo_set, &no_coinbase, 2)
+        .await;
+
+    assert_eq!(returned, 2);
+    assert_eq!(mempool.size(), 2);
+    assert!(mempool.get_transaction(&calculate_tx_id(&parent)).is_some());
+    assert!(mempool.get_transaction(&calculate_tx_id(&child)).is_some());
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&confirmed_in_new_chain))
+        .is_none());
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&double_spent))
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_expired_transactions_evicted_with_descendants() {
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use bllvm_protocol::serialization::transaction::serialize_transaction;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    const HOUR: u64 = 3600;
+    let now = Arc::new(AtomicU64::new(1_700_000_000));
+    let clock = Arc::clone(&now);
+    let mut mempool =
+        MempoolManager::new().with_clock(Arc::new(move || clock.load(Ordering::Relaxed)));
+    mempool.set_expiry(336 * HOUR, 600);
+
+    let parent = tx_paying(
+        OutPoint {
+            hash: [1; 32],
+            index: 0,
+        },
+        90_000,
+    );
+    mempool.add_transaction(parent.clone()).await.unwrap();
+
+    // A week later: a child and grandchild of the parent, plus an unrelated tx
+    now.fetch_add(168 * HOUR, Ordering::Relaxed);
+    let child = tx_paying(
+        OutPoint {
+            hash: calculate_tx_id(&parent),
+            index: 0,
+        },
+        80_000,
+    );
+    let grandchild = tx_paying(
+        OutPoint {
+            hash: calculate_tx_id(&child),
+            index: 0,
+        },
+        70_000,
+    );
+    let unrelated = tx_paying(
+        OutPoint {
+            hash: [2; 32],
+            index: 0,
+        },
+        90_000,
+    );
+    for tx in [&child, &grandchild, &unrelated] {
+        mempool.add_transaction(tx.clone()).await.unwrap();
+    }
+    assert_eq!(mempool.size(), 4);
+
+    // Not yet expired
+    mempool.process_once().await.unwrap();
+    assert_eq!(mempool.size(), 4);
+
+    // Parent passes two weeks; its descendants go with it
+    now.fetch_add(169 * HOUR, Ordering::Relaxed);
+    mempool.process_once().await.unwrap();
+    assert_eq!(mempool.size(), 1);
+    assert!(mempool
+        .get_transaction(&calculate_tx_id(&unrelated))
+        .is_some());
+    assert_eq!(mempool.bytes(), serialize_transaction(&unrelated).len());
+    assert!(!mempool.spends_outpoint(&OutPoint {
+        hash: [1; 32],
+        index: 0,
+    }));
+}
+
+#[test]
+fn test_dust_outputs_rejected_but_op_return_allowed() {
+    use bllvm_node::node::mempool::dust_threshold;
+
+    let prevout = OutPoint {
+        hash: [3u8; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        prevout.clone(),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    let no_coinbase = |_: &OutPoint| false;
+    let mempool = MempoolManager::new();
+
+    let mut p2wpkh = vec![0x00, 0x14];
+    p2wpkh.extend([0x22; 20]);
+    let with_outputs = |outputs: Vec<TransactionOutput>| Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout: prevout.clone(),
+            script_sig: vec![],
+            sequence: 0xffffffff,
+        }],
+        outputs: outputs.into(),
+        lock_time: 0,
+    };
+
+    // (31 + 67) vbytes at 3 sat/vB
+    let threshold = dust_threshold(
+        &TransactionOutput {
+            value: 0,
+            script_pubkey: p2wpkh.clone(),
+        },
+        mempool.dust_relay_fee_per_kvb(),
+    );
+    assert_eq!(threshold, 294);
+
+    let dust = with_outputs(vec![
+        TransactionOutput {
+            value: 90_000,
+            script_pubkey: vec![0x51],
+        },
+        TransactionOutput {
+            value: 293,
+            script_pubkey: p2wpkh.clone(),
+        },
+    ]);
+    assert_eq!(
+        mempool
+            .check_acceptance(&dust, &utxo_set, &no_coinbase, 2)
+            .unwrap_err(),
+        "dust"
+    );
+
+    let at_threshold = with_outputs(vec![TransactionOutput {
+        value: 294,
+        script_pubkey: p2wpkh.clone(),
+    }]);
+    assert!(mempool
+        .check_acceptance(&at_threshold, &utxo_set, &no_coinbase, 2)
+        .is_ok());
+
+    // A zero-value OP_RETURN output is not dust
+    let op_return = TransactionOutput {
+        value: 0,
+        script_pubkey: vec![0x6a, 0x04, 0xde, 0xad, 0xbe, 0xef],
+    };
+    let data = with_outputs(vec![
+        TransactionOutput {
+            value: 90_000,
+            script_pubkey: p2wpkh.clone(),
+        },
+        op_return.clone(),
+    ]);
+    assert!(mempool
+        .check_acceptance(&data, &utxo_set, &no_coinbase, 2)
+        .is_ok());
+
+    // ...but only one is allowed
+    let two_data = with_outputs(vec![
+        TransactionOutput {
+            value: 90_000,
+            script_pubkey: p2wpkh,
+        },
+        op_return.clone(),
+        op_return,
+    ]);
+    assert_eq!(
+        mempool
+            .check_acceptance(&two_data, &utxo_set, &no_coinbase, 2)
+            .unwrap_err(),
+        "multi-op-return"
+    );
+
+    // A zero dust fee disables the limit
+    mempool.set_dust_relay_fee(0);
+    assert!(mempool
+        .check_acceptance(&dust, &utxo_set, &no_coinbase, 2)
+        .is_ok());
+}
+
+#[test]
+fn test_standardness_policy() {
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    let p2sh = {
+        let mut script = vec![0xa9, 0x14];
+        script.extend([0x33; 20]);
+        script.push(0x87);
+        script
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        coin(1),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    utxo_set.insert(
+        coin(2),
+        UTXO {
+            value: 100_000,
+            script_pubkey: p2sh,
+            height: 1,
+        },
+    );
+    let no_coinbase = |_: &OutPoint| false;
+    let mempool = MempoolManager::new();
+    mempool.set_require_standard(true);
+
+    let mut p2wpkh = vec![0x00, 0x14];
+    p2wpkh.extend([0x22; 20]);
+    let build = |prevout: OutPoint, script_sig: Vec<u8>, outputs: Vec<Vec<u8>>| Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout,
+            script_sig,
+            sequence: 0xffffffff,
+        }],
+        outputs: outputs
+            .into_iter()
+            .map(|script_pubkey| TransactionOutput {
+                value: 1000,
+                script_pubkey,
+            })
+            .collect::<Vec<_>>()
+            .into(),
+        lock_time: 0,
+    };
+    let reject = |tx: &Transaction| {
+        mempool
+            .check_acceptance(tx, &utxo_set, &no_coinbase, 2)
+            .unwrap_err()
+    };
+
+    let standard = build(coin(1), vec![0x01, 0x80], vec![p2wpkh.clone()]);
+    assert!(mempool
+        .check_acceptance(&standard, &utxo_set, &no_coinbase, 2)
+        .is_ok());
+
+    // Bare OP_TRUE output
+    let op_true = build(coin(1), vec![], vec![vec![0x51]]);
+    assert_eq!(reject(&op_true), "scriptpubkey");
+
+    // 1-of-4 bare multisig
+    let mut multisig = vec![0x51];
+    for _ in 0..4 {
+        multisig.push(0x21);
+        multisig.extend([0x02; 33]);
+    }
+    multisig.extend([0x54, 0xae]);
+    assert_eq!(
+        reject(&build(coin(1), vec![], vec![multisig])),
+        "bare-multisig"
+    );
+
+    // Pushing 5 as data instead of OP_5
+    let non_minimal = build(coin(1), vec![0x01, 0x05], vec![p2wpkh.clone()]);
+    assert_eq!(reject(&non_minimal), "scriptsig-non-minimal-push");
+
+    // Over 400,000 weight units
+    let oversized = build(coin(1), vec![], vec![p2wpkh.clone(); 3300]);
+    assert_eq!(reject(&oversized), "tx-size");
+
+    // P2SH redeem script of 800 x (OP_16 OP_CHECKMULTISIG)
+    let redeem_script: Vec<u8> = [0x60, 0xae].repeat(800);
+    let mut script_sig = vec![0x4d];
+    script_sig.extend((redeem_script.len() as u16).to_le_bytes());
+    script_sig.extend(&redeem_script);
+    let sigops = build(coin(2), script_sig, vec![p2wpkh]);
+    assert_eq!(reject(&sigops), "bad-txns-too-many-sigops");
+
+    // acceptnonstdtxn lets the non-standard transaction through
+    mempool.set_require_standard(false);
+    assert!(mempool
+        .check_acceptance(&op_true, &utxo_set, &no_coinbase, 2)
+        .is_ok());
+}
+
+#[test]
+fn test_datacarrier_policy() {
+    use bllvm_protocol::{ConsensusProof, ValidationResult};
+
+    let prevout = OutPoint {
+        hash: [1; 32],
+        index: 0,
+    };
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        prevout.clone(),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    let no_coinbase = |_: &OutPoint| false;
+    let mempool = MempoolManager::new();
+    mempool.set_require_standard(true);
+
+    let mut p2wpkh = vec![0x00, 0x14];
+    p2wpkh.extend([0x22; 20]);
+    let op_return = |data_len: usize| {
+        let mut script = vec![0x6a, 0x4c, data_len as u8];
+        script.extend(vec![0xab; data_len]);
+        TransactionOutput {
+            value: 0,
+            script_pubkey: script,
+        }
+    };
+    let with_data = |data: Vec<TransactionOutput>| {
+        let mut outputs = vec![TransactionOutput {
+            value: 90_000,
+            script_pubkey: p2wpkh.clone(),
+        }];
+        outputs.extend(data);
+        Transaction {
+            version: 1,
+            inputs: bllvm_protocol::tx_inputs![TransactionInput {
+                prevout: prevout.clone(),
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: outputs.into(),
+            lock_time: 0,
+        }
+    };
+    let check = |tx: &Transaction| mempool.check_acceptance(tx, &utxo_set, &no_coinbase, 2);
+
+    // 80 bytes of data make an 83-byte script, the default limit
+    let small = with_data(vec![op_return(80)]);
+    assert!(check(&small).is_ok());
+    let large = with_data(vec![op_return(81)]);
+    assert_eq!(check(&large).unwrap_err(), "scriptpubkey");
+    let multiple = with_data(vec![op_return(4), op_return(4)]);
+    assert_eq!(check(&multiple).unwrap_err(), "multi-op-return");
+
+    // A larger datacarriersize admits the larger output
+    mempool.set_datacarrier(true, 200);
+    assert!(check(&large).is_ok());
+
+    // With datacarrier off no OP_RETURN output is relayed
+    mempool.set_datacarrier(false, 200);
+    assert_eq!(mempool.datacarrier_size(), None);
+    assert_eq!(check(&small).unwrap_err(), "scriptpubkey");
+
+    // Policy only: the transactions remain valid for inclusion in blocks
+    for tx in [&small, &large, &multiple] {
+        assert!(matches!(
+            ConsensusProof::new().validate_transaction(tx),
+            Ok(ValidationResult::Valid)
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_getorphantxs_reports_announcing_peer() {
+    use bllvm_node::network::NetworkManager;
+    use bllvm_node::rpc::mempool::MempoolRpc;
+    use bllvm_protocol::block::calculate_tx_id;
+    use serde_json::json;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    let network = Arc::new(NetworkManager::new("127.0.0.1:0".parse().unwrap()));
+    let orphan = Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: [9u8; 32],
+                index: 0,
+            },
+            script_sig: vec![],
+            sequence: 0xffffffff,
+        }],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 1000,
+            script_pubkey: vec![0x51],
+        }],
+        lock_time: 0,
+    };
+    let txid = hex::encode(calculate_tx_id(&orphan));
+    let peer: SocketAddr = "192.0.2.7:8333".parse().unwrap();
+    assert!(network
+        .orphan_pool()
+        .lock()
+        .await
+        .add(orphan, peer, 1_700_000_000));
+
+    let rpc = MempoolRpc::new().with_network(Arc::clone(&network));
+    let info = rpc.getorphaninfo(&json!([])).await.unwrap();
+    assert_eq!(info["size"], 1);
+    assert!(info["bytes"].as_u64().unwrap() > 0);
+
+    assert_eq!(rpc.getorphantxs(&json!([])).await.unwrap(), json!([txid]));
+
+    let verbose = rpc.getorphantxs(&json!([1])).await.unwrap();
+    let entry = &verbose[0];
+    assert_eq!(entry["txid"], txid);
+    assert_eq!(entry["wtxid"], txid);
+    assert_eq!(entry["bytes"], info["bytes"]);
+    assert_eq!(entry["entry_time"], 1_700_000_000u64);
+    assert_eq!(entry["from"], peer.to_string());
+    assert!(entry.get("hex").is_none());
+
+    let with_hex = rpc.getorphantxs(&json!([2])).await.unwrap();
+    assert!(with_hex[0]["hex"].is_string());
+    assert!(rpc.getorphantxs(&json!([3])).await.is_err());
+}
+
+#[tokio::test]
+async fn test_segwit_transaction_txid_wtxid_and_weight() {
+    use bllvm_node::node::block_processor::{calculate_wtxid, serialize_transaction_with_witness};
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let tx = spending_tx(OutPoint {
+        hash: [9u8; 32],
+        index: 0,
+    });
+    let witness = vec![vec![0x30; 72], vec![0x02; 33]];
+    storage
+        .transactions()
+        .index_transaction_with_witness(&tx, std::slice::from_ref(&witness), &[7u8; 32], 1, 1)
+        .unwrap();
+
+    // base: 61 bytes; witness: marker, flag, item count, two items = 110 bytes
+    let txid = hex::encode(calculate_tx_id(&tx));
+    let wtxid = hex::encode(calculate_wtxid(&tx, std::slice::from_ref(&witness)));
+    let wire_hex = hex::encode(serialize_transaction_with_witness(
+        &tx,
+        std::slice::from_ref(&witness),
+    ));
+    assert_ne!(txid, wtxid);
+    let metadata = storage
+        .transactions()
+        .get_metadata(&calculate_tx_id(&tx))
+        .unwrap()
+        .unwrap();
+    assert_eq!((metadata.size, metadata.weight), (171, 354));
+
+    let rpc = RawTxRpc::with_dependencies(storage, Arc::new(MempoolManager::new()), None, None);
+    let verbose = rpc.getrawtransaction(&json!([txid, true])).await.unwrap();
+    assert_eq!(verbose["txid"], txid);
+    assert_eq!(verbose["hash"], wtxid);
+    assert_eq!(verbose["size"], 171);
+    assert_eq!(verbose["weight"], 354);
+    assert_eq!(verbose["vsize"], 89);
+    assert_eq!(verbose["hex"], wire_hex);
+    assert_eq!(
+        rpc.getrawtransaction(&json!([txid, false, null, false]))
+            .await
+            .unwrap(),
+        json!(raw_hex(&tx))
+    );
+
+    let decoded = rpc.decoderawtransaction(&json!([wire_hex])).await.unwrap();
+    assert_eq!(decoded["txid"], txid);
+    assert_eq!(decoded["hash"], wtxid);
+    assert_eq!(decoded["weight"], 354);
+    assert_eq!(decoded["vsize"], 89);
+    assert_eq!(decoded["vin"][0]["txinwitness"][1], hex::encode([0x02; 33]));
+
+    // Without a witness, txid and hash agree and weight is 4x the size
+    let stripped = rpc
+        .decoderawtransaction(&json!([raw_hex(&tx)]))
+        .await
+        .unwrap();
+    assert_eq!(stripped["hash"], txid);
+    assert_eq!(stripped["weight"], 61 * 4);
+
+    // Mempool acceptance reports the wtxid and the witness-discounted vsize
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        tx.inputs[0].prevout.clone(),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    let no_coinbase = |_: &OutPoint| false;
+    let mempool = MempoolManager::new();
+    let accepted = mempool
+        .check_acceptance_with_witness(
+            &tx,
+            std::slice::from_ref(&witness),
+            &utxo_set,
+            &no_coinbase,
+            2,
+        )
+        .unwrap();
+    assert_eq!(hex::encode(accepted.wtxid), wtxid);
+    assert_eq!(accepted.vsize, 89);
+    let accepted = mempool
+        .check_acceptance(&tx, &utxo_set, &no_coinbase, 2)
+        .unwrap();
+    assert_eq!(hex::encode(accepted.wtxid), txid);
+    assert_eq!(accepted.vsize, 61);
+}
+
+#[tokio::test]
+async fn test_accepted_transaction_keeps_its_witness() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let tx = spending_tx(OutPoint {
+        hash: [9u8; 32],
+        index: 0,
+    });
+    let txid = calculate_tx_id(&tx);
+    let mut utxo_set: UtxoSet = HashMap::new();
+    utxo_set.insert(
+        tx.inputs[0].prevout.clone(),
+        UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        },
+    );
+    let witnesses = vec![vec![vec![0x30; 72], vec![0x02; 33]]];
+    let no_coinbase = |_: &OutPoint| false;
+    let mut mempool = MempoolManager::new();
+    let result = mempool
+        .accept_to_memory_pool_with_witness(
+            tx,
+            witnesses.clone(),
+            &utxo_set,
+            &no_coinbase,
+            2,
+            false,
+        )
+        .await;
+    assert!(result.is_accepted());
+    assert_eq!(mempool.get_witness(&txid), witnesses);
+
+    assert!(mempool.remove_transaction(&txid));
+    assert!(mempool.get_witness(&txid).is_empty());
+}
+
+#[tokio::test]
+async fn test_testmempoolaccept_keeps_every_input_witness() {
+    use bllvm_node::node::block_processor::{calculate_wtxid, serialize_transaction_with_witness};
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let coin = |hash: u8| OutPoint {
+        hash: [hash; 32],
+        index: 0,
+    };
+    for hash in [1u8, 2] {
+        let utxo = UTXO {
+            value: 100_000,
+            script_pubkey: vec![0x51],
+            height: 1,
+        };
+        storage.utxos().add_utxo(&coin(hash), &utxo).unwrap();
+    }
+    let mut tx = tx_paying(coin(1), 190_000);
+    tx.inputs.push(TransactionInput {
+        prevout: coin(2),
+        script_sig: vec![],
+        sequence: 0xffffffff,
+    });
+
+    // Only the second input carries a witness
+    let witnesses = vec![vec![], vec![vec![0x30; 72], vec![0x02; 33]]];
+    let wtxid = hex::encode(calculate_wtxid(&tx, &witnesses));
+    assert_ne!(wtxid, hex::encode(calculate_tx_id(&tx)));
+    let wire_hex = hex::encode(serialize_transaction_with_witness(&tx, &witnesses));
+
+    let rpc = RawTxRpc::with_dependencies(storage, Arc::new(MempoolManager::new()), None, None);
+    let result = rpc.testmempoolaccept(&json!([[wire_hex]])).await.unwrap();
+    assert_eq!(result[0]["allowed"], true);
+    assert_eq!(result[0]["txid"], hex::encode(calculate_tx_id(&tx)));
+    assert_eq!(result[0]["wtxid"], wtxid);
+}
+
+#[tokio::test]
+async fn test_gettxspendingprevout_reports_mempool_spender() {
+    use bllvm_node::rpc::mempool::MempoolRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let spent = OutPoint {
+        hash: [3u8; 32],
+        index: 0,
+    };
+    let tx = spending_tx(spent.clone());
+    let spender = hex::encode(calculate_tx_id(&tx));
+
+    let mut mempool = MempoolManager::new();
+    mempool.add_transaction(tx).await.unwrap();
+    let rpc = MempoolRpc::with_dependencies(Arc::new(mempool), storage);
+
+    let txid = hex::encode(spent.hash);
+    let result = rpc
+        .gettxspendingprevout(&json!([[
+            {"txid": txid, "vout": 0},
+            {"txid": txid, "vout": 1}
+        ]]))
+        .await
+        .unwrap();
+    assert_eq!(
+        result,
+        json!([
+            {"txid": txid, "vout": 0, "spendingtxid": spender},
+            {"txid": txid, "vout": 1}
+        ])
+    );
+
+    assert!(rpc.gettxspendingprevout(&json!([[]])).await.is_err());
+    assert!(rpc
+        .gettxspendingprevout(&json!([[{"txid": "zz", "vout": 0}]]))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_submitpackage_accepts_cpfp_package() {
+    use bllvm_node::rpc::rawtx::RawTxRpc;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mempool::calculate_tx_id;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let coin = OutPoint {
+        hash: [4u8; 32],
+        index: 0,
+    };
+    let utxo = UTXO {
+        value: 100_000,
+        script_pubkey: vec![0x51],
+        height: 1,
+    };
+    storage.utxos().add_utxo(&coin, &utxo).unwrap();
+
+    // Parent pays 1 sat, well below the minimum relay fee; the child pays 9,999
+    let parent = tx_paying(coin.clone(), 99_999);
+    let parent_out = OutPoint {
+        hash: calculate_tx_id(&parent),
+        index: 0,
+    };
+    let child = tx_paying(parent_out.clone(), 90_000);
+    let parent_txid = hex::encode(calculate_tx_id(&parent));
+    let child_txid = hex::encode(calculate_tx_id(&child));
+
+    let rpc = RawTxRpc::with_dependencies(
+        Arc::clone(&storage),
+        Arc::new(MempoolManager::new()),
+        None,
+        None,
+    );
+    let alone = rpc
+        .testmempoolaccept(&json!([[raw_hex(&parent)]]))
+        .await
+        .unwrap();
+    assert_eq!(alone[0]["reject-reason"], json!("min relay fee not met"));
+
+    let result = rpc
+        .submitpackage(&json!([[raw_hex(&parent), raw_hex(&child)]]))
+        .await
+        .unwrap();
+    assert_eq!(result["package_msg"], json!("success"));
+    assert_eq!(
+        result["tx-results"][&parent_txid]["txid"],
+        json!(parent_txid)
+    );
+    assert_eq!(
+        result["tx-results"][&parent_txid]["fees"]["base"],
+        json!(0.00000001)
+    );
+    assert_eq!(
+        result["tx-results"][&child_txid]["fees"]["base"],
+        json!(0.00009999)
+    );
+
+    // Children must follow their parents
+    assert!(rpc
+        .submitpackage(&json!([[raw_hex(&child), raw_hex(&parent)]]))
+        .await
+        .is_err());
+
+    // A low-fee child is not carried by its parent
+    let utxo_set = storage.utxos().get_all_utxos().unwrap();
+    let mut mempool = MempoolManager::new();
+    let funded_parent = tx_paying(coin.clone(), 90_000);
+    let cheap_child = tx_paying(
+        OutPoint {
+            hash: calculate_tx_id(&funded_parent),
+            index: 0,
+        },
+        89_999,
+    );
+    let rejected = mempool
+        .accept_package(
+            vec![funded_parent, cheap_child],
+            &utxo_set,
+            &|_: &OutPoint| false,
+            2,
+            false,
+        )
+        .await;
+    assert_eq!(
+        rejected.package_error.as_deref(),
+        Some("transaction failed")
+    );
+    assert!(rejected.results[0].1.is_ok());
+    assert_eq!(
+        rejected.results[1].1,
+        Err("min relay fee not met".to_string())
+    );
+    assert_eq!(mempool.size(), 0);
+
+    // The CPFP package is inserted as a unit
+    let accepted = mempool
+        .accept_package(
+            vec![parent, child],
+            &utxo_set,
+            &|_: &OutPoint| false,
+            2,
+            false,
+        )
+        .await;
+    assert!(accepted.is_accepted());
+    assert_eq!(mempool.size(), 2);
+    assert!(mempool.spends_outpoint(&coin));
+    assert!(mempool.spends_outpoint(&parent_out));
+}
+
+#[tokio::test]
+async fn test_mempool_fee_histogram_sums_to_mempool_vsize() {
+    use bllvm_node::rpc::mempool::MempoolRpc;
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let mut mempool = MempoolManager::new();
+    // Each spending transaction is 61 bytes: pay 1, 20 and 20 sat/vB
+    for (i, fee) in [61i64, 1220, 1220].into_iter().enumerate() {
+        let prevout = OutPoint {
+            hash: [20 + i as u8; 32],
+            index: 0,
+        };
+        let coin = UTXO {
+            value: 900 + fee,
+            script_pubkey: vec![0x51],
+            height: 1,
+        };
+        storage.utxos().add_utxo(&prevout, &coin).unwrap();
+        mempool.add_transaction(spending_tx(prevout)).await.unwrap();
+    }
+    // Inputs unknown to the UTXO set: counted as paying nothing
+    mempool
+        .add_transaction(spending_tx(OutPoint {
+            hash: [30u8; 32],
+            index: 0,
+        }))
+        .await
+        .unwrap();
+    let total_vsize = mempool.bytes() as u64;
+    let rpc = MempoolRpc::with_dependencies(Arc::new(mempool), storage);
+
+    let result = rpc.getmempoolfeehistogram(&json!([])).await.unwrap();
+    let histogram = result["fee_histogram"].as_object().unwrap();
+    let summed: u64 = histogram
+        .values()
+        .map(|bucket| bucket["sizes"].as_u64().unwrap())
+        .sum();
+    assert_eq!(summed, total_vsize);
+    assert_eq!(result["total_vsize"], json!(total_vsize));
+    assert_eq!(histogram["0"]["count"], json!(1));
+    assert_eq!(histogram["1"]["count"], json!(1));
+    assert_eq!(histogram["20"]["count"], json!(2));
+    assert_eq!(histogram["20"]["fees"], json!(2440));
+    assert_eq!(histogram["20"]["to_feerate"], json!(25));
+    assert_eq!(histogram["10000"]["to_feerate"], json!(null));
+
+    let custom = rpc.getmempoolfeehistogram(&json!([[5, 50]])).await.unwrap();
+    let custom = custom["fee_histogram"].as_object().unwrap();
+    assert_eq!(custom.len(), 3);
+    assert_eq!(custom["0"]["count"], json!(2));
+    assert_eq!(custom["5"]["sizes"], json!(2 * total_vsize / 4));
+
+    assert!(rpc.getmempoolfeehistogram(&json!([[5, 5]])).await.is_err());
+    assert!(rpc.getmempoolfeehistogram(&json!([[]])).await.is_err());
+    assert!(rpc.getmempoolfeehistogram(&json!(["1"])).await.is_err());
+}
@@ -1,11 +1,15 @@
 //! Tests for mining RPC implementation
 
 use bllvm_node::node::mempool::MempoolManager;
-use bllvm_node::rpc::mining::MiningRpc;
+use bllvm_node::rpc::mining::{
+    build_witness_commitment, find_witness_commitment, split_coinbase_value, MiningRpc,
+};
 use bllvm_node::storage::Storage;
 use serde_json::json;
 use std::sync::Arc;
 use tempfile::TempDir;
+mod common;
+use common::*;
 
 #[tokio::test]
 async fn test_get_mining_info() {
@@ -24,6 +28,321 @@ async fn test_get_mining_info() {
     assert!(info.get("chain").is_some());
 }
 
+#[tokio::test]
+async fn test_get_mining_info_reflects_regtest_chain() {
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_protocol::OutPoint;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+
+    // Three regtest blocks, ten minutes apart
+    let mut prev_hash = [0u8; 32];
+    let mut tip_header = None;
+    for height in 0..3u64 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(prev_hash)
+            .set_timestamp(1_600_000_000 + 600 * height as u32)
+            .with_bits(0x207fffff)
+            .add_coinbase_transaction(vec![0x51])
+            .build();
+        let hash = storage.blocks().get_block_hash(&block);
+        storage.blocks().store_block(&block).unwrap();
+        storage.blocks().store_height(height, &hash).unwrap();
+        prev_hash = hash;
+        tip_header = Some(block.header);
+    }
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: prev_hash,
+            tip_header: tip_header.unwrap(),
+            height: 2,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "regtest".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    let mut mempool = MempoolManager::new();
+    let tx = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: [1u8; 32],
+            index: 0,
+        })
+        .add_output(1000, vec![0x51])
+        .build();
+    mempool.add_transaction(tx).await.unwrap();
+
+    let mining_rpc = MiningRpc::with_dependencies(storage, Arc::new(mempool));
+    let info = mining_rpc.get_mining_info().await.unwrap();
+
+    assert_eq!(info["blocks"], 2);
+    assert_eq!(info["chain"], "regtest");
+    assert_eq!(info["pooledtx"], 1);
+
+    // 0x207fffff is the regtest proof-of-work limit
+    let difficulty = info["difficulty"].as_f64().unwrap();
+    assert!((difficulty - 4.656542373906925e-10).abs() < 1e-18);
+
+    // Two blocks' worth of work over 1200 seconds
+    let expected_hashps = 2.0 * difficulty * 4294967296.0 / 1200.0;
+    let hashps = info["networkhashps"].as_f64().unwrap();
+    assert!((hashps - expected_hashps).abs() < expected_hashps * 1e-9);
+
+    // No block template generated yet
+    assert!(info["currentblockweight"].is_null());
+    assert!(info["currentblocktx"].is_null());
+}
+
+#[tokio::test]
+async fn test_generate_to_address_mines_spendable_coinbase() {
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::OutPoint;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.blocks().store_block(&genesis).unwrap();
+    storage.blocks().store_height(0, &genesis_hash).unwrap();
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: genesis_hash,
+            tip_header: genesis.header.clone(),
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "regtest".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()));
+    let address = "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c";
+    let hashes = mining_rpc
+        .generate_to_address(&json!([101, address]))
+        .await
+        .unwrap();
+    assert_eq!(hashes.as_array().unwrap().len(), 101);
+    assert_eq!(storage.chain().get_height().unwrap(), Some(101));
+
+    // Block 1's coinbase pays the address and has matured
+    let block1_hash = storage.blocks().get_hash_by_height(1).unwrap().unwrap();
+    assert_eq!(hashes[0], json!(hex::encode(block1_hash)));
+    let block1 = storage.blocks().get_block(&block1_hash).unwrap().unwrap();
+    assert_eq!(
+        block1.transactions[0].outputs.last(),
+        Some(&build_witness_commitment(&[], &[]).output)
+    );
+    let outpoint = OutPoint {
+        hash: calculate_tx_id(&block1.transactions[0]),
+        index: 0,
+    };
+    let coin = storage.utxos().get_utxo(&outpoint).unwrap().unwrap();
+    let mut expected_script = vec![0x00, 0x14];
+    expected_script.extend([0x11; 20]);
+    assert_eq!(coin.script_pubkey, expected_script);
+    assert_eq!(coin.height, 1);
+
+    let spend = TestTransactionBuilder::new()
+        .add_input(outpoint)
+        .add_output(coin.value as u64 - 10_000, vec![0x51])
+        .build();
+    let utxo_set = storage.utxos().get_all_utxos().unwrap();
+    assert!(MempoolManager::new()
+        .check_acceptance(
+            &spend,
+            &utxo_set,
+            &|outpoint: &OutPoint| storage.utxos().is_coinbase_or_unknown(outpoint),
+            102
+        )
+        .is_ok());
+
+    // Mainnet-style addresses are rejected on regtest
+    let err = mining_rpc
+        .generate_to_address(&json!([1, "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -5);
+}
+
+#[tokio::test]
+async fn test_generate_to_address_forwards_fees_to_commons() {
+    use bllvm_node::config::FeeForwardingConfig;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_protocol::ConsensusProof;
+
+    // Rounding favours the miner and never loses value
+    let outputs = split_coinbase_value(1001, &[0x51], Some((&[0x52], 33)));
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0].value, 671);
+    assert_eq!(outputs[1].value, 330);
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.blocks().store_block(&genesis).unwrap();
+    storage.blocks().store_height(0, &genesis_hash).unwrap();
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: genesis_hash,
+            tip_header: genesis.header.clone(),
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "regtest".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    let commons = "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c";
+    let miner = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    let forwarding = FeeForwardingConfig {
+        enabled: true,
+        commons_address: Some(commons.to_string()),
+        forwarding_percentage: 33,
+        contributor_id: None,
+    };
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+            .with_fee_forwarding(forwarding.clone());
+    mining_rpc
+        .generate_to_address(&json!([1, miner]))
+        .await
+        .unwrap();
+
+    let block1_hash = storage.blocks().get_hash_by_height(1).unwrap().unwrap();
+    let block1 = storage.blocks().get_block(&block1_hash).unwrap().unwrap();
+    let coinbase = &block1.transactions[0];
+    // Miner output, commons output, witness commitment
+    assert_eq!(coinbase.outputs.len(), 3);
+    let mut commons_script = vec![0x00, 0x14];
+    commons_script.extend([0x11; 20]);
+    assert_eq!(coinbase.outputs[1].script_pubkey, commons_script);
+
+    let subsidy = ConsensusProof::new().get_block_subsidy(1) as u64;
+    let miner_value = coinbase.outputs[0].value as u64;
+    let commons_value = coinbase.outputs[1].value as u64;
+    assert_eq!(miner_value + commons_value, subsidy);
+    assert_eq!(commons_value, subsidy * 33 / 100);
+
+    // A mainnet commons address is invalid on regtest
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+            .with_fee_forwarding(FeeForwardingConfig {
+                commons_address: Some("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string()),
+                ..forwarding
+            });
+    let err = mining_rpc
+        .generate_to_address(&json!([1, miner]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -5);
+    assert_eq!(storage.chain().get_height().unwrap(), Some(1));
+}
+
+#[tokio::test]
+async fn test_generate_to_address_requires_regtest() {
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: storage.blocks().get_block_hash(&genesis),
+            tip_header: genesis.header,
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams::default(),
+        })
+        .unwrap();
+
+    let mining_rpc = MiningRpc::with_dependencies(storage, Arc::new(MempoolManager::new()));
+    let err = mining_rpc
+        .generate_to_address(&json!([1, "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -32601);
+}
+
+#[test]
+fn test_witness_commitment_matches_known_blocks() {
+    use bllvm_node::node::block_processor::calculate_wtxid;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::OutPoint;
+    use sha2::{Digest, Sha256};
+
+    // Coinbase-only blocks on mainnet all carry this commitment output
+    let empty = build_witness_commitment(&[], &[]);
+    assert_eq!(empty.witness_root, [0u8; 32]);
+    assert_eq!(empty.output.value, 0);
+    assert_eq!(
+        hex::encode(&empty.output.script_pubkey),
+        "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9"
+    );
+
+    // One transaction: root = H(coinbase wtxid (zero) || wtxid)
+    let tx = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: [7u8; 32],
+            index: 0,
+        })
+        .add_output(1000, vec![0x51])
+        .build();
+    let double_sha256 = |data: &[u8]| -> [u8; 32] { Sha256::digest(Sha256::digest(data)).into() };
+    let mut leaves = [0u8; 64];
+    leaves[32..].copy_from_slice(&calculate_tx_id(&tx));
+    let root = double_sha256(&leaves);
+    let mut preimage = [0u8; 64];
+    preimage[..32].copy_from_slice(&root);
+
+    let commitment = build_witness_commitment(std::slice::from_ref(&tx), &[]);
+    assert_eq!(commitment.witness_root, root);
+    assert_eq!(commitment.commitment, double_sha256(&preimage));
+
+    // With a witness the leaf is the wtxid, not the txid
+    let witnesses = vec![vec![vec![vec![0x30; 72], vec![0x02; 33]]]];
+    leaves[32..].copy_from_slice(&calculate_wtxid(&tx, &witnesses[0]));
+    let with_witness = build_witness_commitment(std::slice::from_ref(&tx), &witnesses);
+    assert_eq!(with_witness.witness_root, double_sha256(&leaves));
+    assert_ne!(with_witness.witness_root, root);
+
+    let coinbase = TestTransactionBuilder::new()
+        .add_output(5_000_000_000, vec![0x51])
+        .build();
+    let mut with_commitment = coinbase.clone();
+    with_commitment.outputs =
+        bllvm_protocol::tx_outputs![coinbase.outputs[0].clone(), commitment.output.clone()];
+    assert_eq!(find_witness_commitment(&coinbase), None);
+    assert_eq!(
+        find_witness_commitment(&with_commitment),
+        Some(commitment.commitment)
+    );
+}
+
 #[tokio::test]
 async fn test_get_block_template() {
     let temp_dir = TempDir::new().unwrap();
@@ -74,3 +393,582 @@ async fn test_estimate_smart_fee() {
     assert!(fee_estimate.get("blocks").is_some());
     assert_eq!(fee_estimate.get("blocks").unwrap().as_u64().unwrap(), 6);
 }
+
+#[tokio::test]
+async fn test_get_block_template_longpoll_returns_on_new_block() {
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.blocks().store_block(&genesis).unwrap();
+    storage.blocks().store_height(0, &genesis_hash).unwrap();
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: genesis_hash,
+            tip_header: genesis.header.clone(),
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "regtest".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    let mining_rpc = Arc::new(MiningRpc::with_dependencies(
+        Arc::clone(&storage),
+        Arc::new(MempoolManager::new()),
+    ));
+    // Tip hash followed by the mempool transaction count
+    let current_longpoll_id = || {
+        let tip_hash = storage.chain().get_tip_hash().unwrap().unwrap();
+        format!("{}0", hex::encode(tip_hash))
+    };
+    let params = json!([{ "longpollid": current_longpoll_id() }]);
+
+    // Waits while the tip is unchanged
+    let waiting = tokio::spawn({
+        let mining_rpc = Arc::clone(&mining_rpc);
+        let params = params.clone();
+        async move { mining_rpc.get_block_template(&params).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!waiting.is_finished());
+    assert_eq!(mining_rpc.longpoll_waiters(), 1);
+
+    // Returns once a block is connected
+    let address = "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c";
+    mining_rpc
+        .generate_to_address(&json!([1, address]))
+        .await
+        .unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), waiting)
+        .await
+        .expect("longpoll should return after a new block")
+        .unwrap();
+    if let Ok(template) = result {
+        assert_eq!(template["longpollid"], json!(current_longpoll_id()));
+    }
+    assert_eq!(mining_rpc.longpoll_waiters(), 0);
+
+    // A longpoll dropped by a disconnecting client stops counting as a waiter
+    let params = json!([{ "longpollid": current_longpoll_id() }]);
+    let abandoned = tokio::spawn({
+        let mining_rpc = Arc::clone(&mining_rpc);
+        async move { mining_rpc.get_block_template(&params).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(mining_rpc.longpoll_waiters(), 1);
+    abandoned.abort();
+    let _ = abandoned.await;
+    assert_eq!(mining_rpc.longpoll_waiters(), 0);
+
+    // Times out with the current template
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+            .with_longpoll_timeout(Duration::from_millis(100));
+    let params = json!([{ "longpollid": current_longpoll_id() }]);
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        mining_rpc.get_block_template(&params),
+    )
+    .await
+    .expect("longpoll should time out");
+    assert!(mining_rpc
+        .get_block_template(&json!([{ "longpollid": "abc" }]))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_get_block_template_segwit_rules() {
+    use bllvm_node::rpc::mining::strip_witness_transactions;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::{OutPoint, UTXO};
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.blocks().store_block(&genesis).unwrap();
+    storage.blocks().store_height(0, &genesis_hash).unwrap();
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: genesis_hash,
+            tip_header: genesis.header.clone(),
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "regtest".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+    MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+        .generate_to_address(&json!([
+            101,
+            "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c"
+        ]))
+        .await
+        .unwrap();
+
+    // Block 1's coinbase pays a witness program: spending it needs a
+    // witness, and so does anything built on that spend
+    let block1_hash = storage.blocks().get_hash_by_height(1).unwrap().unwrap();
+    let block1 = storage.blocks().get_block(&block1_hash).unwrap().unwrap();
+    let coinbase_outpoint = OutPoint {
+        hash: calculate_tx_id(&block1.transactions[0]),
+        index: 0,
+    };
+    let coin = storage
+        .utxos()
+        .get_utxo(&coinbase_outpoint)
+        .unwrap()
+        .unwrap();
+    let witness_spend = TestTransactionBuilder::new()
+        .add_input(coinbase_outpoint)
+        .add_output(coin.value as u64 - 10_000, vec![0x51])
+        .build();
+    let descendant = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: calculate_tx_id(&witness_spend),
+            index: 0,
+        })
+        .add_output(coin.value as u64 - 20_000, vec![0x51])
+        .build();
+    let legacy_outpoint = OutPoint {
+        hash: [7u8; 32],
+        index: 0,
+    };
+    storage
+        .utxos()
+        .add_utxo(
+            &legacy_outpoint,
+            &UTXO {
+                value: 100_000,
+                script_pubkey: vec![0x51],
+                height: 1,
+            },
+        )
+        .unwrap();
+    let legacy_spend = TestTransactionBuilder::new()
+        .add_input(legacy_outpoint)
+        .add_output(90_000, vec![0x51])
+        .build();
+
+    let utxo_set = storage.utxos().get_all_utxos().unwrap();
+    assert_eq!(
+        strip_witness_transactions(
+            vec![
+                descendant.clone(),
+                legacy_spend.clone(),
+                witness_spend.clone()
+            ],
+            &utxo_set
+        ),
+        vec![legacy_spend.clone()]
+    );
+
+    let mut mempool = MempoolManager::new();
+    for tx in [&witness_spend, &descendant, &legacy_spend] {
+        mempool.add_transaction(tx.clone()).await.unwrap();
+    }
+    let mining_rpc = MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(mempool));
+    let txids = |template: &serde_json::Value| -> Vec<String> {
+        template["transactions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tx| tx["txid"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // A segwit client gets the witness commitment and weight limits
+    let template = mining_rpc
+        .get_block_template(&json!([{ "rules": ["segwit"], "capabilities": ["longpoll"] }]))
+        .await
+        .unwrap();
+    assert!(template["rules"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("!segwit")));
+    assert!(template["default_witness_commitment"].is_string());
+    assert_eq!(template["weightlimit"], 4000000);
+    assert_eq!(template["sigoplimit"], 80000);
+    assert_eq!(template["vbavailable"], json!({}));
+
+    // Anyone else gets a stripped template
+    let template = mining_rpc.get_block_template(&json!([{}])).await.unwrap();
+    assert!(template["rules"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("!segwit")));
+    assert!(template.get("default_witness_commitment").is_none());
+    assert!(template.get("weightlimit").is_none());
+    assert_eq!(template["sizelimit"], 1000000);
+    assert_eq!(template["sigoplimit"], 20000);
+    let stripped_txids = txids(&template);
+    for tx in [&witness_spend, &descendant] {
+        assert!(!stripped_txids.contains(&hex::encode(calculate_tx_id(tx))));
+    }
+
+    // Rules must be a list of names
+    let err = mining_rpc
+        .get_block_template(&json!([{ "rules": "segwit" }]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -32602);
+}
+
+#[tokio::test]
+async fn test_submit_header_then_block() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+    use bllvm_protocol::serialization::serialize_block_header;
+
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let regtest_storage = |temp_dir: &TempDir| {
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let genesis_hash = storage.blocks().get_block_hash(&genesis);
+        storage.blocks().store_block(&genesis).unwrap();
+        storage.blocks().store_height(0, &genesis_hash).unwrap();
+        storage
+            .chain()
+            .store_chain_info(&ChainInfo {
+                tip_hash: genesis_hash,
+                tip_header: genesis.header.clone(),
+                height: 0,
+                total_work: 0,
+                chain_params: ChainParams {
+                    network: "regtest".to_string(),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        storage
+    };
+
+    // Mine two blocks on one node, then feed them to another
+    let miner_dir = TempDir::new().unwrap();
+    let miner_storage = regtest_storage(&miner_dir);
+    MiningRpc::with_dependencies(Arc::clone(&miner_storage), Arc::new(MempoolManager::new()))
+        .generate_to_address(&json!([2, "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c"]))
+        .await
+        .unwrap();
+    let mined: Vec<_> = (1..=2)
+        .map(|height| {
+            let hash = miner_storage
+                .blocks()
+                .get_hash_by_height(height)
+                .unwrap()
+                .unwrap();
+            let block = miner_storage.blocks().get_block(&hash).unwrap().unwrap();
+            let witnesses = miner_storage
+                .blocks()
+                .get_witness(&hash)
+                .unwrap()
+                .unwrap_or_default();
+            (hash, block, witnesses)
+        })
+        .collect();
+    let header_hex = |index: usize| hex::encode(serialize_block_header(&mined[index].1.header));
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = regtest_storage(&temp_dir);
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()));
+
+    // Headers must connect to a known header and pass proof of work
+    let err = mining_rpc
+        .submit_header(&json!([header_hex(1)]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -25);
+    let mut weak_header = mined[0].1.header.clone();
+    weak_header.bits = 0x1d00ffff;
+    let err = mining_rpc
+        .submit_header(&json!([hex::encode(serialize_block_header(&weak_header))]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -25);
+    assert!(mining_rpc.submit_header(&json!(["00"])).await.is_err());
+
+    // Both headers are accepted ahead of their blocks
+    for index in 0..2 {
+        assert_eq!(
+            mining_rpc
+                .submit_header(&json!([header_hex(index)]))
+                .await
+                .unwrap(),
+            serde_json::Value::Null
+        );
+    }
+    let (hash1, hash2) = (mined[0].0, mined[1].0);
+    assert_eq!(
+        storage.chain().get_chain_tips().unwrap(),
+        vec![(hash2, 2, 2, "headers-only".to_string())]
+    );
+    assert_eq!(storage.chain().get_best_header_height().unwrap(), Some(2));
+    assert!(storage.blocks().get_header(&hash1).unwrap().is_some());
+    assert!(!storage.blocks().has_block(&hash1).unwrap());
+    assert_eq!(storage.chain().get_height().unwrap(), Some(0));
+
+    // The bodies then connect normally
+    for (hash, block, witnesses) in &mined {
+        let block_hex = hex::encode(serialize_block(block, witnesses));
+        assert_eq!(
+            mining_rpc.submit_block(&json!([block_hex])).await.unwrap(),
+            serde_json::Value::Null
+        );
+        assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(*hash));
+    }
+    assert_eq!(storage.chain().get_height().unwrap(), Some(2));
+    assert!(storage.chain().get_chain_tips().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_submit_block_checks_signet_solution() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let regtest_storage = |temp_dir: &TempDir| {
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let genesis_hash = storage.blocks().get_block_hash(&genesis);
+        storage.blocks().store_block(&genesis).unwrap();
+        storage.blocks().store_height(0, &genesis_hash).unwrap();
+        storage
+            .chain()
+            .store_chain_info(&ChainInfo {
+                tip_hash: genesis_hash,
+                tip_header: genesis.header.clone(),
+                height: 0,
+                total_work: 0,
+                chain_params: ChainParams {
+                    network: "regtest".to_string(),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        storage
+    };
+
+    // A block without a signet solution
+    let miner_dir = TempDir::new().unwrap();
+    let miner_storage = regtest_storage(&miner_dir);
+    MiningRpc::with_dependencies(Arc::clone(&miner_storage), Arc::new(MempoolManager::new()))
+        .generate_to_address(&json!([1, "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c"]))
+        .await
+        .unwrap();
+    let hash = miner_storage
+        .blocks()
+        .get_hash_by_height(1)
+        .unwrap()
+        .unwrap();
+    let block = miner_storage.blocks().get_block(&hash).unwrap().unwrap();
+    let witnesses = miner_storage
+        .blocks()
+        .get_witness(&hash)
+        .unwrap()
+        .unwrap_or_default();
+    let block_hex = hex::encode(serialize_block(&block, &witnesses));
+
+    // OP_FALSE cannot be satisfied
+    let temp_dir = TempDir::new().unwrap();
+    let storage = regtest_storage(&temp_dir);
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+            .with_signet_challenge(Some(vec![0x00]));
+    let err = mining_rpc
+        .submit_block(&json!([block_hex]))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("bad-signet-blksig"), "{}", err.message);
+    assert_eq!(storage.chain().get_height().unwrap(), Some(0));
+
+    // OP_TRUE accepts the empty solution
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+            .with_signet_challenge(Some(vec![0x51]));
+    assert_eq!(
+        mining_rpc.submit_block(&json!([block_hex])).await.unwrap(),
+        serde_json::Value::Null
+    );
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(hash));
+}
+
+#[tokio::test]
+async fn test_generate_to_address_needs_trivial_signet_challenge() {
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+
+    let genesis = TestBlockBuilder::new()
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    storage
+        .chain()
+        .store_chain_info(&ChainInfo {
+            tip_hash: storage.blocks().get_block_hash(&genesis),
+            tip_header: genesis.header.clone(),
+            height: 0,
+            total_work: 0,
+            chain_params: ChainParams {
+                network: "signet".to_string(),
+                ..Default::default()
+            },
+        })
+        .unwrap();
+
+    // Blocks this node mines carry no solution, so a real signet challenge
+    // would reject every one of them
+    for challenge in [None, Some(vec![0x00])] {
+        let err =
+            MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()))
+                .with_signet_challenge(challenge)
+                .generate_to_address(&json!([1, "tb1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3"]))
+                .await
+                .unwrap_err();
+        assert!(err.message.contains("OP_TRUE"), "{}", err.message);
+    }
+    assert_eq!(storage.chain().get_height().unwrap(), Some(0));
+}
+
+#[tokio::test]
+async fn test_get_block_template_proposal() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::storage::chainstate::{ChainInfo, ChainParams};
+
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(1_600_000_000)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(vec![0x51])
+        .build();
+    let regtest_storage = |temp_dir: &TempDir| {
+        let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+        let genesis_hash = storage.blocks().get_block_hash(&genesis);
+        storage.blocks().store_block(&genesis).unwrap();
+        storage.blocks().store_height(0, &genesis_hash).unwrap();
+        storage
+            .chain()
+            .store_chain_info(&ChainInfo {
+                tip_hash: genesis_hash,
+                tip_header: genesis.header.clone(),
+                height: 0,
+                total_work: 0,
+                chain_params: ChainParams {
+                    network: "regtest".to_string(),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        storage
+    };
+
+    // A pool assembles a block on the same tip
+    let miner_dir = TempDir::new().unwrap();
+    let miner_storage = regtest_storage(&miner_dir);
+    MiningRpc::with_dependencies(Arc::clone(&miner_storage), Arc::new(MempoolManager::new()))
+        .generate_to_address(&json!([1, "bcrt1qzyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3lgth6c"]))
+        .await
+        .unwrap();
+    let hash = miner_storage
+        .blocks()
+        .get_hash_by_height(1)
+        .unwrap()
+        .unwrap();
+    let block = miner_storage.blocks().get_block(&hash).unwrap().unwrap();
+    let witnesses = miner_storage
+        .blocks()
+        .get_witness(&hash)
+        .unwrap()
+        .unwrap_or_default();
+    let proposal = |block: &bllvm_protocol::Block| {
+        json!([{
+            "mode": "proposal",
+            "data": hex::encode(serialize_block(block, &witnesses)),
+        }])
+    };
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = regtest_storage(&temp_dir);
+    let mining_rpc =
+        MiningRpc::with_dependencies(Arc::clone(&storage), Arc::new(MempoolManager::new()));
+
+    // Acceptable, but not connected
+    assert_eq!(
+        mining_rpc
+            .get_block_template(&proposal(&block))
+            .await
+            .unwrap(),
+        serde_json::Value::Null
+    );
+    assert_eq!(storage.chain().get_height().unwrap(), Some(0));
+    assert!(!storage.blocks().has_block(&hash).unwrap());
+
+    // Proposals are checked before the work is done: an unmined header is
+    // acceptable, though submitblock turns the same block down
+    let mut unmined = block.clone();
+    while bllvm_protocol::pow::check_proof_of_work(&unmined.header).unwrap() {
+        unmined.header.nonce += 1;
+    }
+    assert_eq!(
+        mining_rpc
+            .get_block_template(&proposal(&unmined))
+            .await
+            .unwrap(),
+        serde_json::Value::Null
+    );
+    let err = mining_rpc
+        .submit_block(&json!([hex::encode(serialize_block(&unmined, &witnesses))]))
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("high-hash"), "{}", err.message);
+    assert_eq!(storage.chain().get_height().unwrap(), Some(0));
+
+    // A bad merkle root is rejected with the reason
+    let mut bad_merkle = block.clone();
+    bad_merkle.header.merkle_root[0] ^= 1;
+    assert_eq!(
+        mining_rpc
+            .get_block_template(&proposal(&bad_merkle))
+            .await
+            .unwrap(),
+        json!("bad-txnmrklroot")
+    );
+
+    // Proposals need block data
+    let err = mining_rpc
+        .get_block_template(&json!([{ "mode": "proposal" }]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -32602);
+    let err = mining_rpc
+        .get_block_template(&json!([{ "mode": "bogus" }]))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code.code(), -32602);
+}
@@ -550,6 +550,233 @@ async fn test_mining_coordinator_mining_state() {
     // assert_eq!(miner.get_mining_state(), common::MiningState::Stopped);
 }
 
+#[test]
+fn test_assume_valid_skips_scripts_only_for_ancestors() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mining::calculate_merkle_root;
+    use bllvm_protocol::pow::check_proof_of_work;
+    use bllvm_protocol::{Block, Hash, UtxoSet, UTXO};
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    let blockstore = storage.blocks();
+    let genesis_hash = [0x22u8; 32];
+    blockstore.store_height(0, &genesis_hash).unwrap();
+
+    // P2PKH coins; the spends below only push OP_1, so their scripts fail
+    let funding = [0x11u8; 32];
+    let mut utxo_set = UtxoSet::new();
+    for index in 0..2 {
+        utxo_set.insert(
+            OutPoint {
+                hash: funding,
+                index,
+            },
+            UTXO {
+                value: 1_000_000,
+                script_pubkey: p2pkh_script(random_hash20()),
+                height: 0,
+            },
+        );
+    }
+    let mine = |prev: Hash, index: u64| -> Block {
+        let spend = TestTransactionBuilder::new()
+            .add_input(OutPoint {
+                hash: funding,
+                index,
+            })
+            .add_output(900_000, p2pkh_script(random_hash20()))
+            .build();
+        let mut block = TestBlockBuilder::new()
+            .set_prev_hash(prev)
+            .with_bits(0x207fffff)
+            .add_coinbase_transaction(p2pkh_script(random_hash20()))
+            .add_transaction(spend)
+            .build();
+        block.header.merkle_root = calculate_merkle_root(&block.transactions).unwrap();
+        while !check_proof_of_work(&block.header).unwrap() {
+            block.header.nonce += 1;
+        }
+        block
+    };
+
+    let block1 = mine(genesis_hash, 0);
+    let hash1 = blockstore.store_header(&block1.header).unwrap();
+    let block1_data = serialize_block(&block1, &[]);
+
+    // Without assumevalid the bad script is rejected
+    let utxo_store = Some(storage.utxos_arc());
+    let mut verify_all = sync::SyncCoordinator::new().with_assume_valid(None);
+    let mut scratch = utxo_set.clone();
+    let result = verify_all.process_block(&blockstore, &block1_data, 1, &mut scratch, None, None);
+    assert!(!matches!(result, Ok(true)));
+    assert_eq!(scratch.len(), utxo_set.len());
+
+    // The assumed-valid block itself connects without script checks
+    let mut sync = sync::SyncCoordinator::new()
+        .with_assume_valid(Some(hash1))
+        .with_utxo_store(utxo_store);
+    assert!(sync
+        .process_block(&blockstore, &block1_data, 1, &mut utxo_set, None, None)
+        .unwrap());
+    assert!(!utxo_set.contains_key(&OutPoint {
+        hash: funding,
+        index: 0
+    }));
+
+    // Its descendants are fully verified again
+    let block2 = mine(hash1, 1);
+    let result = sync.process_block(
+        &blockstore,
+        &serialize_block(&block2, &[]),
+        2,
+        &mut utxo_set,
+        None,
+        None,
+    );
+    assert!(!matches!(result, Ok(true)));
+    assert!(utxo_set.contains_key(&OutPoint {
+        hash: funding,
+        index: 1
+    }));
+}
+
+#[test]
+fn test_assume_valid_still_enforces_coinbase_maturity() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::mining::calculate_merkle_root;
+    use bllvm_protocol::pow::check_proof_of_work;
+    use bllvm_protocol::{UtxoSet, UTXO};
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    let blockstore = storage.blocks();
+    let genesis = TestBlockBuilder::new()
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(p2pkh_script(random_hash20()))
+        .build();
+    blockstore.store_block(&genesis).unwrap();
+    let genesis_hash = blockstore.get_block_hash(&genesis);
+    blockstore.store_height(0, &genesis_hash).unwrap();
+    // Marks the genesis coinbase output in the coinbase-outputs index
+    storage.connect_block(&genesis, 0).unwrap();
+
+    let coinbase = OutPoint {
+        hash: calculate_tx_id(&genesis.transactions[0]),
+        index: 0,
+    };
+    let funding = OutPoint {
+        hash: [0x11u8; 32],
+        index: 0,
+    };
+    let mut utxo_set = UtxoSet::new();
+    for outpoint in [&coinbase, &funding] {
+        utxo_set.insert(
+            outpoint.clone(),
+            UTXO {
+                value: 5_000_000_000,
+                script_pubkey: p2pkh_script(random_hash20()),
+                height: 0,
+            },
+        );
+    }
+    let connect = |prevout: &OutPoint, utxo_set: &mut UtxoSet| -> bool {
+        let spend = TestTransactionBuilder::new()
+            .add_input(prevout.clone())
+            .add_output(4_900_000_000, p2pkh_script(random_hash20()))
+            .build();
+        let mut block = TestBlockBuilder::new()
+            .set_prev_hash(genesis_hash)
+            .set_timestamp(1)
+            .with_bits(0x207fffff)
+            .add_coinbase_transaction(p2pkh_script(random_hash20()))
+            .add_transaction(spend)
+            .build();
+        block.header.merkle_root = calculate_merkle_root(&block.transactions).unwrap();
+        while !check_proof_of_work(&block.header).unwrap() {
+            block.header.nonce += 1;
+        }
+        let hash = blockstore.store_header(&block.header).unwrap();
+        let mut sync = sync::SyncCoordinator::new()
+            .with_assume_valid(Some(hash))
+            .with_utxo_store(Some(storage.utxos_arc()));
+        sync.process_block(
+            &blockstore,
+            &serialize_block(&block, &[]),
+            1,
+            utxo_set,
+            None,
+            None,
+        )
+        .unwrap()
+    };
+
+    // Scripts are skipped, but the genesis coinbase is only one block deep
+    assert!(!connect(&coinbase, &mut utxo_set));
+    assert!(utxo_set.contains_key(&coinbase));
+
+    // The same spend of an ordinary coin of that age connects
+    assert!(connect(&funding, &mut utxo_set));
+    assert!(!utxo_set.contains_key(&funding));
+}
+
+#[test]
+fn test_signet_rejects_blocks_without_block_signature() {
+    use bllvm_node::node::block_processor::serialize_block;
+    use bllvm_node::node::chain_params::{ChainParams, Network};
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::mining::calculate_merkle_root;
+    use bllvm_protocol::pow::check_proof_of_work;
+    use bllvm_protocol::UtxoSet;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    let blockstore = storage.blocks();
+    let genesis_hash = [0x33u8; 32];
+    blockstore.store_height(0, &genesis_hash).unwrap();
+
+    // A regtest-style block: proof of work, but no signet solution
+    let mut block = TestBlockBuilder::new()
+        .set_prev_hash(genesis_hash)
+        .with_bits(0x207fffff)
+        .add_coinbase_transaction(p2pkh_script(random_hash20()))
+        .build();
+    block.header.merkle_root = calculate_merkle_root(&block.transactions).unwrap();
+    while !check_proof_of_work(&block.header).unwrap() {
+        block.header.nonce += 1;
+    }
+    let hash = blockstore.store_header(&block.header).unwrap();
+    let block_data = serialize_block(&block, &[]);
+    let challenge = ChainParams::new(Network::Signet).signet_challenge;
+    assert!(challenge.is_some());
+
+    // Scripts are skipped for the block itself; the signet check is not
+    let mut signet = sync::SyncCoordinator::new()
+        .with_assume_valid(Some(hash))
+        .with_signet_challenge(challenge);
+    let mut utxo_set = UtxoSet::new();
+    assert!(!signet
+        .process_block(&blockstore, &block_data, 1, &mut utxo_set, None, None)
+        .unwrap());
+    // Clones (as handed to sync tasks) keep the challenge
+    assert!(!signet
+        .clone()
+        .process_block(&blockstore, &block_data, 1, &mut utxo_set, None, None)
+        .unwrap());
+    assert!(utxo_set.is_empty());
+    assert!(!blockstore.has_block(&hash).unwrap());
+
+    // The same block is fine on regtest
+    let mut regtest = sync::SyncCoordinator::new().with_assume_valid(Some(hash));
+    assert!(regtest
+        .process_block(&blockstore, &block_data, 1, &mut utxo_set, None, None)
+        .unwrap());
+    assert_eq!(utxo_set.len(), 1);
+}
+
 // ===== COMPONENT INTERACTION TESTS =====
 
 #[tokio::test]
@@ -717,3 +944,45 @@ async fn test_node_health_check() {
     let result = node.run_once().await;
     assert!(result.is_ok());
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial]
+async fn test_shutdown_stops_tasks_and_flushes_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let network_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let rpc_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+
+    let mut node = Node::new(
+        temp_dir.path().to_str().unwrap(),
+        network_addr,
+        rpc_addr,
+        Some(ProtocolVersion::Regtest),
+    )
+    .unwrap();
+    let shutdown = node.shutdown_handle();
+    for _ in 0..3 {
+        shutdown.spawn(std::future::pending());
+    }
+    let flushes_before = node.storage().flush_count();
+
+    let running = tokio::spawn(async move {
+        node.start().await.unwrap();
+        node
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    // Network listeners and background tasks joined the spawned ones
+    assert!(shutdown.active_tasks() > 3);
+
+    shutdown.trigger();
+    let mut node = tokio::time::timeout(std::time::Duration::from_secs(15), running)
+        .await
+        .expect("node did not stop in time")
+        .unwrap();
+    assert_eq!(shutdown.active_tasks(), 0);
+    assert_eq!(node.storage().flush_count(), flushes_before + 1);
+    assert!(temp_dir.path().join(mempool::MEMPOOL_FILE).exists());
+
+    // Stopping again does not flush again
+    node.stop().await.unwrap();
+    assert_eq!(node.storage().flush_count(), flushes_before + 1);
+}
@@ -0,0 +1,92 @@
+//! Block and transaction notification feed tests
+
+use bllvm_node::node::mempool::MempoolManager;
+use bllvm_node::node::notifications::{NotificationPublisher, NotificationTopic};
+use bllvm_protocol::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+/// Connect to a topic and wait until the publisher has registered us
+async fn subscribe(publisher: &NotificationPublisher, topic: NotificationTopic) -> TcpStream {
+    let addr = publisher
+        .bind(topic, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    for _ in 0..100 {
+        if publisher.subscriber_count(topic) == 1 {
+            return stream;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("subscriber was not registered");
+}
+
+/// Read one framed message: (topic, body, sequence)
+async fn read_message(stream: &mut TcpStream) -> (String, Vec<u8>, u32) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        let topic_len = stream.read_u8().await.unwrap() as usize;
+        let mut topic = vec![0u8; topic_len];
+        stream.read_exact(&mut topic).await.unwrap();
+        let body_len = stream.read_u32_le().await.unwrap() as usize;
+        let mut body = vec![0u8; body_len];
+        stream.read_exact(&mut body).await.unwrap();
+        let sequence = stream.read_u32_le().await.unwrap();
+        (String::from_utf8(topic).unwrap(), body, sequence)
+    })
+    .await
+    .expect("no notification received")
+}
+
+#[tokio::test]
+async fn test_hashblock_notification_after_block_connects() {
+    let publisher = NotificationPublisher::new(16);
+    assert!(!publisher.is_enabled(NotificationTopic::HashBlock));
+    let mut stream = subscribe(&publisher, NotificationTopic::HashBlock).await;
+    assert!(publisher.is_enabled(NotificationTopic::HashBlock));
+
+    publisher.notify_block(&[0x11; 32], &[0xde, 0xad]);
+    publisher.notify_block(&[0x22; 32], &[0xbe, 0xef]);
+
+    let (topic, body, sequence) = read_message(&mut stream).await;
+    assert_eq!(topic, "hashblock");
+    assert_eq!(body, vec![0x11; 32]);
+    assert_eq!(sequence, 0);
+    let (_, body, sequence) = read_message(&mut stream).await;
+    assert_eq!(body, vec![0x22; 32]);
+    assert_eq!(sequence, 1);
+}
+
+#[tokio::test]
+async fn test_hashtx_notification_on_mempool_acceptance() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let publisher = Arc::new(NotificationPublisher::new(16));
+    let mut stream = subscribe(&publisher, NotificationTopic::HashTx).await;
+    let mut mempool = MempoolManager::new();
+    mempool.set_notifications(Arc::clone(&publisher));
+
+    let tx = Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: [5u8; 32],
+                index: 0,
+            },
+            script_sig: vec![],
+            sequence: 0xffffffff,
+        }],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 900,
+            script_pubkey: vec![0x51],
+        }],
+        lock_time: 0,
+    };
+    mempool.add_transaction(tx.clone()).await.unwrap();
+
+    let (topic, body, _) = read_message(&mut stream).await;
+    assert_eq!(topic, "hashtx");
+    assert_eq!(body, calculate_tx_id(&tx).to_vec());
+}
@@ -28,7 +28,10 @@ async fn test_blockchain_rpc() {
 
     // Test getblock (may fail if storage not set up - that's expected)
     let block_result = blockchain
-        .get_block("0000000000000000000000000000000000000000000000000000000000000000")
+        .get_block(
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            1,
+        )
         .await;
     if let Ok(block) = block_result {
         // If block found, verify structure
@@ -230,7 +233,7 @@ async fn test_blockchain_rpc_getblock() {
 
     // Test getblock with genesis block hash (may fail if storage not set up - that's expected)
     let genesis_hash = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
-    let block_result = blockchain.get_block(genesis_hash).await;
+    let block_result = blockchain.get_block(genesis_hash, 1).await;
 
     if let Ok(block) = block_result {
         // If block found, verify structure
@@ -312,6 +315,57 @@ async fn test_blockchain_rpc_gettxoutsetinfo() {
     // assert!(info.get("total_amount").is_some());
 }
 
+#[tokio::test]
+async fn test_gettxoutsetinfo_muhash_matches_coinstatsindex() {
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::OutPoint;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let coinbase = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: [0u8; 32],
+            index: 0xffffffff,
+        })
+        .add_output(5_000_000_000, p2pkh_script(random_hash20()))
+        .add_output(1_000, p2pkh_script(random_hash20()))
+        .build();
+    let genesis = TestBlockBuilder::new().add_transaction(coinbase).build();
+    storage.blocks().store_block(&genesis).unwrap();
+    storage.chain().initialize(&genesis.header).unwrap();
+    storage.coin_stats().set_enabled(true);
+    storage.connect_block(&genesis, 0).unwrap();
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let indexed = blockchain
+        .get_txoutset_info(&serde_json::json!([]))
+        .await
+        .unwrap();
+    assert_eq!(indexed["txouts"], 2);
+    assert!(indexed.get("hash_serialized_2").is_none());
+
+    // The same hash computed by scanning the UTXO set
+    storage.coin_stats().set_enabled(false);
+    let scanned = blockchain
+        .get_txoutset_info(&serde_json::json!(["muhash"]))
+        .await
+        .unwrap();
+    assert_eq!(scanned["muhash"], indexed["muhash"]);
+    assert_eq!(scanned["total_amount"], indexed["total_amount"]);
+
+    let no_hash = blockchain
+        .get_txoutset_info(&serde_json::json!(["none"]))
+        .await
+        .unwrap();
+    assert!(no_hash.get("muhash").is_none());
+    assert!(blockchain
+        .get_txoutset_info(&serde_json::json!(["sha1"]))
+        .await
+        .is_err());
+}
+
 // ===== NETWORK RPC COMPREHENSIVE TESTS =====
 
 #[tokio::test]
@@ -584,3 +638,956 @@ async fn test_rpc_concurrent_requests() {
         assert!(result.is_ok());
     }
 }
+
+#[tokio::test]
+async fn test_getvalidationstats_percentiles() {
+    use bllvm_node::node::performance::PerformanceProfiler;
+    use std::sync::Arc;
+
+    let profiler = Arc::new(PerformanceProfiler::new(1000));
+    // Record 1..=100ms so nearest-rank percentiles land on exact sample values
+    for ms in 1..=100 {
+        profiler.record_block_processing(Duration::from_millis(ms));
+    }
+    for ms in [5, 10, 15, 20] {
+        profiler.record_utxo_application(Duration::from_millis(ms));
+    }
+
+    let control = control::ControlRpc::new().with_profiler(Arc::clone(&profiler));
+    let stats = control
+        .getvalidationstats(&serde_json::json!([]))
+        .await
+        .unwrap();
+
+    let block = &stats["block_validation"];
+    assert_eq!(block["count"], 100);
+    assert_eq!(block["p50_ms"].as_f64().unwrap(), 50.0);
+    assert_eq!(block["p90_ms"].as_f64().unwrap(), 90.0);
+    assert_eq!(block["p99_ms"].as_f64().unwrap(), 99.0);
+    assert_eq!(block["max_ms"].as_f64().unwrap(), 100.0);
+
+    let utxo = &stats["utxo_application"];
+    assert_eq!(utxo["count"], 4);
+    assert_eq!(utxo["p50_ms"].as_f64().unwrap(), 10.0);
+    assert_eq!(utxo["max_ms"].as_f64().unwrap(), 20.0);
+
+    assert_eq!(stats["message_processing"]["count"], 0);
+}
+
+#[tokio::test]
+async fn test_getvalidationstats_ring_buffer_evicts_oldest() {
+    use bllvm_node::node::performance::PerformanceProfiler;
+    use std::sync::Arc;
+
+    let profiler = Arc::new(PerformanceProfiler::new(10));
+    for ms in 1..=20 {
+        profiler.record_message_processing(Duration::from_millis(ms));
+    }
+
+    let control = control::ControlRpc::new().with_profiler(profiler);
+    let stats = control
+        .getvalidationstats(&serde_json::json!([]))
+        .await
+        .unwrap();
+
+    // Only the most recent 10 samples (11..=20ms) are retained
+    let messages = &stats["message_processing"];
+    assert_eq!(messages["count"], 10);
+    assert_eq!(messages["p50_ms"].as_f64().unwrap(), 15.0);
+    assert_eq!(messages["max_ms"].as_f64().unwrap(), 20.0);
+}
+
+#[tokio::test]
+async fn test_gethealth_reports_network_degraded_without_peers() {
+    use bllvm_node::network::NetworkManager;
+    use bllvm_node::node::mempool::MempoolManager;
+    use bllvm_node::storage::Storage;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let network = Arc::new(NetworkManager::new("127.0.0.1:0".parse().unwrap()));
+    let control = control::ControlRpc::new()
+        .with_dependencies(storage, Arc::new(MempoolManager::new()))
+        .with_network(network);
+
+    let health = control.gethealth(&serde_json::json!([])).await.unwrap();
+    assert_eq!(health["status"], "degraded");
+    assert_eq!(health["network"], false);
+    assert_eq!(health["storage"], true);
+    assert_eq!(health["rpc"], true);
+    assert_eq!(health["peers"], 0);
+    assert_eq!(health["tip_height"], 0);
+    assert_eq!(health["mempool_size"], 0);
+
+    // Without a network manager there is nothing to degrade
+    let health = control::ControlRpc::new()
+        .gethealth(&serde_json::json!([]))
+        .await
+        .unwrap();
+    assert_eq!(health["status"], "healthy");
+}
+
+/// Coinbase paying 50 BTC to `script_pubkey`
+fn coinbase_paying(script_pubkey: Vec<u8>) -> bllvm_protocol::Transaction {
+    TestTransactionBuilder::new()
+        .add_input(bllvm_protocol::OutPoint {
+            hash: [0u8; 32],
+            index: 0xffffffff,
+        })
+        .add_output(5_000_000_000, script_pubkey)
+        .build()
+}
+
+/// Store, connect and index a block, making it the chain tip
+fn connect_test_block(
+    storage: &bllvm_node::storage::Storage,
+    block: &bllvm_protocol::Block,
+    height: u64,
+) {
+    use bllvm_node::node::block_processor::store_block_with_context;
+
+    let hash = storage.blocks().get_block_hash(block);
+    store_block_with_context(&storage.blocks(), block, &[], height).unwrap();
+    storage.connect_block(block, height).unwrap();
+    for (index, tx) in block.transactions.iter().enumerate() {
+        storage
+            .transactions()
+            .index_transaction(tx, &hash, height, index as u32)
+            .unwrap();
+    }
+    storage
+        .chain()
+        .update_tip(&hash, &block.header, height)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_getblock_verbosity_3_includes_prevouts() {
+    use bllvm_node::node::block_processor::store_block_with_context;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::OutPoint;
+    use std::sync::Arc;
+
+    let coinbase = || coinbase_paying(p2pkh_script(random_hash20()));
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    storage
+        .chain()
+        .initialize(&TestBlockBuilder::new().build_header())
+        .unwrap();
+    let block1 = TestBlockBuilder::new().add_transaction(coinbase()).build();
+    connect_test_block(&storage, &block1, 1);
+
+    // Spends block 1's coinbase, then chains a spend within the block
+    let spend = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: calculate_tx_id(&block1.transactions[0]),
+            index: 0,
+        })
+        .add_output(4_999_990_000, p2pkh_script(random_hash20()))
+        .build();
+    let chained = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: calculate_tx_id(&spend),
+            index: 0,
+        })
+        .add_output(4_999_980_000, p2pkh_script(random_hash20()))
+        .build();
+    let block2 = TestBlockBuilder::new()
+        .set_prev_hash(storage.blocks().get_block_hash(&block1))
+        .add_transaction(coinbase())
+        .add_transaction(spend)
+        .add_transaction(chained)
+        .build();
+    connect_test_block(&storage, &block2, 2);
+    let hash2 = hex::encode(storage.blocks().get_block_hash(&block2));
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let block = blockchain.get_block(&hash2, 3).await.unwrap();
+    assert_eq!(block["height"], 2);
+    assert_eq!(block["confirmations"], 1);
+    let txs = block["tx"].as_array().unwrap();
+    assert_eq!(txs.len(), 3);
+    assert!(txs[0]["vin"][0].get("prevout").is_none());
+
+    let prevout = &txs[1]["vin"][0]["prevout"];
+    assert_eq!(prevout["value"], 50.0);
+    assert_eq!(prevout["height"], 1);
+    assert_eq!(prevout["generated"], true);
+    assert_eq!(txs[1]["fee"], 0.0001);
+
+    let chained_prevout = &txs[2]["vin"][0]["prevout"];
+    assert_eq!(chained_prevout["value"], 49.9999);
+    assert_eq!(chained_prevout["height"], 2);
+    assert_eq!(chained_prevout["generated"], false);
+
+    // Verbosity 2 has fees but no prevouts; 1 lists txids
+    let decoded = blockchain.get_block(&hash2, 2).await.unwrap();
+    assert!(decoded["tx"][1]["vin"][0].get("prevout").is_none());
+    assert_eq!(decoded["tx"][1]["fee"], 0.0001);
+    let ids = blockchain.get_block(&hash2, 1).await.unwrap();
+    assert_eq!(ids["tx"][1], txs[1]["txid"]);
+    assert!(blockchain.get_block(&hash2, 0).await.unwrap().is_string());
+
+    // Without undo data or a txindex, unknown prevouts are left out
+    let pruned_dir = tempfile::TempDir::new().unwrap();
+    let pruned = Arc::new(Storage::new(pruned_dir.path()).unwrap());
+    store_block_with_context(&pruned.blocks(), &block2, &[], 2).unwrap();
+    let pruned_rpc = blockchain::BlockchainRpc::with_dependencies(pruned);
+    let block = pruned_rpc.get_block(&hash2, 3).await.unwrap();
+    assert!(block["tx"][1]["vin"][0].get("prevout").is_none());
+    assert!(block["tx"][1].get("fee").is_none());
+    assert_eq!(block["tx"][2]["vin"][0]["prevout"]["value"], 49.9999);
+}
+
+#[tokio::test]
+async fn test_scanblocks_finds_only_paying_blocks() {
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    let address_script = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let mut prev_hash = storage.blocks().get_block_hash(&genesis);
+    let mut paying = Vec::new();
+    for height in 1..=5 {
+        let script = if height == 2 || height == 4 {
+            address_script.clone()
+        } else {
+            p2pkh_script(random_hash20())
+        };
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(prev_hash)
+            .add_transaction(coinbase_paying(script))
+            .build();
+        connect_test_block(&storage, &block, height);
+        prev_hash = storage.blocks().get_block_hash(&block);
+        assert!(storage.filters().has_filter(&prev_hash).unwrap());
+        if height == 2 || height == 4 {
+            paying.push(hex::encode(prev_hash));
+        }
+    }
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(storage);
+    let result = blockchain
+        .scan_blocks(&json!(["start", [format!("addr({address})")]]))
+        .await
+        .unwrap();
+    assert_eq!(result["relevant_blocks"], json!(paying));
+    assert_eq!(result["from_height"], 0);
+    assert_eq!(result["to_height"], 5);
+    assert_eq!(result["completed"], true);
+
+    // A sub-range only reports the paying blocks inside it
+    let result = blockchain
+        .scan_blocks(&json!(["start", [{ "desc": format!("addr({address})") }], 3, 5]))
+        .await
+        .unwrap();
+    assert_eq!(result["relevant_blocks"], json!([paying[1]]));
+
+    // Nothing is running once a scan returns
+    assert!(blockchain
+        .scan_blocks(&json!(["status"]))
+        .await
+        .unwrap()
+        .is_null());
+    assert_eq!(
+        blockchain.scan_blocks(&json!(["abort"])).await.unwrap(),
+        false
+    );
+    assert!(blockchain
+        .scan_blocks(&json!(["start", ["raw(51)"], 0, 6]))
+        .await
+        .is_err());
+    assert!(blockchain
+        .scan_blocks(&json!(["start", ["raw(51)"], 0, 5, "extended"]))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_scanstatus_and_abortscan_stop_a_running_scan() {
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let mut prev_hash = storage.blocks().get_block_hash(&genesis);
+    for height in 1..=30 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(prev_hash)
+            .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+            .build();
+        connect_test_block(&storage, &block, height);
+        prev_hash = storage.blocks().get_block_hash(&block);
+    }
+
+    let blockchain = Arc::new(blockchain::BlockchainRpc::with_dependencies(storage));
+    assert_eq!(blockchain.scan_status().await.unwrap(), json!([]));
+
+    let scan = tokio::spawn({
+        let blockchain = Arc::clone(&blockchain);
+        async move {
+            blockchain
+                .scan_blocks(&json!(["start", ["raw(51)"]]))
+                .await
+                .unwrap()
+        }
+    });
+
+    // Wait for the scan to register, then abort it by id
+    let status = loop {
+        let status = blockchain.scan_status().await.unwrap();
+        if !status.as_array().unwrap().is_empty() {
+            break status;
+        }
+        tokio::task::yield_now().await;
+    };
+    assert_eq!(status[0]["type"], "scanblocks");
+    assert!(status[0]["progress"].as_u64().unwrap() < 100);
+    let scan_id = status[0]["scan_id"].as_u64().unwrap();
+    assert_eq!(
+        blockchain.abort_scan(&json!([scan_id + 1])).await.unwrap(),
+        false
+    );
+    assert_eq!(
+        blockchain.abort_scan(&json!([scan_id])).await.unwrap(),
+        true
+    );
+
+    let result = scan.await.unwrap();
+    assert_eq!(result["completed"], false);
+    assert!(result["to_height"].as_u64().unwrap() < 30);
+    assert_eq!(blockchain.scan_status().await.unwrap(), json!([]));
+    assert!(blockchain.abort_scan(&json!([])).await.is_err());
+}
+
+#[tokio::test]
+async fn test_getblockheaders_returns_linked_sequence() {
+    use bllvm_node::storage::Storage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let genesis_hash = hex::encode(storage.blocks().get_block_hash(&genesis));
+    let mut prev_hash = storage.blocks().get_block_hash(&genesis);
+    for height in 1..=5 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(prev_hash)
+            .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+            .build();
+        connect_test_block(&storage, &block, height);
+        prev_hash = storage.blocks().get_block_hash(&block);
+    }
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(storage);
+
+    // Stops at the tip when more headers are requested than exist
+    let headers = blockchain
+        .get_block_headers(&genesis_hash, 10, true)
+        .await
+        .unwrap();
+    let headers = headers.as_array().unwrap();
+    assert_eq!(headers.len(), 6);
+    assert_eq!(headers[0]["hash"], genesis_hash);
+    for (height, pair) in headers.windows(2).enumerate() {
+        assert_eq!(pair[1]["previousblockhash"], pair[0]["hash"]);
+        assert_eq!(pair[1]["height"], height as u64 + 1);
+    }
+    assert_eq!(headers[5]["hash"], hex::encode(prev_hash));
+
+    // Raw headers starting mid-chain
+    let start = headers[2]["hash"].as_str().unwrap();
+    let raw = blockchain.get_block_headers(start, 2, false).await.unwrap();
+    assert_eq!(raw.as_array().unwrap().len(), 2);
+    assert_eq!(
+        raw[0],
+        blockchain.get_block_header(start, false).await.unwrap()
+    );
+    assert_eq!(raw[1].as_str().unwrap().len(), 160);
+
+    assert!(blockchain
+        .get_block_headers(&hex::encode([0xab; 32]), 1, true)
+        .await
+        .is_err());
+    assert_eq!(
+        blockchain
+            .get_block_headers(&genesis_hash, 0, true)
+            .await
+            .unwrap(),
+        json!([])
+    );
+}
+
+/// Mine a block that passes full validation: BIP34 height push, rising
+/// timestamps and regtest proof of work
+fn mine_regtest_block(prev_hash: bllvm_protocol::Hash, height: u64) -> bllvm_protocol::Block {
+    use bllvm_protocol::mining::calculate_merkle_root;
+    use bllvm_protocol::pow::check_proof_of_work;
+    use bllvm_protocol::{OutPoint, Transaction, TransactionInput, TransactionOutput};
+
+    let height_push = if height == 0 {
+        0x00
+    } else {
+        0x50 + height as u8
+    };
+    let coinbase = Transaction {
+        version: 1,
+        inputs: bllvm_protocol::tx_inputs![TransactionInput {
+            prevout: OutPoint {
+                hash: [0u8; 32],
+                index: 0xffffffff,
+            },
+            script_sig: vec![height_push, 0x00],
+            sequence: 0xffffffff,
+        }],
+        outputs: bllvm_protocol::tx_outputs![TransactionOutput {
+            value: 5_000_000_000,
+            script_pubkey: p2pkh_script(random_hash20()),
+        }],
+        lock_time: 0,
+    };
+    let mut block = TestBlockBuilder::new()
+        .set_prev_hash(prev_hash)
+        .set_timestamp(1_700_000_000 + height as u32 * 600)
+        .with_bits(0x207fffff)
+        .add_transaction(coinbase)
+        .build();
+    block.header.merkle_root = calculate_merkle_root(&block.transactions).unwrap();
+    while !check_proof_of_work(&block.header).unwrap() {
+        block.header.nonce += 1;
+    }
+    block
+}
+
+#[tokio::test]
+async fn test_reindexchainstate_rebuilds_utxo_set() {
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::{OutPoint, UTXO};
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let mut blocks = vec![mine_regtest_block([0u8; 32], 0)];
+    storage.chain().initialize(&blocks[0].header).unwrap();
+    connect_test_block(&storage, &blocks[0], 0);
+    for height in 1..=5 {
+        let prev_hash = storage
+            .blocks()
+            .get_block_hash(&blocks[height as usize - 1]);
+        let block = mine_regtest_block(prev_hash, height);
+        connect_test_block(&storage, &block, height);
+        blocks.push(block);
+    }
+    let before = storage.utxos().get_all_utxos().unwrap();
+    let tip_hash = storage.chain().get_tip_hash().unwrap();
+    assert_eq!(before.len(), 6);
+
+    // Corrupt the UTXO set: drop every coin and add one that never existed
+    storage.utxos().clear().unwrap();
+    let bogus = OutPoint {
+        hash: [0xee; 32],
+        index: 0,
+    };
+    storage
+        .utxos()
+        .add_utxo(
+            &bogus,
+            &UTXO {
+                value: 1,
+                script_pubkey: vec![0x51],
+                height: 0,
+            },
+        )
+        .unwrap();
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let result = blockchain.reindex_chain_state().await.unwrap();
+    assert_eq!(result["height"], 5);
+    assert_eq!(result["blocks"], 6);
+
+    let after = storage.utxos().get_all_utxos().unwrap();
+    assert_eq!(after.len(), before.len());
+    for (outpoint, coin) in &before {
+        let rebuilt = after.get(outpoint).unwrap();
+        assert_eq!(rebuilt.value, coin.value);
+        assert_eq!(rebuilt.script_pubkey, coin.script_pubkey);
+        assert_eq!(rebuilt.height, coin.height);
+    }
+    assert!(!after.contains_key(&bogus));
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), tip_hash);
+    assert_eq!(storage.chain().get_height().unwrap(), Some(5));
+    let coinbase_txid = calculate_tx_id(&blocks[3].transactions[0]);
+    assert!(storage
+        .transactions()
+        .get_transaction(&coinbase_txid)
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        blockchain.scan_status().await.unwrap(),
+        serde_json::json!([])
+    );
+
+    // With a block body pruned, nothing is cleared
+    let pruned_hash = storage.blocks().get_block_hash(&blocks[2]);
+    storage.blocks().remove_block_body(&pruned_hash).unwrap();
+    assert!(blockchain.reindex_chain_state().await.is_err());
+    assert_eq!(storage.utxos().get_all_utxos().unwrap().len(), before.len());
+    assert_eq!(storage.chain().get_height().unwrap(), Some(5));
+}
+
+#[tokio::test]
+async fn test_rebuildutxoindex_repairs_address_index_range() {
+    use bllvm_node::storage::addressindex::AddressHistoryEntry;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    storage.addresses().set_enabled(true);
+    let mut blocks = vec![mine_regtest_block([0u8; 32], 0)];
+    storage.chain().initialize(&blocks[0].header).unwrap();
+    connect_test_block(&storage, &blocks[0], 0);
+    for height in 1..=5 {
+        let prev_hash = storage
+            .blocks()
+            .get_block_hash(&blocks[height as usize - 1]);
+        let block = mine_regtest_block(prev_hash, height);
+        connect_test_block(&storage, &block, height);
+        blocks.push(block);
+    }
+    let scripts: Vec<Vec<u8>> = blocks
+        .iter()
+        .map(|block| block.transactions[0].outputs[0].script_pubkey.clone())
+        .collect();
+    let before: Vec<_> = scripts
+        .iter()
+        .map(|script| storage.addresses().history(script).unwrap())
+        .collect();
+    for (height, history) in before.iter().enumerate() {
+        assert_eq!(
+            history,
+            &vec![AddressHistoryEntry {
+                height: height as u64,
+                txid: calculate_tx_id(&blocks[height].transactions[0]),
+            }]
+        );
+    }
+    let tip = storage.addresses().tip().unwrap().unwrap();
+    assert_eq!(tip.height, 5);
+
+    // Corrupt heights 2-3: lose block 2's entry and claim a height-3
+    // transaction that never existed for the scripts of blocks 3 and 4
+    let bogus = AddressHistoryEntry {
+        height: 3,
+        txid: [0xee; 32],
+    };
+    storage.addresses().set_history(&scripts[2], &[]).unwrap();
+    storage
+        .addresses()
+        .set_history(&scripts[3], &[before[3][0], bogus])
+        .unwrap();
+    storage
+        .addresses()
+        .set_history(&scripts[4], &[bogus, before[4][0]])
+        .unwrap();
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let result = blockchain.rebuild_utxo_index(2, 3).await.unwrap();
+    assert_eq!(result["rebuilt"], serde_json::json!(["addressindex"]));
+    for (script, history) in scripts.iter().zip(&before) {
+        assert_eq!(&storage.addresses().history(script).unwrap(), history);
+    }
+    assert_eq!(storage.addresses().tip().unwrap(), Some(tip));
+
+    // Ranges past the chain tip or leaving a gap after the index are refused
+    assert!(blockchain.rebuild_utxo_index(4, 6).await.is_err());
+    assert!(blockchain.rebuild_utxo_index(3, 2).await.is_err());
+    storage.addresses().clear().unwrap();
+    assert!(blockchain.rebuild_utxo_index(2, 3).await.is_err());
+    blockchain.rebuild_utxo_index(0, 5).await.unwrap();
+    assert_eq!(storage.addresses().tip().unwrap(), Some(tip));
+    assert_eq!(storage.addresses().history(&scripts[4]).unwrap(), before[4]);
+
+    // With a block body in the range pruned, nothing is touched
+    storage.addresses().set_history(&scripts[2], &[]).unwrap();
+    let pruned_hash = storage.blocks().get_block_hash(&blocks[2]);
+    storage.blocks().remove_block_body(&pruned_hash).unwrap();
+    assert!(blockchain.rebuild_utxo_index(2, 3).await.is_err());
+    assert!(storage.addresses().history(&scripts[2]).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_invalidateblock_and_reconsiderblock_reorg() {
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::OutPoint;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let mut hashes = Vec::new();
+    let mut blocks = Vec::new();
+    for height in 0..=5 {
+        let prev_hash = hashes.last().copied().unwrap_or([0u8; 32]);
+        let block = mine_regtest_block(prev_hash, height);
+        if height == 0 {
+            storage.chain().initialize(&block.header).unwrap();
+        }
+        connect_test_block(&storage, &block, height);
+        hashes.push(storage.blocks().get_block_hash(&block));
+        blocks.push(block);
+    }
+    let before = storage.utxos().get_all_utxos().unwrap();
+
+    // A competing block at height 3, known but not connected
+    let fork = mine_regtest_block(hashes[2], 3);
+    let fork_hash = storage.blocks().get_block_hash(&fork);
+    storage.blocks().store_block(&fork).unwrap();
+    storage
+        .chain()
+        .update_best_chain(&fork_hash, &fork.header, 3)
+        .unwrap();
+    storage
+        .chain()
+        .add_chain_tip(&fork_hash, 3, 1, "valid-fork")
+        .unwrap();
+    let coinbase = |block: &bllvm_protocol::Block| OutPoint {
+        hash: calculate_tx_id(&block.transactions[0]),
+        index: 0,
+    };
+
+    // Invalidating block 3 rolls the chain back and onto the fork
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    blockchain
+        .invalidate_block(&serde_json::json!([hex::encode(hashes[3])]))
+        .await
+        .unwrap();
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(fork_hash));
+    assert_eq!(storage.chain().get_height().unwrap(), Some(3));
+    for hash in &hashes[3..] {
+        assert!(storage.chain().is_invalid(hash).unwrap());
+    }
+    let utxos = storage.utxos();
+    assert!(utxos.has_utxo(&coinbase(&fork)).unwrap());
+    for block in &blocks[3..] {
+        assert!(!utxos.has_utxo(&coinbase(block)).unwrap());
+    }
+    let tips = blockchain.get_chain_tips().await.unwrap();
+    assert!(tips
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|tip| { tip["hash"] == hex::encode(hashes[5]) && tip["status"] == "invalid" }));
+
+    // Reconsidering it rolls forward to the original tip, which has more work
+    blockchain
+        .reconsider_block(&serde_json::json!([hex::encode(hashes[3])]))
+        .await
+        .unwrap();
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(hashes[5]));
+    assert_eq!(storage.chain().get_height().unwrap(), Some(5));
+    assert!(storage.chain().get_invalid_blocks().unwrap().is_empty());
+    assert_eq!(
+        storage.blocks().get_hash_by_height(3).unwrap(),
+        Some(hashes[3])
+    );
+    let after = storage.utxos().get_all_utxos().unwrap();
+    assert_eq!(after.len(), before.len());
+    assert!(before.keys().all(|outpoint| after.contains_key(outpoint)));
+    assert!(storage
+        .chain()
+        .get_chain_tips()
+        .unwrap()
+        .iter()
+        .any(|(hash, _, _, status)| *hash == fork_hash && status == "valid-fork"));
+
+    // The genesis block cannot be invalidated
+    assert!(blockchain
+        .invalidate_block(&serde_json::json!([hex::encode(hashes[0])]))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_preciousblock_flips_equal_work_tip() {
+    use bllvm_node::storage::chainstate::BestChainUpdate;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::block::calculate_tx_id;
+    use bllvm_protocol::OutPoint;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let mut hashes = Vec::new();
+    for height in 0..=2 {
+        let prev_hash = hashes.last().copied().unwrap_or([0u8; 32]);
+        let block = mine_regtest_block(prev_hash, height);
+        if height == 0 {
+            storage.chain().initialize(&block.header).unwrap();
+        }
+        connect_test_block(&storage, &block, height);
+        hashes.push(storage.blocks().get_block_hash(&block));
+    }
+
+    // Two blocks at height 3 with equal work: the first one connected stays
+    let first = mine_regtest_block(hashes[2], 3);
+    let first_hash = storage.blocks().get_block_hash(&first);
+    connect_test_block(&storage, &first, 3);
+    let second = mine_regtest_block(hashes[2], 3);
+    let second_hash = storage.blocks().get_block_hash(&second);
+    storage.blocks().store_block(&second).unwrap();
+    assert_eq!(
+        storage
+            .chain()
+            .update_best_chain(&second_hash, &second.header, 3)
+            .unwrap(),
+        BestChainUpdate::Unchanged
+    );
+    storage
+        .chain()
+        .add_chain_tip(&second_hash, 3, 1, "valid-fork")
+        .unwrap();
+    let has_coinbase = |block: &bllvm_protocol::Block| {
+        storage
+            .utxos()
+            .has_utxo(&OutPoint {
+                hash: calculate_tx_id(&block.transactions[0]),
+                index: 0,
+            })
+            .unwrap()
+    };
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    blockchain
+        .precious_block(&serde_json::json!([hex::encode(second_hash)]))
+        .await
+        .unwrap();
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(second_hash));
+    assert_eq!(storage.chain().get_height().unwrap(), Some(3));
+    assert!(has_coinbase(&second) && !has_coinbase(&first));
+    assert_eq!(
+        storage.chain().get_chainwork(&first_hash).unwrap(),
+        storage.chain().get_chainwork(&second_hash).unwrap()
+    );
+
+    // The latest call wins
+    blockchain
+        .precious_block(&serde_json::json!([hex::encode(first_hash)]))
+        .await
+        .unwrap();
+    assert_eq!(storage.chain().get_tip_hash().unwrap(), Some(first_hash));
+    assert!(has_coinbase(&first) && !has_coinbase(&second));
+}
+
+#[tokio::test]
+async fn test_pruned_blocks_report_pruned_not_missing() {
+    use bllvm_node::network::chain_access::NodeChainAccess;
+    use bllvm_node::node::mempool::MempoolManager;
+    use bllvm_node::rpc::errors::{RpcError, RpcErrorCode};
+    use bllvm_node::storage::blockstore::BlockAvailability;
+    use bllvm_node::storage::Storage;
+    use bllvm_protocol::network::ChainStateAccess;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let mut hashes = vec![storage.blocks().get_block_hash(&genesis)];
+    for height in 1..=5 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(hashes[height as usize - 1])
+            .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+            .build();
+        connect_test_block(&storage, &block, height);
+        hashes.push(storage.blocks().get_block_hash(&block));
+    }
+
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let info = blockchain.get_blockchain_info().await.unwrap();
+    assert_eq!(info["pruneheight"], 0);
+
+    // Prune bodies below height 3, as the pruning manager does
+    for height in 0..3 {
+        storage.blocks().remove_block_by_height(height).unwrap();
+    }
+    let info = blockchain.get_blockchain_info().await.unwrap();
+    assert_eq!(info["pruneheight"], 3);
+
+    let rpc_code = |err: anyhow::Error| err.downcast::<RpcError>().unwrap().code;
+    assert!(matches!(
+        storage.blocks().get_block_availability(&hashes[1]).unwrap(),
+        BlockAvailability::Pruned
+    ));
+    assert!(matches!(
+        storage
+            .blocks()
+            .get_block_availability(&[0xab; 32])
+            .unwrap(),
+        BlockAvailability::NotFound
+    ));
+
+    let err = blockchain
+        .get_block(&hex::encode(hashes[1]), 1)
+        .await
+        .unwrap_err();
+    assert_eq!(rpc_code(err), RpcErrorCode::BlockPruned);
+    let err = blockchain
+        .get_block(&hex::encode([0xab; 32]), 1)
+        .await
+        .unwrap_err();
+    assert_eq!(rpc_code(err), RpcErrorCode::BlockNotFound);
+    assert!(blockchain
+        .get_block(&hex::encode(hashes[3]), 1)
+        .await
+        .is_ok());
+
+    let err = blockchain.get_block_stats(&json!([2])).await.unwrap_err();
+    assert_eq!(rpc_code(err), RpcErrorCode::BlockPruned);
+    assert_eq!(RpcErrorCode::BlockPruned.code(), -1);
+    assert!(blockchain.get_block_stats(&json!([4])).await.is_ok());
+
+    // Peers asking for a pruned block get nothing back
+    let chain_access = NodeChainAccess::new(
+        storage.blocks(),
+        storage.transactions(),
+        Arc::new(MempoolManager::new()),
+    );
+    assert!(chain_access.get_object(&hashes[2]).is_none());
+    assert!(!chain_access.has_object(&hashes[2]));
+    assert!(chain_access.get_object(&hashes[3]).is_some());
+}
+
+#[tokio::test]
+async fn test_low_work_chain_stays_in_initial_block_download() {
+    use bllvm_node::storage::Storage;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let now = bllvm_node::utils::current_timestamp() as u32;
+    let genesis = TestBlockBuilder::new()
+        .set_timestamp(now)
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let mut prev_hash = storage.blocks().get_block_hash(&genesis);
+
+    // Difficulty-1 blocks each add 0x100010001 work; require four of them
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage))
+        .with_minimum_chain_work(4 * 0x100010001);
+    let info = blockchain.get_blockchain_info().await.unwrap();
+    assert_eq!(info["initialblockdownload"], true);
+
+    for height in 1..=3 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(prev_hash)
+            .set_timestamp(now)
+            .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+            .build();
+        connect_test_block(&storage, &block, height);
+        prev_hash = storage.blocks().get_block_hash(&block);
+
+        // Caught up with a recent tip, but not enough work until the fourth block
+        let info = blockchain.get_blockchain_info().await.unwrap();
+        assert_eq!(info["blocks"], height);
+        assert_eq!(info["initialblockdownload"], height < 3, "height {height}");
+    }
+    let info = blockchain.get_blockchain_info().await.unwrap();
+    assert_eq!(info["chainwork"], format!("{:064x}", 4 * 0x100010001u128));
+
+    // Zero disables the check
+    let unchecked = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage))
+        .with_minimum_chain_work(0);
+    let info = unchecked.get_blockchain_info().await.unwrap();
+    assert_eq!(info["initialblockdownload"], false);
+}
+
+#[tokio::test]
+async fn test_getchainstates_reports_background_validation() {
+    use bllvm_node::node::chainstates::BackgroundChainstate;
+    use bllvm_node::storage::Storage;
+    use std::sync::Arc;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+    let genesis = TestBlockBuilder::new()
+        .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+        .build();
+    storage.chain().initialize(&genesis.header).unwrap();
+    connect_test_block(&storage, &genesis, 0);
+    let mut hashes = vec![storage.blocks().get_block_hash(&genesis)];
+    for height in 1..=5 {
+        let block = TestBlockBuilder::new()
+            .set_prev_hash(hashes[height as usize - 1])
+            .add_transaction(coinbase_paying(p2pkh_script(random_hash20())))
+            .build();
+        connect_test_block(&storage, &block, height);
+        hashes.push(storage.blocks().get_block_hash(&block));
+    }
+
+    // No snapshot: a single validated chainstate
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let result = blockchain.get_chain_states().await.unwrap();
+    let chainstates = result["chainstates"].as_array().unwrap();
+    assert_eq!(result["headers"], 5);
+    assert_eq!(chainstates.len(), 1);
+    assert_eq!(chainstates[0]["blocks"], 5);
+    assert_eq!(chainstates[0]["validated"], true);
+    assert!(chainstates[0].get("snapshot_blockhash").is_none());
+
+    // Snapshot based at height 3, background validation still at height 1
+    let background = Arc::new(BackgroundChainstate::new(hashes[3], 3));
+    background.record_validated(1, hashes[1]);
+    let blockchain = blockchain::BlockchainRpc::with_dependencies(Arc::clone(&storage))
+        .with_background_chainstate(Arc::clone(&background));
+    let result = blockchain.get_chain_states().await.unwrap();
+    let chainstates = result["chainstates"].as_array().unwrap();
+    assert_eq!(chainstates.len(), 2);
+    assert_eq!(chainstates[0]["blocks"], 1);
+    assert_eq!(chainstates[0]["bestblockhash"], hex::encode(hashes[1]));
+    assert_eq!(chainstates[0]["validated"], true);
+    assert_eq!(chainstates[1]["blocks"], 5);
+    assert_eq!(chainstates[1]["bestblockhash"], hex::encode(hashes[5]));
+    assert_eq!(chainstates[1]["validated"], false);
+    assert_eq!(chainstates[1]["snapshot_blockhash"], hex::encode(hashes[3]));
+
+    // Background validation reaching the snapshot base validates it
+    background.record_validated(3, hashes[3]);
+    let result = blockchain.get_chain_states().await.unwrap();
+    let chainstates = result["chainstates"].as_array().unwrap();
+    assert_eq!(chainstates.len(), 1);
+    assert_eq!(chainstates[0]["validated"], true);
+    assert_eq!(chainstates[0]["snapshot_blockhash"], hex::encode(hashes[3]));
+}
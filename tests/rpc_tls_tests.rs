@@ -0,0 +1,113 @@
+//! RPC over TLS tests
+
+#![cfg(feature = "rpc-tls")]
+
+use bllvm_node::config::RpcTlsConfig;
+use bllvm_node::rpc::auth::RpcAuthManager;
+use bllvm_node::rpc::server::RpcServer;
+use bllvm_node::rpc::tls::{certificate_fingerprint, RpcTlsAcceptor};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+/// Start a TLS RPC server requiring auth, accepting `client_fingerprint`
+async fn start_tls_server(
+    dir: &TempDir,
+    client_fingerprint: String,
+) -> (SocketAddr, RootCertStore) {
+    let server_cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let cert_file = dir.path().join("rpc.cert");
+    let key_file = dir.path().join("rpc.key");
+    let cert_pem = server_cert.serialize_pem().unwrap();
+    std::fs::write(&cert_file, &cert_pem).unwrap();
+    std::fs::write(&key_file, server_cert.serialize_private_key_pem()).unwrap();
+
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut cert_pem.as_bytes()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+
+    let auth = Arc::new(RpcAuthManager::new(true));
+    auth.add_certificate(client_fingerprint).await.unwrap();
+    let tls = RpcTlsAcceptor::from_config(&RpcTlsConfig {
+        cert_file,
+        key_file,
+        require_client_cert: false,
+    })
+    .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = RpcServer::with_auth(addr, auth).with_tls(Arc::new(tls));
+    tokio::spawn(async move { server.serve(listener).await });
+    (addr, roots)
+}
+
+/// Send one JSON-RPC request over TLS and return the raw HTTP response
+async fn tls_call(
+    addr: SocketAddr,
+    config: ClientConfig,
+    extra_headers: &str,
+    body: &str,
+) -> String {
+    let connector = TlsConnector::from(Arc::new(config));
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await
+        .unwrap();
+    let request = format!(
+        "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        extra_headers,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+fn client_config_builder() -> rustls::ConfigBuilder<ClientConfig, rustls::WantsVerifier> {
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_rpc_over_tls_with_client_certificate() {
+    let dir = TempDir::new().unwrap();
+    let client_cert = rcgen::generate_simple_self_signed(vec!["rpc-client".into()]).unwrap();
+    let client_der = client_cert.serialize_der().unwrap();
+    let client_key = client_cert.serialize_private_key_der();
+    let (addr, roots) = start_tls_server(&dir, certificate_fingerprint(&client_der)).await;
+    let body = r#"{"jsonrpc":"2.0","method":"uptime","params":[],"id":1}"#;
+
+    // Authenticated by the certificate presented in the handshake
+    let config = client_config_builder()
+        .with_root_certificates(roots.clone())
+        .with_client_auth_cert(
+            vec![CertificateDer::from(client_der.clone())],
+            PrivateKeyDer::Pkcs8(client_key.into()),
+        )
+        .unwrap();
+    let response = tls_call(addr, config, "", body).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("\"result\""), "{}", response);
+
+    // Without a certificate, claiming its fingerprint in a header is not enough
+    let config = client_config_builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let spoofed = format!(
+        "X-Client-Cert-Fingerprint: {}\r\n",
+        certificate_fingerprint(&client_der)
+    );
+    let response = tls_call(addr, config, &spoofed, body).await;
+    assert!(response.starts_with("HTTP/1.1 401"), "{}", response);
+}
@@ -5,7 +5,7 @@ use bllvm_protocol::*;
 use tempfile::TempDir;
 mod common;
 use bllvm_node::storage::blockstore::BlockStore;
-use bllvm_node::storage::chainstate::ChainState;
+use bllvm_node::storage::chainstate::{BestChainUpdate, ChainState};
 use bllvm_node::storage::txindex::TxIndex;
 use bllvm_node::storage::utxostore::UtxoStore;
 use common::*;
@@ -112,6 +112,22 @@ fn test_chain_state() {
     assert_ne!(tip_hash, [0u8; 32]);
 }
 
+#[test]
+fn test_chain_state_best_header_height() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    let chainstate = storage.chain();
+    assert_eq!(chainstate.get_best_header_height().unwrap(), None);
+
+    chainstate.update_best_header_height(100).unwrap();
+    // Lower heights (e.g. headers from a shorter fork) don't move it back
+    chainstate.update_best_header_height(50).unwrap();
+    assert_eq!(chainstate.get_best_header_height().unwrap(), Some(100));
+
+    chainstate.update_best_header_height(150).unwrap();
+    assert_eq!(chainstate.get_best_header_height().unwrap(), Some(150));
+}
+
 #[test]
 fn test_transaction_index() {
     let temp_dir = TempDir::new().unwrap();
@@ -192,6 +208,30 @@ fn test_block_store_retrieval_by_height() {
     }
 }
 
+#[test]
+fn test_block_store_headers_by_height_range() {
+    let temp_db = TempDb::new().unwrap();
+    let blockstore = &temp_db.block_store;
+
+    for i in 0..5 {
+        let block = TestBlockBuilder::new()
+            .set_timestamp((1234567890 + i as u64) as u32)
+            .add_coinbase_transaction(p2pkh_script(random_hash20()))
+            .build();
+        blockstore.store_block(&block).unwrap();
+        let block_hash = blockstore.get_block_hash(&block);
+        blockstore.store_height(i, &block_hash).unwrap();
+    }
+
+    // Window is inclusive and ordered by height; missing heights are skipped
+    let headers = blockstore.get_headers_by_height_range(1, 7).unwrap();
+    let timestamps: Vec<u64> = headers.iter().map(|h| h.timestamp).collect();
+    assert_eq!(
+        timestamps,
+        vec![1234567891, 1234567892, 1234567893, 1234567894]
+    );
+}
+
 #[test]
 fn test_block_store_header_only() {
     let temp_db = TempDb::new().unwrap();
@@ -537,6 +577,97 @@ fn test_chain_state_reorg_handling() {
     let _ = current_height;
 }
 
+/// Header extending `prev`; `nonce` distinguishes sibling blocks
+fn child_header(blockstore: &BlockStore, prev: Hash, bits: u64, nonce: u64) -> (Hash, BlockHeader) {
+    let header = BlockHeader {
+        version: 1,
+        prev_block_hash: prev,
+        merkle_root: [0u8; 32],
+        timestamp: 1234567890,
+        bits,
+        nonce,
+    };
+    let block = Block {
+        header: header.clone(),
+        transactions: vec![].into_boxed_slice(),
+    };
+    (blockstore.get_block_hash(&block), header)
+}
+
+#[test]
+fn test_best_chain_equal_work_keeps_first_seen() {
+    let temp_db = TempDb::new().unwrap();
+    let chainstate = &temp_db.chain_state;
+    chainstate.initialize(&valid_block_header()).unwrap();
+    let genesis = chainstate.get_tip_hash().unwrap().unwrap();
+
+    let (a1, a1_header) = child_header(&temp_db.block_store, genesis, 0x0300ffff, 1);
+    let (b1, b1_header) = child_header(&temp_db.block_store, genesis, 0x0300ffff, 2);
+
+    assert_eq!(
+        chainstate.update_best_chain(&a1, &a1_header, 1).unwrap(),
+        BestChainUpdate::Advanced {
+            previous_tip: genesis
+        }
+    );
+    // Same work as the tip: the first-seen block stays
+    assert_eq!(
+        chainstate.update_best_chain(&b1, &b1_header, 1).unwrap(),
+        BestChainUpdate::Unchanged
+    );
+    assert_eq!(chainstate.get_tip_hash().unwrap(), Some(a1));
+
+    // Extending the other branch gives it more work
+    let (b2, b2_header) = child_header(&temp_db.block_store, b1, 0x0300ffff, 3);
+    assert_eq!(
+        chainstate.update_best_chain(&b2, &b2_header, 2).unwrap(),
+        BestChainUpdate::Advanced { previous_tip: a1 }
+    );
+    assert_eq!(chainstate.get_tip_hash().unwrap(), Some(b2));
+    assert_eq!(chainstate.get_height().unwrap(), Some(2));
+    assert_eq!(chainstate.get_previous_tip().unwrap(), Some(a1));
+}
+
+#[test]
+fn test_best_chain_prefers_most_work_over_length() {
+    let temp_db = TempDb::new().unwrap();
+    let chainstate = &temp_db.chain_state;
+    chainstate.initialize(&valid_block_header()).unwrap();
+    let genesis = chainstate.get_tip_hash().unwrap().unwrap();
+
+    // Two easy blocks (target 0xffff00) ...
+    let (easy1, easy1_header) = child_header(&temp_db.block_store, genesis, 0x0400ffff, 1);
+    let (easy2, easy2_header) = child_header(&temp_db.block_store, easy1, 0x0400ffff, 2);
+    chainstate
+        .update_best_chain(&easy1, &easy1_header, 1)
+        .unwrap();
+    chainstate
+        .update_best_chain(&easy2, &easy2_header, 2)
+        .unwrap();
+    assert_eq!(chainstate.get_tip_hash().unwrap(), Some(easy2));
+
+    // ... lose to one block with a 256x harder target (0xffff)
+    let (hard, hard_header) = child_header(&temp_db.block_store, genesis, 0x0300ffff, 3);
+    assert_eq!(
+        chainstate
+            .update_best_chain(&hard, &hard_header, 1)
+            .unwrap(),
+        BestChainUpdate::Advanced {
+            previous_tip: easy2
+        }
+    );
+    assert_eq!(chainstate.get_height().unwrap(), Some(1));
+
+    // Invalid blocks are never selected
+    let (bad, bad_header) = child_header(&temp_db.block_store, hard, 0x0300ffff, 4);
+    chainstate.mark_invalid(&bad).unwrap();
+    assert_eq!(
+        chainstate.update_best_chain(&bad, &bad_header, 2).unwrap(),
+        BestChainUpdate::Unchanged
+    );
+    assert_eq!(chainstate.get_tip_hash().unwrap(), Some(hard));
+}
+
 // ===== TRANSACTION INDEX COMPREHENSIVE TESTS =====
 
 #[test]
@@ -842,3 +973,495 @@ async fn test_storage_integration_workflow() {
     assert!(retrieved_tx_block.is_some());
     assert_eq!(retrieved_tx_block.unwrap(), block_hash);
 }
+
+/// Database wrapper counting reads across all trees
+struct CountingDatabase {
+    inner: Box<dyn bllvm_node::storage::database::Database>,
+    reads: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+struct CountingTree {
+    inner: Box<dyn bllvm_node::storage::database::Tree>,
+    reads: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl bllvm_node::storage::database::Database for CountingDatabase {
+    fn open_tree(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<Box<dyn bllvm_node::storage::database::Tree>> {
+        Ok(Box::new(CountingTree {
+            inner: self.inner.open_tree(name)?,
+            reads: self.reads.clone(),
+        }))
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl bllvm_node::storage::database::Tree for CountingTree {
+    fn insert(&self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.inner.insert(key, value)
+    }
+
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.inner.get(key)
+    }
+
+    fn remove(&self, key: &[u8]) -> anyhow::Result<()> {
+        self.inner.remove(key)
+    }
+
+    fn contains_key(&self, key: &[u8]) -> anyhow::Result<bool> {
+        self.inner.contains_key(key)
+    }
+
+    fn clear(&self) -> anyhow::Result<()> {
+        self.inner.clear()
+    }
+
+    fn len(&self) -> anyhow::Result<usize> {
+        self.inner.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = anyhow::Result<(Vec<u8>, Vec<u8>)>> + '_> {
+        self.inner.iter()
+    }
+}
+
+#[test]
+fn test_repeated_header_lookups_hit_cache() {
+    use bllvm_node::storage::database::{create_database, default_backend};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let temp_dir = TempDir::new().unwrap();
+    let reads = std::sync::Arc::new(AtomicUsize::new(0));
+    let db = std::sync::Arc::new(CountingDatabase {
+        inner: create_database(temp_dir.path(), default_backend()).unwrap(),
+        reads: reads.clone(),
+    });
+    let blockstore = BlockStore::new(db).unwrap();
+
+    let block = TestBlockBuilder::new().build();
+    let hash = blockstore.get_block_hash(&block);
+    blockstore.store_block(&block).unwrap();
+    blockstore.store_height(1, &hash).unwrap();
+    // Start cold so the first lookup has to go to the database
+    blockstore.clear_caches();
+    reads.store(0, Ordering::SeqCst);
+
+    assert!(blockstore.get_header(&hash).unwrap().is_some());
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+    for _ in 0..10 {
+        let header = blockstore.get_header(&hash).unwrap().unwrap();
+        assert_eq!(header.nonce, block.header.nonce);
+        assert_eq!(blockstore.get_hash_by_height(1).unwrap(), Some(hash));
+    }
+    // Only the first height lookup reads the database
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+    let stats = blockstore.cache_stats();
+    assert_eq!(stats.headers.hits, 19);
+    assert_eq!(stats.headers.misses, 2);
+
+    // Cached block bodies are invalidated when pruned
+    assert!(blockstore.get_block(&hash).unwrap().is_some());
+    assert!(blockstore.get_block(&hash).unwrap().is_some());
+    assert_eq!(blockstore.cache_stats().blocks.hits, 1);
+    blockstore.remove_block_body(&hash).unwrap();
+    assert!(blockstore.get_block(&hash).unwrap().is_none());
+}
+
+/// Sorted, comparable view of the persisted UTXO set
+fn utxo_snapshot(utxostore: &UtxoStore) -> Vec<(Hash, u64, i64, Vec<u8>, u64)> {
+    let mut entries: Vec<_> = utxostore
+        .load_utxo_set()
+        .unwrap()
+        .into_iter()
+        .map(|(outpoint, utxo)| {
+            (
+                outpoint.hash,
+                outpoint.index,
+                utxo.value,
+                utxo.script_pubkey.to_vec(),
+                utxo.height,
+            )
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+#[test]
+fn test_connect_then_disconnect_block_restores_utxo_set() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+
+    // Two coins from earlier blocks; the block spends one of them
+    let funding = OutPoint {
+        hash: random_hash(),
+        index: 0,
+    };
+    let untouched = OutPoint {
+        hash: random_hash(),
+        index: 1,
+    };
+    for (outpoint, value) in [(&funding, 5000), (&untouched, 7000)] {
+        let utxo = UTXO {
+            value,
+            script_pubkey: p2pkh_script(random_hash20()),
+            height: 10,
+        };
+        storage.utxos().add_utxo(outpoint, &utxo).unwrap();
+    }
+    let before = utxo_snapshot(storage.utxos());
+
+    let coinbase = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: [0u8; 32],
+            index: 0xffffffff,
+        })
+        .add_output(5_000_000_000, p2pkh_script(random_hash20()))
+        .build();
+    let spend = TestTransactionBuilder::new()
+        .add_input(funding.clone())
+        .add_output(3000, p2pkh_script(random_hash20()))
+        .add_output(1500, p2pkh_script(random_hash20()))
+        .build();
+    // Spends the first output of `spend` within the same block
+    let spend_txid = calculate_tx_id(&spend);
+    let chained = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: spend_txid,
+            index: 0,
+        })
+        .add_output(2500, p2pkh_script(random_hash20()))
+        .build();
+    let block = TestBlockBuilder::new()
+        .add_transaction(coinbase)
+        .add_transaction(spend)
+        .add_transaction(chained)
+        .build();
+    let block_hash = storage.blocks().get_block_hash(&block);
+
+    let undo = storage.connect_block(&block, 11).unwrap();
+    assert_eq!(undo.spent.len(), 1);
+    assert_eq!(undo.spent[0].0, funding);
+    // Coinbase, spend output 1 and the chained output; spend output 0 was consumed
+    assert_eq!(undo.created.len(), 3);
+    assert!(!storage.utxos().has_utxo(&funding).unwrap());
+    assert!(!storage
+        .utxos()
+        .has_utxo(&OutPoint {
+            hash: spend_txid,
+            index: 0,
+        })
+        .unwrap());
+    assert_eq!(storage.utxos().utxo_count().unwrap(), 4);
+
+    let stored = storage.undo().get_undo(&block_hash).unwrap().unwrap();
+    assert_eq!(stored.created, undo.created);
+
+    storage.disconnect_block(&block_hash).unwrap();
+    assert_eq!(utxo_snapshot(storage.utxos()), before);
+    assert!(!storage.undo().has_undo(&block_hash).unwrap());
+
+    // A second disconnect has nothing to undo
+    assert!(storage.disconnect_block(&block_hash).is_err());
+}
+
+#[test]
+fn test_coinbase_outputs_marked_and_restored_on_disconnect() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    let coinbase_tx = |tag: u8| {
+        TestTransactionBuilder::new()
+            .add_input(OutPoint {
+                hash: [0u8; 32],
+                index: 0xffffffff,
+            })
+            .add_output(5_000_000_000 + tag as u64, p2pkh_script(random_hash20()))
+            .build()
+    };
+
+    let coinbase1 = coinbase_tx(1);
+    let block1 = TestBlockBuilder::new()
+        .add_transaction(coinbase1.clone())
+        .build();
+    storage.connect_block(&block1, 1).unwrap();
+    let reward = OutPoint {
+        hash: calculate_tx_id(&coinbase1),
+        index: 0,
+    };
+    assert!(storage.utxos().is_coinbase(&reward).unwrap());
+
+    // Block 2 spends the block 1 reward
+    let spend = TestTransactionBuilder::new()
+        .add_input(reward.clone())
+        .add_output(4_000_000_000, p2pkh_script(random_hash20()))
+        .build();
+    let spend_out = OutPoint {
+        hash: calculate_tx_id(&spend),
+        index: 0,
+    };
+    let block2 = TestBlockBuilder::new()
+        .set_prev_hash(storage.blocks().get_block_hash(&block1))
+        .add_transaction(coinbase_tx(2))
+        .add_transaction(spend)
+        .build();
+    let undo = storage.connect_block(&block2, 101).unwrap();
+    assert_eq!(undo.spent_coinbase, vec![reward.clone()]);
+    assert!(!storage.utxos().is_coinbase(&reward).unwrap());
+    assert!(!storage.utxos().is_coinbase(&spend_out).unwrap());
+    assert_eq!(storage.utxos().get_coinbase_outputs().unwrap().len(), 1);
+
+    storage
+        .disconnect_block(&storage.blocks().get_block_hash(&block2))
+        .unwrap();
+    assert!(storage.utxos().is_coinbase(&reward).unwrap());
+    assert_eq!(storage.utxos().get_coinbase_outputs().unwrap().len(), 1);
+}
+
+/// Store a block spending `spends` on top of `prev` at `height`, and return it
+fn store_chain_block(storage: &Storage, prev: Hash, height: u64, spends: &[OutPoint]) -> Block {
+    let coinbase = TestTransactionBuilder::new()
+        .add_input(OutPoint {
+            hash: [0u8; 32],
+            index: 0xffffffff,
+        })
+        .add_output(5_000_000_000 + height, p2pkh_script(random_hash20()))
+        .build();
+    let mut builder = TestBlockBuilder::new()
+        .set_prev_hash(prev)
+        .set_timestamp(1_600_000_000 + height as u32)
+        .add_transaction(coinbase);
+    for outpoint in spends {
+        builder = builder.add_transaction(
+            TestTransactionBuilder::new()
+                .add_input(outpoint.clone())
+                .add_output(1000, p2pkh_script(random_hash20()))
+                .build(),
+        );
+    }
+    let block = builder.build();
+    let hash = storage.blocks().get_block_hash(&block);
+    storage.blocks().store_block(&block).unwrap();
+    storage.blocks().store_height(height, &hash).unwrap();
+    block
+}
+
+#[test]
+fn test_recovery_reconciles_utxo_set_with_chain_tip() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+
+    let genesis = store_chain_block(&storage, [0u8; 32], 0, &[]);
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.chain().initialize(&genesis.header).unwrap();
+    storage.connect_block(&genesis, 0).unwrap();
+    let genesis_coin = OutPoint {
+        hash: calculate_tx_id(&genesis.transactions[0]),
+        index: 0,
+    };
+
+    let block1 = store_chain_block(&storage, genesis_hash, 1, &[genesis_coin.clone()]);
+    let hash1 = storage.blocks().get_block_hash(&block1);
+    storage.connect_block(&block1, 1).unwrap();
+    storage
+        .chain()
+        .update_tip(&hash1, &block1.header, 1)
+        .unwrap();
+    let consistent = utxo_snapshot(storage.utxos());
+    assert_eq!(
+        storage.recover_utxo_consistency().unwrap(),
+        RecoveryOutcome {
+            disconnected: 0,
+            connected: 0,
+            height: 1,
+        }
+    );
+
+    // Crash after the UTXO batch but before the tip update: roll back
+    let block2 = store_chain_block(&storage, hash1, 2, &[]);
+    let hash2 = storage.blocks().get_block_hash(&block2);
+    storage.connect_block(&block2, 2).unwrap();
+    let outcome = storage.recover_utxo_consistency().unwrap();
+    assert_eq!(outcome.disconnected, 1);
+    assert_eq!(outcome.connected, 0);
+    assert_eq!(utxo_snapshot(storage.utxos()), consistent);
+    assert_eq!(storage.undo().get_utxo_tip().unwrap().unwrap().hash, hash1);
+
+    // Tip updated but UTXO changes lost: roll forward
+    storage
+        .chain()
+        .update_tip(&hash2, &block2.header, 2)
+        .unwrap();
+    let outcome = storage.recover_utxo_consistency().unwrap();
+    assert_eq!(outcome.disconnected, 0);
+    assert_eq!(outcome.connected, 1);
+    let utxo_tip = storage.undo().get_utxo_tip().unwrap().unwrap();
+    assert_eq!((utxo_tip.hash, utxo_tip.height), (hash2, 2));
+    assert!(!storage.utxos().has_utxo(&genesis_coin).unwrap());
+
+    // UTXO set on a stale fork: disconnect it and follow the active chain
+    let fork = store_chain_block(&storage, hash1, 2, &[]);
+    let fork_hash = storage.blocks().get_block_hash(&fork);
+    storage.disconnect_block(&hash2).unwrap();
+    storage.connect_block(&fork, 2).unwrap();
+    storage.blocks().store_height(2, &hash2).unwrap();
+    let outcome = storage.recover_utxo_consistency().unwrap();
+    assert_eq!((outcome.disconnected, outcome.connected), (1, 1));
+    assert_eq!(storage.undo().get_utxo_tip().unwrap().unwrap().hash, hash2);
+    assert!(!storage.undo().has_undo(&fork_hash).unwrap());
+}
+
+/// Coin statistics recomputed from a full UTXO set scan
+fn scanned_coin_stats(storage: &Storage) -> (u64, u64, [u8; 32]) {
+    use bllvm_node::storage::coinstatsindex::serialize_coin;
+    use bllvm_node::storage::muhash::MuHash3072;
+
+    let mut muhash = MuHash3072::new();
+    let mut total_amount = 0u64;
+    let utxos = storage.utxos().load_utxo_set().unwrap();
+    for (outpoint, utxo) in &utxos {
+        let coinbase = storage.utxos().is_coinbase(outpoint).unwrap();
+        muhash.insert(&serialize_coin(outpoint, utxo, coinbase));
+        total_amount += utxo.value as u64;
+    }
+    (utxos.len() as u64, total_amount, muhash.finalize())
+}
+
+#[test]
+fn test_coin_stats_index_across_disconnect_and_reconnect() {
+    use bllvm_protocol::block::calculate_tx_id;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Storage::new(temp_dir.path()).unwrap();
+    storage.coin_stats().set_enabled(true);
+    let index_stats = |storage: &Storage| {
+        let (stats, muhash) = storage.coin_stats().tip().unwrap().unwrap();
+        (stats, (stats.txouts, stats.total_amount, muhash.finalize()))
+    };
+
+    let genesis = store_chain_block(&storage, [0u8; 32], 0, &[]);
+    let genesis_hash = storage.blocks().get_block_hash(&genesis);
+    storage.connect_block(&genesis, 0).unwrap();
+    let (stats, totals) = index_stats(&storage);
+    assert_eq!((stats.block_hash, stats.height), (genesis_hash, 0));
+    assert_eq!(totals, scanned_coin_stats(&storage));
+
+    let genesis_coin = OutPoint {
+        hash: calculate_tx_id(&genesis.transactions[0]),
+        index: 0,
+    };
+    let block1 = store_chain_block(&storage, genesis_hash, 1, &[genesis_coin]);
+    let hash1 = storage.blocks().get_block_hash(&block1);
+    storage.connect_block(&block1, 1).unwrap();
+    let after_block1 = index_stats(&storage);
+    assert_eq!(after_block1.1, scanned_coin_stats(&storage));
+
+    let block2 = store_chain_block(&storage, hash1, 2, &[]);
+    let hash2 = storage.blocks().get_block_hash(&block2);
+    storage.connect_block(&block2, 2).unwrap();
+    let after_block2 = index_stats(&storage);
+    assert_eq!(after_block2.0.height, 2);
+    assert_eq!(after_block2.1, scanned_coin_stats(&storage));
+    assert!(storage.coin_stats().stats_at(&hash2).unwrap().is_some());
+
+    // Disconnecting returns the index to exactly the block 1 state
+    storage.disconnect_block(&hash2).unwrap();
+    assert_eq!(index_stats(&storage), after_block1);
+    assert_eq!(after_block1.1, scanned_coin_stats(&storage));
+    assert!(storage.coin_stats().stats_at(&hash2).unwrap().is_none());
+
+    // Reconnecting returns it to the block 2 state
+    storage.connect_block(&block2, 2).unwrap();
+    assert_eq!(index_stats(&storage), after_block2);
+
+    // A block that does not extend the index tip leaves it untouched
+    let fork = store_chain_block(&storage, genesis_hash, 1, &[]);
+    storage.connect_block(&fork, 1).unwrap();
+    assert_eq!(index_stats(&storage), after_block2);
+}
+
+#[tokio::test]
+async fn test_block_filter_index_builds_historical_filters() {
+    use bllvm_node::node::blockfilterindex::{BlockFilterIndexBuilder, BUILD_BATCH_SIZE};
+    use bllvm_node::rpc::blockchain::BlockchainRpc;
+    use bllvm_protocol::block::calculate_tx_id;
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(Storage::new(temp_dir.path()).unwrap());
+
+    let genesis = store_chain_block(&storage, [0u8; 32], 0, &[]);
+    let mut prev = storage.blocks().get_block_hash(&genesis);
+    storage.chain().initialize(&genesis.header).unwrap();
+    storage.connect_block(&genesis, 0).unwrap();
+    let mut spends = vec![OutPoint {
+        hash: calculate_tx_id(&genesis.transactions[0]),
+        index: 0,
+    }];
+    let mut hashes = vec![prev];
+    for height in 1..=4 {
+        let block = store_chain_block(&storage, prev, height, &spends);
+        prev = storage.blocks().get_block_hash(&block);
+        storage.connect_block(&block, height).unwrap();
+        storage
+            .chain()
+            .update_tip(&prev, &block.header, height)
+            .unwrap();
+        spends = vec![OutPoint {
+            hash: calculate_tx_id(&block.transactions[0]),
+            index: 0,
+        }];
+        hashes.push(prev);
+    }
+    assert_eq!(storage.filter_index_height().unwrap(), Some(4));
+    let expected_header = storage
+        .filters()
+        .get_filter_header(&hashes[2])
+        .unwrap()
+        .unwrap()
+        .header_hash();
+
+    // Blocks connected before the index existed have no filters
+    storage.filters().clear().unwrap();
+    assert_eq!(storage.filter_index_height().unwrap(), None);
+
+    // A pruned block in the range is refused
+    let builder = BlockFilterIndexBuilder::new(Arc::clone(&storage));
+    storage.blocks().remove_block_body(&hashes[0]).unwrap();
+    assert!(builder.build_batch(BUILD_BATCH_SIZE).is_err());
+    assert_eq!(storage.filter_index_height().unwrap(), None);
+    storage.blocks().store_block(&genesis).unwrap();
+
+    // Builds in batches, resuming from the end of the header chain
+    assert!(!builder.build_batch(2).unwrap());
+    assert_eq!(storage.filter_index_height().unwrap(), Some(1));
+    let builder = BlockFilterIndexBuilder::new(Arc::clone(&storage));
+    assert!(builder.build_batch(BUILD_BATCH_SIZE).unwrap());
+    assert_eq!(storage.filter_index_height().unwrap(), Some(4));
+
+    let rpc = BlockchainRpc::with_dependencies(Arc::clone(&storage));
+    let filter = rpc
+        .get_block_filter(&serde_json::json!([hex::encode(hashes[2])]))
+        .await
+        .unwrap();
+    assert!(!filter["filter"].as_str().unwrap().is_empty());
+    assert_eq!(filter["header"], hex::encode(expected_header));
+
+    let info = rpc.get_index_info(&serde_json::json!([])).await.unwrap();
+    let index = &info["basic block filter index"];
+    assert_eq!(index["synced"], true);
+    assert_eq!(index["best_block_height"], 4);
+}